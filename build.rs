@@ -0,0 +1,11 @@
+fn main() {
+    // Stubs for the gRPC transform API; protoc is vendored so facility
+    // build machines need nothing installed
+    #[cfg(feature = "grpc")]
+    {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc");
+        std::env::set_var("PROTOC", protoc);
+        tonic_build::compile_protos("proto/kenya_fhir_bridge.proto")
+            .expect("failed to compile proto/kenya_fhir_bridge.proto");
+    }
+}
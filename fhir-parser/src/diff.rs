@@ -0,0 +1,154 @@
+//! Semantic diff of two bundles or resources.
+//!
+//! Bundle entries are paired by `Type/id` (fullUrl when there is no id), so
+//! entry order does not matter. Bundle `id`/`timestamp` and `meta.lastUpdated`
+//! change on every run and are ignored.
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::fhir::bundle::Bundle;
+
+/// One differing element inside a resource present on both sides.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementChange {
+    /// `Type/id` of the resource
+    pub resource: String,
+    /// JSON path inside the resource, e.g. `code.coding[0].code`
+    pub path: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Diff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<ElementChange>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+fn resource_key(resource: &Value, fallback: impl FnOnce() -> String) -> String {
+    match (
+        resource.get("resourceType").and_then(Value::as_str),
+        resource.get("id").and_then(Value::as_str),
+    ) {
+        (Some(rt), Some(id)) => format!("{}/{}", rt, id),
+        _ => fallback(),
+    }
+}
+
+fn resources_by_key(bundle: &Bundle) -> BTreeMap<String, &Value> {
+    let mut out = BTreeMap::new();
+    for (i, entry) in bundle
+        .entry
+        .as_deref()
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+    {
+        let Some(res) = &entry.resource else {
+            continue;
+        };
+        let key = resource_key(res, || {
+            entry
+                .full_url
+                .clone()
+                .unwrap_or_else(|| format!("entry[{}]", i))
+        });
+        out.insert(key, res);
+    }
+    out
+}
+
+fn join(path: &str, segment: &str) -> String {
+    if path.is_empty() {
+        segment.to_string()
+    } else {
+        format!("{}.{}", path, segment)
+    }
+}
+
+/// Leaf-level JSON diff. Arrays of different length are reported whole.
+fn diff_values(
+    resource: &str,
+    path: &str,
+    before: Option<&Value>,
+    after: Option<&Value>,
+    out: &mut Vec<ElementChange>,
+) {
+    if path == "meta.lastUpdated" {
+        return;
+    }
+    match (before, after) {
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            let mut keys: Vec<&String> = a.keys().chain(b.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for k in keys {
+                diff_values(resource, &join(path, k), a.get(k), b.get(k), out);
+            }
+        }
+        (Some(Value::Array(a)), Some(Value::Array(b))) if a.len() == b.len() => {
+            for (i, (x, y)) in a.iter().zip(b).enumerate() {
+                diff_values(resource, &format!("{}[{}]", path, i), Some(x), Some(y), out);
+            }
+        }
+        (a, b) if a != b => out.push(ElementChange {
+            resource: resource.to_string(),
+            path: path.to_string(),
+            before: a.cloned(),
+            after: b.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+/// Diff two bundles entry by entry.
+pub fn diff_bundles(before: &Bundle, after: &Bundle) -> Diff {
+    let old = resources_by_key(before);
+    let new = resources_by_key(after);
+    let mut diff = Diff::default();
+
+    if before.bundle_type != after.bundle_type {
+        diff.changed.push(ElementChange {
+            resource: "Bundle".to_string(),
+            path: "type".to_string(),
+            before: before.bundle_type.clone().map(Value::String),
+            after: after.bundle_type.clone().map(Value::String),
+        });
+    }
+    for key in old.keys().filter(|k| !new.contains_key(*k)) {
+        diff.removed.push(key.clone());
+    }
+    for (key, res) in &new {
+        match old.get(key) {
+            Some(prev) => diff_values(key, "", Some(prev), Some(res), &mut diff.changed),
+            None => diff.added.push(key.clone()),
+        }
+    }
+    diff
+}
+
+/// Diff two JSON documents: bundles entry by entry, anything else as a
+/// single resource.
+pub fn diff_json(before: &Value, after: &Value) -> Diff {
+    let is_bundle = |v: &Value| v.get("resourceType").and_then(Value::as_str) == Some("Bundle");
+    if is_bundle(before) && is_bundle(after) {
+        if let (Ok(a), Ok(b)) = (
+            serde_json::from_value::<Bundle>(before.clone()),
+            serde_json::from_value::<Bundle>(after.clone()),
+        ) {
+            return diff_bundles(&a, &b);
+        }
+    }
+    let key = resource_key(before, || "resource".to_string());
+    let mut diff = Diff::default();
+    diff_values(&key, "", Some(before), Some(after), &mut diff.changed);
+    diff
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::meta::Meta;
+use super::observation::{CodeableConcept, Reference};
+
+/// FHIR R4 Appointment — a booked visit, e.g. the follow-up a clinician
+/// schedules at the end of a consultation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Appointment {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// proposed | pending | booked | arrived | fulfilled | cancelled | noshow | ...
+    pub status: String,
+    #[serde(rename = "appointmentType", skip_serializing_if = "Option::is_none")]
+    pub appointment_type: Option<CodeableConcept>,
+    /// Conditions the appointment is for
+    #[serde(rename = "reasonReference", skip_serializing_if = "Option::is_none")]
+    pub reason_reference: Option<Vec<Reference>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Required once booked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    /// When the appointment was made
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    pub participant: Vec<AppointmentParticipant>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Patient, practitioner or location taking part.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppointmentParticipant {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor: Option<Reference>,
+    /// required | optional | information-only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<String>,
+    /// accepted | declined | tentative | needs-action
+    pub status: String,
+}
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
+
+use super::meta::Meta;
+use super::observation::{Coding, Reference};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Bundle {
@@ -8,6 +11,8 @@ pub struct Bundle {
     /// Unique identifier for this bundle instance
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
     /// When the bundle was assembled (RFC3339)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timestamp: Option<String>,
@@ -15,6 +20,11 @@ pub struct Bundle {
     pub bundle_type: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub entry: Option<Vec<BundleEntry>>,
+    /// Digital signature over the rest of the bundle
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<Signature>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,3 +43,23 @@ pub struct BundleRequest {
     pub method: String,
     pub url: String,
 }
+
+/// FHIR R4 Signature datatype
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Signature {
+    /// Purpose of the signature (ASTM E1762 signature type)
+    #[serde(rename = "type")]
+    pub signature_type: Vec<Coding>,
+    /// When the signature was created (RFC3339)
+    pub when: String,
+    pub who: Reference,
+    /// Format of the signed content, including canonicalization
+    #[serde(rename = "targetFormat", skip_serializing_if = "Option::is_none")]
+    pub target_format: Option<String>,
+    /// Format of the signature, e.g. `application/jose`
+    #[serde(rename = "sigFormat", skip_serializing_if = "Option::is_none")]
+    pub sig_format: Option<String>,
+    /// Base64 of the signature itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+}
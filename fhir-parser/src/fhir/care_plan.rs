@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::encounter::Period;
+use super::meta::Meta;
+use super::observation::{CodeableConcept, Reference};
+
+/// FHIR R4 CarePlan — a treatment plan, e.g. the TB or ART regimen a
+/// programme patient is on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CarePlan {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// draft | active | on-hold | revoked | completed | entered-in-error | unknown
+    pub status: String,
+    /// proposal | plan | order | option
+    pub intent: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<Vec<CodeableConcept>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub subject: Reference,
+    /// Encounter during which the plan was made
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encounter: Option<Reference>,
+    /// Time the plan covers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period: Option<Period>,
+    /// Conditions the plan treats
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub addresses: Option<Vec<Reference>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub activity: Option<Vec<CarePlanActivity>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Something the plan does: an existing request (`reference`) or one
+/// described inline (`detail`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CarePlanActivity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference: Option<Reference>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<CarePlanActivityDetail>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CarePlanActivityDetail {
+    /// Resource type the activity would be, e.g. `Appointment`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<CodeableConcept>,
+    /// not-started | scheduled | in-progress | on-hold | completed | cancelled | ...
+    pub status: String,
+    #[serde(rename = "scheduledPeriod", skip_serializing_if = "Option::is_none")]
+    pub scheduled_period: Option<Period>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
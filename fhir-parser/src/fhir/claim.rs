@@ -1,8 +1,16 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
+use super::meta::Meta;
 use super::observation::{CodeableConcept, Coding, Reference};
 use super::patient::Identifier;
 
+/// SHA identifier and code systems
+pub const SHA_PAYER_SYSTEM: &str = "http://sha.health.go.ke/identifier/payer";
+pub const SHA_MEMBER_SYSTEM: &str = "http://sha.health.go.ke/identifier/member";
+pub const SHA_COVERAGE_TYPE_SYSTEM: &str = "http://sha.health.go.ke/CodeSystem/coverage-type";
+pub const SHA_INTERVENTION_SYSTEM: &str = "http://sha.health.go.ke/CodeSystem/interventions";
+
 /// FHIR R4 Claim — represents a SHA/SHIF preauthorisation request.
 /// use = "preauthorization" per SHA workflow requirements.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +19,8 @@ pub struct Claim {
     pub resource_type: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
     /// Claim status — "active" for submitted claims
     pub status: String,
     /// Claim use — "preauthorization" for SHA pre-auth flow
@@ -40,6 +50,21 @@ pub struct Claim {
     /// Diagnosis reference
     #[serde(skip_serializing_if = "Option::is_none")]
     pub diagnosis: Option<Vec<ClaimDiagnosis>>,
+    /// Clinical justification — references to vitals and the Condition
+    #[serde(rename = "supportingInfo", skip_serializing_if = "Option::is_none")]
+    pub supporting_info: Option<Vec<ClaimSupportingInfo>>,
+    /// Sum of the item net amounts — SHA rejects claims without it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<Money>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// FHIR Money — an amount in an ISO 4217 currency (KES for SHA).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Money {
+    pub value: f64,
+    pub currency: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -58,6 +83,12 @@ pub struct ClaimItem {
     /// Date of service
     #[serde(rename = "servicedDate", skip_serializing_if = "Option::is_none")]
     pub serviced_date: Option<String>,
+    /// Tariff amount for one unit of the service
+    #[serde(rename = "unitPrice", skip_serializing_if = "Option::is_none")]
+    pub unit_price: Option<Money>,
+    /// unitPrice × quantity (always one unit here)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub net: Option<Money>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,14 +98,27 @@ pub struct ClaimDiagnosis {
     pub diagnosis_codeable_concept: CodeableConcept,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaimSupportingInfo {
+    pub sequence: u32,
+    /// claiminformationcategory, e.g. "info"
+    pub category: CodeableConcept,
+    #[serde(rename = "valueReference", skip_serializing_if = "Option::is_none")]
+    pub value_reference: Option<Reference>,
+}
+
 /// SHA payer Organization — a lightweight inline Organization for the insurer entry.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShaPayerOrganization {
     #[serde(rename = "resourceType")]
     pub resource_type: String,
     pub id: String,
-    pub identifier: Vec<crate::fhir::patient::Identifier>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    pub identifier: Vec<Identifier>,
     pub name: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 /// Convenience: canonical SHA payer organization resource
@@ -82,22 +126,22 @@ pub fn sha_payer_org() -> ShaPayerOrganization {
     ShaPayerOrganization {
         resource_type: "Organization".to_string(),
         id: "org-sha-payer".to_string(),
-        identifier: vec![crate::fhir::patient::Identifier {
-            system: Some("http://sha.health.go.ke/identifier/payer".to_string()),
+        meta: None,
+        identifier: vec![Identifier {
+            system: Some(SHA_PAYER_SYSTEM.to_string()),
             value: "SHA-KE-001".to_string(),
         }],
         name: "Social Health Authority Kenya".to_string(),
+        extra: Default::default(),
     }
 }
 
 /// Build a Coverage resource from a SHA member number.
-pub fn build_coverage(
-    patient_id: &str,
-    sha_member_number: &str,
-) -> super::coverage::Coverage {
+pub fn build_coverage(patient_id: &str, sha_member_number: &str) -> super::coverage::Coverage {
     super::coverage::Coverage {
         resource_type: "Coverage".to_string(),
         id: Some(format!("cov-{}", patient_id)),
+        meta: None,
         status: "active".to_string(),
         payor: vec![Reference {
             reference: Some("Organization/org-sha-payer".to_string()),
@@ -107,18 +151,19 @@ pub fn build_coverage(
             reference: Some(format!("Patient/{}", patient_id)),
             display: None,
         },
-        identifier: Some(vec![crate::fhir::patient::Identifier {
-            system: Some("http://sha.health.go.ke/identifier/member".to_string()),
+        identifier: Some(vec![Identifier {
+            system: Some(SHA_MEMBER_SYSTEM.to_string()),
             value: sha_member_number.to_string(),
         }]),
         coverage_type: Some(CodeableConcept {
             coding: Some(vec![Coding {
-                system: Some("http://sha.health.go.ke/CodeSystem/coverage-type".to_string()),
+                system: Some(SHA_COVERAGE_TYPE_SYSTEM.to_string()),
                 code: Some("CAT-SHA-001".to_string()),
                 display: Some("SHA Contributory Scheme".to_string()),
             }]),
             text: Some("SHA Contributory Scheme".to_string()),
         }),
+        extra: Default::default(),
     }
 }
 
@@ -151,6 +196,7 @@ pub fn build_claim(
     Claim {
         resource_type: "Claim".to_string(),
         id: Some(format!("claim-{}", patient_id)),
+        meta: None,
         status: "active".to_string(),
         use_field: "preauthorization".to_string(),
         claim_type: CodeableConcept {
@@ -194,18 +240,23 @@ pub fn build_claim(
             sequence: 1,
             product_or_service: CodeableConcept {
                 coding: Some(vec![Coding {
-                    system: Some("http://sha.health.go.ke/CodeSystem/interventions".to_string()),
+                    system: Some(SHA_INTERVENTION_SYSTEM.to_string()),
                     code: Some(sha_intervention_code.to_string()),
                     display: None,
                 }]),
                 text: Some(sha_intervention_code.to_string()),
             },
             serviced_date: Some(service_date.to_string()),
+            unit_price: None,
+            net: None,
         }]),
         encounter: Some(vec![Reference {
             reference: Some(format!("Encounter/{}", encounter_id)),
             display: None,
         }]),
         diagnosis,
+        supporting_info: None,
+        total: None,
+        extra: Default::default(),
     }
 }
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::meta::Meta;
+use super::observation::Reference;
+
+/// FHIR R4 ClinicalImpression — the clinician's assessment of the visit,
+/// here the free-text note beyond the complaint and diagnosis.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClinicalImpression {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// in-progress | completed | entered-in-error
+    pub status: String,
+    pub subject: Reference,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encounter: Option<Reference>,
+    #[serde(rename = "effectiveDateTime", skip_serializing_if = "Option::is_none")]
+    pub effective_date_time: Option<String>,
+    /// When the assessment was documented
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// The clinician who made the assessment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assessor: Option<Reference>,
+    /// Conditions the assessment is about
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub problem: Option<Vec<Reference>>,
+    /// The assessment in the clinician's words
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
+use super::extension::Extension;
+use super::meta::Meta;
 use super::observation::{CodeableConcept, Reference};
 
 /// FHIR R4 Condition — represents a diagnosis / clinical finding.
@@ -9,12 +12,23 @@ pub struct Condition {
     pub resource_type: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// Kenya-specific extensions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension: Option<Vec<Extension>>,
     /// Clinical status: active | recurrence | relapse | inactive | remission | resolved
     #[serde(rename = "clinicalStatus", skip_serializing_if = "Option::is_none")]
     pub clinical_status: Option<CodeableConcept>,
     /// Verification status: unconfirmed | provisional | differential | confirmed | refuted | entered-in-error
     #[serde(rename = "verificationStatus", skip_serializing_if = "Option::is_none")]
     pub verification_status: Option<CodeableConcept>,
+    /// problem-list-item | encounter-diagnosis
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<Vec<CodeableConcept>>,
+    /// Subjective severity: mild | moderate | severe (SNOMED CT)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<CodeableConcept>,
     /// The coded diagnosis (ICD-10, SNOMED, or free text)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub code: Option<CodeableConcept>,
@@ -24,12 +38,30 @@ pub struct Condition {
     /// Encounter during which the condition was recorded
     #[serde(skip_serializing_if = "Option::is_none")]
     pub encounter: Option<Reference>,
-    /// Date/time of onset or record
+    /// When the condition began
     #[serde(rename = "onsetDateTime", skip_serializing_if = "Option::is_none")]
     pub onset_date_time: Option<String>,
+    /// When the condition was first recorded
+    #[serde(rename = "recordedDate", skip_serializing_if = "Option::is_none")]
+    pub recorded_date: Option<String>,
+    /// Clinical stage or phase, e.g. WHO HIV clinical stage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stage: Option<Vec<ConditionStage>>,
     /// Free text notes
     #[serde(skip_serializing_if = "Option::is_none")]
     pub note: Option<Vec<Annotation>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConditionStage {
+    /// The stage itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<CodeableConcept>,
+    /// Kind of staging, e.g. WHO clinical staging
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub type_field: Option<CodeableConcept>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
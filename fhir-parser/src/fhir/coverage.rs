@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
+use super::meta::Meta;
 use super::observation::{CodeableConcept, Reference};
 use super::patient::Identifier;
 
@@ -11,6 +13,8 @@ pub struct Coverage {
     pub resource_type: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
     /// Active coverage status
     pub status: String,
     /// Payer — reference to the SHA Organization entry
@@ -23,4 +27,6 @@ pub struct Coverage {
     /// Coverage type/class — SHA scheme code (e.g. CAT-SHA-001)
     #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
     pub coverage_type: Option<CodeableConcept>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
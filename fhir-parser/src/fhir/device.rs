@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::meta::Meta;
+use super::observation::{CodeableConcept, Reference};
+use super::patient::Identifier;
+
+/// FHIR R4 Device — an instrument a measurement was taken with, e.g. a
+/// facility's pulse oximeter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Device {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// The facility's asset or serial number
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identifier: Option<Vec<Identifier>>,
+    /// active | inactive | entered-in-error | unknown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub type_field: Option<CodeableConcept>,
+    /// Organization responsible for the device
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<Reference>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
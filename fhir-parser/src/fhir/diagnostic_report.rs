@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::meta::Meta;
+use super::observation::{CodeableConcept, Reference};
+
+/// FHIR R4 DiagnosticReport — here the report on an imaging study, with
+/// the radiologist's conclusion.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticReport {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// The orders this report fulfils
+    #[serde(rename = "basedOn", skip_serializing_if = "Option::is_none")]
+    pub based_on: Option<Vec<Reference>>,
+    /// registered | partial | preliminary | final | amended | ...
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<Vec<CodeableConcept>>,
+    pub code: CodeableConcept,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<Reference>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encounter: Option<Reference>,
+    #[serde(rename = "effectiveDateTime", skip_serializing_if = "Option::is_none")]
+    pub effective_date_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conclusion: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
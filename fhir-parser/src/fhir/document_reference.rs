@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::meta::Meta;
+use super::observation::{Attachment, CodeableConcept, Coding, Reference};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentReference {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// current | superseded | entered-in-error
+    pub status: String,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub doc_type: Option<CodeableConcept>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<Vec<CodeableConcept>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<Reference>,
+    /// When this reference was created (instant)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Confidentiality, e.g. `R` (restricted) for biometrics
+    #[serde(rename = "securityLabel", skip_serializing_if = "Option::is_none")]
+    pub security_label: Option<Vec<CodeableConcept>>,
+    pub content: Vec<DocumentReferenceContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<DocumentReferenceContext>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentReferenceContent {
+    pub attachment: Attachment,
+    /// Format/content rules beyond the MIME type (e.g. a template standard)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<Coding>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentReferenceContext {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encounter: Option<Vec<Reference>>,
+    /// Related resources, e.g. the imaging order an image belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub related: Option<Vec<Reference>>,
+}
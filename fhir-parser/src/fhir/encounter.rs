@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
+use super::extension::Extension;
+use super::meta::Meta;
 use super::observation::{CodeableConcept, Coding, Reference};
+use super::patient::Identifier;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Encounter {
@@ -9,11 +13,22 @@ pub struct Encounter {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// Kenya-specific extensions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension: Option<Vec<Extension>>,
+    /// The clinic's own visit number, when it sends one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identifier: Option<Vec<Identifier>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub status: Option<String>,
     /// FHIR R4 Encounter.class — AfyaLink SHR requires "OP" (outpatient),
     /// not "AMB", for outpatient facility visits.
     #[serde(rename = "class", skip_serializing_if = "Option::is_none")]
     pub class: Option<Coding>,
+    /// Urgency, from the triage category (v3-ActPriority)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<CodeableConcept>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub subject: Option<Reference>,
     /// Attending practitioner (HWR PUID reference).
@@ -27,6 +42,20 @@ pub struct Encounter {
     /// Chief complaint / presenting problem
     #[serde(rename = "reasonCode", skip_serializing_if = "Option::is_none")]
     pub reason_code: Option<Vec<CodeableConcept>>,
+    /// Care programme(s) the visit belongs to
+    #[serde(rename = "episodeOfCare", skip_serializing_if = "Option::is_none")]
+    pub episode_of_care: Option<Vec<Reference>>,
+    /// The earlier visit this one follows up
+    #[serde(rename = "partOf", skip_serializing_if = "Option::is_none")]
+    pub part_of: Option<Reference>,
+    /// Where in the facility the visit took place
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<Vec<EncounterLocation>>,
+    /// The visit's diagnosis, ranked and marked for billing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diagnosis: Option<Vec<EncounterDiagnosis>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +66,23 @@ pub struct EncounterParticipant {
     pub individual: Reference,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncounterDiagnosis {
+    /// Condition reference
+    pub condition: Reference,
+    /// Role of the diagnosis (diagnosis-role, e.g. "billing")
+    #[serde(rename = "use", skip_serializing_if = "Option::is_none")]
+    pub use_field: Option<CodeableConcept>,
+    /// 1 = primary diagnosis
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rank: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncounterLocation {
+    pub location: Reference,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Period {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::encounter::Period;
+use super::meta::Meta;
+use super::observation::{CodeableConcept, Reference};
+
+/// FHIR R4 EpisodeOfCare — a patient's enrolment in a care programme
+/// (HIV, TB, NCD clinic) that its Encounters belong to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpisodeOfCare {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// planned | waitlist | active | onhold | finished | cancelled
+    pub status: String,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub type_field: Option<Vec<CodeableConcept>>,
+    pub patient: Reference,
+    #[serde(
+        rename = "managingOrganization",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub managing_organization: Option<Reference>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period: Option<Period>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
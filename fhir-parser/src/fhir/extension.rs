@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+use super::observation::{CodeableConcept, Coding, Reference};
+use super::patient::Identifier;
+
+/// FHIR Extension — a `url` plus one value[x], or nested extensions for a
+/// complex extension. Carries Kenya-specific data (Huduma Namba, refugee ID,
+/// estimated age, ...) that the base resources have no element for.
+///
+/// Only the value types the Kenyan profiles use are modelled; build one with
+/// the constructors, e.g. `Extension::boolean(url, true)`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Extension {
+    pub url: String,
+    #[serde(rename = "valueBoolean", skip_serializing_if = "Option::is_none")]
+    pub value_boolean: Option<bool>,
+    #[serde(rename = "valueInteger", skip_serializing_if = "Option::is_none")]
+    pub value_integer: Option<i64>,
+    #[serde(rename = "valueString", skip_serializing_if = "Option::is_none")]
+    pub value_string: Option<String>,
+    /// FHIR date (YYYY, YYYY-MM or YYYY-MM-DD)
+    #[serde(rename = "valueDate", skip_serializing_if = "Option::is_none")]
+    pub value_date: Option<String>,
+    #[serde(rename = "valueCoding", skip_serializing_if = "Option::is_none")]
+    pub value_coding: Option<Coding>,
+    #[serde(
+        rename = "valueCodeableConcept",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub value_codeable_concept: Option<CodeableConcept>,
+    #[serde(rename = "valueIdentifier", skip_serializing_if = "Option::is_none")]
+    pub value_identifier: Option<Identifier>,
+    #[serde(rename = "valueReference", skip_serializing_if = "Option::is_none")]
+    pub value_reference: Option<Reference>,
+    /// Parts of a complex extension (no value[x] then)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension: Option<Vec<Extension>>,
+}
+
+impl Extension {
+    fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn boolean(url: &str, value: bool) -> Self {
+        Self {
+            value_boolean: Some(value),
+            ..Self::new(url)
+        }
+    }
+
+    pub fn integer(url: &str, value: i64) -> Self {
+        Self {
+            value_integer: Some(value),
+            ..Self::new(url)
+        }
+    }
+
+    pub fn string(url: &str, value: impl Into<String>) -> Self {
+        Self {
+            value_string: Some(value.into()),
+            ..Self::new(url)
+        }
+    }
+
+    pub fn date(url: &str, value: impl Into<String>) -> Self {
+        Self {
+            value_date: Some(value.into()),
+            ..Self::new(url)
+        }
+    }
+
+    pub fn coding(url: &str, value: Coding) -> Self {
+        Self {
+            value_coding: Some(value),
+            ..Self::new(url)
+        }
+    }
+
+    pub fn codeable_concept(url: &str, value: CodeableConcept) -> Self {
+        Self {
+            value_codeable_concept: Some(value),
+            ..Self::new(url)
+        }
+    }
+
+    pub fn identifier(url: &str, value: Identifier) -> Self {
+        Self {
+            value_identifier: Some(value),
+            ..Self::new(url)
+        }
+    }
+
+    pub fn reference(url: &str, value: Reference) -> Self {
+        Self {
+            value_reference: Some(value),
+            ..Self::new(url)
+        }
+    }
+
+    /// A complex extension made of `parts`.
+    pub fn complex(url: &str, parts: Vec<Extension>) -> Self {
+        Self {
+            extension: Some(parts),
+            ..Self::new(url)
+        }
+    }
+}
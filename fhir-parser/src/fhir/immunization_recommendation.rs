@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::meta::Meta;
+use super::observation::{CodeableConcept, Reference};
+
+/// FHIR R4 ImmunizationRecommendation — the vaccine doses a patient is due
+/// for, as forecast against a schedule.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImmunizationRecommendation {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    pub patient: Reference,
+    /// When the forecast was made
+    pub date: String,
+    /// Organization responsible for the forecast
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authority: Option<Reference>,
+    pub recommendation: Vec<Recommendation>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// One recommended dose.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recommendation {
+    #[serde(rename = "vaccineCode", skip_serializing_if = "Option::is_none")]
+    pub vaccine_code: Option<Vec<CodeableConcept>>,
+    /// due | overdue | immune | contraindicated | complete
+    #[serde(rename = "forecastStatus")]
+    pub forecast_status: CodeableConcept,
+    #[serde(rename = "dateCriterion", skip_serializing_if = "Option::is_none")]
+    pub date_criterion: Option<Vec<DateCriterion>>,
+    /// Name of the schedule, e.g. `KEPI`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub series: Option<String>,
+    #[serde(
+        rename = "doseNumberPositiveInt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub dose_number_positive_int: Option<u32>,
+    /// Dose numbers a positiveInt cannot hold, e.g. `0` for a birth dose
+    #[serde(rename = "doseNumberString", skip_serializing_if = "Option::is_none")]
+    pub dose_number_string: Option<String>,
+}
+
+/// A date that matters for the dose: due, overdue, earliest, latest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DateCriterion {
+    pub code: CodeableConcept,
+    pub value: String,
+}
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::meta::Meta;
+use super::observation::{CodeableConcept, Reference};
+
+/// FHIR R4 Location — a service delivery point (department, ward, clinic)
+/// within a facility.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Location {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// active | suspended | inactive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Kind of service delivered here
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub type_field: Option<Vec<CodeableConcept>>,
+    /// Building, wing, ward, room ... (location-physical-type)
+    #[serde(rename = "physicalType", skip_serializing_if = "Option::is_none")]
+    pub physical_type: Option<CodeableConcept>,
+    #[serde(
+        rename = "managingOrganization",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub managing_organization: Option<Reference>,
+    /// The Location this one is physically part of
+    #[serde(rename = "partOf", skip_serializing_if = "Option::is_none")]
+    pub part_of: Option<Reference>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::encounter::Period;
+use super::meta::Meta;
+use super::observation::{CodeableConcept, Quantity, Reference};
+
+/// FHIR R4 MeasureReport — the result of evaluating a quality indicator
+/// (e.g. a DHA indicator) over a reporting period.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeasureReport {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// complete | pending | error
+    pub status: String,
+    /// individual | subject-list | summary | data-collection
+    #[serde(rename = "type")]
+    pub report_type: String,
+    /// Canonical URL of the Measure that was evaluated
+    pub measure: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// Reporting facility
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reporter: Option<Reference>,
+    pub period: Period,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<Vec<MeasureReportGroup>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeasureReportGroup {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<CodeableConcept>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub population: Option<Vec<MeasureReportPopulation>>,
+    /// Proportion measures: numerator / denominator
+    #[serde(rename = "measureScore", skip_serializing_if = "Option::is_none")]
+    pub measure_score: Option<Quantity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeasureReportPopulation {
+    /// measure-population code: initial-population | numerator | denominator | ...
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<CodeableConcept>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+}
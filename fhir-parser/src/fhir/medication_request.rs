@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
-use super::observation::{CodeableConcept, Reference};
+use super::extension::Extension;
+use super::meta::Meta;
+use super::observation::{CodeableConcept, Quantity, Reference};
 
 /// FHIR R4 MedicationRequest — records a prescription or medication order.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,28 +12,91 @@ pub struct MedicationRequest {
     pub resource_type: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// Kenya-specific extensions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension: Option<Vec<Extension>>,
     /// active | on-hold | cancelled | completed | entered-in-error | stopped | draft | unknown
     pub status: String,
     /// proposal | plan | order | original-order | reflex-order | filler-order | instance-order | option
     pub intent: String,
     /// The medication (coded or free text)
-    #[serde(rename = "medicationCodeableConcept", skip_serializing_if = "Option::is_none")]
+    #[serde(
+        rename = "medicationCodeableConcept",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub medication_codeable_concept: Option<CodeableConcept>,
     /// The patient for whom the medication is requested
     pub subject: Reference,
     /// The encounter in which this was prescribed
     #[serde(skip_serializing_if = "Option::is_none")]
     pub encounter: Option<Reference>,
-    /// Dosage instructions as free text
+    /// Dosage instructions (free text, structured where parsed)
     #[serde(rename = "dosageInstruction", skip_serializing_if = "Option::is_none")]
     pub dosage_instruction: Option<Vec<Dosage>>,
     /// The date/time of the prescription
     #[serde(rename = "authoredOn", skip_serializing_if = "Option::is_none")]
     pub authored_on: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Dosage {
     /// Free-text dosage instructions
     pub text: String,
+    /// When the medication should be taken
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing: Option<Timing>,
+    /// PRN — take as needed
+    #[serde(rename = "asNeededBoolean", skip_serializing_if = "Option::is_none")]
+    pub as_needed_boolean: Option<bool>,
+    /// How the drug enters the body (SNOMED CT route)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route: Option<CodeableConcept>,
+    /// Amount per administration
+    #[serde(rename = "doseAndRate", skip_serializing_if = "Option::is_none")]
+    pub dose_and_rate: Option<Vec<DoseAndRate>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Timing {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat: Option<TimingRepeat>,
+}
+
+/// Timing.repeat — "frequency times per period periodUnit", optionally
+/// bounded by a duration or a total count.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimingRepeat {
+    #[serde(rename = "boundsDuration", skip_serializing_if = "Option::is_none")]
+    pub bounds_duration: Option<Duration>,
+    /// Total number of administrations (1 for a stat dose)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period: Option<f64>,
+    /// s | min | h | d | wk | mo | a
+    #[serde(rename = "periodUnit", skip_serializing_if = "Option::is_none")]
+    pub period_unit: Option<String>,
+}
+
+/// FHIR Duration — a UCUM time quantity; `code` is mandatory (drt-1).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Duration {
+    pub value: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoseAndRate {
+    #[serde(rename = "doseQuantity", skip_serializing_if = "Option::is_none")]
+    pub dose_quantity: Option<Quantity>,
 }
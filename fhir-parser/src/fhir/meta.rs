@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+use super::observation::Coding;
+
+/// FHIR Meta — the profiles a resource claims to conform to (e.g. the Kenya
+/// FHIR IG's), plus workflow tags and security labels.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Meta {
+    /// StructureDefinition canonical URLs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<Vec<Coding>>,
+    /// Security labels (v3-Confidentiality, v3-ObservationValue, ...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security: Option<Vec<Coding>>,
+}
@@ -1,10 +1,32 @@
+//! FHIR R4 resource models, covering the elements the bridge reads and
+//! writes. Each resource also has a flattened `extra` map holding the
+//! elements its model does not cover, so a parsed resource re-serializes
+//! without losing them.
+
+pub mod appointment;
 pub mod bundle;
+pub mod care_plan;
 pub mod claim;
+pub mod clinical_impression;
 pub mod condition;
 pub mod coverage;
+pub mod device;
+pub mod diagnostic_report;
+pub mod document_reference;
 pub mod encounter;
+pub mod episode_of_care;
+pub mod extension;
+pub mod flag;
+pub mod immunization_recommendation;
+pub mod location;
+pub mod measure_report;
 pub mod medication_request;
+pub mod meta;
 pub mod observation;
+pub mod operation_outcome;
 pub mod organization;
 pub mod patient;
 pub mod practitioner;
+pub mod practitioner_role;
+pub mod service_request;
+pub mod specimen;
@@ -1,4 +1,8 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::extension::Extension;
+use super::meta::Meta;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Observation {
@@ -6,6 +10,11 @@ pub struct Observation {
     pub resource_type: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// Kenya-specific extensions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension: Option<Vec<Extension>>,
     pub status: String,
     /// Required for vital-signs profile — use observation-category codesystem
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -13,13 +22,39 @@ pub struct Observation {
     pub code: CodeableConcept,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub subject: Option<Reference>,
+    /// Visit the observation was made at
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encounter: Option<Reference>,
     #[serde(rename = "effectiveDateTime", skip_serializing_if = "Option::is_none")]
     pub effective_date_time: Option<String>,
+    /// Who took the measurement
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub performer: Option<Vec<Reference>>,
     #[serde(rename = "valueQuantity", skip_serializing_if = "Option::is_none")]
     pub value_quantity: Option<Quantity>,
+    /// Coded result, e.g. a malnutrition classification
+    #[serde(
+        rename = "valueCodeableConcept",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub value_codeable_concept: Option<CodeableConcept>,
+    /// Result as written, e.g. a Snellen fraction
+    #[serde(rename = "valueString", skip_serializing_if = "Option::is_none")]
+    pub value_string: Option<String>,
+    /// High, low, normal, ... — v3 ObservationInterpretation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interpretation: Option<Vec<CodeableConcept>>,
     /// Used for BP panel — systolic and diastolic as components
     #[serde(skip_serializing_if = "Option::is_none")]
     pub component: Option<Vec<ObservationComponent>>,
+    /// Instrument the measurement was taken with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<Reference>,
+    /// Observations a calculated value (BMI, z-score) was derived from
+    #[serde(rename = "derivedFrom", skip_serializing_if = "Option::is_none")]
+    pub derived_from: Option<Vec<Reference>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -63,3 +98,19 @@ pub struct Reference {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub display: Option<String>,
 }
+
+/// Content inline (base64 `data`) or by `url`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    #[serde(rename = "contentType", skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Bytes of the content (before base64)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
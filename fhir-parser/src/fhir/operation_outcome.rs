@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::meta::Meta;
+use super::observation::CodeableConcept;
+
+/// FHIR R4 OperationOutcome — returned by `$validate` and on request errors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationOutcome {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    #[serde(default)]
+    pub issue: Vec<OperationOutcomeIssue>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationOutcomeIssue {
+    /// fatal | error | warning | information
+    pub severity: String,
+    /// IssueType code (e.g. "invalid", "required", "code-invalid")
+    pub code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<CodeableConcept>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<String>,
+    /// FHIRPath expressions locating the issue
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expression: Option<Vec<String>>,
+}
+
+impl OperationOutcomeIssue {
+    /// True for `fatal` and `error` severities.
+    pub fn is_error(&self) -> bool {
+        matches!(self.severity.as_str(), "fatal" | "error")
+    }
+}
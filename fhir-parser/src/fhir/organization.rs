@@ -1,9 +1,14 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
-use super::patient::Identifier;
+use super::extension::Extension;
+use super::meta::Meta;
+use super::observation::{CodeableConcept, Reference};
+use super::patient::{Address, ContactPoint, Identifier};
 
 /// FHIR R4 Organization resource.
-/// Used to represent the clinic/facility (identified by KMFL ID).
+/// Used to represent the clinic/facility (identified by KMFL ID) and the
+/// sub-county and county health offices above it.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Organization {
     #[serde(rename = "resourceType")]
@@ -11,9 +16,25 @@ pub struct Organization {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// Kenya-specific extensions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension: Option<Vec<Extension>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub identifier: Option<Vec<Identifier>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub active: Option<bool>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub type_field: Option<Vec<CodeableConcept>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telecom: Option<Vec<ContactPoint>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<Vec<Address>>,
+    /// The organization this one is part of (facility → sub-county → county)
+    #[serde(rename = "partOf", skip_serializing_if = "Option::is_none")]
+    pub part_of: Option<Reference>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
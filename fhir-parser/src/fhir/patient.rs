@@ -1,5 +1,10 @@
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+pub use super::extension::Extension;
+use super::meta::Meta;
+use super::observation::{Attachment, Reference};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Patient {
@@ -8,6 +13,11 @@ pub struct Patient {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// Kenya-specific extensions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension: Option<Vec<Extension>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub identifier: Option<Vec<Identifier>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<Vec<HumanName>>,
@@ -17,8 +27,34 @@ pub struct Patient {
     pub gender: Option<String>,
     #[serde(rename = "birthDate", skip_serializing_if = "Option::is_none")]
     pub birth_date: Option<NaiveDate>,
+    /// Extensions on birthDate (the `_birthDate` element), e.g. an
+    /// estimated-date flag
+    #[serde(rename = "_birthDate", skip_serializing_if = "Option::is_none")]
+    pub birth_date_element: Option<PrimitiveElement>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub address: Option<Vec<Address>>,
+    /// Identification photo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub photo: Option<Vec<Attachment>>,
+    /// Other records of the same person (e.g. a Client Registry entry)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<Vec<PatientLink>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Patient.link — `type` is replaced-by, replaces, refer or seealso.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatientLink {
+    pub other: Reference,
+    #[serde(rename = "type")]
+    pub type_field: String,
+}
+
+/// Extensions attached to a primitive value (`_field` in FHIR JSON).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrimitiveElement {
+    pub extension: Vec<Extension>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,6 +76,8 @@ pub struct HumanName {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Address {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension: Option<Vec<Extension>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub line: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
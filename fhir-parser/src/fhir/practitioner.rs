@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
+use super::extension::Extension;
+use super::meta::Meta;
 use super::patient::{HumanName, Identifier};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,9 +12,16 @@ pub struct Practitioner {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// Kenya-specific extensions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension: Option<Vec<Extension>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub identifier: Option<Vec<Identifier>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<Vec<HumanName>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub gender: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::meta::Meta;
+use super::observation::{CodeableConcept, Reference};
+
+/// FHIR R4 PractitionerRole — what a Practitioner does at an Organization:
+/// their cadre (`code`) and the services they deliver (`specialty`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PractitionerRole {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub practitioner: Option<Reference>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub organization: Option<Reference>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<Vec<CodeableConcept>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub specialty: Option<Vec<CodeableConcept>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
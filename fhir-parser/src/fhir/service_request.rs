@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::meta::Meta;
+use super::observation::{CodeableConcept, Reference};
+
+/// FHIR R4 ServiceRequest — an order, here a lab test or imaging study
+/// ordered at a visit for the lab or radiology system to pick up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceRequest {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// draft | active | on-hold | revoked | completed | entered-in-error | unknown
+    pub status: String,
+    /// proposal | plan | directive | order | ...
+    pub intent: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<Vec<CodeableConcept>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<CodeableConcept>,
+    pub subject: Reference,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encounter: Option<Reference>,
+    #[serde(rename = "authoredOn", skip_serializing_if = "Option::is_none")]
+    pub authored_on: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requester: Option<Reference>,
+    #[serde(rename = "reasonReference", skip_serializing_if = "Option::is_none")]
+    pub reason_reference: Option<Vec<Reference>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub specimen: Option<Vec<Reference>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
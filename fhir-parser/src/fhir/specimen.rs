@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::meta::Meta;
+use super::observation::{CodeableConcept, Reference};
+
+/// FHIR R4 Specimen — the sample a lab order is run on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Specimen {
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// available | unavailable | unsatisfactory | entered-in-error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub type_field: Option<CodeableConcept>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<Reference>,
+    /// ServiceRequests the specimen was taken for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request: Option<Vec<Reference>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collection: Option<SpecimenCollection>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpecimenCollection {
+    #[serde(rename = "collectedDateTime", skip_serializing_if = "Option::is_none")]
+    pub collected_date_time: Option<String>,
+}
@@ -0,0 +1,189 @@
+//! FHIRPath-lite: the navigation subset of FHIRPath evaluated over raw JSON.
+//!
+//! Supported: dotted paths (arrays are flattened, choice types like `value`
+//! match `valueQuantity`), a leading resource type (`Bundle.entry...`),
+//! indexers (`name[0]`), and the functions `ofType(Type)`, `first()`,
+//! `last()`, `count()`, `exists()` and `where(path = 'literal')` (also `!=`).
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Field(String),
+    Index(usize),
+    OfType(String),
+    First,
+    Last,
+    Count,
+    Exists,
+    Where {
+        path: Vec<Step>,
+        negate: bool,
+        literal: Value,
+    },
+}
+
+/// Split on `sep` outside quotes and parentheses.
+fn split_top_level(expr: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut in_quote, mut start) = (0usize, false, 0);
+    for (i, c) in expr.char_indices() {
+        match c {
+            '\'' => in_quote = !in_quote,
+            '(' if !in_quote => depth += 1,
+            ')' if !in_quote => depth = depth.saturating_sub(1),
+            c if c == sep && !in_quote && depth == 0 => {
+                parts.push(&expr[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&expr[start..]);
+    parts
+}
+
+fn parse_literal(raw: &str) -> Result<Value> {
+    let raw = raw.trim();
+    if let Some(s) = raw.strip_prefix('\'').and_then(|r| r.strip_suffix('\'')) {
+        return Ok(Value::String(s.to_string()));
+    }
+    match raw {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+    serde_json::from_str::<serde_json::Number>(raw)
+        .map(Value::Number)
+        .with_context(|| format!("Unsupported literal: {}", raw))
+}
+
+fn parse_where(args: &str) -> Result<Step> {
+    let (path, literal, negate) = if let Some((p, l)) = args.split_once("!=") {
+        (p, l, true)
+    } else if let Some((p, l)) = args.split_once('=') {
+        (p, l, false)
+    } else {
+        bail!("where() needs a comparison, e.g. where(status = 'final')");
+    };
+    Ok(Step::Where {
+        path: parse(path.trim())?,
+        negate,
+        literal: parse_literal(literal)?,
+    })
+}
+
+fn parse_step(segment: &str) -> Result<Vec<Step>> {
+    let segment = segment.trim();
+    if let Some((name, rest)) = segment.split_once('(') {
+        let args = rest
+            .strip_suffix(')')
+            .with_context(|| format!("Unclosed '(' in {}", segment))?
+            .trim();
+        let step = match name {
+            "ofType" => Step::OfType(args.to_string()),
+            "first" => Step::First,
+            "last" => Step::Last,
+            "count" => Step::Count,
+            "exists" => Step::Exists,
+            "where" => parse_where(args)?,
+            other => bail!("Unsupported function: {}()", other),
+        };
+        return Ok(vec![step]);
+    }
+
+    let (name, indexes) = match segment.find('[') {
+        Some(i) => (&segment[..i], &segment[i..]),
+        None => (segment, ""),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        bail!("Invalid path segment: {:?}", segment);
+    }
+    let mut steps = vec![Step::Field(name.to_string())];
+    for idx in indexes.split_terminator(']') {
+        let n = idx
+            .strip_prefix('[')
+            .and_then(|n| n.trim().parse().ok())
+            .with_context(|| format!("Invalid indexer in {}", segment))?;
+        steps.push(Step::Index(n));
+    }
+    Ok(steps)
+}
+
+fn parse(expr: &str) -> Result<Vec<Step>> {
+    let mut steps = Vec::new();
+    for segment in split_top_level(expr, '.') {
+        steps.extend(parse_step(segment)?);
+    }
+    Ok(steps)
+}
+
+/// `name` on one JSON object, including choice types (`value` → `valueQuantity`).
+fn field<'a>(item: &'a Value, name: &str) -> Option<&'a Value> {
+    let obj = item.as_object()?;
+    obj.get(name).or_else(|| {
+        obj.iter()
+            .find(|(k, _)| {
+                k.strip_prefix(name)
+                    .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_uppercase()))
+            })
+            .map(|(_, v)| v)
+    })
+}
+
+fn push_flat(out: &mut Vec<Value>, value: &Value) {
+    match value {
+        Value::Array(items) => out.extend(items.iter().cloned()),
+        other => out.push(other.clone()),
+    }
+}
+
+fn apply(steps: &[Step], input: Vec<Value>) -> Vec<Value> {
+    let mut current = input;
+    for step in steps {
+        current = match step {
+            Step::Field(name) => {
+                let mut out = Vec::new();
+                for item in &current {
+                    if let Some(v) = field(item, name) {
+                        push_flat(&mut out, v);
+                    }
+                }
+                out
+            }
+            Step::Index(n) => current.into_iter().nth(*n).into_iter().collect(),
+            Step::OfType(t) => current
+                .into_iter()
+                .filter(|v| v.get("resourceType").and_then(Value::as_str) == Some(t))
+                .collect(),
+            Step::First => current.into_iter().next().into_iter().collect(),
+            Step::Last => current.into_iter().last().into_iter().collect(),
+            Step::Count => vec![Value::from(current.len())],
+            Step::Exists => vec![Value::Bool(!current.is_empty())],
+            Step::Where {
+                path,
+                negate,
+                literal,
+            } => current
+                .into_iter()
+                .filter(|item| {
+                    let hit = apply(path, vec![item.clone()]).contains(literal);
+                    hit != *negate
+                })
+                .collect(),
+        };
+    }
+    current
+}
+
+/// Evaluate `expr` against a resource and return the resulting collection.
+pub fn evaluate(expr: &str, resource: &Value) -> Result<Vec<Value>> {
+    let mut steps = parse(expr)?;
+    // A leading type name (`Bundle.`, `Patient.`) selects the root itself.
+    if let Some(Step::Field(first)) = steps.first() {
+        if resource.get("resourceType").and_then(Value::as_str) == Some(first.as_str()) {
+            steps.remove(0);
+        }
+    }
+    Ok(apply(&steps, vec![resource.clone()]))
+}
@@ -1,3 +1,7 @@
+pub mod diff;
 pub mod fhir;
+pub mod fhirpath;
+pub mod masking;
 pub mod output;
+pub mod references;
 pub mod validation;
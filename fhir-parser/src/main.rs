@@ -1,41 +1,154 @@
 use std::fs;
 
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
+use fhir_parser::diff::diff_json;
 use fhir_parser::fhir::bundle::Bundle;
+use fhir_parser::fhir::claim::Claim;
+use fhir_parser::fhir::condition::Condition;
+use fhir_parser::fhir::coverage::Coverage;
 use fhir_parser::fhir::encounter::Encounter;
+use fhir_parser::fhir::medication_request::MedicationRequest;
 use fhir_parser::fhir::observation::Observation;
+use fhir_parser::fhir::organization::Organization;
 use fhir_parser::fhir::patient::Patient;
 use fhir_parser::fhir::practitioner::Practitioner;
+use fhir_parser::fhirpath;
+use fhir_parser::masking::{mask_identifier, mask_reference, set_reveal_identifiers};
 use fhir_parser::output::{
-    format_encounter, format_observation, format_patient, format_practitioner,
+    format_bundle, format_claim, format_condition, format_coverage, format_encounter,
+    format_medication_request, format_observation, format_organization, format_patient,
+    format_practitioner,
 };
+use fhir_parser::references::check_references;
 use fhir_parser::validation::{validate_observation, validate_patient};
 
 #[derive(Parser, Debug)]
 #[command(name = "fhir-parser")]
 #[command(about = "Parse and summarize FHIR R4 resources")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path to FHIR JSON file
-    #[arg(short, long)]
-    file: String,
+    #[arg(short, long, required = true)]
+    file: Option<String>,
 
-    /// Resource type: patient, observation, encounter, practitioner, bundle
-    #[arg(short, long)]
-    resource_type: String,
+    /// Resource type: patient, observation, encounter, practitioner, condition,
+    /// medication-request, coverage, claim, organization, bundle
+    #[arg(short, long, required_unless_present = "query")]
+    resource_type: Option<String>,
 
     /// Validate the resource and print warnings/errors
     #[arg(short, long, default_value_t = false)]
     validate: bool,
+
+    /// Bundle only: report references that do not resolve to an entry
+    #[arg(long, default_value_t = false)]
+    check_references: bool,
+
+    /// FHIRPath-lite expression to evaluate instead of summarising, e.g.
+    /// `Bundle.entry.resource.ofType(Observation).valueQuantity.value`
+    #[arg(short, long)]
+    query: Option<String>,
+
+    /// Show identifiers and resource IDs unmasked (debugging only)
+    #[arg(long, global = true, default_value_t = false)]
+    show_identifiers: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compare two bundles/resources, ignoring entry order, bundle ids and timestamps
+    Diff {
+        /// Expected (e.g. golden) file
+        before: String,
+        /// Actual file
+        after: String,
+    },
+}
+
+fn read_json(path: &str) -> Result<serde_json::Value> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+    serde_json::from_str(&content).with_context(|| format!("Invalid JSON in {}", path))
+}
+
+/// Value for display; identifier values and references are masked.
+fn show_value(path: &str, value: &Option<serde_json::Value>) -> String {
+    match value {
+        None => "(none)".to_string(),
+        Some(serde_json::Value::String(s)) if path.ends_with("reference") => {
+            format!("{:?}", mask_reference(s))
+        }
+        Some(serde_json::Value::String(s))
+            if path.contains("identifier") && path.ends_with("value") =>
+        {
+            format!("{:?}", mask_identifier(s))
+        }
+        Some(v) => v.to_string(),
+    }
+}
+
+/// Print the differences; exits non-zero when the files differ (like diff(1)).
+fn run_diff(before: &str, after: &str) -> Result<()> {
+    let diff = diff_json(&read_json(before)?, &read_json(after)?);
+    for key in &diff.added {
+        println!("+ {}", mask_reference(key));
+    }
+    for key in &diff.removed {
+        println!("- {}", mask_reference(key));
+    }
+    for c in &diff.changed {
+        println!(
+            "~ {} {}: {} -> {}",
+            mask_reference(&c.resource),
+            c.path,
+            show_value(&c.path, &c.before),
+            show_value(&c.path, &c.after)
+        );
+    }
+    if diff.is_empty() {
+        println!("No differences");
+        return Ok(());
+    }
+    println!(
+        "{} added, {} removed, {} changed element(s)",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len()
+    );
+    std::process::exit(1);
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    let content =
-        fs::read_to_string(&cli.file).with_context(|| format!("Failed to read {}", cli.file))?;
+    set_reveal_identifiers(cli.show_identifiers);
+    if let Some(Command::Diff { before, after }) = &cli.command {
+        return run_diff(before, after);
+    }
+
+    let file = cli.file.as_deref().context("--file is required")?;
+    let content = fs::read_to_string(file).with_context(|| format!("Failed to read {}", file))?;
 
-    match cli.resource_type.as_str() {
+    if let Some(ref expr) = cli.query {
+        let json: serde_json::Value = serde_json::from_str(&content).context("Invalid JSON")?;
+        for value in fhirpath::evaluate(expr, &json)? {
+            match value {
+                serde_json::Value::String(s) => println!("{}", s),
+                other => println!("{}", other),
+            }
+        }
+        return Ok(());
+    }
+
+    let resource_type = cli.resource_type.as_deref().unwrap_or_default();
+    if cli.check_references && resource_type != "bundle" {
+        anyhow::bail!("--check-references requires --resource-type bundle");
+    }
+
+    match resource_type {
         "patient" => {
             let patient: Patient =
                 serde_json::from_str(&content).context("Invalid Patient JSON")?;
@@ -68,15 +181,45 @@ fn main() -> Result<()> {
                 serde_json::from_str(&content).context("Invalid Practitioner JSON")?;
             print!("{}", format_practitioner(&prac));
         }
+        "condition" => {
+            let cond: Condition =
+                serde_json::from_str(&content).context("Invalid Condition JSON")?;
+            print!("{}", format_condition(&cond));
+        }
+        "medication-request" => {
+            let req: MedicationRequest =
+                serde_json::from_str(&content).context("Invalid MedicationRequest JSON")?;
+            print!("{}", format_medication_request(&req));
+        }
+        "coverage" => {
+            let cov: Coverage = serde_json::from_str(&content).context("Invalid Coverage JSON")?;
+            print!("{}", format_coverage(&cov));
+        }
+        "claim" => {
+            let claim: Claim = serde_json::from_str(&content).context("Invalid Claim JSON")?;
+            print!("{}", format_claim(&claim));
+        }
+        "organization" => {
+            let org: Organization =
+                serde_json::from_str(&content).context("Invalid Organization JSON")?;
+            print!("{}", format_organization(&org));
+        }
         "bundle" => {
-            let bundle: Bundle =
-                serde_json::from_str(&content).context("Invalid Bundle JSON")?;
-            println!("## Bundle\n");
-            if let Some(ref t) = bundle.bundle_type {
-                println!("- **Type**: {}", t);
-            }
-            if let Some(ref entries) = bundle.entry {
-                println!("- **Entries**: {}", entries.len());
+            let bundle: Bundle = serde_json::from_str(&content).context("Invalid Bundle JSON")?;
+            print!("{}", format_bundle(&bundle));
+            if cli.check_references {
+                let dangling = check_references(&bundle);
+                for d in &dangling {
+                    eprintln!(
+                        "[REFERENCE] {}: {} does not resolve within the bundle",
+                        d.location,
+                        mask_reference(&d.reference)
+                    );
+                }
+                if !dangling.is_empty() {
+                    anyhow::bail!("{} dangling reference(s)", dangling.len());
+                }
+                println!("\n## References\n\n- All references resolve");
             }
         }
         other => anyhow::bail!("Unsupported resource type: {}", other),
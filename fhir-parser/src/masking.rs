@@ -0,0 +1,42 @@
+//! Display masking for identifiers in CLI output, logs and reports.
+//!
+//! National IDs, member numbers and resource IDs derived from them are shown
+//! as `****` + last 4 characters. Masking is on by default; `--show-identifiers`
+//! turns it off for debugging.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REVEAL_IDENTIFIERS: AtomicBool = AtomicBool::new(false);
+
+/// Globally enable/disable unmasked display (debugging only).
+pub fn set_reveal_identifiers(reveal: bool) {
+    REVEAL_IDENTIFIERS.store(reveal, Ordering::Relaxed);
+}
+
+pub fn identifiers_revealed() -> bool {
+    REVEAL_IDENTIFIERS.load(Ordering::Relaxed)
+}
+
+/// `27845612` → `****5612`. Values of 4 characters or fewer are fully masked.
+pub fn mask_identifier(value: &str) -> String {
+    if identifiers_revealed() {
+        return value.to_string();
+    }
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= 4 {
+        return "****".to_string();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("****{}", tail)
+}
+
+/// Mask the id part of a `Type/id` or `urn:uuid:id` reference, keeping the type.
+pub fn mask_reference(reference: &str) -> String {
+    match reference.rfind(['/', ':']) {
+        Some(i) => format!(
+            "{}{}",
+            &reference[..=i],
+            mask_identifier(&reference[i + 1..])
+        ),
+        None => mask_identifier(reference),
+    }
+}
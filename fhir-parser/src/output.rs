@@ -1,22 +1,30 @@
+use std::collections::BTreeMap;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::fhir::bundle::Bundle;
+use crate::fhir::claim::Claim;
+use crate::fhir::condition::Condition;
+use crate::fhir::coverage::Coverage;
 use crate::fhir::encounter::Encounter;
-use crate::fhir::observation::Observation;
+use crate::fhir::medication_request::MedicationRequest;
+use crate::fhir::observation::{CodeableConcept, Observation};
+use crate::fhir::organization::Organization;
 use crate::fhir::patient::Patient;
 use crate::fhir::practitioner::Practitioner;
+use crate::masking::{mask_identifier, mask_reference};
 
 pub fn format_patient(patient: &Patient) -> String {
     let mut out = String::from("## Patient\n\n");
 
     if let Some(ref id) = patient.id {
-        out.push_str(&format!("- **ID**: {}\n", id));
+        out.push_str(&format!("- **ID**: {}\n", mask_identifier(id)));
     }
 
     if let Some(ref names) = patient.name {
         for n in names {
-            let given = n
-                .given
-                .as_ref()
-                .map(|g| g.join(" "))
-                .unwrap_or_default();
+            let given = n.given.as_ref().map(|g| g.join(" ")).unwrap_or_default();
             let family = n.family.as_deref().unwrap_or("");
             out.push_str(&format!("- **Name**: {} {}\n", given, family));
         }
@@ -33,7 +41,11 @@ pub fn format_patient(patient: &Patient) -> String {
     if let Some(ref ids) = patient.identifier {
         for ident in ids {
             let sys = ident.system.as_deref().unwrap_or("unknown");
-            out.push_str(&format!("- **Identifier** ({}): {}\n", sys, ident.value));
+            out.push_str(&format!(
+                "- **Identifier** ({}): {}\n",
+                sys,
+                mask_identifier(&ident.value)
+            ));
         }
     }
 
@@ -52,7 +64,7 @@ pub fn format_observation(obs: &Observation) -> String {
     let mut out = String::from("## Observation\n\n");
 
     if let Some(ref id) = obs.id {
-        out.push_str(&format!("- **ID**: {}\n", id));
+        out.push_str(&format!("- **ID**: {}\n", mask_identifier(id)));
     }
 
     out.push_str(&format!("- **Status**: {}\n", obs.status));
@@ -69,7 +81,7 @@ pub fn format_observation(obs: &Observation) -> String {
 
     if let Some(ref subj) = obs.subject {
         if let Some(ref r) = subj.reference {
-            out.push_str(&format!("- **Subject**: {}\n", r));
+            out.push_str(&format!("- **Subject**: {}\n", mask_reference(r)));
         }
     }
 
@@ -78,6 +90,14 @@ pub fn format_observation(obs: &Observation) -> String {
         out.push_str(&format!("- **Value**: {} {}\n", q.value, unit));
     }
 
+    for comp in obs.component.iter().flatten() {
+        let code = concept_label(&comp.code).unwrap_or_else(|| "n/a".to_string());
+        if let Some(ref q) = comp.value_quantity {
+            let unit = q.unit.as_deref().unwrap_or("");
+            out.push_str(&format!("- **{}**: {} {}\n", code, q.value, unit));
+        }
+    }
+
     out
 }
 
@@ -85,7 +105,7 @@ pub fn format_encounter(enc: &Encounter) -> String {
     let mut out = String::from("## Encounter\n\n");
 
     if let Some(ref id) = enc.id {
-        out.push_str(&format!("- **ID**: {}\n", id));
+        out.push_str(&format!("- **ID**: {}\n", mask_identifier(id)));
     }
 
     if let Some(ref status) = enc.status {
@@ -94,7 +114,7 @@ pub fn format_encounter(enc: &Encounter) -> String {
 
     if let Some(ref subj) = enc.subject {
         if let Some(ref r) = subj.reference {
-            out.push_str(&format!("- **Subject**: {}\n", r));
+            out.push_str(&format!("- **Subject**: {}\n", mask_reference(r)));
         }
     }
 
@@ -111,16 +131,12 @@ pub fn format_practitioner(prac: &Practitioner) -> String {
     let mut out = String::from("## Practitioner\n\n");
 
     if let Some(ref id) = prac.id {
-        out.push_str(&format!("- **ID**: {}\n", id));
+        out.push_str(&format!("- **ID**: {}\n", mask_identifier(id)));
     }
 
     if let Some(ref names) = prac.name {
         for n in names {
-            let given = n
-                .given
-                .as_ref()
-                .map(|g| g.join(" "))
-                .unwrap_or_default();
+            let given = n.given.as_ref().map(|g| g.join(" ")).unwrap_or_default();
             let family = n.family.as_deref().unwrap_or("");
             out.push_str(&format!("- **Name**: {} {}\n", given, family));
         }
@@ -132,3 +148,247 @@ pub fn format_practitioner(prac: &Practitioner) -> String {
 
     out
 }
+
+/// `text`, else the first coding as `display (code)`.
+fn concept_label(concept: &CodeableConcept) -> Option<String> {
+    if let Some(ref text) = concept.text {
+        return Some(text.clone());
+    }
+    let c = concept.coding.as_ref()?.first()?;
+    let display = c.display.as_deref().unwrap_or("n/a");
+    let code = c.code.as_deref().unwrap_or("n/a");
+    Some(format!("{} ({})", display, code))
+}
+
+pub fn format_condition(cond: &Condition) -> String {
+    let mut out = String::from("## Condition\n\n");
+
+    if let Some(ref id) = cond.id {
+        out.push_str(&format!("- **ID**: {}\n", mask_identifier(id)));
+    }
+
+    if let Some(label) = cond.clinical_status.as_ref().and_then(concept_label) {
+        out.push_str(&format!("- **Clinical Status**: {}\n", label));
+    }
+
+    if let Some(ref code) = cond.code {
+        for c in code.coding.iter().flatten() {
+            let system = c.system.as_deref().unwrap_or("unknown");
+            let value = c.code.as_deref().unwrap_or("n/a");
+            let display = c.display.as_deref().unwrap_or("");
+            out.push_str(&format!("- **Code** ({}): {} {}\n", system, value, display));
+        }
+        if let Some(ref text) = code.text {
+            out.push_str(&format!("- **Text**: {}\n", text));
+        }
+    }
+
+    if let Some(ref r) = cond.subject.as_ref().and_then(|s| s.reference.clone()) {
+        out.push_str(&format!("- **Subject**: {}\n", mask_reference(r)));
+    }
+
+    if let Some(ref onset) = cond.onset_date_time {
+        out.push_str(&format!("- **Onset**: {}\n", onset));
+    }
+
+    out
+}
+
+pub fn format_medication_request(req: &MedicationRequest) -> String {
+    let mut out = String::from("## MedicationRequest\n\n");
+
+    if let Some(ref id) = req.id {
+        out.push_str(&format!("- **ID**: {}\n", mask_identifier(id)));
+    }
+
+    out.push_str(&format!("- **Status**: {}\n", req.status));
+    out.push_str(&format!("- **Intent**: {}\n", req.intent));
+
+    if let Some(label) = req
+        .medication_codeable_concept
+        .as_ref()
+        .and_then(concept_label)
+    {
+        out.push_str(&format!("- **Medication**: {}\n", label));
+    }
+
+    for d in req.dosage_instruction.iter().flatten() {
+        out.push_str(&format!("- **Dosage**: {}\n", d.text));
+    }
+
+    if let Some(ref r) = req.subject.reference {
+        out.push_str(&format!("- **Subject**: {}\n", mask_reference(r)));
+    }
+
+    if let Some(ref authored) = req.authored_on {
+        out.push_str(&format!("- **Authored On**: {}\n", authored));
+    }
+
+    out
+}
+
+pub fn format_coverage(cov: &Coverage) -> String {
+    let mut out = String::from("## Coverage\n\n");
+
+    if let Some(ref id) = cov.id {
+        out.push_str(&format!("- **ID**: {}\n", mask_identifier(id)));
+    }
+
+    out.push_str(&format!("- **Status**: {}\n", cov.status));
+
+    if let Some(label) = cov.coverage_type.as_ref().and_then(concept_label) {
+        out.push_str(&format!("- **Type**: {}\n", label));
+    }
+
+    for ident in cov.identifier.iter().flatten() {
+        let sys = ident.system.as_deref().unwrap_or("unknown");
+        out.push_str(&format!(
+            "- **Identifier** ({}): {}\n",
+            sys,
+            mask_identifier(&ident.value)
+        ));
+    }
+
+    if let Some(ref r) = cov.beneficiary.reference {
+        out.push_str(&format!("- **Beneficiary**: {}\n", mask_reference(r)));
+    }
+
+    for p in &cov.payor {
+        let payor = p
+            .display
+            .as_deref()
+            .or(p.reference.as_deref())
+            .unwrap_or("n/a");
+        out.push_str(&format!("- **Payor**: {}\n", payor));
+    }
+
+    out
+}
+
+pub fn format_claim(claim: &Claim) -> String {
+    let mut out = String::from("## Claim\n\n");
+
+    if let Some(ref id) = claim.id {
+        out.push_str(&format!("- **ID**: {}\n", mask_identifier(id)));
+    }
+
+    out.push_str(&format!("- **Status**: {}\n", claim.status));
+    out.push_str(&format!("- **Use**: {}\n", claim.use_field));
+
+    if let Some(label) = concept_label(&claim.claim_type) {
+        out.push_str(&format!("- **Type**: {}\n", label));
+    }
+
+    out.push_str(&format!("- **Created**: {}\n", claim.created));
+
+    if let Some(ref r) = claim.patient.reference {
+        out.push_str(&format!("- **Patient**: {}\n", mask_reference(r)));
+    }
+
+    for item in claim.item.iter().flatten() {
+        if let Some(label) = concept_label(&item.product_or_service) {
+            out.push_str(&format!("- **Item {}**: {}\n", item.sequence, label));
+        }
+    }
+
+    for dx in claim.diagnosis.iter().flatten() {
+        if let Some(label) = concept_label(&dx.diagnosis_codeable_concept) {
+            out.push_str(&format!("- **Diagnosis {}**: {}\n", dx.sequence, label));
+        }
+    }
+
+    out
+}
+
+pub fn format_organization(org: &Organization) -> String {
+    let mut out = String::from("## Organization\n\n");
+
+    if let Some(ref id) = org.id {
+        out.push_str(&format!("- **ID**: {}\n", id));
+    }
+
+    if let Some(ref name) = org.name {
+        out.push_str(&format!("- **Name**: {}\n", name));
+    }
+
+    for ident in org.identifier.iter().flatten() {
+        let sys = ident.system.as_deref().unwrap_or("unknown");
+        out.push_str(&format!("- **Identifier** ({}): {}\n", sys, ident.value));
+    }
+
+    if let Some(active) = org.active {
+        out.push_str(&format!("- **Active**: {}\n", active));
+    }
+
+    out
+}
+
+/// Deserialize an entry resource and format it, or note why it could not be.
+fn summarize_entry<T: DeserializeOwned>(resource: &Value, format: fn(&T) -> String) -> String {
+    match serde_json::from_value::<T>(resource.clone()) {
+        Ok(r) => format(&r),
+        Err(e) => format!("_Could not parse resource: {}_\n", e),
+    }
+}
+
+/// Per-resource summary of every entry, dispatched on `resourceType`.
+pub fn format_bundle(bundle: &Bundle) -> String {
+    let mut out = String::from("## Bundle\n\n");
+
+    if let Some(ref t) = bundle.bundle_type {
+        out.push_str(&format!("- **Type**: {}\n", t));
+    }
+
+    let entries = bundle.entry.as_deref().unwrap_or_default();
+    out.push_str(&format!("- **Entries**: {}\n", entries.len()));
+
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut claim_items = 0;
+    let mut sections = Vec::new();
+    for entry in entries {
+        let Some(resource) = &entry.resource else {
+            continue;
+        };
+        let resource_type = resource
+            .get("resourceType")
+            .and_then(Value::as_str)
+            .unwrap_or("Unknown");
+        *counts.entry(resource_type).or_default() += 1;
+
+        let section = match resource_type {
+            "Patient" => summarize_entry(resource, format_patient),
+            "Observation" => summarize_entry(resource, format_observation),
+            "Encounter" => summarize_entry(resource, format_encounter),
+            "Practitioner" => summarize_entry(resource, format_practitioner),
+            "Condition" => summarize_entry(resource, format_condition),
+            "MedicationRequest" => summarize_entry(resource, format_medication_request),
+            "Coverage" => summarize_entry(resource, format_coverage),
+            "Claim" => {
+                if let Ok(claim) = serde_json::from_value::<Claim>(resource.clone()) {
+                    claim_items += claim.item.as_ref().map_or(0, Vec::len);
+                }
+                summarize_entry(resource, format_claim)
+            }
+            "Organization" => summarize_entry(resource, format_organization),
+            other => format!("## {}\n\n_No summary for this resource type_\n", other),
+        };
+        sections.push(section);
+    }
+
+    for (resource_type, n) in &counts {
+        out.push_str(&format!("- **{}**: {}\n", resource_type, n));
+    }
+    if let Some(claims) = counts.get("Claim") {
+        out.push_str(&format!(
+            "- **Claim totals**: {} claim(s), {} line item(s)\n",
+            claims, claim_items
+        ));
+    }
+
+    for section in sections {
+        out.push('\n');
+        out.push_str(&section);
+    }
+
+    out
+}
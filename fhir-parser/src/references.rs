@@ -0,0 +1,104 @@
+//! Reference integrity for Bundles: every `Reference.reference` should point
+//! at an entry in the same bundle.
+//!
+//! A reference resolves when it equals an entry's `fullUrl`, or is a relative
+//! `Type/id` URL matching an entry's resource (directly, or as the tail of an
+//! absolute `fullUrl` such as `http://server/fhir/Patient/123`). Contained
+//! (`#id`) references are out of scope and always accepted, as are
+//! conditional references (`Encounter?identifier=...`), which the server
+//! resolves when it processes a transaction.
+use std::collections::HashSet;
+
+use serde_json::Value;
+
+use crate::fhir::bundle::Bundle;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DanglingReference {
+    /// JSON path of the Reference, e.g. `entry[2].resource.subject`
+    pub location: String,
+    pub reference: String,
+}
+
+/// Collect every `Reference.reference` string with its JSON path.
+pub fn collect_references(value: &Value, path: &str, out: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) => {
+            if let Some(Value::String(r)) = map.get("reference") {
+                out.push((path.to_string(), r.clone()));
+            }
+            for (k, v) in map {
+                collect_references(v, &format!("{}.{}", path, k), out);
+            }
+        }
+        Value::Array(items) => {
+            for (i, v) in items.iter().enumerate() {
+                collect_references(v, &format!("{}[{}]", path, i), out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `http://server/fhir/Patient/123` → `Patient/123`.
+fn relative_tail(url: &str) -> Option<String> {
+    let mut parts = url.rsplit('/');
+    let id = parts.next()?;
+    let resource_type = parts.next()?;
+    if id.is_empty() || !resource_type.starts_with(|c: char| c.is_ascii_uppercase()) {
+        return None;
+    }
+    Some(format!("{}/{}", resource_type, id))
+}
+
+/// Everything a reference in `bundle` may legitimately point at.
+fn reference_targets(bundle: &Bundle) -> HashSet<String> {
+    let mut targets = HashSet::new();
+    for entry in bundle.entry.as_deref().unwrap_or_default() {
+        if let Some(url) = entry.full_url.as_deref() {
+            targets.insert(url.to_string());
+            if let Some(tail) = relative_tail(url) {
+                targets.insert(tail);
+            }
+        }
+        if let Some(res) = &entry.resource {
+            if let (Some(rt), Some(id)) = (
+                res.get("resourceType").and_then(Value::as_str),
+                res.get("id").and_then(Value::as_str),
+            ) {
+                targets.insert(format!("{}/{}", rt, id));
+            }
+        }
+    }
+    targets
+}
+
+/// Every reference in the bundle that does not resolve to one of its entries.
+pub fn check_references(bundle: &Bundle) -> Vec<DanglingReference> {
+    let targets = reference_targets(bundle);
+    let mut dangling = Vec::new();
+    for (i, entry) in bundle
+        .entry
+        .as_deref()
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+    {
+        let Some(resource) = &entry.resource else {
+            continue;
+        };
+        let mut refs = Vec::new();
+        collect_references(resource, &format!("entry[{}].resource", i), &mut refs);
+        for (location, reference) in refs {
+            if reference.starts_with('#') || reference.contains('?') || targets.contains(&reference)
+            {
+                continue;
+            }
+            dangling.push(DanglingReference {
+                location,
+                reference,
+            });
+        }
+    }
+    dangling
+}
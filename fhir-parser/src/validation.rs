@@ -1,46 +1,158 @@
+use std::fmt;
+
 use crate::fhir::observation::Observation;
+use crate::fhir::operation_outcome::{OperationOutcome, OperationOutcomeIssue};
 use crate::fhir::patient::Patient;
 
-pub fn validate_patient(patient: &Patient) -> Vec<String> {
-    let mut errors = Vec::new();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    /// OperationOutcome.issue.severity code
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// One validation finding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    /// FHIR IssueType code, e.g. `required`, `invalid`
+    pub code: &'static str,
+    /// FHIRPath-style location, e.g. `Patient.name[0]`
+    pub path: String,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn error(code: &'static str, path: impl Into<String>, message: &str) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            path: path.into(),
+            message: message.to_string(),
+        }
+    }
+
+    fn warning(code: &'static str, path: impl Into<String>, message: &str) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(code, path, message)
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {}",
+            self.severity.as_str(),
+            self.path,
+            self.message
+        )
+    }
+}
+
+/// Wrap issues in an OperationOutcome (as a FHIR server's `$validate` would).
+pub fn to_operation_outcome(issues: &[ValidationIssue]) -> OperationOutcome {
+    OperationOutcome {
+        resource_type: "OperationOutcome".to_string(),
+        id: None,
+        meta: None,
+        issue: issues
+            .iter()
+            .map(|i| OperationOutcomeIssue {
+                severity: i.severity.as_str().to_string(),
+                code: i.code.to_string(),
+                details: None,
+                diagnostics: Some(i.message.clone()),
+                expression: Some(vec![i.path.clone()]),
+            })
+            .collect(),
+        extra: Default::default(),
+    }
+}
+
+pub fn validate_patient(patient: &Patient) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
 
     if patient.resource_type != "Patient" {
-        errors.push("resourceType must be \"Patient\"".into());
+        issues.push(ValidationIssue::error(
+            "invalid",
+            "Patient.resourceType",
+            "resourceType must be \"Patient\"",
+        ));
     }
 
     if patient.identifier.is_none() && patient.name.is_none() {
-        errors.push("Warning: Patient should have at least an identifier or name".into());
+        issues.push(ValidationIssue::warning(
+            "required",
+            "Patient",
+            "Patient should have at least an identifier or name",
+        ));
     }
 
     if let Some(ref names) = patient.name {
-        for n in names {
+        for (i, n) in names.iter().enumerate() {
             if n.family.is_none() && n.given.is_none() {
-                errors.push("Warning: HumanName has neither family nor given".into());
+                issues.push(ValidationIssue::warning(
+                    "incomplete",
+                    format!("Patient.name[{}]", i),
+                    "HumanName has neither family nor given",
+                ));
             }
         }
     }
 
-    errors
+    issues
 }
 
-pub fn validate_observation(obs: &Observation) -> Vec<String> {
-    let mut errors = Vec::new();
+pub fn validate_observation(obs: &Observation) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
 
     if obs.resource_type != "Observation" {
-        errors.push("resourceType must be \"Observation\"".into());
+        issues.push(ValidationIssue::error(
+            "invalid",
+            "Observation.resourceType",
+            "resourceType must be \"Observation\"",
+        ));
     }
 
     if obs.status.is_empty() {
-        errors.push("Observation.status is required".into());
+        issues.push(ValidationIssue::error(
+            "required",
+            "Observation.status",
+            "Observation.status is required",
+        ));
     }
 
     if obs.code.coding.is_none() && obs.code.text.is_none() {
-        errors.push("Observation.code must have coding or text".into());
+        issues.push(ValidationIssue::error(
+            "required",
+            "Observation.code",
+            "Observation.code must have coding or text",
+        ));
     }
 
     if obs.subject.is_none() {
-        errors.push("Warning: Observation should have a subject reference".into());
+        issues.push(ValidationIssue::warning(
+            "required",
+            "Observation.subject",
+            "Observation should have a subject reference",
+        ));
     }
 
-    errors
+    issues
 }
@@ -0,0 +1,59 @@
+/*
+ * Kenya FHIR Bridge: C interface for in-process embedding.
+ *
+ * Build the shared library with
+ *   cargo rustc --lib --release --features ffi --crate-type cdylib
+ * (kenya_fhir_bridge.dll / libkenya_fhir_bridge.so / .dylib).
+ *
+ * All functions use the C calling convention (Delphi: cdecl). Strings are
+ * NUL-terminated UTF-8. After a failure, kfb_last_error() and
+ * kfb_last_error_message() describe it for the calling thread.
+ */
+#ifndef KENYA_FHIR_BRIDGE_H
+#define KENYA_FHIR_BRIDGE_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define KFB_OK 0
+/* NULL pointer or text that is not UTF-8 */
+#define KFB_ERR_ARGUMENT 1
+/* The record breaks the input rules; send it back to the clinic */
+#define KFB_ERR_VALIDATION 2
+#define KFB_ERR_MAPPING 3
+#define KFB_ERR_TERMINOLOGY 4
+/* A server did not answer; worth retrying later */
+#define KFB_ERR_NETWORK 5
+#define KFB_ERR_QUEUE 6
+#define KFB_ERR_STORAGE 7
+#define KFB_ERR_CONFIG 8
+#define KFB_ERR_IO 9
+/* A bug in the bridge */
+#define KFB_ERR_INTERNAL 99
+
+/* Kenyan record JSON -> compact FHIR R4 Bundle JSON, or NULL on failure.
+ * Free the result with kfb_string_free. */
+char *kfb_transform_json(const char *record_json);
+
+/* Check a Kenyan record JSON against the input rules: KFB_OK or an error code. */
+int kfb_validate_json(const char *record_json);
+
+/* Code of the calling thread's last call (KFB_OK after a success). */
+int kfb_last_error(void);
+
+/* Message of the calling thread's last failure ("" after a success). Owned by
+ * the bridge and valid until the thread's next call; do not free. */
+const char *kfb_last_error_message(void);
+
+/* Release a string returned by kfb_transform_json. NULL is ignored. */
+void kfb_string_free(char *s);
+
+/* Bridge version, e.g. "0.1.0"; static, do not free. */
+const char *kfb_version(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* KENYA_FHIR_BRIDGE_H */
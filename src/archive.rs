@@ -0,0 +1,450 @@
+use std::path::Path;
+
+use chrono::Utc;
+use fhir_parser::fhir::bundle::Bundle;
+use rusqlite::{params, Connection};
+use serde_json::Value;
+
+use crate::error::{BridgeError, Context, Result};
+use crate::migrations::{self, add_column_if_missing, Migration};
+
+/// Archive schema history. Append new migrations; never edit shipped ones.
+/// Version 1 uses `IF NOT EXISTS` because archives predate
+/// `schema_version`.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "archived_bundles table and full-text index",
+        apply: |conn| {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS archived_bundles (
+                    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                    bundle_id   TEXT NOT NULL UNIQUE,
+                    bundle_json TEXT NOT NULL,
+                    patient_id  TEXT NOT NULL,
+                    facility    TEXT NOT NULL,
+                    archived_at TEXT NOT NULL
+                );
+                CREATE VIRTUAL TABLE IF NOT EXISTS archive_fts USING fts5(
+                    metadata, diagnosis, treatment, claims
+                );",
+            )
+        },
+    },
+    Migration {
+        version: 2,
+        description: "visits by patient number, date and diagnosis",
+        apply: |conn| {
+            add_column_if_missing(conn, "archived_bundles", "patient_number", "TEXT")?;
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS archived_visits (
+                    bundle_row INTEGER NOT NULL,
+                    visit_date TEXT NOT NULL,
+                    diagnosis  TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS idx_patient_number
+                    ON archived_bundles(patient_number);
+                CREATE INDEX IF NOT EXISTS idx_visit_bundle ON archived_visits(bundle_row);",
+            )?;
+            // Index the bundles archived before this version
+            let mut stmt = conn.prepare("SELECT id, bundle_json FROM archived_bundles")?;
+            let rows = stmt
+                .query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            for (row_id, json) in rows {
+                let Ok(bundle) = serde_json::from_str::<Bundle>(&json) else {
+                    continue;
+                };
+                index_visits(conn, row_id, &index_fields(&bundle))?;
+            }
+            Ok(())
+        },
+    },
+];
+
+/// SQLite archive of submitted bundles with a full-text index.
+///
+/// The FTS table indexes bundle metadata (bundle id, facility, visit dates)
+/// plus diagnosis text, treatment and claim numbers, so support staff can
+/// find one submission among thousands with `archive search --text`. Each
+/// visit is also filed under the clinic's patient number, so clinicians
+/// can pull up a patient's past submissions offline with `archive history`.
+pub struct BundleArchive {
+    conn: Connection,
+}
+
+/// Fields pulled out of a bundle for indexing.
+#[derive(Debug, Default)]
+struct IndexedFields {
+    patient_id: String,
+    /// The clinic's own patient number
+    patient_number: Option<String>,
+    facility: String,
+    /// Date and diagnosis of each Encounter
+    visits: Vec<(String, String)>,
+    metadata: Vec<String>,
+    diagnosis: Vec<String>,
+    treatment: Vec<String>,
+    claims: Vec<String>,
+}
+
+fn concept_terms(concept: &Value, out: &mut Vec<String>) {
+    if let Some(text) = concept.get("text").and_then(Value::as_str) {
+        out.push(text.to_string());
+    }
+    for coding in concept["coding"].as_array().into_iter().flatten() {
+        for key in ["code", "display"] {
+            if let Some(v) = coding.get(key).and_then(Value::as_str) {
+                out.push(v.to_string());
+            }
+        }
+    }
+}
+
+/// Display text of a coded concept: its text, else the first display.
+fn concept_display(concept: &Value) -> Option<&str> {
+    concept.get("text").and_then(Value::as_str).or_else(|| {
+        concept["coding"]
+            .as_array()?
+            .iter()
+            .find_map(|c| c.get("display").and_then(Value::as_str))
+    })
+}
+
+fn index_fields(bundle: &Bundle) -> IndexedFields {
+    let mut f = IndexedFields::default();
+    if let Some(id) = &bundle.id {
+        f.metadata.push(id.clone());
+    }
+    let resources: Vec<&Value> = bundle
+        .entry
+        .iter()
+        .flatten()
+        .filter_map(|e| e.resource.as_ref())
+        .collect();
+    for res in &resources {
+        let id = res.get("id").and_then(Value::as_str).unwrap_or_default();
+        match res.get("resourceType").and_then(Value::as_str) {
+            Some("Patient") => {
+                f.patient_id = id.to_string();
+                f.patient_number = res["identifier"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .find(|i| {
+                        i["system"]
+                            .as_str()
+                            .is_some_and(|s| s.ends_with("/patient-number"))
+                    })
+                    .and_then(|i| i["value"].as_str())
+                    .map(str::to_string);
+            }
+            Some("Organization") if id != "org-sha-payer" && f.facility.is_empty() => {
+                if let Some(code) = res["identifier"][0]["value"].as_str() {
+                    f.facility = code.to_string();
+                }
+            }
+            Some("Encounter") => {
+                if let Some(start) = res["period"]["start"].as_str() {
+                    f.metadata.push(start.to_string());
+                    // The visit's diagnosis is the Encounter's first
+                    let diagnosis = res["diagnosis"][0]["condition"]["reference"]
+                        .as_str()
+                        .and_then(|r| r.strip_prefix("Condition/"))
+                        .and_then(|cond_id| {
+                            resources
+                                .iter()
+                                .find(|r| r["resourceType"] == "Condition" && r["id"] == cond_id)
+                        })
+                        .and_then(|c| concept_display(&c["code"]))
+                        .unwrap_or_default();
+                    f.visits.push((
+                        start.get(..10).unwrap_or(start).to_string(),
+                        diagnosis.to_string(),
+                    ));
+                }
+            }
+            Some("Condition") => concept_terms(&res["code"], &mut f.diagnosis),
+            Some("MedicationRequest") => {
+                concept_terms(&res["medicationCodeableConcept"], &mut f.treatment);
+                for d in res["dosageInstruction"].as_array().into_iter().flatten() {
+                    if let Some(text) = d.get("text").and_then(Value::as_str) {
+                        f.treatment.push(text.to_string());
+                    }
+                }
+            }
+            Some("Claim") => {
+                f.claims.push(id.to_string());
+                for ident in res["identifier"].as_array().into_iter().flatten() {
+                    if let Some(v) = ident.get("value").and_then(Value::as_str) {
+                        f.claims.push(v.to_string());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if !f.facility.is_empty() {
+        f.metadata.push(f.facility.clone());
+    }
+    f
+}
+
+/// Turn free text into an FTS5 query: every word must match, a trailing `*`
+/// is a prefix search, and other FTS syntax in user input is taken literally.
+fn fts_query(text: &str) -> String {
+    text.split_whitespace()
+        .map(|w| match w.strip_suffix('*') {
+            Some(prefix) if !prefix.is_empty() => {
+                format!("\"{}\"*", prefix.replace('"', "\"\""))
+            }
+            _ => format!("\"{}\"", w.replace('"', "\"\"")),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn index_visits(conn: &Connection, row_id: i64, fields: &IndexedFields) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE archived_bundles SET patient_number = ?2 WHERE id = ?1",
+        params![row_id, fields.patient_number],
+    )?;
+    for (date, diagnosis) in &fields.visits {
+        conn.execute(
+            "INSERT INTO archived_visits (bundle_row, visit_date, diagnosis)
+             VALUES (?1, ?2, ?3)",
+            params![row_id, date, diagnosis],
+        )?;
+    }
+    Ok(())
+}
+
+impl BundleArchive {
+    /// Open (or create) the archive database at the given path.
+    pub fn open(db_path: &Path) -> Result<Self> {
+        let conn = Connection::open(db_path).with_context(BridgeError::Storage, || {
+            format!("Failed to open archive db at {:?}", db_path)
+        })?;
+        migrations::migrate(&conn, MIGRATIONS)
+            .context(BridgeError::Storage, "Failed to initialise archive schema")?;
+
+        Ok(Self { conn })
+    }
+
+    /// Archive a bundle and index it. Re-archiving the same bundle id replaces it.
+    pub fn store(&self, bundle: &Bundle) -> Result<i64> {
+        self.write(bundle, None)
+    }
+
+    /// Archive a corrected resubmission in place of the bundle archived as
+    /// `old_bundle_id`.
+    pub fn replace(&self, old_bundle_id: &str, bundle: &Bundle) -> Result<i64> {
+        self.write(bundle, Some(old_bundle_id))
+    }
+
+    fn write(&self, bundle: &Bundle, replaces: Option<&str>) -> Result<i64> {
+        let bundle_id = bundle
+            .id
+            .as_deref()
+            .context(BridgeError::Validation, "Bundle has no id")?;
+        let fields = index_fields(bundle);
+        let json = serde_json::to_string(bundle)?;
+        let now = Utc::now().to_rfc3339();
+
+        let tx = self.conn.unchecked_transaction()?;
+        for old in std::iter::once(bundle_id).chain(replaces) {
+            tx.execute(
+                "DELETE FROM archive_fts WHERE rowid IN
+                    (SELECT id FROM archived_bundles WHERE bundle_id = ?1)",
+                params![old],
+            )?;
+            tx.execute(
+                "DELETE FROM archived_visits WHERE bundle_row IN
+                    (SELECT id FROM archived_bundles WHERE bundle_id = ?1)",
+                params![old],
+            )?;
+            tx.execute(
+                "DELETE FROM archived_bundles WHERE bundle_id = ?1",
+                params![old],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO archived_bundles
+                (bundle_id, bundle_json, patient_id, facility, archived_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![bundle_id, json, fields.patient_id, fields.facility, now],
+        )?;
+        let row_id = tx.last_insert_rowid();
+        index_visits(&tx, row_id, &fields)?;
+        tx.execute(
+            "INSERT INTO archive_fts (rowid, metadata, diagnosis, treatment, claims)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                row_id,
+                fields.metadata.join(" "),
+                fields.diagnosis.join(" "),
+                fields.treatment.join(" "),
+                fields.claims.join(" "),
+            ],
+        )?;
+        tx.commit()
+            .context(BridgeError::Storage, "Failed to archive bundle")?;
+        Ok(row_id)
+    }
+
+    /// Archived bundles whose JSON contains `text` verbatim (not the
+    /// full-text index, which splits identifiers into words).
+    pub fn containing(&self, text: &str) -> Result<Vec<ArchivedBundle>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT bundle_json, patient_id, facility FROM archived_bundles
+             WHERE instr(bundle_json, ?1) > 0
+             ORDER BY id",
+        )?;
+        let rows = stmt.query_map(params![text], |row| {
+            Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?))
+        })?;
+        let mut found = Vec::new();
+        for row in rows {
+            let (json, patient_id, facility) =
+                row.context(BridgeError::Storage, "Failed to read archive")?;
+            found.push(ArchivedBundle {
+                bundle: serde_json::from_str(&json)
+                    .context(BridgeError::Storage, "Corrupt archived bundle")?,
+                patient_id,
+                facility,
+            });
+        }
+        Ok(found)
+    }
+
+    /// Full-text search, best matches first.
+    pub fn search(&self, text: &str, limit: usize) -> Result<Vec<ArchiveHit>> {
+        let query = fts_query(text);
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut stmt = self.conn.prepare(
+            "SELECT b.id, b.bundle_id, b.patient_id, b.facility, b.archived_at,
+                    snippet(archive_fts, -1, '[', ']', '…', 8)
+             FROM archive_fts
+             JOIN archived_bundles b ON b.id = archive_fts.rowid
+             WHERE archive_fts MATCH ?1
+             ORDER BY rank
+             LIMIT ?2",
+        )?;
+
+        let rows = stmt.query_map(params![query, limit as i64], |row| {
+            Ok(ArchiveHit {
+                row_id: row.get(0)?,
+                bundle_id: row.get(1)?,
+                patient_id: row.get(2)?,
+                facility: row.get(3)?,
+                archived_at: row.get(4)?,
+                snippet: row.get(5)?,
+            })
+        })?;
+
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .context(BridgeError::Storage, "Failed to search archive")
+    }
+
+    /// Archived visits of the patient with the clinic patient number
+    /// `patient_number` (at `facility` only, when given), latest first.
+    pub fn history(
+        &self,
+        patient_number: &str,
+        facility: Option<&str>,
+    ) -> Result<Vec<ArchivedVisit>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT v.visit_date, v.diagnosis, b.bundle_id, b.facility, b.archived_at
+             FROM archived_visits v
+             JOIN archived_bundles b ON b.id = v.bundle_row
+             WHERE b.patient_number = ?1 AND (?2 IS NULL OR b.facility = ?2)
+             ORDER BY v.visit_date DESC, b.id DESC",
+        )?;
+        let rows = stmt.query_map(params![patient_number, facility], |row| {
+            Ok(ArchivedVisit {
+                visit_date: row.get(0)?,
+                diagnosis: row.get(1)?,
+                bundle_id: row.get(2)?,
+                facility: row.get(3)?,
+                archived_at: row.get(4)?,
+            })
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .context(BridgeError::Storage, "Failed to read patient history")
+    }
+}
+
+/// One visit in an archived bundle.
+#[derive(Debug)]
+pub struct ArchivedVisit {
+    /// YYYY-MM-DD
+    pub visit_date: String,
+    pub diagnosis: String,
+    pub bundle_id: String,
+    pub facility: String,
+    pub archived_at: String,
+}
+
+/// A bundle as archived, with the columns it is filed under.
+#[derive(Debug)]
+pub struct ArchivedBundle {
+    pub bundle: Bundle,
+    pub patient_id: String,
+    pub facility: String,
+}
+
+#[derive(Debug)]
+pub struct ArchiveHit {
+    pub row_id: i64,
+    pub bundle_id: String,
+    pub patient_id: String,
+    pub facility: String,
+    pub archived_at: String,
+    /// Matching text with hits in `[brackets]`
+    pub snippet: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn archives_from_before_migrations_get_their_visits_indexed() {
+        let f = NamedTempFile::new().unwrap();
+        let bundle = include_str!("../tests/golden/kenyan_patient_1.json");
+        {
+            let conn = Connection::open(f.path()).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE archived_bundles (
+                    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                    bundle_id   TEXT NOT NULL UNIQUE,
+                    bundle_json TEXT NOT NULL,
+                    patient_id  TEXT NOT NULL,
+                    facility    TEXT NOT NULL,
+                    archived_at TEXT NOT NULL
+                );
+                CREATE VIRTUAL TABLE archive_fts USING fts5(
+                    metadata, diagnosis, treatment, claims
+                );",
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO archived_bundles
+                    (bundle_id, bundle_json, patient_id, facility, archived_at)
+                 VALUES ('b1', ?1, 'p1', 'KEN-NAIROBI-001', '2026-02-15T10:00:00Z')",
+                params![bundle],
+            )
+            .unwrap();
+        }
+
+        let archive = BundleArchive::open(f.path()).unwrap();
+        let visits = archive.history("12345", None).unwrap();
+        assert_eq!(visits.len(), 1);
+        assert_eq!(visits[0].visit_date, "2026-02-15");
+        assert_eq!(visits[0].diagnosis, "Upper respiratory tract infection");
+        assert_eq!(visits[0].bundle_id, "b1");
+    }
+}
@@ -0,0 +1,144 @@
+//! Progress manifest for long batch runs.
+//!
+//! A backfill of thousands of records at a rural site is often cut short by
+//! a power cut. The manifest is a JSON Lines file with one entry per input
+//! file or record and its outcome, appended and synced to disk as each one
+//! finishes. A run opened with `resume` skips the entries already done, so
+//! it neither redoes them nor writes their bundles a second time; failed
+//! entries get another go. A line cut short by the crash is ignored.
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{BridgeError, Context, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryStatus {
+    Ok,
+    Failed,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ManifestEntry {
+    /// Input file path or record key, as the run names it
+    pub key: String,
+    pub status: EntryStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+pub struct BatchManifest {
+    file: File,
+    done: HashSet<String>,
+}
+
+impl BatchManifest {
+    /// Open the manifest at `path`. With `resume` the entries of earlier
+    /// runs are kept and later ones appended; without, it starts empty.
+    pub fn open(path: &Path, resume: bool) -> Result<Self> {
+        let mut done = HashSet::new();
+        let mut torn = false;
+        if resume && path.exists() {
+            let raw = std::fs::read(path).with_context(BridgeError::Io, || {
+                format!("Failed to read manifest {:?}", path)
+            })?;
+            torn = raw.last().is_some_and(|b| *b != b'\n');
+            // The last outcome of a key counts
+            for entry in String::from_utf8_lossy(&raw)
+                .lines()
+                .filter_map(|line| serde_json::from_str::<ManifestEntry>(line).ok())
+            {
+                match entry.status {
+                    EntryStatus::Ok => done.insert(entry.key),
+                    EntryStatus::Failed => done.remove(&entry.key),
+                };
+            }
+        }
+        let mut options = OpenOptions::new();
+        if resume {
+            options.append(true).create(true);
+        } else {
+            options.write(true).create(true).truncate(true);
+        }
+        let mut file = options.open(path).with_context(BridgeError::Io, || {
+            format!("Failed to open manifest {:?}", path)
+        })?;
+        // New entries go on a line of their own, not onto the torn one
+        if torn {
+            file.write_all(b"\n")
+                .context(BridgeError::Io, "Failed to write manifest")?;
+        }
+        Ok(Self { file, done })
+    }
+
+    /// Entries already done in an earlier run.
+    pub fn done_count(&self) -> usize {
+        self.done.len()
+    }
+
+    pub fn is_done(&self, key: &str) -> bool {
+        self.done.contains(key)
+    }
+
+    /// Append the outcome of `key` and sync it to disk.
+    pub fn record(&mut self, key: &str, status: EntryStatus, detail: Option<&str>) -> Result<()> {
+        let entry = ManifestEntry {
+            key: key.to_string(),
+            status,
+            detail: detail.map(str::to_string),
+            at: Utc::now(),
+        };
+        let mut line = serde_json::to_string(&entry)
+            .context(BridgeError::Io, "Failed to serialise manifest entry")?;
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|_| self.file.sync_data())
+            .context(BridgeError::Io, "Failed to write manifest")?;
+        match status {
+            EntryStatus::Ok => self.done.insert(entry.key),
+            EntryStatus::Failed => self.done.remove(&entry.key),
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_skips_done_entries_and_retries_failed_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("manifest.jsonl");
+        let mut first = BatchManifest::open(&path, false).unwrap();
+        first.record("a.json", EntryStatus::Ok, None).unwrap();
+        first
+            .record("b.json", EntryStatus::Failed, Some("no diagnosis"))
+            .unwrap();
+        drop(first);
+        // Power cut halfway through the next line
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"key":"c.json","sta"#).unwrap();
+        drop(file);
+
+        let mut resumed = BatchManifest::open(&path, true).unwrap();
+        assert_eq!(resumed.done_count(), 1);
+        assert!(resumed.is_done("a.json"));
+        assert!(!resumed.is_done("b.json") && !resumed.is_done("c.json"));
+        resumed.record("b.json", EntryStatus::Ok, None).unwrap();
+        drop(resumed);
+        let reread = BatchManifest::open(&path, true).unwrap();
+        assert!(reread.is_done("a.json") && reread.is_done("b.json"));
+
+        let fresh = BatchManifest::open(&path, false).unwrap();
+        assert_eq!(fresh.done_count(), 0);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+    }
+}
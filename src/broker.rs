@@ -0,0 +1,227 @@
+//! Publishing bundles to a message broker (`broker` feature).
+//!
+//! The national data warehouse consumes bundle events from Kafka or
+//! RabbitMQ rather than a FHIR endpoint. Like every other network call in
+//! the bridge these go through curl, to the brokers' HTTP front doors:
+//! the Confluent REST Proxy for Kafka and the management API's publish
+//! endpoint for RabbitMQ. That keeps librdkafka and an async AMQP client
+//! out of the facility build. Messages are keyed by the patient's CR ID so
+//! one patient's bundles land on one partition, in order.
+//!
+//! Credentials, when the broker wants them, come from `BROKER_USERNAME` /
+//! `BROKER_PASSWORD`.
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::http::{self, url_encode, HttpRequest};
+use crate::systems::CR_SYSTEM;
+use crate::transport::{BundleTransport, DeliveryResult};
+
+const TIMEOUT_SECS: u32 = 60;
+
+/// The patient's CR ID in a bundle, else `fallback` (its Bundle.id).
+pub fn message_key(json: &[u8], fallback: &str) -> String {
+    serde_json::from_slice::<Value>(json)
+        .ok()
+        .and_then(|bundle| {
+            bundle["entry"]
+                .as_array()?
+                .iter()
+                .map(|e| &e["resource"])
+                .filter(|r| r["resourceType"] == "Patient")
+                .flat_map(|p| p["identifier"].as_array().into_iter().flatten())
+                .find(|i| i["system"] == CR_SYSTEM)
+                .and_then(|i| i["value"].as_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+/// `Authorization` header from the environment, when set.
+fn auth_headers() -> Vec<String> {
+    match (
+        std::env::var("BROKER_USERNAME")
+            .ok()
+            .filter(|v| !v.is_empty()),
+        std::env::var("BROKER_PASSWORD").ok(),
+    ) {
+        (Some(user), password) => vec![format!(
+            "Authorization: Basic {}",
+            STANDARD.encode(format!("{}:{}", user, password.unwrap_or_default()))
+        )],
+        (None, _) => Vec::new(),
+    }
+}
+
+fn post_json(url: &str, content_type: &str, body: &Value) -> Result<http::HttpResponse> {
+    let body = serde_json::to_vec(body)?;
+    let mut headers = vec![
+        format!("Content-Type: {}", content_type),
+        "Accept: application/json".to_string(),
+    ];
+    headers.extend(auth_headers());
+    http::send(&HttpRequest {
+        method: "POST",
+        url,
+        headers,
+        body: Some(&body),
+        timeout_secs: TIMEOUT_SECS,
+        tls: None,
+    })
+}
+
+/// Kafka topic, through a Confluent REST Proxy (v2 API).
+pub struct KafkaTransport {
+    /// REST Proxy base, e.g. `https://kafka-rest.dha.go.ke`
+    rest_url: String,
+    topic: String,
+}
+
+impl KafkaTransport {
+    pub fn new(rest_url: &str, topic: &str) -> Self {
+        Self {
+            rest_url: rest_url.trim_end_matches('/').to_string(),
+            topic: topic.to_string(),
+        }
+    }
+}
+
+impl BundleTransport for KafkaTransport {
+    fn describe(&self) -> String {
+        format!("Kafka topic {} ({})", self.topic, self.rest_url)
+    }
+
+    fn send(&self, bundle_id: &str, json: &[u8]) -> Result<DeliveryResult> {
+        let bundle: Value =
+            serde_json::from_slice(json).context(BridgeError::Validation, "Bundle is not JSON")?;
+        let url = format!("{}/topics/{}", self.rest_url, url_encode(&self.topic));
+        let response = post_json(
+            &url,
+            "application/vnd.kafka.json.v2+json",
+            &json!({ "records": [{ "key": message_key(json, bundle_id), "value": bundle }] }),
+        )?;
+        if !response.is_success() {
+            return Ok(DeliveryResult::Rejected {
+                reason: format!("Kafka REST Proxy returned HTTP {}", response.status),
+            });
+        }
+        // Per-record errors come back with a 200
+        let reply: Value = serde_json::from_str(&response.body)
+            .context(BridgeError::Network, "Invalid Kafka REST Proxy reply")?;
+        let offset = &reply["offsets"][0];
+        if let Some(error) = offset["error"].as_str() {
+            return Ok(DeliveryResult::Rejected {
+                reason: format!("Kafka rejected the record: {}", error),
+            });
+        }
+        Ok(DeliveryResult::Delivered {
+            receipt: format!(
+                "partition {} offset {}",
+                offset["partition"], offset["offset"]
+            ),
+        })
+    }
+}
+
+/// RabbitMQ exchange, through the management API's publish endpoint.
+/// Messages are persistent, and one that no bound queue receives counts as
+/// rejected.
+pub struct AmqpTransport {
+    /// Management API base, e.g. `https://rabbit.dha.go.ke:15672`
+    management_url: String,
+    vhost: String,
+    exchange: String,
+}
+
+impl AmqpTransport {
+    pub fn new(management_url: &str, vhost: &str, exchange: &str) -> Result<Self> {
+        if exchange.is_empty() {
+            bail!(
+                Config,
+                "AMQP transport needs a named exchange (the default exchange cannot be published to over HTTP)"
+            );
+        }
+        Ok(Self {
+            management_url: management_url.trim_end_matches('/').to_string(),
+            vhost: vhost.to_string(),
+            exchange: exchange.to_string(),
+        })
+    }
+}
+
+impl BundleTransport for AmqpTransport {
+    fn describe(&self) -> String {
+        format!(
+            "RabbitMQ exchange {} on vhost {} ({})",
+            self.exchange, self.vhost, self.management_url
+        )
+    }
+
+    fn send(&self, bundle_id: &str, json: &[u8]) -> Result<DeliveryResult> {
+        let payload =
+            std::str::from_utf8(json).context(BridgeError::Validation, "Bundle is not UTF-8")?;
+        let url = format!(
+            "{}/api/exchanges/{}/{}/publish",
+            self.management_url,
+            url_encode(&self.vhost),
+            url_encode(&self.exchange)
+        );
+        let response = post_json(
+            &url,
+            "application/json",
+            &json!({
+                "properties": {
+                    "content_type": "application/fhir+json",
+                    "delivery_mode": 2,
+                    "message_id": bundle_id,
+                },
+                "routing_key": message_key(json, bundle_id),
+                "payload": payload,
+                "payload_encoding": "string",
+            }),
+        )?;
+        if !response.is_success() {
+            return Ok(DeliveryResult::Rejected {
+                reason: format!("RabbitMQ returned HTTP {}", response.status),
+            });
+        }
+        let reply: Value = serde_json::from_str(&response.body)
+            .context(BridgeError::Network, "Invalid RabbitMQ reply")?;
+        Ok(if reply["routed"] == true {
+            DeliveryResult::Delivered {
+                receipt: format!("routed by {}", self.exchange),
+            }
+        } else {
+            DeliveryResult::Rejected {
+                reason: format!("Exchange {} has no queue for the message", self.exchange),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_keyed_by_cr_id() {
+        let bundle = json!({
+            "resourceType": "Bundle",
+            "entry": [
+                { "resource": { "resourceType": "Organization", "id": "org-1" } },
+                { "resource": {
+                    "resourceType": "Patient",
+                    "identifier": [
+                        { "system": "http://moh.go.ke/fhir/national-id", "value": "23456789" },
+                        { "system": CR_SYSTEM, "value": "CR-0001" },
+                    ],
+                } },
+            ],
+        });
+        let json = serde_json::to_vec(&bundle).unwrap();
+        assert_eq!(message_key(&json, "b1"), "CR-0001");
+        assert_eq!(message_key(b"{}", "b1"), "b1");
+    }
+}
@@ -0,0 +1,105 @@
+//! FHIR Bulk Data export of generated bundles.
+//!
+//! County analytics pipelines load the Bulk Data `$export` output format:
+//! one `{resourceType}.ndjson` file per resource type, one resource per
+//! line. Bundles share resources (the facility Organization, the
+//! Practitioner, the patient across visits), so each resource is written
+//! once, as the last bundle holding it has it.
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use fhir_parser::fhir::bundle::Bundle;
+use serde_json::Value;
+
+use crate::error::{BridgeError, Context, Result};
+
+/// Resources of `bundles` grouped by type, each resource once, in the
+/// order first seen.
+pub fn group_resources(bundles: &[Bundle]) -> BTreeMap<String, Vec<Value>> {
+    let mut groups: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    let mut seen: HashMap<(String, String), usize> = HashMap::new();
+    for resource in bundles
+        .iter()
+        .flat_map(|b| b.entry.iter().flatten())
+        .filter_map(|e| e.resource.as_ref())
+    {
+        let Some(resource_type) = resource.get("resourceType").and_then(Value::as_str) else {
+            continue;
+        };
+        let group = groups.entry(resource_type.to_string()).or_default();
+        match resource.get("id").and_then(Value::as_str) {
+            Some(id) => match seen.entry((resource_type.to_string(), id.to_string())) {
+                Entry::Occupied(at) => {
+                    group[*at.get()] = resource.clone();
+                }
+                Entry::Vacant(slot) => {
+                    slot.insert(group.len());
+                    group.push(resource.clone());
+                }
+            },
+            None => group.push(resource.clone()),
+        }
+    }
+    groups
+}
+
+/// Write `{resourceType}.ndjson` files for `bundles` into `dir`, creating
+/// it if needed. Returns each file written and how many resources it holds.
+pub fn write_ndjson(bundles: &[Bundle], dir: &Path) -> Result<Vec<(PathBuf, usize)>> {
+    std::fs::create_dir_all(dir)
+        .with_context(BridgeError::Io, || format!("Failed to create {:?}", dir))?;
+    let mut written = Vec::new();
+    for (resource_type, resources) in group_resources(bundles) {
+        let mut ndjson = String::new();
+        for resource in &resources {
+            ndjson.push_str(&resource.to_string());
+            ndjson.push('\n');
+        }
+        let path = dir.join(format!("{}.ndjson", resource_type));
+        std::fs::write(&path, ndjson)
+            .with_context(BridgeError::Io, || format!("Failed to write {:?}", path))?;
+        written.push((path, resources.len()));
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn bundle(resources: Vec<Value>) -> Bundle {
+        serde_json::from_value(json!({
+            "resourceType": "Bundle",
+            "type": "transaction",
+            "entry": resources
+                .into_iter()
+                .map(|r| json!({ "resource": r }))
+                .collect::<Vec<_>>(),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn shared_resources_are_written_once_as_last_seen() {
+        let first = bundle(vec![
+            json!({ "resourceType": "Organization", "id": "org-1", "name": "Old name" }),
+            json!({ "resourceType": "Encounter", "id": "enc-1" }),
+        ]);
+        let second = bundle(vec![
+            json!({ "resourceType": "Organization", "id": "org-1", "name": "New name" }),
+            json!({ "resourceType": "Encounter", "id": "enc-2" }),
+        ]);
+
+        let groups = group_resources(&[first, second]);
+        assert_eq!(
+            groups.keys().collect::<Vec<_>>(),
+            ["Encounter", "Organization"]
+        );
+        assert_eq!(groups["Organization"].len(), 1);
+        assert_eq!(groups["Organization"][0]["name"], "New name");
+        let encounters: Vec<_> = groups["Encounter"].iter().map(|r| &r["id"]).collect();
+        assert_eq!(encounters, ["enc-1", "enc-2"]);
+    }
+}
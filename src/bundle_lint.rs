@@ -0,0 +1,233 @@
+//! Structural checks on generated Bundles, independent of any FHIR server.
+//!
+//! Covers the R4 invariants this bridge depends on (see GUIDELINES.md):
+//! every transaction entry has a fullUrl + request, fullUrls are unique,
+//! references resolve inside the bundle, and each resource carries the
+//! elements its resource type requires.
+use std::collections::HashSet;
+use std::fmt;
+
+use fhir_parser::fhir::bundle::Bundle;
+use fhir_parser::masking::{mask_identifier, mask_reference};
+use fhir_parser::references::check_references;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintSeverity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct LintIssue {
+    pub severity: LintSeverity,
+    /// Where the problem is, e.g. `entry[3].resource.subject`
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sev = match self.severity {
+            LintSeverity::Error => "error",
+            LintSeverity::Warning => "warning",
+        };
+        write!(f, "{} {}: {}", sev, self.location, self.message)
+    }
+}
+
+/// Required top-level elements per resource type (R4 min cardinality ≥ 1,
+/// plus what AfyaLink/SHA reject without). `[x]` matches any choice type.
+fn required_elements(resource_type: &str) -> &'static [&'static str] {
+    match resource_type {
+        "Patient" => &["identifier"],
+        "Encounter" => &["status", "class", "subject"],
+        "Observation" => &["status", "code", "subject"],
+        "Condition" => &["code", "subject"],
+        "MedicationRequest" => &["status", "intent", "medication[x]", "subject"],
+        "Coverage" => &["status", "beneficiary", "payor"],
+        "DocumentReference" => &["status", "content"],
+        "EpisodeOfCare" => &["status", "patient"],
+        "CarePlan" => &["status", "intent", "subject"],
+        "ServiceRequest" => &["status", "intent", "subject"],
+        "DiagnosticReport" => &["status", "code"],
+        "ClinicalImpression" => &["status", "subject"],
+        "Flag" => &["status", "code", "subject"],
+        "Claim" => &[
+            "status",
+            "type",
+            "use",
+            "patient",
+            "created",
+            "provider",
+            "priority",
+            "insurance",
+        ],
+        "Organization" | "Practitioner" => &["identifier"],
+        _ => &[],
+    }
+}
+
+fn has_element(resource: &Value, element: &str) -> bool {
+    let Some(obj) = resource.as_object() else {
+        return false;
+    };
+    match element.strip_suffix("[x]") {
+        Some(prefix) => obj
+            .keys()
+            .any(|k| k.starts_with(prefix) && k.len() > prefix.len()),
+        None => obj.get(element).is_some_and(|v| !v.is_null()),
+    }
+}
+
+/// Lint a Bundle. An empty result means the bundle passed every check.
+pub fn lint_bundle(bundle: &Bundle) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+    let mut warnings = Vec::new();
+    let mut error = |location: String, message: String| {
+        issues.push(LintIssue {
+            severity: LintSeverity::Error,
+            location,
+            message,
+        })
+    };
+
+    if bundle.resource_type != "Bundle" {
+        error("Bundle".into(), "resourceType must be \"Bundle\"".into());
+    }
+    let is_transaction = bundle.bundle_type.as_deref() == Some("transaction");
+    if bundle.bundle_type.is_none() {
+        error("Bundle.type".into(), "Bundle.type is required".into());
+    }
+
+    let entries = bundle.entry.as_deref().unwrap_or_default();
+
+    let mut full_urls: HashSet<&str> = HashSet::new();
+    for (i, entry) in entries.iter().enumerate() {
+        if let Some(url) = entry.full_url.as_deref() {
+            if !full_urls.insert(url) {
+                error(format!("entry[{}].fullUrl", i), "Duplicate fullUrl".into());
+            }
+        }
+    }
+
+    for (i, entry) in entries.iter().enumerate() {
+        let loc = format!("entry[{}]", i);
+
+        if entry.full_url.is_none() {
+            error(
+                format!("{}.fullUrl", loc),
+                "Every entry must have a fullUrl".into(),
+            );
+        }
+        if is_transaction && entry.request.is_none() {
+            error(
+                format!("{}.request", loc),
+                "Transaction entries must have request.method + request.url".into(),
+            );
+        }
+
+        let Some(resource) = &entry.resource else {
+            error(format!("{}.resource", loc), "Entry has no resource".into());
+            continue;
+        };
+        let Some(resource_type) = resource.get("resourceType").and_then(Value::as_str) else {
+            error(
+                format!("{}.resource", loc),
+                "Resource has no resourceType".into(),
+            );
+            continue;
+        };
+
+        if let (Some(req), Some(id)) = (&entry.request, resource.get("id").and_then(Value::as_str))
+        {
+            if req.method == "PUT" && req.url != format!("{}/{}", resource_type, id) {
+                error(
+                    format!("{}.request.url", loc),
+                    format!(
+                        "PUT url should be {}/{}",
+                        resource_type,
+                        mask_identifier(id)
+                    ),
+                );
+            }
+        }
+
+        for element in required_elements(resource_type) {
+            if !has_element(resource, element) {
+                error(
+                    format!("{}.resource.{}", loc, element),
+                    format!("{}.{} is required", resource_type, element),
+                );
+            }
+        }
+        if resource_type == "Claim" && !has_element(resource, "total") {
+            warnings.push(LintIssue {
+                severity: LintSeverity::Warning,
+                location: format!("{}.resource.total", loc),
+                message: "Claim has no total; SHA adjudication rejects claims without amounts"
+                    .into(),
+            });
+        }
+    }
+
+    for dangling in check_references(bundle) {
+        error(
+            dangling.location,
+            format!(
+                "Reference to {} does not resolve within the bundle",
+                mask_reference(&dangling.reference)
+            ),
+        );
+    }
+
+    issues.extend(warnings);
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fhir_parser::fhir::bundle::{BundleEntry, BundleRequest};
+    use serde_json::json;
+
+    fn entry(resource: Value) -> BundleEntry {
+        let rt = resource["resourceType"].as_str().unwrap().to_string();
+        let id = resource["id"].as_str().unwrap().to_string();
+        BundleEntry {
+            full_url: Some(format!("urn:uuid:{}", id)),
+            resource: Some(resource),
+            request: Some(BundleRequest {
+                method: "PUT".into(),
+                url: format!("{}/{}", rt, id),
+            }),
+        }
+    }
+
+    #[test]
+    fn flags_dangling_reference_and_missing_elements() {
+        let bundle = Bundle {
+            resource_type: "Bundle".into(),
+            id: None,
+            meta: None,
+            timestamp: None,
+            bundle_type: Some("transaction".into()),
+            entry: Some(vec![entry(json!({
+                "resourceType": "Encounter",
+                "id": "enc-1",
+                "status": "finished",
+                "subject": {"reference": "Patient/missing"}
+            }))]),
+            signature: None,
+            extra: Default::default(),
+        };
+        let issues = lint_bundle(&bundle);
+        let messages: Vec<String> = issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(issues.len(), 2, "{:?}", messages);
+        assert!(messages
+            .iter()
+            .any(|m| m.contains("Encounter.class is required")));
+        // Reference ids are masked in lint output by default
+        assert!(messages.iter().any(|m| m.contains("Patient/****sing")));
+    }
+}
@@ -0,0 +1,148 @@
+//! Circuit breaker for registry lookups.
+//!
+//! At a site that has lost its connection every live CR lookup waits out
+//! curl's 5-second timeout before the synthetic fallback. After `threshold`
+//! consecutive failures the breaker opens and lookups go straight to the
+//! fallback for `cool_down`; the first lookup after that is a trial, which
+//! closes the breaker on success and reopens it on failure.
+//!
+//! State lives in memory, shared by clones, so one process (a batch import,
+//! the pipeline) sees every failure. Runs that transform one record per
+//! process share it through a state file instead.
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    /// Registry name for log lines, e.g. `CR`
+    name: String,
+    pub threshold: u32,
+    pub cool_down: Duration,
+    state_file: Option<PathBuf>,
+    state: Arc<Mutex<BreakerState>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct BreakerState {
+    /// Consecutive failed calls
+    failures: u32,
+    open_until: Option<DateTime<Utc>>,
+}
+
+impl CircuitBreaker {
+    /// Opens after 3 failures in a row, for 5 minutes.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            threshold: 3,
+            cool_down: Duration::from_secs(300),
+            state_file: None,
+            state: Arc::default(),
+        }
+    }
+
+    /// Keep the state in `path` (JSON), read before and written after each
+    /// call. An unreadable file counts as a closed breaker.
+    pub fn with_state_file(mut self, path: &Path) -> Self {
+        self.state_file = Some(path.to_path_buf());
+        self
+    }
+
+    /// Whether a call may go out now.
+    pub fn allows(&self) -> bool {
+        self.load()
+            .open_until
+            .is_none_or(|until| Utc::now() >= until)
+    }
+
+    /// Count the outcome of a call that [`allows`](Self::allows) let through.
+    pub fn record(&self, success: bool) {
+        let mut state = self.load();
+        if success {
+            state = BreakerState::default();
+        } else {
+            state.failures += 1;
+            if state.failures >= self.threshold {
+                let cool_down = chrono::Duration::from_std(self.cool_down).unwrap_or_default();
+                state.open_until = Some(Utc::now() + cool_down);
+                eprintln!(
+                    "[{}] {} lookups failed in a row; no registry calls for {}s, using fallbacks",
+                    self.name,
+                    state.failures,
+                    self.cool_down.as_secs()
+                );
+            }
+        }
+        self.store(state);
+    }
+
+    fn load(&self) -> BreakerState {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(path) = &self.state_file {
+            *state = std::fs::read_to_string(path)
+                .ok()
+                .and_then(|raw| serde_json::from_str(&raw).ok())
+                .unwrap_or_default();
+        }
+        state.clone()
+    }
+
+    fn store(&self, new: BreakerState) {
+        if let Some(path) = &self.state_file {
+            // Best effort: without the file the breaker still works in memory
+            if let Ok(json) = serde_json::to_string(&new) {
+                let _ = std::fs::write(path, json);
+            }
+        }
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) = new;
+    }
+}
+
+impl Default for CircuitBreaker {
+    /// The Client Registry breaker.
+    fn default() -> Self {
+        Self::new("CR")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_threshold_failures_and_closes_on_success() {
+        let breaker = CircuitBreaker::new("CR");
+        for _ in 0..2 {
+            breaker.record(false);
+            assert!(breaker.allows());
+        }
+        breaker.record(false);
+        assert!(!breaker.allows());
+        // Clones share the state
+        assert!(!breaker.clone().allows());
+
+        let mut trial = breaker.clone();
+        trial.cool_down = Duration::ZERO;
+        trial.record(false);
+        assert!(trial.allows(), "cool-down over: the next call is a trial");
+        trial.record(true);
+        trial.record(false);
+        assert!(trial.allows(), "a success resets the count");
+    }
+
+    #[test]
+    fn state_file_is_shared_between_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cr-breaker.json");
+        let mut first = CircuitBreaker::new("CR").with_state_file(&path);
+        first.threshold = 1;
+        first.record(false);
+
+        let second = CircuitBreaker::new("CR").with_state_file(&path);
+        assert!(!second.allows());
+    }
+}
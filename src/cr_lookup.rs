@@ -1,5 +1,8 @@
+use chrono::{Datelike, NaiveDate};
 use uuid::Uuid;
 
+use crate::circuit_breaker::CircuitBreaker;
+
 /// Client Registry (CR) lookup result.
 ///
 /// The CR ID is the canonical patient identifier in AfyaLink — it takes the
@@ -9,6 +12,59 @@ pub struct CrLookupResult {
     pub cr_id: String,
     /// True if the ID was resolved from the live registry; false = synthetic fallback.
     pub live: bool,
+    /// Demographics of the CR patient, for a live lookup
+    pub registered: Option<CrDemographics>,
+}
+
+/// Name and date of birth the Client Registry holds for a patient, to check
+/// the CR matched the right person before adopting its ID.
+#[derive(Debug, Clone, Default)]
+pub struct CrDemographics {
+    pub family: Option<String>,
+    pub given: Vec<String>,
+    pub birth_date: Option<NaiveDate>,
+}
+
+impl CrDemographics {
+    /// What the CR holds differently from a record with these names and
+    /// date of birth: `"name"` when its family name or first given name is
+    /// none of `names`, allowing one typo and swapped names as the patient
+    /// matcher does; `"date of birth"` when the dates differ (only the year
+    /// when the record's date is estimated).
+    pub fn differences(
+        &self,
+        names: &[&str],
+        birth_date: NaiveDate,
+        estimated: bool,
+    ) -> Vec<&'static str> {
+        let names: Vec<String> = names.iter().map(|n| letters(n)).collect();
+        let known = |name: &String| {
+            let name = letters(name);
+            names
+                .iter()
+                .any(|n| !n.is_empty() && (*n == name || within_one_edit(n, &name)))
+        };
+        let mut differs = Vec::new();
+        if self
+            .family
+            .iter()
+            .chain(self.given.first())
+            .any(|n| !known(n))
+        {
+            differs.push("name");
+        }
+        if let Some(dob) = self.birth_date {
+            let same = if estimated {
+                dob.year() == birth_date.year()
+            } else {
+                dob == birth_date
+            };
+            if !same {
+                differs.push("date of birth");
+            }
+        }
+        differs
+    }
 }
 
 /// Attempt to resolve a Client Registry ID for the given national ID.
@@ -16,32 +72,53 @@ pub struct CrLookupResult {
 /// Strategy (offline-first):
 ///  1. Try the AfyaLink UAT endpoint (GET /v1/patient-search?identification_number={id}).
 ///     This requires a bearer token in AFYALINK_TOKEN env var and network connectivity.
+///     While `breaker` is open after repeated failures (site offline) the
+///     call is skipped.
 ///  2. On any failure (no token, network error, 404, timeout) fall back to a
 ///     **deterministic synthetic CR-ID** derived from the national ID using UUID v5.
 ///     This keeps the pipeline running offline while producing stable, reproducible IDs.
 ///
 /// The synthetic ID format mirrors the real format (`CR-{uuid-v5-suffix}`) so it
 /// is visually distinguishable and can be replaced in-place once connectivity
-/// is restored.
-pub fn resolve_cr_id(national_id: &str) -> CrLookupResult {
+/// is restored (see [`crate::cr_reconcile`]).
+pub fn resolve_cr_id(national_id: &str, breaker: &CircuitBreaker) -> CrLookupResult {
     // Try live lookup first (best-effort, fire-and-forget timeout)
-    if let Some(cr_id) = try_live_cr_lookup(national_id) {
-        return CrLookupResult { cr_id, live: true };
+    if let Some((cr_id, registered)) = live_cr_lookup(national_id, breaker) {
+        return CrLookupResult {
+            cr_id,
+            live: true,
+            registered: Some(registered),
+        };
     }
 
     // Offline fallback: deterministic UUID v5 from national ID
     let cr_id = synthetic_cr_id(national_id);
-    CrLookupResult { cr_id, live: false }
+    CrLookupResult {
+        cr_id,
+        live: false,
+        registered: None,
+    }
 }
 
-/// Attempt a live lookup against the AfyaLink UAT CR endpoint.
+/// Attempt a live lookup against the AfyaLink UAT CR endpoint: the CR ID and
+/// the demographics registered with it.
 /// Returns None on any error (missing token, network failure, non-200 response).
-fn try_live_cr_lookup(national_id: &str) -> Option<String> {
+#[cfg(feature = "network")]
+pub fn live_cr_lookup(
+    national_id: &str,
+    breaker: &CircuitBreaker,
+) -> Option<(String, CrDemographics)> {
     let token = std::env::var("AFYALINK_TOKEN").ok()?;
-    let base = std::env::var("AFYALINK_BASE_URL")
-        .unwrap_or_else(|_| "https://uat.dha.go.ke".to_string());
+    if !breaker.allows() {
+        return None;
+    }
+    let base =
+        std::env::var("AFYALINK_BASE_URL").unwrap_or_else(|_| "https://uat.dha.go.ke".to_string());
 
-    let url = format!("{}/v1/patient-search?identification_number={}", base, national_id);
+    let url = format!(
+        "{}/v1/patient-search?identification_number={}",
+        base, national_id
+    );
 
     // Use a blocking HTTP call with a short timeout via std::process (no reqwest dep needed)
     // We shell out to curl so we don't add a heavy async runtime dep to the CLI.
@@ -56,12 +133,13 @@ fn try_live_cr_lookup(national_id: &str) -> Option<String> {
             "Accept: application/fhir+json",
             &url,
         ])
-        .output()
-        .ok()?;
+        .output();
 
-    if !output.status.success() {
-        return None;
-    }
+    // Only a call that got no answer (curl error, timeout) counts as a
+    // failure; a search without a match is the registry working.
+    let answered = matches!(&output, Ok(o) if o.status.success());
+    breaker.record(answered);
+    let output = output.ok().filter(|_| answered)?;
 
     let body = String::from_utf8(output.stdout).ok()?;
     // Parse the CR ID from the response — the real endpoint returns a Bundle of
@@ -69,18 +147,74 @@ fn try_live_cr_lookup(national_id: &str) -> Option<String> {
     extract_cr_id_from_response(&body)
 }
 
-/// Extract a CR ID from an AfyaLink patient-search Bundle response.
-fn extract_cr_id_from_response(json: &str) -> Option<String> {
+/// Built without `network`: always the synthetic fallback.
+#[cfg(not(feature = "network"))]
+pub fn live_cr_lookup(
+    _national_id: &str,
+    _breaker: &CircuitBreaker,
+) -> Option<(String, CrDemographics)> {
+    None
+}
+
+/// Extract a CR ID, and the demographics it is registered with, from an
+/// AfyaLink patient-search Bundle response.
+#[cfg(feature = "network")]
+fn extract_cr_id_from_response(json: &str) -> Option<(String, CrDemographics)> {
     let v: serde_json::Value = serde_json::from_str(json).ok()?;
     // Expect a Bundle; take the first entry's resource.id
     let entry = v.get("entry")?.as_array()?.first()?;
     let resource = entry.get("resource")?;
     let id = resource.get("id")?.as_str()?;
-    if id.starts_with("CR-") {
-        Some(id.to_string())
+    let cr_id = if id.starts_with("CR-") {
+        id.to_string()
     } else {
         // Wrap bare IDs in CR- prefix for consistency
-        Some(format!("CR-{}", id))
+        format!("CR-{}", id)
+    };
+    let name = &resource["name"][0];
+    let registered = CrDemographics {
+        family: name["family"].as_str().map(str::to_string),
+        given: name["given"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|g| g.as_str().map(str::to_string))
+            .collect(),
+        birth_date: resource["birthDate"]
+            .as_str()
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()),
+    };
+    Some((cr_id, registered))
+}
+
+/// Letters of a name, lower-cased: spacing, punctuation and digits dropped.
+pub(crate) fn letters(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_alphabetic())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Levenshtein distance ≤ 1.
+pub(crate) fn within_one_edit(a: &str, b: &str) -> bool {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let (short, long) = if a.len() <= b.len() {
+        (&a, &b)
+    } else {
+        (&b, &a)
+    };
+    if long.len() - short.len() > 1 {
+        return false;
+    }
+    let prefix = short
+        .iter()
+        .zip(long.iter())
+        .take_while(|(x, y)| x == y)
+        .count();
+    if short.len() == long.len() {
+        short[prefix + 1..] == long[prefix + 1..]
+    } else {
+        short[prefix..] == long[prefix + 1..]
     }
 }
 
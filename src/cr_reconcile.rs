@@ -0,0 +1,198 @@
+//! Replacing synthetic CR IDs once the Client Registry is reachable.
+//!
+//! Offline, a record gets a `CR-SYNTH-` ID (see
+//! [`resolve_cr_id`](crate::cr_lookup::resolve_cr_id)) meant to be swapped
+//! for the registry's when the connection returns. [`reconcile`] looks each
+//! one up again. Pending queue rows are rewritten in place. Archived bundles
+//! have already gone out, so they are archived again under a new Bundle.id
+//! and queued for resubmission, which updates the Patient on the SHR. A CR
+//! patient whose name or date of birth differs from the bundle's keeps the
+//! synthetic ID, as at transform time.
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use fhir_parser::fhir::bundle::Bundle;
+use fhir_parser::masking::mask_identifier;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::archive::BundleArchive;
+use crate::cr_lookup::CrDemographics;
+use crate::error::{BridgeError, Context, Result};
+use crate::offline_queue::OfflineQueue;
+use crate::systems::{BIRTH_DATE_ESTIMATED_URL, CR_SYSTEM, NATIONAL_ID_SYSTEM};
+
+const SYNTHETIC_PREFIX: &str = "CR-SYNTH-";
+
+/// Outcome of a [`reconcile`] run.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReconcileSummary {
+    /// Pending queue rows rewritten in place
+    pub queued: usize,
+    /// Archived bundles rewritten and queued again
+    pub resubmitted: usize,
+    /// Bundles still holding a synthetic ID: not in the registry, no
+    /// answer, or registered with other demographics
+    pub unresolved: usize,
+}
+
+/// Replace synthetic CR IDs in the pending rows of `queue` and, when given,
+/// the bundles in `archive`. `lookup` resolves a national ID to the CR ID
+/// and registered demographics; it is asked once per national ID.
+pub fn reconcile<F>(
+    queue: &OfflineQueue,
+    archive: Option<&BundleArchive>,
+    mut lookup: F,
+) -> Result<ReconcileSummary>
+where
+    F: FnMut(&str) -> Option<(String, CrDemographics)>,
+{
+    let mut found: HashMap<String, Option<(String, CrDemographics)>> = HashMap::new();
+    let mut lookup = |national_id: &str| {
+        found
+            .entry(national_id.to_string())
+            .or_insert_with(|| lookup(national_id))
+            .clone()
+    };
+    let mut summary = ReconcileSummary::default();
+
+    // Bundle id → corrected bundle, for pending rows that are also archived
+    let mut pending: HashMap<String, Option<Bundle>> = HashMap::new();
+    for row in queue.pending_within_window()? {
+        if !row.bundle_json.contains(SYNTHETIC_PREFIX) {
+            continue;
+        }
+        let mut bundle: Bundle = serde_json::from_str(&row.bundle_json)
+            .with_context(BridgeError::Queue, || {
+                format!("Queue row {} is not a bundle", row.row_id)
+            })?;
+        let (replaced, left) = replace_synthetic_cr_ids(&mut bundle, &mut lookup);
+        if left > 0 {
+            summary.unresolved += 1;
+        }
+        let corrected = if replaced > 0 {
+            queue.replace_pending(row.row_id, &serde_json::to_string(&bundle)?)?;
+            summary.queued += 1;
+            Some(bundle)
+        } else {
+            None
+        };
+        pending.insert(row.bundle_id, corrected);
+    }
+
+    let Some(archive) = archive else {
+        return Ok(summary);
+    };
+    for archived in archive.containing(SYNTHETIC_PREFIX)? {
+        let mut bundle = archived.bundle;
+        let old_id = bundle.id.clone().unwrap_or_default();
+        // Still queued: the queue pass corrected it and it has not gone out
+        if let Some(corrected) = pending.get(&old_id) {
+            if let Some(corrected) = corrected {
+                archive.store(corrected)?;
+            }
+            continue;
+        }
+        let (replaced, left) = replace_synthetic_cr_ids(&mut bundle, &mut lookup);
+        if left > 0 {
+            summary.unresolved += 1;
+        }
+        if replaced == 0 {
+            continue;
+        }
+        let new_id = Uuid::new_v4().to_string();
+        bundle.id = Some(new_id.clone());
+        archive.replace(&old_id, &bundle)?;
+        queue.enqueue(
+            &new_id,
+            &serde_json::to_string(&bundle)?,
+            &archived.patient_id,
+            &archived.facility,
+        )?;
+        summary.resubmitted += 1;
+    }
+    Ok(summary)
+}
+
+/// Swap each Patient's `CR-SYNTH-` identifier for the CR ID `lookup` finds
+/// under its national ID. Returns how many were replaced and how many
+/// synthetic IDs are left.
+pub fn replace_synthetic_cr_ids<F>(bundle: &mut Bundle, lookup: &mut F) -> (usize, usize)
+where
+    F: FnMut(&str) -> Option<(String, CrDemographics)>,
+{
+    let (mut replaced, mut left) = (0, 0);
+    for patient in bundle
+        .entry
+        .iter_mut()
+        .flatten()
+        .filter_map(|e| e.resource.as_mut())
+        .filter(|r| r["resourceType"] == "Patient")
+    {
+        let national_id = identifier(patient, NATIONAL_ID_SYSTEM).map(str::to_string);
+        if !identifier(patient, CR_SYSTEM).is_some_and(|v| v.starts_with(SYNTHETIC_PREFIX)) {
+            continue;
+        }
+        let Some((cr_id, registered)) = national_id.as_deref().and_then(&mut *lookup) else {
+            left += 1;
+            continue;
+        };
+        let differs = match demographics(patient) {
+            Some((names, birth_date, estimated)) => {
+                registered.differences(&names, birth_date, estimated)
+            }
+            None => vec!["date of birth"],
+        };
+        if !differs.is_empty() {
+            eprintln!(
+                "[CR] CR patient {} differs in {}; synthetic ID kept",
+                mask_identifier(&cr_id),
+                differs.join(" and ")
+            );
+            left += 1;
+            continue;
+        }
+        for entry in patient["identifier"].as_array_mut().into_iter().flatten() {
+            if entry["system"] == CR_SYSTEM {
+                entry["value"] = cr_id.clone().into();
+            }
+        }
+        replaced += 1;
+    }
+    (replaced, left)
+}
+
+fn identifier<'a>(patient: &'a Value, system: &str) -> Option<&'a str> {
+    patient["identifier"]
+        .as_array()?
+        .iter()
+        .find(|i| i["system"] == system)?["value"]
+        .as_str()
+}
+
+/// Names, birth date and whether it is estimated.
+fn demographics(patient: &Value) -> Option<(Vec<&str>, NaiveDate, bool)> {
+    let birth_date = patient["birthDate"]
+        .as_str()
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())?;
+    let names = patient["name"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|name| {
+            name["family"].as_str().into_iter().chain(
+                name["given"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str),
+            )
+        })
+        .collect();
+    let estimated = patient["_birthDate"]["extension"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .any(|e| e["url"] == BIRTH_DATE_ESTIMATED_URL && e["valueBoolean"] == true);
+    Some((names, birth_date, estimated))
+}
@@ -0,0 +1,179 @@
+//! De-identified bundles for research and analytics (`--deidentify`).
+//!
+//! Runs on the finished bundle, so every mapping option still applies. The
+//! Patient keeps gender, birth year and county; names, phone, photo,
+//! identifiers and the sub-county go, as does the sub-county health office
+//! (the facility hangs off the county instead). Its id becomes a keyed pseudonym of the
+//! national ID — the same person gets the same pseudonym across runs and
+//! facilities under one key, and nobody without the key can reverse it — and
+//! every id and reference built from the old id is rewritten to match.
+//! Coverage and Claim (SHA member number), DocumentReferences (biometrics,
+//! scanned documents), the ImmunizationRecommendation (its due dates give
+//! away the date of birth) and the ClinicalImpression (a free-text note may
+//! name people) are dropped; clinical resources are kept as they are.
+use fhir_parser::fhir::bundle::{Bundle, BundleEntry};
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::systems::{NATIONAL_ID_SYSTEM, PSEUDONYM_SYSTEM, SUBCOUNTY_SYSTEM};
+
+/// Resources removed outright: they carry direct identifiers, dates that
+/// give them away, or free text that may.
+const DROPPED_RESOURCES: &[&str] = &[
+    "Coverage",
+    "Claim",
+    "DocumentReference",
+    "ImmunizationRecommendation",
+    "ClinicalImpression",
+];
+
+/// Shorter keys make a dictionary attack on 8-digit national IDs feasible.
+const MIN_KEY_BYTES: usize = 16;
+
+#[derive(Clone)]
+pub struct Deidentifier {
+    key: Vec<u8>,
+}
+
+impl std::fmt::Debug for Deidentifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Deidentifier { key: <redacted> }")
+    }
+}
+
+impl Deidentifier {
+    pub fn new(key: &[u8]) -> Result<Self> {
+        if key.len() < MIN_KEY_BYTES {
+            bail!(
+                Config,
+                "De-identification key must be at least {} bytes",
+                MIN_KEY_BYTES
+            );
+        }
+        Ok(Self { key: key.to_vec() })
+    }
+
+    /// Key from `DEIDENTIFY_KEY`; keep it with whoever may re-link records.
+    pub fn from_env() -> Result<Self> {
+        let key = std::env::var("DEIDENTIFY_KEY")
+            .ok()
+            .filter(|k| !k.is_empty())
+            .context(BridgeError::Config, "--deidentify needs DEIDENTIFY_KEY")?;
+        Self::new(key.as_bytes())
+    }
+
+    /// HMAC-SHA256 of `value`, first 128 bits in hex (a valid FHIR id).
+    pub fn pseudonym(&self, value: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC takes any key size");
+        mac.update(value.as_bytes());
+        mac.finalize().into_bytes()[..16]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Strip or pseudonymize direct identifiers in `bundle`.
+    pub fn apply(&self, bundle: &mut Bundle) -> Result<()> {
+        let entries = bundle.entry.get_or_insert_with(Vec::new);
+        entries.retain(|e| {
+            let resource_type = e
+                .resource
+                .as_ref()
+                .and_then(|r| r.get("resourceType"))
+                .and_then(Value::as_str);
+            !resource_type.is_some_and(|t| DROPPED_RESOURCES.contains(&t))
+        });
+        drop_subcounty_offices(entries);
+
+        let patient = entries
+            .iter_mut()
+            .filter_map(|e| e.resource.as_mut())
+            .find(|r| r.get("resourceType").and_then(Value::as_str) == Some("Patient"))
+            .context(BridgeError::Mapping, "Bundle has no Patient")?;
+        let old_id = patient["id"]
+            .as_str()
+            .context(BridgeError::Mapping, "Patient.id not set")?
+            .to_string();
+        let national_id = patient["identifier"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|i| i["system"] == NATIONAL_ID_SYSTEM)
+            .and_then(|i| i["value"].as_str())
+            .unwrap_or(&old_id)
+            .to_string();
+        let new_id = self.pseudonym(&national_id);
+        strip_patient(patient, &new_id);
+
+        // Encounter, Observation, ... ids embed the patient id, as do all
+        // references and fullUrls: rewrite them in one pass.
+        let json = serde_json::to_string(entries)?.replace(&old_id, &new_id);
+        *entries = serde_json::from_str(&json)?;
+        Ok(())
+    }
+}
+
+/// Remove sub-county Organizations; whatever was partOf one becomes partOf
+/// its parent (the county).
+fn drop_subcounty_offices(entries: &mut Vec<BundleEntry>) {
+    let mut parents = Vec::new();
+    entries.retain(|e| {
+        let Some(r) = &e.resource else {
+            return true;
+        };
+        let is_office = r["resourceType"] == "Organization"
+            && r["identifier"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .any(|i| i["system"] == SUBCOUNTY_SYSTEM);
+        if is_office {
+            let id = r["id"].as_str().unwrap_or_default();
+            parents.push((format!("Organization/{}", id), r["partOf"].clone()));
+        }
+        !is_office
+    });
+    for resource in entries.iter_mut().filter_map(|e| e.resource.as_mut()) {
+        let Some(obj) = resource.as_object_mut() else {
+            continue;
+        };
+        let Some(part_of) = obj.get("partOf").and_then(|p| p["reference"].as_str()) else {
+            continue;
+        };
+        if let Some((_, parent)) = parents.iter().find(|(office, _)| office == part_of) {
+            match parent {
+                Value::Null => obj.remove("partOf"),
+                parent => obj.insert("partOf".into(), parent.clone()),
+            };
+        }
+    }
+}
+
+fn strip_patient(patient: &mut Value, pseudonym: &str) {
+    let Some(obj) = patient.as_object_mut() else {
+        return;
+    };
+    for field in ["name", "telecom", "photo", "contact", "link"] {
+        obj.remove(field);
+    }
+    obj.insert(
+        "identifier".into(),
+        serde_json::json!([{"system": PSEUDONYM_SYSTEM, "value": pseudonym}]),
+    );
+    // Year only; `_birthDate` (estimated flag) stays
+    if let Some(year) = obj
+        .get("birthDate")
+        .and_then(Value::as_str)
+        .and_then(|d| d.get(..4))
+    {
+        let year = year.to_string();
+        obj.insert("birthDate".into(), year.into());
+    }
+    if let Some(Value::Array(addresses)) = obj.get_mut("address") {
+        for address in addresses.iter_mut().filter_map(Value::as_object_mut) {
+            address.retain(|k, _| matches!(k.as_str(), "extension" | "district" | "country"));
+        }
+    }
+}
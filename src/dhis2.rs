@@ -0,0 +1,324 @@
+//! MOH 705 outpatient morbidity summary as a DHIS2 dataValueSet.
+//!
+//! Visits in a reporting month are counted per facility, diagnosis (MOH 705
+//! row), age band (705A under five / 705B five and over) and gender, and
+//! written as the JSON a KHIS import (`POST /api/dataValueSets`) accepts —
+//! the figures records officers otherwise copy from the paper register.
+//! Only counts are emitted, no patient identifiers.
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::kenyan::schema::KenyanPatient;
+
+/// MOH 705 splits under-fives (705A) from everyone else (705B).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AgeBand {
+    Under5,
+    FiveAndOver,
+}
+
+impl AgeBand {
+    /// Age in completed years on the visit date.
+    pub fn at(date_of_birth: NaiveDate, visit_date: NaiveDate) -> Self {
+        match visit_date.years_since(date_of_birth) {
+            Some(age) if age >= 5 => Self::FiveAndOver,
+            _ => Self::Under5,
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Self::Under5 => "under5",
+            Self::FiveAndOver => "over5",
+        }
+    }
+}
+
+/// One MOH 705 row. Its `terms` are lower-case phrases looked for in the
+/// diagnosis text; a row without terms takes every diagnosis no earlier row
+/// matched ("All other diseases").
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Moh705Element {
+    /// DHIS2 data element (UID or code, per `id_scheme`)
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub terms: Vec<String>,
+}
+
+/// How register rows, facilities and disaggregations map to DHIS2
+/// identifiers — the built-in defaults, or a JSON file from the county
+/// HRIO with the KHIS UIDs.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Dhis2Mapping {
+    #[serde(default = "default_data_set")]
+    pub data_set: String,
+    /// `UID` or `CODE`; tells DHIS2 how to read the ids below
+    #[serde(default = "default_id_scheme")]
+    pub id_scheme: String,
+    /// clinic_id → organisation unit; unlisted clinics are sent as-is
+    #[serde(default)]
+    pub org_units: BTreeMap<String, String>,
+    /// Rows in matching order
+    pub data_elements: Vec<Moh705Element>,
+    /// `under5_male`, `under5_female`, `over5_male`, `over5_female` →
+    /// category option combo
+    pub category_option_combos: BTreeMap<String, String>,
+}
+
+fn default_data_set() -> String {
+    "MOH705".to_string()
+}
+
+fn default_id_scheme() -> String {
+    "CODE".to_string()
+}
+
+// (code, name, terms). Placeholder codes until the KHIS ones are configured,
+// like the identifier URIs.
+const DEFAULT_ELEMENTS: &[(&str, &str, &[&str])] = &[
+    (
+        "MOH705_DIARRHOEA",
+        "Diarrhoea",
+        &["diarrhoea", "diarrhea", "gastroenteritis"],
+    ),
+    ("MOH705_TB", "Tuberculosis", &["tuberculosis"]),
+    ("MOH705_MALARIA", "Malaria", &["malaria"]),
+    ("MOH705_PNEUMONIA", "Pneumonia", &["pneumonia"]),
+    (
+        "MOH705_URTI",
+        "Upper respiratory tract infections",
+        &[
+            "upper respiratory",
+            "urti",
+            "common cold",
+            "tonsillitis",
+            "pharyngitis",
+        ],
+    ),
+    ("MOH705_UTI", "Urinary tract infection", &["urinary tract"]),
+    ("MOH705_HYPERTENSION", "Hypertension", &["hypertension"]),
+    ("MOH705_DIABETES", "Diabetes", &["diabetes"]),
+    (
+        "MOH705_SKIN",
+        "Diseases of the skin",
+        &["skin", "dermatitis", "eczema"],
+    ),
+    ("MOH705_OTHER", "All other diseases", &[]),
+];
+
+impl Default for Dhis2Mapping {
+    fn default() -> Self {
+        let data_elements = DEFAULT_ELEMENTS
+            .iter()
+            .map(|(id, name, terms)| Moh705Element {
+                id: id.to_string(),
+                name: name.to_string(),
+                terms: terms.iter().map(|t| t.to_string()).collect(),
+            })
+            .collect();
+        let category_option_combos = ["under5_male", "under5_female", "over5_male", "over5_female"]
+            .iter()
+            .map(|k| (k.to_string(), k.to_uppercase()))
+            .collect();
+        Self {
+            data_set: default_data_set(),
+            id_scheme: default_id_scheme(),
+            org_units: BTreeMap::new(),
+            data_elements,
+            category_option_combos,
+        }
+    }
+}
+
+impl Dhis2Mapping {
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path).with_context(BridgeError::Io, || {
+            format!("Failed to read DHIS2 mapping {:?}", path)
+        })?;
+        serde_json::from_str(&raw).context(BridgeError::Config, "Invalid DHIS2 mapping JSON")
+    }
+
+    /// First row whose terms appear in the diagnosis.
+    pub fn element_for(&self, diagnosis: &str) -> Option<&Moh705Element> {
+        let lower = diagnosis.to_lowercase();
+        self.data_elements.iter().find(|e| {
+            e.terms.is_empty() || e.terms.iter().any(|t| lower.contains(&t.to_lowercase()))
+        })
+    }
+}
+
+/// Visits for one register cell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Moh705Count {
+    pub clinic_id: String,
+    pub data_element: String,
+    pub age_band: AgeBand,
+    /// `male` or `female`
+    pub gender: &'static str,
+    pub count: u32,
+}
+
+/// Counts for a month, plus what could not be placed in the register.
+#[derive(Debug, Default)]
+pub struct Moh705Tally {
+    pub counts: Vec<Moh705Count>,
+    /// Visits without a recorded gender (no register column for them)
+    pub unknown_gender: u32,
+    /// Visits whose diagnosis matched no row and no catch-all is configured
+    pub unmapped: u32,
+}
+
+/// `YYYYMM` DHIS2 monthly period → the `YYYY-MM` visit date prefix.
+pub fn month_prefix(period: &str) -> Result<String> {
+    let valid = period.len() == 6
+        && period.bytes().all(|b| b.is_ascii_digit())
+        && (1..=12).contains(&period[4..].parse::<u32>().unwrap_or(0));
+    if !valid {
+        bail!(
+            Config,
+            "Period must be a DHIS2 monthly period (YYYYMM), got {:?}",
+            period
+        );
+    }
+    Ok(format!("{}-{}", &period[..4], &period[4..]))
+}
+
+/// Count the visits of `period` (YYYYMM) per register cell.
+pub fn aggregate(
+    records: &[KenyanPatient],
+    mapping: &Dhis2Mapping,
+    period: &str,
+) -> Result<Moh705Tally> {
+    let prefix = month_prefix(period)?;
+    let mut tally = Moh705Tally::default();
+    let mut counts: BTreeMap<(String, String, AgeBand, &'static str), u32> = BTreeMap::new();
+
+    for record in records {
+        let gender = match record.gender.as_str() {
+            "M" => Some("male"),
+            "F" => Some("female"),
+            _ => None,
+        };
+        for visit in record.visits.iter().filter(|v| v.date.starts_with(&prefix)) {
+            let Some(gender) = gender else {
+                tally.unknown_gender += 1;
+                continue;
+            };
+            let Some(element) = mapping.element_for(&visit.diagnosis) else {
+                tally.unmapped += 1;
+                continue;
+            };
+            let date = NaiveDate::parse_from_str(&visit.date, "%Y-%m-%d").context(
+                BridgeError::Validation,
+                "Invalid visit date format — expected YYYY-MM-DD",
+            )?;
+            let band = AgeBand::at(record.date_of_birth, date);
+            *counts
+                .entry((record.clinic_id.clone(), element.id.clone(), band, gender))
+                .or_default() += 1;
+        }
+    }
+
+    tally.counts = counts
+        .into_iter()
+        .map(
+            |((clinic_id, data_element, age_band, gender), count)| Moh705Count {
+                clinic_id,
+                data_element,
+                age_band,
+                gender,
+                count,
+            },
+        )
+        .collect();
+    Ok(tally)
+}
+
+/// DHIS2 `dataValueSet` import payload.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataValueSet {
+    pub data_set: String,
+    pub period: String,
+    pub id_scheme: String,
+    pub data_values: Vec<DataValue>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataValue {
+    pub data_element: String,
+    pub period: String,
+    pub org_unit: String,
+    pub category_option_combo: String,
+    /// DHIS2 carries values as strings
+    pub value: String,
+}
+
+pub fn to_data_value_set(
+    counts: &[Moh705Count],
+    mapping: &Dhis2Mapping,
+    period: &str,
+) -> Result<DataValueSet> {
+    let data_values = counts
+        .iter()
+        .map(|c| {
+            let combo_key = format!("{}_{}", c.age_band.key(), c.gender);
+            let combo = mapping
+                .category_option_combos
+                .get(&combo_key)
+                .with_context(BridgeError::Config, || {
+                    format!("DHIS2 mapping has no category option combo {}", combo_key)
+                })?;
+            Ok(DataValue {
+                data_element: c.data_element.clone(),
+                period: period.to_string(),
+                org_unit: mapping
+                    .org_units
+                    .get(&c.clinic_id)
+                    .unwrap_or(&c.clinic_id)
+                    .clone(),
+                category_option_combo: combo.clone(),
+                value: c.count.to_string(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(DataValueSet {
+        data_set: mapping.data_set.clone(),
+        period: period.to_string(),
+        id_scheme: mapping.id_scheme.clone(),
+        data_values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn fifth_birthday_moves_a_child_to_705b() {
+        let dob = date("2021-10-16");
+        assert_eq!(AgeBand::at(dob, date("2026-10-15")), AgeBand::Under5);
+        assert_eq!(AgeBand::at(dob, date("2026-10-16")), AgeBand::FiveAndOver);
+    }
+
+    #[test]
+    fn diagnoses_fall_through_to_all_other_diseases() {
+        let mapping = Dhis2Mapping::default();
+        let id = |d: &str| mapping.element_for(d).map(|e| e.id.as_str());
+        assert_eq!(id("Malaria (confirmed, RDT)"), Some("MOH705_MALARIA"));
+        assert_eq!(id("Upper respiratory tract infection"), Some("MOH705_URTI"));
+        assert_eq!(id("No acute illness noted"), Some("MOH705_OTHER"));
+        assert!(month_prefix("202613").is_err());
+        assert_eq!(month_prefix("202610").unwrap(), "2026-10");
+    }
+}
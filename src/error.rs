@@ -0,0 +1,144 @@
+//! Library error type.
+//!
+//! Every fallible library function returns [`BridgeError`], so embedding
+//! crates can tell a record that has to go back to the clinic (validation)
+//! from one worth retrying later (network) without inspecting messages.
+//! Messages follow the same rule as logs: no PHI.
+use std::fmt;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum BridgeError {
+    /// The input record, a bundle or signed data breaks a rule
+    #[error("{0}")]
+    Validation(String),
+    /// A record could not be turned into FHIR, or FHIR back into a record
+    #[error("{0}")]
+    Mapping(String),
+    /// A code system, crosswalk or terminology server failed
+    #[error("{0}")]
+    Terminology(String),
+    /// No response from a server, or an unexpected one
+    #[error("{0}")]
+    Network(String),
+    /// The offline queue, its encryption keys or its policy
+    #[error("{0}")]
+    Queue(String),
+    /// Another local database (archive, visit ledger, patient index, caches)
+    #[error("{0}")]
+    Storage(String),
+    /// A configuration file or setting is missing or malformed
+    #[error("{0}")]
+    Config(String),
+    /// A file could not be read or written
+    #[error("{0}")]
+    Io(String),
+}
+
+pub type Result<T, E = BridgeError> = std::result::Result<T, E>;
+
+impl BridgeError {
+    /// The same error with `context` in front of the message.
+    pub fn context(self, context: impl fmt::Display) -> Self {
+        let prefix = |message: String| format!("{}: {}", context, message);
+        match self {
+            Self::Validation(m) => Self::Validation(prefix(m)),
+            Self::Mapping(m) => Self::Mapping(prefix(m)),
+            Self::Terminology(m) => Self::Terminology(prefix(m)),
+            Self::Network(m) => Self::Network(prefix(m)),
+            Self::Queue(m) => Self::Queue(prefix(m)),
+            Self::Storage(m) => Self::Storage(prefix(m)),
+            Self::Config(m) => Self::Config(prefix(m)),
+            Self::Io(m) => Self::Io(prefix(m)),
+        }
+    }
+}
+
+impl From<std::io::Error> for BridgeError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e.to_string())
+    }
+}
+
+/// Bare `?` on serde_json is (de)serializing resources; parsing of files
+/// and responses gives its own context and kind.
+impl From<serde_json::Error> for BridgeError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Mapping(e.to_string())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for BridgeError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::Storage(e.to_string())
+    }
+}
+
+/// Turn a foreign error (or a missing value) into a [`BridgeError`] of the
+/// given kind, with a message in front: `.context(BridgeError::Config, "..")`.
+pub(crate) trait Context<T> {
+    fn context(self, kind: fn(String) -> BridgeError, message: impl fmt::Display) -> Result<T>;
+
+    fn with_context<M: fmt::Display>(
+        self,
+        kind: fn(String) -> BridgeError,
+        message: impl FnOnce() -> M,
+    ) -> Result<T>;
+}
+
+impl<T, E: fmt::Display> Context<T> for std::result::Result<T, E> {
+    fn context(self, kind: fn(String) -> BridgeError, message: impl fmt::Display) -> Result<T> {
+        self.map_err(|e| kind(format!("{}: {}", message, e)))
+    }
+
+    fn with_context<M: fmt::Display>(
+        self,
+        kind: fn(String) -> BridgeError,
+        message: impl FnOnce() -> M,
+    ) -> Result<T> {
+        self.map_err(|e| kind(format!("{}: {}", message(), e)))
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context(self, kind: fn(String) -> BridgeError, message: impl fmt::Display) -> Result<T> {
+        self.ok_or_else(|| kind(message.to_string()))
+    }
+
+    fn with_context<M: fmt::Display>(
+        self,
+        kind: fn(String) -> BridgeError,
+        message: impl FnOnce() -> M,
+    ) -> Result<T> {
+        self.ok_or_else(|| kind(message().to_string()))
+    }
+}
+
+/// `bail!(Validation, "...", args)`: return a [`BridgeError`] of that kind.
+macro_rules! bail {
+    ($kind:ident, $($arg:tt)+) => {
+        return Err($crate::error::BridgeError::$kind(format!($($arg)+)))
+    };
+}
+pub(crate) use bail;
+
+#[cfg(all(test, feature = "sqlite", feature = "network"))]
+mod tests {
+    use super::*;
+    use crate::queue_crypto::QueueKeys;
+
+    #[test]
+    fn context_keeps_the_kind() {
+        let err = match QueueKeys::from_json("not json") {
+            Err(e) => e.context("Loading queue keys"),
+            Ok(_) => panic!("invalid key set accepted"),
+        };
+        assert!(matches!(err, BridgeError::Queue(_)), "{:?}", err);
+        assert!(err.to_string().starts_with("Loading queue keys: "));
+
+        let missing: Result<()> = None.context(BridgeError::Config, "no channel");
+        assert!(matches!(missing, Err(BridgeError::Config(m)) if m == "no channel"));
+    }
+}
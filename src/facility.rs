@@ -0,0 +1,197 @@
+//! Facility contact details for the serviceProvider Organization.
+//!
+//! The SHR requires telecom and address on the facility Organization, and
+//! the Kenyan record carries neither. They come from a facility contacts
+//! file (`--facility-contacts`), a JSON object from clinic_id to
+//! [`FacilityContact`], and for clinics it does not list optionally from the
+//! Facility Registry. A failed registry lookup leaves the Organization
+//! without them.
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use serde::Deserialize;
+#[cfg(feature = "network")]
+use serde_json::Value;
+
+use crate::error::{bail, BridgeError, Context, Result};
+#[cfg(feature = "network")]
+use crate::http::{self, url_encode, HttpRequest};
+#[cfg(feature = "network")]
+use crate::kenyan::counties::{county_code, COUNTIES};
+
+/// Contact details of one facility.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct FacilityContact {
+    pub phone: Option<String>,
+    pub email: Option<String>,
+    /// Street or postal address, e.g. `P.O. Box 20-80100`
+    pub address: Option<String>,
+    pub town: Option<String>,
+    pub county: Option<String>,
+    pub subcounty: Option<String>,
+}
+
+/// Where facility contact details come from.
+#[derive(Debug, Clone, Default)]
+pub struct FacilityDirectory {
+    contacts: HashMap<String, FacilityContact>,
+    /// Look up clinics the file does not list in the Facility Registry
+    pub registry: bool,
+    memo: Arc<Mutex<HashMap<String, Option<FacilityContact>>>>,
+}
+
+impl FacilityDirectory {
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path).with_context(BridgeError::Io, || {
+            format!("Failed to read facility contacts {:?}", path)
+        })?;
+        let contacts = serde_json::from_str(&raw)
+            .context(BridgeError::Config, "Invalid facility contacts JSON")?;
+        Ok(Self {
+            contacts,
+            ..Self::default()
+        })
+    }
+
+    /// Contact details for `clinic_id`: the file's entry, else the Facility
+    /// Registry's (asked once per run), else none.
+    pub fn contact(&self, clinic_id: &str) -> Option<FacilityContact> {
+        if let Some(contact) = self.contacts.get(clinic_id) {
+            return Some(contact.clone());
+        }
+        if !self.registry {
+            return None;
+        }
+        let mut memo = self.memo.lock().unwrap_or_else(|e| e.into_inner());
+        memo.entry(clinic_id.to_string())
+            .or_insert_with(|| {
+                registry_lookup(clinic_id).unwrap_or_else(|e| {
+                    eprintln!(
+                        "[FR] Facility lookup failed, organization has no contact details: {:#}",
+                        e
+                    );
+                    None
+                })
+            })
+            .clone()
+    }
+}
+
+/// Facility Registry search on AfyaLink (token from AFYALINK_TOKEN).
+#[cfg(feature = "network")]
+fn registry_lookup(clinic_id: &str) -> Result<Option<FacilityContact>> {
+    let token = std::env::var("AFYALINK_TOKEN")
+        .context(BridgeError::Config, "AFYALINK_TOKEN is not set")?;
+    let base =
+        std::env::var("AFYALINK_BASE_URL").unwrap_or_else(|_| "https://uat.dha.go.ke".to_string());
+    let url = format!(
+        "{}/v1/facility-search?facility_code={}",
+        base,
+        url_encode(clinic_id)
+    );
+    let response = http::send(&HttpRequest {
+        method: "GET",
+        url: &url,
+        headers: vec![
+            format!("Authorization: Bearer {}", token),
+            "Accept: application/fhir+json".to_string(),
+        ],
+        body: None,
+        timeout_secs: 5,
+        tls: None,
+    })?;
+    if !response.is_success() {
+        bail!(Network, "facility search returned HTTP {}", response.status);
+    }
+    let bundle: Value = serde_json::from_str(&response.body)
+        .context(BridgeError::Network, "Invalid facility search response")?;
+    Ok(bundle["entry"]
+        .as_array()
+        .and_then(|entries| entries.first())
+        .map(|entry| contact_from_resource(&entry["resource"])))
+}
+
+/// Built without `network`: the registry cannot be asked.
+#[cfg(not(feature = "network"))]
+fn registry_lookup(_clinic_id: &str) -> Result<Option<FacilityContact>> {
+    bail!(Config, "built without the `network` feature")
+}
+
+/// Read a registry Organization or Location. Address lines naming a
+/// sub-county of the address's county give the sub-county, as the bridge
+/// writes it; the rest is the street address.
+#[cfg(feature = "network")]
+fn contact_from_resource(resource: &Value) -> FacilityContact {
+    let telecom = |system: &str| {
+        resource["telecom"]
+            .as_array()?
+            .iter()
+            .find(|t| t["system"] == system)?["value"]
+            .as_str()
+            .map(str::to_string)
+    };
+    let address = &resource["address"];
+    // Location.address is one Address, Organization.address a list
+    let address = if address.is_array() {
+        &address[0]
+    } else {
+        address
+    };
+    let county = address["district"]
+        .as_str()
+        .or_else(|| address["state"].as_str())
+        .map(str::to_string);
+    let subcounties = county
+        .as_deref()
+        .and_then(county_code)
+        .and_then(|code| COUNTIES.iter().find(|(c, _, _)| *c == code))
+        .map(|(_, _, subs)| *subs)
+        .unwrap_or_default();
+    let (subcounty, street): (Vec<&str>, Vec<&str>) = address["line"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .partition(|line| subcounties.contains(line));
+    FacilityContact {
+        phone: telecom("phone"),
+        email: telecom("email"),
+        address: (!street.is_empty()).then(|| street.join(", ")),
+        town: address["city"].as_str().map(str::to_string),
+        county,
+        subcounty: subcounty.first().map(|s| s.to_string()),
+    }
+}
+
+#[cfg(all(test, feature = "network"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_address_lines_split_into_subcounty_and_street() {
+        let resource = serde_json::json!({
+            "resourceType": "Organization",
+            "telecom": [
+                {"system": "phone", "value": "+254722000111"},
+                {"system": "email", "value": "info@example.org"}
+            ],
+            "address": [{
+                "line": ["Kenyatta Avenue", "Nakuru Town East"],
+                "city": "Nakuru",
+                "district": "Nakuru"
+            }]
+        });
+        assert_eq!(
+            contact_from_resource(&resource),
+            FacilityContact {
+                phone: Some("+254722000111".to_string()),
+                email: Some("info@example.org".to_string()),
+                address: Some("Kenyatta Avenue".to_string()),
+                town: Some("Nakuru".to_string()),
+                county: Some("Nakuru".to_string()),
+                subcounty: Some("Nakuru Town East".to_string()),
+            }
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! C interface for in-process embedding (`ffi` feature).
+//!
+//! Several Delphi/VB-era hospital systems can load a DLL but not run a
+//! sidecar service. This is a small, stable C ABI over the same mapping as
+//! the CLI; the declarations are in `include/kenya_fhir_bridge.h`. Build the
+//! shared library with
+//! `cargo rustc --lib --release --features ffi --crate-type cdylib`.
+//!
+//! Every call returns or sets a `KFB_*` code; after a failure
+//! `kfb_last_error_message` says why, for the calling thread. Strings the
+//! bridge returns belong to the caller and go back through
+//! `kfb_string_free`. Panics never cross the boundary.
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, UnwindSafe};
+use std::ptr;
+
+use crate::error::{BridgeError, Context, Result};
+use crate::kenyan::schema::KenyanPatient;
+use crate::transform::{transform_json, TransformOptions};
+use crate::validation::validate_kenyan_patient;
+
+pub const KFB_OK: c_int = 0;
+/// NULL pointer or text that is not UTF-8
+pub const KFB_ERR_ARGUMENT: c_int = 1;
+pub const KFB_ERR_VALIDATION: c_int = 2;
+pub const KFB_ERR_MAPPING: c_int = 3;
+pub const KFB_ERR_TERMINOLOGY: c_int = 4;
+pub const KFB_ERR_NETWORK: c_int = 5;
+pub const KFB_ERR_QUEUE: c_int = 6;
+pub const KFB_ERR_STORAGE: c_int = 7;
+pub const KFB_ERR_CONFIG: c_int = 8;
+pub const KFB_ERR_IO: c_int = 9;
+/// A bug in the bridge; the message has the panic text
+pub const KFB_ERR_INTERNAL: c_int = 99;
+
+thread_local! {
+    static LAST_ERROR: RefCell<(c_int, CString)> = RefCell::new((KFB_OK, CString::default()));
+}
+
+fn set_last_error(code: c_int, message: &str) {
+    // Interior NULs would cut the message short on the C side
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = (code, message));
+}
+
+fn error_code(e: &BridgeError) -> c_int {
+    match e {
+        BridgeError::Validation(_) => KFB_ERR_VALIDATION,
+        BridgeError::Mapping(_) => KFB_ERR_MAPPING,
+        BridgeError::Terminology(_) => KFB_ERR_TERMINOLOGY,
+        BridgeError::Network(_) => KFB_ERR_NETWORK,
+        BridgeError::Queue(_) => KFB_ERR_QUEUE,
+        BridgeError::Storage(_) => KFB_ERR_STORAGE,
+        BridgeError::Config(_) => KFB_ERR_CONFIG,
+        BridgeError::Io(_) => KFB_ERR_IO,
+    }
+}
+
+/// Run `call` on the text at `input`, recording the outcome as the last
+/// error; `None` on any failure.
+///
+/// # Safety
+/// `input` is NULL or a NUL-terminated string valid for the call.
+unsafe fn guarded<T, F>(input: *const c_char, call: F) -> Option<T>
+where
+    F: FnOnce(&str) -> Result<T> + UnwindSafe,
+{
+    if input.is_null() {
+        set_last_error(KFB_ERR_ARGUMENT, "input is NULL");
+        return None;
+    }
+    let Ok(text) = CStr::from_ptr(input).to_str() else {
+        set_last_error(KFB_ERR_ARGUMENT, "input is not UTF-8");
+        return None;
+    };
+    match catch_unwind(|| call(text)) {
+        Ok(Ok(value)) => {
+            set_last_error(KFB_OK, "");
+            Some(value)
+        }
+        Ok(Err(e)) => {
+            set_last_error(error_code(&e), &e.to_string());
+            None
+        }
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            set_last_error(KFB_ERR_INTERNAL, &format!("internal error: {}", message));
+            None
+        }
+    }
+}
+
+/// Kenyan record JSON → compact FHIR R4 Bundle JSON, or NULL on failure
+/// (see `kfb_last_error`). Free the result with `kfb_string_free`.
+///
+/// # Safety
+/// `record_json` is NULL or a NUL-terminated string valid for the call.
+#[no_mangle]
+pub unsafe extern "C" fn kfb_transform_json(record_json: *const c_char) -> *mut c_char {
+    guarded(record_json, |json| {
+        transform_json(json, &TransformOptions::default())
+    })
+    .and_then(|bundle| CString::new(bundle).ok())
+    .map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Check a Kenyan record JSON against the input rules: `KFB_OK`, or the
+/// error code (message from `kfb_last_error_message`).
+///
+/// # Safety
+/// `record_json` is NULL or a NUL-terminated string valid for the call.
+#[no_mangle]
+pub unsafe extern "C" fn kfb_validate_json(record_json: *const c_char) -> c_int {
+    guarded(record_json, |json| {
+        let kenyan: KenyanPatient = serde_json::from_str(json)
+            .context(BridgeError::Validation, "Invalid Kenyan JSON payload")?;
+        validate_kenyan_patient(&kenyan)
+    });
+    kfb_last_error()
+}
+
+/// Code of the calling thread's last call (`KFB_OK` after a success).
+#[no_mangle]
+pub extern "C" fn kfb_last_error() -> c_int {
+    LAST_ERROR.with(|last| last.borrow().0)
+}
+
+/// Message of the calling thread's last failure ("" after a success). The
+/// text stays owned by the bridge and valid until the thread's next call;
+/// do not free it.
+#[no_mangle]
+pub extern "C" fn kfb_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().1.as_ptr())
+}
+
+/// Release a string the bridge returned. NULL is ignored.
+///
+/// # Safety
+/// `s` is NULL or a pointer from `kfb_transform_json` not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn kfb_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Bridge version, e.g. `0.1.0`; static, do not free.
+#[no_mangle]
+pub extern "C" fn kfb_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_message() -> String {
+        unsafe { CStr::from_ptr(kfb_last_error_message()) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn transforms_and_reports_errors_through_the_c_interface() {
+        let record = CString::new(include_str!("../tests/fixtures/kenyan_patient_1.json")).unwrap();
+        unsafe {
+            let bundle = kfb_transform_json(record.as_ptr());
+            assert!(!bundle.is_null());
+            assert_eq!(kfb_last_error(), KFB_OK);
+            assert!(CStr::from_ptr(bundle)
+                .to_str()
+                .unwrap()
+                .starts_with("{\"resourceType\":\"Bundle\""));
+            kfb_string_free(bundle);
+
+            assert_eq!(kfb_validate_json(record.as_ptr()), KFB_OK);
+            assert_eq!(last_message(), "");
+
+            let invalid = CString::new("{}").unwrap();
+            assert!(kfb_transform_json(invalid.as_ptr()).is_null());
+            assert_eq!(kfb_last_error(), KFB_ERR_VALIDATION);
+            assert!(last_message().starts_with("Invalid Kenyan JSON payload"));
+
+            assert_eq!(kfb_validate_json(ptr::null()), KFB_ERR_ARGUMENT);
+            kfb_string_free(ptr::null_mut());
+        }
+        let version = unsafe { CStr::from_ptr(kfb_version()) };
+        assert_eq!(version.to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+    }
+}
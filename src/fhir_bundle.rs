@@ -1,33 +1,138 @@
-use chrono::Utc;
-use uuid::Uuid;
-
+use fhir_parser::fhir::appointment::Appointment;
 use fhir_parser::fhir::bundle::{Bundle, BundleEntry, BundleRequest};
+use fhir_parser::fhir::care_plan::CarePlan;
+use fhir_parser::fhir::clinical_impression::ClinicalImpression;
 use fhir_parser::fhir::condition::Condition;
+use fhir_parser::fhir::device::Device;
+use fhir_parser::fhir::document_reference::DocumentReference;
 use fhir_parser::fhir::encounter::Encounter;
+use fhir_parser::fhir::episode_of_care::EpisodeOfCare;
+use fhir_parser::fhir::flag::Flag;
+use fhir_parser::fhir::immunization_recommendation::ImmunizationRecommendation;
+use fhir_parser::fhir::location::Location;
 use fhir_parser::fhir::medication_request::MedicationRequest;
 use fhir_parser::fhir::observation::Observation;
+use fhir_parser::fhir::operation_outcome::{OperationOutcome, OperationOutcomeIssue};
 use fhir_parser::fhir::organization::Organization;
 use fhir_parser::fhir::patient::Patient;
 use fhir_parser::fhir::practitioner::Practitioner;
-use serde_json::json;
+use fhir_parser::fhir::practitioner_role::PractitionerRole;
+use serde_json::{json, Value};
 
+use crate::mapper::antenatal::AntenatalResources;
+use crate::mapper::imaging::ImagingResources;
+use crate::mapper::lab_order::LabOrderResources;
 use crate::mapper::sha::ShaClaims;
 
+/// Layout of serialized bundle JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonLayout {
+    /// Indented, for reading
+    Pretty,
+    /// No whitespace, for transmission (pretty output is ~40% larger)
+    Compact,
+}
+
+/// Serialize a bundle in the given layout.
+pub fn bundle_to_json(bundle: &Bundle, layout: JsonLayout) -> serde_json::Result<String> {
+    match layout {
+        JsonLayout::Pretty => serde_json::to_string_pretty(bundle),
+        JsonLayout::Compact => serde_json::to_string(bundle),
+    }
+}
+
+/// Resources generated for a single visit.
+pub struct VisitResources {
+    pub encounter: Encounter,
+    pub observations: Vec<Observation>,
+    pub condition: Condition,
+    pub medication_request: MedicationRequest,
+    /// Present when the visit carries an attending_puid.
+    pub practitioner: Option<Practitioner>,
+    /// Present when the visit also carries an attending_cadre.
+    pub practitioner_role: Option<PractitionerRole>,
+    /// Present when vitals_taken_by names someone other than the attending
+    /// clinician.
+    pub vitals_performer: Option<Practitioner>,
+    /// Instruments the vitals were taken with; shared by every visit that
+    /// used them.
+    pub devices: Vec<Device>,
+    /// Present for SHA/SHIF visits (sha_member_number set).
+    pub sha_claims: Option<ShaClaims>,
+    /// Scanned documents from the visit, linked to its Encounter.
+    pub documents: Vec<DocumentReference>,
+    /// Present for chronic-care programme visits; shared by every visit in
+    /// the same programme.
+    pub episode_of_care: Option<EpisodeOfCare>,
+    /// Programme regimen under `--profile`, or the plan for a hypertension
+    /// or diabetes visit with a review date; addresses the Condition.
+    pub care_plan: Option<CarePlan>,
+    /// Present when the visit has a clinical note.
+    pub clinical_impression: Option<ClinicalImpression>,
+    /// Present for ANC visits.
+    pub antenatal: Option<AntenatalResources>,
+    /// School-health vision and hearing screening Observations.
+    pub screening: Vec<Observation>,
+    /// Present for triaged visits.
+    pub triage: Option<Observation>,
+    /// Danger signs noted at triage, one alert Flag each.
+    pub flags: Vec<Flag>,
+    /// Present when the visit names its department; shared by every visit
+    /// to the same department.
+    pub location: Option<Location>,
+    /// Present when the visit books a next appointment.
+    pub appointment: Option<Appointment>,
+    /// Lab tests ordered at the visit, each with its Specimen.
+    pub lab_orders: Vec<LabOrderResources>,
+    /// Imaging studies ordered at the visit, with their reports and
+    /// attachments.
+    pub imaging_orders: Vec<ImagingResources>,
+}
+
+/// Append a PUT entry for `{resource_type}/{id}`.
+///
+/// Resources shared between visits (Practitioner, PractitionerRole,
+/// Location, Device, Coverage, SHA payer, EpisodeOfCare) are only added once — a transaction
+/// Bundle must not repeat a fullUrl.
+fn push_put_entry(entries: &mut Vec<BundleEntry>, resource_type: &str, id: &str, resource: Value) {
+    let full_url = format!("urn:uuid:{}", id);
+    if entries
+        .iter()
+        .any(|e| e.full_url.as_deref() == Some(full_url.as_str()))
+    {
+        return;
+    }
+    entries.push(BundleEntry {
+        full_url: Some(full_url),
+        resource: Some(resource),
+        request: Some(BundleRequest {
+            method: "PUT".to_string(),
+            url: format!("{}/{}", resource_type, id),
+        }),
+    });
+}
+
 /// Build a FHIR R4 transaction Bundle.
 ///
 /// Every entry gets a `fullUrl` in `urn:uuid:` format so resources can
 /// reference each other before the server assigns real IDs — required by spec.
-/// When sha_claims is Some, Coverage + Claim (preauthorization) + SHA payer
-/// Organization are included — covering the SHA/SHIF workflow.
+/// Each visit contributes its own Encounter, Condition, MedicationRequest and
+/// vitals. When a visit has sha_claims, Coverage + Claim (preauthorization) +
+/// SHA payer Organization are included — covering the SHA/SHIF workflow.
+/// `parent_organizations` (sub-county, county) come after the Patient, so
+/// the facility stays the first Organization and the Patient the second
+/// entry.
+/// `documents` are patient-level DocumentReferences (biometric templates);
+/// a visit's own documents come after its Encounter.
+/// `id` and `timestamp` become Bundle.id and Bundle.timestamp.
 pub fn create_transaction_bundle(
     patient: &Patient,
     organization: &Organization,
-    encounter: &Encounter,
-    observations: &[Observation],
-    condition: &Condition,
-    medication_request: &MedicationRequest,
-    practitioner: Option<&Practitioner>,
-    sha_claims: Option<&ShaClaims>,
+    parent_organizations: &[Organization],
+    documents: &[DocumentReference],
+    visits: &[VisitResources],
+    id: String,
+    timestamp: String,
 ) -> Bundle {
     let mut entries: Vec<BundleEntry> = Vec::new();
 
@@ -35,128 +140,241 @@ pub fn create_transaction_bundle(
 
     // Organization (facility) — must come before Encounter that references it
     let org_id = organization.id.as_ref().expect("organization.id required");
-    entries.push(BundleEntry {
-        full_url: Some(format!("urn:uuid:{}", org_id)),
-        resource: Some(json!(organization)),
-        request: Some(BundleRequest {
-            method: "PUT".to_string(),
-            url: format!("Organization/{}", org_id),
-        }),
-    });
+    push_put_entry(&mut entries, "Organization", org_id, json!(organization));
 
     // Patient
-    entries.push(BundleEntry {
-        full_url: Some(format!("urn:uuid:{}", patient_id)),
-        resource: Some(json!(patient)),
-        request: Some(BundleRequest {
-            method: "PUT".to_string(),
-            url: format!("Patient/{}", patient_id),
-        }),
-    });
+    push_put_entry(&mut entries, "Patient", patient_id, json!(patient));
 
-    // Encounter
-    let enc_id = encounter.id.as_ref().expect("encounter.id required");
-    entries.push(BundleEntry {
-        full_url: Some(format!("urn:uuid:{}", enc_id)),
-        resource: Some(json!(encounter)),
-        request: Some(BundleRequest {
-            method: "PUT".to_string(),
-            url: format!("Encounter/{}", enc_id),
-        }),
-    });
+    // DocumentReference (biometric templates) — after the Patient they describe
+    for doc in documents {
+        let doc_id = doc.id.as_ref().expect("document_reference.id required");
+        push_put_entry(&mut entries, "DocumentReference", doc_id, json!(doc));
+    }
 
-    // Condition (diagnosis)
-    let cond_id = condition.id.as_ref().expect("condition.id required");
-    entries.push(BundleEntry {
-        full_url: Some(format!("urn:uuid:{}", cond_id)),
-        resource: Some(json!(condition)),
-        request: Some(BundleRequest {
-            method: "PUT".to_string(),
-            url: format!("Condition/{}", cond_id),
-        }),
-    });
+    // Sub-county and county Organizations the facility is partOf
+    for parent in parent_organizations {
+        let parent_id = parent.id.as_ref().expect("organization.id required");
+        push_put_entry(&mut entries, "Organization", parent_id, json!(parent));
+    }
 
-    // MedicationRequest (treatment)
-    let med_id = medication_request
-        .id
-        .as_ref()
-        .expect("medication_request.id required");
-    entries.push(BundleEntry {
-        full_url: Some(format!("urn:uuid:{}", med_id)),
-        resource: Some(json!(medication_request)),
-        request: Some(BundleRequest {
-            method: "PUT".to_string(),
-            url: format!("MedicationRequest/{}", med_id),
-        }),
-    });
+    for visit in visits {
+        // Location (department) — before the Encounters that reference it
+        if let Some(location) = &visit.location {
+            let loc_id = location.id.as_ref().expect("location.id required");
+            push_put_entry(&mut entries, "Location", loc_id, json!(location));
+        }
 
-    // Observations (vitals)
-    for obs in observations {
-        let oid = obs.id.as_ref().expect("observation.id required");
-        entries.push(BundleEntry {
-            full_url: Some(format!("urn:uuid:{}", oid)),
-            resource: Some(json!(obs)),
-            request: Some(BundleRequest {
-                method: "PUT".to_string(),
-                url: format!("Observation/{}", oid),
-            }),
-        });
-    }
+        // EpisodeOfCare — before the Encounters that reference it
+        if let Some(episode) = &visit.episode_of_care {
+            let eoc_id = episode.id.as_ref().expect("episode_of_care.id required");
+            push_put_entry(&mut entries, "EpisodeOfCare", eoc_id, json!(episode));
+        }
 
-    // Practitioner (HWR PUID) — included when attending_puid is present
-    if let Some(prac) = practitioner {
-        let prac_id = prac.id.as_ref().expect("practitioner.id required");
-        entries.push(BundleEntry {
-            full_url: Some(format!("urn:uuid:{}", prac_id)),
-            resource: Some(json!(prac)),
-            request: Some(BundleRequest {
-                method: "PUT".to_string(),
-                url: format!("Practitioner/{}", prac_id),
-            }),
-        });
-    }
+        // Encounter
+        let enc_id = visit.encounter.id.as_ref().expect("encounter.id required");
+        push_put_entry(&mut entries, "Encounter", enc_id, json!(&visit.encounter));
+
+        // DocumentReference (scanned notes) — reference the Encounter above
+        for doc in &visit.documents {
+            let doc_id = doc.id.as_ref().expect("document_reference.id required");
+            push_put_entry(&mut entries, "DocumentReference", doc_id, json!(doc));
+        }
+
+        // Condition (diagnosis)
+        let cond_id = visit.condition.id.as_ref().expect("condition.id required");
+        push_put_entry(&mut entries, "Condition", cond_id, json!(&visit.condition));
+
+        // CarePlan (programme regimen) — addresses the Condition above
+        if let Some(plan) = &visit.care_plan {
+            let plan_id = plan.id.as_ref().expect("care_plan.id required");
+            push_put_entry(&mut entries, "CarePlan", plan_id, json!(plan));
+        }
+
+        // ClinicalImpression (clinical note) — assesses the Condition above
+        if let Some(impression) = &visit.clinical_impression {
+            let ci_id = impression
+                .id
+                .as_ref()
+                .expect("clinical_impression.id required");
+            push_put_entry(&mut entries, "ClinicalImpression", ci_id, json!(impression));
+        }
 
-    // SHA Coverage + Claim + payer Organization — included for SHA/SHIF visits
-    if let Some(sha) = sha_claims {
-        // SHA payer Organization
-        let payer_id = &sha.payer_org.id;
-        entries.push(BundleEntry {
-            full_url: Some(format!("urn:uuid:{}", payer_id)),
-            resource: Some(json!(&sha.payer_org)),
-            request: Some(BundleRequest {
-                method: "PUT".to_string(),
-                url: format!("Organization/{}", payer_id),
-            }),
-        });
-
-        // Coverage
-        let cov_id = sha.coverage.id.as_deref().expect("coverage.id required");
-        entries.push(BundleEntry {
-            full_url: Some(format!("urn:uuid:{}", cov_id)),
-            resource: Some(json!(&sha.coverage)),
-            request: Some(BundleRequest {
-                method: "PUT".to_string(),
-                url: format!("Coverage/{}", cov_id),
-            }),
-        });
-
-        // Claim (preauthorization)
-        let claim_id = sha.claim.id.as_deref().expect("claim.id required");
-        entries.push(BundleEntry {
-            full_url: Some(format!("urn:uuid:{}", claim_id)),
-            resource: Some(json!(&sha.claim)),
-            request: Some(BundleRequest {
-                method: "POST".to_string(),
-                url: "Claim".to_string(),
-            }),
-        });
+        // MedicationRequest (treatment)
+        let med_id = visit
+            .medication_request
+            .id
+            .as_ref()
+            .expect("medication_request.id required");
+        push_put_entry(
+            &mut entries,
+            "MedicationRequest",
+            med_id,
+            json!(&visit.medication_request),
+        );
+
+        // ServiceRequest + Specimen (lab orders)
+        for order in &visit.lab_orders {
+            let sr = &order.service_request;
+            let sr_id = sr.id.as_ref().expect("service_request.id required");
+            push_put_entry(&mut entries, "ServiceRequest", sr_id, json!(sr));
+            let spec_id = order.specimen.id.as_ref().expect("specimen.id required");
+            push_put_entry(&mut entries, "Specimen", spec_id, json!(&order.specimen));
+        }
+
+        // ServiceRequest + DiagnosticReport (imaging), then the images and
+        // report PDFs that relate to them
+        for order in &visit.imaging_orders {
+            let sr = &order.service_request;
+            let sr_id = sr.id.as_ref().expect("service_request.id required");
+            push_put_entry(&mut entries, "ServiceRequest", sr_id, json!(sr));
+            if let Some(report) = &order.diagnostic_report {
+                let dr_id = report.id.as_ref().expect("diagnostic_report.id required");
+                push_put_entry(&mut entries, "DiagnosticReport", dr_id, json!(report));
+            }
+            for doc in &order.documents {
+                let doc_id = doc.id.as_ref().expect("document_reference.id required");
+                push_put_entry(&mut entries, "DocumentReference", doc_id, json!(doc));
+            }
+        }
+
+        // Observations (vitals)
+        for obs in &visit.observations {
+            let oid = obs.id.as_ref().expect("observation.id required");
+            push_put_entry(&mut entries, "Observation", oid, json!(obs));
+        }
+
+        // Observation (triage category)
+        if let Some(triage) = &visit.triage {
+            let tid = triage.id.as_ref().expect("observation.id required");
+            push_put_entry(&mut entries, "Observation", tid, json!(triage));
+        }
+
+        // Flags (triage danger signs)
+        for flag in &visit.flags {
+            let fid = flag.id.as_ref().expect("flag.id required");
+            push_put_entry(&mut entries, "Flag", fid, json!(flag));
+        }
+
+        // Pregnancy Condition + ANC Observations — included for ANC visits
+        if let Some(anc) = &visit.antenatal {
+            let preg_id = anc.pregnancy.id.as_ref().expect("condition.id required");
+            push_put_entry(&mut entries, "Condition", preg_id, json!(&anc.pregnancy));
+            for obs in &anc.observations {
+                let oid = obs.id.as_ref().expect("observation.id required");
+                push_put_entry(&mut entries, "Observation", oid, json!(obs));
+            }
+        }
+
+        // Observations (school-health screening)
+        for obs in &visit.screening {
+            let oid = obs.id.as_ref().expect("observation.id required");
+            push_put_entry(&mut entries, "Observation", oid, json!(obs));
+        }
+
+        // Practitioner (HWR PUID) — included when attending_puid is present
+        if let Some(prac) = &visit.practitioner {
+            let prac_id = prac.id.as_ref().expect("practitioner.id required");
+            push_put_entry(&mut entries, "Practitioner", prac_id, json!(prac));
+        }
+        // PractitionerRole — after the Practitioner and Organization it links
+        if let Some(role) = &visit.practitioner_role {
+            let role_id = role.id.as_ref().expect("practitioner_role.id required");
+            push_put_entry(&mut entries, "PractitionerRole", role_id, json!(role));
+        }
+        // Practitioner who took the vitals, when not the attending clinician
+        if let Some(prac) = &visit.vitals_performer {
+            let prac_id = prac.id.as_ref().expect("practitioner.id required");
+            push_put_entry(&mut entries, "Practitioner", prac_id, json!(prac));
+        }
+        // Devices — the vitals instruments
+        for device in &visit.devices {
+            let device_id = device.id.as_ref().expect("device.id required");
+            push_put_entry(&mut entries, "Device", device_id, json!(device));
+        }
+        // Appointment — the follow-up, after the Practitioner it books
+        if let Some(appointment) = &visit.appointment {
+            let appt_id = appointment.id.as_ref().expect("appointment.id required");
+            push_put_entry(&mut entries, "Appointment", appt_id, json!(appointment));
+        }
+
+        // SHA Coverage + Claim + payer Organization — included for SHA/SHIF visits
+        if let Some(sha) = &visit.sha_claims {
+            // SHA payer Organization
+            let payer_id = &sha.payer_org.id;
+            push_put_entry(
+                &mut entries,
+                "Organization",
+                payer_id,
+                json!(&sha.payer_org),
+            );
+
+            // Coverage
+            let cov_id = sha.coverage.id.as_deref().expect("coverage.id required");
+            push_put_entry(&mut entries, "Coverage", cov_id, json!(&sha.coverage));
+
+            // Claim (preauthorization)
+            let claim_id = sha.claim.id.as_deref().expect("claim.id required");
+            entries.push(BundleEntry {
+                full_url: Some(format!("urn:uuid:{}", claim_id)),
+                resource: Some(json!(&sha.claim)),
+                request: Some(BundleRequest {
+                    method: "POST".to_string(),
+                    url: "Claim".to_string(),
+                }),
+            });
+        }
     }
 
     Bundle {
         resource_type: "Bundle".to_string(),
-        id: Some(Uuid::new_v4().to_string()),
-        timestamp: Some(Utc::now().to_rfc3339()),
+        id: Some(id),
+        meta: None,
+        timestamp: Some(timestamp),
         bundle_type: Some("transaction".to_string()),
         entry: Some(entries),
+        signature: None,
+        extra: Default::default(),
     }
 }
+
+/// Add the KEPI forecast after the visits it was made from.
+pub fn add_immunization_recommendation(
+    bundle: &mut Bundle,
+    recommendation: &ImmunizationRecommendation,
+) {
+    let id = recommendation
+        .id
+        .as_ref()
+        .expect("immunization_recommendation.id required");
+    let entries = bundle.entry.get_or_insert_with(Vec::new);
+    push_put_entry(
+        entries,
+        "ImmunizationRecommendation",
+        id,
+        json!(recommendation),
+    );
+}
+
+/// Add an OperationOutcome (`oo-{patient_id}`) with one `processing` error
+/// per resource that was left out of the bundle.
+pub fn add_operation_outcome(bundle: &mut Bundle, patient_id: &str, skipped: &[String]) {
+    let id = format!("oo-{}", patient_id);
+    let outcome = OperationOutcome {
+        resource_type: "OperationOutcome".to_string(),
+        id: Some(id.clone()),
+        meta: None,
+        issue: skipped
+            .iter()
+            .map(|note| OperationOutcomeIssue {
+                severity: "error".to_string(),
+                code: "processing".to_string(),
+                details: None,
+                diagnostics: Some(note.clone()),
+                expression: None,
+            })
+            .collect(),
+        extra: Default::default(),
+    };
+    let entries = bundle.entry.get_or_insert_with(Vec::new);
+    push_put_entry(entries, "OperationOutcome", &id, json!(outcome));
+}
@@ -0,0 +1,232 @@
+//! FHIR release of the generated bundle.
+//!
+//! The mappers build R4, which AfyaLink and the SHR take today. For the R5
+//! pilots on the DHA roadmap, [`FhirVersion::apply`] rewrites the elements
+//! the bridge emits that R5 renamed or retyped. R4B changed none of them, so
+//! an R4B bundle is the R4 one. `bundle lint` and `bundle to-kenyan` read
+//! R4 only.
+use fhir_parser::fhir::bundle::Bundle;
+use serde_json::{json, Map, Value};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FhirVersion {
+    #[default]
+    R4,
+    R4B,
+    R5,
+}
+
+impl FhirVersion {
+    /// Rewrite a bundle as the mappers build it (R4) for this release.
+    pub fn apply(self, bundle: &mut Bundle) {
+        if self != FhirVersion::R5 {
+            return;
+        }
+        for resource in bundle
+            .entry
+            .iter_mut()
+            .flatten()
+            .filter_map(|e| e.resource.as_mut())
+            .filter_map(Value::as_object_mut)
+        {
+            match resource.get("resourceType").and_then(Value::as_str) {
+                Some("Encounter") => encounter_r5(resource),
+                Some("MedicationRequest") => medication_request_r5(resource),
+                Some("ServiceRequest") => service_request_r5(resource),
+                Some("CarePlan") => care_plan_r5(resource),
+                Some("ClinicalImpression") => rename(resource, "assessor", "performer"),
+                Some("DocumentReference") => document_reference_r5(resource),
+                Some("Coverage") => coverage_r5(resource),
+                Some("Location") => rename(resource, "physicalType", "form"),
+                Some("Organization") => organization_r5(resource),
+                Some("Device") => device_r5(resource),
+                Some("Appointment") => appointment_r5(resource),
+                Some("ImmunizationRecommendation") => immunization_recommendation_r5(resource),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn rename(object: &mut Map<String, Value>, from: &str, to: &str) {
+    if let Some(value) = object.remove(from) {
+        object.insert(to.to_string(), value);
+    }
+}
+
+/// The objects in the array `object[key]`.
+fn objects_mut<'a>(
+    object: &'a mut Map<String, Value>,
+    key: &str,
+) -> impl Iterator<Item = &'a mut Map<String, Value>> {
+    object
+        .get_mut(key)
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
+
+/// R5: status `finished` is `completed`; class is a list of
+/// CodeableConcepts; period is actualPeriod; reasonCode becomes reason
+/// (one CodeableReference each); participant.individual is actor; a
+/// diagnosis condition is a CodeableReference and has no rank.
+fn encounter_r5(encounter: &mut Map<String, Value>) {
+    if encounter.get("status").and_then(Value::as_str) == Some("finished") {
+        encounter.insert("status".into(), "completed".into());
+    }
+    if let Some(class) = encounter.remove("class") {
+        encounter.insert("class".into(), json!([{ "coding": [class] }]));
+    }
+    rename(encounter, "period", "actualPeriod");
+    if let Some(Value::Array(reasons)) = encounter.remove("reasonCode") {
+        let reason: Vec<Value> = reasons
+            .into_iter()
+            .map(|concept| json!({ "value": [{ "concept": concept }] }))
+            .collect();
+        encounter.insert("reason".into(), reason.into());
+    }
+    for participant in objects_mut(encounter, "participant") {
+        rename(participant, "individual", "actor");
+    }
+    for diagnosis in objects_mut(encounter, "diagnosis") {
+        if let Some(condition) = diagnosis.remove("condition") {
+            diagnosis.insert("condition".into(), json!([{ "reference": condition }]));
+        }
+        if let Some(role) = diagnosis.remove("use") {
+            diagnosis.insert("use".into(), json!([role]));
+        }
+        diagnosis.remove("rank");
+    }
+}
+
+/// R5: medication[x] is a CodeableReference; Dosage.asNeededBoolean is asNeeded.
+fn medication_request_r5(request: &mut Map<String, Value>) {
+    if let Some(concept) = request.remove("medicationCodeableConcept") {
+        request.insert("medication".into(), json!({ "concept": concept }));
+    }
+    for dosage in objects_mut(request, "dosageInstruction") {
+        rename(dosage, "asNeededBoolean", "asNeeded");
+    }
+}
+
+/// R5: code is a CodeableReference; reasonReference becomes reason.
+fn service_request_r5(request: &mut Map<String, Value>) {
+    if let Some(concept) = request.remove("code") {
+        request.insert("code".into(), json!({ "concept": concept }));
+    }
+    if let Some(Value::Array(reasons)) = request.remove("reasonReference") {
+        let reason: Vec<Value> = reasons
+            .into_iter()
+            .map(|reference| json!({ "reference": reference }))
+            .collect();
+        request.insert("reason".into(), reason.into());
+    }
+}
+
+/// R5: addresses are CodeableReferences; activity.reference is
+/// plannedActivityReference, and activity.detail is gone (the review date
+/// stays in period.end).
+fn care_plan_r5(plan: &mut Map<String, Value>) {
+    if let Some(Value::Array(addresses)) = plan.remove("addresses") {
+        let addresses: Vec<Value> = addresses
+            .into_iter()
+            .map(|reference| json!({ "reference": reference }))
+            .collect();
+        plan.insert("addresses".into(), addresses.into());
+    }
+    if let Some(Value::Array(activities)) = plan.remove("activity") {
+        let activities: Vec<Value> = activities
+            .into_iter()
+            .filter_map(|activity| activity.get("reference").cloned())
+            .map(|reference| json!({ "plannedActivityReference": reference }))
+            .collect();
+        if !activities.is_empty() {
+            plan.insert("activity".into(), activities.into());
+        }
+    }
+}
+
+/// R5: context is the list of encounter references itself, and a related
+/// order is basedOn (R5 has no link to the report); content.format moved to
+/// content.profile.
+fn document_reference_r5(document: &mut Map<String, Value>) {
+    if let Some(mut context) = document.remove("context") {
+        if let Some(encounters) = context.get_mut("encounter").map(Value::take) {
+            document.insert("context".into(), encounters);
+        }
+        if let Some(Value::Array(related)) = context.get_mut("related").map(Value::take) {
+            let orders: Vec<Value> = related
+                .into_iter()
+                .filter(|r| {
+                    r.get("reference")
+                        .and_then(Value::as_str)
+                        .is_some_and(|r| r.starts_with("ServiceRequest/"))
+                })
+                .collect();
+            if !orders.is_empty() {
+                document.insert("basedOn".into(), orders.into());
+            }
+        }
+    }
+    for content in objects_mut(document, "content") {
+        if let Some(format) = content.remove("format") {
+            content.insert("profile".into(), json!([{ "valueCoding": format }]));
+        }
+    }
+}
+
+/// R5: telecom and address moved into contact (ExtendedContactDetail),
+/// which takes one address.
+fn organization_r5(organization: &mut Map<String, Value>) {
+    let mut contact = Map::new();
+    if let Some(telecom) = organization.remove("telecom") {
+        contact.insert("telecom".into(), telecom);
+    }
+    if let Some(Value::Array(mut addresses)) = organization.remove("address") {
+        if !addresses.is_empty() {
+            contact.insert("address".into(), addresses.swap_remove(0));
+        }
+    }
+    if !contact.is_empty() {
+        organization.insert("contact".into(), json!([contact]));
+    }
+}
+
+/// R5: reasonReference becomes reason (one CodeableReference each).
+fn appointment_r5(appointment: &mut Map<String, Value>) {
+    if let Some(Value::Array(reasons)) = appointment.remove("reasonReference") {
+        let reason: Vec<Value> = reasons
+            .into_iter()
+            .map(|reference| json!({ "reference": reference }))
+            .collect();
+        appointment.insert("reason".into(), reason.into());
+    }
+}
+
+/// R5: type is a list.
+fn device_r5(device: &mut Map<String, Value>) {
+    if let Some(device_type) = device.remove("type") {
+        device.insert("type".into(), json!([device_type]));
+    }
+}
+
+/// R5: doseNumber is a string.
+fn immunization_recommendation_r5(recommendation: &mut Map<String, Value>) {
+    for dose in objects_mut(recommendation, "recommendation") {
+        rename(dose, "doseNumberString", "doseNumber");
+        if let Some(number) = dose.remove("doseNumberPositiveInt") {
+            dose.insert("doseNumber".into(), number.to_string().into());
+        }
+    }
+}
+
+/// R5: payor is gone; the payer is the insurer, and kind is required.
+fn coverage_r5(coverage: &mut Map<String, Value>) {
+    if let Some(Value::Array(mut payors)) = coverage.remove("payor") {
+        if !payors.is_empty() {
+            coverage.insert("insurer".into(), payors.swap_remove(0));
+        }
+    }
+    coverage.insert("kind".into(), "insurance".into());
+}
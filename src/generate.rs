@@ -0,0 +1,433 @@
+//! Synthetic Kenyan clinic records for load tests and fixtures.
+//!
+//! Records are plausible rather than real: common names, counties and
+//! sub-counties, ages skewed towards children and young adults, diagnoses
+//! drawn roughly in MOH 705 proportions with complaints, vitals and
+//! treatments to match. The same seed always gives the same records. No
+//! value is taken from a real patient; identifiers only have the right shape.
+use chrono::{Duration, NaiveDate};
+
+use crate::kenyan::schema::{DiagnosisCategory, KenyanPatient, Location, Names, Visit, Vitals};
+
+#[derive(Debug, Clone)]
+pub struct GenerateOptions {
+    pub count: usize,
+    pub seed: u64,
+    /// Facilities records are spread over
+    pub clinics: Vec<String>,
+    /// Visit dates are drawn from `[from, to]`
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Each record gets 1..=max_visits visits
+    pub max_visits: usize,
+    /// Share of visits (0–1) billed to SHA, with member number and
+    /// intervention code
+    pub sha_share: f64,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self {
+            count: 100,
+            seed: 1,
+            clinics: vec!["KEN-NAIROBI-001".to_string()],
+            from: NaiveDate::from_ymd_opt(2026, 1, 1).unwrap(),
+            to: NaiveDate::from_ymd_opt(2026, 12, 31).unwrap(),
+            max_visits: 1,
+            sha_share: 0.3,
+        }
+    }
+}
+
+/// splitmix64; enough for fixtures and keeps the crate free of a `rand`
+/// dependency.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in `[low, high]`.
+    fn range(&mut self, low: i64, high: i64) -> i64 {
+        low + (self.next() % (high - low + 1) as u64) as i64
+    }
+
+    fn uniform(&mut self, low: f64, high: f64) -> f64 {
+        low + self.unit() * (high - low)
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.next() as usize % items.len()]
+    }
+
+    fn chance(&mut self, p: f64) -> bool {
+        self.unit() < p
+    }
+}
+
+const FEMALE_NAMES: &[&str] = &[
+    "Wanjiru",
+    "Akinyi",
+    "Njeri",
+    "Atieno",
+    "Chebet",
+    "Mwende",
+    "Amina",
+    "Nafula",
+    "Wairimu",
+    "Achieng",
+    "Jepkosgei",
+    "Kawira",
+    "Faith",
+    "Mercy",
+    "Grace",
+    "Halima",
+];
+const MALE_NAMES: &[&str] = &[
+    "Kamau", "Otieno", "Kiprono", "Mutua", "Omondi", "Wekesa", "Hassan", "Kipchoge", "Mwangi",
+    "Ochieng", "Barasa", "Kibet", "Brian", "Dennis", "Joseph", "Abdi",
+];
+const SURNAMES: &[&str] = &[
+    "Kamau",
+    "Otieno",
+    "Wanjiku",
+    "Odhiambo",
+    "Kiprotich",
+    "Mutiso",
+    "Wafula",
+    "Njoroge",
+    "Onyango",
+    "Chepkoech",
+    "Mohamed",
+    "Kariuki",
+    "Nyambura",
+    "Owino",
+    "Rotich",
+    "Musyoka",
+];
+const LOCATIONS: &[(&str, &[&str])] = &[
+    (
+        "Nairobi",
+        &["Westlands", "Kasarani", "Embakasi East", "Langata"],
+    ),
+    ("Kisumu", &["Kisumu Central", "Kisumu East", "Nyando"]),
+    ("Mombasa", &["Mvita", "Likoni", "Nyali"]),
+    ("Nakuru", &["Nakuru Town East", "Naivasha", "Molo"]),
+    ("Kakamega", &["Lurambi", "Mumias East", "Butere"]),
+    ("Machakos", &["Machakos Town", "Mavoko", "Kangundo"]),
+    ("Uasin Gishu", &["Ainabkoi", "Kapseret", "Turbo"]),
+    ("Garissa", &["Garissa Township", "Dadaab"]),
+];
+
+#[derive(Clone, Copy)]
+enum Profile {
+    Febrile,
+    Hypertensive,
+    Hypoxic,
+    Routine,
+}
+
+struct Presentation {
+    diagnosis: &'static str,
+    complaint: &'static str,
+    treatment: &'static str,
+    profile: Profile,
+    weight: u32,
+}
+
+// Weights follow the usual MOH 705 ordering at a county facility.
+const PRESENTATIONS: &[Presentation] = &[
+    Presentation {
+        diagnosis: "Upper respiratory tract infection",
+        complaint: "Cough and runny nose",
+        treatment: "Paracetamol 500mg TDS for 3 days",
+        profile: Profile::Febrile,
+        weight: 30,
+    },
+    Presentation {
+        diagnosis: "Malaria",
+        complaint: "Fever, chills and headache",
+        treatment: "Artemether-lumefantrine 80/480mg BD for 3 days",
+        profile: Profile::Febrile,
+        weight: 18,
+    },
+    Presentation {
+        diagnosis: "Diarrhoea",
+        complaint: "Loose stools for two days",
+        treatment: "ORS and zinc 20mg OD for 10 days",
+        profile: Profile::Routine,
+        weight: 12,
+    },
+    Presentation {
+        diagnosis: "Urinary tract infection",
+        complaint: "Painful urination",
+        treatment: "Nitrofurantoin 100mg BD for 5 days",
+        profile: Profile::Routine,
+        weight: 8,
+    },
+    Presentation {
+        diagnosis: "Hypertension",
+        complaint: "Headache and dizziness",
+        treatment: "Amlodipine 5mg once daily",
+        profile: Profile::Hypertensive,
+        weight: 8,
+    },
+    Presentation {
+        diagnosis: "Pneumonia",
+        complaint: "Cough and difficulty breathing",
+        treatment: "Amoxicillin 500mg TDS for 5 days",
+        profile: Profile::Hypoxic,
+        weight: 6,
+    },
+    Presentation {
+        diagnosis: "Skin infection",
+        complaint: "Itchy rash",
+        treatment: "Cloxacillin 500mg QID for 5 days",
+        profile: Profile::Routine,
+        weight: 6,
+    },
+    Presentation {
+        diagnosis: "Type 2 diabetes mellitus",
+        complaint: "Frequent urination and thirst",
+        treatment: "Metformin 500mg BD",
+        profile: Profile::Routine,
+        weight: 4,
+    },
+    Presentation {
+        diagnosis: "Pulmonary tuberculosis",
+        complaint: "Cough for more than two weeks and night sweats",
+        treatment: "Referred to TB clinic for RHZE",
+        profile: Profile::Hypoxic,
+        weight: 2,
+    },
+    Presentation {
+        diagnosis: "No acute illness",
+        complaint: "Routine check-up",
+        treatment: "Health education",
+        profile: Profile::Routine,
+        weight: 6,
+    },
+];
+
+fn presentation(rng: &mut Rng) -> &'static Presentation {
+    let total: u32 = PRESENTATIONS.iter().map(|p| p.weight).sum();
+    let mut roll = rng.range(0, total as i64 - 1) as u32;
+    for p in PRESENTATIONS {
+        if roll < p.weight {
+            return p;
+        }
+        roll -= p.weight;
+    }
+    &PRESENTATIONS[0]
+}
+
+/// Rough weight-for-age; children by the WHO median, adults in a broad band.
+fn body_weight(rng: &mut Rng, age_years: i64) -> f64 {
+    let kg = match age_years {
+        0 => rng.uniform(3.0, 9.5),
+        1..=4 => rng.uniform(9.0, 18.0),
+        5..=12 => 18.0 + (age_years - 5) as f64 * 3.0 + rng.uniform(-2.0, 4.0),
+        13..=17 => rng.uniform(38.0, 65.0),
+        _ => rng.uniform(48.0, 95.0),
+    };
+    (kg * 10.0).round() / 10.0
+}
+
+fn vitals(rng: &mut Rng, profile: Profile, age_years: i64) -> Vitals {
+    let (temperature, systolic, diastolic, spo2) = match profile {
+        Profile::Febrile => (
+            rng.uniform(37.8, 39.8),
+            rng.range(100, 130),
+            rng.range(60, 85),
+            rng.uniform(95.0, 99.0),
+        ),
+        Profile::Hypertensive => (
+            rng.uniform(36.3, 37.2),
+            rng.range(145, 185),
+            rng.range(92, 115),
+            rng.uniform(95.0, 99.0),
+        ),
+        Profile::Hypoxic => (
+            rng.uniform(37.2, 39.0),
+            rng.range(100, 130),
+            rng.range(60, 85),
+            rng.uniform(86.0, 94.0),
+        ),
+        Profile::Routine => (
+            rng.uniform(36.2, 37.3),
+            rng.range(105, 135),
+            rng.range(65, 88),
+            rng.uniform(96.0, 100.0),
+        ),
+    };
+    let pulse = if age_years < 5 {
+        rng.range(95, 140)
+    } else {
+        rng.range(62, 100)
+    };
+    Vitals {
+        temperature_celsius: (temperature * 10.0).round() / 10.0,
+        bp_systolic: systolic as i32,
+        bp_diastolic: diastolic as i32,
+        weight_kg: body_weight(rng, age_years),
+        pulse_rate: rng.chance(0.8).then_some(pulse as i32),
+        o2_saturation: rng
+            .chance(0.6)
+            .then(|| (spo2.min(100.0) * 10.0).round() / 10.0),
+        height_cm: None,
+        muac_cm: None,
+        devices: Vec::new(),
+    }
+}
+
+/// Age in years: a quarter under five, most of the rest working-age adults.
+fn age(rng: &mut Rng) -> i64 {
+    let roll = rng.unit();
+    if roll < 0.25 {
+        rng.range(0, 4)
+    } else if roll < 0.4 {
+        rng.range(5, 17)
+    } else if roll < 0.9 {
+        rng.range(18, 59)
+    } else {
+        rng.range(60, 90)
+    }
+}
+
+fn patient(rng: &mut Rng, index: usize, options: &GenerateOptions) -> KenyanPatient {
+    let female = rng.chance(0.55);
+    let first = rng.pick(if female { FEMALE_NAMES } else { MALE_NAMES });
+    let middle = if rng.chance(0.7) {
+        rng.pick(if female { FEMALE_NAMES } else { MALE_NAMES })
+            .to_string()
+    } else {
+        String::new()
+    };
+    let (county, subcounties) = rng.pick(LOCATIONS);
+    let clinic_id = rng.pick(&options.clinics).clone();
+
+    let span = (options.to - options.from).num_days().max(0);
+    let mut dates: Vec<NaiveDate> = (0..rng.range(1, options.max_visits.max(1) as i64))
+        .map(|_| options.from + Duration::days(rng.range(0, span)))
+        .collect();
+    dates.sort();
+    let age_years = age(rng);
+    let date_of_birth = dates[0] - Duration::days(age_years * 365 + rng.range(0, 364));
+
+    let sha_member_number = format!("SHA/{}/{:06}", rng.range(2024, 2026), rng.range(1, 999_999));
+    let puid = format!("HWR-KE-{:05}", rng.range(10_000, 99_999));
+    let visits = dates
+        .into_iter()
+        .map(|date| {
+            let p = presentation(rng);
+            let sha = rng.chance(options.sha_share);
+            Visit {
+                date: date.format("%Y-%m-%d").to_string(),
+                complaint: p.complaint.to_string(),
+                vitals: vitals(rng, p.profile, age_years),
+                diagnosis: p.diagnosis.to_string(),
+                diagnosis_category: DiagnosisCategory::default(),
+                diagnosis_severity: None,
+                onset_date: None,
+                treatment: p.treatment.to_string(),
+                clinical_note: None,
+                attending_puid: rng.chance(0.8).then(|| puid.clone()),
+                attending_cadre: None,
+                vitals_taken_by: None,
+                sha_member_number: sha.then(|| sha_member_number.clone()),
+                sha_intervention_code: sha.then(|| "SHA-OPD-001".to_string()),
+                attachments: Vec::new(),
+                lab_orders: Vec::new(),
+                imaging_orders: Vec::new(),
+                visit_id: None,
+                previous_visit_id: None,
+                programme: None,
+                hiv_who_stage: None,
+                tb_phase: None,
+                regimen: None,
+                review_date: None,
+                next_appointment_date: None,
+                anc: None,
+                screening: None,
+                triage_category: None,
+                danger_signs: Vec::new(),
+                department: None,
+            }
+        })
+        .collect();
+
+    KenyanPatient {
+        clinic_id,
+        patient_number: format!("{}", 100_000 + index),
+        national_id: format!("{:08}", rng.range(10_000_000, 39_999_999)),
+        names: Names {
+            first: first.to_string(),
+            middle,
+            last: rng.pick(SURNAMES).to_string(),
+        },
+        gender: if female { "F" } else { "M" }.to_string(),
+        date_of_birth,
+        birth_date_estimated: false,
+        phone: format!(
+            "+254{}{:08}",
+            rng.pick(&["7", "1"]),
+            rng.range(0, 99_999_999)
+        ),
+        location: Location {
+            county: county.to_string(),
+            subcounty: rng.pick(subcounties).to_string(),
+        },
+        visits,
+        photo: None,
+        biometrics: Vec::new(),
+        immunizations: Vec::new(),
+        data_quality: Vec::new(),
+    }
+}
+
+/// `options.count` records, reproducible from `options.seed`.
+pub fn generate(options: &GenerateOptions) -> Vec<KenyanPatient> {
+    let mut rng = Rng(options.seed);
+    (0..options.count)
+        .map(|i| patient(&mut rng, i, options))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::validate_kenyan_patient;
+
+    #[test]
+    fn same_seed_same_valid_records() {
+        let options = GenerateOptions {
+            count: 200,
+            seed: 42,
+            max_visits: 3,
+            ..GenerateOptions::default()
+        };
+        let records = generate(&options);
+        for record in &records {
+            validate_kenyan_patient(record).unwrap();
+        }
+        let json = |r: &[KenyanPatient]| serde_json::to_string(r).unwrap();
+        assert_eq!(json(&records), json(&generate(&options)));
+        assert_ne!(
+            json(&records),
+            json(&generate(&GenerateOptions {
+                seed: 43,
+                ..options
+            }))
+        );
+    }
+}
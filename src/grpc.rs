@@ -0,0 +1,301 @@
+//! The transform API over gRPC (`grpc` feature).
+//!
+//! For EMR vendors whose stacks prefer gRPC to REST: transform, validate
+//! and enqueue a record, or stream a batch of records and get each one's
+//! bundle (or error) back as it is done. The service definition is
+//! `proto/kenya_fhir_bridge.proto`. Records and bundles travel as JSON
+//! strings, so the wire contract is the same as everywhere else.
+//!
+//! The mappers, CR lookup and queue are blocking, so each record runs on
+//! tokio's blocking pool, one at a time like the MLLP listener: the queue's
+//! SQLite connection is not shared between threads.
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::fhir_bundle::{bundle_to_json, JsonLayout};
+use crate::kenyan::schema::KenyanPatient;
+use crate::offline_queue::OfflineQueue;
+use crate::transform::{transform, TransformOptions};
+use crate::validation::validate_kenyan_patient;
+
+/// Types and stubs generated from `proto/kenya_fhir_bridge.proto`.
+pub mod proto {
+    tonic::include_proto!("kenya_fhir_bridge.v1");
+}
+
+use proto::batch_result::Outcome;
+use proto::transform_service_server::{TransformService, TransformServiceServer};
+use proto::{BatchResult, EnqueueResponse, RecordRequest, TransformResponse, ValidateResponse};
+
+/// What every call needs, behind one lock.
+struct State {
+    options: TransformOptions,
+    queue: Option<OfflineQueue>,
+}
+
+/// The `TransformService` implementation.
+#[derive(Clone)]
+pub struct TransformApi {
+    state: Arc<Mutex<State>>,
+}
+
+impl TransformApi {
+    /// Transform and validate only; `Enqueue` fails until a queue is added.
+    pub fn new(options: TransformOptions) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                options,
+                queue: None,
+            })),
+        }
+    }
+
+    /// Queue `Enqueue`d bundles here.
+    pub fn with_queue(self, queue: OfflineQueue) -> Self {
+        self.state.lock().expect("gRPC state poisoned").queue = Some(queue);
+        self
+    }
+
+    pub fn into_service(self) -> TransformServiceServer<Self> {
+        TransformServiceServer::new(self)
+    }
+
+    /// Run `work` on the blocking pool with the state locked.
+    async fn run<T, F>(&self, work: F) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&State) -> Result<T> + Send + 'static,
+    {
+        let state = Arc::clone(&self.state);
+        tokio::task::spawn_blocking(move || {
+            let state = state.lock().expect("gRPC state poisoned");
+            work(&state)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("Record handler failed: {}", e)))?
+        .map_err(status)
+    }
+}
+
+/// gRPC status for a library error: the caller's fault or ours, retryable
+/// or not.
+fn status(e: BridgeError) -> Status {
+    let message = e.to_string();
+    match e {
+        BridgeError::Validation(_) | BridgeError::Mapping(_) => Status::invalid_argument(message),
+        BridgeError::Network(_) | BridgeError::Terminology(_) => Status::unavailable(message),
+        BridgeError::Queue(_) | BridgeError::Config(_) => Status::failed_precondition(message),
+        BridgeError::Storage(_) | BridgeError::Io(_) => Status::internal(message),
+    }
+}
+
+fn parse_record(json: &str) -> Result<KenyanPatient> {
+    serde_json::from_str(json).context(BridgeError::Validation, "Invalid Kenyan JSON payload")
+}
+
+fn transform_record(state: &State, json: &str) -> Result<(KenyanPatient, TransformResponse)> {
+    let kenyan = parse_record(json)?;
+    validate_kenyan_patient(&kenyan)
+        .context(BridgeError::Validation, "Patient record failed validation")?;
+    let bundle = transform(&kenyan, &state.options)?;
+    let response = TransformResponse {
+        bundle_id: bundle.id.clone().unwrap_or_default(),
+        bundle_json: bundle_to_json(&bundle, JsonLayout::Compact)?,
+        data_quality: kenyan.data_quality.clone(),
+    };
+    Ok((kenyan, response))
+}
+
+fn validate_record(json: &str) -> ValidateResponse {
+    match parse_record(json).and_then(|k| validate_kenyan_patient(&k).map(|()| k)) {
+        Ok(kenyan) => ValidateResponse {
+            valid: true,
+            error: String::new(),
+            data_quality: kenyan.data_quality,
+        },
+        Err(e) => ValidateResponse {
+            valid: false,
+            error: e.to_string(),
+            data_quality: Vec::new(),
+        },
+    }
+}
+
+fn enqueue_record(state: &State, json: &str) -> Result<EnqueueResponse> {
+    let Some(queue) = &state.queue else {
+        bail!(Config, "This server was started without an offline queue");
+    };
+    let (kenyan, bundle) = transform_record(state, json)?;
+    let row_id = queue.enqueue(
+        &bundle.bundle_id,
+        &bundle.bundle_json,
+        &kenyan.patient_number,
+        &kenyan.clinic_id,
+    )?;
+    Ok(EnqueueResponse {
+        bundle_id: bundle.bundle_id,
+        row_id,
+    })
+}
+
+type BatchStream = Pin<Box<dyn Stream<Item = Result<BatchResult, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl TransformService for TransformApi {
+    async fn transform(
+        &self,
+        request: Request<RecordRequest>,
+    ) -> Result<Response<TransformResponse>, Status> {
+        let json = request.into_inner().record_json;
+        let (_, response) = self
+            .run(move |state| transform_record(state, &json))
+            .await?;
+        Ok(Response::new(response))
+    }
+
+    async fn validate(
+        &self,
+        request: Request<RecordRequest>,
+    ) -> Result<Response<ValidateResponse>, Status> {
+        let json = request.into_inner().record_json;
+        let response = self.run(move |_| Ok(validate_record(&json))).await?;
+        Ok(Response::new(response))
+    }
+
+    async fn enqueue(
+        &self,
+        request: Request<RecordRequest>,
+    ) -> Result<Response<EnqueueResponse>, Status> {
+        let json = request.into_inner().record_json;
+        let response = self.run(move |state| enqueue_record(state, &json)).await?;
+        Ok(Response::new(response))
+    }
+
+    type TransformBatchStream = BatchStream;
+
+    async fn transform_batch(
+        &self,
+        request: Request<Streaming<RecordRequest>>,
+    ) -> Result<Response<Self::TransformBatchStream>, Status> {
+        let mut records = request.into_inner();
+        let (sender, results) = mpsc::channel(16);
+        let api = self.clone();
+        tokio::spawn(async move {
+            let mut index = 0;
+            while let Some(record) = records.next().await {
+                let result = match record {
+                    Ok(RecordRequest { record_json }) => {
+                        let outcome = match api
+                            .run(move |state| transform_record(state, &record_json))
+                            .await
+                        {
+                            Ok((_, bundle)) => Outcome::Bundle(bundle),
+                            Err(status) => Outcome::Error(status.message().to_string()),
+                        };
+                        Ok(BatchResult {
+                            index,
+                            outcome: Some(outcome),
+                        })
+                    }
+                    // The client's stream broke; pass that on and stop
+                    Err(status) => Err(status),
+                };
+                let stop = result.is_err();
+                if sender.send(result).await.is_err() || stop {
+                    break;
+                }
+                index += 1;
+            }
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(results))))
+    }
+}
+
+/// Serve `api` on `addr` until the process is stopped.
+pub fn serve(addr: SocketAddr, api: TransformApi) -> Result<()> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context(BridgeError::Io, "Failed to start the gRPC runtime")?;
+    runtime
+        .block_on(
+            tonic::transport::Server::builder()
+                .add_service(api.into_service())
+                .serve(addr),
+        )
+        .with_context(BridgeError::Network, || {
+            format!("gRPC server on {} failed", addr)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::proto::transform_service_client::TransformServiceClient;
+    use super::*;
+    use tokio_stream::wrappers::TcpListenerStream;
+
+    #[test]
+    fn serves_unary_calls_and_streams_batch_results_in_order() {
+        let record = include_str!("../tests/fixtures/kenyan_patient_1.json");
+        let options = TransformOptions {
+            dry_run: true,
+            ..TransformOptions::default()
+        };
+        let api = TransformApi::new(options).with_queue(OfflineQueue::open_in_memory().unwrap());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(
+                tonic::transport::Server::builder()
+                    .add_service(api.into_service())
+                    .serve_with_incoming(TcpListenerStream::new(listener)),
+            );
+            let mut client = TransformServiceClient::connect(format!("http://{}", addr))
+                .await
+                .unwrap();
+            let request = |json: &str| RecordRequest {
+                record_json: json.to_string(),
+            };
+
+            let bundle = client
+                .transform(request(record))
+                .await
+                .unwrap()
+                .into_inner();
+            assert!(bundle
+                .bundle_json
+                .starts_with("{\"resourceType\":\"Bundle\""));
+
+            let invalid = client.validate(request("{}")).await.unwrap().into_inner();
+            assert!(!invalid.valid && !invalid.error.is_empty());
+            let refused = client.transform(request("{}")).await.unwrap_err();
+            assert_eq!(refused.code(), tonic::Code::InvalidArgument);
+
+            let queued = client.enqueue(request(record)).await.unwrap().into_inner();
+            assert!(queued.row_id > 0);
+
+            let batch = tokio_stream::iter(vec![request(record), request("{}"), request(record)]);
+            let results: Vec<BatchResult> = client
+                .transform_batch(batch)
+                .await
+                .unwrap()
+                .into_inner()
+                .map(|r| r.unwrap())
+                .collect()
+                .await;
+            let outcomes: Vec<_> = results
+                .iter()
+                .map(|r| (r.index, matches!(r.outcome, Some(Outcome::Bundle(_)))))
+                .collect();
+            assert_eq!(outcomes, [(0, true), (1, false), (2, true)]);
+        });
+    }
+}
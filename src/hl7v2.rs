@@ -0,0 +1,380 @@
+//! HL7 v2 adapter: one v2.x message (e.g. ADT^A04 or ADT^A08 from a
+//! facility EMR) → a [`KenyanPatient`] with one visit.
+//!
+//! Fields read:
+//!
+//! | Kenyan field | v2 |
+//! |---|---|
+//! | clinic_id | MSH-4 (sending facility) |
+//! | patient_number | PID-3 with type `MR`/`PI`, else the first PID-3 |
+//! | national_id | PID-3 with type `NI` or `NNKEN` |
+//! | names | PID-5 (family^given^middle) |
+//! | date_of_birth, gender | PID-7, PID-8 |
+//! | location | PID-11 county (XAD-9, else XAD-4) and sub-county (XAD-3) |
+//! | phone | PID-13 |
+//! | visit date | PV1-44, else MSH-7 |
+//! | attending_puid, visit_id | PV1-7, PV1-19 |
+//! | complaint | PV2-3 |
+//! | diagnosis | DG1-3 / DG1-4, joined |
+//! | vitals | OBX by LOINC code in OBX-3 |
+//! | treatment | RXE-2 / RXO-1, joined |
+//!
+//! Like the OpenMRS import, a message without vitals, a diagnosis or a
+//! treatment is refused, never guessed.
+use chrono::NaiveDate;
+
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::kenyan::counties::official_location;
+use crate::kenyan::schema::{
+    gender_code, DiagnosisCategory, KenyanPatient, Location, Names, Visit, Vitals,
+};
+
+/// A parsed message: segments of fields, with the message's own
+/// separators.
+#[derive(Debug)]
+pub struct Message {
+    segments: Vec<Vec<String>>,
+    component: char,
+    repetition: char,
+}
+
+impl Message {
+    /// Parse a message; segments end in CR (LF and CRLF are accepted too).
+    pub fn parse(raw: &str) -> Result<Self> {
+        let raw = raw.trim_start();
+        if !raw.starts_with("MSH") || raw.len() < 8 {
+            bail!(Validation, "HL7 v2 message does not start with MSH");
+        }
+        let mut chars = raw[3..].chars();
+        let field = chars.next().unwrap_or('|');
+        let component = chars.next().unwrap_or('^');
+        let repetition = chars.next().unwrap_or('~');
+        let segments = raw
+            .split(['\r', '\n'])
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| {
+                let mut fields: Vec<String> = s.split(field).map(str::to_string).collect();
+                // MSH-1 is the field separator itself, so MSH field n is at n
+                if fields[0] == "MSH" {
+                    fields.insert(1, field.to_string());
+                }
+                fields
+            })
+            .collect();
+        Ok(Self {
+            segments,
+            component,
+            repetition,
+        })
+    }
+
+    /// Segments with this id, in order.
+    pub fn segments<'a>(&'a self, id: &'a str) -> impl Iterator<Item = &'a [String]> {
+        self.segments
+            .iter()
+            .filter(move |s| s[0] == id)
+            .map(Vec::as_slice)
+    }
+
+    /// Field `n` of the first `id` segment, when not empty.
+    pub fn field(&self, id: &str, n: usize) -> Option<&str> {
+        self.segments
+            .iter()
+            .find(|s| s[0] == id)
+            .and_then(|s| non_empty(s, n))
+    }
+
+    /// Component `c` (1-based) of a field value.
+    pub fn component<'a>(&self, value: &'a str, c: usize) -> Option<&'a str> {
+        value
+            .split(self.component)
+            .nth(c - 1)
+            .filter(|v| !v.is_empty())
+    }
+
+    /// Repetitions of a field value.
+    pub fn repetitions<'a>(&self, value: &'a str) -> impl Iterator<Item = &'a str> {
+        value.split(self.repetition).filter(|v| !v.is_empty())
+    }
+
+    /// MSH-9 message type, e.g. `ADT^A04`.
+    pub fn message_type(&self) -> Option<&str> {
+        self.field("MSH", 9)
+    }
+
+    /// MSH-10, echoed in the acknowledgement.
+    pub fn control_id(&self) -> Option<&str> {
+        self.field("MSH", 10)
+    }
+
+    /// Text of a coded element (CE/CWE): the text component, else the code.
+    fn coded_text(&self, value: &str) -> Option<String> {
+        self.component(value, 2)
+            .or_else(|| self.component(value, 1))
+            .map(str::to_string)
+    }
+}
+
+fn non_empty(segment: &[String], n: usize) -> Option<&str> {
+    segment.get(n).map(String::as_str).filter(|v| !v.is_empty())
+}
+
+/// `YYYYMMDD[HHMM[SS]]` → date.
+fn v2_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()
+}
+
+/// LOINC codes of the vitals the Kenyan schema carries.
+const TEMPERATURE: &str = "8310-5";
+const SYSTOLIC: &str = "8480-6";
+const DIASTOLIC: &str = "8462-4";
+const WEIGHT: &str = "29463-7";
+const PULSE: &str = "8867-4";
+const SPO2: [&str; 2] = ["59408-5", "2708-6"];
+const HEIGHT: &str = "8302-2";
+const MUAC: &str = "56072-2";
+
+/// Convert a message to a Kenyan record with one visit.
+pub fn message_to_kenyan(message: &Message) -> Result<KenyanPatient> {
+    let mut data_quality = Vec::new();
+    let required = |id: &str, n: usize, what: &str| {
+        message
+            .field(id, n)
+            .with_context(BridgeError::Validation, || {
+                format!("HL7 v2 message has no {} ({}-{})", what, id, n)
+            })
+    };
+
+    let identifiers: Vec<(&str, Option<&str>)> = message
+        .repetitions(required("PID", 3, "patient identifier")?)
+        .filter_map(|cx| Some((message.component(cx, 1)?, message.component(cx, 5))))
+        .collect();
+    let of_type = |types: &[&str]| {
+        identifiers
+            .iter()
+            .find(|(_, t)| t.is_some_and(|t| types.contains(&t)))
+            .map(|(id, _)| id.to_string())
+    };
+    let national_id = of_type(&["NI", "NNKEN"]).context(
+        BridgeError::Validation,
+        "HL7 v2 message has no national ID (PID-3 type NI)",
+    )?;
+    let patient_number = of_type(&["MR", "PI"])
+        .or_else(|| {
+            identifiers
+                .iter()
+                .find(|(id, _)| *id != national_id)
+                .map(|(id, _)| id.to_string())
+        })
+        .context(
+            BridgeError::Validation,
+            "HL7 v2 message has no patient number (PID-3 type MR)",
+        )?;
+
+    let name = required("PID", 5, "patient name")?;
+    let address = message.field("PID", 11).unwrap_or_default();
+    let location = Location {
+        county: message
+            .component(address, 9)
+            .or_else(|| message.component(address, 4))
+            .unwrap_or_default()
+            .to_string(),
+        subcounty: message
+            .component(address, 3)
+            .unwrap_or_default()
+            .to_string(),
+    };
+    let phone = message.field("PID", 13).map(|xtn| {
+        message
+            .component(xtn, 1)
+            .or_else(|| message.component(xtn, 12))
+            .unwrap_or_default()
+            .to_string()
+    });
+
+    Ok(KenyanPatient {
+        clinic_id: message
+            .component(required("MSH", 4, "sending facility")?, 1)
+            .unwrap_or_default()
+            .to_string(),
+        patient_number,
+        national_id,
+        names: Names {
+            first: message.component(name, 2).unwrap_or_default().to_string(),
+            middle: message.component(name, 3).unwrap_or_default().to_string(),
+            last: message.component(name, 1).unwrap_or_default().to_string(),
+        },
+        gender: gender_code(
+            message.field("PID", 8).unwrap_or_default(),
+            &mut data_quality,
+        ),
+        date_of_birth: v2_date(required("PID", 7, "date of birth")?).context(
+            BridgeError::Validation,
+            "HL7 v2 date of birth (PID-7) is not YYYYMMDD",
+        )?,
+        birth_date_estimated: false,
+        phone: phone.unwrap_or_default(),
+        location: official_location(location, &mut data_quality),
+        visits: vec![visit(message)?],
+        photo: None,
+        biometrics: Vec::new(),
+        immunizations: Vec::new(),
+        data_quality,
+    })
+}
+
+fn visit(message: &Message) -> Result<Visit> {
+    let date = message
+        .field("PV1", 44)
+        .or_else(|| message.field("MSH", 7))
+        .and_then(v2_date)
+        .context(
+            BridgeError::Validation,
+            "HL7 v2 message has no visit date (PV1-44 or MSH-7)",
+        )?;
+
+    let number = |codes: &[&str]| {
+        message
+            .segments("OBX")
+            .filter(|obx| {
+                non_empty(obx, 3)
+                    .and_then(|code| message.component(code, 1))
+                    .is_some_and(|code| codes.contains(&code))
+            })
+            .find_map(|obx| non_empty(obx, 5)?.trim().parse::<f64>().ok())
+    };
+    let vital = |code: &str, what: &str| {
+        number(&[code]).with_context(BridgeError::Validation, || {
+            format!("HL7 v2 message has no {} (OBX {})", what, code)
+        })
+    };
+    let joined = |values: Vec<String>| Some(values.join("; ")).filter(|v| !v.is_empty());
+
+    let diagnosis = joined(
+        message
+            .segments("DG1")
+            .filter_map(|dg1| {
+                non_empty(dg1, 4)
+                    .map(str::to_string)
+                    .or_else(|| message.coded_text(non_empty(dg1, 3)?))
+            })
+            .collect(),
+    )
+    .context(
+        BridgeError::Validation,
+        "HL7 v2 message has no diagnosis (DG1)",
+    )?;
+    let treatment = joined(
+        message
+            .segments("RXE")
+            .filter_map(|rxe| message.coded_text(non_empty(rxe, 2)?))
+            .chain(
+                message
+                    .segments("RXO")
+                    .filter_map(|rxo| message.coded_text(non_empty(rxo, 1)?)),
+            )
+            .collect(),
+    )
+    .context(
+        BridgeError::Validation,
+        "HL7 v2 message has no treatment (RXE or RXO)",
+    )?;
+
+    Ok(Visit {
+        date: date.to_string(),
+        complaint: message
+            .field("PV2", 3)
+            .and_then(|ce| message.coded_text(ce))
+            .unwrap_or_default(),
+        vitals: Vitals {
+            temperature_celsius: vital(TEMPERATURE, "temperature")?,
+            bp_systolic: vital(SYSTOLIC, "systolic BP")?.round() as i32,
+            bp_diastolic: vital(DIASTOLIC, "diastolic BP")?.round() as i32,
+            weight_kg: vital(WEIGHT, "weight")?,
+            pulse_rate: number(&[PULSE]).map(|v| v.round() as i32),
+            o2_saturation: number(&SPO2),
+            height_cm: number(&[HEIGHT]),
+            muac_cm: number(&[MUAC]),
+            devices: Vec::new(),
+        },
+        diagnosis,
+        diagnosis_category: DiagnosisCategory::default(),
+        diagnosis_severity: None,
+        onset_date: None,
+        treatment,
+        clinical_note: None,
+        attending_puid: message
+            .field("PV1", 7)
+            .and_then(|xcn| message.component(xcn, 1))
+            .map(str::to_string),
+        attending_cadre: None,
+        vitals_taken_by: None,
+        sha_member_number: None,
+        sha_intervention_code: None,
+        attachments: Vec::new(),
+        lab_orders: Vec::new(),
+        imaging_orders: Vec::new(),
+        visit_id: message
+            .field("PV1", 19)
+            .and_then(|cx| message.component(cx, 1))
+            .map(str::to_string),
+        previous_visit_id: None,
+        programme: None,
+        hiv_who_stage: None,
+        tb_phase: None,
+        regimen: None,
+        review_date: None,
+        next_appointment_date: None,
+        anc: None,
+        screening: None,
+        triage_category: None,
+        danger_signs: Vec::new(),
+        department: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADT_A04: &str = "MSH|^~\\&|KENYAEMR|12345|KFB|MOH|20250310091500||ADT^A04|MSG0001|P|2.5\r\
+PID|1||KE-2025-000123^^^12345^MR~23456789^^^KEN^NI||Wanjiru^Grace^Njeri||19900415|F|||Kenyatta Ave^^Nakuru Town East^Nakuru^^KEN^^^Nakuru||0712345678\r\
+PV1|1|O|OPD||||HWR-0042^Otieno^James||||||||||||V-778|||||||||||||||||||||||||20250310090000\r\
+PV2|||^Fever and cough\r\
+OBX|1|NM|8310-5^Body temperature^LN||38.4|Cel\r\
+OBX|2|NM|8480-6^Systolic BP^LN||118|mm[Hg]\r\
+OBX|3|NM|8462-4^Diastolic BP^LN||76|mm[Hg]\r\
+OBX|4|NM|29463-7^Body weight^LN||61.5|kg\r\
+OBX|5|NM|8867-4^Heart rate^LN||92|/min\r\
+DG1|1||J06.9^Acute upper respiratory infection^I10||\r\
+RXE|1|^Paracetamol 500mg tablets\r";
+
+    #[test]
+    fn adt_message_becomes_a_kenyan_record() {
+        let message = Message::parse(ADT_A04).unwrap();
+        assert_eq!(message.message_type(), Some("ADT^A04"));
+        assert_eq!(message.control_id(), Some("MSG0001"));
+
+        let kenyan = message_to_kenyan(&message).unwrap();
+        assert_eq!(kenyan.clinic_id, "12345");
+        assert_eq!(kenyan.patient_number, "KE-2025-000123");
+        assert_eq!(kenyan.national_id, "23456789");
+        assert_eq!(kenyan.names.first, "Grace");
+        assert_eq!(kenyan.names.last, "Wanjiru");
+        assert_eq!(kenyan.gender, "F");
+        assert_eq!(kenyan.location.county, "Nakuru");
+        let visit = &kenyan.visits[0];
+        assert_eq!(visit.date, "2025-03-10");
+        assert_eq!(visit.complaint, "Fever and cough");
+        assert_eq!(visit.vitals.bp_systolic, 118);
+        assert_eq!(visit.vitals.pulse_rate, Some(92));
+        assert_eq!(visit.diagnosis, "Acute upper respiratory infection");
+        assert_eq!(visit.treatment, "Paracetamol 500mg tablets");
+        assert_eq!(visit.attending_puid.as_deref(), Some("HWR-0042"));
+        assert_eq!(visit.visit_id.as_deref(), Some("V-778"));
+
+        let without_weight = ADT_A04.replace("OBX|4|NM|29463-7^Body weight^LN||61.5|kg\r", "");
+        let err = message_to_kenyan(&Message::parse(&without_weight).unwrap()).unwrap_err();
+        assert!(err.to_string().contains("no weight"));
+    }
+}
@@ -0,0 +1,287 @@
+//! Hot-folder intake (`transform --watch`).
+//!
+//! Most EMRs in use export a visit by dropping a file into a shared folder.
+//! Each Kenyan record file (.json or .xml) is handed over once it has
+//! stopped changing, then moved to `processed/`, or to `failed/` next to a
+//! `<name>.error.txt` saying why when the record itself is at fault
+//! (validation, mapping). A file that failed for any other reason (queue,
+//! network, disk) stays where it is and is tried again later. Files already
+//! in the folder at start-up go first, so nothing dropped while the bridge
+//! was down is missed.
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::run_summary::FailureKind;
+
+/// How long a file must go unchanged before it is read, so one the EMR is
+/// still writing is not picked up half-written.
+const SETTLE: Duration = Duration::from_secs(2);
+
+/// Wait before trying a file again after a failure that was not its own.
+const RETRY: Duration = Duration::from_secs(30);
+
+const EXTENSIONS: &[&str] = &["json", "xml"];
+
+pub struct HotFolder {
+    dir: PathBuf,
+    processed: PathBuf,
+    failed: PathBuf,
+}
+
+impl HotFolder {
+    /// The folder, with its `processed/` and `failed/` subfolders created
+    /// if missing.
+    pub fn open(dir: &Path) -> Result<Self> {
+        // Watch events carry absolute paths
+        let dir = dir
+            .canonicalize()
+            .with_context(|| format!("Failed to open watch folder {:?}", dir))?;
+        let folder = Self {
+            processed: dir.join("processed"),
+            failed: dir.join("failed"),
+            dir,
+        };
+        for sub in [&folder.processed, &folder.failed] {
+            fs::create_dir_all(sub).with_context(|| format!("Failed to create {:?}", sub))?;
+        }
+        Ok(folder)
+    }
+
+    fn is_record(&self, path: &Path) -> bool {
+        path.parent() == Some(self.dir.as_path())
+            && path.is_file()
+            && path
+                .extension()
+                .is_some_and(|ext| EXTENSIONS.iter().any(|e| ext == *e))
+    }
+
+    /// The record files in the folder now, each due once it has gone
+    /// [`SETTLE`] without changes.
+    fn existing(&self) -> Result<HashMap<PathBuf, Instant>> {
+        let now = Instant::now();
+        let mut due = HashMap::new();
+        for entry in fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to list {:?}", self.dir))?
+            .flatten()
+        {
+            let path = entry.path();
+            if !self.is_record(&path) {
+                continue;
+            }
+            let age = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .unwrap_or_default();
+            due.insert(path, now + SETTLE.saturating_sub(age));
+        }
+        Ok(due)
+    }
+
+    /// Hand over record files as they appear. `handle` returns what became
+    /// of the record, for the log. Runs until the watch itself fails or a
+    /// file cannot be moved.
+    pub fn watch<F>(&self, mut handle: F) -> Result<()>
+    where
+        F: FnMut(&Path) -> Result<String>,
+    {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(tx).context("Failed to start the folder watcher")?;
+        watcher
+            .watch(&self.dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {:?}", self.dir))?;
+        // Only now, so a file dropped in between is not missed
+        let mut due = self.existing()?;
+        loop {
+            self.process_due(&mut due, &mut handle)?;
+            match rx.recv_timeout(SETTLE / 4) {
+                Ok(event) => {
+                    let event = event.context("Folder watch failed")?;
+                    if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                        for path in event.paths {
+                            due.insert(path, Instant::now() + SETTLE);
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => bail!("Folder watcher stopped"),
+            }
+        }
+    }
+
+    /// Hand over the files that are due, in name order; the number handed
+    /// over. Files to try again stay in `due` with a later time.
+    fn process_due<F>(&self, due: &mut HashMap<PathBuf, Instant>, handle: &mut F) -> Result<usize>
+    where
+        F: FnMut(&Path) -> Result<String>,
+    {
+        let now = Instant::now();
+        let mut ready: Vec<PathBuf> = due
+            .iter()
+            .filter(|(_, at)| **at <= now)
+            .map(|(path, _)| path.clone())
+            .collect();
+        ready.sort();
+        let mut handed = 0;
+        for path in ready {
+            due.remove(&path);
+            // Gone already (moved by us, or by the EMR) or not a record
+            if !self.is_record(&path) {
+                continue;
+            }
+            handed += 1;
+            if !self.process(&path, handle)? {
+                due.insert(path, Instant::now() + RETRY);
+            }
+        }
+        Ok(handed)
+    }
+
+    /// Hand one file over and file it away; `false` when it stays to be
+    /// tried again.
+    fn process<F>(&self, path: &Path, handle: &mut F) -> Result<bool>
+    where
+        F: FnMut(&Path) -> Result<String>,
+    {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        match handle(path) {
+            Ok(outcome) => {
+                move_into(path, &self.processed)?;
+                eprintln!("[WATCH] {}: {}", name, outcome);
+            }
+            Err(e)
+                if matches!(
+                    FailureKind::of(&e),
+                    FailureKind::Validation | FailureKind::Mapping
+                ) =>
+            {
+                let moved = move_into(path, &self.failed)?;
+                let mut note = moved.clone().into_os_string();
+                note.push(".error.txt");
+                fs::write(&note, format!("{:#}\n", e))
+                    .with_context(|| format!("Failed to write {:?}", note))?;
+                eprintln!("[WATCH] {} failed: {:#}", name, e);
+            }
+            Err(e) => {
+                eprintln!(
+                    "[WATCH] {} not processed, trying again in {}s: {:#}",
+                    name,
+                    RETRY.as_secs(),
+                    e
+                );
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Move `path` into `dir`, numbering the name if an earlier export of the
+/// same name is already there.
+fn move_into(path: &Path, dir: &Path) -> Result<PathBuf> {
+    let name = path.file_name().context("No file name")?;
+    let mut to = dir.join(name);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    let mut n = 1;
+    while to.exists() {
+        to = dir.join(format!("{}-{}.{}", stem, n, ext));
+        n += 1;
+    }
+    fs::rename(path, &to).with_context(|| format!("Failed to move {:?} to {:?}", path, to))?;
+    Ok(to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_summary::Failure;
+    use anyhow::anyhow;
+    use kenya_fhir_bridge::error::BridgeError;
+    use std::time::SystemTime;
+
+    /// A file last written `age` ago.
+    fn write_aged(path: &Path, contents: &str, age: Duration) {
+        fs::write(path, contents).unwrap();
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[test]
+    fn sorts_records_into_processed_and_failed() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = Duration::from_secs(60);
+        for name in ["a.json", "b.xml", "c.json", "notes.txt"] {
+            write_aged(&dir.path().join(name), "{}", old);
+        }
+        fs::create_dir(dir.path().join("processed")).unwrap();
+        fs::write(dir.path().join("processed/a.json"), "earlier").unwrap();
+
+        let folder = HotFolder::open(dir.path()).unwrap();
+        let mut due = folder.existing().unwrap();
+        let mut seen = Vec::new();
+        let handed = folder
+            .process_due(&mut due, &mut |path: &Path| {
+                seen.push(path.file_name().unwrap().to_owned());
+                match path.file_name().unwrap().to_str() {
+                    Some("a.json") => Ok("queued".to_string()),
+                    Some("b.xml") => Err(anyhow!("Invalid Kenyan XML payload").context(
+                        Failure::new(FailureKind::Validation, "Patient record failed validation"),
+                    )),
+                    _ => Err(BridgeError::Queue("database is locked".into()).into()),
+                }
+            })
+            .unwrap();
+
+        assert_eq!(handed, 3);
+        assert_eq!(seen, ["a.json", "b.xml", "c.json"]);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("processed/a.json")).unwrap(),
+            "earlier"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("processed/a-1.json")).unwrap(),
+            "{}"
+        );
+        assert!(dir.path().join("failed/b.xml").is_file());
+        let note = fs::read_to_string(dir.path().join("failed/b.xml.error.txt")).unwrap();
+        assert_eq!(
+            note,
+            "Patient record failed validation: Invalid Kenyan XML payload\n"
+        );
+        assert!(dir.path().join("notes.txt").is_file());
+        assert!(!dir.path().join("a.json").exists());
+        // Not the record's fault: left in place for another try
+        assert!(dir.path().join("c.json").is_file());
+        assert!(due.contains_key(&folder.dir.join("c.json")));
+    }
+
+    #[test]
+    fn waits_for_a_file_still_being_written_at_start_up() {
+        let dir = tempfile::tempdir().unwrap();
+        write_aged(&dir.path().join("old.json"), "{}", Duration::from_secs(60));
+        fs::write(dir.path().join("copying.json"), "{\"clinic_id\": ").unwrap();
+
+        let folder = HotFolder::open(dir.path()).unwrap();
+        let mut due = folder.existing().unwrap();
+        let mut seen = Vec::new();
+        folder
+            .process_due(&mut due, &mut |path: &Path| {
+                seen.push(path.file_name().unwrap().to_owned());
+                Ok("queued".to_string())
+            })
+            .unwrap();
+
+        assert_eq!(seen, ["old.json"]);
+        assert!(dir.path().join("copying.json").is_file());
+        assert_eq!(due.len(), 1);
+    }
+}
@@ -0,0 +1,216 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::error::{BridgeError, Context, Result};
+
+/// Status, headers + body of an HTTP exchange.
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// First header with this name (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// TLS settings beyond curl's defaults (PEM files): a client certificate
+/// for mutual TLS, as interoperability layers such as OpenHIM require, and
+/// a CA bundle for servers under a private (county) CA.
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    pub client_cert: Option<PathBuf>,
+    /// Private key, when not in the certificate file
+    pub client_key: Option<PathBuf>,
+    pub ca_cert: Option<PathBuf>,
+}
+
+/// One HTTP request, sent with [`send`].
+#[derive(Debug)]
+pub struct HttpRequest<'a> {
+    pub method: &'a str,
+    pub url: &'a str,
+    /// Extra `Name: value` headers
+    pub headers: Vec<String>,
+    pub body: Option<&'a [u8]>,
+    pub timeout_secs: u32,
+    pub tls: Option<&'a TlsOptions>,
+}
+
+/// A transport failure from [`try_send`].
+#[derive(Debug)]
+pub struct SendError {
+    /// None of the request went out (host or proxy not resolved, connection
+    /// refused), so sending it again cannot apply it twice. After a timeout
+    /// or dropped connection the server may have acted on it.
+    pub not_sent: bool,
+    pub error: BridgeError,
+}
+
+/// curl exit codes for failures before the connection was made: proxy or
+/// host not resolved, connection refused.
+const NOT_SENT_EXIT_CODES: &[i32] = &[5, 6, 7];
+
+/// Send a request via curl.
+///
+/// Like the CR lookup we shell out to curl rather than pull in an HTTP
+/// client + async runtime. Non-2xx statuses are returned, not treated as
+/// errors; only transport failures (DNS, refused, timeout) are `Err`.
+pub fn send(request: &HttpRequest) -> Result<HttpResponse> {
+    try_send(request).map_err(|e| e.error)
+}
+
+/// [`send`], telling a request that never went out from one that may have
+/// reached the server.
+pub fn try_send(request: &HttpRequest) -> Result<HttpResponse, SendError> {
+    let unknown = |error| SendError {
+        not_sent: false,
+        error,
+    };
+    let timeout = request.timeout_secs.to_string();
+    // Response headers go to a scratch file so they never mix with the body.
+    let header_file = std::env::temp_dir().join(format!("kfb-headers-{}", uuid::Uuid::new_v4()));
+
+    let mut cmd = Command::new("curl");
+    cmd.args(["--silent", "--show-error", "--max-time", &timeout])
+        .arg("--dump-header")
+        .arg(&header_file)
+        .args(["--write-out", "\n%{http_code}"]);
+    if request.method == "HEAD" {
+        cmd.arg("--head");
+    } else {
+        cmd.args(["--request", request.method]);
+    }
+    if let Some(tls) = request.tls {
+        if let Some(cert) = &tls.client_cert {
+            cmd.arg("--cert").arg(cert);
+        }
+        if let Some(key) = &tls.client_key {
+            cmd.arg("--key").arg(key);
+        }
+        if let Some(ca_cert) = &tls.ca_cert {
+            cmd.arg("--cacert").arg(ca_cert);
+        }
+    }
+    for header in &request.headers {
+        cmd.args(["--header", header]);
+    }
+    if request.body.is_some() {
+        cmd.args(["--data-binary", "@-"]);
+    }
+    let mut child = cmd
+        .arg(request.url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context(BridgeError::Network, "Failed to run curl")
+        .map_err(|error| SendError {
+            not_sent: true,
+            error,
+        })?;
+
+    let mut stdin = child
+        .stdin
+        .take()
+        .context(BridgeError::Network, "curl stdin unavailable")
+        .map_err(unknown)?;
+    if let Some(body) = request.body {
+        // curl stops reading when it cannot connect; its exit code says why
+        let _ = stdin.write_all(body);
+    }
+    drop(stdin);
+    let output = child.wait_with_output().map_err(|e| unknown(e.into()))?;
+    let raw_headers = fs::read_to_string(&header_file).unwrap_or_default();
+    let _ = fs::remove_file(&header_file);
+
+    if !output.status.success() {
+        return Err(SendError {
+            not_sent: output
+                .status
+                .code()
+                .is_some_and(|code| NOT_SENT_EXIT_CODES.contains(&code)),
+            error: BridgeError::Network(format!(
+                "HTTP request failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+        });
+    }
+
+    let mut response =
+        parse_curl_output(&String::from_utf8_lossy(&output.stdout)).map_err(unknown)?;
+    response.headers = parse_headers(&raw_headers);
+    Ok(response)
+}
+
+/// POST `body` to `url` via curl.
+pub fn post(url: &str, content_type: &str, body: &[u8], timeout_secs: u32) -> Result<HttpResponse> {
+    send(&HttpRequest {
+        method: "POST",
+        url,
+        headers: vec![
+            format!("Content-Type: {}", content_type),
+            "Accept: application/fhir+json, application/json".to_string(),
+        ],
+        body: Some(body),
+        timeout_secs,
+        tls: None,
+    })
+}
+
+/// Percent-encode a query-string or form value (RFC 3986 unreserved set kept).
+pub fn url_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+/// Split curl's stdout into body and the trailing `--write-out` status line.
+fn parse_curl_output(stdout: &str) -> Result<HttpResponse> {
+    let (body, status) = stdout
+        .rsplit_once('\n')
+        .context(BridgeError::Network, "Malformed curl output")?;
+    let status = status
+        .trim()
+        .parse()
+        .context(BridgeError::Network, "Malformed HTTP status from curl")?;
+    Ok(HttpResponse {
+        status,
+        headers: Vec::new(),
+        body: body.to_string(),
+    })
+}
+
+/// Header lines from `--dump-header`; with redirects or `100 Continue` only
+/// the last response block counts.
+fn parse_headers(raw: &str) -> Vec<(String, String)> {
+    let mut headers = Vec::new();
+    for line in raw.lines() {
+        if line.starts_with("HTTP/") {
+            headers.clear();
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    headers
+}
@@ -0,0 +1,115 @@
+//! Kenya FHIR IG conformance tagging.
+//!
+//! A receiving server validates a resource against the profiles listed in
+//! its `meta.profile`. The built-in URLs are placeholders under the same
+//! digitalhealth.go.ke base as the other Kenya-specific systems, until the
+//! national IG publishes canonical StructureDefinition URLs; a facility can
+//! load its own map instead.
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use fhir_parser::fhir::bundle::Bundle;
+use serde_json::Value;
+
+use crate::error::{BridgeError, Context, Result};
+use crate::systems::KENYA_IG_BASE;
+
+/// (resourceType, profile name under [`KENYA_IG_BASE`])
+const KENYA_PROFILES: &[(&str, &str)] = &[
+    ("Patient", "ke-patient"),
+    ("Organization", "ke-organization"),
+    ("Practitioner", "ke-practitioner"),
+    ("PractitionerRole", "ke-practitioner-role"),
+    ("Location", "ke-location"),
+    ("Device", "ke-device"),
+    ("Encounter", "ke-encounter"),
+    ("Appointment", "ke-appointment"),
+    ("EpisodeOfCare", "ke-episode-of-care"),
+    ("Observation", "ke-observation"),
+    ("Condition", "ke-condition"),
+    ("MedicationRequest", "ke-medication-request"),
+    ("ServiceRequest", "ke-service-request"),
+    ("Specimen", "ke-specimen"),
+    ("DiagnosticReport", "ke-diagnostic-report"),
+    ("ClinicalImpression", "ke-clinical-impression"),
+    ("Flag", "ke-flag"),
+    ("CarePlan", "ke-care-plan"),
+    (
+        "ImmunizationRecommendation",
+        "ke-immunization-recommendation",
+    ),
+    ("DocumentReference", "ke-document-reference"),
+    ("Coverage", "ke-coverage"),
+    ("Claim", "ke-claim"),
+];
+
+/// Profile URLs per resourceType.
+#[derive(Debug, Clone)]
+pub struct IgProfiles {
+    profiles: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for IgProfiles {
+    /// The Kenya IG profiles.
+    fn default() -> Self {
+        let profiles = KENYA_PROFILES
+            .iter()
+            .map(|(resource_type, name)| {
+                (
+                    resource_type.to_string(),
+                    vec![format!("{}/{}", KENYA_IG_BASE, name)],
+                )
+            })
+            .collect();
+        Self { profiles }
+    }
+}
+
+impl IgProfiles {
+    /// Load a profile map: a JSON object from resourceType to a list of
+    /// profile URLs. Resource types it leaves out are not tagged.
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path).with_context(BridgeError::Io, || {
+            format!("Failed to read IG profile map {:?}", path)
+        })?;
+        let profiles = serde_json::from_str(&raw)
+            .context(BridgeError::Config, "Invalid IG profile map JSON")?;
+        Ok(Self { profiles })
+    }
+
+    pub fn profiles(&self, resource_type: &str) -> &[String] {
+        self.profiles
+            .get(resource_type)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Add each entry's profiles to its `meta.profile`, keeping any already
+    /// there.
+    pub fn apply(&self, bundle: &mut Bundle) {
+        for resource in bundle
+            .entry
+            .iter_mut()
+            .flatten()
+            .filter_map(|e| e.resource.as_mut())
+        {
+            let Some(resource_type) = resource.get("resourceType").and_then(Value::as_str) else {
+                continue;
+            };
+            let wanted = self.profiles(resource_type);
+            if wanted.is_empty() {
+                continue;
+            }
+            let mut listed = match resource["meta"]["profile"].take() {
+                Value::Array(listed) => listed,
+                _ => Vec::new(),
+            };
+            for url in wanted {
+                if !listed.iter().any(|p| p == url.as_str()) {
+                    listed.push(url.as_str().into());
+                }
+            }
+            resource["meta"]["profile"] = Value::Array(listed);
+        }
+    }
+}
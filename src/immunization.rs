@@ -0,0 +1,291 @@
+//! KEPI immunization forecast and defaulter list.
+//!
+//! From a child's date of birth and the doses in the record's
+//! `immunizations`, [`forecast`] works out the next dose of each antigen on
+//! the Kenya Expanded Programme on Immunization schedule and whether it is
+//! due or overdue. The transform sends it to the SHR as an
+//! ImmunizationRecommendation; [`due_list`] gives facilities the doses due
+//! across their records, for defaulter tracing. Records without an
+//! immunization history get neither: an empty list usually means the clinic
+//! did not send the card, not that the child has had nothing.
+use std::collections::BTreeMap;
+
+use chrono::{Days, Months, NaiveDate};
+
+use crate::kenyan::schema::{ImmunizationDose, KenyanPatient, Vaccine};
+use crate::surveillance::csv_field;
+
+/// Age at which a dose is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Age {
+    Weeks(u64),
+    Months(u32),
+}
+
+impl Age {
+    fn after(self, birth: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Weeks(weeks) => birth + Days::new(weeks * 7),
+            Self::Months(months) => birth + Months::new(months),
+        }
+    }
+}
+
+/// One dose of the schedule.
+struct ScheduledDose {
+    vaccine: Vaccine,
+    dose: u8,
+    age: Age,
+    /// Past this age the dose is no longer given and the series moves on
+    latest: Option<Age>,
+}
+
+const fn dose(vaccine: Vaccine, dose: u8, age: Age) -> ScheduledDose {
+    ScheduledDose {
+        vaccine,
+        dose,
+        age,
+        latest: None,
+    }
+}
+
+/// KEPI routine schedule for children under one, with the second
+/// measles-rubella dose at 18 months. Doses of a series are in order.
+const SCHEDULE: &[ScheduledDose] = &[
+    dose(Vaccine::Bcg, 1, Age::Weeks(0)),
+    ScheduledDose {
+        latest: Some(Age::Weeks(2)),
+        ..dose(Vaccine::Opv, 0, Age::Weeks(0))
+    },
+    dose(Vaccine::Opv, 1, Age::Weeks(6)),
+    dose(Vaccine::Opv, 2, Age::Weeks(10)),
+    dose(Vaccine::Opv, 3, Age::Weeks(14)),
+    dose(Vaccine::Penta, 1, Age::Weeks(6)),
+    dose(Vaccine::Penta, 2, Age::Weeks(10)),
+    dose(Vaccine::Penta, 3, Age::Weeks(14)),
+    dose(Vaccine::Pcv, 1, Age::Weeks(6)),
+    dose(Vaccine::Pcv, 2, Age::Weeks(10)),
+    dose(Vaccine::Pcv, 3, Age::Weeks(14)),
+    dose(Vaccine::Rota, 1, Age::Weeks(6)),
+    dose(Vaccine::Rota, 2, Age::Weeks(10)),
+    dose(Vaccine::Ipv, 1, Age::Weeks(14)),
+    dose(Vaccine::MeaslesRubella, 1, Age::Months(9)),
+    dose(Vaccine::MeaslesRubella, 2, Age::Months(18)),
+];
+
+/// Shortest gap between two doses of a series.
+const MIN_INTERVAL_DAYS: u64 = 28;
+/// A dose not given this long after its due date is overdue.
+const OVERDUE_AFTER_DAYS: u64 = 28;
+/// The routine schedule is for under-fives; older children get no forecast.
+const FORECAST_MONTHS: u32 = 60;
+
+/// Whether `dose` is a dose of `vaccine` on the schedule.
+pub fn is_scheduled(vaccine: Vaccine, dose: u8) -> bool {
+    SCHEDULE
+        .iter()
+        .any(|s| s.vaccine == vaccine && s.dose == dose)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForecastStatus {
+    Due,
+    Overdue,
+}
+
+impl ForecastStatus {
+    /// immunization-recommendation-status code
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Due => "due",
+            Self::Overdue => "overdue",
+        }
+    }
+
+    pub fn display(self) -> &'static str {
+        match self {
+            Self::Due => "Due",
+            Self::Overdue => "Overdue",
+        }
+    }
+}
+
+/// The next dose of one series.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForecastDose {
+    pub vaccine: Vaccine,
+    pub dose: u8,
+    /// Recommended age, or the minimum interval after the previous dose
+    /// when that was late
+    pub due: NaiveDate,
+    pub overdue: NaiveDate,
+    /// As of the forecast date; a dose due later is `Due`
+    pub status: ForecastStatus,
+}
+
+/// Next dose of every series not yet complete, as of `as_of`, in schedule
+/// order. Empty for a child five or older.
+pub fn forecast(
+    date_of_birth: NaiveDate,
+    history: &[ImmunizationDose],
+    as_of: NaiveDate,
+) -> Vec<ForecastDose> {
+    if as_of >= date_of_birth + Months::new(FORECAST_MONTHS) {
+        return Vec::new();
+    }
+    let mut forecast = Vec::new();
+    for vaccine in Vaccine::ALL {
+        // The highest dose given stands for the series so far
+        let last = history
+            .iter()
+            .filter(|d| d.vaccine == vaccine)
+            .max_by_key(|d| (d.dose, d.date));
+        let next = SCHEDULE.iter().find(|s| {
+            s.vaccine == vaccine
+                && last.is_none_or(|last| s.dose > last.dose)
+                && s.latest
+                    .is_none_or(|latest| as_of < latest.after(date_of_birth))
+        });
+        let Some(next) = next else {
+            continue;
+        };
+        let mut due = next.age.after(date_of_birth);
+        if let Some(last) = last {
+            due = due.max(last.date + Days::new(MIN_INTERVAL_DAYS));
+        }
+        let overdue = due + Days::new(OVERDUE_AFTER_DAYS);
+        forecast.push(ForecastDose {
+            vaccine,
+            dose: next.dose,
+            due,
+            overdue,
+            status: if as_of >= overdue {
+                ForecastStatus::Overdue
+            } else {
+                ForecastStatus::Due
+            },
+        });
+    }
+    forecast
+}
+
+/// One dose a child is due or overdue for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DueEntry {
+    pub clinic_id: String,
+    pub patient_number: String,
+    pub name: String,
+    pub date_of_birth: NaiveDate,
+    /// Carer's phone, for tracing
+    pub phone: String,
+    pub dose: ForecastDose,
+    /// Days from the due date to `as_of`
+    pub days_since_due: i64,
+}
+
+/// Doses due on or before `as_of` for every child whose records carry an
+/// immunization history, ordered by facility, patient number and due date.
+/// A patient's records (e.g. monthly exports) are taken together.
+pub fn due_list(records: &[KenyanPatient], as_of: NaiveDate) -> Vec<DueEntry> {
+    let mut patients: BTreeMap<(&str, &str), (&KenyanPatient, Vec<ImmunizationDose>)> =
+        BTreeMap::new();
+    for record in records.iter().filter(|r| !r.immunizations.is_empty()) {
+        let key = (record.clinic_id.as_str(), record.patient_number.as_str());
+        let (_, history) = patients.entry(key).or_insert((record, Vec::new()));
+        history.extend(record.immunizations.iter().cloned());
+    }
+
+    let mut entries = Vec::new();
+    for (record, history) in patients.into_values() {
+        let names = &record.names;
+        let name = [&names.first, &names.middle, &names.last]
+            .iter()
+            .filter(|n| !n.is_empty())
+            .map(|n| n.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut doses = forecast(record.date_of_birth, &history, as_of);
+        doses.retain(|d| d.due <= as_of);
+        doses.sort_by_key(|d| d.due);
+        for dose in doses {
+            entries.push(DueEntry {
+                clinic_id: record.clinic_id.clone(),
+                patient_number: record.patient_number.clone(),
+                name: name.clone(),
+                date_of_birth: record.date_of_birth,
+                phone: record.phone.clone(),
+                days_since_due: (as_of - dose.due).num_days(),
+                dose,
+            });
+        }
+    }
+    entries
+}
+
+/// CSV rendering, one row per dose.
+pub fn to_csv(entries: &[DueEntry]) -> String {
+    let mut out = String::from(
+        "clinic_id,patient_number,name,date_of_birth,phone,vaccine,dose,due_date,status,\
+         days_since_due\n",
+    );
+    for e in entries {
+        let fields = [
+            e.clinic_id.clone(),
+            e.patient_number.clone(),
+            e.name.clone(),
+            e.date_of_birth.to_string(),
+            e.phone.clone(),
+            e.dose.vaccine.display().to_string(),
+            e.dose.dose.to_string(),
+            e.dose.due.to_string(),
+            e.dose.status.code().to_string(),
+            e.days_since_due.to_string(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn given(vaccine: Vaccine, dose: u8, on: &str) -> ImmunizationDose {
+        ImmunizationDose {
+            vaccine,
+            dose,
+            date: date(on),
+        }
+    }
+
+    #[test]
+    fn late_dose_pushes_the_next_one_back_and_missed_birth_dose_is_dropped() {
+        let birth = date("2026-01-05");
+        let history = [
+            given(Vaccine::Bcg, 1, "2026-01-05"),
+            // Six-week doses given at ten weeks
+            given(Vaccine::Penta, 1, "2026-03-16"),
+        ];
+        let doses = forecast(birth, &history, date("2026-04-20"));
+        let find = |vaccine| doses.iter().find(|d| d.vaccine == vaccine).unwrap();
+
+        assert!(doses.iter().all(|d| d.vaccine != Vaccine::Bcg));
+        // 28 days after the late first dose, not ten weeks of age
+        let penta = find(Vaccine::Penta);
+        assert_eq!((penta.dose, penta.due), (2, date("2026-04-13")));
+        assert_eq!(penta.status, ForecastStatus::Due);
+        // Past the two-week window for OPV0 the series starts at OPV1
+        assert_eq!(find(Vaccine::Opv).dose, 1);
+        // Never started: due at six weeks, overdue four weeks later
+        let pcv = find(Vaccine::Pcv);
+        assert_eq!((pcv.dose, pcv.due), (1, date("2026-02-16")));
+        assert_eq!(pcv.status, ForecastStatus::Overdue);
+        assert_eq!(find(Vaccine::MeaslesRubella).due, date("2026-10-05"));
+    }
+}
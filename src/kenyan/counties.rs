@@ -0,0 +1,631 @@
+//! Kenyan counties (KNBS codes 001–047) and their sub-counties.
+//!
+//! Record locations are checked against this list so a typo such as
+//! "Niarobi" is caught instead of reaching the SHR as a new county. A name
+//! within two edits of exactly one official name is taken as that name and
+//! noted; anything else is kept as sent and noted. Sub-counties are the
+//! constituency-based ones, plus the MoH health sub-county names that differ
+//! from them.
+use super::schema::Location;
+
+/// (KNBS code, county, sub-counties)
+pub const COUNTIES: &[(&str, &str, &[&str])] = &[
+    (
+        "001",
+        "Mombasa",
+        &["Changamwe", "Jomvu", "Kisauni", "Nyali", "Likoni", "Mvita"],
+    ),
+    (
+        "002",
+        "Kwale",
+        &["Msambweni", "Lunga Lunga", "Matuga", "Kinango"],
+    ),
+    (
+        "003",
+        "Kilifi",
+        &[
+            "Kilifi North",
+            "Kilifi South",
+            "Kaloleni",
+            "Rabai",
+            "Ganze",
+            "Malindi",
+            "Magarini",
+        ],
+    ),
+    ("004", "Tana River", &["Garsen", "Galole", "Bura"]),
+    ("005", "Lamu", &["Lamu East", "Lamu West"]),
+    (
+        "006",
+        "Taita Taveta",
+        &["Taveta", "Wundanyi", "Mwatate", "Voi"],
+    ),
+    (
+        "007",
+        "Garissa",
+        &[
+            "Garissa Township",
+            "Balambala",
+            "Lagdera",
+            "Dadaab",
+            "Fafi",
+            "Ijara",
+        ],
+    ),
+    (
+        "008",
+        "Wajir",
+        &[
+            "Wajir North",
+            "Wajir East",
+            "Tarbaj",
+            "Wajir West",
+            "Eldas",
+            "Wajir South",
+        ],
+    ),
+    (
+        "009",
+        "Mandera",
+        &[
+            "Mandera West",
+            "Banissa",
+            "Mandera North",
+            "Mandera South",
+            "Mandera East",
+            "Lafey",
+        ],
+    ),
+    (
+        "010",
+        "Marsabit",
+        &["Moyale", "North Horr", "Saku", "Laisamis"],
+    ),
+    ("011", "Isiolo", &["Isiolo North", "Isiolo South"]),
+    (
+        "012",
+        "Meru",
+        &[
+            "Igembe South",
+            "Igembe Central",
+            "Igembe North",
+            "Tigania West",
+            "Tigania East",
+            "North Imenti",
+            "Buuri",
+            "Central Imenti",
+            "South Imenti",
+        ],
+    ),
+    (
+        "013",
+        "Tharaka-Nithi",
+        &["Maara", "Chuka/Igambang'ombe", "Tharaka"],
+    ),
+    (
+        "014",
+        "Embu",
+        &["Manyatta", "Runyenjes", "Mbeere South", "Mbeere North"],
+    ),
+    (
+        "015",
+        "Kitui",
+        &[
+            "Mwingi North",
+            "Mwingi West",
+            "Mwingi Central",
+            "Kitui West",
+            "Kitui Rural",
+            "Kitui Central",
+            "Kitui East",
+            "Kitui South",
+        ],
+    ),
+    (
+        "016",
+        "Machakos",
+        &[
+            "Masinga",
+            "Yatta",
+            "Kangundo",
+            "Matungulu",
+            "Kathiani",
+            "Mavoko",
+            "Machakos Town",
+            "Mwala",
+        ],
+    ),
+    (
+        "017",
+        "Makueni",
+        &[
+            "Mbooni",
+            "Kilome",
+            "Kaiti",
+            "Makueni",
+            "Kibwezi West",
+            "Kibwezi East",
+        ],
+    ),
+    (
+        "018",
+        "Nyandarua",
+        &["Kinangop", "Kipipiri", "Ol Kalou", "Ol Jorok", "Ndaragwa"],
+    ),
+    (
+        "019",
+        "Nyeri",
+        &[
+            "Tetu",
+            "Kieni",
+            "Mathira",
+            "Othaya",
+            "Mukurweini",
+            "Nyeri Town",
+        ],
+    ),
+    (
+        "020",
+        "Kirinyaga",
+        &["Mwea", "Gichugu", "Ndia", "Kirinyaga Central"],
+    ),
+    (
+        "021",
+        "Murang'a",
+        &[
+            "Kangema", "Mathioya", "Kiharu", "Kigumo", "Maragwa", "Kandara", "Gatanga",
+        ],
+    ),
+    (
+        "022",
+        "Kiambu",
+        &[
+            "Gatundu South",
+            "Gatundu North",
+            "Juja",
+            "Thika Town",
+            "Ruiru",
+            "Githunguri",
+            "Kiambu",
+            "Kiambaa",
+            "Kabete",
+            "Kikuyu",
+            "Limuru",
+            "Lari",
+        ],
+    ),
+    (
+        "023",
+        "Turkana",
+        &[
+            "Turkana North",
+            "Turkana West",
+            "Turkana Central",
+            "Loima",
+            "Turkana South",
+            "Turkana East",
+        ],
+    ),
+    (
+        "024",
+        "West Pokot",
+        &["Kapenguria", "Sigor", "Kacheliba", "Pokot South"],
+    ),
+    (
+        "025",
+        "Samburu",
+        &["Samburu West", "Samburu North", "Samburu East"],
+    ),
+    (
+        "026",
+        "Trans Nzoia",
+        &["Kwanza", "Endebess", "Saboti", "Kiminini", "Cherangany"],
+    ),
+    (
+        "027",
+        "Uasin Gishu",
+        &["Soy", "Turbo", "Moiben", "Ainabkoi", "Kapseret", "Kesses"],
+    ),
+    (
+        "028",
+        "Elgeyo-Marakwet",
+        &[
+            "Marakwet East",
+            "Marakwet West",
+            "Keiyo North",
+            "Keiyo South",
+        ],
+    ),
+    (
+        "029",
+        "Nandi",
+        &[
+            "Tinderet",
+            "Aldai",
+            "Nandi Hills",
+            "Chesumei",
+            "Emgwen",
+            "Mosop",
+        ],
+    ),
+    (
+        "030",
+        "Baringo",
+        &[
+            "Tiaty",
+            "Baringo North",
+            "Baringo Central",
+            "Baringo South",
+            "Mogotio",
+            "Eldama Ravine",
+        ],
+    ),
+    (
+        "031",
+        "Laikipia",
+        &["Laikipia West", "Laikipia East", "Laikipia North"],
+    ),
+    (
+        "032",
+        "Nakuru",
+        &[
+            "Molo",
+            "Njoro",
+            "Naivasha",
+            "Gilgil",
+            "Kuresoi South",
+            "Kuresoi North",
+            "Subukia",
+            "Rongai",
+            "Bahati",
+            "Nakuru Town West",
+            "Nakuru Town East",
+            "Nakuru East",
+            "Nakuru West",
+        ],
+    ),
+    (
+        "033",
+        "Narok",
+        &[
+            "Kilgoris",
+            "Emurua Dikirr",
+            "Narok North",
+            "Narok East",
+            "Narok South",
+            "Narok West",
+        ],
+    ),
+    (
+        "034",
+        "Kajiado",
+        &[
+            "Kajiado North",
+            "Kajiado Central",
+            "Kajiado East",
+            "Kajiado West",
+            "Kajiado South",
+        ],
+    ),
+    (
+        "035",
+        "Kericho",
+        &[
+            "Kipkelion East",
+            "Kipkelion West",
+            "Ainamoi",
+            "Bureti",
+            "Belgut",
+            "Sigowet/Soin",
+        ],
+    ),
+    (
+        "036",
+        "Bomet",
+        &[
+            "Sotik",
+            "Chepalungu",
+            "Bomet East",
+            "Bomet Central",
+            "Konoin",
+        ],
+    ),
+    (
+        "037",
+        "Kakamega",
+        &[
+            "Lugari",
+            "Likuyani",
+            "Malava",
+            "Lurambi",
+            "Navakholo",
+            "Mumias West",
+            "Mumias East",
+            "Matungu",
+            "Butere",
+            "Khwisero",
+            "Shinyalu",
+            "Ikolomani",
+        ],
+    ),
+    (
+        "038",
+        "Vihiga",
+        &["Vihiga", "Sabatia", "Hamisi", "Luanda", "Emuhaya"],
+    ),
+    (
+        "039",
+        "Bungoma",
+        &[
+            "Mt. Elgon",
+            "Sirisia",
+            "Kabuchai",
+            "Bumula",
+            "Kanduyi",
+            "Webuye East",
+            "Webuye West",
+            "Kimilili",
+            "Tongaren",
+        ],
+    ),
+    (
+        "040",
+        "Busia",
+        &[
+            "Teso North",
+            "Teso South",
+            "Nambale",
+            "Matayos",
+            "Butula",
+            "Funyula",
+            "Budalangi",
+        ],
+    ),
+    (
+        "041",
+        "Siaya",
+        &[
+            "Ugenya",
+            "Ugunja",
+            "Alego Usonga",
+            "Gem",
+            "Bondo",
+            "Rarieda",
+        ],
+    ),
+    (
+        "042",
+        "Kisumu",
+        &[
+            "Kisumu East",
+            "Kisumu West",
+            "Kisumu Central",
+            "Seme",
+            "Nyando",
+            "Muhoroni",
+            "Nyakach",
+        ],
+    ),
+    (
+        "043",
+        "Homa Bay",
+        &[
+            "Kasipul",
+            "Kabondo Kasipul",
+            "Karachuonyo",
+            "Rangwe",
+            "Homa Bay Town",
+            "Ndhiwa",
+            "Suba North",
+            "Suba South",
+        ],
+    ),
+    (
+        "044",
+        "Migori",
+        &[
+            "Rongo",
+            "Awendo",
+            "Suna East",
+            "Suna West",
+            "Uriri",
+            "Nyatike",
+            "Kuria West",
+            "Kuria East",
+        ],
+    ),
+    (
+        "045",
+        "Kisii",
+        &[
+            "Bonchari",
+            "South Mugirango",
+            "Bomachoge Borabu",
+            "Bobasi",
+            "Bomachoge Chache",
+            "Nyaribari Masaba",
+            "Nyaribari Chache",
+            "Kitutu Chache North",
+            "Kitutu Chache South",
+        ],
+    ),
+    (
+        "046",
+        "Nyamira",
+        &[
+            "Kitutu Masaba",
+            "West Mugirango",
+            "North Mugirango",
+            "Borabu",
+        ],
+    ),
+    (
+        "047",
+        "Nairobi",
+        &[
+            "Westlands",
+            "Dagoretti North",
+            "Dagoretti South",
+            "Langata",
+            "Kibra",
+            "Roysambu",
+            "Kasarani",
+            "Ruaraka",
+            "Embakasi South",
+            "Embakasi North",
+            "Embakasi Central",
+            "Embakasi East",
+            "Embakasi West",
+            "Makadara",
+            "Kamukunji",
+            "Starehe",
+            "Mathare",
+        ],
+    ),
+];
+
+/// Letters and digits only, lower case: "Murang'a County" and "muranga"
+/// compare equal.
+fn key(name: &str) -> String {
+    let lower = name.trim().to_lowercase();
+    let lower = lower.strip_suffix(" county").unwrap_or(&lower);
+    lower.chars().filter(|c| c.is_alphanumeric()).collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+enum Found<'a> {
+    Exact(&'a str),
+    /// Unique official name within the typo allowance
+    Near(&'a str),
+    None,
+}
+
+fn find<'a>(name: &str, official: impl Iterator<Item = &'a str>) -> Found<'a> {
+    let wanted = key(name);
+    // One typo in short names, two in longer ones
+    let allowance = if wanted.chars().count() < 6 { 1 } else { 2 };
+    let mut near = Vec::new();
+    for candidate in official {
+        let distance = edit_distance(&wanted, &key(candidate));
+        if distance == 0 {
+            return Found::Exact(candidate);
+        }
+        if distance <= allowance {
+            near.push(candidate);
+        }
+    }
+    match near.as_slice() {
+        [only] => Found::Near(only),
+        _ => Found::None,
+    }
+}
+
+/// KNBS code of an official county name.
+pub fn county_code(county: &str) -> Option<&'static str> {
+    COUNTIES
+        .iter()
+        .find(|(_, name, _)| *name == county)
+        .map(|(code, _, _)| *code)
+}
+
+/// `location` with official spellings, noting corrections and names not on
+/// the list in `data_quality`. A blank county is left alone.
+pub(crate) fn official_location(location: Location, data_quality: &mut Vec<String>) -> Location {
+    if location.county.trim().is_empty() {
+        return location;
+    }
+    let county = match find(&location.county, COUNTIES.iter().map(|(_, name, _)| *name)) {
+        Found::Exact(name) => name,
+        Found::Near(name) => {
+            data_quality.push(format!(
+                "county looks like a misspelling of {}; recorded as {}",
+                name, name
+            ));
+            name
+        }
+        Found::None => {
+            data_quality.push("county not on the KNBS county list".to_string());
+            return location;
+        }
+    };
+    let subcounties = COUNTIES
+        .iter()
+        .find(|(_, name, _)| *name == county)
+        .map(|(_, _, subs)| *subs)
+        .unwrap_or_default();
+    let subcounty = if location.subcounty.trim().is_empty() {
+        location.subcounty
+    } else {
+        match find(&location.subcounty, subcounties.iter().copied()) {
+            Found::Exact(name) => name.to_string(),
+            Found::Near(name) => {
+                data_quality.push(format!(
+                    "sub-county looks like a misspelling of {}; recorded as {}",
+                    name, name
+                ));
+                name.to_string()
+            }
+            Found::None => {
+                data_quality.push(format!("sub-county not listed under {}", county));
+                location.subcounty
+            }
+        }
+    };
+    Location {
+        county: county.to_string(),
+        subcounty,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(county: &str, subcounty: &str) -> (Location, Vec<String>) {
+        let mut notes = Vec::new();
+        let location = Location {
+            county: county.to_string(),
+            subcounty: subcounty.to_string(),
+        };
+        (official_location(location, &mut notes), notes)
+    }
+
+    #[test]
+    fn corrects_typos_and_flags_unknown_names() {
+        let (location, notes) = check("nairobi county", "kasarani");
+        assert_eq!(
+            (location.county.as_str(), location.subcounty.as_str()),
+            ("Nairobi", "Kasarani")
+        );
+        assert!(notes.is_empty());
+        assert_eq!(county_code("Nairobi"), Some("047"));
+
+        let (location, notes) = check("Niarobi", "Westlnds");
+        assert_eq!(location.county, "Nairobi");
+        assert_eq!(location.subcounty, "Westlands");
+        assert_eq!(notes.len(), 2);
+
+        let (location, notes) = check("Muranga", "");
+        assert_eq!(location.county, "Murang'a");
+        assert!(notes.is_empty());
+
+        let (location, notes) = check("Atlantis", "Harbour");
+        assert_eq!(location.county, "Atlantis");
+        assert_eq!(notes, ["county not on the KNBS county list"]);
+        assert_eq!(COUNTIES.len(), 47);
+    }
+}
@@ -1,3 +1,4 @@
+pub mod counties;
 pub mod schema;
 pub mod xml_schema;
 
@@ -1,17 +1,198 @@
-use chrono::NaiveDate;
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, FixedOffset, Months, NaiveDate};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::error::{bail, BridgeError, Context};
+
+use super::counties::official_location;
 
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(try_from = "KenyanPatientInput")]
 pub struct KenyanPatient {
     pub clinic_id: String,
     pub patient_number: String,
     pub national_id: String,
     pub names: Names,
+    /// `M`, `F`, `O` (other, intersex) or `U` (unknown), normalized from
+    /// whatever the clinic sent (see [`normalize_gender`])
     pub gender: String,
+    /// Recorded, or estimated from a stated `age` when the clinic does not
+    /// know it (see `birth_date_estimated`)
     pub date_of_birth: NaiveDate,
+    /// The date of birth was worked out from the patient's stated age
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub birth_date_estimated: bool,
     pub phone: String,
     pub location: Location,
-    pub visit: Visit,
+    /// One entry per visit. Accepts either a single `"visit": {...}` object
+    /// (per-visit records) or a `"visits": [...]` array (monthly EMR exports);
+    /// each visit becomes its own Encounter in the bundle.
+    pub visits: Vec<Visit>,
+    /// Identification photo from UHC registration, becomes Patient.photo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub photo: Option<InlineAttachment>,
+    /// Biometric templates, each becomes a restricted DocumentReference
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub biometrics: Vec<Biometric>,
+    /// KEPI vaccine doses the child has had; the bundle gets the doses due
+    /// next as an ImmunizationRecommendation (see [`crate::immunization`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub immunizations: Vec<ImmunizationDose>,
+    /// Problems found while reading the record that did not stop it, e.g.
+    /// an unrecognised gender. Generic messages only — no values, no PHI.
+    #[serde(skip)]
+    pub data_quality: Vec<String>,
+}
+
+/// The record as sent: `date_of_birth`, or `age` (`34` or
+/// `{"years": 1, "months": 6}`) when only the age is known.
+#[derive(Deserialize)]
+struct KenyanPatientInput {
+    clinic_id: String,
+    patient_number: String,
+    national_id: String,
+    names: Names,
+    gender: String,
+    date_of_birth: Option<NaiveDate>,
+    #[serde(default)]
+    birth_date_estimated: bool,
+    #[serde(default, deserialize_with = "years_or_stated_age")]
+    age: Option<StatedAge>,
+    phone: String,
+    location: Location,
+    #[serde(alias = "visit", deserialize_with = "one_or_many_visits")]
+    visits: Vec<Visit>,
+    #[serde(default)]
+    photo: Option<InlineAttachment>,
+    #[serde(default)]
+    biometrics: Vec<Biometric>,
+    #[serde(default)]
+    immunizations: Vec<ImmunizationDose>,
+}
+
+impl TryFrom<KenyanPatientInput> for KenyanPatient {
+    type Error = BridgeError;
+
+    fn try_from(p: KenyanPatientInput) -> crate::error::Result<Self> {
+        let (date_of_birth, estimated) = birth_date(p.date_of_birth, p.age, &p.visits)?;
+        let mut data_quality = Vec::new();
+        Ok(Self {
+            clinic_id: p.clinic_id,
+            patient_number: p.patient_number,
+            national_id: p.national_id,
+            names: p.names,
+            gender: gender_code(&p.gender, &mut data_quality),
+            date_of_birth,
+            birth_date_estimated: estimated || p.birth_date_estimated,
+            phone: p.phone,
+            location: official_location(p.location, &mut data_quality),
+            visits: p.visits,
+            photo: p.photo,
+            biometrics: p.biometrics,
+            immunizations: p.immunizations,
+            data_quality,
+        })
+    }
+}
+
+/// Canonical gender code for the values clinics send: `Male`/`male`/`M`/`1`,
+/// `Female`/`F `/`2`, `intersex`/`other`/`3`, and blank or `unknown`.
+/// `None` when the value means none of these.
+pub fn normalize_gender(raw: &str) -> Option<&'static str> {
+    Some(match raw.trim().to_lowercase().as_str() {
+        "m" | "male" | "man" | "boy" | "1" => "M",
+        "f" | "female" | "woman" | "girl" | "2" => "F",
+        "o" | "other" | "intersex" | "3" => "O",
+        "" | "u" | "unknown" | "not known" | "not stated" | "9" => "U",
+        _ => return None,
+    })
+}
+
+/// [`normalize_gender`], recording an unrecognised value as `U` with a
+/// data-quality note instead of passing it on.
+pub(crate) fn gender_code(raw: &str, data_quality: &mut Vec<String>) -> String {
+    normalize_gender(raw)
+        .unwrap_or_else(|| {
+            data_quality.push("gender value not recognised; recorded as unknown".to_string());
+            "U"
+        })
+        .to_string()
+}
+
+/// Age as stated by the patient or carer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StatedAge {
+    #[serde(default)]
+    pub years: u32,
+    #[serde(default)]
+    pub months: u32,
+}
+
+fn years_or_stated_age<'de, D>(deserializer: D) -> Result<Option<StatedAge>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum YearsOrAge {
+        Years(u32),
+        Age(StatedAge),
+    }
+
+    Ok(
+        Option::<YearsOrAge>::deserialize(deserializer)?.map(|a| match a {
+            YearsOrAge::Years(years) => StatedAge { years, months: 0 },
+            YearsOrAge::Age(age) => age,
+        }),
+    )
+}
+
+/// The recorded date of birth, else one estimated from `age` as stated at
+/// the earliest visit with a valid date; the flag tells which. A recorded date wins over an age.
+pub(crate) fn birth_date(
+    recorded: Option<NaiveDate>,
+    age: Option<StatedAge>,
+    visits: &[Visit],
+) -> crate::error::Result<(NaiveDate, bool)> {
+    if let Some(date) = recorded {
+        return Ok((date, false));
+    }
+    let Some(age) = age else {
+        bail!(Validation, "date_of_birth or age is required");
+    };
+    if age.months > 11 && age.years > 0 {
+        bail!(Validation, "age months must be 0–11 when years are given");
+    }
+    // A visit with a bad date is rejected on its own later; only fail here
+    // when no visit gives a date to count back from.
+    let first_visit = visits
+        .iter()
+        .filter_map(|visit| NaiveDate::parse_from_str(&visit.date, "%Y-%m-%d").ok())
+        .min()
+        .context(
+            BridgeError::Validation,
+            "An age needs a valid visit date (YYYY-MM-DD) to count back from",
+        )?;
+    let estimated = first_visit
+        .checked_sub_months(Months::new(age.years * 12 + age.months))
+        .context(BridgeError::Validation, "age out of range")?;
+    Ok((estimated, true))
+}
+
+fn one_or_many_visits<'de, D>(deserializer: D) -> Result<Vec<Visit>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(Box<Visit>),
+        Many(Vec<Visit>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(visit) => vec![*visit],
+        OneOrMany::Many(visits) => visits,
+    })
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -23,22 +204,160 @@ pub struct Names {
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Location {
+    /// Official county name where the KNBS list knows it
     pub county: String,
     pub subcounty: String,
 }
 
+/// Base64-encoded content sent inline with the record.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InlineAttachment {
+    /// MIME type, e.g. `image/jpeg`
+    pub content_type: String,
+    /// Standard base64, no data-URL prefix
+    pub data: String,
+}
+
+/// A biometric template captured for identification.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Biometric {
+    pub modality: BiometricModality,
+    /// MIME type of the template, e.g. `application/octet-stream` for an
+    /// ISO/IEC 19794-2 minutiae record
+    pub content_type: String,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BiometricModality {
+    Fingerprint,
+    Iris,
+    Face,
+}
+
+impl BiometricModality {
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Fingerprint => "fingerprint",
+            Self::Iris => "iris",
+            Self::Face => "face",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "fingerprint" => Some(Self::Fingerprint),
+            "iris" => Some(Self::Iris),
+            "face" => Some(Self::Face),
+            _ => None,
+        }
+    }
+}
+
+/// A vaccine dose given, from the child's immunization card.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ImmunizationDose {
+    pub vaccine: Vaccine,
+    /// Dose in the series: 0 for the OPV birth dose, else from 1
+    pub dose: u8,
+    pub date: NaiveDate,
+}
+
+/// KEPI antigens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Vaccine {
+    Bcg,
+    /// Oral polio (bOPV)
+    Opv,
+    /// DPT-HepB-Hib
+    Penta,
+    /// Pneumococcal conjugate
+    Pcv,
+    /// Rotavirus
+    Rota,
+    /// Inactivated polio
+    Ipv,
+    /// Measles-rubella
+    #[serde(alias = "mr")]
+    MeaslesRubella,
+}
+
+impl Vaccine {
+    pub const ALL: [Self; 7] = [
+        Self::Bcg,
+        Self::Opv,
+        Self::Penta,
+        Self::Pcv,
+        Self::Rota,
+        Self::Ipv,
+        Self::MeaslesRubella,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Bcg => "bcg",
+            Self::Opv => "opv",
+            Self::Penta => "penta",
+            Self::Pcv => "pcv",
+            Self::Rota => "rota",
+            Self::Ipv => "ipv",
+            Self::MeaslesRubella => "mr",
+        }
+    }
+
+    pub fn display(self) -> &'static str {
+        match self {
+            Self::Bcg => "BCG",
+            Self::Opv => "Oral polio vaccine",
+            Self::Penta => "DPT-HepB-Hib (pentavalent)",
+            Self::Pcv => "Pneumococcal conjugate vaccine",
+            Self::Rota => "Rotavirus vaccine",
+            Self::Ipv => "Inactivated polio vaccine",
+            Self::MeaslesRubella => "Measles-rubella",
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Visit {
     pub date: String,
     pub complaint: String,
     pub vitals: Vitals,
     pub diagnosis: String,
+    /// Whether the diagnosis is a chronic problem carried from visit to
+    /// visit or this visit's own (Condition.category)
+    #[serde(
+        default,
+        skip_serializing_if = "DiagnosisCategory::is_encounter_diagnosis"
+    )]
+    pub diagnosis_category: DiagnosisCategory,
+    /// Clinician's grading of the diagnosis (Condition.severity)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnosis_severity: Option<Severity>,
+    /// When the illness began, where the clinician recorded it
+    /// (Condition.onsetDateTime); the visit date is when it was diagnosed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub onset_date: Option<NaiveDate>,
     pub treatment: String,
+    /// The clinician's assessment in their own words, beyond the complaint
+    /// and diagnosis (ClinicalImpression.summary)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clinical_note: Option<String>,
     /// Health Worker Registry PUID of the attending clinician.
     /// Required by AfyaLink for Encounter.participant.
     /// Optional — older records may not carry this.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub attending_puid: Option<String>,
+    /// Cadre of the attending clinician; with `attending_puid` it becomes a
+    /// PractitionerRole at the facility
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attending_cadre: Option<Cadre>,
+    /// HWR PUID of whoever took the vitals, when not the attending
+    /// clinician (Observation.performer; `attending_puid` otherwise)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vitals_taken_by: Option<String>,
     /// SHA scheme member number (e.g. SHA/2024/001234).
     /// Used to build Coverage + Claim resources for SHIF preauthorisation.
     /// Optional — cash/non-SHA visits omit this.
@@ -48,6 +367,473 @@ pub struct Visit {
     /// Required when sha_member_number is present.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sha_intervention_code: Option<String>,
+    /// Scanned lab reports, referral letters and notes from this visit
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<VisitAttachment>,
+    /// Lab tests ordered at this visit and not yet resulted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lab_orders: Vec<LabOrder>,
+    /// Imaging studies ordered at this visit, with the report once there
+    /// is one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub imaging_orders: Vec<ImagingOrder>,
+    /// The clinic's own visit number (Encounter.identifier), so a later
+    /// visit can refer back to this one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visit_id: Option<String>,
+    /// `visit_id` of the earlier visit this one follows up, in this record
+    /// or an earlier one (Encounter.partOf)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_visit_id: Option<String>,
+    /// Chronic-care programme the visit is part of (EpisodeOfCare)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub programme: Option<CareProgramme>,
+    /// WHO clinical stage (1–4) of an HIV diagnosis
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hiv_who_stage: Option<u8>,
+    /// Treatment phase of a TB diagnosis
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tb_phase: Option<TbPhase>,
+    /// Programme regimen, e.g. `2RHZE/4RH` or `TDF/3TC/DTG`. Mapped under
+    /// `--profile`; `treatment` stands in when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regimen: Option<String>,
+    /// Next clinic review. With a hypertension or diabetes diagnosis it
+    /// gives a CarePlan with the medication and the follow-up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review_date: Option<NaiveDate>,
+    /// Follow-up visit booked at the end of this one (Appointment), for
+    /// defaulter tracing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_appointment_date: Option<NaiveDate>,
+    /// Antenatal findings; ANC visits only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anc: Option<AntenatalFindings>,
+    /// School-health vision and hearing screening
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub screening: Option<ScreeningFindings>,
+    /// KTAS triage category, 1 (resuscitation) to 5 (non-urgent)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub triage_category: Option<u8>,
+    /// Danger signs noted at triage; each becomes an active Flag so
+    /// viewers show it as an alert
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub danger_signs: Vec<DangerSign>,
+    /// Service delivery point within the facility (Encounter.location)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub department: Option<Department>,
+}
+
+/// Findings recorded at an antenatal (ANC) visit.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AntenatalFindings {
+    /// Completed weeks of gestation (LOINC 49051-6)
+    pub gestational_age_weeks: u8,
+    /// Pregnancies including this one (LOINC 11996-6)
+    pub gravida: u8,
+    /// Previous births (LOINC 11977-6)
+    pub para: u8,
+    /// Symphysis-fundal height in cm (LOINC 11881-0). Optional.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fundal_height_cm: Option<f64>,
+    /// Fetal heart rate in beats per minute (LOINC 55283-6). Optional.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetal_heart_rate: Option<u16>,
+}
+
+/// FHIR condition-category of the visit's diagnosis.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosisCategory {
+    /// Diagnosed at this visit, e.g. malaria
+    #[default]
+    EncounterDiagnosis,
+    /// A chronic problem on the patient's problem list, e.g. hypertension
+    /// under follow-up
+    ProblemListItem,
+}
+
+impl DiagnosisCategory {
+    pub const ALL: [Self; 2] = [Self::EncounterDiagnosis, Self::ProblemListItem];
+
+    /// condition-category code
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::EncounterDiagnosis => "encounter-diagnosis",
+            Self::ProblemListItem => "problem-list-item",
+        }
+    }
+
+    pub fn display(self) -> &'static str {
+        match self {
+            Self::EncounterDiagnosis => "Encounter Diagnosis",
+            Self::ProblemListItem => "Problem List Item",
+        }
+    }
+
+    pub fn is_encounter_diagnosis(&self) -> bool {
+        *self == Self::EncounterDiagnosis
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Mild,
+    Moderate,
+    Severe,
+}
+
+impl Severity {
+    pub const ALL: [Self; 3] = [Self::Mild, Self::Moderate, Self::Severe];
+
+    /// SNOMED CT code, from the FHIR condition-severity value set
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Mild => "255604002",
+            Self::Moderate => "6736007",
+            Self::Severe => "24484000",
+        }
+    }
+
+    pub fn display(self) -> &'static str {
+        match self {
+            Self::Mild => "Mild",
+            Self::Moderate => "Moderate",
+            Self::Severe => "Severe",
+        }
+    }
+}
+
+/// Vision and hearing screening done by the school health programme, which
+/// sends its visits through the same OPD pipeline. Each finding is optional.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ScreeningFindings {
+    /// Uncorrected visual acuity of the left eye as a Snellen fraction,
+    /// e.g. `6/9` (LOINC 79880-1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visual_acuity_left: Option<String>,
+    /// Same for the right eye (LOINC 79882-7)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visual_acuity_right: Option<String>,
+    /// Hearing screen result (LOINC 46216-8)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hearing: Option<ScreeningResult>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreeningResult {
+    Pass,
+    /// Refer for a full assessment
+    Fail,
+}
+
+impl ScreeningResult {
+    pub const ALL: [Self; 2] = [Self::Pass, Self::Fail];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Pass => "pass",
+            Self::Fail => "fail",
+        }
+    }
+
+    pub fn display(self) -> &'static str {
+        match self {
+            Self::Pass => "Pass",
+            Self::Fail => "Fail",
+        }
+    }
+}
+
+/// General danger signs (IMCI, and the adult ETAT equivalents) that call
+/// for immediate attention whatever the triage category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DangerSign {
+    Convulsions,
+    /// Lethargic or unconscious
+    Unconscious,
+    /// Unable to drink or breastfeed
+    UnableToDrink,
+    VomitsEverything,
+    SevereDehydration,
+    /// Chest indrawing, grunting or central cyanosis
+    RespiratoryDistress,
+    SevereBleeding,
+}
+
+impl DangerSign {
+    pub const ALL: [Self; 7] = [
+        Self::Convulsions,
+        Self::Unconscious,
+        Self::UnableToDrink,
+        Self::VomitsEverything,
+        Self::SevereDehydration,
+        Self::RespiratoryDistress,
+        Self::SevereBleeding,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Convulsions => "convulsions",
+            Self::Unconscious => "unconscious",
+            Self::UnableToDrink => "unable-to-drink",
+            Self::VomitsEverything => "vomits-everything",
+            Self::SevereDehydration => "severe-dehydration",
+            Self::RespiratoryDistress => "respiratory-distress",
+            Self::SevereBleeding => "severe-bleeding",
+        }
+    }
+
+    pub fn display(self) -> &'static str {
+        match self {
+            Self::Convulsions => "Convulsions",
+            Self::Unconscious => "Lethargic or unconscious",
+            Self::UnableToDrink => "Unable to drink or breastfeed",
+            Self::VomitsEverything => "Vomits everything",
+            Self::SevereDehydration => "Severe dehydration",
+            Self::RespiratoryDistress => "Respiratory distress",
+            Self::SevereBleeding => "Severe bleeding",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CareProgramme {
+    /// HIV care and treatment (CCC)
+    Hiv,
+    Tb,
+    /// Non-communicable diseases: hypertension, diabetes
+    Ncd,
+}
+
+impl CareProgramme {
+    pub const ALL: [Self; 3] = [Self::Hiv, Self::Tb, Self::Ncd];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Hiv => "hiv",
+            Self::Tb => "tb",
+            Self::Ncd => "ncd",
+        }
+    }
+
+    pub fn display(self) -> &'static str {
+        match self {
+            Self::Hiv => "HIV care and treatment",
+            Self::Tb => "TB treatment",
+            Self::Ncd => "Non-communicable disease care",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TbPhase {
+    Intensive,
+    Continuation,
+}
+
+impl TbPhase {
+    pub const ALL: [Self; 2] = [Self::Intensive, Self::Continuation];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Intensive => "intensive",
+            Self::Continuation => "continuation",
+        }
+    }
+
+    pub fn display(self) -> &'static str {
+        match self {
+            Self::Intensive => "Intensive phase",
+            Self::Continuation => "Continuation phase",
+        }
+    }
+}
+
+/// Service delivery point within a facility.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Department {
+    /// Outpatient department
+    #[serde(alias = "OPD")]
+    Opd,
+    /// Maternal and child health clinic
+    #[serde(alias = "MCH")]
+    Mch,
+    /// Comprehensive care clinic (HIV)
+    #[serde(alias = "CCC")]
+    Ccc,
+    #[serde(alias = "Emergency")]
+    Emergency,
+}
+
+impl Department {
+    pub const ALL: [Self; 4] = [Self::Opd, Self::Mch, Self::Ccc, Self::Emergency];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Opd => "opd",
+            Self::Mch => "mch",
+            Self::Ccc => "ccc",
+            Self::Emergency => "emergency",
+        }
+    }
+
+    pub fn display(self) -> &'static str {
+        match self {
+            Self::Opd => "Outpatient department",
+            Self::Mch => "Maternal and child health clinic",
+            Self::Ccc => "Comprehensive care clinic",
+            Self::Emergency => "Emergency department",
+        }
+    }
+}
+
+/// Health-worker cadre of the attending clinician.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cadre {
+    #[serde(alias = "Nurse")]
+    Nurse,
+    #[serde(alias = "co", alias = "CO", alias = "Clinical Officer")]
+    ClinicalOfficer,
+    /// Medical officer (doctor)
+    #[serde(alias = "mo", alias = "MO", alias = "Medical Officer")]
+    MedicalOfficer,
+}
+
+impl Cadre {
+    pub const ALL: [Self; 3] = [Self::Nurse, Self::ClinicalOfficer, Self::MedicalOfficer];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Nurse => "nurse",
+            Self::ClinicalOfficer => "clinical-officer",
+            Self::MedicalOfficer => "medical-officer",
+        }
+    }
+
+    pub fn display(self) -> &'static str {
+        match self {
+            Self::Nurse => "Nurse",
+            Self::ClinicalOfficer => "Clinical Officer",
+            Self::MedicalOfficer => "Medical Officer",
+        }
+    }
+}
+
+/// A document from a visit, sent inline (base64 `data`) or held elsewhere
+/// (`url`) — exactly one of the two.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct VisitAttachment {
+    #[serde(default)]
+    pub kind: DocumentKind,
+    /// `application/pdf`, `image/jpeg` or `image/png`
+    pub content_type: String,
+    /// Shown to clinicians; keep names and other PHI out of it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// An imaging study ordered at the visit, e.g. a chest X-ray in a TB
+/// workup; becomes a ServiceRequest, and a DiagnosticReport once reported.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ImagingOrder {
+    /// LOINC code of the study, e.g. `36643-5` (chest X-ray, 2 views)
+    pub study_code: String,
+    /// Study name as the clinic writes it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub study_name: Option<String>,
+    /// The radiologist's conclusion; the study counts as reported once set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conclusion: Option<String>,
+    /// Images or the report PDF, each a DocumentReference linked to the
+    /// order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<VisitAttachment>,
+}
+
+/// A lab test ordered at the visit; becomes an active ServiceRequest with
+/// its Specimen for the lab information system.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LabOrder {
+    /// LOINC code of the test, e.g. `32700-7` (malaria smear)
+    pub test_code: String,
+    /// Test name as the clinic writes it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub test_name: Option<String>,
+    pub specimen: SpecimenType,
+    /// When the specimen was taken, once it has been
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collected_at: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpecimenType {
+    Blood,
+    Serum,
+    Plasma,
+    Urine,
+    Stool,
+    Sputum,
+    Csf,
+}
+
+impl SpecimenType {
+    pub const ALL: [Self; 7] = [
+        Self::Blood,
+        Self::Serum,
+        Self::Plasma,
+        Self::Urine,
+        Self::Stool,
+        Self::Sputum,
+        Self::Csf,
+    ];
+
+    /// HL7 v2 table 0487 code
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Blood => "BLD",
+            Self::Serum => "SER",
+            Self::Plasma => "PLAS",
+            Self::Urine => "UR",
+            Self::Stool => "STL",
+            Self::Sputum => "SPT",
+            Self::Csf => "CSF",
+        }
+    }
+
+    pub fn display(self) -> &'static str {
+        match self {
+            Self::Blood => "Whole blood",
+            Self::Serum => "Serum",
+            Self::Plasma => "Plasma",
+            Self::Urine => "Urine",
+            Self::Stool => "Stool = Fecal",
+            Self::Sputum => "Sputum",
+            Self::Csf => "Cerebral spinal fluid",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentKind {
+    LabReport,
+    ImagingReport,
+    ReferralLetter,
+    #[default]
+    ClinicalNote,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -62,4 +848,78 @@ pub struct Vitals {
     /// Oxygen saturation % (LOINC 59408-5). Optional.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub o2_saturation: Option<f64>,
+    /// Height (or length, under-twos) in cm (LOINC 8302-2). Optional.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height_cm: Option<f64>,
+    /// Mid-upper arm circumference in cm (LOINC 56072-2). Optional.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub muac_cm: Option<f64>,
+    /// Instruments the readings were taken with, where the facility
+    /// records them; each becomes a Device (Observation.device)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<VitalsDevice>,
+}
+
+/// An instrument used for the vitals, e.g. a pulse oximeter.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct VitalsDevice {
+    pub kind: DeviceKind,
+    /// The facility's asset or serial number for the instrument
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceKind {
+    PulseOximeter,
+    Thermometer,
+    BpMonitor,
+    WeighingScale,
+    Stadiometer,
+    MuacTape,
+}
+
+impl DeviceKind {
+    pub const ALL: [Self; 6] = [
+        Self::PulseOximeter,
+        Self::Thermometer,
+        Self::BpMonitor,
+        Self::WeighingScale,
+        Self::Stadiometer,
+        Self::MuacTape,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::PulseOximeter => "pulse-oximeter",
+            Self::Thermometer => "thermometer",
+            Self::BpMonitor => "bp-monitor",
+            Self::WeighingScale => "weighing-scale",
+            Self::Stadiometer => "stadiometer",
+            Self::MuacTape => "muac-tape",
+        }
+    }
+
+    pub fn display(self) -> &'static str {
+        match self {
+            Self::PulseOximeter => "Pulse oximeter",
+            Self::Thermometer => "Thermometer",
+            Self::BpMonitor => "Blood pressure monitor",
+            Self::WeighingScale => "Weighing scale",
+            Self::Stadiometer => "Stadiometer",
+            Self::MuacTape => "MUAC tape",
+        }
+    }
+
+    /// LOINC codes of the vitals Observations this instrument measures.
+    pub fn measures(self) -> &'static [&'static str] {
+        match self {
+            Self::PulseOximeter => &["59408-5", "8867-4"],
+            Self::Thermometer => &["8310-5"],
+            Self::BpMonitor => &["85354-9"],
+            Self::WeighingScale => &["29463-7"],
+            Self::Stadiometer => &["8302-2"],
+            Self::MuacTape => &["56072-2"],
+        }
+    }
 }
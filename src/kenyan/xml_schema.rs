@@ -1,50 +1,63 @@
-/// XML-native representation of a Kenyan clinic record.
-///
-/// Supports all fields including optional attending_puid, sha_member_number,
-/// and sha_intervention_code introduced in AfyaLink 2025 compliance update.
-///
-/// Expected XML structure:
-/// ```xml
-/// <patient>
-///   <clinic_id>KEN-NAIROBI-001</clinic_id>
-///   <patient_number>12345</patient_number>
-///   <national_id>27845612</national_id>
-///   <names>
-///     <first>Wanjiru</first>
-///     <middle>Njeri</middle>
-///     <last>Kamau</last>
-///   </names>
-///   <gender>F</gender>
-///   <date_of_birth>1985-03-15</date_of_birth>
-///   <phone>+254712345678</phone>
-///   <location>
-///     <county>Nairobi</county>
-///     <subcounty>Westlands</subcounty>
-///   </location>
-///   <visit>
-///     <date>2026-02-15</date>
-///     <complaint>Fever and cough</complaint>
-///     <vitals>
-///       <temperature_celsius>38.5</temperature_celsius>
-///       <bp_systolic>120</bp_systolic>
-///       <bp_diastolic>80</bp_diastolic>
-///       <weight_kg>65.0</weight_kg>
-///       <!-- optional: -->
-///       <pulse_rate>88</pulse_rate>
-///       <o2_saturation>98.0</o2_saturation>
-///     </vitals>
-///     <diagnosis>Upper respiratory tract infection</diagnosis>
-///     <treatment>Amoxicillin 500mg TDS for 7 days</treatment>
-///     <!-- optional AfyaLink 2025 fields: -->
-///     <attending_puid>HWR-KE-12345</attending_puid>
-///     <sha_member_number>SHA/2024/001234</sha_member_number>
-///     <sha_intervention_code>SHA-OPD-001</sha_intervention_code>
-///   </visit>
-/// </patient>
-/// ```
+//! XML-native representation of a Kenyan clinic record.
+//!
+//! Supports all fields including optional attending_puid, sha_member_number,
+//! and sha_intervention_code introduced in AfyaLink 2025 compliance update.
+//!
+//! Expected XML structure:
+//! ```xml
+//! <patient>
+//!   <clinic_id>KEN-NAIROBI-001</clinic_id>
+//!   <patient_number>12345</patient_number>
+//!   <national_id>27845612</national_id>
+//!   <names>
+//!     <first>Wanjiru</first>
+//!     <middle>Njeri</middle>
+//!     <last>Kamau</last>
+//!   </names>
+//!   <gender>F</gender>
+//!   <date_of_birth>1985-03-15</date_of_birth>
+//!   <!-- or, when only the age is known: <age><years>38</years></age> -->
+//!   <phone>+254712345678</phone>
+//!   <location>
+//!     <county>Nairobi</county>
+//!     <subcounty>Westlands</subcounty>
+//!   </location>
+//!   <visit>
+//!     <date>2026-02-15</date>
+//!     <complaint>Fever and cough</complaint>
+//!     <vitals>
+//!       <temperature_celsius>38.5</temperature_celsius>
+//!       <bp_systolic>120</bp_systolic>
+//!       <bp_diastolic>80</bp_diastolic>
+//!       <weight_kg>65.0</weight_kg>
+//!       <!-- optional: -->
+//!       <pulse_rate>88</pulse_rate>
+//!       <o2_saturation>98.0</o2_saturation>
+//!       <height_cm>170.0</height_cm>
+//!       <muac_cm>27.5</muac_cm>
+//!     </vitals>
+//!     <diagnosis>Upper respiratory tract infection</diagnosis>
+//!     <treatment>Amoxicillin 500mg TDS for 7 days</treatment>
+//!     <!-- optional AfyaLink 2025 fields: -->
+//!     <attending_puid>HWR-KE-12345</attending_puid>
+//!     <attending_cadre>clinical_officer</attending_cadre>
+//!     <sha_member_number>SHA/2024/001234</sha_member_number>
+//!     <sha_intervention_code>SHA-OPD-001</sha_intervention_code>
+//!     <department>OPD</department>
+//!   </visit>
+//!   <!-- repeat <visit> for multi-visit exports -->
+//! </patient>
+//! ```
+use chrono::NaiveDate;
 use serde::Deserialize;
 
-use super::schema::{KenyanPatient, Location, Names, Visit, Vitals};
+use crate::error::BridgeError;
+
+use super::counties::official_location;
+use super::schema::{
+    birth_date, gender_code, Cadre, Department, DiagnosisCategory, KenyanPatient, Location, Names,
+    Severity, StatedAge, Visit, Vitals,
+};
 
 #[derive(Debug, Deserialize)]
 #[serde(rename = "patient")]
@@ -54,10 +67,13 @@ pub struct XmlPatient {
     pub national_id: String,
     pub names: XmlNames,
     pub gender: String,
-    pub date_of_birth: String,
+    pub date_of_birth: Option<String>,
+    /// Stated age, for records without a date of birth
+    pub age: Option<StatedAge>,
     pub phone: String,
     pub location: XmlLocation,
-    pub visit: XmlVisit,
+    /// One or more `<visit>` elements.
+    pub visit: Vec<XmlVisit>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -81,6 +97,8 @@ pub struct XmlVitals {
     pub weight_kg: f64,
     pub pulse_rate: Option<i32>,
     pub o2_saturation: Option<f64>,
+    pub height_cm: Option<f64>,
+    pub muac_cm: Option<f64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -89,22 +107,48 @@ pub struct XmlVisit {
     pub complaint: String,
     pub vitals: XmlVitals,
     pub diagnosis: String,
+    /// `encounter_diagnosis` (default) or `problem_list_item`
+    pub diagnosis_category: Option<DiagnosisCategory>,
+    /// `mild`, `moderate` or `severe` (optional)
+    pub diagnosis_severity: Option<Severity>,
+    /// When the illness began, YYYY-MM-DD (optional)
+    pub onset_date: Option<NaiveDate>,
     pub treatment: String,
+    /// Clinician's free-text assessment (optional)
+    pub clinical_note: Option<String>,
     /// HWR PUID of the attending clinician (AfyaLink 2025 — optional)
     pub attending_puid: Option<String>,
+    /// `nurse`, `clinical_officer` or `medical_officer` (optional)
+    pub attending_cadre: Option<Cadre>,
+    /// HWR PUID of whoever took the vitals, if not the attending clinician
+    pub vitals_taken_by: Option<String>,
+    /// Next clinic review, YYYY-MM-DD (optional)
+    pub review_date: Option<NaiveDate>,
+    /// Booked follow-up visit, YYYY-MM-DD (optional)
+    pub next_appointment_date: Option<NaiveDate>,
     /// SHA scheme member number (optional — cash visits omit this)
     pub sha_member_number: Option<String>,
     /// SHA intervention/CPT code (optional)
     pub sha_intervention_code: Option<String>,
+    /// `opd`, `mch`, `ccc` or `emergency` (optional)
+    pub department: Option<Department>,
 }
 
 /// Convert the XML-deserialized struct into the canonical `KenyanPatient`,
 /// re-using all existing mappers unchanged.
-pub fn xml_to_kenyan(x: XmlPatient) -> anyhow::Result<KenyanPatient> {
-    use chrono::NaiveDate;
-
-    let dob = NaiveDate::parse_from_str(&x.date_of_birth, "%Y-%m-%d")
-        .map_err(|e| anyhow::anyhow!("Invalid date_of_birth '{}': {}", x.date_of_birth, e))?;
+pub fn xml_to_kenyan(x: XmlPatient) -> crate::error::Result<KenyanPatient> {
+    let dob = x
+        .date_of_birth
+        .as_deref()
+        .map(|d| {
+            NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|e| {
+                BridgeError::Validation(format!("Invalid date_of_birth '{}': {}", d, e))
+            })
+        })
+        .transpose()?;
+    let visits: Vec<Visit> = x.visit.into_iter().map(xml_visit_to_kenyan).collect();
+    let (date_of_birth, birth_date_estimated) = birth_date(dob, x.age, &visits)?;
+    let mut data_quality = Vec::new();
 
     Ok(KenyanPatient {
         clinic_id: x.clinic_id,
@@ -115,29 +159,66 @@ pub fn xml_to_kenyan(x: XmlPatient) -> anyhow::Result<KenyanPatient> {
             middle: x.names.middle,
             last: x.names.last,
         },
-        gender: x.gender,
-        date_of_birth: dob,
+        gender: gender_code(&x.gender, &mut data_quality),
+        date_of_birth,
+        birth_date_estimated,
         phone: x.phone,
-        location: Location {
-            county: x.location.county,
-            subcounty: x.location.subcounty,
-        },
-        visit: Visit {
-            date: x.visit.date,
-            complaint: x.visit.complaint,
-            vitals: Vitals {
-                temperature_celsius: x.visit.vitals.temperature_celsius,
-                bp_systolic: x.visit.vitals.bp_systolic,
-                bp_diastolic: x.visit.vitals.bp_diastolic,
-                weight_kg: x.visit.vitals.weight_kg,
-                pulse_rate: x.visit.vitals.pulse_rate,
-                o2_saturation: x.visit.vitals.o2_saturation,
+        location: official_location(
+            Location {
+                county: x.location.county,
+                subcounty: x.location.subcounty,
             },
-            diagnosis: x.visit.diagnosis,
-            treatment: x.visit.treatment,
-            attending_puid: x.visit.attending_puid,
-            sha_member_number: x.visit.sha_member_number,
-            sha_intervention_code: x.visit.sha_intervention_code,
-        },
+            &mut data_quality,
+        ),
+        visits,
+        photo: None,
+        biometrics: Vec::new(),
+        immunizations: Vec::new(),
+        data_quality,
     })
 }
+
+fn xml_visit_to_kenyan(v: XmlVisit) -> Visit {
+    Visit {
+        date: v.date,
+        complaint: v.complaint,
+        vitals: Vitals {
+            temperature_celsius: v.vitals.temperature_celsius,
+            bp_systolic: v.vitals.bp_systolic,
+            bp_diastolic: v.vitals.bp_diastolic,
+            weight_kg: v.vitals.weight_kg,
+            pulse_rate: v.vitals.pulse_rate,
+            o2_saturation: v.vitals.o2_saturation,
+            height_cm: v.vitals.height_cm,
+            muac_cm: v.vitals.muac_cm,
+            devices: Vec::new(),
+        },
+        diagnosis: v.diagnosis,
+        diagnosis_category: v.diagnosis_category.unwrap_or_default(),
+        diagnosis_severity: v.diagnosis_severity,
+        onset_date: v.onset_date,
+        treatment: v.treatment,
+        clinical_note: v.clinical_note,
+        attending_puid: v.attending_puid,
+        attending_cadre: v.attending_cadre,
+        vitals_taken_by: v.vitals_taken_by,
+        sha_member_number: v.sha_member_number,
+        sha_intervention_code: v.sha_intervention_code,
+        attachments: Vec::new(),
+        lab_orders: Vec::new(),
+        imaging_orders: Vec::new(),
+        visit_id: None,
+        previous_visit_id: None,
+        programme: None,
+        hiv_who_stage: None,
+        tb_phase: None,
+        regimen: None,
+        review_date: v.review_date,
+        next_appointment_date: v.next_appointment_date,
+        anc: None,
+        screening: None,
+        triage_category: None,
+        danger_signs: Vec::new(),
+        department: v.department,
+    }
+}
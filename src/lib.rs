@@ -1,7 +1,71 @@
+#[cfg(feature = "sqlite")]
+pub mod archive;
+pub mod batch_manifest;
+#[cfg(feature = "broker")]
+pub mod broker;
+pub mod bulk_export;
+pub mod bundle_lint;
+pub mod circuit_breaker;
 pub mod cr_lookup;
+#[cfg(all(feature = "sqlite", feature = "network"))]
+pub mod cr_reconcile;
+pub mod deidentify;
+pub mod dhis2;
+pub mod error;
+pub mod facility;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod fhir_bundle;
+pub mod fhir_version;
+pub mod generate;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod hl7v2;
+#[cfg(feature = "network")]
+pub mod http;
+pub mod ig_profile;
+pub mod immunization;
 pub mod kenyan;
 pub mod mapper;
+pub mod measures;
+#[cfg(feature = "sqlite")]
+pub mod migrations;
+#[cfg(feature = "network")]
+pub mod mllp;
+#[cfg(all(feature = "sqlite", feature = "network"))]
 pub mod offline_queue;
+#[cfg(feature = "network")]
+pub mod openhim;
+#[cfg(feature = "network")]
+pub mod openmrs;
+#[cfg(all(feature = "sqlite", feature = "network"))]
+pub mod patient_match;
+#[cfg(all(feature = "sqlite", feature = "network"))]
+pub mod pipeline;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(all(feature = "sqlite", feature = "network"))]
+pub mod queue_alerts;
+#[cfg(all(feature = "sqlite", feature = "network"))]
+pub mod queue_crypto;
+pub mod register;
+#[cfg(feature = "network")]
+pub mod remote_validate;
+pub mod reprocess;
+pub mod roundtrip;
+pub mod signing;
+pub mod surveillance;
+pub mod systems;
+pub mod terminology;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod transform;
+#[cfg(feature = "network")]
+pub mod transport;
+#[cfg(feature = "network")]
+pub mod upload;
 pub mod validation;
-
+#[cfg(feature = "sqlite")]
+pub mod visit_ledger;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -1,22 +1,73 @@
 use std::fs;
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
-use clap::{Parser, ValueEnum};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use serde_json::to_string_pretty;
 
-use kenya_fhir_bridge::fhir_bundle::create_transaction_bundle;
+use chrono::{NaiveDate, NaiveTime};
+use fhir_parser::fhir::bundle::{Bundle, BundleEntry};
+use fhir_parser::masking::{mask_identifier, mask_reference, set_reveal_identifiers};
+use kenya_fhir_bridge::archive::BundleArchive;
+use kenya_fhir_bridge::batch_manifest::{BatchManifest, EntryStatus};
+use kenya_fhir_bridge::bulk_export;
+use kenya_fhir_bridge::bundle_lint::{lint_bundle, LintSeverity};
+use kenya_fhir_bridge::circuit_breaker::CircuitBreaker;
+use kenya_fhir_bridge::cr_lookup::live_cr_lookup;
+use kenya_fhir_bridge::cr_reconcile::reconcile;
+use kenya_fhir_bridge::deidentify::Deidentifier;
+use kenya_fhir_bridge::dhis2::{self, Dhis2Mapping};
+use kenya_fhir_bridge::error::BridgeError;
+use kenya_fhir_bridge::facility::FacilityDirectory;
+use kenya_fhir_bridge::fhir_bundle::{bundle_to_json, JsonLayout};
+use kenya_fhir_bridge::fhir_version::FhirVersion;
+use kenya_fhir_bridge::generate::{generate, GenerateOptions};
+#[cfg(feature = "grpc")]
+use kenya_fhir_bridge::grpc;
+use kenya_fhir_bridge::hl7v2;
+use kenya_fhir_bridge::ig_profile::IgProfiles;
+use kenya_fhir_bridge::immunization;
 use kenya_fhir_bridge::kenyan::schema::KenyanPatient;
 use kenya_fhir_bridge::kenyan::xml_schema::{xml_to_kenyan, XmlPatient};
-use kenya_fhir_bridge::mapper::condition::map_condition;
-use kenya_fhir_bridge::mapper::encounter::map_encounter;
-use kenya_fhir_bridge::mapper::medication_request::map_medication_request;
-use kenya_fhir_bridge::mapper::observation::map_vitals;
-use kenya_fhir_bridge::mapper::organization::map_organization;
-use kenya_fhir_bridge::mapper::patient::map_patient;
-use kenya_fhir_bridge::mapper::practitioner::map_practitioner;
-use kenya_fhir_bridge::mapper::sha::map_sha_claims;
-use kenya_fhir_bridge::validation::validate_kenyan_patient;
+use kenya_fhir_bridge::mapper::patient::patient_uuid;
+use kenya_fhir_bridge::mapper::programme::ProgrammeProfile;
+use kenya_fhir_bridge::measures::{self, IndicatorSet};
+use kenya_fhir_bridge::mllp::{self, AckCode};
+use kenya_fhir_bridge::offline_queue::{BundleStatus, OfflineQueue, QueueFilter, QueuePolicy};
+use kenya_fhir_bridge::openhim::OpenHimConfig;
+use kenya_fhir_bridge::openmrs::{self, ObsConcepts, OpenMrsClient};
+use kenya_fhir_bridge::patient_match::{MatchAction, MatchSource, PatientMatcher};
+use kenya_fhir_bridge::queue_alerts::{self, AlertConfig};
+use kenya_fhir_bridge::queue_crypto::QueueKeys;
+use kenya_fhir_bridge::register;
+use kenya_fhir_bridge::remote_validate::{validate_remote, ValidateTarget};
+use kenya_fhir_bridge::reprocess::{self, ChangeKind};
+use kenya_fhir_bridge::roundtrip::bundle_to_kenyan;
+use kenya_fhir_bridge::signing::{load_verifying_key, verify_bundle, BundleSigner};
+use kenya_fhir_bridge::surveillance::{self, SurveillanceFeed, SyndromeRules};
+use kenya_fhir_bridge::systems::SystemUris;
+use kenya_fhir_bridge::terminology::complaint::ComplaintTerminology;
+use kenya_fhir_bridge::terminology::icd11::Icd11Client;
+use kenya_fhir_bridge::terminology::translate::TerminologyService;
+use kenya_fhir_bridge::transform::{
+    fhir_id, transform, transform_with_ledger, BundleIdSource, DuplicateVisitAction,
+    DuplicateVisitCheck, FailurePolicy, LedgerVisits, TimestampSource, TransformOptions,
+    Transformed,
+};
+use kenya_fhir_bridge::transport::{
+    BundleTransport, DeliveryResult, HttpsTransport, OpenHimTransport, TransportConfig,
+};
+use kenya_fhir_bridge::upload::UploadOptions;
+use kenya_fhir_bridge::validation::{validate_kenyan_patient, validate_record};
+
+mod hot_folder;
+mod run_summary;
+
+use hot_folder::HotFolder;
+use run_summary::{Failure, FailureKind, RunSummary};
 
 #[derive(Debug, Clone, ValueEnum)]
 enum InputFormat {
@@ -24,91 +75,2291 @@ enum InputFormat {
     Xml,
 }
 
+#[derive(Debug, Clone, ValueEnum)]
+enum FeedFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum DuplicateAction {
+    /// Report the match and keep the synthetic CR ID
+    Warn,
+    /// Use the matched patient's CR ID
+    Link,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum RepeatedVisit {
+    /// Report the repeat and include the visit anyway
+    Warn,
+    /// Leave the visit out (no second Claim)
+    Skip,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum Profile {
+    /// National TB programme: treatment phase and regimen
+    Tb,
+    /// HIV care and treatment: WHO clinical stage and ART regimen
+    Hiv,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum FhirRelease {
+    /// FHIR R4 (4.0.1), what AfyaLink and the SHR accept
+    R4,
+    /// FHIR R4B (4.3.0); the same resources as R4
+    R4b,
+    /// FHIR R5 (5.0.0), for R5 pilots
+    R5,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum QueueStatus {
+    Pending,
+    Sent,
+    /// Expired or out of retries
+    Failed,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum OnError {
+    /// A bad field fails the whole record
+    Abort,
+    /// Leave out what cannot be mapped and list it in an OperationOutcome
+    Skip,
+}
+
+/// Without a subcommand the binary runs `transform`, so scripts written
+/// before the subcommands existed keep working.
 #[derive(Parser, Debug)]
 #[command(name = "kenya-fhir-bridge")]
 #[command(about = "Transform Kenyan clinic JSON or XML into FHIR R4 Bundle")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    /// Input file (Kenyan JSON or XML)
-    #[arg(short, long)]
-    input: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    /// Input format
-    #[arg(short, long, value_enum, default_value = "json")]
-    format: InputFormat,
+    #[command(flatten)]
+    transform: TransformArgs,
+
+    /// Show identifiers unmasked in console output (debugging only)
+    #[arg(long, global = true)]
+    show_identifiers: bool,
+
+    /// Write a run summary (JSON: outcome, exit code, counts, status and
+    /// timing per record) here, whatever the outcome
+    #[arg(long, value_name = "FILE", global = true)]
+    summary_json: Option<PathBuf>,
+}
+
+/// Options of `transform`, also accepted without the subcommand.
+#[derive(Args, Debug)]
+struct TransformArgs {
+    /// Input file (Kenyan JSON or XML); `-` reads stdin, JSON or XML by
+    /// the first character
+    #[arg(short, long, required_unless_present = "watch")]
+    input: Option<PathBuf>,
+
+    /// Input format (default: from the content, else the file extension)
+    #[arg(short, long, value_enum)]
+    format: Option<InputFormat>,
 
     /// Output FHIR Bundle JSON file (if omitted, prints to stdout)
     #[arg(short, long)]
     output: Option<PathBuf>,
+
+    /// Minified JSON; the default for --output files, which are usually
+    /// headed for transmission
+    #[arg(long, conflicts_with = "pretty")]
+    compact: bool,
+
+    /// Indented JSON; the default on stdout
+    #[arg(long)]
+    pretty: bool,
+
+    /// Presenting-complaint code list (JSON) replacing the built-in list
+    #[arg(long)]
+    complaint_codes: Option<PathBuf>,
+
+    /// Auto-code diagnoses the built-in crosswalk does not know via the WHO
+    /// ICD-11 API (credentials from ICD11_CLIENT_ID / ICD11_CLIENT_SECRET)
+    #[arg(long)]
+    icd11_autocode: bool,
+
+    /// SQLite cache for ICD-11 autocode results
+    #[arg(long, value_name = "DB", default_value = "terminology.db")]
+    icd11_cache: PathBuf,
+
+    /// Terminology server config (JSON) for ConceptMap $translate of
+    /// diagnosis, medication and intervention codes
+    #[arg(long, value_name = "FILE")]
+    terminology_config: Option<PathBuf>,
+
+    /// Bundle.id to use instead of a random UUID
+    #[arg(long, value_name = "ID")]
+    bundle_id: Option<String>,
+
+    /// Bundle.timestamp (RFC 3339) to use instead of the current time
+    #[arg(long, value_name = "RFC3339")]
+    timestamp: Option<chrono::DateTime<chrono::FixedOffset>>,
+
+    /// Derive Bundle.id and timestamp from the input record, so the same
+    /// record always gives a byte-identical bundle; --bundle-id and
+    /// --timestamp still take precedence
+    #[arg(long)]
+    deterministic: bool,
+
+    /// Before keeping a synthetic CR ID, look for the patient under another
+    /// national ID with this Client Registry's Patient/$match
+    #[arg(long, value_name = "URL", conflicts_with = "match_db")]
+    match_cr: Option<String>,
+
+    /// Like --match-cr, against a local index of patients already seen
+    /// (name, date of birth, phone)
+    #[arg(long, value_name = "DB")]
+    match_db: Option<PathBuf>,
+
+    /// What to do when --match-cr/--match-db finds a likely duplicate
+    #[arg(long, value_enum, default_value = "warn")]
+    match_action: DuplicateAction,
+
+    /// Consecutive failed live CR lookups after which lookups stop and
+    /// synthetic CR IDs are used until --cr-cool-down has passed
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    cr_failures: u32,
+
+    /// Seconds without live CR lookups after --cr-failures failures
+    #[arg(long, value_name = "SECS", default_value_t = 300)]
+    cr_cool_down: u64,
+
+    /// File keeping the CR lookup failure count between runs, for sites
+    /// that transform one record per run
+    #[arg(long, value_name = "FILE")]
+    cr_breaker_state: Option<PathBuf>,
+
+    /// Facility contact details (JSON: clinic_id → phone, email, address,
+    /// town, county, subcounty) for the facility Organization's telecom and
+    /// address, which the SHR requires
+    #[arg(long, value_name = "FILE")]
+    facility_contacts: Option<PathBuf>,
+
+    /// Look up the contact details of clinics --facility-contacts does not
+    /// list in the Facility Registry (AFYALINK_TOKEN)
+    #[arg(long)]
+    facility_registry: bool,
+
+    /// FHIR server base URL; the bundle is checked with its `$validate`
+    /// operation and errors abort before any output is written
+    #[arg(long, value_name = "SERVER")]
+    remote_validate: Option<String>,
+
+    /// With --remote-validate: validate each resource instead of the whole bundle
+    #[arg(long, requires = "remote_validate")]
+    validate_each_resource: bool,
+
+    /// Ledger of visits already transformed (SQLite); a visit with the same
+    /// patient, date and diagnosis as a recorded one is a repeat
+    #[arg(long, value_name = "DB")]
+    visit_ledger: Option<PathBuf>,
+
+    /// What to do with a visit --visit-ledger has seen before
+    #[arg(long, value_enum, default_value = "warn", requires = "visit_ledger")]
+    duplicate_visits: RepeatedVisit,
+
+    /// What a visit field that cannot be mapped (out-of-range vitals, bad
+    /// date or attachment) does to the bundle
+    #[arg(long, value_enum, default_value = "abort")]
+    on_error: OnError,
+
+    /// National programme profile: visits with its diagnosis get a
+    /// programme EpisodeOfCare, Condition.stage and a regimen CarePlan
+    #[arg(long, value_enum)]
+    profile: Option<Profile>,
+
+    /// Research/analytics output: drop names, phone, photo and identifiers,
+    /// keep the birth year only and replace the patient id with a keyed
+    /// pseudonym (key from DEIDENTIFY_KEY)
+    #[arg(long)]
+    deidentify: bool,
+
+    /// FHIR release of the bundle (R5 renames and retypes some elements;
+    /// `bundle lint` and `bundle to-kenyan` read R4 only)
+    #[arg(long, value_enum, default_value = "r4")]
+    fhir_version: FhirRelease,
+
+    /// Tag each resource's meta.profile with its Kenya FHIR IG profile, so
+    /// the receiving server validates against the national profiles
+    #[arg(long)]
+    ig_profiles: bool,
+
+    /// Profile map (JSON: resourceType → list of profile URLs) replacing the
+    /// built-in Kenya IG one; implies --ig-profiles
+    #[arg(long, value_name = "FILE")]
+    ig_profile_map: Option<PathBuf>,
+
+    /// System URI map (JSON: built-in URI or base → this deployment's), for
+    /// registries whose identifier and code systems differ from the built-in
+    /// ones
+    #[arg(long, value_name = "FILE")]
+    system_uris: Option<PathBuf>,
+
+    /// Also store the bundle in this archive database (searchable with `archive search`)
+    #[arg(long, value_name = "DB")]
+    archive: Option<PathBuf>,
+
+    /// Sign the bundle (detached ES256 JWS in Bundle.signature) with this
+    /// facility P-256 private key (PKCS#8 PEM)
+    #[arg(long, value_name = "PEM")]
+    sign_key: Option<PathBuf>,
+
+    /// Parse, validate and map, then print the bundle and what it holds;
+    /// no live CR lookup or patient matching, and nothing is written,
+    /// archived or recorded in the visit ledger
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Watch this folder instead of reading --input: each Kenyan record file
+    /// dropped in is transformed, its bundle queued for `serve`, and the
+    /// file moved to processed/ or failed/
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["input", "output", "dry_run"])]
+    watch: Option<PathBuf>,
+
+    /// With --watch: queue database
+    #[arg(
+        long,
+        value_name = "DB",
+        default_value = "queue.db",
+        conflicts_with = "input"
+    )]
+    queue_db: PathBuf,
+
+    /// With --watch: transmission window / retry limit (JSON); defaults to
+    /// 7 days, 10 attempts
+    #[arg(long, value_name = "FILE", conflicts_with = "input")]
+    queue_policy: Option<PathBuf>,
+
+    /// With --watch: queue key set (JSON) for encrypted rows; if omitted
+    /// the OS keyring is used (Windows, macOS)
+    #[arg(long, value_name = "FILE", conflicts_with = "input")]
+    queue_keys: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Transform one Kenyan record into a FHIR Bundle (the default)
+    Transform(Box<TransformArgs>),
+    /// Check Kenyan records against the input rules without transforming them
+    Validate(ValidateArgs),
+    /// Aggregate syndromic surveillance signals from Kenyan records into a daily feed
+    Surveillance(SurveillanceArgs),
+    /// Evaluate DHA quality indicators over archived bundles → MeasureReports + CSV
+    Measures(MeasuresArgs),
+    /// Submit a bundle file, compressed and resumable where the endpoint allows
+    #[command(alias = "submit")]
+    Send(SendArgs),
+    /// Keep sending the offline queue: each pass expires bundles past the
+    /// transmission window and submits the rest
+    Serve(ServeArgs),
+    /// Accept HL7 v2 messages over MLLP, convert them and queue the bundles
+    /// for `serve`
+    MllpListen(MllpListenArgs),
+    /// Serve the transform/validate/enqueue API over gRPC
+    /// (proto/kenya_fhir_bridge.proto)
+    #[cfg(feature = "grpc")]
+    GrpcServe(GrpcServeArgs),
+    /// Re-run archived inputs with the current mappers; nothing is submitted
+    Reprocess(ReprocessArgs),
+    /// Replace synthetic CR IDs in queued and archived bundles with the
+    /// Client Registry's, once it is reachable (needs AFYALINK_TOKEN)
+    Reconcile(ReconcileArgs),
+    /// Inspect generated FHIR Bundles
+    Bundle {
+        #[command(subcommand)]
+        command: BundleCommand,
+    },
+    /// Archive of submitted bundles with full-text search
+    Archive {
+        /// Archive database
+        #[arg(long, default_value = "archive.db")]
+        db: PathBuf,
+
+        #[command(subcommand)]
+        command: ArchiveCommand,
+    },
+    /// Pull visits from an EMR and turn them into bundles
+    Import {
+        #[command(subcommand)]
+        command: ImportCommand,
+    },
+    /// Aggregate reports for the MOH registers
+    Report {
+        #[command(subcommand)]
+        command: ReportCommand,
+    },
+    /// Export generated bundles for analytics
+    Export {
+        #[command(subcommand)]
+        command: ExportCommand,
+    },
+    /// Write synthetic Kenyan records for load tests and fixtures
+    Generate(GenerateArgs),
+    /// Offline transmission queue maintenance
+    Queue {
+        #[command(flatten)]
+        queue: QueueArgs,
+
+        #[command(subcommand)]
+        command: QueueCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ImportCommand {
+    /// Encounters from a KenyaEMR/OpenMRS REST API (credentials from
+    /// OPENMRS_USERNAME / OPENMRS_PASSWORD)
+    Openmrs(OpenMrsArgs),
+}
+
+#[derive(Subcommand, Debug)]
+enum ReportCommand {
+    /// MOH 705 outpatient morbidity counts as a DHIS2 dataValueSet
+    Dhis2(Dhis2Args),
+    /// OPD register line list (MOH 204A/B, with MOH 705 rows) as CSV
+    Register(RegisterArgs),
+    /// KEPI doses due or overdue, per child, as CSV for defaulter tracing
+    ImmunizationDue(ImmunizationDueArgs),
+}
+
+#[derive(Subcommand, Debug)]
+enum ExportCommand {
+    /// FHIR Bulk Data NDJSON, one `<resourceType>.ndjson` file per type
+    Bulk(BulkExportArgs),
+}
+
+#[derive(Subcommand, Debug)]
+enum QueueCommand {
+    /// Generate a new queue encryption key, re-encrypt every queued bundle
+    /// with it and retire the old keys (the first run turns encryption on)
+    RotateKey {
+        /// Queue key set (JSON); if omitted the OS keyring is used (Windows, macOS)
+        #[arg(long, value_name = "FILE")]
+        keys: Option<PathBuf>,
+    },
+    /// Mark pending bundles older than the transmission window as failed
+    Expire,
+    /// Nightly upkeep: expire, move sent rows past the retention period
+    /// (policy `sent_retention_days`, default 30) into the compressed sent
+    /// archive, and VACUUM
+    Maintain {
+        /// Also write the maintenance report here (JSON)
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+    },
+    /// Check pending rows for tampering or corruption before transmission
+    Verify {
+        /// Queue key set (JSON) for encrypted rows; if omitted the OS
+        /// keyring is used (Windows, macOS)
+        #[arg(long, value_name = "FILE")]
+        keys: Option<PathBuf>,
+    },
+    /// List queued bundles, in any status, matching every filter given
+    List {
+        /// The clinic's patient number
+        #[arg(long)]
+        patient: Option<String>,
+
+        /// Facility id
+        #[arg(long)]
+        clinic: Option<String>,
+
+        #[arg(long, value_enum)]
+        status: Option<QueueStatus>,
+
+        /// Queued on or after this date (YYYY-MM-DD, UTC)
+        #[arg(long)]
+        from: Option<NaiveDate>,
+
+        /// Queued on or before this date (YYYY-MM-DD, UTC)
+        #[arg(long)]
+        to: Option<NaiveDate>,
+
+        /// Queue key set (JSON) for encrypted rows; if omitted the OS
+        /// keyring is used (Windows, macOS)
+        #[arg(long, value_name = "FILE")]
+        keys: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ArchiveCommand {
+    /// Add existing bundle files (or directories of them) to the archive
+    Add {
+        #[arg(required = true)]
+        bundles: Vec<PathBuf>,
+    },
+    /// Find archived bundles by diagnosis, treatment, claim number or metadata
+    Search {
+        /// Words to look for; all must match
+        #[arg(long)]
+        text: String,
+
+        /// Maximum number of results
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// A patient's archived visits, latest first, to look up past
+    /// submissions offline
+    History {
+        /// The clinic's patient number
+        #[arg(long)]
+        patient: String,
+
+        /// Only this facility's visits (patient numbers are per clinic)
+        #[arg(long)]
+        facility: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BundleCommand {
+    /// Check a Bundle against the FHIR R4 invariants the bridge relies on
+    Lint {
+        /// Bundle JSON file
+        file: PathBuf,
+    },
+    /// Reconstruct the Kenyan JSON record a Bundle was produced from
+    ToKenyan {
+        /// Bundle JSON file
+        file: PathBuf,
+
+        /// Output Kenyan JSON file (if omitted, prints to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// System URI map the bundle was transformed with
+        #[arg(long, value_name = "FILE")]
+        system_uris: Option<PathBuf>,
+    },
+    /// Check a signed Bundle's JWS against the facility public key
+    Verify {
+        /// Bundle JSON file
+        file: PathBuf,
+
+        /// Facility P-256 public key (SPKI PEM)
+        #[arg(long, value_name = "PEM")]
+        public_key: PathBuf,
+    },
+}
+
+#[derive(Args, Debug)]
+struct SurveillanceArgs {
+    /// Input files (Kenyan JSON or XML); repeat or pass several
+    #[arg(short, long, required = true, num_args = 1..)]
+    input: Vec<PathBuf>,
+
+    /// Input format (default: from the content, else the file extension)
+    #[arg(short, long, value_enum)]
+    format: Option<InputFormat>,
+
+    /// Only count visits on this date (YYYY-MM-DD)
+    #[arg(long)]
+    date: Option<String>,
+
+    /// Syndrome rules (JSON) replacing the built-in list
+    #[arg(long)]
+    rules: Option<PathBuf>,
+
+    /// Feed format
+    #[arg(long, value_enum, default_value = "json")]
+    feed_format: FeedFormat,
+
+    /// Surveillance endpoint to POST the feed to
+    #[arg(long)]
+    endpoint: Option<String>,
+
+    /// Write the feed to a file (if omitted and no endpoint, prints to stdout)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct GenerateArgs {
+    /// Number of records
+    #[arg(short = 'n', long, default_value_t = 100)]
+    count: usize,
+
+    /// Random seed; the same seed writes the same records (default: from
+    /// the clock, printed so the run can be repeated)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Facility ids to spread records over; repeat for several
+    #[arg(long = "clinic-id", default_value = "KEN-NAIROBI-001")]
+    clinic_ids: Vec<String>,
+
+    /// First visit date (YYYY-MM-DD)
+    #[arg(long, default_value = "2026-01-01")]
+    from: NaiveDate,
+
+    /// Last visit date (YYYY-MM-DD)
+    #[arg(long, default_value = "2026-12-31")]
+    to: NaiveDate,
+
+    /// Up to this many visits per record
+    #[arg(long, default_value_t = 1)]
+    max_visits: usize,
+
+    /// Share of visits (0–1) with SHA member number and intervention code
+    #[arg(long, default_value_t = 0.3)]
+    sha_share: f64,
+
+    /// Directory for the records, one `<clinic id>_<patient number>.json` each
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct OpenMrsArgs {
+    /// OpenMRS root URL, e.g. https://emr.example.go.ke/openmrs
+    #[arg(long)]
+    base_url: String,
+
+    /// Facility id the records are filed under
+    #[arg(long)]
+    clinic_id: String,
+
+    /// First encounter date (YYYY-MM-DD, inclusive)
+    #[arg(long)]
+    from: NaiveDate,
+
+    /// Last encounter date (YYYY-MM-DD, inclusive)
+    #[arg(long)]
+    to: NaiveDate,
+
+    /// Concept UUIDs (JSON) replacing the built-in CIEL ones
+    #[arg(long, value_name = "FILE")]
+    concepts: Option<PathBuf>,
+
+    /// Directory for the bundles, one `<bundle id>.json` per patient
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Also keep the converted Kenyan records in this directory
+    #[arg(long, value_name = "DIR")]
+    records: Option<PathBuf>,
+
+    /// Also store the bundles in this archive database
+    #[arg(long, value_name = "DB")]
+    archive: Option<PathBuf>,
+
+    /// Presenting-complaint code list (JSON) replacing the built-in list
+    #[arg(long)]
+    complaint_codes: Option<PathBuf>,
+
+    /// Progress manifest (JSON Lines): each patient (by Patient resource
+    /// id) with its outcome, written as the run goes
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<PathBuf>,
+
+    /// Skip the patients --manifest records as done, so an interrupted
+    /// backfill carries on without writing their bundles twice
+    #[arg(long, requires = "manifest")]
+    resume: bool,
+}
+
+#[derive(Args, Debug)]
+struct Dhis2Args {
+    /// Input files (Kenyan JSON or XML); repeat or pass several
+    #[arg(short, long, required = true, num_args = 1..)]
+    input: Vec<PathBuf>,
+
+    /// Input format (default: from the content, else the file extension)
+    #[arg(short, long, value_enum)]
+    format: Option<InputFormat>,
+
+    /// Reporting month as a DHIS2 period (YYYYMM)
+    #[arg(long)]
+    period: String,
+
+    /// Data set, data element, org unit and category option combo ids
+    /// (JSON) replacing the built-in codes
+    #[arg(long, value_name = "FILE")]
+    mapping: Option<PathBuf>,
+
+    /// Write the dataValueSet here (if omitted, prints to stdout)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+#[command(group = clap::ArgGroup::new("source").required(true).args(["input", "queue"]))]
+struct RegisterArgs {
+    /// Input files (Kenyan JSON or XML); repeat or pass several
+    #[arg(short, long, num_args = 1..)]
+    input: Vec<PathBuf>,
+
+    /// Input format (default: from the content, else the file extension)
+    #[arg(short, long, value_enum)]
+    format: Option<InputFormat>,
+
+    /// List the bundles pending in this offline queue database instead
+    #[arg(long, value_name = "DB")]
+    queue: Option<PathBuf>,
+
+    /// Queue key set (JSON) for encrypted rows; if omitted the OS keyring
+    /// is used (Windows, macOS)
+    #[arg(long, value_name = "FILE", requires = "queue")]
+    keys: Option<PathBuf>,
+
+    /// First visit date to list (YYYY-MM-DD, inclusive)
+    #[arg(long)]
+    from: Option<NaiveDate>,
+
+    /// Last visit date to list (YYYY-MM-DD, inclusive)
+    #[arg(long)]
+    to: Option<NaiveDate>,
+
+    /// MOH 705 rows (JSON, as for `report dhis2`) replacing the built-in list
+    #[arg(long, value_name = "FILE")]
+    mapping: Option<PathBuf>,
+
+    /// Write the CSV here (if omitted, prints to stdout)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+#[command(group = clap::ArgGroup::new("source").required(true).args(["bundles", "queue"]))]
+struct BulkExportArgs {
+    /// Bundle JSON files, or directories of them
+    #[arg(short, long, num_args = 1..)]
+    bundles: Vec<PathBuf>,
+
+    /// Export the bundles sent from this offline queue database instead
+    /// (those `queue maintain` has not yet moved to its sent archive)
+    #[arg(long, value_name = "DB")]
+    queue: Option<PathBuf>,
+
+    /// Queue key set (JSON) for encrypted rows; if omitted the OS keyring
+    /// is used (Windows, macOS)
+    #[arg(long, value_name = "FILE", requires = "queue")]
+    keys: Option<PathBuf>,
+
+    /// Directory for the NDJSON files
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct ImmunizationDueArgs {
+    /// Input files (Kenyan JSON or XML); repeat or pass several
+    #[arg(short, long, required = true, num_args = 1..)]
+    input: Vec<PathBuf>,
+
+    /// Input format (default: from the content, else the file extension)
+    #[arg(short, long, value_enum)]
+    format: Option<InputFormat>,
+
+    /// List doses due on or before this date (YYYY-MM-DD; default: today)
+    #[arg(long)]
+    as_of: Option<NaiveDate>,
+
+    /// Write the CSV here (if omitted, prints to stdout)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct MeasuresArgs {
+    /// Archived bundle JSON files, or directories of them
+    #[arg(short, long, required = true, num_args = 1..)]
+    bundles: Vec<PathBuf>,
+
+    /// Reporting period start (YYYY-MM-DD, inclusive)
+    #[arg(long)]
+    from: NaiveDate,
+
+    /// Reporting period end (YYYY-MM-DD, inclusive)
+    #[arg(long)]
+    to: NaiveDate,
+
+    /// Indicator definitions (JSON) replacing the built-in set
+    #[arg(long)]
+    indicators: Option<PathBuf>,
+
+    /// Write the MeasureReport collection Bundle here (if omitted, prints to stdout)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Also write the results as CSV
+    #[arg(long)]
+    csv: Option<PathBuf>,
+}
+
+/// The offline queue database and its policy, shared by `queue` and `serve`.
+#[derive(Args, Debug)]
+struct QueueArgs {
+    /// Queue database
+    #[arg(long, default_value = "queue.db")]
+    db: PathBuf,
+
+    /// Transmission window / retry limit (JSON); defaults to 7 days, 10 attempts
+    #[arg(long, value_name = "FILE")]
+    policy: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct ValidateArgs {
+    /// Input files (Kenyan JSON or XML), or directories of them
+    #[arg(short, long, required = true, num_args = 1..)]
+    input: Vec<PathBuf>,
+
+    /// Input format (default: from the content, else the file extension)
+    #[arg(short, long, value_enum)]
+    format: Option<InputFormat>,
+}
+
+#[derive(Args, Debug)]
+struct SendArgs {
+    /// Bundle JSON file
+    file: PathBuf,
+
+    #[command(flatten)]
+    upload: UploadArgs,
+}
+
+#[derive(Args, Debug)]
+struct ServeArgs {
+    #[command(flatten)]
+    queue: QueueArgs,
+
+    /// Queue key set (JSON) for encrypted rows; if omitted the OS keyring
+    /// is used (Windows, macOS)
+    #[arg(long, value_name = "FILE")]
+    keys: Option<PathBuf>,
+
+    /// Seconds between passes
+    #[arg(long, default_value_t = 300)]
+    interval: u64,
+
+    /// Run a single pass and exit (for cron or a scheduled task)
+    #[arg(long)]
+    once: bool,
+
+    /// Also maintain the queue (as `queue maintain`) once a day, at the
+    /// first pass after this local time (HH:MM)
+    #[arg(long, value_name = "TIME")]
+    maintain_at: Option<NaiveTime>,
+
+    /// Alert facility IT about failed bundles, queue depth and bundles near
+    /// the end of the transmission window (JSON: webhook and/or command,
+    /// thresholds)
+    #[arg(long, value_name = "FILE")]
+    alerts: Option<PathBuf>,
+
+    // Default destination; rows queued with their own endpoint go there
+    #[command(flatten)]
+    upload: UploadArgs,
+}
+
+#[derive(Args, Debug)]
+struct MllpListenArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "0.0.0.0:2575")]
+    bind: String,
+
+    #[command(flatten)]
+    queue: QueueArgs,
+
+    /// Queue key set (JSON) for encrypted rows; if omitted the OS keyring
+    /// is used (Windows, macOS)
+    #[arg(long, value_name = "FILE")]
+    keys: Option<PathBuf>,
+}
+
+#[cfg(feature = "grpc")]
+#[derive(Args, Debug)]
+struct GrpcServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "0.0.0.0:50051")]
+    bind: std::net::SocketAddr,
+
+    #[command(flatten)]
+    queue: QueueArgs,
+
+    /// Queue key set (JSON) for encrypted rows; if omitted the OS keyring
+    /// is used (Windows, macOS)
+    #[arg(long, value_name = "FILE")]
+    keys: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct ReconcileArgs {
+    #[command(flatten)]
+    queue: QueueArgs,
+
+    /// Queue key set (JSON) for encrypted rows; if omitted the OS keyring
+    /// is used (Windows, macOS)
+    #[arg(long, value_name = "FILE")]
+    keys: Option<PathBuf>,
+
+    /// Archive database; its bundles with a synthetic CR ID are corrected,
+    /// given a new Bundle.id and queued again
+    #[arg(long, value_name = "DB")]
+    archive: Option<PathBuf>,
+}
+
+/// Where and how bundles are submitted, shared by `send` and `serve`.
+#[derive(Args, Debug)]
+struct UploadArgs {
+    /// FHIR endpoint (transaction base or upload URL)
+    #[arg(long, required_unless_present_any = ["openhim", "transport"])]
+    endpoint: Option<String>,
+
+    /// Submit through an OpenHIM channel (JSON: base URL, channel paths,
+    /// client certificate, auth); secrets come from OPENHIM_CLIENT_*
+    #[arg(long, value_name = "FILE", conflicts_with = "endpoint")]
+    openhim: Option<PathBuf>,
+
+    /// OpenHIM channel to submit to
+    #[arg(long, default_value = "default", requires = "openhim")]
+    channel: String,
+
+    /// Deliver through a configured transport instead (JSON, `type`:
+    /// https, openhim, drop_folder or sftp; kafka and amqp in builds with
+    /// the `broker` feature)
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["endpoint", "openhim"])]
+    transport: Option<PathBuf>,
+
+    /// Send the body uncompressed
+    #[arg(long)]
+    no_compress: bool,
+
+    /// Payloads above this many bytes use resumable (tus) upload if offered
+    #[arg(long, default_value_t = 256 * 1024)]
+    chunk_size: usize,
+
+    /// Retries per request after a dropped connection
+    #[arg(long, default_value_t = 5)]
+    retries: u32,
+
+    /// Show what would be submitted and where; nothing is sent and the
+    /// queue is left as it is
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+#[command(group = clap::ArgGroup::new("action").required(true).multiple(true).args(["compare", "output"]))]
+struct ReprocessArgs {
+    /// Archived input files (Kenyan JSON or XML, by extension), or directories of them
+    #[arg(short, long, required = true, num_args = 1..)]
+    input: Vec<PathBuf>,
+
+    /// Directory of archived bundles, named after their input (`<stem>.json`)
+    #[arg(long, requires = "compare")]
+    archived: Option<PathBuf>,
+
+    /// Diff each re-transform against its archived bundle and summarise changes
+    #[arg(long, requires = "archived")]
+    compare: bool,
+
+    /// Write re-transformed bundles to this directory
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Presenting-complaint code list (JSON) replacing the built-in list
+    #[arg(long)]
+    complaint_codes: Option<PathBuf>,
+
+    /// Auto-code diagnoses the built-in crosswalk does not know via the WHO
+    /// ICD-11 API (credentials from ICD11_CLIENT_ID / ICD11_CLIENT_SECRET)
+    #[arg(long)]
+    icd11_autocode: bool,
+
+    /// SQLite cache for ICD-11 autocode results
+    #[arg(long, value_name = "DB", default_value = "terminology.db")]
+    icd11_cache: PathBuf,
+
+    /// Terminology server config (JSON) for ConceptMap $translate of
+    /// diagnosis, medication and intervention codes
+    #[arg(long, value_name = "FILE")]
+    terminology_config: Option<PathBuf>,
+
+    /// Progress manifest (JSON Lines): each input file with its outcome,
+    /// written as the run goes
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<PathBuf>,
+
+    /// Skip the files --manifest records as done, to carry on after an
+    /// interrupted run
+    #[arg(long, requires = "manifest")]
+    resume: bool,
+}
+
+/// Expand files and/or directories (matching `extensions` inside, sorted).
+fn collect_files(paths: &[PathBuf], extensions: &[&str]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut in_dir: Vec<PathBuf> = fs::read_dir(path)
+                .with_context(|| format!("Failed to list {:?}", path))?
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| {
+                    p.extension()
+                        .is_some_and(|ext| extensions.iter().any(|e| ext == *e))
+                })
+                .collect();
+            in_dir.sort();
+            files.extend(in_dir);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+/// Read and parse one Bundle JSON file.
+fn load_bundle(path: &Path) -> Result<Bundle> {
+    let raw = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    serde_json::from_str(&raw).with_context(|| format!("Invalid Bundle JSON in {:?}", path))
+}
+
+/// Load bundles from files and/or directories (every `*.json` inside, sorted).
+fn load_bundles(paths: &[PathBuf]) -> Result<Vec<Bundle>> {
+    collect_files(paths, &["json"])?
+        .iter()
+        .map(|f| load_bundle(f))
+        .collect()
+}
+
+/// Format of a record read without `--format`: from the first characters
+/// of the content, else the file extension. CSV and HL7 v2 are recognised
+/// only to say so, rather than failing as an invalid JSON payload.
+fn detect_format(path: &Path, input: &str) -> Result<InputFormat> {
+    let content = input.trim_start();
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    let unsupported = match (content.as_bytes().first(), extension.as_deref()) {
+        (Some(b'<'), _) => return Ok(InputFormat::Xml),
+        (Some(b'{'), _) => return Ok(InputFormat::Json),
+        _ if content.starts_with("MSH|") => "HL7 v2",
+        (_, Some("hl7")) => "HL7 v2",
+        (_, Some("csv")) => "CSV",
+        (_, Some("xml")) => return Ok(InputFormat::Xml),
+        _ if content.lines().next().is_some_and(|l| l.contains(',')) => "CSV",
+        _ => return Ok(InputFormat::Json),
+    };
+    Err(Failure::new(
+        FailureKind::Validation,
+        format!(
+            "{:?} looks like {}; only Kenyan JSON and XML records can be read",
+            path, unsupported
+        ),
+    )
+    .into())
 }
 
-fn run(cli: Cli) -> Result<()> {
-    let input_str =
-        fs::read_to_string(&cli.input).with_context(|| format!("Failed to read {:?}", cli.input))?;
+/// Read, parse and validate one Kenyan record; `-` reads it from stdin.
+/// Without a format it is detected.
+fn load_record(path: &Path, format: Option<&InputFormat>) -> Result<KenyanPatient> {
+    load_record_with(path, format, validate_kenyan_patient)
+}
+
+/// Full record validation, or record-level only.
+type Validate = fn(&KenyanPatient) -> Result<(), BridgeError>;
+
+/// [`load_record`] with another validation, e.g. record-level only.
+fn load_record_with(
+    path: &Path,
+    format: Option<&InputFormat>,
+    validate: Validate,
+) -> Result<KenyanPatient> {
+    let input_str = if path == Path::new("-") {
+        let mut input = String::new();
+        std::io::stdin()
+            .read_to_string(&mut input)
+            .context("Failed to read the record from stdin")?;
+        input
+    } else {
+        fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?
+    };
+    let format = match format {
+        Some(format) => format.clone(),
+        None => detect_format(path, &input_str)?,
+    };
 
-    let kenyan: KenyanPatient = match cli.format {
+    let parsed: Result<KenyanPatient> = match format {
         InputFormat::Json => {
-            serde_json::from_str(&input_str).context("Invalid Kenyan JSON payload")?
+            serde_json::from_str(&input_str).context("Invalid Kenyan JSON payload")
+        }
+        InputFormat::Xml => serde_xml_rs::from_str::<XmlPatient>(&input_str)
+            .context("Invalid Kenyan XML payload")
+            .and_then(|xml| Ok(xml_to_kenyan(xml)?)),
+    };
+    let kenyan = parsed
+        .and_then(|kenyan| Ok(validate(&kenyan).map(|()| kenyan)?))
+        .context(Failure::new(
+            FailureKind::Validation,
+            "Patient record failed validation",
+        ))?;
+    for note in &kenyan.data_quality {
+        eprintln!("[QUALITY] {}", note);
+    }
+    Ok(kenyan)
+}
+
+fn run_transform(args: TransformArgs, summary: &mut RunSummary) -> Result<()> {
+    if let Some(dir) = &args.watch {
+        return run_watch(dir, &args);
+    }
+    let input = args.input.as_ref().context("--input is required")?;
+    let started = Instant::now();
+    let result = transform_record(input, &args);
+    summary.record(input, &result, started.elapsed());
+    result
+}
+
+fn transform_record(input: &Path, args: &TransformArgs) -> Result<()> {
+    let options = transform_options(args)?;
+    let (_, bundle, visits) = load_and_transform(input, args, &options)?;
+
+    let to_file = args.output.is_some() && !args.dry_run;
+    let layout = if args.compact || (to_file && !args.pretty) {
+        JsonLayout::Compact
+    } else {
+        JsonLayout::Pretty
+    };
+    let json = bundle_to_json(&bundle, layout)?;
+
+    if args.dry_run {
+        report_dry_run(&bundle, args);
+        println!("{json}");
+        return Ok(());
+    }
+
+    if let Some(db) = &args.archive {
+        BundleArchive::open(db)?.store(&bundle)?;
+    }
+
+    if let Some(output_path) = &args.output {
+        fs::write(output_path, json)
+            .with_context(|| format!("Failed to write {:?}", output_path))?;
+    } else {
+        println!("{json}");
+    }
+    visits.record()?;
+    Ok(())
+}
+
+/// `transform --watch`: every record file dropped in the folder becomes a
+/// queued bundle. The options are built once, so the CR circuit breaker and
+/// caches carry over from file to file.
+fn run_watch(dir: &Path, args: &TransformArgs) -> Result<()> {
+    let mut queue = open_queue(&QueueArgs {
+        db: args.queue_db.clone(),
+        policy: args.queue_policy.clone(),
+    })?;
+    if let Some(keys) = load_queue_keys(args.queue_keys.as_deref())? {
+        queue = queue.with_keys(keys);
+    }
+    let options = transform_options(args)?;
+    let archive = args
+        .archive
+        .as_deref()
+        .map(BundleArchive::open)
+        .transpose()?;
+    let folder = HotFolder::open(dir)?;
+    eprintln!("[WATCH] watching {:?}", dir);
+    folder.watch(|path| {
+        let (kenyan, bundle, visits) = load_and_transform(path, args, &options)?;
+        let bundle_id = bundle.id.clone().unwrap_or_default();
+        let json = bundle_to_json(&bundle, JsonLayout::Compact)?;
+        queue.enqueue(&bundle_id, &json, &kenyan.patient_number, &kenyan.clinic_id)?;
+        // Queued is what counts: a retry after this would queue it twice
+        if let Err(e) = visits.record() {
+            eprintln!("[WATCH] bundle {} visits not recorded: {:#}", bundle_id, e);
+        }
+        if let Some(archive) = &archive {
+            if let Err(e) = archive.store(&bundle) {
+                eprintln!("[WATCH] bundle {} not archived: {:#}", bundle_id, e);
+            }
+        }
+        Ok(format!("queued as bundle {}", bundle_id))
+    })
+}
+
+fn transform_options(args: &TransformArgs) -> Result<TransformOptions> {
+    let mut options = TransformOptions {
+        on_error: match args.on_error {
+            OnError::Abort => FailurePolicy::Abort,
+            OnError::Skip => FailurePolicy::Skip,
+        },
+        ..TransformOptions::default()
+    };
+    if let Some(path) = &args.complaint_codes {
+        options.complaints = ComplaintTerminology::from_json_file(path)?;
+    }
+    if args.icd11_autocode {
+        options.icd11 = Some(Icd11Client::from_env(&args.icd11_cache)?);
+    }
+    if let Some(path) = &args.terminology_config {
+        options.translate = Some(TerminologyService::from_json_file(path)?);
+    }
+    let match_source = match (&args.match_cr, &args.match_db) {
+        (Some(url), _) => Some(MatchSource::Registry {
+            base_url: url.clone(),
+        }),
+        (None, Some(db)) => Some(MatchSource::Local { db: db.clone() }),
+        (None, None) => None,
+    };
+    options.bundle_id = match (&args.bundle_id, args.deterministic) {
+        (Some(id), _) => BundleIdSource::Fixed(fhir_id(id)?),
+        (None, true) => BundleIdSource::FromInput,
+        (None, false) => BundleIdSource::Random,
+    };
+    options.timestamp = match (args.timestamp, args.deterministic) {
+        (Some(at), _) => TimestampSource::Fixed(at),
+        (None, true) => TimestampSource::FromInput,
+        (None, false) => TimestampSource::Now,
+    };
+    options.duplicate_visits = args.visit_ledger.clone().map(|db| DuplicateVisitCheck {
+        db,
+        action: match args.duplicate_visits {
+            RepeatedVisit::Warn => DuplicateVisitAction::Warn,
+            RepeatedVisit::Skip => DuplicateVisitAction::Skip,
+        },
+    });
+    options.profile = args.profile.as_ref().map(|profile| match profile {
+        Profile::Tb => ProgrammeProfile::Tb,
+        Profile::Hiv => ProgrammeProfile::Hiv,
+    });
+    options.dry_run = args.dry_run;
+    if args.deidentify {
+        options.deidentify = Some(Deidentifier::from_env()?);
+    }
+    options.fhir_version = match args.fhir_version {
+        FhirRelease::R4 => FhirVersion::R4,
+        FhirRelease::R4b => FhirVersion::R4B,
+        FhirRelease::R5 => FhirVersion::R5,
+    };
+    options.ig_profiles = match &args.ig_profile_map {
+        Some(path) => Some(IgProfiles::from_json_file(path)?),
+        None => args.ig_profiles.then(IgProfiles::default),
+    };
+    if let Some(path) = &args.system_uris {
+        options.system_uris = SystemUris::from_json_file(path)?;
+    }
+    options.cr_breaker.threshold = args.cr_failures;
+    options.cr_breaker.cool_down = Duration::from_secs(args.cr_cool_down);
+    if let Some(path) = &args.cr_breaker_state {
+        options.cr_breaker = options.cr_breaker.with_state_file(path);
+    }
+    let mut facilities = match &args.facility_contacts {
+        Some(path) => Some(FacilityDirectory::from_json_file(path)?),
+        None => args.facility_registry.then(FacilityDirectory::default),
+    };
+    if let Some(facilities) = &mut facilities {
+        facilities.registry = args.facility_registry;
+    }
+    options.facilities = facilities;
+    options.patient_match = match_source.map(|source| {
+        let action = match args.match_action {
+            DuplicateAction::Warn => MatchAction::Warn,
+            DuplicateAction::Link => MatchAction::Link,
+        };
+        PatientMatcher::new(source, action)
+    });
+    Ok(options)
+}
+
+/// Load, validate and map one record, then remote-validate and sign the
+/// bundle as asked. Its visits are for the caller to record once the bundle
+/// is out.
+fn load_and_transform(
+    input: &Path,
+    args: &TransformArgs,
+    options: &TransformOptions,
+) -> Result<(KenyanPatient, Bundle, LedgerVisits)> {
+    let validate: Validate = match args.on_error {
+        OnError::Abort => validate_kenyan_patient,
+        OnError::Skip => validate_record,
+    };
+    let kenyan = load_record_with(input, args.format.as_ref(), validate)?;
+
+    let Transformed {
+        mut bundle,
+        visits,
+        skipped,
+        data_quality,
+    } = transform_with_ledger(&kenyan, options).context(Failure::new(
+        FailureKind::Mapping,
+        "Record could not be mapped to FHIR",
+    ))?;
+    for note in &data_quality {
+        eprintln!("[QUALITY] {}", note);
+    }
+    for note in &skipped {
+        eprintln!("[SKIPPED] {}", note);
+    }
+    if let Some(server) = &args.remote_validate {
+        let target = if args.validate_each_resource {
+            ValidateTarget::EachResource
+        } else {
+            ValidateTarget::Bundle
+        };
+        let issues = validate_remote(server, &bundle, target)?;
+        for i in &issues {
+            let location = i
+                .issue
+                .expression
+                .as_ref()
+                .map(|e| format!(" at {}", e.join(", ")))
+                .unwrap_or_default();
+            eprintln!(
+                "[REMOTE-VALIDATE] {} {}{}: {}",
+                i.issue.severity,
+                mask_reference(&i.target),
+                location,
+                i.issue.diagnostics.as_deref().unwrap_or(&i.issue.code),
+            );
+        }
+        let errors = issues.iter().filter(|i| i.issue.is_error()).count();
+        if errors > 0 {
+            return Err(Failure::new(
+                FailureKind::Validation,
+                format!("Remote validation reported {} error(s)", errors),
+            )
+            .into());
+        }
+    }
+
+    // Last, so the signature covers exactly what is written and archived
+    if let Some(key) = &args.sign_key {
+        BundleSigner::from_pem_file(key)?.sign(&mut bundle)?;
+    }
+    Ok((kenyan, bundle, visits))
+}
+
+/// What a dry run would have produced and left undone, on stderr so stdout
+/// stays the bundle.
+fn report_dry_run(bundle: &Bundle, args: &TransformArgs) {
+    let mut counts = std::collections::BTreeMap::new();
+    let entries = bundle.entry.iter().flatten();
+    for resource in entries.filter_map(|e| e.resource.as_ref()) {
+        let resource_type = resource["resourceType"].as_str().unwrap_or("?");
+        *counts.entry(resource_type).or_insert(0) += 1;
+    }
+    let summary: Vec<String> = counts
+        .iter()
+        .map(|(resource_type, n)| format!("{} {}", resource_type, n))
+        .collect();
+    eprintln!(
+        "[DRY-RUN] would generate {} entries: {}",
+        bundle.entry.as_ref().map_or(0, Vec::len),
+        summary.join(", ")
+    );
+    if let Some(path) = &args.output {
+        eprintln!("[DRY-RUN] not written to {:?}", path);
+    }
+    if args.archive.is_some() {
+        eprintln!("[DRY-RUN] not archived");
+    }
+}
+
+fn run_surveillance(args: SurveillanceArgs) -> Result<()> {
+    let records = args
+        .input
+        .iter()
+        .map(|p| load_record(p, args.format.as_ref()).with_context(|| format!("In {:?}", p)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let rules = match &args.rules {
+        Some(path) => SyndromeRules::from_json_file(path)?,
+        None => SyndromeRules::default(),
+    };
+    let signals = surveillance::aggregate(&records, &rules, args.date.as_deref());
+
+    let (payload, content_type) = match args.feed_format {
+        FeedFormat::Json => {
+            let feed = SurveillanceFeed {
+                generated_at: chrono::Utc::now().to_rfc3339(),
+                signals,
+            };
+            (to_string_pretty(&feed)?, "application/json")
+        }
+        FeedFormat::Csv => (surveillance::to_csv(&signals), "text/csv"),
+    };
+
+    if let Some(endpoint) = &args.endpoint {
+        surveillance::post_feed(endpoint, content_type, &payload)?;
+    }
+    if let Some(output_path) = &args.output {
+        fs::write(output_path, &payload)
+            .with_context(|| format!("Failed to write {:?}", output_path))?;
+    } else if args.endpoint.is_none() {
+        print!("{payload}");
+    }
+
+    Ok(())
+}
+
+fn run_import(command: ImportCommand) -> Result<()> {
+    match command {
+        ImportCommand::Openmrs(args) => run_import_openmrs(args),
+    }
+}
+
+fn run_generate(args: GenerateArgs) -> Result<()> {
+    if args.from > args.to {
+        anyhow::bail!("--from must not be after --to");
+    }
+    if !(0.0..=1.0).contains(&args.sha_share) {
+        anyhow::bail!("--sha-share must be between 0 and 1");
+    }
+    let seed = args.seed.unwrap_or_else(|| {
+        let seed = chrono::Utc::now().timestamp_micros() as u64;
+        eprintln!("[GENERATE] seed {}", seed);
+        seed
+    });
+    let options = GenerateOptions {
+        count: args.count,
+        seed,
+        clinics: args.clinic_ids,
+        from: args.from,
+        to: args.to,
+        max_visits: args.max_visits,
+        sha_share: args.sha_share,
+    };
+    fs::create_dir_all(&args.output)
+        .with_context(|| format!("Failed to create {:?}", args.output))?;
+    let records = generate(&options);
+    for record in &records {
+        let path = args.output.join(format!(
+            "{}_{}.json",
+            record.clinic_id, record.patient_number
+        ));
+        fs::write(&path, to_string_pretty(record)?)
+            .with_context(|| format!("Failed to write {:?}", path))?;
+    }
+    println!(
+        "Wrote {} synthetic records to {}",
+        records.len(),
+        args.output.display()
+    );
+    Ok(())
+}
+
+fn run_import_openmrs(args: OpenMrsArgs) -> Result<()> {
+    let concepts = match &args.concepts {
+        Some(path) => ObsConcepts::from_json_file(path)?,
+        None => ObsConcepts::default(),
+    };
+    let mut options = TransformOptions::default();
+    if let Some(path) = &args.complaint_codes {
+        options.complaints = ComplaintTerminology::from_json_file(path)?;
+    }
+    let archive = args
+        .archive
+        .as_deref()
+        .map(BundleArchive::open)
+        .transpose()?;
+    for dir in std::iter::once(&args.output).chain(&args.records) {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    }
+
+    let client = OpenMrsClient::from_env(&args.base_url)?;
+    let encounters = client.encounters(args.from, args.to)?;
+    let import = openmrs::encounters_to_kenyan(&encounters, &args.clinic_id, &concepts)?;
+    if import.incomplete_visits > 0 {
+        eprintln!(
+            "[OPENMRS] {} visit(s) without vitals, diagnosis or treatment left out",
+            import.incomplete_visits
+        );
+    }
+    if import.skipped_patients > 0 {
+        eprintln!(
+            "[OPENMRS] {} patient(s) without a national ID, clinic number, name or birth date left out",
+            import.skipped_patients
+        );
+    }
+
+    let mut manifest = open_manifest(args.manifest.as_deref(), args.resume)?;
+    let mut written = 0;
+    for (i, kenyan) in import.records.iter().enumerate() {
+        // The Patient resource id: no identifiers on disk beyond the bundles
+        let key = patient_uuid(&kenyan.clinic_id, &kenyan.patient_number);
+        if manifest.as_ref().is_some_and(|m| m.is_done(&key)) {
+            continue;
+        }
+        let outcome = import_record(i, kenyan, &args, &options, archive.as_ref());
+        let (status, detail) = match &outcome {
+            Ok(None) => (EntryStatus::Ok, None),
+            Ok(Some(refused)) => {
+                // Position only: no identifiers in logs
+                eprintln!("[OPENMRS] record {} {}", i + 1, refused);
+                (EntryStatus::Failed, Some(refused.clone()))
+            }
+            Err(e) => (EntryStatus::Failed, Some(format!("{:#}", e))),
+        };
+        if let Some(manifest) = &mut manifest {
+            manifest.record(&key, status, detail.as_deref())?;
+        }
+        if outcome?.is_none() {
+            written += 1;
+        }
+    }
+    println!(
+        "Imported {} encounter(s) into {} bundle(s)",
+        encounters.len(),
+        written
+    );
+    Ok(())
+}
+
+/// Transform, check and write one imported record; why it was refused, if
+/// it was.
+fn import_record(
+    i: usize,
+    kenyan: &KenyanPatient,
+    args: &OpenMrsArgs,
+    options: &TransformOptions,
+    archive: Option<&BundleArchive>,
+) -> Result<Option<String>> {
+    if let Err(e) = validate_kenyan_patient(kenyan) {
+        return Ok(Some(format!("failed validation: {:#}", e)));
+    }
+    for note in &kenyan.data_quality {
+        eprintln!("[QUALITY] record {}: {}", i + 1, note);
+    }
+    let bundle = transform(kenyan, options)?;
+    let errors = lint_bundle(&bundle)
+        .into_iter()
+        .filter(|i| i.severity == LintSeverity::Error)
+        .count();
+    if errors > 0 {
+        return Ok(Some(format!("failed bundle lint ({} error(s))", errors)));
+    }
+
+    let bundle_id = bundle.id.clone().context("Bundle.id not set")?;
+    let out_path = args.output.join(format!("{}.json", bundle_id));
+    fs::write(&out_path, bundle_to_json(&bundle, JsonLayout::Compact)?)
+        .with_context(|| format!("Failed to write {:?}", out_path))?;
+    if let Some(dir) = &args.records {
+        let record_path = dir.join(format!("{}.json", bundle_id));
+        fs::write(&record_path, to_string_pretty(kenyan)?)
+            .with_context(|| format!("Failed to write {:?}", record_path))?;
+    }
+    if let Some(archive) = archive {
+        archive.store(&bundle)?;
+    }
+    Ok(None)
+}
+
+/// The run's progress manifest, if it keeps one, saying how much an
+/// earlier run got through.
+fn open_manifest(path: Option<&Path>, resume: bool) -> Result<Option<BatchManifest>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let manifest = BatchManifest::open(path, resume)?;
+    if manifest.done_count() > 0 {
+        eprintln!(
+            "[BATCH] resuming: {} already done according to {:?}",
+            manifest.done_count(),
+            path
+        );
+    }
+    Ok(Some(manifest))
+}
+
+fn run_report(command: ReportCommand) -> Result<()> {
+    match command {
+        ReportCommand::Dhis2(args) => run_report_dhis2(args),
+        ReportCommand::Register(args) => run_report_register(args),
+        ReportCommand::ImmunizationDue(args) => run_report_immunization_due(args),
+    }
+}
+
+fn run_export(command: ExportCommand) -> Result<()> {
+    match command {
+        ExportCommand::Bulk(args) => run_export_bulk(args),
+    }
+}
+
+fn run_export_bulk(args: BulkExportArgs) -> Result<()> {
+    let bundles = match &args.queue {
+        Some(db) => {
+            let mut queue = OfflineQueue::open(db)?;
+            if let Some(keys) = load_queue_keys(args.keys.as_deref())? {
+                queue = queue.with_keys(keys);
+            }
+            queue
+                .find_by_status(BundleStatus::Sent)?
+                .into_iter()
+                .map(|row| {
+                    serde_json::from_str::<Bundle>(&row.bundle_json)
+                        .with_context(|| format!("Queue row {} is not a Bundle", row.row_id))
+                })
+                .collect::<Result<Vec<_>>>()?
         }
-        InputFormat::Xml => {
-            let xml_patient: XmlPatient =
-                serde_xml_rs::from_str(&input_str).context("Invalid Kenyan XML payload")?;
-            xml_to_kenyan(xml_patient)?
+        None => load_bundles(&args.bundles)?,
+    };
+    for (path, count) in bulk_export::write_ndjson(&bundles, &args.output)? {
+        eprintln!("[EXPORT] {} resources → {:?}", count, path);
+    }
+    Ok(())
+}
+
+fn run_report_immunization_due(args: ImmunizationDueArgs) -> Result<()> {
+    let records = args
+        .input
+        .iter()
+        .map(|p| load_record(p, args.format.as_ref()).with_context(|| format!("In {:?}", p)))
+        .collect::<Result<Vec<_>>>()?;
+    let as_of = args
+        .as_of
+        .unwrap_or_else(|| chrono::Local::now().date_naive());
+
+    let csv = immunization::to_csv(&immunization::due_list(&records, as_of));
+    if let Some(output_path) = &args.output {
+        fs::write(output_path, csv)
+            .with_context(|| format!("Failed to write {:?}", output_path))?;
+    } else {
+        print!("{csv}");
+    }
+    Ok(())
+}
+
+fn run_report_register(args: RegisterArgs) -> Result<()> {
+    let records = match &args.queue {
+        Some(db) => {
+            let mut queue = OfflineQueue::open(db)?;
+            if let Some(keys) = load_queue_keys(args.keys.as_deref())? {
+                queue = queue.with_keys(keys);
+            }
+            let mut records = Vec::new();
+            for pending in queue.pending_within_window()? {
+                let record = serde_json::from_str::<Bundle>(&pending.bundle_json)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|b| Ok(bundle_to_kenyan(&b)?));
+                match record {
+                    Ok(record) => records.push(record),
+                    Err(e) => eprintln!(
+                        "[REGISTER] queue row {} left out, not a visit bundle: {:#}",
+                        pending.row_id, e
+                    ),
+                }
+            }
+            records
         }
+        None => args
+            .input
+            .iter()
+            .map(|p| load_record(p, args.format.as_ref()).with_context(|| format!("In {:?}", p)))
+            .collect::<Result<Vec<_>>>()?,
+    };
+    let mapping = match &args.mapping {
+        Some(path) => Dhis2Mapping::from_json_file(path)?,
+        None => Dhis2Mapping::default(),
+    };
+
+    let entries = register::line_list(&records, &mapping, args.from, args.to)?;
+    let csv = register::to_csv(&entries);
+    if let Some(output_path) = &args.output {
+        fs::write(output_path, csv)
+            .with_context(|| format!("Failed to write {:?}", output_path))?;
+    } else {
+        print!("{csv}");
+    }
+    Ok(())
+}
+
+fn run_report_dhis2(args: Dhis2Args) -> Result<()> {
+    let records = args
+        .input
+        .iter()
+        .map(|p| load_record(p, args.format.as_ref()).with_context(|| format!("In {:?}", p)))
+        .collect::<Result<Vec<_>>>()?;
+    let mapping = match &args.mapping {
+        Some(path) => Dhis2Mapping::from_json_file(path)?,
+        None => Dhis2Mapping::default(),
+    };
+
+    let tally = dhis2::aggregate(&records, &mapping, &args.period)?;
+    if tally.unknown_gender > 0 {
+        eprintln!(
+            "[DHIS2] {} visit(s) without a recorded gender left out of the report",
+            tally.unknown_gender
+        );
+    }
+    if tally.unmapped > 0 {
+        eprintln!(
+            "[DHIS2] {} visit(s) matched no MOH 705 row and were left out",
+            tally.unmapped
+        );
+    }
+    let json = to_string_pretty(&dhis2::to_data_value_set(
+        &tally.counts,
+        &mapping,
+        &args.period,
+    )?)?;
+
+    if let Some(output_path) = &args.output {
+        fs::write(output_path, json)
+            .with_context(|| format!("Failed to write {:?}", output_path))?;
+    } else {
+        println!("{json}");
+    }
+    Ok(())
+}
+
+fn run_measures(args: MeasuresArgs) -> Result<()> {
+    let bundles = load_bundles(&args.bundles)?;
+    let indicators = match &args.indicators {
+        Some(path) => IndicatorSet::from_json_file(path)?,
+        None => IndicatorSet::default(),
     };
+    let (start, end) = (args.from.to_string(), args.to.to_string());
+    let results = measures::evaluate(&bundles, &indicators, &start, &end);
 
-    validate_kenyan_patient(&kenyan).context("Patient record failed validation")?;
+    let entries = results
+        .iter()
+        .map(|r| {
+            let report = measures::to_measure_report(r, &start, &end);
+            Ok(BundleEntry {
+                full_url: report.id.as_ref().map(|id| format!("urn:uuid:{}", id)),
+                resource: Some(serde_json::to_value(&report)?),
+                request: None,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let collection = Bundle {
+        resource_type: "Bundle".to_string(),
+        id: Some(uuid::Uuid::new_v4().to_string()),
+        meta: None,
+        timestamp: Some(chrono::Utc::now().to_rfc3339()),
+        bundle_type: Some("collection".to_string()),
+        entry: Some(entries),
+        signature: None,
+        extra: Default::default(),
+    };
+    let json = to_string_pretty(&collection)?;
+
+    if let Some(csv_path) = &args.csv {
+        fs::write(csv_path, measures::to_csv(&results, &start, &end))
+            .with_context(|| format!("Failed to write {:?}", csv_path))?;
+    }
+    if let Some(output_path) = &args.output {
+        fs::write(output_path, json)
+            .with_context(|| format!("Failed to write {:?}", output_path))?;
+    } else {
+        println!("{json}");
+    }
+    Ok(())
+}
 
-    let patient = map_patient(&kenyan);
-    let patient_id = patient.id.as_ref().context("Patient.id not set")?.clone();
+impl UploadArgs {
+    /// The default transport, plus the upload options for rows queued
+    /// with their own endpoint.
+    fn resolve(&self) -> Result<(Box<dyn BundleTransport>, UploadOptions)> {
+        let options = UploadOptions {
+            compress: !self.no_compress,
+            chunk_size: self.chunk_size,
+            max_retries: self.retries,
+            ..UploadOptions::default()
+        };
+        let transport: Box<dyn BundleTransport> = match (&self.transport, &self.openhim) {
+            (Some(path), _) => TransportConfig::from_json_file(path)?.build(options.clone())?,
+            (None, Some(path)) => Box::new(OpenHimTransport::new(
+                &OpenHimConfig::from_json_file(path)?,
+                &self.channel,
+                options.clone(),
+            )?),
+            (None, None) => Box::new(HttpsTransport::new(
+                self.endpoint
+                    .as_deref()
+                    .context("--endpoint, --openhim or --transport is required")?,
+                options.clone(),
+            )),
+        };
+        Ok((transport, options))
+    }
+}
 
-    let organization = map_organization(&kenyan);
+fn run_send(args: SendArgs) -> Result<()> {
+    let json = fs::read(&args.file).with_context(|| format!("Failed to read {:?}", args.file))?;
+    let (transport, _) = args.upload.resolve()?;
+    if args.upload.dry_run {
+        println!(
+            "[DRY-RUN] would submit {} bytes to {}",
+            json.len(),
+            transport.describe()
+        );
+        return Ok(());
+    }
+    // File-based transports name the file after the Bundle.id
+    let bundle_id = serde_json::from_slice::<serde_json::Value>(&json)
+        .ok()
+        .and_then(|b| b.get("id").and_then(|id| id.as_str()).map(str::to_string))
+        .or_else(|| {
+            args.file
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+        })
+        .context("Bundle has no id")?;
+    match transport.send(&bundle_id, &json)? {
+        DeliveryResult::Delivered { receipt } => {
+            println!("Submitted ({} bytes, {})", json.len(), receipt);
+            Ok(())
+        }
+        DeliveryResult::Rejected { reason } => {
+            Err(Failure::new(FailureKind::Network, reason).into())
+        }
+    }
+}
 
-    // Build practitioner from PUID if present
-    let practitioner = kenyan.visit.attending_puid.as_deref().map(map_practitioner);
-    let practitioner_id = practitioner.as_ref().and_then(|p| p.id.as_deref());
+fn run_serve(args: ServeArgs) -> Result<()> {
+    let (default_transport, options) = args.upload.resolve()?;
+    let mut queue = open_queue(&args.queue)?;
+    if let Some(keys) = load_queue_keys(args.keys.as_deref())? {
+        queue = queue.with_keys(keys);
+    }
+    let alerts = args
+        .alerts
+        .as_deref()
+        .map(AlertConfig::from_json_file)
+        .transpose()?;
+    if args.upload.dry_run {
+        for row in queue.pending_within_window()? {
+            println!(
+                "[DRY-RUN] would submit bundle {} ({} bytes) to {}",
+                row.bundle_id,
+                row.bundle_json.len(),
+                row.destination_endpoint
+                    .clone()
+                    .unwrap_or_else(|| default_transport.describe())
+            );
+        }
+        return Ok(());
+    }
+    loop {
+        let expired = queue.expire_old_bundles()?;
+        let summary = queue.flush(|row| {
+            let routed;
+            let transport = match &row.destination_endpoint {
+                Some(endpoint) => {
+                    routed = HttpsTransport::new(endpoint, options.clone());
+                    &routed as &dyn BundleTransport
+                }
+                None => default_transport.as_ref(),
+            };
+            match transport.send(&row.bundle_id, row.bundle_json.as_bytes())? {
+                DeliveryResult::Delivered { .. } => Ok(()),
+                DeliveryResult::Rejected { reason } => Err(BridgeError::Network(reason)),
+            }
+        })?;
+        eprintln!(
+            "[SERVE] sent {}, failed {}, expired {}",
+            summary.sent, summary.failed, expired
+        );
+        if let Some(at) = args.maintain_at {
+            if queue.maintenance_due(at, chrono::Local::now())? {
+                eprintln!("[SERVE] maintenance: {}", queue.maintain()?);
+            }
+        }
+        if let Some(config) = &alerts {
+            let raised = queue_alerts::notify(&queue, config)?;
+            if raised > 0 {
+                eprintln!("[SERVE] raised {} alert(s)", raised);
+            }
+        }
+        if args.once {
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_secs(args.interval));
+    }
+}
 
-    let encounter = map_encounter(&kenyan, &patient_id, practitioner_id);
-    let encounter_id = encounter.id.as_ref().context("Encounter.id not set")?.clone();
+fn run_mllp_listen(args: MllpListenArgs) -> Result<()> {
+    let mut queue = open_queue(&args.queue)?;
+    if let Some(keys) = load_queue_keys(args.keys.as_deref())? {
+        queue = queue.with_keys(keys);
+    }
+    let options = TransformOptions::default();
+    let listener = std::net::TcpListener::bind(&args.bind)
+        .with_context(|| format!("Failed to listen on {}", args.bind))?;
+    eprintln!("[MLLP] listening on {}", listener.local_addr()?);
+    mllp::listen(listener, |raw| {
+        let message = hl7v2::Message::parse(raw);
+        // Control id only: no identifiers in logs
+        let control_id = message
+            .as_ref()
+            .ok()
+            .and_then(|m| m.control_id())
+            .unwrap_or_default()
+            .to_string();
+        let kenyan = match message
+            .and_then(|m| hl7v2::message_to_kenyan(&m))
+            .and_then(|k| validate_kenyan_patient(&k).map(|_| k))
+        {
+            Ok(kenyan) => kenyan,
+            Err(e) => {
+                eprintln!("[MLLP] message {} refused: {:#}", control_id, e);
+                return mllp::ack(raw, AckCode::Error, &format!("{:#}", e));
+            }
+        };
+        let queued = transform(&kenyan, &options).and_then(|bundle| {
+            let bundle_id = bundle.id.clone().unwrap_or_default();
+            let json = bundle_to_json(&bundle, JsonLayout::Compact)?;
+            queue.enqueue(&bundle_id, &json, &kenyan.patient_number, &kenyan.clinic_id)?;
+            Ok(bundle_id)
+        });
+        match queued {
+            Ok(bundle_id) => {
+                eprintln!(
+                    "[MLLP] message {} queued as bundle {}",
+                    control_id, bundle_id
+                );
+                mllp::ack(raw, AckCode::Accept, "")
+            }
+            Err(e) => {
+                eprintln!("[MLLP] message {} not queued: {:#}", control_id, e);
+                mllp::ack(raw, AckCode::Reject, &format!("{:#}", e))
+            }
+        }
+    })?;
+    Ok(())
+}
 
-    let observations = map_vitals(&kenyan.visit.vitals, &patient_id, &kenyan.visit.date);
-    let condition = map_condition(&kenyan, &patient_id, &encounter_id);
-    let medication_request = map_medication_request(&kenyan, &patient_id, &encounter_id);
+#[cfg(feature = "grpc")]
+fn run_grpc_serve(args: GrpcServeArgs) -> Result<()> {
+    let mut queue = open_queue(&args.queue)?;
+    if let Some(keys) = load_queue_keys(args.keys.as_deref())? {
+        queue = queue.with_keys(keys);
+    }
+    let api = grpc::TransformApi::new(TransformOptions::default()).with_queue(queue);
+    eprintln!("[GRPC] listening on {}", args.bind);
+    grpc::serve(args.bind, api)?;
+    Ok(())
+}
 
-    // SHA Coverage + Claim — only present when sha_member_number is set
-    // Pull ICD-11 code from the diagnosis crosswalk (same logic as condition mapper)
-    let icd11_pair = kenya_fhir_bridge::mapper::condition::diagnosis_coding(&kenyan.visit.diagnosis);
-    let sha_claims = map_sha_claims(
-        &kenyan,
-        &patient_id,
-        &encounter_id,
-        organization.id.as_deref().unwrap_or("org-unknown"),
-        icd11_pair.map(|(_, _, c, _)| c),
-        icd11_pair.map(|(_, _, _, d)| d),
+fn run_reconcile(args: ReconcileArgs) -> Result<()> {
+    std::env::var("AFYALINK_TOKEN")
+        .context("reconcile needs AFYALINK_TOKEN for the live Client Registry")?;
+    let mut queue = open_queue(&args.queue)?;
+    if let Some(keys) = load_queue_keys(args.keys.as_deref())? {
+        queue = queue.with_keys(keys);
+    }
+    let archive = args
+        .archive
+        .as_deref()
+        .map(BundleArchive::open)
+        .transpose()?;
+    let breaker = CircuitBreaker::default();
+    let summary = reconcile(&queue, archive.as_ref(), |national_id| {
+        live_cr_lookup(national_id, &breaker)
+    })?;
+    println!(
+        "Replaced synthetic CR IDs: {} queued bundle(s) rewritten, {} archived bundle(s) queued again, {} left synthetic",
+        summary.queued, summary.resubmitted, summary.unresolved
     );
+    Ok(())
+}
+
+fn run_validate(args: ValidateArgs, summary: &mut RunSummary) -> Result<()> {
+    let files = collect_files(&args.input, &["json", "xml"])?;
+    let mut invalid = 0;
+    for file in &files {
+        let started = Instant::now();
+        let result = load_record(file, args.format.as_ref()).map(|_| ());
+        match &result {
+            Ok(()) => println!("{}: valid", file.display()),
+            Err(e) => {
+                invalid += 1;
+                eprintln!("{}: {:#}", file.display(), e);
+            }
+        }
+        summary.record(file, &result, started.elapsed());
+    }
+    if invalid > 0 {
+        return Err(Failure::new(
+            FailureKind::Validation,
+            format!("{} of {} record(s) failed validation", invalid, files.len()),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn run_reprocess(args: ReprocessArgs) -> Result<()> {
+    let mut options = TransformOptions::default();
+    if let Some(path) = &args.complaint_codes {
+        options.complaints = ComplaintTerminology::from_json_file(path)?;
+    }
+    if args.icd11_autocode {
+        options.icd11 = Some(Icd11Client::from_env(&args.icd11_cache)?);
+    }
+    if let Some(path) = &args.terminology_config {
+        options.translate = Some(TerminologyService::from_json_file(path)?);
+    }
+    if let Some(dir) = &args.output {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    }
+
+    let mut manifest = open_manifest(args.manifest.as_deref(), args.resume)?;
+    let (mut compared, mut unchanged, mut coding, mut content) = (0, 0, 0, 0);
+    let mut reprocess_file = |path: &Path| -> Result<()> {
+        let kenyan = load_record(path, None).with_context(|| format!("In {:?}", path))?;
+        let bundle = transform(&kenyan, &options)?;
+        let stem = path
+            .file_stem()
+            .context("Input path has no file name")?
+            .to_string_lossy();
+
+        if let Some(dir) = &args.output {
+            let out_path = dir.join(format!("{}.json", stem));
+            fs::write(&out_path, to_string_pretty(&bundle)?)
+                .with_context(|| format!("Failed to write {:?}", out_path))?;
+        }
+
+        if let Some(archived_dir) = args.archived.as_ref().filter(|_| args.compare) {
+            let archived_path = archived_dir.join(format!("{}.json", stem));
+            if !archived_path.exists() {
+                println!("{}: no archived bundle, skipped", stem);
+                return Ok(());
+            }
+            let archived = load_bundle(&archived_path)?;
+            let diff = reprocess::compare_bundles(&archived, &bundle);
+            compared += 1;
+            if diff.is_empty() {
+                unchanged += 1;
+            }
+            coding += diff.count(ChangeKind::Coding);
+            content += diff.count(ChangeKind::Content);
+            print!("{}", reprocess::summarize(&stem, &diff));
+        }
+        Ok(())
+    };
+    for path in collect_files(&args.input, &["json", "xml"])? {
+        let key = path.display().to_string();
+        if manifest.as_ref().is_some_and(|m| m.is_done(&key)) {
+            continue;
+        }
+        let result = reprocess_file(&path);
+        if let Some(manifest) = &mut manifest {
+            match &result {
+                Ok(()) => manifest.record(&key, EntryStatus::Ok, None)?,
+                Err(e) => manifest.record(&key, EntryStatus::Failed, Some(&format!("{:#}", e)))?,
+            }
+        }
+        result?;
+    }
+
+    if args.compare {
+        println!(
+            "Compared {} record(s): {} unchanged, {} coding change(s), {} content change(s)",
+            compared, unchanged, coding, content
+        );
+    }
+    Ok(())
+}
+
+fn run_archive(db: &Path, command: ArchiveCommand) -> Result<()> {
+    let archive = BundleArchive::open(db)?;
+    match command {
+        ArchiveCommand::Add { bundles } => {
+            let bundles = load_bundles(&bundles)?;
+            for bundle in &bundles {
+                archive.store(bundle)?;
+            }
+            println!("Archived {} bundle(s)", bundles.len());
+        }
+        ArchiveCommand::Search { text, limit } => {
+            let hits = archive.search(&text, limit)?;
+            for hit in &hits {
+                println!(
+                    "{}  {}  Patient/{}  {}  {}",
+                    hit.archived_at,
+                    hit.bundle_id,
+                    mask_identifier(&hit.patient_id),
+                    hit.facility,
+                    hit.snippet
+                );
+            }
+            println!("{} match(es)", hits.len());
+        }
+        ArchiveCommand::History { patient, facility } => {
+            let visits = archive.history(&patient, facility.as_deref())?;
+            for visit in &visits {
+                println!(
+                    "{}  {}  {}  {}  archived {}",
+                    visit.visit_date,
+                    visit.facility,
+                    visit.diagnosis,
+                    visit.bundle_id,
+                    visit.archived_at
+                );
+            }
+            println!("{} visit(s)", visits.len());
+        }
+    }
+    Ok(())
+}
+
+/// Queue keys for reading rows: from `path`, else the OS keyring where there
+/// is one. `None` when neither has keys (an unencrypted queue).
+fn load_queue_keys(path: Option<&Path>) -> Result<Option<QueueKeys>> {
+    match path {
+        Some(path) => Ok(Some(QueueKeys::from_json_file(path)?)),
+        None if cfg!(any(windows, target_os = "macos")) => Ok(QueueKeys::from_keyring()?),
+        None => Ok(None),
+    }
+}
+
+/// Open the queue database with its policy.
+fn open_queue(args: &QueueArgs) -> Result<OfflineQueue> {
+    let policy = match &args.policy {
+        Some(path) => QueuePolicy::from_json_file(path)?,
+        None => QueuePolicy::default(),
+    };
+    Ok(OfflineQueue::open(&args.db)?.with_policy(policy))
+}
+
+fn run_queue(args: &QueueArgs, command: QueueCommand) -> Result<()> {
+    match command {
+        QueueCommand::RotateKey { keys: key_file } => {
+            let existing = match &key_file {
+                Some(path) if path.exists() => Some(QueueKeys::from_json_file(path)?),
+                Some(_) => None,
+                None => QueueKeys::from_keyring()?,
+            };
+            let save = |keys: &QueueKeys| match &key_file {
+                Some(path) => keys.save_json_file(path),
+                None => keys.save_to_keyring(),
+            };
+            let mut keys = match existing {
+                Some(mut keys) => {
+                    keys.rotate();
+                    keys
+                }
+                None => QueueKeys::generate(),
+            };
+            // Persist the new key before any row depends on it
+            save(&keys)?;
+
+            let queue = open_queue(args)?.with_keys(keys.clone());
+            let rewritten = queue.reencrypt()?;
+            let retired = keys.retire_except(&queue.key_ids_in_use()?);
+            save(&keys)?;
+            println!(
+                "Re-encrypted {} queued bundle(s) with key {}; retired {} old key(s)",
+                rewritten,
+                keys.active,
+                retired.len()
+            );
+        }
+        QueueCommand::Expire => {
+            let queue = open_queue(args)?;
+            let expired = queue.expire_old_bundles()?;
+            println!(
+                "Expired {} bundle(s) older than {} days",
+                expired,
+                queue.policy().transmission_window_days
+            );
+        }
+        QueueCommand::Maintain { report: path } => {
+            let report = open_queue(args)?.maintain()?;
+            println!("Queue maintained: {}", report);
+            if let Some(path) = &path {
+                fs::write(path, to_string_pretty(&report)?)
+                    .with_context(|| format!("Failed to write {:?}", path))?;
+            }
+        }
+        QueueCommand::Verify { keys } => {
+            let mut queue = open_queue(args)?;
+            if let Some(keys) = load_queue_keys(keys.as_deref())? {
+                queue = queue.with_keys(keys);
+            }
+            let issues = queue.verify()?;
+            for issue in &issues {
+                eprintln!(
+                    "[QUEUE] row {} (bundle {}): {}",
+                    issue.row_id, issue.bundle_id, issue.problem
+                );
+            }
+            if !issues.is_empty() {
+                anyhow::bail!("Queue verification found {} bad row(s)", issues.len());
+            }
+            println!(
+                "Queue verified: {} pending bundle(s) intact",
+                queue.stats()?.pending
+            );
+        }
+        QueueCommand::List {
+            patient,
+            clinic,
+            status,
+            from,
+            to,
+            keys,
+        } => {
+            let mut queue = open_queue(args)?;
+            if let Some(keys) = load_queue_keys(keys.as_deref())? {
+                queue = queue.with_keys(keys);
+            }
+            let rows = queue.find(&QueueFilter {
+                patient_id: patient,
+                clinic_id: clinic,
+                status: status.map(|status| match status {
+                    QueueStatus::Pending => BundleStatus::Pending,
+                    QueueStatus::Sent => BundleStatus::Sent,
+                    QueueStatus::Failed => BundleStatus::Failed,
+                }),
+                from,
+                to,
+            })?;
+            for row in &rows {
+                println!(
+                    "{}  {}  {}  {}  {}  {}  retries {}{}",
+                    row.row_id,
+                    row.status.as_str(),
+                    row.created_at,
+                    row.bundle_id,
+                    row.clinic_id,
+                    mask_identifier(&row.patient_id),
+                    row.retry_count,
+                    row.last_error
+                        .as_deref()
+                        .map(|e| format!("  last error: {}", e))
+                        .unwrap_or_default()
+                );
+            }
+            println!("{} bundle(s)", rows.len());
+        }
+    }
+    Ok(())
+}
+
+fn run_bundle_lint(file: &Path) -> Result<()> {
+    let bundle = load_bundle(file)?;
 
-    let bundle = create_transaction_bundle(
-        &patient,
-        &organization,
-        &encounter,
-        &observations,
-        &condition,
-        &medication_request,
-        practitioner.as_ref(),
-        sha_claims.as_ref(),
+    let issues = lint_bundle(&bundle);
+    for issue in &issues {
+        eprintln!("[LINT] {}", issue);
+    }
+    let errors = issues
+        .iter()
+        .filter(|i| i.severity == LintSeverity::Error)
+        .count();
+    if errors > 0 {
+        return Err(Failure::new(
+            FailureKind::Validation,
+            format!("Bundle lint found {} error(s)", errors),
+        )
+        .into());
+    }
+    println!(
+        "Bundle lint passed ({} entries)",
+        bundle.entry.map_or(0, |e| e.len())
     );
-    let json = to_string_pretty(&bundle)?;
+    Ok(())
+}
 
-    if let Some(output_path) = cli.output {
-        fs::write(&output_path, json)
+fn run_bundle_to_kenyan(
+    file: &Path,
+    output: Option<&Path>,
+    system_uris: Option<&Path>,
+) -> Result<()> {
+    let mut bundle = load_bundle(file)?;
+    if let Some(path) = system_uris {
+        SystemUris::from_json_file(path)?.revert(&mut bundle);
+    }
+    let kenyan = bundle_to_kenyan(&bundle)
+        .with_context(|| format!("Cannot map {:?} back to a Kenyan record", file))?;
+    let json = to_string_pretty(&kenyan)?;
+
+    if let Some(output_path) = output {
+        fs::write(output_path, json)
             .with_context(|| format!("Failed to write {:?}", output_path))?;
     } else {
         println!("{json}");
     }
+    Ok(())
+}
 
+fn run_bundle_verify(file: &Path, public_key: &Path) -> Result<()> {
+    let bundle = load_bundle(file)?;
+    let key = load_verifying_key(public_key)?;
+    let kid = verify_bundle(&bundle, &key)
+        .with_context(|| format!("Signature check failed for {:?}", file))?;
+    println!("Bundle signature valid (kid {})", kid);
     Ok(())
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-    run(cli)
+fn main() -> ExitCode {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    set_reveal_identifiers(cli.show_identifiers);
+
+    let mut summary = RunSummary::start(matches.subcommand_name().unwrap_or("transform"));
+    let result = dispatch(cli.command, cli.transform, &mut summary);
+    let mut code = summary.finish(&result);
+    if let Err(e) = &result {
+        eprintln!("Error: {:?}", e);
+    }
+    if let Some(path) = &cli.summary_json {
+        if let Err(e) = summary.write_json(path) {
+            eprintln!("Error: {:?}", e);
+            if code == 0 {
+                code = FailureKind::Io.exit_code();
+            }
+        }
+    }
+    ExitCode::from(code)
+}
+
+fn dispatch(
+    command: Option<Command>,
+    default: TransformArgs,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    match command {
+        Some(Command::Transform(args)) => run_transform(*args, summary),
+        Some(Command::Validate(args)) => run_validate(args, summary),
+        Some(Command::Surveillance(args)) => run_surveillance(args),
+        Some(Command::Measures(args)) => run_measures(args),
+        Some(Command::Send(args)) => run_send(args),
+        Some(Command::Serve(args)) => run_serve(args),
+        Some(Command::Reprocess(args)) => run_reprocess(args),
+        Some(Command::Reconcile(args)) => run_reconcile(args),
+        Some(Command::MllpListen(args)) => run_mllp_listen(args),
+        #[cfg(feature = "grpc")]
+        Some(Command::GrpcServe(args)) => run_grpc_serve(args),
+        Some(Command::Bundle {
+            command: BundleCommand::Lint { file },
+        }) => run_bundle_lint(&file),
+        Some(Command::Bundle {
+            command:
+                BundleCommand::ToKenyan {
+                    file,
+                    output,
+                    system_uris,
+                },
+        }) => run_bundle_to_kenyan(&file, output.as_deref(), system_uris.as_deref()),
+        Some(Command::Bundle {
+            command: BundleCommand::Verify { file, public_key },
+        }) => run_bundle_verify(&file, &public_key),
+        Some(Command::Archive { db, command }) => run_archive(&db, command),
+        Some(Command::Import { command }) => run_import(command),
+        Some(Command::Generate(args)) => run_generate(args),
+        Some(Command::Report { command }) => run_report(command),
+        Some(Command::Export { command }) => run_export(command),
+        Some(Command::Queue { queue, command }) => run_queue(&queue, command),
+        None => run_transform(default, summary),
+    }
 }
@@ -0,0 +1,183 @@
+use fhir_parser::fhir::condition::{Condition, ConditionStage};
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Observation, Quantity, Reference};
+
+use crate::kenyan::schema::{AntenatalFindings, DiagnosisCategory};
+use crate::mapper::condition::condition_category;
+use crate::mapper::observation::exam_category;
+
+/// SNOMED CT "Pregnancy"; identifies the pregnancy Condition on the way back.
+pub const PREGNANCY_SNOMED: &str = "77386006";
+
+/// ANC observations plus the pregnancy Condition they belong to.
+#[derive(Debug, Clone)]
+pub struct AntenatalResources {
+    pub pregnancy: Condition,
+    pub observations: Vec<Observation>,
+}
+
+fn coding(system: &str, code: &str, display: &str) -> Coding {
+    Coding {
+        system: Some(system.to_string()),
+        code: Some(code.to_string()),
+        display: Some(display.to_string()),
+    }
+}
+
+/// Maps ANC findings → FHIR R4 Observations and a pregnancy Condition.
+///
+/// - Gestational age: LOINC 49051-6 (wk)
+/// - Gravida: LOINC 11996-6, para: LOINC 11977-6
+/// - Fundal height: LOINC 11881-0 (cm, optional)
+/// - Fetal heart rate: LOINC 55283-6 (/min, optional)
+///
+/// The Condition (`preg-{key}`) is coded SNOMED CT 77386006 + ICD-11 QA41 +
+/// ICD-10 Z34.9, like the diagnosis Condition's dual coding, and carries
+/// the trimester as its stage. It is this visit's encounter diagnosis.
+pub fn map_antenatal(
+    anc: &AntenatalFindings,
+    patient_id: &str,
+    visit_key: &str,
+    encounter_id: &str,
+    visit_date: &str,
+) -> AntenatalResources {
+    let subject = Reference {
+        reference: Some(format!("Patient/{}", patient_id)),
+        display: None,
+    };
+    let observation = |id: &str, code: &str, display: &str, value: f64, unit: &str| Observation {
+        resource_type: "Observation".to_string(),
+        id: Some(format!("{}-{}", id, visit_key)),
+        meta: None,
+        extension: None,
+        status: "final".to_string(),
+        category: Some(exam_category()),
+        code: CodeableConcept {
+            coding: Some(vec![coding("http://loinc.org", code, display)]),
+            text: Some(display.to_string()),
+        },
+        subject: Some(subject.clone()),
+        encounter: Some(Reference {
+            reference: Some(format!("Encounter/{}", encounter_id)),
+            display: None,
+        }),
+        effective_date_time: Some(visit_date.to_string()),
+        performer: None,
+        value_quantity: Some(Quantity {
+            value,
+            unit: Some(unit.to_string()),
+            system: Some("http://unitsofmeasure.org".to_string()),
+        }),
+        value_codeable_concept: None,
+        value_string: None,
+        interpretation: None,
+        component: None,
+        device: None,
+        derived_from: None,
+        extra: Default::default(),
+    };
+
+    let mut observations = vec![
+        observation(
+            "ga",
+            "49051-6",
+            "Gestational age in weeks",
+            anc.gestational_age_weeks as f64,
+            "wk",
+        ),
+        observation(
+            "gravida",
+            "11996-6",
+            "[#] Pregnancies",
+            anc.gravida as f64,
+            "{#}",
+        ),
+        observation(
+            "para",
+            "11977-6",
+            "[#] Parturitions",
+            anc.para as f64,
+            "{#}",
+        ),
+    ];
+    if let Some(height) = anc.fundal_height_cm {
+        observations.push(observation(
+            "fundal",
+            "11881-0",
+            "Uterus Fundal height Tape measure",
+            height,
+            "cm",
+        ));
+    }
+    if let Some(rate) = anc.fetal_heart_rate {
+        observations.push(observation(
+            "fhr",
+            "55283-6",
+            "Fetal Heart rate",
+            rate as f64,
+            "/min",
+        ));
+    }
+
+    let trimester = match anc.gestational_age_weeks {
+        0..=13 => "First trimester",
+        14..=27 => "Second trimester",
+        _ => "Third trimester",
+    };
+    let pregnancy = Condition {
+        resource_type: "Condition".to_string(),
+        id: Some(format!("preg-{}", visit_key)),
+        meta: None,
+        extension: None,
+        clinical_status: Some(CodeableConcept {
+            coding: Some(vec![coding(
+                "http://terminology.hl7.org/CodeSystem/condition-clinical",
+                "active",
+                "Active",
+            )]),
+            text: None,
+        }),
+        verification_status: Some(CodeableConcept {
+            coding: Some(vec![coding(
+                "http://terminology.hl7.org/CodeSystem/condition-ver-status",
+                "confirmed",
+                "Confirmed",
+            )]),
+            text: None,
+        }),
+        category: Some(condition_category(DiagnosisCategory::EncounterDiagnosis)),
+        code: Some(CodeableConcept {
+            coding: Some(vec![
+                coding("http://snomed.info/sct", PREGNANCY_SNOMED, "Pregnancy"),
+                coding("http://id.who.int/icd11/mms", "QA41", "Pregnant state"),
+                coding(
+                    "http://hl7.org/fhir/sid/icd-10",
+                    "Z34.9",
+                    "Supervision of normal pregnancy, unspecified",
+                ),
+            ]),
+            text: Some("Pregnancy".to_string()),
+        }),
+        subject: Some(subject),
+        encounter: Some(Reference {
+            reference: Some(format!("Encounter/{}", encounter_id)),
+            display: None,
+        }),
+        severity: None,
+        onset_date_time: None,
+        recorded_date: Some(visit_date.to_string()),
+        stage: Some(vec![ConditionStage {
+            summary: Some(CodeableConcept {
+                coding: None,
+                text: Some(trimester.to_string()),
+            }),
+            type_field: None,
+        }]),
+        note: None,
+        extra: Default::default(),
+    };
+
+    AntenatalResources {
+        pregnancy,
+        observations,
+    }
+}
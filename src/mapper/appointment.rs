@@ -0,0 +1,64 @@
+use chrono::NaiveDate;
+use fhir_parser::fhir::appointment::{Appointment, AppointmentParticipant};
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Reference};
+
+/// The record books a day, not a slot: a booked Appointment must have start
+/// and end, so they span the clinic day (East Africa Time).
+const CLINIC_OPENS: &str = "08:00:00+03:00";
+const CLINIC_CLOSES: &str = "17:00:00+03:00";
+
+fn participant(reference: String, required: &str) -> AppointmentParticipant {
+    AppointmentParticipant {
+        actor: Some(Reference {
+            reference: Some(reference),
+            display: None,
+        }),
+        required: Some(required.to_string()),
+        status: "accepted".to_string(),
+    }
+}
+
+/// Maps the next appointment date → FHIR R4 Appointment (`appt-{key}`),
+/// booked for the visit's Condition with the patient, the attending
+/// practitioner and the department when the visit names them.
+pub fn map_appointment(
+    date: NaiveDate,
+    visit_date: &str,
+    patient_id: &str,
+    visit_key: &str,
+    condition_id: &str,
+    practitioner_id: Option<&str>,
+    location_id: Option<&str>,
+) -> Appointment {
+    let mut participants = vec![participant(format!("Patient/{}", patient_id), "required")];
+    if let Some(id) = practitioner_id {
+        participants.push(participant(format!("Practitioner/{}", id), "required"));
+    }
+    if let Some(id) = location_id {
+        participants.push(participant(format!("Location/{}", id), "required"));
+    }
+    Appointment {
+        resource_type: "Appointment".to_string(),
+        id: Some(format!("appt-{}", visit_key)),
+        meta: None,
+        status: "booked".to_string(),
+        appointment_type: Some(CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some("http://terminology.hl7.org/CodeSystem/v2-0276".to_string()),
+                code: Some("FOLLOWUP".to_string()),
+                display: Some("A follow up visit from a previous appointment".to_string()),
+            }]),
+            text: None,
+        }),
+        reason_reference: Some(vec![Reference {
+            reference: Some(format!("Condition/{}", condition_id)),
+            display: None,
+        }]),
+        description: Some("Follow-up visit".to_string()),
+        start: Some(format!("{}T{}", date, CLINIC_OPENS)),
+        end: Some(format!("{}T{}", date, CLINIC_CLOSES)),
+        created: Some(visit_date.to_string()),
+        participant: participants,
+        extra: Default::default(),
+    }
+}
@@ -0,0 +1,37 @@
+use fhir_parser::fhir::clinical_impression::ClinicalImpression;
+use fhir_parser::fhir::observation::Reference;
+
+fn reference(resource_type: &str, id: &str) -> Reference {
+    Reference {
+        reference: Some(format!("{}/{}", resource_type, id)),
+        display: None,
+    }
+}
+
+/// Maps the visit's clinical note → FHIR R4 ClinicalImpression
+/// (`ci-{key}`), a completed assessment of the visit's Condition by the
+/// attending clinician, with the note as its summary.
+pub fn map_clinical_impression(
+    note: &str,
+    patient_id: &str,
+    visit_key: &str,
+    encounter_id: &str,
+    condition_id: &str,
+    visit_date: &str,
+    assessor_id: Option<&str>,
+) -> ClinicalImpression {
+    ClinicalImpression {
+        resource_type: "ClinicalImpression".to_string(),
+        id: Some(format!("ci-{}", visit_key)),
+        meta: None,
+        status: "completed".to_string(),
+        subject: reference("Patient", patient_id),
+        encounter: Some(reference("Encounter", encounter_id)),
+        effective_date_time: Some(visit_date.to_string()),
+        date: Some(visit_date.to_string()),
+        assessor: assessor_id.map(|id| reference("Practitioner", id)),
+        problem: Some(vec![reference("Condition", condition_id)]),
+        summary: Some(note.to_string()),
+        extra: Default::default(),
+    }
+}
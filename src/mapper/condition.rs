@@ -1,82 +1,225 @@
 use fhir_parser::fhir::condition::{Annotation, Condition};
 use fhir_parser::fhir::observation::{CodeableConcept, Coding, Reference};
 
-use crate::kenyan::schema::KenyanPatient;
+use crate::kenyan::schema::{DiagnosisCategory, Visit};
+use crate::terminology::Icd11Match;
 
-/// Returns `(icd10_code, icd10_display, icd11_code, icd11_display)` for a
-/// known diagnosis string, or `None` for free-text/unknown.
+/// The visit's diagnosis, `cond-{visit_key}`.
+pub fn condition_id(visit_key: &str) -> String {
+    format!("cond-{}", visit_key)
+}
+
+/// Condition.category for a problem-list item or an encounter diagnosis.
+pub fn condition_category(category: DiagnosisCategory) -> Vec<CodeableConcept> {
+    vec![CodeableConcept {
+        coding: Some(vec![Coding {
+            system: Some("http://terminology.hl7.org/CodeSystem/condition-category".to_string()),
+            code: Some(category.code().to_string()),
+            display: Some(category.display().to_string()),
+        }]),
+        text: None,
+    }]
+}
+
+/// One row of the diagnosis crosswalk.
+#[derive(Debug, Clone, Copy)]
+pub struct DiagnosisCoding {
+    pub icd10_code: &'static str,
+    pub icd10_display: &'static str,
+    pub icd11_code: &'static str,
+    pub icd11_display: &'static str,
+    /// SNOMED CT `(concept id, preferred term)`, where a mapping is known
+    pub snomed: Option<(&'static str, &'static str)>,
+}
+
+/// Crosswalk row for a known diagnosis string, or `None` for free-text/unknown.
 ///
 /// ICD-11 MMS codes sourced from WHO ICD-11 2024-01 release.
 /// ICD-10 codes retained for backward-compat with systems not yet on ICD-11.
+/// SNOMED CT concepts (International Edition) for KenyaEMR-based SHR consumers.
 /// Exposed pub(crate) so the SHA mapper can reuse the crosswalk.
-pub fn diagnosis_coding(
-    diagnosis: &str,
-) -> Option<(&'static str, &'static str, &'static str, &'static str)> {
+pub fn diagnosis_coding(diagnosis: &str) -> Option<DiagnosisCoding> {
     let lower = diagnosis.to_lowercase();
 
-    // (ICD-10 code, ICD-10 display, ICD-11 MMS code, ICD-11 display)
-    if lower.contains("upper respiratory tract infection") || lower.contains("urti") {
-        Some(("J06.9", "Acute upper respiratory infection, unspecified", "CA0Z", "Acute upper respiratory infections, unspecified"))
+    // (ICD-10 code, ICD-10 display, ICD-11 MMS code, ICD-11 display, SNOMED CT)
+    let row = if lower.contains("upper respiratory tract infection") || lower.contains("urti") {
+        Some((
+            "J06.9",
+            "Acute upper respiratory infection, unspecified",
+            "CA0Z",
+            "Acute upper respiratory infections, unspecified",
+            Some(("54150009", "Upper respiratory infection")),
+        ))
     } else if lower.contains("malaria") {
-        Some(("B54", "Unspecified malaria", "1F4Z", "Malaria, unspecified"))
+        Some((
+            "B54",
+            "Unspecified malaria",
+            "1F4Z",
+            "Malaria, unspecified",
+            Some(("61462000", "Malaria")),
+        ))
     } else if lower.contains("hypertension") {
-        Some(("I10", "Essential (primary) hypertension", "BA00", "Essential hypertension"))
+        Some((
+            "I10",
+            "Essential (primary) hypertension",
+            "BA00",
+            "Essential hypertension",
+            Some(("59621000", "Essential hypertension")),
+        ))
     } else if lower.contains("diabetes") {
-        Some(("E11.9", "Type 2 diabetes mellitus without complications", "5A11", "Type 2 diabetes mellitus"))
+        Some((
+            "E11.9",
+            "Type 2 diabetes mellitus without complications",
+            "5A11",
+            "Type 2 diabetes mellitus",
+            Some(("44054006", "Diabetes mellitus type 2")),
+        ))
     } else if lower.contains("tuberculosis") || (lower.contains("tb") && !lower.contains("otb")) {
-        Some(("A15.9", "Respiratory tuberculosis, unspecified", "1B12", "Pulmonary tuberculosis"))
+        Some((
+            "A15.9",
+            "Respiratory tuberculosis, unspecified",
+            "1B12",
+            "Pulmonary tuberculosis",
+            Some(("154283005", "Pulmonary tuberculosis")),
+        ))
     } else if lower.contains("pneumonia") {
-        Some(("J18.9", "Pneumonia, unspecified organism", "CA40.Z", "Pneumonia, unspecified"))
+        Some((
+            "J18.9",
+            "Pneumonia, unspecified organism",
+            "CA40.Z",
+            "Pneumonia, unspecified",
+            Some(("233604007", "Pneumonia")),
+        ))
     } else if lower.contains("diarrhoea") || lower.contains("diarrhea") {
-        Some(("A09", "Other and unspecified gastroenteritis and colitis", "1A40", "Gastroenteritis or colitis of infectious origin"))
+        Some((
+            "A09",
+            "Other and unspecified gastroenteritis and colitis",
+            "1A40",
+            "Gastroenteritis or colitis of infectious origin",
+            Some(("25374005", "Gastroenteritis")),
+        ))
     } else if lower.contains("anaemia") || lower.contains("anemia") {
-        Some(("D64.9", "Anaemia, unspecified", "3A00.Z", "Anaemia, unspecified"))
+        Some((
+            "D64.9",
+            "Anaemia, unspecified",
+            "3A00.Z",
+            "Anaemia, unspecified",
+            Some(("271737000", "Anemia")),
+        ))
     } else if lower.contains("urinary tract infection") || lower.contains("uti") {
-        Some(("N39.0", "Urinary tract infection, site not specified", "GC08", "Urinary tract infection"))
+        Some((
+            "N39.0",
+            "Urinary tract infection, site not specified",
+            "GC08",
+            "Urinary tract infection",
+            Some(("68566005", "Urinary tract infectious disease")),
+        ))
     } else if lower.contains("typhoid") {
-        Some(("A01.0", "Typhoid fever", "1A07", "Typhoid fever"))
+        Some((
+            "A01.0",
+            "Typhoid fever",
+            "1A07",
+            "Typhoid fever",
+            Some(("4834000", "Typhoid fever")),
+        ))
     } else if lower.contains("hiv") || lower.contains("aids") {
-        Some(("B24", "Unspecified human immunodeficiency virus disease", "1C62.Z", "HIV disease, unspecified"))
+        Some((
+            "B24",
+            "Unspecified human immunodeficiency virus disease",
+            "1C62.Z",
+            "HIV disease, unspecified",
+            Some(("86406008", "Human immunodeficiency virus infection")),
+        ))
     } else if lower.contains("cholera") {
-        Some(("A00.9", "Cholera, unspecified", "1A00.Z", "Cholera, unspecified"))
+        Some((
+            "A00.9",
+            "Cholera, unspecified",
+            "1A00.Z",
+            "Cholera, unspecified",
+            Some(("63650001", "Cholera")),
+        ))
     } else {
         None
-    }
+    };
+
+    row.map(
+        |(icd10_code, icd10_display, icd11_code, icd11_display, snomed)| DiagnosisCoding {
+            icd10_code,
+            icd10_display,
+            icd11_code,
+            icd11_display,
+            snomed,
+        },
+    )
 }
 
 /// Maps visit.diagnosis → FHIR R4 Condition.
 ///
 /// Emits **dual coding** — both ICD-10 (for backward compat) and ICD-11 MMS
 /// (required by Kenya DHA Digital Health Regulations 2025) — per the HL7
-/// guidance of including multiple codings in a single CodeableConcept.
+/// guidance of including multiple codings in a single CodeableConcept, plus
+/// a SNOMED CT coding when the crosswalk has one: KenyaEMR-based SHR
+/// consumers key off SNOMED while claims key off ICD-11.
+/// Diagnoses the crosswalk does not know may still carry an ICD-11 code
+/// auto-coded via the WHO API (`autocoded`), emitted on its own.
 /// verificationStatus = confirmed when coded, provisional otherwise.
-pub fn map_condition(kenyan: &KenyanPatient, patient_id: &str, encounter_id: &str) -> Condition {
+/// category is the visit's `diagnosis_category`: an encounter diagnosis
+/// unless the record marks it a chronic problem-list item. The visit date
+/// is recordedDate; onsetDateTime and severity are set only when the
+/// record gives them.
+pub fn map_condition(
+    visit: &Visit,
+    patient_id: &str,
+    visit_key: &str,
+    encounter_id: &str,
+    autocoded: Option<&Icd11Match>,
+) -> Condition {
     let (code_codings, verification_code, verification_display) =
-        match diagnosis_coding(&kenyan.visit.diagnosis) {
-            Some((icd10_code, icd10_display, icd11_code, icd11_display)) => (
-                Some(vec![
+        match diagnosis_coding(&visit.diagnosis) {
+            Some(dx) => {
+                let mut codings = vec![
                     // ICD-11 MMS (primary — required by Kenya DHA 2025)
                     Coding {
                         system: Some("http://id.who.int/icd11/mms".to_string()),
-                        code: Some(icd11_code.to_string()),
-                        display: Some(icd11_display.to_string()),
+                        code: Some(dx.icd11_code.to_string()),
+                        display: Some(dx.icd11_display.to_string()),
                     },
                     // ICD-10 (retained for backward compat with KenyaEMR / older SHR)
                     Coding {
                         system: Some("http://hl7.org/fhir/sid/icd-10".to_string()),
-                        code: Some(icd10_code.to_string()),
-                        display: Some(icd10_display.to_string()),
+                        code: Some(dx.icd10_code.to_string()),
+                        display: Some(dx.icd10_display.to_string()),
                     },
-                ]),
-                "confirmed",
-                "Confirmed",
-            ),
-            None => (None, "provisional", "Provisional"),
+                ];
+                // SNOMED CT (KenyaEMR-based SHR consumers)
+                if let Some((snomed_code, snomed_display)) = dx.snomed {
+                    codings.push(Coding {
+                        system: Some("http://snomed.info/sct".to_string()),
+                        code: Some(snomed_code.to_string()),
+                        display: Some(snomed_display.to_string()),
+                    });
+                }
+                (Some(codings), "confirmed", "Confirmed")
+            }
+            None => match autocoded {
+                Some(m) => (
+                    Some(vec![Coding {
+                        system: Some("http://id.who.int/icd11/mms".to_string()),
+                        code: Some(m.code.clone()),
+                        display: Some(m.display.clone()),
+                    }]),
+                    "confirmed",
+                    "Confirmed",
+                ),
+                None => (None, "provisional", "Provisional"),
+            },
         };
 
     Condition {
         resource_type: "Condition".to_string(),
-        id: Some(format!("cond-{}", patient_id)),
+        id: Some(condition_id(visit_key)),
+        meta: None,
+        extension: None,
         clinical_status: Some(CodeableConcept {
             coding: Some(vec![Coding {
                 system: Some(
@@ -97,9 +240,18 @@ pub fn map_condition(kenyan: &KenyanPatient, patient_id: &str, encounter_id: &st
             }]),
             text: None,
         }),
+        category: Some(condition_category(visit.diagnosis_category)),
+        severity: visit.diagnosis_severity.map(|severity| CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some("http://snomed.info/sct".to_string()),
+                code: Some(severity.code().to_string()),
+                display: Some(severity.display().to_string()),
+            }]),
+            text: None,
+        }),
         code: Some(CodeableConcept {
             coding: code_codings,
-            text: Some(kenyan.visit.diagnosis.clone()),
+            text: Some(visit.diagnosis.clone()),
         }),
         subject: Some(Reference {
             reference: Some(format!("Patient/{}", patient_id)),
@@ -109,9 +261,12 @@ pub fn map_condition(kenyan: &KenyanPatient, patient_id: &str, encounter_id: &st
             reference: Some(format!("Encounter/{}", encounter_id)),
             display: None,
         }),
-        onset_date_time: Some(kenyan.visit.date.clone()),
+        onset_date_time: visit.onset_date.map(|d| d.to_string()),
+        recorded_date: Some(visit.date.clone()),
+        stage: None,
         note: Some(vec![Annotation {
-            text: format!("Complaint: {}", kenyan.visit.complaint),
+            text: format!("Complaint: {}", visit.complaint),
         }]),
+        extra: Default::default(),
     }
 }
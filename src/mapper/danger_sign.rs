@@ -0,0 +1,82 @@
+use fhir_parser::fhir::encounter::Period;
+use fhir_parser::fhir::extension::Extension;
+use fhir_parser::fhir::flag::Flag;
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Reference};
+
+use crate::kenyan::schema::DangerSign;
+use crate::systems::DANGER_SIGN_SYSTEM;
+
+const FLAG_PRIORITY_URL: &str = "http://hl7.org/fhir/StructureDefinition/flag-priority";
+
+fn reference(resource_type: &str, id: &str) -> Reference {
+    Reference {
+        reference: Some(format!("{}/{}", resource_type, id)),
+        display: None,
+    }
+}
+
+fn coding(system: &str, code: &str, display: &str) -> Coding {
+    Coding {
+        system: Some(system.to_string()),
+        code: Some(code.to_string()),
+        display: Some(display.to_string()),
+    }
+}
+
+/// Maps the danger signs noted at triage → FHIR R4 Flags (`flag-{key}-{n}`):
+/// active clinical alerts from the visit date, at high flag-priority so
+/// viewers put them above the rest of the record.
+pub fn map_danger_signs(
+    signs: &[DangerSign],
+    patient_id: &str,
+    visit_key: &str,
+    encounter_id: &str,
+    visit_date: &str,
+    author_id: Option<&str>,
+) -> Vec<Flag> {
+    signs
+        .iter()
+        .enumerate()
+        .map(|(i, sign)| Flag {
+            resource_type: "Flag".to_string(),
+            id: Some(format!("flag-{}-{}", visit_key, i + 1)),
+            meta: None,
+            extension: Some(vec![Extension::codeable_concept(
+                FLAG_PRIORITY_URL,
+                CodeableConcept {
+                    coding: Some(vec![coding(
+                        "http://hl7.org/fhir/flag-priority-code",
+                        "PH",
+                        "High priority",
+                    )]),
+                    text: None,
+                },
+            )]),
+            status: "active".to_string(),
+            category: Some(vec![CodeableConcept {
+                coding: Some(vec![coding(
+                    "http://terminology.hl7.org/CodeSystem/flag-category",
+                    "clinical",
+                    "Clinical",
+                )]),
+                text: None,
+            }]),
+            code: CodeableConcept {
+                coding: Some(vec![coding(
+                    DANGER_SIGN_SYSTEM,
+                    sign.code(),
+                    sign.display(),
+                )]),
+                text: Some(format!("Danger sign: {}", sign.display())),
+            },
+            subject: reference("Patient", patient_id),
+            period: Some(Period {
+                start: Some(visit_date.to_string()),
+                end: None,
+            }),
+            encounter: Some(reference("Encounter", encounter_id)),
+            author: author_id.map(|id| reference("Practitioner", id)),
+            extra: Default::default(),
+        })
+        .collect()
+}
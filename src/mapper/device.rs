@@ -0,0 +1,65 @@
+use fhir_parser::fhir::device::Device;
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Observation, Reference};
+use fhir_parser::fhir::patient::Identifier;
+
+use crate::kenyan::schema::VitalsDevice;
+use crate::systems::{device_id_system, DEVICE_TYPE_SYSTEM};
+
+/// One Device per facility and asset number, `dev-{org_id}-{asset}`.
+pub fn device_id(org_id: &str, asset: &str) -> String {
+    let asset: String = asset
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("dev-{}-{}", org_id, asset)
+}
+
+/// Maps a vitals instrument → FHIR R4 Device owned by the facility.
+pub fn map_device(device: &VitalsDevice, clinic_id: &str, org_id: &str) -> Device {
+    Device {
+        resource_type: "Device".to_string(),
+        id: Some(device_id(org_id, &device.id)),
+        meta: None,
+        identifier: Some(vec![Identifier {
+            system: Some(device_id_system(clinic_id)),
+            value: device.id.clone(),
+        }]),
+        status: Some("active".to_string()),
+        type_field: Some(CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some(DEVICE_TYPE_SYSTEM.to_string()),
+                code: Some(device.kind.code().to_string()),
+                display: Some(device.kind.display().to_string()),
+            }]),
+            text: None,
+        }),
+        owner: Some(Reference {
+            reference: Some(format!("Organization/{}", org_id)),
+            display: None,
+        }),
+        extra: Default::default(),
+    }
+}
+
+/// Point each vitals Observation at the instrument that measures it
+/// (see [`DeviceKind::measures`](crate::kenyan::schema::DeviceKind::measures)).
+pub fn link_devices(observations: &mut [Observation], devices: &[VitalsDevice], org_id: &str) {
+    for observation in observations {
+        let code = observation
+            .code
+            .coding
+            .iter()
+            .flatten()
+            .find_map(|c| c.code.as_deref());
+        let Some(device) = devices
+            .iter()
+            .find(|d| code.is_some_and(|code| d.kind.measures().contains(&code)))
+        else {
+            continue;
+        };
+        observation.device = Some(Reference {
+            reference: Some(format!("Device/{}", device_id(org_id, &device.id))),
+            display: None,
+        });
+    }
+}
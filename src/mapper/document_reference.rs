@@ -0,0 +1,168 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use fhir_parser::fhir::document_reference::{
+    DocumentReference, DocumentReferenceContent, DocumentReferenceContext,
+};
+use fhir_parser::fhir::observation::{Attachment, CodeableConcept, Coding, Reference};
+
+use crate::kenyan::schema::{Biometric, DocumentKind, InlineAttachment, Visit, VisitAttachment};
+use crate::systems::BIOMETRIC_TYPE_SYSTEM;
+
+const LOINC_SYSTEM: &str = "http://loinc.org";
+const CONFIDENTIALITY_SYSTEM: &str = "http://terminology.hl7.org/CodeSystem/v3-Confidentiality";
+
+/// FHIR Attachment for inline content, with its decoded size.
+pub fn inline_attachment(content: &InlineAttachment) -> Attachment {
+    Attachment {
+        content_type: Some(content.content_type.clone()),
+        size: STANDARD.decode(&content.data).ok().map(|b| b.len() as u64),
+        data: Some(content.data.clone()),
+        url: None,
+        title: None,
+    }
+}
+
+/// One DocumentReference per biometric template, `bio-{patient_id}-{n}`.
+///
+/// Templates are marked restricted (v3-Confidentiality `R`) so servers that
+/// honour security labels keep them out of ordinary record views.
+pub fn map_biometrics(biometrics: &[Biometric], patient_id: &str) -> Vec<DocumentReference> {
+    biometrics
+        .iter()
+        .enumerate()
+        .map(|(i, biometric)| {
+            let modality = biometric.modality.code();
+            DocumentReference {
+                resource_type: "DocumentReference".to_string(),
+                id: Some(format!("bio-{}-{}", patient_id, i + 1)),
+                meta: None,
+                status: "current".to_string(),
+                doc_type: Some(CodeableConcept {
+                    coding: Some(vec![Coding {
+                        system: Some(BIOMETRIC_TYPE_SYSTEM.to_string()),
+                        code: Some(modality.to_string()),
+                        display: Some(format!("{} template", modality)),
+                    }]),
+                    text: None,
+                }),
+                category: None,
+                subject: Some(Reference {
+                    reference: Some(format!("Patient/{}", patient_id)),
+                    display: None,
+                }),
+                date: None,
+                description: None,
+                security_label: Some(vec![CodeableConcept {
+                    coding: Some(vec![Coding {
+                        system: Some(CONFIDENTIALITY_SYSTEM.to_string()),
+                        code: Some("R".to_string()),
+                        display: Some("restricted".to_string()),
+                    }]),
+                    text: None,
+                }]),
+                content: vec![DocumentReferenceContent {
+                    attachment: inline_attachment(&InlineAttachment {
+                        content_type: biometric.content_type.clone(),
+                        data: biometric.data.clone(),
+                    }),
+                    format: None,
+                }],
+                context: None,
+                extra: Default::default(),
+            }
+        })
+        .collect()
+}
+
+/// LOINC document type for each kind of visit attachment.
+pub fn document_type(kind: DocumentKind) -> (&'static str, &'static str) {
+    match kind {
+        DocumentKind::LabReport => ("11502-2", "Laboratory report"),
+        DocumentKind::ImagingReport => ("18748-4", "Diagnostic imaging study"),
+        DocumentKind::ReferralLetter => ("57133-1", "Referral note"),
+        DocumentKind::ClinicalNote => ("34109-9", "Note"),
+    }
+}
+
+/// One DocumentReference per visit attachment, `doc-{key}-{n}`, linked to
+/// the visit's Encounter.
+pub fn map_visit_documents(
+    visit: &Visit,
+    patient_id: &str,
+    key: &str,
+    encounter_id: &str,
+) -> Vec<DocumentReference> {
+    visit
+        .attachments
+        .iter()
+        .enumerate()
+        .map(|(i, attachment)| {
+            map_attachment(
+                attachment,
+                format!("doc-{}-{}", key, i + 1),
+                patient_id,
+                encounter_id,
+            )
+        })
+        .collect()
+}
+
+/// DocumentReference `id` for an attachment made at the visit whose
+/// Encounter is `encounter_id`. Content is inline or by URL, as sent.
+pub fn map_attachment(
+    attachment: &VisitAttachment,
+    id: String,
+    patient_id: &str,
+    encounter_id: &str,
+) -> DocumentReference {
+    let (code, display) = document_type(attachment.kind);
+    let mut content = match &attachment.data {
+        Some(data) => inline_attachment(&InlineAttachment {
+            content_type: attachment.content_type.clone(),
+            data: data.clone(),
+        }),
+        None => Attachment {
+            content_type: Some(attachment.content_type.clone()),
+            data: None,
+            url: attachment.url.clone(),
+            title: None,
+            size: None,
+        },
+    };
+    content.title = attachment.title.clone();
+    DocumentReference {
+        resource_type: "DocumentReference".to_string(),
+        id: Some(id),
+        meta: None,
+        status: "current".to_string(),
+        doc_type: Some(CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some(LOINC_SYSTEM.to_string()),
+                code: Some(code.to_string()),
+                display: Some(display.to_string()),
+            }]),
+            text: None,
+        }),
+        category: None,
+        subject: Some(Reference {
+            reference: Some(format!("Patient/{}", patient_id)),
+            display: None,
+        }),
+        date: None,
+        description: None,
+        security_label: None,
+        content: vec![DocumentReferenceContent {
+            attachment: content,
+            format: None,
+        }],
+        context: Some(DocumentReferenceContext {
+            encounter: Some(vec![Reference {
+                reference: Some(format!("Encounter/{}", encounter_id)),
+                display: None,
+            }]),
+            related: None,
+        }),
+        extra: Default::default(),
+    }
+}
@@ -0,0 +1,287 @@
+use fhir_parser::fhir::medication_request::{Dosage, DoseAndRate, Duration, Timing, TimingRepeat};
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Quantity};
+
+const UCUM_SYSTEM: &str = "http://unitsofmeasure.org";
+const SNOMED_SYSTEM: &str = "http://snomed.info/sct";
+
+/// Frequency shorthand → (times, per period, period unit)
+fn frequency_abbreviation(word: &str) -> Option<(u32, f64, &'static str)> {
+    match word {
+        "od" | "qd" | "daily" | "nocte" | "mane" => Some((1, 1.0, "d")),
+        "bd" | "bid" => Some((2, 1.0, "d")),
+        "tds" | "tid" => Some((3, 1.0, "d")),
+        "qid" | "qds" => Some((4, 1.0, "d")),
+        _ => None,
+    }
+}
+
+/// Route shorthand → SNOMED CT route of administration
+fn route(word: &str) -> Option<(&'static str, &'static str)> {
+    match word {
+        "po" | "oral" | "orally" => Some(("26643006", "Oral route")),
+        "im" => Some(("78421000", "Intramuscular route")),
+        "iv" => Some(("47625008", "Intravenous route")),
+        "sc" | "subcut" | "sq" => Some(("34206005", "Subcutaneous route")),
+        "pr" => Some(("37161004", "Rectal route")),
+        "topical" | "topically" => Some(("6064005", "Topical route")),
+        _ => None,
+    }
+}
+
+/// Dose unit → UCUM unit (as in the vitals Observations, the UCUM code goes
+/// in Quantity.unit)
+fn dose_unit(word: &str) -> Option<&'static str> {
+    match word {
+        "mg" => Some("mg"),
+        "g" => Some("g"),
+        "mcg" | "ug" | "µg" => Some("ug"),
+        "ml" => Some("mL"),
+        "iu" => Some("[iU]"),
+        "tab" | "tabs" | "tablet" | "tablets" => Some("{tbl}"),
+        "cap" | "caps" | "capsule" | "capsules" => Some("{capsule}"),
+        _ => None,
+    }
+}
+
+/// Duration unit → (UCUM code, display unit)
+fn time_unit(word: &str) -> Option<(&'static str, &'static str)> {
+    match word {
+        "hour" | "hours" | "hr" | "hrs" | "h" => Some(("h", "hours")),
+        "day" | "days" | "d" => Some(("d", "days")),
+        "week" | "weeks" | "wk" | "wks" => Some(("wk", "weeks")),
+        "month" | "months" | "mo" => Some(("mo", "months")),
+        _ => None,
+    }
+}
+
+fn times_word(word: &str) -> Option<u32> {
+    match word {
+        "once" => Some(1),
+        "twice" => Some(2),
+        "thrice" => Some(3),
+        _ => word.parse().ok(),
+    }
+}
+
+/// `500mg` → (500, "mg"); `500` → (500, "")
+fn split_number(word: &str) -> Option<(f64, &str)> {
+    let end = word
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(word.len());
+    let value = word[..end].parse().ok()?;
+    Some((value, &word[end..]))
+}
+
+fn duration(value: f64, code: &str, unit: &str) -> Duration {
+    Duration {
+        value,
+        unit: Some(unit.to_string()),
+        system: Some(UCUM_SYSTEM.to_string()),
+        code: code.to_string(),
+    }
+}
+
+/// Kenyan "n/7, n/52, n/12" duration shorthand (days, weeks, months).
+fn slash_duration(word: &str) -> Option<Duration> {
+    let (n, denominator) = word.split_once('/')?;
+    let n: f64 = n.parse().ok()?;
+    match denominator {
+        "7" => Some(duration(n, "d", "days")),
+        "52" => Some(duration(n, "wk", "weeks")),
+        "12" => Some(duration(n, "mo", "months")),
+        _ => None,
+    }
+}
+
+/// Parse a prescription string into a structured Dosage.
+///
+/// Understands the shorthand common in Kenyan prescriptions:
+/// - frequency: OD, BD, TDS, QID, nocte, "twice daily", "3 times a day",
+///   "8 hourly", "every 6 hours", "once weekly"
+/// - duration: "for 7 days", "x 2 weeks", "x 5/7"
+/// - single dose: "stat"; as needed: PRN, "as needed", "when required"
+/// - route: PO, IM, IV, SC, PR, topical
+/// - dose: "500mg", "2 tabs" (combination strengths like "80/480mg" are left
+///   to the free text)
+///
+/// The original string is always kept as Dosage.text; anything not
+/// recognised is simply not structured.
+pub fn parse_dosage(text: &str) -> Dosage {
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | '(' | ')'))
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    let mut repeat = TimingRepeat::default();
+    let mut as_needed = None;
+    let mut route_coding = None;
+    let mut dose = None;
+
+    let mut i = 0;
+    while i < words.len() {
+        let word = words[i];
+        let next = words.get(i + 1).copied().unwrap_or_default();
+        let after = words.get(i + 2).copied().unwrap_or_default();
+
+        if let Some((frequency, period, unit)) = frequency_abbreviation(word) {
+            repeat.frequency.get_or_insert(frequency);
+            repeat.period.get_or_insert(period);
+            repeat.period_unit.get_or_insert(unit.to_string());
+        } else if word == "stat" {
+            repeat.count = Some(1);
+        } else if word == "prn" {
+            as_needed = Some(true);
+        } else if (word == "as" && next == "needed")
+            || (word == "when" && matches!(next, "required" | "necessary"))
+        {
+            as_needed = Some(true);
+            i += 1;
+        } else if let Some(r) = route(word) {
+            route_coding.get_or_insert(r);
+        } else if matches!(word, "for" | "x") {
+            // for 7 days | x 2 weeks | x 5/7
+            if let Some(d) = slash_duration(next) {
+                repeat.bounds_duration = Some(d);
+                i += 1;
+            } else if let (Ok(n), Some((code, unit))) = (next.parse(), time_unit(after)) {
+                repeat.bounds_duration = Some(duration(n, code, unit));
+                i += 2;
+            }
+        } else if let Some(times) = times_word(word) {
+            // once daily | twice a day | 3 times daily | once weekly
+            let rest = if next == "times" {
+                &words[(i + 2).min(words.len())..]
+            } else {
+                &words[i + 1..]
+            };
+            let rest = if rest.first() == Some(&"a") {
+                &rest[1..]
+            } else {
+                rest
+            };
+            let period_unit = match rest.first().copied() {
+                Some("daily" | "day") => Some("d"),
+                Some("weekly" | "week") => Some("wk"),
+                _ => None,
+            };
+            if let Some(unit) = period_unit.filter(|_| repeat.frequency.is_none()) {
+                repeat.frequency = Some(times);
+                repeat.period = Some(1.0);
+                repeat.period_unit = Some(unit.to_string());
+                i = words.len() - rest.len();
+            } else if next == "hourly" && repeat.frequency.is_none() && word.parse::<u32>().is_ok()
+            {
+                // 8 hourly
+                repeat.frequency = Some(1);
+                repeat.period = Some(times as f64);
+                repeat.period_unit = Some("h".to_string());
+                i += 1;
+            } else if dose.is_none() {
+                if let Some(unit) = dose_unit(next) {
+                    // 2 tabs | 500 mg
+                    dose = Some((times as f64, unit));
+                    i += 1;
+                }
+            }
+        } else if word == "every" && repeat.frequency.is_none() {
+            // every 6 hours
+            if let (Ok(n), Some((code, _))) = (next.parse::<f64>(), time_unit(after)) {
+                repeat.frequency = Some(1);
+                repeat.period = Some(n);
+                repeat.period_unit = Some(code.to_string());
+                i += 2;
+            }
+        } else if dose.is_none() && !word.contains('/') {
+            // 500mg
+            if let Some((value, unit)) = split_number(word) {
+                if let Some(unit) = dose_unit(unit) {
+                    dose = Some((value, unit));
+                }
+            }
+        }
+        i += 1;
+    }
+
+    let has_timing =
+        repeat.frequency.is_some() || repeat.count.is_some() || repeat.bounds_duration.is_some();
+    Dosage {
+        text: text.to_string(),
+        timing: has_timing.then_some(Timing {
+            repeat: Some(repeat),
+        }),
+        as_needed_boolean: as_needed,
+        route: route_coding.map(|(code, display)| CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some(SNOMED_SYSTEM.to_string()),
+                code: Some(code.to_string()),
+                display: Some(display.to_string()),
+            }]),
+            text: None,
+        }),
+        dose_and_rate: dose.map(|(value, unit)| {
+            vec![DoseAndRate {
+                dose_quantity: Some(Quantity {
+                    value,
+                    unit: Some(unit.to_string()),
+                    system: Some(UCUM_SYSTEM.to_string()),
+                }),
+            }]
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dose_frequency_and_duration() {
+        let dosage = parse_dosage("Amoxicillin 500mg TDS for 7 days");
+        let repeat = dosage.timing.unwrap().repeat.unwrap();
+        assert_eq!(repeat.frequency, Some(3));
+        assert_eq!(repeat.period_unit.as_deref(), Some("d"));
+        let bounds = repeat.bounds_duration.unwrap();
+        assert_eq!((bounds.value, bounds.code.as_str()), (7.0, "d"));
+        let dose = dosage.dose_and_rate.unwrap()[0]
+            .dose_quantity
+            .clone()
+            .unwrap();
+        assert_eq!((dose.value, dose.unit.as_deref()), (500.0, Some("mg")));
+    }
+
+    #[test]
+    fn parses_stat_route_and_prn() {
+        let stat = parse_dosage("Ceftriaxone 1g IM stat");
+        assert_eq!(stat.timing.unwrap().repeat.unwrap().count, Some(1));
+        let route = stat.route.unwrap().coding.unwrap();
+        assert_eq!(route[0].code.as_deref(), Some("78421000"));
+
+        let prn = parse_dosage("Paracetamol 1g PO PRN");
+        assert_eq!(prn.as_needed_boolean, Some(true));
+        assert!(prn.timing.is_none());
+    }
+
+    #[test]
+    fn parses_spelled_out_and_shorthand_forms() {
+        let dosage = parse_dosage("Artemether-Lumefantrine 80/480mg twice daily x 3/7");
+        let repeat = dosage.timing.unwrap().repeat.unwrap();
+        assert_eq!(repeat.frequency, Some(2));
+        assert_eq!(repeat.bounds_duration.unwrap().value, 3.0);
+        // combination strength is not a single dose quantity
+        assert!(dosage.dose_and_rate.is_none());
+
+        let hourly = parse_dosage("Metronidazole 400mg 8 hourly");
+        let repeat = hourly.timing.unwrap().repeat.unwrap();
+        assert_eq!((repeat.frequency, repeat.period), (Some(1), Some(8.0)));
+        assert_eq!(repeat.period_unit.as_deref(), Some("h"));
+    }
+
+    #[test]
+    fn unstructured_text_is_kept_as_is() {
+        let dosage = parse_dosage("None required");
+        assert_eq!(dosage.text, "None required");
+        assert!(dosage.timing.is_none() && dosage.route.is_none());
+        assert!(dosage.dose_and_rate.is_none());
+    }
+}
@@ -1,12 +1,57 @@
-use fhir_parser::fhir::encounter::{Encounter, EncounterParticipant, Period};
+use fhir_parser::fhir::encounter::{
+    Encounter, EncounterDiagnosis, EncounterLocation, EncounterParticipant, Period,
+};
 use fhir_parser::fhir::observation::{CodeableConcept, Coding, Reference};
+use fhir_parser::fhir::patient::Identifier;
 
-use crate::kenyan::schema::KenyanPatient;
+use crate::kenyan::schema::{CareProgramme, KenyanPatient, Visit};
+use crate::mapper::condition::condition_id;
+use crate::mapper::episode_of_care::episode_id;
+use crate::mapper::location::location_id;
+use crate::mapper::triage::triage_priority;
+use crate::mapper::visit_key;
+use crate::systems::visit_id_system;
+use crate::terminology::complaint::ComplaintTerminology;
 
+/// Reference to the visit `visit_id`: the Encounter in this bundle when the
+/// record holds that visit, otherwise a conditional reference on the visit
+/// number that the server resolves to the Encounter sent before.
+pub fn visit_reference(kenyan: &KenyanPatient, patient_id: &str, visit_id: &str) -> Reference {
+    let reference = match kenyan
+        .visits
+        .iter()
+        .position(|v| v.visit_id.as_deref() == Some(visit_id))
+    {
+        Some(i) => format!("Encounter/enc-{}", visit_key(patient_id, i)),
+        None => format!(
+            "Encounter?identifier={}|{}",
+            visit_id_system(&kenyan.clinic_id),
+            visit_id
+        ),
+    };
+    Reference {
+        reference: Some(reference),
+        display: None,
+    }
+}
+
+/// Maps a visit → FHIR R4 Encounter.
+///
+/// reasonCode carries the presenting complaint(s), coded against the
+/// complaint terminology where recognised. A follow-up visit points at the
+/// visit it follows (partOf) and a programme visit at its EpisodeOfCare.
+/// A triaged visit carries its urgency as priority, and one with a
+/// department the Location it took place in. The visit's Condition is its
+/// rank-1 billing diagnosis.
+/// `programme` is the visit's own or the one a `--profile` assigns.
 pub fn map_encounter(
     kenyan: &KenyanPatient,
+    visit: &Visit,
     patient_id: &str,
+    visit_key: &str,
     practitioner_id: Option<&str>,
+    programme: Option<CareProgramme>,
+    complaints: &ComplaintTerminology,
 ) -> Encounter {
     let org_id = format!("org-{}", kenyan.clinic_id.replace('/', "-"));
 
@@ -32,7 +77,15 @@ pub fn map_encounter(
 
     Encounter {
         resource_type: "Encounter".to_string(),
-        id: Some(format!("enc-{}", patient_id)),
+        id: Some(format!("enc-{}", visit_key)),
+        meta: None,
+        extension: None,
+        identifier: visit.visit_id.as_ref().map(|id| {
+            vec![Identifier {
+                system: Some(visit_id_system(&kenyan.clinic_id)),
+                value: id.clone(),
+            }]
+        }),
         status: Some("finished".to_string()),
         // AfyaLink SHR requires "OP" (outpatient) — not "AMB" — for OPD visits.
         class: Some(Coding {
@@ -40,6 +93,7 @@ pub fn map_encounter(
             code: Some("OP".to_string()),
             display: Some("outpatient".to_string()),
         }),
+        priority: visit.triage_category.map(triage_priority),
         subject: Some(Reference {
             reference: Some(format!("Patient/{}", patient_id)),
             display: None,
@@ -50,12 +104,48 @@ pub fn map_encounter(
             display: None,
         }),
         period: Some(Period {
-            start: Some(kenyan.visit.date.clone()),
-            end: Some(kenyan.visit.date.clone()),
+            start: Some(visit.date.clone()),
+            end: Some(visit.date.clone()),
+        }),
+        reason_code: Some(complaints.reason_codes(&visit.complaint)),
+        episode_of_care: programme.map(|programme| {
+            vec![Reference {
+                reference: Some(format!(
+                    "EpisodeOfCare/{}",
+                    episode_id(patient_id, programme)
+                )),
+                display: None,
+            }]
+        }),
+        part_of: visit
+            .previous_visit_id
+            .as_deref()
+            .map(|id| visit_reference(kenyan, patient_id, id)),
+        location: visit.department.map(|department| {
+            vec![EncounterLocation {
+                location: Reference {
+                    reference: Some(format!("Location/{}", location_id(&org_id, department))),
+                    display: None,
+                },
+            }]
         }),
-        reason_code: Some(vec![CodeableConcept {
-            coding: None,
-            text: Some(kenyan.visit.complaint.clone()),
+        diagnosis: Some(vec![EncounterDiagnosis {
+            condition: Reference {
+                reference: Some(format!("Condition/{}", condition_id(visit_key))),
+                display: None,
+            },
+            use_field: Some(CodeableConcept {
+                coding: Some(vec![Coding {
+                    system: Some(
+                        "http://terminology.hl7.org/CodeSystem/diagnosis-role".to_string(),
+                    ),
+                    code: Some("billing".to_string()),
+                    display: Some("Billing".to_string()),
+                }]),
+                text: None,
+            }),
+            rank: Some(1),
         }]),
+        extra: Default::default(),
     }
 }
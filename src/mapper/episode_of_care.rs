@@ -0,0 +1,47 @@
+use fhir_parser::fhir::episode_of_care::EpisodeOfCare;
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Reference};
+
+use crate::kenyan::schema::CareProgramme;
+use crate::systems::CARE_PROGRAMME_SYSTEM;
+
+/// One episode per patient and programme, `eoc-{patient_id}-{programme}`.
+/// The id does not depend on the visits, so every bundle for the patient
+/// updates the same EpisodeOfCare.
+pub fn episode_id(patient_id: &str, programme: CareProgramme) -> String {
+    format!("eoc-{}-{}", patient_id, programme.code())
+}
+
+/// Maps a programme enrolment → FHIR R4 EpisodeOfCare (status active).
+///
+/// No period: enrolment dates are not in the record, and the first visit
+/// in one bundle is not the first visit in the programme.
+pub fn map_episode_of_care(
+    programme: CareProgramme,
+    patient_id: &str,
+    org_id: &str,
+) -> EpisodeOfCare {
+    EpisodeOfCare {
+        resource_type: "EpisodeOfCare".to_string(),
+        id: Some(episode_id(patient_id, programme)),
+        meta: None,
+        status: "active".to_string(),
+        type_field: Some(vec![CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some(CARE_PROGRAMME_SYSTEM.to_string()),
+                code: Some(programme.code().to_string()),
+                display: Some(programme.display().to_string()),
+            }]),
+            text: None,
+        }]),
+        patient: Reference {
+            reference: Some(format!("Patient/{}", patient_id)),
+            display: None,
+        },
+        managing_organization: Some(Reference {
+            reference: Some(format!("Organization/{}", org_id)),
+            display: None,
+        }),
+        period: None,
+        extra: Default::default(),
+    }
+}
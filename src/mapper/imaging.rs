@@ -0,0 +1,141 @@
+use fhir_parser::fhir::diagnostic_report::DiagnosticReport;
+use fhir_parser::fhir::document_reference::DocumentReference;
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Reference};
+use fhir_parser::fhir::service_request::ServiceRequest;
+
+use crate::kenyan::schema::ImagingOrder;
+use crate::mapper::document_reference::map_attachment;
+
+/// SNOMED CT "Imaging", the ServiceRequest category.
+pub const IMAGING_SNOMED: &str = "363679005";
+
+/// An imaging order, its report once there is one, and the images or
+/// report PDF attached to it.
+#[derive(Debug, Clone)]
+pub struct ImagingResources {
+    pub service_request: ServiceRequest,
+    pub diagnostic_report: Option<DiagnosticReport>,
+    pub documents: Vec<DocumentReference>,
+}
+
+fn reference(resource_type: &str, id: &str) -> Reference {
+    Reference {
+        reference: Some(format!("{}/{}", resource_type, id)),
+        display: None,
+    }
+}
+
+fn coding(system: &str, code: &str, display: Option<&str>) -> Coding {
+    Coding {
+        system: Some(system.to_string()),
+        code: Some(code.to_string()),
+        display: display.map(str::to_string),
+    }
+}
+
+/// Maps the visit's imaging orders → FHIR R4 ServiceRequests
+/// (`img-{key}-{n}`, for the visit's Condition). An order with a conclusion
+/// is `completed` and gets a final DiagnosticReport (`dr-{key}-{n}`); one
+/// without stays `active` for the radiology system. Attachments become
+/// DocumentReferences (`imgdoc-{key}-{n}-{m}`) on the visit's Encounter,
+/// related to the order and its report.
+pub fn map_imaging_orders(
+    orders: &[ImagingOrder],
+    patient_id: &str,
+    key: &str,
+    encounter_id: &str,
+    visit_date: &str,
+    requester_id: Option<&str>,
+    condition_id: &str,
+) -> Vec<ImagingResources> {
+    orders
+        .iter()
+        .enumerate()
+        .map(|(i, order)| {
+            let request_id = format!("img-{}-{}", key, i + 1);
+            let report_id = format!("dr-{}-{}", key, i + 1);
+            let code = CodeableConcept {
+                coding: Some(vec![coding("http://loinc.org", &order.study_code, None)]),
+                text: order.study_name.clone(),
+            };
+            let service_request = ServiceRequest {
+                resource_type: "ServiceRequest".to_string(),
+                id: Some(request_id.clone()),
+                meta: None,
+                status: if order.conclusion.is_some() {
+                    "completed"
+                } else {
+                    "active"
+                }
+                .to_string(),
+                intent: "order".to_string(),
+                category: Some(vec![CodeableConcept {
+                    coding: Some(vec![coding(
+                        "http://snomed.info/sct",
+                        IMAGING_SNOMED,
+                        Some("Imaging"),
+                    )]),
+                    text: None,
+                }]),
+                code: Some(code.clone()),
+                subject: reference("Patient", patient_id),
+                encounter: Some(reference("Encounter", encounter_id)),
+                authored_on: Some(visit_date.to_string()),
+                requester: requester_id.map(|id| reference("Practitioner", id)),
+                reason_reference: Some(vec![reference("Condition", condition_id)]),
+                specimen: None,
+                extra: Default::default(),
+            };
+            let diagnostic_report = order
+                .conclusion
+                .as_ref()
+                .map(|conclusion| DiagnosticReport {
+                    resource_type: "DiagnosticReport".to_string(),
+                    id: Some(report_id.clone()),
+                    meta: None,
+                    based_on: Some(vec![reference("ServiceRequest", &request_id)]),
+                    status: "final".to_string(),
+                    category: Some(vec![CodeableConcept {
+                        coding: Some(vec![coding(
+                            "http://terminology.hl7.org/CodeSystem/v2-0074",
+                            "RAD",
+                            Some("Radiology"),
+                        )]),
+                        text: None,
+                    }]),
+                    code,
+                    subject: Some(reference("Patient", patient_id)),
+                    encounter: Some(reference("Encounter", encounter_id)),
+                    effective_date_time: Some(visit_date.to_string()),
+                    conclusion: Some(conclusion.clone()),
+                    extra: Default::default(),
+                });
+            let mut related = vec![reference("ServiceRequest", &request_id)];
+            if diagnostic_report.is_some() {
+                related.push(reference("DiagnosticReport", &report_id));
+            }
+            let documents = order
+                .attachments
+                .iter()
+                .enumerate()
+                .map(|(j, attachment)| {
+                    let mut document = map_attachment(
+                        attachment,
+                        format!("imgdoc-{}-{}-{}", key, i + 1, j + 1),
+                        patient_id,
+                        encounter_id,
+                    );
+                    if let Some(context) = &mut document.context {
+                        context.related = Some(related.clone());
+                    }
+                    document
+                })
+                .collect();
+            ImagingResources {
+                service_request,
+                diagnostic_report,
+                documents,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,82 @@
+use chrono::NaiveDate;
+use fhir_parser::fhir::immunization_recommendation::{
+    DateCriterion, ImmunizationRecommendation, Recommendation,
+};
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Reference};
+
+use crate::immunization::ForecastDose;
+use crate::systems::KEPI_VACCINE_SYSTEM;
+
+const FORECAST_STATUS_SYSTEM: &str =
+    "http://terminology.hl7.org/CodeSystem/immunization-recommendation-status";
+
+/// LOINC "Date vaccine due" and "Date when overdue for immunization".
+const DUE_DATE_LOINC: (&str, &str) = ("30980-7", "Date vaccine due");
+const OVERDUE_DATE_LOINC: (&str, &str) = ("59778-1", "Date when overdue for immunization");
+
+fn coding(system: &str, code: &str, display: &str) -> CodeableConcept {
+    CodeableConcept {
+        coding: Some(vec![Coding {
+            system: Some(system.to_string()),
+            code: Some(code.to_string()),
+            display: Some(display.to_string()),
+        }]),
+        text: None,
+    }
+}
+
+fn date_criterion((code, display): (&str, &str), date: NaiveDate) -> DateCriterion {
+    DateCriterion {
+        code: coding("http://loinc.org", code, display),
+        value: date.to_string(),
+    }
+}
+
+/// Maps a KEPI forecast → FHIR R4 ImmunizationRecommendation
+/// (`imr-{patient_id}`), one recommendation per dose with its due and
+/// overdue dates. `as_of` is the date the forecast was made for.
+pub fn map_immunization_recommendation(
+    forecast: &[ForecastDose],
+    patient_id: &str,
+    org_id: &str,
+    as_of: NaiveDate,
+) -> ImmunizationRecommendation {
+    ImmunizationRecommendation {
+        resource_type: "ImmunizationRecommendation".to_string(),
+        id: Some(format!("imr-{}", patient_id)),
+        meta: None,
+        patient: Reference {
+            reference: Some(format!("Patient/{}", patient_id)),
+            display: None,
+        },
+        date: as_of.to_string(),
+        authority: Some(Reference {
+            reference: Some(format!("Organization/{}", org_id)),
+            display: None,
+        }),
+        recommendation: forecast
+            .iter()
+            .map(|dose| Recommendation {
+                vaccine_code: Some(vec![coding(
+                    KEPI_VACCINE_SYSTEM,
+                    dose.vaccine.code(),
+                    dose.vaccine.display(),
+                )]),
+                forecast_status: coding(
+                    FORECAST_STATUS_SYSTEM,
+                    dose.status.code(),
+                    dose.status.display(),
+                ),
+                date_criterion: Some(vec![
+                    date_criterion(DUE_DATE_LOINC, dose.due),
+                    date_criterion(OVERDUE_DATE_LOINC, dose.overdue),
+                ]),
+                series: Some("KEPI".to_string()),
+                // positiveInt starts at 1; the OPV birth dose is dose 0
+                dose_number_positive_int: (dose.dose > 0).then_some(u32::from(dose.dose)),
+                dose_number_string: (dose.dose == 0).then(|| "0".to_string()),
+            })
+            .collect(),
+        extra: Default::default(),
+    }
+}
@@ -0,0 +1,98 @@
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Reference};
+use fhir_parser::fhir::service_request::ServiceRequest;
+use fhir_parser::fhir::specimen::{Specimen, SpecimenCollection};
+
+use crate::kenyan::schema::LabOrder;
+
+/// SNOMED CT "Laboratory procedure", the ServiceRequest category.
+pub const LAB_PROCEDURE_SNOMED: &str = "108252007";
+
+/// A lab order and the specimen it runs on.
+#[derive(Debug, Clone)]
+pub struct LabOrderResources {
+    pub service_request: ServiceRequest,
+    pub specimen: Specimen,
+}
+
+fn reference(resource_type: &str, id: &str) -> Reference {
+    Reference {
+        reference: Some(format!("{}/{}", resource_type, id)),
+        display: None,
+    }
+}
+
+/// Maps the visit's lab orders → FHIR R4 ServiceRequests (`lab-{key}-{n}`,
+/// active orders coded in LOINC) and their Specimens (`spec-{key}-{n}`,
+/// typed from HL7 v2 table 0487). A Specimen is `available` once it has a
+/// collection time; until then it is the specimen the order asks for.
+pub fn map_lab_orders(
+    orders: &[LabOrder],
+    patient_id: &str,
+    key: &str,
+    encounter_id: &str,
+    visit_date: &str,
+    requester_id: Option<&str>,
+) -> Vec<LabOrderResources> {
+    orders
+        .iter()
+        .enumerate()
+        .map(|(i, order)| {
+            let request_id = format!("lab-{}-{}", key, i + 1);
+            let specimen_id = format!("spec-{}-{}", key, i + 1);
+            let service_request = ServiceRequest {
+                resource_type: "ServiceRequest".to_string(),
+                id: Some(request_id.clone()),
+                meta: None,
+                status: "active".to_string(),
+                intent: "order".to_string(),
+                category: Some(vec![CodeableConcept {
+                    coding: Some(vec![Coding {
+                        system: Some("http://snomed.info/sct".to_string()),
+                        code: Some(LAB_PROCEDURE_SNOMED.to_string()),
+                        display: Some("Laboratory procedure".to_string()),
+                    }]),
+                    text: None,
+                }]),
+                code: Some(CodeableConcept {
+                    coding: Some(vec![Coding {
+                        system: Some("http://loinc.org".to_string()),
+                        code: Some(order.test_code.clone()),
+                        display: None,
+                    }]),
+                    text: order.test_name.clone(),
+                }),
+                subject: reference("Patient", patient_id),
+                encounter: Some(reference("Encounter", encounter_id)),
+                authored_on: Some(visit_date.to_string()),
+                requester: requester_id.map(|id| reference("Practitioner", id)),
+                reason_reference: None,
+                specimen: Some(vec![reference("Specimen", &specimen_id)]),
+                extra: Default::default(),
+            };
+            let specimen = Specimen {
+                resource_type: "Specimen".to_string(),
+                id: Some(specimen_id),
+                meta: None,
+                status: order.collected_at.map(|_| "available".to_string()),
+                type_field: Some(CodeableConcept {
+                    coding: Some(vec![Coding {
+                        system: Some("http://terminology.hl7.org/CodeSystem/v2-0487".to_string()),
+                        code: Some(order.specimen.code().to_string()),
+                        display: Some(order.specimen.display().to_string()),
+                    }]),
+                    text: None,
+                }),
+                subject: Some(reference("Patient", patient_id)),
+                request: Some(vec![reference("ServiceRequest", &request_id)]),
+                collection: order.collected_at.map(|at| SpecimenCollection {
+                    collected_date_time: Some(at.to_rfc3339()),
+                }),
+                extra: Default::default(),
+            };
+            LabOrderResources {
+                service_request,
+                specimen,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,48 @@
+use fhir_parser::fhir::location::Location;
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Reference};
+
+use crate::kenyan::schema::Department;
+use crate::systems::DEPARTMENT_SYSTEM;
+
+/// One Location per facility and department, `loc-{org_id}-{department}`.
+pub fn location_id(org_id: &str, department: Department) -> String {
+    format!("loc-{}-{}", org_id, department.code())
+}
+
+/// Maps a visit's department → FHIR R4 Location, a wing of the facility.
+///
+/// The facility is an Organization, and Location.partOf can only point at
+/// another Location, so the department links to it as managingOrganization.
+pub fn map_location(department: Department, org_id: &str) -> Location {
+    Location {
+        resource_type: "Location".to_string(),
+        id: Some(location_id(org_id, department)),
+        meta: None,
+        status: Some("active".to_string()),
+        name: Some(department.display().to_string()),
+        type_field: Some(vec![CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some(DEPARTMENT_SYSTEM.to_string()),
+                code: Some(department.code().to_string()),
+                display: Some(department.display().to_string()),
+            }]),
+            text: None,
+        }]),
+        physical_type: Some(CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some(
+                    "http://terminology.hl7.org/CodeSystem/location-physical-type".to_string(),
+                ),
+                code: Some("wi".to_string()),
+                display: Some("Wing".to_string()),
+            }]),
+            text: None,
+        }),
+        managing_organization: Some(Reference {
+            reference: Some(format!("Organization/{}", org_id)),
+            display: None,
+        }),
+        part_of: None,
+        extra: Default::default(),
+    }
+}
@@ -1,27 +1,34 @@
-use fhir_parser::fhir::medication_request::{Dosage, MedicationRequest};
+use fhir_parser::fhir::medication_request::MedicationRequest;
 use fhir_parser::fhir::observation::{CodeableConcept, Reference};
 
-use crate::kenyan::schema::KenyanPatient;
+use crate::kenyan::schema::Visit;
+use crate::mapper::dosage::parse_dosage;
+use crate::terminology::formulary::Formulary;
 
 /// Maps visit.treatment → FHIR R4 MedicationRequest.
 ///
-/// The treatment string (e.g. "Amoxicillin 500mg TDS for 7 days") is recorded as
-/// free-text dosage instruction. No RxNorm/SNOMED coding is applied — the source
-/// record does not carry structured medication data.
+/// The treatment string (e.g. "Amoxicillin 500mg TDS for 7 days") is kept as
+/// dosage instruction text and medicationCodeableConcept.text; the dosage
+/// shorthand is also parsed into timing, route and dose where recognised.
+/// When it confidently matches the KEML formulary, an ATC Coding is added
+/// alongside the text.
 pub fn map_medication_request(
-    kenyan: &KenyanPatient,
+    visit: &Visit,
     patient_id: &str,
+    visit_key: &str,
     encounter_id: &str,
+    formulary: &Formulary,
 ) -> MedicationRequest {
     MedicationRequest {
         resource_type: "MedicationRequest".to_string(),
-        id: Some(format!("med-{}", patient_id)),
+        id: Some(format!("med-{}", visit_key)),
+        meta: None,
+        extension: None,
         status: "active".to_string(),
         intent: "order".to_string(),
         medication_codeable_concept: Some(CodeableConcept {
-            coding: None,
-            // Free text — structured coding would require a formulary lookup
-            text: Some(kenyan.visit.treatment.clone()),
+            coding: formulary.coding(&visit.treatment).map(|c| vec![c]),
+            text: Some(visit.treatment.clone()),
         }),
         subject: Reference {
             reference: Some(format!("Patient/{}", patient_id)),
@@ -31,9 +38,8 @@ pub fn map_medication_request(
             reference: Some(format!("Encounter/{}", encounter_id)),
             display: None,
         }),
-        dosage_instruction: Some(vec![Dosage {
-            text: kenyan.visit.treatment.clone(),
-        }]),
-        authored_on: Some(kenyan.visit.date.clone()),
+        dosage_instruction: Some(vec![parse_dosage(&visit.treatment)]),
+        authored_on: Some(visit.date.clone()),
+        extra: Default::default(),
     }
 }
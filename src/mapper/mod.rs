@@ -1,8 +1,36 @@
+pub mod antenatal;
+pub mod appointment;
+pub mod clinical_impression;
 pub mod condition;
+pub mod danger_sign;
+pub mod device;
+pub mod document_reference;
+pub mod dosage;
 pub mod encounter;
+pub mod episode_of_care;
+pub mod imaging;
+pub mod immunization;
+pub mod lab_order;
+pub mod location;
 pub mod medication_request;
+pub mod nutrition;
 pub mod observation;
 pub mod organization;
 pub mod patient;
 pub mod practitioner;
+pub mod programme;
+pub mod screening;
 pub mod sha;
+pub mod triage;
+
+/// Key used to derive per-visit resource IDs (`enc-{key}`, `cond-{key}`, ...).
+///
+/// The first visit keeps the bare patient ID so single-visit records produce
+/// the same IDs as before; later visits get a 1-based `-{n}` suffix.
+pub fn visit_key(patient_id: &str, visit_index: usize) -> String {
+    if visit_index == 0 {
+        patient_id.to_string()
+    } else {
+        format!("{}-{}", patient_id, visit_index + 1)
+    }
+}
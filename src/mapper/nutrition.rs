@@ -0,0 +1,267 @@
+use chrono::NaiveDate;
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Observation, Quantity, Reference};
+
+use crate::kenyan::schema::Vitals;
+use crate::mapper::observation::exam_category;
+use crate::register::months_between;
+use crate::systems::NUTRITION_SYSTEM;
+
+const INTERPRETATION_SYSTEM: &str =
+    "http://terminology.hl7.org/CodeSystem/v3-ObservationInterpretation";
+
+/// WHO Child Growth Standards (2006) weight-for-age LMS parameters,
+/// `(age in months, L, M, S)`. Abridged to the months listed; ages in
+/// between are interpolated linearly. Good enough to classify against the
+/// −3/−2/+2 cut-offs; growth monitoring proper needs the full table.
+const WFA_BOYS: &[(u32, f64, f64, f64)] = &[
+    (0, 0.3487, 3.3464, 0.14602),
+    (1, 0.2297, 4.4709, 0.13395),
+    (2, 0.1970, 5.5675, 0.12385),
+    (3, 0.1738, 6.3762, 0.11727),
+    (4, 0.1553, 7.0023, 0.11316),
+    (5, 0.1395, 7.5105, 0.11080),
+    (6, 0.1257, 7.9340, 0.10958),
+    (9, 0.0917, 8.9014, 0.10881),
+    (12, 0.0644, 9.6479, 0.10925),
+    (18, 0.0211, 10.9385, 0.11119),
+    (24, -0.0137, 12.1515, 0.11426),
+    (36, -0.0689, 14.3429, 0.12060),
+    (48, -0.1006, 16.3489, 0.12578),
+    (60, -0.1166, 18.3366, 0.13045),
+];
+
+const WFA_GIRLS: &[(u32, f64, f64, f64)] = &[
+    (0, 0.3809, 3.2322, 0.14171),
+    (1, 0.1714, 4.1873, 0.13724),
+    (2, 0.0962, 5.1282, 0.13000),
+    (3, 0.0402, 5.8458, 0.12619),
+    (4, -0.0050, 6.4237, 0.12402),
+    (5, -0.0430, 6.8985, 0.12274),
+    (6, -0.0756, 7.2970, 0.12204),
+    (9, -0.1506, 8.2254, 0.12157),
+    (12, -0.2024, 8.9481, 0.12268),
+    (18, -0.2637, 10.2315, 0.12632),
+    (24, -0.2941, 11.4775, 0.13022),
+    (36, -0.3275, 13.8503, 0.13840),
+    (48, -0.3518, 16.0697, 0.14468),
+    (60, -0.3833, 18.2193, 0.14911),
+];
+
+/// LMS parameters at `months`, interpolated between table rows.
+fn lms_at(table: &[(u32, f64, f64, f64)], months: u32) -> Option<(f64, f64, f64)> {
+    let upper = table.iter().position(|row| row.0 >= months)?;
+    let (m1, l1, mm1, s1) = table[upper];
+    if m1 == months || upper == 0 {
+        return Some((l1, mm1, s1));
+    }
+    let (m0, l0, mm0, s0) = table[upper - 1];
+    let t = (months - m0) as f64 / (m1 - m0) as f64;
+    let lerp = |a: f64, b: f64| a + (b - a) * t;
+    Some((lerp(l0, l1), lerp(mm0, mm1), lerp(s0, s1)))
+}
+
+/// WHO weight-for-age z-score for a child of `sex` (`M`/`F`) aged
+/// `months` (0–60), or `None` outside the table.
+///
+/// Beyond ±3 SD the WHO restricted computation is used: the distance past
+/// the 3 SD line is measured in units of the 2–3 SD interval, so extreme
+/// weights do not get inflated z-scores from the skewed distribution.
+pub fn weight_for_age_z(sex: &str, months: u32, weight_kg: f64) -> Option<f64> {
+    let table = match sex {
+        "M" => WFA_BOYS,
+        "F" => WFA_GIRLS,
+        _ => return None,
+    };
+    let (l, m, s) = lms_at(table, months)?;
+    let sd = |z: f64| m * (1.0 + l * s * z).powf(1.0 / l);
+    let z = ((weight_kg / m).powf(l) - 1.0) / (l * s);
+    Some(if z > 3.0 {
+        3.0 + (weight_kg - sd(3.0)) / (sd(3.0) - sd(2.0))
+    } else if z < -3.0 {
+        -3.0 + (weight_kg - sd(-3.0)) / (sd(-2.0) - sd(-3.0))
+    } else {
+        z
+    })
+}
+
+/// Interpretation `(v3 code, display)` plus the classification text.
+type Class = (&'static str, &'static str, &'static str);
+
+fn weight_for_age_class(z: f64) -> Class {
+    if z < -3.0 {
+        ("LL", "Critical low", "Severely underweight")
+    } else if z < -2.0 {
+        ("L", "Low", "Underweight")
+    } else if z > 2.0 {
+        ("H", "High", "Possible growth problem")
+    } else {
+        ("N", "Normal", "Normal weight for age")
+    }
+}
+
+/// WHO adult BMI categories.
+fn bmi_class(bmi: f64) -> Class {
+    if bmi < 18.5 {
+        ("L", "Low", "Underweight")
+    } else if bmi < 25.0 {
+        ("N", "Normal", "Normal weight")
+    } else if bmi < 30.0 {
+        ("H", "High", "Overweight")
+    } else {
+        ("HH", "Critical high", "Obese")
+    }
+}
+
+/// MUAC cut-offs for children 6–59 months (IMAM guideline); the code is
+/// the [`NUTRITION_SYSTEM`] classification.
+fn muac_class(muac_cm: f64) -> (Class, &'static str) {
+    if muac_cm < 11.5 {
+        (("LL", "Critical low", "Severe acute malnutrition"), "sam")
+    } else if muac_cm < 12.5 {
+        (("L", "Low", "Moderate acute malnutrition"), "mam")
+    } else {
+        (("N", "Normal", "No acute malnutrition"), "normal")
+    }
+}
+
+fn interpretation((code, display, text): Class) -> Vec<CodeableConcept> {
+    vec![CodeableConcept {
+        coding: Some(vec![Coding {
+            system: Some(INTERPRETATION_SYSTEM.to_string()),
+            code: Some(code.to_string()),
+            display: Some(display.to_string()),
+        }]),
+        text: Some(text.to_string()),
+    }]
+}
+
+/// Maps raw anthropometrics → derived nutrition Observations, each with an
+/// interpretation and `derivedFrom` pointing at the measurements used.
+///
+/// - BMI: LOINC 39156-5 whenever height is known; the WHO category as
+///   interpretation for adults (18+) only — children need BMI-for-age.
+/// - Weight-for-age z-score, under-fives of known sex (WHO tables above).
+/// - MUAC classification (SAM / MAM / normal), children 6–59 months.
+///
+/// Nothing age-dependent is emitted when the visit date does not parse.
+pub fn map_nutrition(
+    vitals: &Vitals,
+    date_of_birth: NaiveDate,
+    sex: &str,
+    patient_id: &str,
+    visit_key: &str,
+    encounter_id: &str,
+    visit_date: &str,
+) -> Vec<Observation> {
+    let months = NaiveDate::parse_from_str(visit_date, "%Y-%m-%d")
+        .ok()
+        .map(|date| months_between(date_of_birth, date));
+    let observation = |id: &str, code: Coding, text: &str, derived_from: &[&str]| Observation {
+        resource_type: "Observation".to_string(),
+        id: Some(format!("{}-{}", id, visit_key)),
+        meta: None,
+        extension: None,
+        status: "final".to_string(),
+        category: Some(exam_category()),
+        code: CodeableConcept {
+            coding: Some(vec![code]),
+            text: Some(text.to_string()),
+        },
+        subject: Some(Reference {
+            reference: Some(format!("Patient/{}", patient_id)),
+            display: None,
+        }),
+        encounter: Some(Reference {
+            reference: Some(format!("Encounter/{}", encounter_id)),
+            display: None,
+        }),
+        effective_date_time: Some(visit_date.to_string()),
+        performer: None,
+        value_quantity: None,
+        value_codeable_concept: None,
+        value_string: None,
+        interpretation: None,
+        component: None,
+        device: None,
+        derived_from: Some(
+            derived_from
+                .iter()
+                .map(|source| Reference {
+                    reference: Some(format!("Observation/{}-{}", source, visit_key)),
+                    display: None,
+                })
+                .collect(),
+        ),
+        extra: Default::default(),
+    };
+    let local = |code: &str, display: &str| Coding {
+        system: Some(NUTRITION_SYSTEM.to_string()),
+        code: Some(code.to_string()),
+        display: Some(display.to_string()),
+    };
+    let quantity = |value: f64, unit: &str| Quantity {
+        value: (value * 100.0).round() / 100.0,
+        unit: Some(unit.to_string()),
+        system: Some("http://unitsofmeasure.org".to_string()),
+    };
+
+    let mut observations = Vec::new();
+    if let Some(height) = vitals.height_cm {
+        let bmi = vitals.weight_kg / (height / 100.0).powi(2);
+        let loinc = Coding {
+            system: Some("http://loinc.org".to_string()),
+            code: Some("39156-5".to_string()),
+            display: Some("Body mass index (BMI) [Ratio]".to_string()),
+        };
+        let mut obs = observation("bmi", loinc, "BMI", &["weight", "height"]);
+        obs.value_quantity = Some(quantity(bmi, "kg/m2"));
+        if months.is_some_and(|m| m >= 18 * 12) {
+            obs.interpretation = Some(interpretation(bmi_class(bmi)));
+        }
+        observations.push(obs);
+    }
+    if let Some(z) = months
+        .filter(|m| *m <= 60)
+        .and_then(|m| weight_for_age_z(sex, m, vitals.weight_kg))
+    {
+        let code = local("weight-for-age-z", "Weight-for-age z-score");
+        let mut obs = observation("wfa", code, "Weight-for-age z-score", &["weight"]);
+        obs.value_quantity = Some(quantity(z, "{Z-score}"));
+        obs.interpretation = Some(interpretation(weight_for_age_class(z)));
+        observations.push(obs);
+    }
+    if let (Some(muac), Some(6..=59)) = (vitals.muac_cm, months) {
+        let (class, class_code) = muac_class(muac);
+        let code = local("muac-nutrition-status", "MUAC nutrition status");
+        let mut obs = observation("muac-class", code, "MUAC nutrition status", &["muac"]);
+        obs.value_codeable_concept = Some(CodeableConcept {
+            coding: Some(vec![local(class_code, class.2)]),
+            text: None,
+        });
+        obs.interpretation = Some(interpretation(class));
+        observations.push(obs);
+    }
+    observations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weight_for_age_z_uses_who_medians_and_restricted_tails() {
+        // The median weight is z = 0, at a table row and between rows
+        assert!(weight_for_age_z("M", 12, 9.6479).unwrap().abs() < 1e-9);
+        assert!(weight_for_age_z("F", 0, 3.2322).unwrap().abs() < 1e-9);
+        let between = weight_for_age_z("F", 15, (8.9481 + 10.2315) / 2.0).unwrap();
+        assert!(between.abs() < 0.01);
+
+        let wasted = weight_for_age_z("M", 24, 8.0).unwrap();
+        assert!(wasted < -3.0);
+        assert_eq!(weight_for_age_class(wasted).2, "Severely underweight");
+        assert_eq!(weight_for_age_class(-2.5).0, "L");
+
+        assert_eq!(weight_for_age_z("U", 12, 9.0), None);
+        assert_eq!(weight_for_age_z("M", 61, 18.0), None);
+    }
+}
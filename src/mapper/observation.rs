@@ -8,9 +8,7 @@ use crate::kenyan::schema::Vitals;
 fn vital_signs_category() -> Vec<CodeableConcept> {
     vec![CodeableConcept {
         coding: Some(vec![Coding {
-            system: Some(
-                "http://terminology.hl7.org/CodeSystem/observation-category".to_string(),
-            ),
+            system: Some("http://terminology.hl7.org/CodeSystem/observation-category".to_string()),
             code: Some("vital-signs".to_string()),
             display: Some("Vital Signs".to_string()),
         }]),
@@ -18,6 +16,18 @@ fn vital_signs_category() -> Vec<CodeableConcept> {
     }]
 }
 
+/// Observation category `exam` — findings that are not vital signs.
+pub fn exam_category() -> Vec<CodeableConcept> {
+    vec![CodeableConcept {
+        coding: Some(vec![Coding {
+            system: Some("http://terminology.hl7.org/CodeSystem/observation-category".to_string()),
+            code: Some("exam".to_string()),
+            display: Some("Exam".to_string()),
+        }]),
+        text: None,
+    }]
+}
+
 /// Maps Kenyan clinic vitals → FHIR R4 Observations.
 ///
 /// - Temperature: LOINC 8310-5
@@ -26,17 +36,40 @@ fn vital_signs_category() -> Vec<CodeableConcept> {
 ///   diastolic (8462-2) as `component` — per FHIR vital-signs profile.
 /// - Pulse rate: LOINC 8867-4 (optional)
 /// - O2 saturation: LOINC 59408-5 (optional)
-pub fn map_vitals(vitals: &Vitals, patient_id: &str, visit_date: &str) -> Vec<Observation> {
+/// - Height: LOINC 8302-2 (optional)
+/// - MUAC: LOINC 56072-2, category exam (optional)
+///
+/// `performer` is the Practitioner id of whoever took them.
+pub fn map_vitals(
+    vitals: &Vitals,
+    patient_id: &str,
+    visit_key: &str,
+    encounter_id: &str,
+    visit_date: &str,
+    performer: Option<&str>,
+) -> Vec<Observation> {
     let subject = Reference {
         reference: Some(format!("Patient/{}", patient_id)),
         display: None,
     };
+    let encounter = Reference {
+        reference: Some(format!("Encounter/{}", encounter_id)),
+        display: None,
+    };
+    let performer = performer.map(|id| {
+        vec![Reference {
+            reference: Some(format!("Practitioner/{}", id)),
+            display: None,
+        }]
+    });
 
     let mut observations = vec![
         // ── Temperature ──────────────────────────────────────────────────
         Observation {
             resource_type: "Observation".to_string(),
-            id: Some(format!("temp-{}", patient_id)),
+            id: Some(format!("temp-{}", visit_key)),
+            meta: None,
+            extension: None,
             status: "final".to_string(),
             category: Some(vital_signs_category()),
             code: CodeableConcept {
@@ -48,19 +81,28 @@ pub fn map_vitals(vitals: &Vitals, patient_id: &str, visit_date: &str) -> Vec<Ob
                 text: Some("Temperature".to_string()),
             },
             subject: Some(subject.clone()),
+            encounter: Some(encounter.clone()),
             effective_date_time: Some(visit_date.to_string()),
+            performer: performer.clone(),
             value_quantity: Some(Quantity {
                 value: vitals.temperature_celsius,
                 unit: Some("Cel".to_string()),
                 system: Some("http://unitsofmeasure.org".to_string()),
             }),
+            value_codeable_concept: None,
+            value_string: None,
+            interpretation: None,
             component: None,
+            device: None,
+            derived_from: None,
+            extra: Default::default(),
         },
-
         // ── Weight ───────────────────────────────────────────────────────
         Observation {
             resource_type: "Observation".to_string(),
-            id: Some(format!("weight-{}", patient_id)),
+            id: Some(format!("weight-{}", visit_key)),
+            meta: None,
+            extension: None,
             status: "final".to_string(),
             category: Some(vital_signs_category()),
             code: CodeableConcept {
@@ -72,15 +114,22 @@ pub fn map_vitals(vitals: &Vitals, patient_id: &str, visit_date: &str) -> Vec<Ob
                 text: Some("Weight".to_string()),
             },
             subject: Some(subject.clone()),
+            encounter: Some(encounter.clone()),
             effective_date_time: Some(visit_date.to_string()),
+            performer: performer.clone(),
             value_quantity: Some(Quantity {
                 value: vitals.weight_kg,
                 unit: Some("kg".to_string()),
                 system: Some("http://unitsofmeasure.org".to_string()),
             }),
+            value_codeable_concept: None,
+            value_string: None,
+            interpretation: None,
             component: None,
+            device: None,
+            derived_from: None,
+            extra: Default::default(),
         },
-
         // ── Blood Pressure panel ─────────────────────────────────────────
         // FHIR vital-signs profile requires:
         //   code = 85354-9 (Blood pressure panel)
@@ -88,7 +137,9 @@ pub fn map_vitals(vitals: &Vitals, patient_id: &str, visit_date: &str) -> Vec<Ob
         //   component[1] = 8462-2 (Diastolic)
         Observation {
             resource_type: "Observation".to_string(),
-            id: Some(format!("bp-{}", patient_id)),
+            id: Some(format!("bp-{}", visit_key)),
+            meta: None,
+            extension: None,
             status: "final".to_string(),
             category: Some(vital_signs_category()),
             code: CodeableConcept {
@@ -100,8 +151,13 @@ pub fn map_vitals(vitals: &Vitals, patient_id: &str, visit_date: &str) -> Vec<Ob
                 text: Some("Blood Pressure".to_string()),
             },
             subject: Some(subject.clone()),
+            encounter: Some(encounter.clone()),
             effective_date_time: Some(visit_date.to_string()),
+            performer: performer.clone(),
             value_quantity: None,
+            value_codeable_concept: None,
+            value_string: None,
+            interpretation: None,
             component: Some(vec![
                 ObservationComponent {
                     code: CodeableConcept {
@@ -134,6 +190,9 @@ pub fn map_vitals(vitals: &Vitals, patient_id: &str, visit_date: &str) -> Vec<Ob
                     }),
                 },
             ]),
+            device: None,
+            derived_from: None,
+            extra: Default::default(),
         },
     ];
 
@@ -141,7 +200,9 @@ pub fn map_vitals(vitals: &Vitals, patient_id: &str, visit_date: &str) -> Vec<Ob
     if let Some(pulse) = vitals.pulse_rate {
         observations.push(Observation {
             resource_type: "Observation".to_string(),
-            id: Some(format!("pulse-{}", patient_id)),
+            id: Some(format!("pulse-{}", visit_key)),
+            meta: None,
+            extension: None,
             status: "final".to_string(),
             category: Some(vital_signs_category()),
             code: CodeableConcept {
@@ -153,13 +214,21 @@ pub fn map_vitals(vitals: &Vitals, patient_id: &str, visit_date: &str) -> Vec<Ob
                 text: Some("Pulse Rate".to_string()),
             },
             subject: Some(subject.clone()),
+            encounter: Some(encounter.clone()),
             effective_date_time: Some(visit_date.to_string()),
+            performer: performer.clone(),
             value_quantity: Some(Quantity {
                 value: pulse as f64,
                 unit: Some("/min".to_string()),
                 system: Some("http://unitsofmeasure.org".to_string()),
             }),
+            value_codeable_concept: None,
+            value_string: None,
+            interpretation: None,
             component: None,
+            device: None,
+            derived_from: None,
+            extra: Default::default(),
         });
     }
 
@@ -167,7 +236,9 @@ pub fn map_vitals(vitals: &Vitals, patient_id: &str, visit_date: &str) -> Vec<Ob
     if let Some(spo2) = vitals.o2_saturation {
         observations.push(Observation {
             resource_type: "Observation".to_string(),
-            id: Some(format!("spo2-{}", patient_id)),
+            id: Some(format!("spo2-{}", visit_key)),
+            meta: None,
+            extension: None,
             status: "final".to_string(),
             category: Some(vital_signs_category()),
             code: CodeableConcept {
@@ -180,14 +251,94 @@ pub fn map_vitals(vitals: &Vitals, patient_id: &str, visit_date: &str) -> Vec<Ob
                 }]),
                 text: Some("O2 Saturation".to_string()),
             },
-            subject: Some(subject),
+            subject: Some(subject.clone()),
+            encounter: Some(encounter.clone()),
             effective_date_time: Some(visit_date.to_string()),
+            performer: performer.clone(),
             value_quantity: Some(Quantity {
                 value: spo2,
                 unit: Some("%".to_string()),
                 system: Some("http://unitsofmeasure.org".to_string()),
             }),
+            value_codeable_concept: None,
+            value_string: None,
+            interpretation: None,
+            component: None,
+            device: None,
+            derived_from: None,
+            extra: Default::default(),
+        });
+    }
+
+    // ── Height (optional) ─────────────────────────────────────────────────
+    if let Some(height) = vitals.height_cm {
+        observations.push(Observation {
+            resource_type: "Observation".to_string(),
+            id: Some(format!("height-{}", visit_key)),
+            meta: None,
+            extension: None,
+            status: "final".to_string(),
+            category: Some(vital_signs_category()),
+            code: CodeableConcept {
+                coding: Some(vec![Coding {
+                    system: Some("http://loinc.org".to_string()),
+                    code: Some("8302-2".to_string()),
+                    display: Some("Body height".to_string()),
+                }]),
+                text: Some("Height".to_string()),
+            },
+            subject: Some(subject.clone()),
+            encounter: Some(encounter.clone()),
+            effective_date_time: Some(visit_date.to_string()),
+            performer: performer.clone(),
+            value_quantity: Some(Quantity {
+                value: height,
+                unit: Some("cm".to_string()),
+                system: Some("http://unitsofmeasure.org".to_string()),
+            }),
+            value_codeable_concept: None,
+            value_string: None,
+            interpretation: None,
+            component: None,
+            device: None,
+            derived_from: None,
+            extra: Default::default(),
+        });
+    }
+
+    // ── MUAC (optional) ───────────────────────────────────────────────────
+    if let Some(muac) = vitals.muac_cm {
+        observations.push(Observation {
+            resource_type: "Observation".to_string(),
+            id: Some(format!("muac-{}", visit_key)),
+            meta: None,
+            extension: None,
+            status: "final".to_string(),
+            category: Some(exam_category()),
+            code: CodeableConcept {
+                coding: Some(vec![Coding {
+                    system: Some("http://loinc.org".to_string()),
+                    code: Some("56072-2".to_string()),
+                    display: Some("Circumference Mid upper arm".to_string()),
+                }]),
+                text: Some("MUAC".to_string()),
+            },
+            subject: Some(subject),
+            encounter: Some(encounter),
+            effective_date_time: Some(visit_date.to_string()),
+            performer: performer.clone(),
+            value_quantity: Some(Quantity {
+                value: muac,
+                unit: Some("cm".to_string()),
+                system: Some("http://unitsofmeasure.org".to_string()),
+            }),
+            value_codeable_concept: None,
+            value_string: None,
+            interpretation: None,
             component: None,
+            device: None,
+            derived_from: None,
+            extra: Default::default(),
         });
     }
 
@@ -1,21 +1,170 @@
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Reference};
 use fhir_parser::fhir::organization::Organization;
-use fhir_parser::fhir::patient::Identifier;
+use fhir_parser::fhir::patient::{Address, ContactPoint, Identifier};
 
+use crate::facility::FacilityContact;
+use crate::kenyan::counties::{county_code, COUNTIES};
 use crate::kenyan::schema::KenyanPatient;
+use crate::mapper::patient::kenyan_address;
+use crate::systems::{COUNTY_CODE_SYSTEM, FACILITY_SYSTEM, SUBCOUNTY_SYSTEM};
 
 /// Maps clinic_id → FHIR R4 Organization with a Kenya DHA Facility Registry (FID) identifier.
 ///
 /// System URI per DHA Digital Health Regulations 2025 — the old MFL URI
 /// (kmhfl.health.go.ke) is superseded by the new Facility Registry.
-pub fn map_organization(kenyan: &KenyanPatient) -> Organization {
+/// The facility is partOf its sub-county health office, or the county
+/// department of health when the sub-county is not known
+/// (see [`map_parent_organizations`]).
+///
+/// Telecom and address, which the SHR requires on the serviceProvider,
+/// come from `contact` (see [`crate::facility`]).
+pub fn map_organization(kenyan: &KenyanPatient, contact: Option<&FacilityContact>) -> Organization {
     Organization {
         resource_type: "Organization".to_string(),
         id: Some(format!("org-{}", kenyan.clinic_id.replace('/', "-"))),
+        meta: None,
+        extension: None,
         identifier: Some(vec![Identifier {
-            system: Some("http://facility-registry.dha.go.ke/fhir/Location".to_string()),
+            system: Some(FACILITY_SYSTEM.to_string()),
             value: kenyan.clinic_id.clone(),
         }]),
         name: Some(kenyan.clinic_id.clone()),
         active: Some(true),
+        type_field: None,
+        telecom: contact.map(facility_telecom).filter(|t| !t.is_empty()),
+        address: contact.and_then(facility_address).map(|a| vec![a]),
+        part_of: map_parent_organizations(kenyan)
+            .first()
+            .and_then(|parent| parent.id.as_deref())
+            .map(organization_reference),
+        extra: Default::default(),
+    }
+}
+
+/// The sub-county health office and county department of health above the
+/// facility, nearest first, with partOf chaining them: `org-county-{code}`
+/// and `org-subcounty-{code}-{sub-county}`.
+///
+/// Only official KNBS names are used, so a misspelt location does not
+/// create a new county on the SHR: none for a county off the list, only
+/// the county for a sub-county not listed under it.
+pub fn map_parent_organizations(kenyan: &KenyanPatient) -> Vec<Organization> {
+    let county = kenyan.location.county.as_str();
+    let Some(code) = county_code(county) else {
+        return Vec::new();
+    };
+    let county_id = format!("org-county-{}", code);
+    let county_org = government_organization(
+        county_id.clone(),
+        Identifier {
+            system: Some(COUNTY_CODE_SYSTEM.to_string()),
+            value: code.to_string(),
+        },
+        format!("{} County Department of Health", county),
+        None,
+    );
+
+    let subcounty = kenyan.location.subcounty.as_str();
+    let listed = COUNTIES
+        .iter()
+        .any(|(c, _, subs)| *c == code && subs.contains(&subcounty));
+    if !listed {
+        return vec![county_org];
     }
+    let subcounty_org = government_organization(
+        format!("org-subcounty-{}-{}", code, slug(subcounty)),
+        Identifier {
+            system: Some(SUBCOUNTY_SYSTEM.to_string()),
+            value: format!("{}/{}", code, subcounty),
+        },
+        format!("{} Sub-County Health Office", subcounty),
+        Some(&county_id),
+    );
+    vec![subcounty_org, county_org]
+}
+
+fn government_organization(
+    id: String,
+    identifier: Identifier,
+    name: String,
+    part_of: Option<&str>,
+) -> Organization {
+    Organization {
+        resource_type: "Organization".to_string(),
+        id: Some(id),
+        meta: None,
+        extension: None,
+        identifier: Some(vec![identifier]),
+        name: Some(name),
+        active: Some(true),
+        type_field: Some(vec![CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some("http://terminology.hl7.org/CodeSystem/organization-type".to_string()),
+                code: Some("govt".to_string()),
+                display: Some("Government".to_string()),
+            }]),
+            text: None,
+        }]),
+        telecom: None,
+        address: None,
+        part_of: part_of.map(organization_reference),
+        extra: Default::default(),
+    }
+}
+
+fn facility_telecom(contact: &FacilityContact) -> Vec<ContactPoint> {
+    [("phone", &contact.phone), ("email", &contact.email)]
+        .into_iter()
+        .filter_map(|(system, value)| {
+            Some(ContactPoint {
+                system: Some(system.to_string()),
+                value: value.clone()?,
+                use_field: Some("work".to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Shaped like the Patient address, with the street address and town
+/// added; none when the contact has no address part at all.
+fn facility_address(contact: &FacilityContact) -> Option<Address> {
+    if contact.county.is_none() && contact.address.is_none() && contact.town.is_none() {
+        return None;
+    }
+    let mut address = match &contact.county {
+        Some(county) => kenyan_address(county, contact.subcounty.as_deref().unwrap_or_default()),
+        None => Address {
+            extension: None,
+            line: None,
+            city: None,
+            district: None,
+            state: None,
+            country: Some("KE".to_string()),
+        },
+    };
+    let line: Vec<String> = contact
+        .address
+        .iter()
+        .chain(contact.subcounty.iter())
+        .cloned()
+        .collect();
+    address.line = (!line.is_empty()).then_some(line);
+    address.city = contact.town.clone();
+    Some(address)
+}
+
+fn organization_reference(id: &str) -> Reference {
+    Reference {
+        reference: Some(format!("Organization/{}", id)),
+        display: None,
+    }
+}
+
+/// `Lunga Lunga` → `lunga-lunga`
+fn slug(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
 }
@@ -1,15 +1,28 @@
 use chrono::NaiveDate;
 use uuid::Uuid;
 
-use fhir_parser::fhir::patient::{Address, ContactPoint, HumanName, Identifier, Patient};
+use fhir_parser::fhir::meta::Meta;
+use fhir_parser::fhir::observation::{Coding, Reference};
+use fhir_parser::fhir::patient::{
+    Address, ContactPoint, Extension, HumanName, Identifier, Patient, PatientLink, PrimitiveElement,
+};
 
-use crate::cr_lookup::resolve_cr_id;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::cr_lookup::{resolve_cr_id, synthetic_cr_id};
+use crate::kenyan::counties::county_code;
 use crate::kenyan::schema::KenyanPatient;
+use crate::mapper::document_reference::inline_attachment;
+use crate::systems::{
+    patient_number_system, BIRTH_DATE_ESTIMATED_URL, COUNTY_CODE_SYSTEM, COUNTY_EXTENSION_URL,
+    CR_SYSTEM, DATA_QUALITY_SYSTEM, NATIONAL_ID_SYSTEM,
+};
 
 /// DNS namespace UUID for Kenya FHIR Bridge patient IDs.
 /// A private fixed UUID used as the namespace for UUID v5 derivation.
-const KENYA_PATIENT_NAMESPACE: Uuid =
-    uuid::uuid!("6ba7b810-9dad-11d1-80b4-00c04fd430c9"); // UUID DNS namespace
+const KENYA_PATIENT_NAMESPACE: Uuid = uuid::uuid!("6ba7b810-9dad-11d1-80b4-00c04fd430c9"); // UUID DNS namespace
+
+/// [`DATA_QUALITY_SYSTEM`] code of a CR patient linked rather than adopted.
+pub const CR_MISMATCH_CODE: &str = "cr-demographics-mismatch";
 
 /// Derive a stable UUID v5 from clinic_id + patient_number.
 /// This is deterministic (same input always produces same UUID) and spec-compliant.
@@ -18,34 +31,87 @@ pub fn patient_uuid(clinic_id: &str, patient_number: &str) -> String {
     Uuid::new_v5(&KENYA_PATIENT_NAMESPACE, name.as_bytes()).to_string()
 }
 
-pub fn map_patient(kenyan: &KenyanPatient) -> Patient {
+/// `cr_lookup`: try the live Client Registry first, unless its breaker is
+/// open; without it the CR ID is always the synthetic one (dry runs). A CR
+/// patient that does not match is noted in `data_quality`.
+pub fn map_patient(
+    kenyan: &KenyanPatient,
+    cr_lookup: Option<&CircuitBreaker>,
+    data_quality: &mut Vec<String>,
+) -> Patient {
     let id = patient_uuid(&kenyan.clinic_id, &kenyan.patient_number);
 
     // CR lookup: try live AfyaLink UAT, fall back to deterministic synthetic ID
-    let cr = resolve_cr_id(&kenyan.national_id);
+    let (mut cr_id, registered) = match cr_lookup {
+        Some(breaker) => {
+            let found = resolve_cr_id(&kenyan.national_id, breaker);
+            (found.cr_id, found.registered)
+        }
+        None => (synthetic_cr_id(&kenyan.national_id), None),
+    };
+
+    // A CR patient with another name or date of birth may be someone else
+    // registered under this national ID: keep the synthetic ID, point at the
+    // CR record for a person to review, and flag the Patient.
+    let mut link = None;
+    let mut meta = None;
+    let differs = registered
+        .as_ref()
+        .map(|registered| {
+            let names = [
+                &kenyan.names.first,
+                &kenyan.names.middle,
+                &kenyan.names.last,
+            ];
+            registered.differences(
+                &names.map(String::as_str),
+                kenyan.date_of_birth,
+                kenyan.birth_date_estimated,
+            )
+        })
+        .unwrap_or_default();
+    if !differs.is_empty() {
+        data_quality.push(format!(
+            "Client Registry patient differs in {}; linked, not adopted",
+            differs.join(" and ")
+        ));
+        link = Some(vec![PatientLink {
+            other: Reference {
+                reference: Some(format!("Patient?identifier={}|{}", CR_SYSTEM, cr_id)),
+                display: None,
+            },
+            type_field: "seealso".to_string(),
+        }]);
+        meta = Some(Meta {
+            tag: Some(vec![Coding {
+                system: Some(DATA_QUALITY_SYSTEM.to_string()),
+                code: Some(CR_MISMATCH_CODE.to_string()),
+                display: Some("Demographics differ from the Client Registry".to_string()),
+            }]),
+            ..Meta::default()
+        });
+        cr_id = synthetic_cr_id(&kenyan.national_id);
+    }
 
     Patient {
         resource_type: "Patient".to_string(),
         id: Some(id),
+        meta,
+        extension: None,
         identifier: Some(vec![
             // Primary: Client Registry ID (Maisha Namba / UPI)
             // Live when AFYALINK_TOKEN is set, synthetic otherwise
             Identifier {
-                system: Some("http://cr.dha.go.ke/fhir/Patient".to_string()),
-                value: cr.cr_id,
+                system: Some(CR_SYSTEM.to_string()),
+                value: cr_id,
             },
             // National ID (secondary — retained for backward compat)
             Identifier {
-                system: Some(
-                    "https://digitalhealth.go.ke/identifier/national-id".to_string(),
-                ),
+                system: Some(NATIONAL_ID_SYSTEM.to_string()),
                 value: kenyan.national_id.clone(),
             },
             Identifier {
-                system: Some(format!(
-                    "http://facility-registry.dha.go.ke/fhir/Location/{}/patient-number",
-                    kenyan.clinic_id
-                )),
+                system: Some(patient_number_system(&kenyan.clinic_id)),
                 value: kenyan.patient_number.clone(),
             },
         ]),
@@ -55,7 +121,10 @@ pub fn map_patient(kenyan: &KenyanPatient) -> Patient {
             given: if kenyan.names.middle.is_empty() {
                 Some(vec![kenyan.names.first.clone()])
             } else {
-                Some(vec![kenyan.names.first.clone(), kenyan.names.middle.clone()])
+                Some(vec![
+                    kenyan.names.first.clone(),
+                    kenyan.names.middle.clone(),
+                ])
             },
         }]),
         telecom: if kenyan.phone.is_empty() {
@@ -67,26 +136,55 @@ pub fn map_patient(kenyan: &KenyanPatient) -> Patient {
                 use_field: Some("mobile".to_string()),
             }])
         },
-        gender: Some(match kenyan.gender.as_str() {
-            "M" => "male",
-            "F" => "female",
-            _ => "unknown",
-        }
-        .to_string()),
+        gender: Some(
+            match kenyan.gender.as_str() {
+                "M" => "male",
+                "F" => "female",
+                "O" => "other",
+                _ => "unknown",
+            }
+            .to_string(),
+        ),
         birth_date: Some(kenyan.date_of_birth),
-        // Kenya: county is the administrative district level (Address.district per FHIR R4)
-        // subcounty goes in Address.line
-        address: Some(vec![Address {
-            line: Some(vec![kenyan.location.subcounty.clone()]),
-            city: None,
-            district: Some(kenyan.location.county.clone()),
-            state: None,
-            country: Some("KE".to_string()),
-        }]),
+        birth_date_element: kenyan.birth_date_estimated.then(|| PrimitiveElement {
+            extension: vec![Extension::boolean(BIRTH_DATE_ESTIMATED_URL, true)],
+        }),
+        address: Some(vec![kenyan_address(
+            &kenyan.location.county,
+            &kenyan.location.subcounty,
+        )]),
+        photo: kenyan
+            .photo
+            .as_ref()
+            .map(|photo| vec![inline_attachment(photo)]),
+        link,
+        extra: Default::default(),
+    }
+}
+
+/// Kenya: county is the administrative district level (Address.district per
+/// FHIR R4), with its KNBS code in an extension; subcounty goes in
+/// Address.line.
+pub fn kenyan_address(county: &str, subcounty: &str) -> Address {
+    Address {
+        extension: county_code(county).map(|code| {
+            vec![Extension::coding(
+                COUNTY_EXTENSION_URL,
+                Coding {
+                    system: Some(COUNTY_CODE_SYSTEM.to_string()),
+                    code: Some(code.to_string()),
+                    display: Some(county.to_string()),
+                },
+            )]
+        }),
+        line: Some(vec![subcounty.to_string()]),
+        city: None,
+        district: Some(county.to_string()),
+        state: None,
+        country: Some("KE".to_string()),
     }
 }
 
 pub fn parse_date(date: &str) -> NaiveDate {
     NaiveDate::parse_from_str(date, "%Y-%m-%d").expect("invalid date format")
 }
-
@@ -1,5 +1,12 @@
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Reference};
 use fhir_parser::fhir::patient::Identifier;
 use fhir_parser::fhir::practitioner::Practitioner;
+use fhir_parser::fhir::practitioner_role::PractitionerRole;
+
+use crate::kenyan::schema::Cadre;
+use crate::systems::{CADRE_SYSTEM, HWR_SYSTEM};
+
+const SNOMED_SYSTEM: &str = "http://snomed.info/sct";
 
 /// Maps a Health Worker Registry PUID → FHIR R4 Practitioner.
 ///
@@ -8,12 +15,71 @@ use fhir_parser::fhir::practitioner::Practitioner;
 pub fn map_practitioner(puid: &str) -> Practitioner {
     Practitioner {
         resource_type: "Practitioner".to_string(),
-        id: Some(format!("prac-{}", puid.replace('/', "-"))),
+        id: Some(practitioner_id(puid)),
+        meta: None,
+        extension: None,
         identifier: Some(vec![Identifier {
-            system: Some("http://hwr.dha.go.ke/fhir/Practitioner".to_string()),
+            system: Some(HWR_SYSTEM.to_string()),
             value: puid.to_string(),
         }]),
         name: None,
         gender: None,
+        extra: Default::default(),
+    }
+}
+
+fn practitioner_id(puid: &str) -> String {
+    format!("prac-{}", puid.replace('/', "-"))
+}
+
+/// Maps the attending clinician's cadre → FHIR R4 PractitionerRole at the
+/// facility, `role-{puid}-{org_id}`: one role per clinician and facility.
+///
+/// `code` carries the cadre, plus the SNOMED_SYSTEM CT occupation where one exists
+/// (there is none for clinical officers). Visits here are outpatient, so
+/// the specialty is general practice.
+pub fn map_practitioner_role(puid: &str, cadre: Cadre, org_id: &str) -> PractitionerRole {
+    let mut coding = vec![Coding {
+        system: Some(CADRE_SYSTEM.to_string()),
+        code: Some(cadre.code().to_string()),
+        display: Some(cadre.display().to_string()),
+    }];
+    let occupation = match cadre {
+        Cadre::Nurse => Some(("106292003", "Professional nurse")),
+        Cadre::MedicalOfficer => Some(("158965000", "Medical practitioner")),
+        Cadre::ClinicalOfficer => None,
+    };
+    coding.extend(occupation.map(|(code, display)| Coding {
+        system: Some(SNOMED_SYSTEM.to_string()),
+        code: Some(code.to_string()),
+        display: Some(display.to_string()),
+    }));
+
+    PractitionerRole {
+        resource_type: "PractitionerRole".to_string(),
+        id: Some(format!("role-{}-{}", puid.replace('/', "-"), org_id)),
+        meta: None,
+        active: Some(true),
+        practitioner: Some(Reference {
+            reference: Some(format!("Practitioner/{}", practitioner_id(puid))),
+            display: None,
+        }),
+        organization: Some(Reference {
+            reference: Some(format!("Organization/{}", org_id)),
+            display: None,
+        }),
+        code: Some(vec![CodeableConcept {
+            coding: Some(coding),
+            text: None,
+        }]),
+        specialty: Some(vec![CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some(SNOMED_SYSTEM.to_string()),
+                code: Some("394814009".to_string()),
+                display: Some("General practice".to_string()),
+            }]),
+            text: None,
+        }]),
+        extra: Default::default(),
     }
 }
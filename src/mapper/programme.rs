@@ -0,0 +1,228 @@
+use fhir_parser::fhir::care_plan::{CarePlan, CarePlanActivity, CarePlanActivityDetail};
+use fhir_parser::fhir::condition::ConditionStage;
+use fhir_parser::fhir::encounter::Period;
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Reference};
+
+use crate::kenyan::schema::{CareProgramme, Visit};
+use crate::mapper::condition::diagnosis_coding;
+use crate::systems::{CARE_PROGRAMME_SYSTEM, HIV_WHO_STAGE_SYSTEM, TB_PHASE_SYSTEM};
+
+/// National programme whose reporting requirements a run follows
+/// (`--profile`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgrammeProfile {
+    Tb,
+    Hiv,
+}
+
+impl ProgrammeProfile {
+    pub fn programme(self) -> CareProgramme {
+        match self {
+            Self::Tb => CareProgramme::Tb,
+            Self::Hiv => CareProgramme::Hiv,
+        }
+    }
+
+    /// Whether the visit's diagnosis falls under this programme, by the
+    /// crosswalk's ICD-10 code (A15.9 TB, B24 HIV).
+    pub fn applies_to(self, visit: &Visit) -> bool {
+        let code = diagnosis_coding(&visit.diagnosis).map(|dx| dx.icd10_code);
+        match self {
+            Self::Tb => code == Some("A15.9"),
+            Self::Hiv => code == Some("B24"),
+        }
+    }
+}
+
+/// Condition.stage for a programme diagnosis: WHO clinical stage for HIV,
+/// treatment phase for TB. `None` when the visit does not carry it.
+pub fn condition_stage(profile: ProgrammeProfile, visit: &Visit) -> Option<ConditionStage> {
+    let (system, code, display, kind) = match profile {
+        ProgrammeProfile::Hiv => {
+            let stage = visit.hiv_who_stage?;
+            (
+                HIV_WHO_STAGE_SYSTEM,
+                stage.to_string(),
+                format!("WHO clinical stage {}", stage),
+                "WHO HIV clinical staging",
+            )
+        }
+        ProgrammeProfile::Tb => {
+            let phase = visit.tb_phase?;
+            (
+                TB_PHASE_SYSTEM,
+                phase.code().to_string(),
+                phase.display().to_string(),
+                "TB treatment phase",
+            )
+        }
+    };
+    Some(ConditionStage {
+        summary: Some(CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some(system.to_string()),
+                code: Some(code),
+                display: Some(display),
+            }]),
+            text: None,
+        }),
+        type_field: Some(CodeableConcept {
+            coding: None,
+            text: Some(kind.to_string()),
+        }),
+    })
+}
+
+/// Maps the programme regimen → FHIR R4 CarePlan addressing the visit's
+/// Condition. The regimen falls back to the visit's treatment text.
+pub fn map_care_plan(
+    profile: ProgrammeProfile,
+    visit: &Visit,
+    patient_id: &str,
+    visit_key: &str,
+    encounter_id: &str,
+    condition_id: &str,
+) -> CarePlan {
+    let programme = profile.programme();
+    CarePlan {
+        resource_type: "CarePlan".to_string(),
+        id: Some(format!("cp-{}", visit_key)),
+        meta: None,
+        status: "active".to_string(),
+        intent: "plan".to_string(),
+        category: Some(vec![CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some(CARE_PROGRAMME_SYSTEM.to_string()),
+                code: Some(programme.code().to_string()),
+                display: Some(programme.display().to_string()),
+            }]),
+            text: None,
+        }]),
+        title: Some(
+            match profile {
+                ProgrammeProfile::Tb => "TB treatment regimen",
+                ProgrammeProfile::Hiv => "ART regimen",
+            }
+            .to_string(),
+        ),
+        description: Some(
+            visit
+                .regimen
+                .clone()
+                .unwrap_or_else(|| visit.treatment.clone()),
+        ),
+        subject: Reference {
+            reference: Some(format!("Patient/{}", patient_id)),
+            display: None,
+        },
+        encounter: Some(Reference {
+            reference: Some(format!("Encounter/{}", encounter_id)),
+            display: None,
+        }),
+        period: None,
+        addresses: Some(vec![Reference {
+            reference: Some(format!("Condition/{}", condition_id)),
+            display: None,
+        }]),
+        activity: None,
+        extra: Default::default(),
+    }
+}
+
+/// SNOMED CT "Follow-up encounter", the review visit of an NCD CarePlan.
+pub const FOLLOW_UP_SNOMED: &str = "390906007";
+
+/// Title of the CarePlan for a chronic NCD diagnosis (crosswalk ICD-10
+/// I10 hypertension, E11.9 type 2 diabetes), `None` for any other.
+fn ncd_plan_title(visit: &Visit) -> Option<&'static str> {
+    match diagnosis_coding(&visit.diagnosis)?.icd10_code {
+        "I10" => Some("Hypertension care plan"),
+        "E11.9" => Some("Diabetes care plan"),
+        _ => None,
+    }
+}
+
+/// Maps a hypertension or diabetes visit with a `review_date` → FHIR R4
+/// CarePlan (`cp-{key}`): the visit's MedicationRequest as one activity and
+/// the review as a scheduled follow-up; the plan runs until the review.
+/// `None` for other diagnoses or without a review date.
+pub fn map_ncd_care_plan(
+    visit: &Visit,
+    patient_id: &str,
+    visit_key: &str,
+    encounter_id: &str,
+    condition_id: &str,
+    medication_request_id: &str,
+) -> Option<CarePlan> {
+    let title = ncd_plan_title(visit)?;
+    let review = visit.review_date?.to_string();
+    let programme = CareProgramme::Ncd;
+    Some(CarePlan {
+        resource_type: "CarePlan".to_string(),
+        id: Some(format!("cp-{}", visit_key)),
+        meta: None,
+        status: "active".to_string(),
+        intent: "plan".to_string(),
+        category: Some(vec![CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some(CARE_PROGRAMME_SYSTEM.to_string()),
+                code: Some(programme.code().to_string()),
+                display: Some(programme.display().to_string()),
+            }]),
+            text: None,
+        }]),
+        title: Some(title.to_string()),
+        description: Some(
+            visit
+                .regimen
+                .clone()
+                .unwrap_or_else(|| visit.treatment.clone()),
+        ),
+        subject: Reference {
+            reference: Some(format!("Patient/{}", patient_id)),
+            display: None,
+        },
+        encounter: Some(Reference {
+            reference: Some(format!("Encounter/{}", encounter_id)),
+            display: None,
+        }),
+        period: Some(Period {
+            start: Some(visit.date.clone()),
+            end: Some(review.clone()),
+        }),
+        addresses: Some(vec![Reference {
+            reference: Some(format!("Condition/{}", condition_id)),
+            display: None,
+        }]),
+        activity: Some(vec![
+            CarePlanActivity {
+                reference: Some(Reference {
+                    reference: Some(format!("MedicationRequest/{}", medication_request_id)),
+                    display: None,
+                }),
+                detail: None,
+            },
+            CarePlanActivity {
+                reference: None,
+                detail: Some(CarePlanActivityDetail {
+                    kind: Some("Appointment".to_string()),
+                    code: Some(CodeableConcept {
+                        coding: Some(vec![Coding {
+                            system: Some("http://snomed.info/sct".to_string()),
+                            code: Some(FOLLOW_UP_SNOMED.to_string()),
+                            display: Some("Follow-up encounter".to_string()),
+                        }]),
+                        text: None,
+                    }),
+                    status: "scheduled".to_string(),
+                    scheduled_period: Some(Period {
+                        start: Some(review),
+                        end: None,
+                    }),
+                    description: Some("Clinic review".to_string()),
+                }),
+            },
+        ]),
+        extra: Default::default(),
+    })
+}
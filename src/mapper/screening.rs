@@ -0,0 +1,111 @@
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Observation, Reference};
+
+use crate::kenyan::schema::{ScreeningFindings, ScreeningResult};
+use crate::mapper::observation::exam_category;
+use crate::systems::SCREENING_RESULT_SYSTEM;
+
+const INTERPRETATION_SYSTEM: &str =
+    "http://terminology.hl7.org/CodeSystem/v3-ObservationInterpretation";
+
+/// LOINC codes of the screening observations, read back by `bundle to-kenyan`.
+pub const VISUAL_ACUITY_LEFT_LOINC: &str = "79880-1";
+pub const VISUAL_ACUITY_RIGHT_LOINC: &str = "79882-7";
+pub const HEARING_SCREEN_LOINC: &str = "46216-8";
+
+fn coding(system: &str, code: &str, display: &str) -> Coding {
+    Coding {
+        system: Some(system.to_string()),
+        code: Some(code.to_string()),
+        display: Some(display.to_string()),
+    }
+}
+
+/// Maps school-health screening → FHIR R4 Observations.
+///
+/// - Visual acuity, uncorrected, by Snellen chart: LOINC 79880-1 (left
+///   eye, `va-left-{key}`) and 79882-7 (right eye, `va-right-{key}`), the
+///   fraction as valueString
+/// - Hearing screen: LOINC 46216-8 (`hearing-{key}`), pass or fail, with
+///   a fail interpreted as abnormal so it shows up for referral
+pub fn map_screening(
+    screening: &ScreeningFindings,
+    patient_id: &str,
+    visit_key: &str,
+    encounter_id: &str,
+    visit_date: &str,
+) -> Vec<Observation> {
+    let observation = |id: &str, code: &str, display: &str| Observation {
+        resource_type: "Observation".to_string(),
+        id: Some(format!("{}-{}", id, visit_key)),
+        meta: None,
+        extension: None,
+        status: "final".to_string(),
+        category: Some(exam_category()),
+        code: CodeableConcept {
+            coding: Some(vec![coding("http://loinc.org", code, display)]),
+            text: Some(display.to_string()),
+        },
+        subject: Some(Reference {
+            reference: Some(format!("Patient/{}", patient_id)),
+            display: None,
+        }),
+        encounter: Some(Reference {
+            reference: Some(format!("Encounter/{}", encounter_id)),
+            display: None,
+        }),
+        effective_date_time: Some(visit_date.to_string()),
+        performer: None,
+        value_quantity: None,
+        value_codeable_concept: None,
+        value_string: None,
+        interpretation: None,
+        component: None,
+        device: None,
+        derived_from: None,
+        extra: Default::default(),
+    };
+
+    let mut observations = Vec::new();
+    let eyes = [
+        (
+            "va-left",
+            VISUAL_ACUITY_LEFT_LOINC,
+            "Visual acuity uncorrected Left eye by Snellen eye chart",
+            &screening.visual_acuity_left,
+        ),
+        (
+            "va-right",
+            VISUAL_ACUITY_RIGHT_LOINC,
+            "Visual acuity uncorrected Right eye by Snellen eye chart",
+            &screening.visual_acuity_right,
+        ),
+    ];
+    for (id, code, display, acuity) in eyes {
+        if let Some(acuity) = acuity {
+            let mut obs = observation(id, code, display);
+            obs.value_string = Some(acuity.clone());
+            observations.push(obs);
+        }
+    }
+    if let Some(result) = screening.hearing {
+        let mut obs = observation("hearing", HEARING_SCREEN_LOINC, "Hearing screen");
+        obs.value_codeable_concept = Some(CodeableConcept {
+            coding: Some(vec![coding(
+                SCREENING_RESULT_SYSTEM,
+                result.code(),
+                result.display(),
+            )]),
+            text: None,
+        });
+        let (code, display) = match result {
+            ScreeningResult::Pass => ("N", "Normal"),
+            ScreeningResult::Fail => ("A", "Abnormal"),
+        };
+        obs.interpretation = Some(vec![CodeableConcept {
+            coding: Some(vec![coding(INTERPRETATION_SYSTEM, code, display)]),
+            text: None,
+        }]);
+        observations.push(obs);
+    }
+    observations
+}
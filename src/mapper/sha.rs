@@ -1,7 +1,29 @@
-use fhir_parser::fhir::claim::{build_claim, build_coverage, sha_payer_org, Claim, ShaPayerOrganization};
+use fhir_parser::fhir::claim::{
+    build_claim, build_coverage, sha_payer_org, Claim, ClaimSupportingInfo, Money,
+    ShaPayerOrganization,
+};
+use fhir_parser::fhir::condition::Condition;
 use fhir_parser::fhir::coverage::Coverage;
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Observation, Reference};
 
-use crate::kenyan::schema::KenyanPatient;
+use crate::kenyan::schema::Visit;
+
+/// SHA benefit-package tariff: (intervention code, KES per unit of service).
+/// Keep in step with the tariffs SHA publishes; a code missing here yields a
+/// Claim without amounts, which the bundle lint warns about.
+const SHA_TARIFF: &[(&str, f64)] = &[
+    ("SHA-OPD-001", 500.0),
+    ("SHA-ANC-001", 500.0),
+    ("SHA-MAT-001", 10_200.0),
+];
+
+/// Tariff amount in KES for an SHA intervention code.
+pub fn tariff_kes(intervention_code: &str) -> Option<f64> {
+    SHA_TARIFF
+        .iter()
+        .find(|(code, _)| *code == intervention_code)
+        .map(|(_, kes)| *kes)
+}
 
 pub struct ShaClaims {
     pub payer_org: ShaPayerOrganization,
@@ -13,32 +35,104 @@ pub struct ShaClaims {
 ///
 /// Returns None if sha_member_number is not set on the visit (cash/non-SHA visit).
 /// The ICD-11 condition code is pulled from the condition mapper's crosswalk if available.
+/// Coverage is per patient; the Claim is per visit (`claim-{visit_key}`).
 pub fn map_sha_claims(
-    kenyan: &KenyanPatient,
+    visit: &Visit,
     patient_id: &str,
+    visit_key: &str,
     encounter_id: &str,
     facility_org_id: &str,
     icd11_code: Option<&str>,
     icd11_display: Option<&str>,
 ) -> Option<ShaClaims> {
-    let member_number = kenyan.visit.sha_member_number.as_deref()?;
-    let intervention_code = kenyan
-        .visit
+    let member_number = visit.sha_member_number.as_deref()?;
+    let intervention_code = visit
         .sha_intervention_code
         .as_deref()
         .unwrap_or("SHA-OPD-001"); // default OPD code when not specified
 
+    let mut claim = build_claim(
+        patient_id,
+        facility_org_id,
+        encounter_id,
+        &visit.date,
+        intervention_code,
+        icd11_code,
+        icd11_display,
+    );
+    claim.id = Some(format!("claim-{}", visit_key));
+    price_items(&mut claim);
+
     Some(ShaClaims {
         payer_org: sha_payer_org(),
         coverage: build_coverage(patient_id, member_number),
-        claim: build_claim(
-            patient_id,
-            facility_org_id,
-            encounter_id,
-            &kenyan.visit.date,
-            intervention_code,
-            icd11_code,
-            icd11_display,
-        ),
+        claim,
     })
 }
+
+/// Fill unitPrice/net on each item from the tariff table, and the Claim
+/// total once every item is priced.
+fn price_items(claim: &mut Claim) {
+    let kes = |value: f64| Money {
+        value,
+        currency: "KES".to_string(),
+    };
+    let mut total = Some(0.0);
+    for item in claim.item.iter_mut().flatten() {
+        let code = item
+            .product_or_service
+            .coding
+            .iter()
+            .flatten()
+            .find_map(|c| c.code.as_deref());
+        match code.and_then(tariff_kes) {
+            Some(price) => {
+                item.unit_price = Some(kes(price));
+                item.net = Some(kes(price));
+                total = total.map(|t| t + price);
+            }
+            None => total = None,
+        }
+    }
+    claim.total = total.map(kes);
+}
+
+/// Point the Claim's supportingInfo at the visit's vitals and Condition, so
+/// SHA adjudicators see the clinical justification in the preauth itself.
+///
+/// Call once the visit's resources are final: vitals dropped under
+/// `FailurePolicy::Skip` must not be referenced.
+pub fn add_supporting_info(claim: &mut Claim, observations: &[Observation], condition: &Condition) {
+    let references = observations
+        .iter()
+        .filter_map(|o| o.id.as_deref())
+        .map(|id| format!("Observation/{}", id))
+        .chain(
+            condition
+                .id
+                .as_deref()
+                .map(|id| format!("Condition/{}", id)),
+        );
+    let info: Vec<ClaimSupportingInfo> = references
+        .enumerate()
+        .map(|(i, reference)| ClaimSupportingInfo {
+            sequence: i as u32 + 1,
+            category: CodeableConcept {
+                coding: Some(vec![Coding {
+                    system: Some(
+                        "http://terminology.hl7.org/CodeSystem/claiminformationcategory"
+                            .to_string(),
+                    ),
+                    code: Some("info".to_string()),
+                    display: Some("Information".to_string()),
+                }]),
+                text: None,
+            },
+            value_reference: Some(Reference {
+                reference: Some(reference),
+                display: None,
+            }),
+        })
+        .collect();
+    claim.supporting_info = (!info.is_empty()).then_some(info);
+}
@@ -0,0 +1,88 @@
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Observation, Reference};
+
+use crate::mapper::observation::exam_category;
+use crate::systems::KTAS_SYSTEM;
+
+/// LOINC "Acuity assessment at First encounter", the triage Observation code.
+pub const ACUITY_LOINC: &str = "11283-9";
+
+/// Display name of KTAS category 1–5.
+pub fn ktas_display(category: u8) -> &'static str {
+    match category {
+        1 => "Resuscitation",
+        2 => "Emergency",
+        3 => "Urgent",
+        4 => "Less urgent",
+        _ => "Non-urgent",
+    }
+}
+
+/// Encounter.priority for a KTAS category: 1–2 emergency, 3 urgent,
+/// 4–5 routine (v3-ActPriority). The category itself is in the text.
+pub fn triage_priority(category: u8) -> CodeableConcept {
+    let (code, display) = match category {
+        1 | 2 => ("EM", "emergency"),
+        3 => ("UR", "urgent"),
+        _ => ("R", "routine"),
+    };
+    CodeableConcept {
+        coding: Some(vec![Coding {
+            system: Some("http://terminology.hl7.org/CodeSystem/v3-ActPriority".to_string()),
+            code: Some(code.to_string()),
+            display: Some(display.to_string()),
+        }]),
+        text: Some(format!("KTAS {} — {}", category, ktas_display(category))),
+    }
+}
+
+/// Maps the triage category → FHIR R4 Observation (`triage-{key}`):
+/// LOINC 11283-9 with the KTAS category as a coded value.
+pub fn map_triage(
+    category: u8,
+    patient_id: &str,
+    visit_key: &str,
+    encounter_id: &str,
+    visit_date: &str,
+) -> Observation {
+    Observation {
+        resource_type: "Observation".to_string(),
+        id: Some(format!("triage-{}", visit_key)),
+        meta: None,
+        extension: None,
+        status: "final".to_string(),
+        category: Some(exam_category()),
+        code: CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some("http://loinc.org".to_string()),
+                code: Some(ACUITY_LOINC.to_string()),
+                display: Some("Acuity assessment at First encounter".to_string()),
+            }]),
+            text: Some("Triage category".to_string()),
+        },
+        subject: Some(Reference {
+            reference: Some(format!("Patient/{}", patient_id)),
+            display: None,
+        }),
+        encounter: Some(Reference {
+            reference: Some(format!("Encounter/{}", encounter_id)),
+            display: None,
+        }),
+        effective_date_time: Some(visit_date.to_string()),
+        performer: None,
+        value_quantity: None,
+        value_codeable_concept: Some(CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some(KTAS_SYSTEM.to_string()),
+                code: Some(category.to_string()),
+                display: Some(ktas_display(category).to_string()),
+            }]),
+            text: None,
+        }),
+        value_string: None,
+        interpretation: None,
+        component: None,
+        device: None,
+        derived_from: None,
+        extra: Default::default(),
+    }
+}
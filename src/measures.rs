@@ -0,0 +1,335 @@
+//! DHA quality indicators evaluated over a period of archived bundles.
+//!
+//! An indicator is a proportion over visits (Encounters): the denominator
+//! criteria select eligible visits, the numerator criteria the subset that
+//! met the standard — e.g. "% of hypertensive visits with BP recorded".
+//! Results are emitted as FHIR MeasureReports (one per indicator per
+//! facility) and as CSV for spreadsheet users.
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use crate::error::{BridgeError, Context, Result};
+use crate::systems::MEASURE_BASE;
+use fhir_parser::fhir::bundle::Bundle;
+use fhir_parser::fhir::condition::Condition;
+use fhir_parser::fhir::encounter::{Encounter, Period};
+use fhir_parser::fhir::measure_report::{
+    MeasureReport, MeasureReportGroup, MeasureReportPopulation,
+};
+use fhir_parser::fhir::medication_request::MedicationRequest;
+use fhir_parser::fhir::observation::{CodeableConcept, Coding, Observation, Quantity, Reference};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// One condition a visit must satisfy.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Criterion {
+    /// A Condition recorded at the visit has one of these codes (any system).
+    Condition { codes: Vec<String> },
+    /// An Observation taken at the visit has one of these codes (e.g. LOINC).
+    Observation { codes: Vec<String> },
+    /// A MedicationRequest at the visit mentions one of these terms.
+    Medication { terms: Vec<String> },
+}
+
+/// A proportion indicator. An empty denominator means "every visit".
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Indicator {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub denominator: Vec<Criterion>,
+    pub numerator: Vec<Criterion>,
+}
+
+/// Indicator definitions — built-in defaults or a JSON file as disseminated
+/// by DHA (array of `Indicator`).
+#[derive(Debug, Clone)]
+pub struct IndicatorSet {
+    indicators: Vec<Indicator>,
+}
+
+impl Default for IndicatorSet {
+    fn default() -> Self {
+        let codes = |c: &[&str]| c.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        Self {
+            indicators: vec![
+                Indicator {
+                    id: "htn-bp-recorded".into(),
+                    name: "Hypertensive patient visits with blood pressure recorded".into(),
+                    denominator: vec![Criterion::Condition {
+                        codes: codes(&["BA00", "I10"]),
+                    }],
+                    numerator: vec![Criterion::Observation {
+                        codes: codes(&["85354-9"]),
+                    }],
+                },
+                Indicator {
+                    id: "malaria-act".into(),
+                    name: "Malaria visits treated with an artemisinin-based combination".into(),
+                    denominator: vec![Criterion::Condition {
+                        codes: codes(&["1F4Z", "B54"]),
+                    }],
+                    numerator: vec![Criterion::Medication {
+                        terms: codes(&["artemether", "artesunate", "dihydroartemisinin"]),
+                    }],
+                },
+                Indicator {
+                    id: "opd-spo2-recorded".into(),
+                    name: "OPD visits with oxygen saturation recorded".into(),
+                    denominator: vec![],
+                    numerator: vec![Criterion::Observation {
+                        codes: codes(&["59408-5"]),
+                    }],
+                },
+            ],
+        }
+    }
+}
+
+impl IndicatorSet {
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path).with_context(BridgeError::Io, || {
+            format!("Failed to read indicator definitions {:?}", path)
+        })?;
+        let indicators: Vec<Indicator> = serde_json::from_str(&raw)
+            .context(BridgeError::Config, "Invalid indicator definitions JSON")?;
+        Ok(Self { indicators })
+    }
+}
+
+/// What an indicator can see about one visit.
+#[derive(Debug, Default)]
+struct VisitFacts {
+    facility: String,
+    date: String,
+    condition_codes: HashSet<String>,
+    observation_codes: HashSet<String>,
+    medication_text: String,
+}
+
+impl VisitFacts {
+    fn satisfies(&self, criterion: &Criterion) -> bool {
+        match criterion {
+            Criterion::Condition { codes } => {
+                codes.iter().any(|c| self.condition_codes.contains(c))
+            }
+            Criterion::Observation { codes } => {
+                codes.iter().any(|c| self.observation_codes.contains(c))
+            }
+            Criterion::Medication { terms } => terms
+                .iter()
+                .any(|t| self.medication_text.contains(&t.to_lowercase())),
+        }
+    }
+}
+
+fn codes_of(cc: Option<&CodeableConcept>) -> impl Iterator<Item = String> + '_ {
+    cc.and_then(|c| c.coding.as_ref())
+        .into_iter()
+        .flatten()
+        .filter_map(|c| c.code.clone())
+}
+
+fn resources_of<T: serde::de::DeserializeOwned>(bundle: &Bundle, resource_type: &str) -> Vec<T> {
+    bundle
+        .entry
+        .iter()
+        .flatten()
+        .filter_map(|e| e.resource.as_ref())
+        .filter(|r| r.get("resourceType").and_then(Value::as_str) == Some(resource_type))
+        .filter_map(|r| serde_json::from_value(r.clone()).ok())
+        .collect()
+}
+
+/// Split a bundle into per-Encounter facts: Conditions, Observations and
+/// MedicationRequests belong to the Encounter their `encounter` references.
+fn visits_in_bundle(bundle: &Bundle) -> Vec<VisitFacts> {
+    let encounters: Vec<Encounter> = resources_of(bundle, "Encounter");
+    let conditions: Vec<Condition> = resources_of(bundle, "Condition");
+    let observations: Vec<Observation> = resources_of(bundle, "Observation");
+    let medications: Vec<MedicationRequest> = resources_of(bundle, "MedicationRequest");
+
+    let reference = |r: Option<&Reference>| r.and_then(|r| r.reference.clone());
+
+    encounters
+        .iter()
+        .map(|enc| {
+            let enc_ref = enc.id.as_ref().map(|id| format!("Encounter/{}", id));
+            let date: String = enc
+                .period
+                .as_ref()
+                .and_then(|p| p.start.clone())
+                .unwrap_or_default();
+
+            let mut facts = VisitFacts {
+                facility: reference(enc.service_provider.as_ref())
+                    .map(|r| r.trim_start_matches("Organization/").to_string())
+                    .unwrap_or_else(|| "unknown".into()),
+                date: date.chars().take(10).collect(),
+                ..Default::default()
+            };
+
+            for cond in conditions
+                .iter()
+                .filter(|c| reference(c.encounter.as_ref()) == enc_ref)
+            {
+                facts.condition_codes.extend(codes_of(cond.code.as_ref()));
+            }
+            for obs in observations
+                .iter()
+                .filter(|o| reference(o.encounter.as_ref()) == enc_ref)
+            {
+                facts.observation_codes.extend(codes_of(Some(&obs.code)));
+            }
+            for med in medications
+                .iter()
+                .filter(|m| reference(m.encounter.as_ref()) == enc_ref)
+            {
+                if let Some(text) = med
+                    .medication_codeable_concept
+                    .as_ref()
+                    .and_then(|c| c.text.as_ref())
+                {
+                    facts.medication_text.push_str(&text.to_lowercase());
+                    facts.medication_text.push(' ');
+                }
+            }
+            facts
+        })
+        .collect()
+}
+
+/// Numerator/denominator counts for one indicator at one facility.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndicatorResult {
+    pub indicator_id: String,
+    pub indicator_name: String,
+    pub facility: String,
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl IndicatorResult {
+    pub fn score(&self) -> Option<f64> {
+        (self.denominator > 0).then(|| self.numerator as f64 / self.denominator as f64)
+    }
+}
+
+/// Evaluate every indicator over visits whose date falls in `[start, end]`
+/// (inclusive, YYYY-MM-DD). Facilities with no eligible visits are omitted.
+pub fn evaluate(
+    bundles: &[Bundle],
+    indicators: &IndicatorSet,
+    start: &str,
+    end: &str,
+) -> Vec<IndicatorResult> {
+    let visits: Vec<VisitFacts> = bundles
+        .iter()
+        .flat_map(visits_in_bundle)
+        .filter(|v| v.date.as_str() >= start && v.date.as_str() <= end)
+        .collect();
+
+    let mut results = Vec::new();
+    for indicator in &indicators.indicators {
+        let mut per_facility: BTreeMap<&str, (u32, u32)> = BTreeMap::new();
+        for visit in &visits {
+            if !indicator.denominator.iter().all(|c| visit.satisfies(c)) {
+                continue;
+            }
+            let counts = per_facility.entry(&visit.facility).or_default();
+            counts.1 += 1;
+            if indicator.numerator.iter().all(|c| visit.satisfies(c)) {
+                counts.0 += 1;
+            }
+        }
+        for (facility, (numerator, denominator)) in per_facility {
+            results.push(IndicatorResult {
+                indicator_id: indicator.id.clone(),
+                indicator_name: indicator.name.clone(),
+                facility: facility.to_string(),
+                numerator,
+                denominator,
+            });
+        }
+    }
+    results
+}
+
+fn population(code: &str, display: &str, count: u32) -> MeasureReportPopulation {
+    MeasureReportPopulation {
+        code: Some(CodeableConcept {
+            coding: Some(vec![Coding {
+                system: Some(
+                    "http://terminology.hl7.org/CodeSystem/measure-population".to_string(),
+                ),
+                code: Some(code.to_string()),
+                display: Some(display.to_string()),
+            }]),
+            text: None,
+        }),
+        count: Some(count),
+    }
+}
+
+/// Summary MeasureReport for one indicator result.
+pub fn to_measure_report(result: &IndicatorResult, start: &str, end: &str) -> MeasureReport {
+    MeasureReport {
+        resource_type: "MeasureReport".to_string(),
+        id: Some(format!(
+            "mr-{}-{}-{}",
+            result.indicator_id, result.facility, start
+        )),
+        meta: None,
+        status: "complete".to_string(),
+        report_type: "summary".to_string(),
+        measure: format!("{}/{}", MEASURE_BASE, result.indicator_id),
+        date: Some(chrono::Utc::now().to_rfc3339()),
+        reporter: Some(Reference {
+            reference: Some(format!("Organization/{}", result.facility)),
+            display: None,
+        }),
+        period: Period {
+            start: Some(start.to_string()),
+            end: Some(end.to_string()),
+        },
+        group: Some(vec![MeasureReportGroup {
+            code: Some(CodeableConcept {
+                coding: None,
+                text: Some(result.indicator_name.clone()),
+            }),
+            population: Some(vec![
+                population("denominator", "Denominator", result.denominator),
+                population("numerator", "Numerator", result.numerator),
+            ]),
+            measure_score: result.score().map(|s| Quantity {
+                value: s,
+                unit: None,
+                system: None,
+            }),
+        }]),
+        extra: Default::default(),
+    }
+}
+
+/// CSV rendering: one row per indicator per facility.
+pub fn to_csv(results: &[IndicatorResult], start: &str, end: &str) -> String {
+    let mut out = String::from(
+        "indicator_id,indicator_name,facility,period_start,period_end,numerator,denominator,score\n",
+    );
+    for r in results {
+        out.push_str(&format!(
+            "{},\"{}\",{},{},{},{},{},{}\n",
+            r.indicator_id,
+            r.indicator_name.replace('"', "\"\""),
+            r.facility,
+            start,
+            end,
+            r.numerator,
+            r.denominator,
+            r.score().map(|s| format!("{:.3}", s)).unwrap_or_default()
+        ));
+    }
+    out
+}
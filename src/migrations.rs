@@ -0,0 +1,133 @@
+use crate::error::{bail, BridgeError, Context, Result};
+use chrono::Utc;
+use rusqlite::{params, Connection, Transaction, TransactionBehavior};
+
+/// One schema change. Versions start at 1 and increase by one; once shipped
+/// a migration is never edited, only followed by a new one.
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub apply: fn(&Connection) -> rusqlite::Result<()>,
+}
+
+/// Bring `conn` up to the last of `migrations`, recording each step in
+/// `schema_version`. Every migration runs in its own transaction, so an
+/// interrupted upgrade resumes where it stopped. Returns the new version.
+///
+/// Refuses databases written by a newer build (a version this build does
+/// not know) rather than risk writing rows an older schema misreads.
+pub fn migrate(conn: &Connection, migrations: &[Migration]) -> Result<u32> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version     INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at  TEXT NOT NULL
+        );",
+    )
+    .context(
+        BridgeError::Storage,
+        "Failed to create schema_version table",
+    )?;
+
+    let applied = schema_version(conn)?;
+    let latest = migrations.last().map_or(0, |m| m.version);
+    if applied > latest {
+        bail!(
+            Storage,
+            "Database schema version {} is newer than this build supports ({})",
+            applied,
+            latest
+        );
+    }
+
+    let mut current = applied;
+    for migration in migrations.iter().filter(|m| m.version > applied) {
+        // Take the write lock first and re-check: another process opening
+        // the same database may have applied this migration meanwhile.
+        let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+        if schema_version(&tx)? >= migration.version {
+            current = migration.version;
+            continue;
+        }
+        (migration.apply)(&tx).with_context(BridgeError::Storage, || {
+            format!(
+                "Migration {} ({}) failed",
+                migration.version, migration.description
+            )
+        })?;
+        tx.execute(
+            "INSERT INTO schema_version (version, description, applied_at)
+             VALUES (?1, ?2, ?3)",
+            params![
+                migration.version,
+                migration.description,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        tx.commit()?;
+        current = migration.version;
+    }
+    Ok(current)
+}
+
+/// Highest applied migration; 0 for a database without any.
+pub fn schema_version(conn: &Connection) -> Result<u32> {
+    Ok(conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+        [],
+        |r| r.get(0),
+    )?)
+}
+
+/// `ALTER TABLE ... ADD COLUMN` unless the column exists, for databases
+/// that gained it before they were under migration control.
+pub fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    declaration: &str,
+) -> rusqlite::Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2",
+        params![table, column],
+        |r| r.get(0),
+    )?;
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {};",
+            table, column, declaration
+        ))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            description: "create t",
+            apply: |c| c.execute_batch("CREATE TABLE t (a TEXT);"),
+        },
+        Migration {
+            version: 2,
+            description: "add t.b",
+            apply: |c| add_column_if_missing(c, "t", "b", "TEXT"),
+        },
+    ];
+
+    #[test]
+    fn applies_pending_migrations_once() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(migrate(&conn, &MIGRATIONS[..1]).unwrap(), 1);
+        assert_eq!(migrate(&conn, MIGRATIONS).unwrap(), 2);
+        assert_eq!(migrate(&conn, MIGRATIONS).unwrap(), 2);
+        conn.execute("INSERT INTO t (a, b) VALUES ('x', 'y')", [])
+            .unwrap();
+
+        // A build that only knows version 1 must not open it
+        assert!(migrate(&conn, &MIGRATIONS[..1]).is_err());
+    }
+}
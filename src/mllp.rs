@@ -0,0 +1,186 @@
+//! MLLP (Minimal Lower Layer Protocol) listener for HL7 v2 feeds.
+//!
+//! Lab analyzers and EMRs push v2 messages over long-lived TCP connections,
+//! each message framed as `<VT> message <FS><CR>` and answered with an
+//! acknowledgement in the same framing before the sender moves on. Every
+//! connection gets its own thread for the socket, but messages are handed
+//! one at a time to a single handler on the calling thread, so it can own
+//! state that is not `Send` (the queue's SQLite connection).
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+
+use chrono::Local;
+
+use crate::error::{bail, Result};
+use crate::hl7v2::Message;
+
+const START_BLOCK: u8 = 0x0b;
+const END_BLOCK: u8 = 0x1c;
+const CARRIAGE_RETURN: u8 = 0x0d;
+
+/// Read one framed message; `None` once the peer has closed the connection.
+pub fn read_frame(reader: &mut impl BufRead) -> Result<Option<String>> {
+    // Anything before the start block (stray CR/LF between frames) is skipped
+    let mut skipped = Vec::new();
+    if reader.read_until(START_BLOCK, &mut skipped)? == 0 {
+        return Ok(None);
+    }
+    if skipped.last() != Some(&START_BLOCK) {
+        return Ok(None);
+    }
+    let mut frame = Vec::new();
+    reader.read_until(END_BLOCK, &mut frame)?;
+    if frame.pop() != Some(END_BLOCK) {
+        bail!(Network, "MLLP connection closed inside a message");
+    }
+    let mut trailer = [0u8; 1];
+    reader.read_exact(&mut trailer)?;
+    if trailer[0] != CARRIAGE_RETURN {
+        bail!(Network, "MLLP frame does not end in <FS><CR>");
+    }
+    Ok(Some(String::from_utf8_lossy(&frame).into_owned()))
+}
+
+/// Write one framed message.
+pub fn write_frame(writer: &mut impl Write, message: &str) -> Result<()> {
+    writer.write_all(&[START_BLOCK])?;
+    writer.write_all(message.as_bytes())?;
+    writer.write_all(&[END_BLOCK, CARRIAGE_RETURN])?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Acknowledgement code (MSA-1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckCode {
+    /// Accepted
+    Accept,
+    /// Refused for its content; resending it unchanged will not help
+    Error,
+    /// Refused for a reason on this side (e.g. the queue is unavailable)
+    Reject,
+}
+
+impl AckCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AckCode::Accept => "AA",
+            AckCode::Error => "AE",
+            AckCode::Reject => "AR",
+        }
+    }
+}
+
+/// ACK for `raw` (the message as received, parsed or not): sender and
+/// receiver swapped, MSA echoing its control id, with `text` in MSA-3.
+pub fn ack(raw: &str, code: AckCode, text: &str) -> String {
+    let message = Message::parse(raw).ok();
+    let msh = |n| {
+        message
+            .as_ref()
+            .and_then(|m| m.field("MSH", n))
+            .unwrap_or_default()
+    };
+    let trigger = msh(9).split('^').nth(1).unwrap_or_default();
+    let now = Local::now().format("%Y%m%d%H%M%S");
+    // Keep MSA-3 a single field whatever the text says
+    let text: String = text
+        .chars()
+        .map(|c| if "|^~\\&\r\n".contains(c) { ' ' } else { c })
+        .collect();
+    format!(
+        "MSH|^~\\&|{}|{}|{}|{}|{}||ACK^{}^ACK|ACK{}|P|{}\rMSA|{}|{}|{}\r",
+        msh(5),
+        msh(6),
+        msh(3),
+        msh(4),
+        now,
+        trigger,
+        now,
+        Some(msh(12)).filter(|v| !v.is_empty()).unwrap_or("2.5"),
+        code.as_str(),
+        msh(10),
+        text
+    )
+}
+
+/// A received message and where its acknowledgement goes.
+struct Received {
+    message: String,
+    reply: mpsc::Sender<String>,
+}
+
+/// Accept connections on `listener` and answer every message with the ACK
+/// `handle` returns. Runs until the listener fails.
+pub fn listen<F>(listener: TcpListener, mut handle: F) -> Result<()>
+where
+    F: FnMut(&str) -> String,
+{
+    let (sender, received) = mpsc::channel::<Received>();
+    let acceptor = thread::spawn(move || -> std::io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let sender = sender.clone();
+            thread::spawn(move || {
+                if let Err(e) = serve_connection(stream, sender) {
+                    eprintln!("[MLLP] connection closed: {:#}", e);
+                }
+            });
+        }
+        Ok(())
+    });
+    for Received { message, reply } in received {
+        // The sender may have gone; its connection thread reports that
+        let _ = reply.send(handle(&message));
+    }
+    match acceptor.join() {
+        Ok(result) => Ok(result?),
+        Err(_) => bail!(Network, "MLLP listener thread panicked"),
+    }
+}
+
+fn serve_connection(stream: TcpStream, handler: mpsc::Sender<Received>) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    while let Some(message) = read_frame(&mut reader)? {
+        let (reply, ack) = mpsc::channel();
+        if handler.send(Received { message, reply }).is_err() {
+            break;
+        }
+        match ack.recv() {
+            Ok(ack) => write_frame(&mut writer, &ack)?,
+            Err(_) => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_round_trip_and_ack_echoes_the_control_id() {
+        let mut framed = b"\r\n".to_vec();
+        write_frame(
+            &mut framed,
+            "MSH|^~\\&|LIS|LAB1|KFB|12345|20250310||ADT^A04|C42|P|2.3\r",
+        )
+        .unwrap();
+        let mut reader = &framed[..];
+        let message = read_frame(&mut reader).unwrap().unwrap();
+        assert!(message.ends_with("|C42|P|2.3\r"));
+        assert!(read_frame(&mut reader).unwrap().is_none());
+
+        let reply = ack(&message, AckCode::Error, "no weight | OBX");
+        let msa = reply.lines().last().unwrap();
+        assert!(reply.starts_with("MSH|^~\\&|KFB|12345|LIS|LAB1|"));
+        assert!(reply.contains("|ACK^A04^ACK|"));
+        assert_eq!(
+            msa.split('\r').find(|s| s.starts_with("MSA")),
+            Some("MSA|AE|C42|no weight   OBX")
+        );
+    }
+}
@@ -1,11 +1,120 @@
 use std::path::Path;
+use std::time::Duration;
 
-use anyhow::{Context, Result};
-use chrono::Utc;
-use rusqlite::{params, Connection};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::migrations::{self, add_column_if_missing, Migration};
+use crate::queue_alerts::{AlertConfig, QueueAlert};
+use crate::queue_crypto::{self, QueueKeys};
+use crate::upload::{compact_json, gunzip, gzip};
+
+/// Queue schema history. Append new migrations; never edit shipped ones.
+/// Version 1 uses `IF NOT EXISTS` because clinic databases predate
+/// `schema_version`.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "pending_bundles table",
+        apply: |conn| {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS pending_bundles (
+                    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                    bundle_id   TEXT NOT NULL,
+                    bundle_json TEXT NOT NULL,
+                    patient_id  TEXT NOT NULL,
+                    clinic_id   TEXT NOT NULL,
+                    created_at  TEXT NOT NULL,
+                    retry_count INTEGER NOT NULL DEFAULT 0,
+                    last_error  TEXT,
+                    status      TEXT NOT NULL DEFAULT 'pending'
+                );
+                CREATE INDEX IF NOT EXISTS idx_status ON pending_bundles(status);
+                CREATE INDEX IF NOT EXISTS idx_created ON pending_bundles(created_at);",
+            )
+        },
+    },
+    Migration {
+        version: 2,
+        description: "content hash for integrity checks and deduplication",
+        apply: |conn| {
+            add_column_if_missing(conn, "pending_bundles", "content_hash", "TEXT")?;
+            conn.execute_batch(
+                "CREATE INDEX IF NOT EXISTS idx_bundle_id ON pending_bundles(bundle_id);
+                 CREATE INDEX IF NOT EXISTS idx_content_hash ON pending_bundles(content_hash);",
+            )
+        },
+    },
+    Migration {
+        version: 3,
+        description: "priority and destination endpoint routing",
+        apply: |conn| {
+            add_column_if_missing(
+                conn,
+                "pending_bundles",
+                "priority",
+                "INTEGER NOT NULL DEFAULT 0",
+            )?;
+            add_column_if_missing(conn, "pending_bundles", "destination_endpoint", "TEXT")?;
+            conn.execute_batch(
+                "CREATE INDEX IF NOT EXISTS idx_route
+                 ON pending_bundles(status, destination_endpoint, priority);",
+            )
+        },
+    },
+    Migration {
+        version: 4,
+        description: "sent-row archive and maintenance log",
+        apply: |conn| {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS sent_archive (
+                    id                   INTEGER PRIMARY KEY,
+                    bundle_id            TEXT NOT NULL,
+                    bundle_json          TEXT NOT NULL,
+                    content_hash         TEXT,
+                    patient_id           TEXT NOT NULL,
+                    clinic_id            TEXT NOT NULL,
+                    destination_endpoint TEXT,
+                    created_at           TEXT NOT NULL,
+                    archived_at          TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS idx_archive_bundle_id ON sent_archive(bundle_id);
+                CREATE INDEX IF NOT EXISTS idx_archive_content_hash
+                    ON sent_archive(content_hash);
+                CREATE TABLE IF NOT EXISTS maintenance_log (
+                    id     INTEGER PRIMARY KEY AUTOINCREMENT,
+                    ran_at TEXT NOT NULL,
+                    report TEXT NOT NULL
+                );",
+            )
+        },
+    },
+    Migration {
+        version: 5,
+        description: "alert log",
+        apply: |conn| {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS alert_log (
+                    event     TEXT NOT NULL,
+                    row_id    INTEGER NOT NULL,
+                    raised_at TEXT NOT NULL,
+                    PRIMARY KEY (event, row_id)
+                );",
+            )
+        },
+    },
+];
+
+/// Tables holding sealed bundle JSON.
+const BUNDLE_TABLES: [&str; 2] = ["pending_bundles", "sent_archive"];
 
 /// Pending bundle states
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BundleStatus {
     Pending,
     Sent,
@@ -13,50 +122,213 @@ pub enum BundleStatus {
 }
 
 impl BundleStatus {
-    fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &'static str {
         match self {
             BundleStatus::Pending => "pending",
             BundleStatus::Sent => "sent",
             BundleStatus::Failed => "failed",
         }
     }
+
+    fn from_str(value: &str) -> Self {
+        match value {
+            "sent" => BundleStatus::Sent,
+            "failed" => BundleStatus::Failed,
+            _ => BundleStatus::Pending,
+        }
+    }
+}
+
+/// Which rows [`OfflineQueue::find`] returns; fields left `None` match
+/// every row.
+#[derive(Debug, Clone, Default)]
+pub struct QueueFilter {
+    /// The clinic's patient number
+    pub patient_id: Option<String>,
+    pub clinic_id: Option<String>,
+    pub status: Option<BundleStatus>,
+    /// Queued on or after this date (UTC)
+    pub from: Option<NaiveDate>,
+    /// Queued on or before this date (UTC)
+    pub to: Option<NaiveDate>,
+}
+
+/// Prefix of compressed, unencrypted `bundle_json` values
+const GZIP_PREFIX: &str = "gz:";
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// How long a connection waits for another process's lock before giving up.
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Transaction that holds the write lock from the start, so it cannot fail
+/// with SQLITE_BUSY halfway through when upgrading from a read.
+fn write_transaction(conn: &Connection) -> rusqlite::Result<Transaction<'_>> {
+    Transaction::new_unchecked(conn, TransactionBehavior::Immediate)
+}
+
+/// Transmission priority; higher priorities are sent first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// SHR submissions and everything else
+    #[default]
+    Routine,
+    /// SHA claims: payment deadlines make these more urgent than SHR updates
+    Claim,
+}
+
+impl Priority {
+    fn as_i64(self) -> i64 {
+        match self {
+            Priority::Routine => 0,
+            Priority::Claim => 10,
+        }
+    }
+
+    fn from_i64(value: i64) -> Self {
+        if value >= Priority::Claim.as_i64() {
+            Priority::Claim
+        } else {
+            Priority::Routine
+        }
+    }
+}
+
+/// Where and how urgently a queued bundle should be sent.
+#[derive(Debug, Clone, Default)]
+pub struct QueueRouting {
+    pub priority: Priority,
+    /// Target endpoint (e.g. county SHR, national SHR, SHA claims API);
+    /// `None` is the facility's default endpoint
+    pub destination_endpoint: Option<String>,
+}
+
+/// Transmission window, retry limit and how long sent rows stay in the
+/// queue.
+///
+/// The defaults follow the DHA 7-day offline-facility transmission window
+/// (Digital Health Regulations 2025); counties that negotiated a longer
+/// window set it in a policy file, e.g. `{"transmission_window_days": 14}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QueuePolicy {
+    /// Pending bundles older than this are no longer sent and get expired
+    pub transmission_window_days: u32,
+    /// Failed attempts after which a bundle is marked failed
+    pub max_retries: u32,
+    /// Sent rows older than this move to the sent archive at maintenance
+    pub sent_retention_days: u32,
+}
+
+impl Default for QueuePolicy {
+    fn default() -> Self {
+        Self {
+            transmission_window_days: 7,
+            max_retries: 10,
+            sent_retention_days: 30,
+        }
+    }
+}
+
+impl QueuePolicy {
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path).with_context(BridgeError::Io, || {
+            format!("Failed to read queue policy {:?}", path)
+        })?;
+        let policy: Self =
+            serde_json::from_str(&raw).context(BridgeError::Queue, "Invalid queue policy JSON")?;
+        if policy.transmission_window_days == 0
+            || policy.max_retries == 0
+            || policy.sent_retention_days == 0
+        {
+            bail!(
+                Queue,
+                "Queue policy window, retry limit and sent retention must be at least 1"
+            );
+        }
+        Ok(policy)
+    }
+
+    fn cutoff(&self) -> String {
+        (Utc::now() - chrono::Duration::days(self.transmission_window_days.into())).to_rfc3339()
+    }
 }
 
 /// SQLite-backed offline queue for FHIR bundles awaiting transmission.
 ///
-/// Bundles are queued locally and retried for up to 7 days per DHA
-/// offline-facility transmission window (Digital Health Regulations 2025).
+/// Bundles are queued locally and retried within the transmission window
+/// of the [`QueuePolicy`] (7 days and 10 attempts unless configured).
+/// Queued bundles contain PHI: with [`QueueKeys`] set, `bundle_json` is
+/// stored AES-256-GCM encrypted and only the routing columns stay readable.
+///
+/// Several processes may use one queue file at once (the CLI enqueuing
+/// while the worker drains): the database runs in WAL mode so readers never
+/// block the writer, every connection waits up to [`BUSY_TIMEOUT`] for a
+/// lock instead of failing with `database is locked`, and multi-statement
+/// writes take the write lock up front (`BEGIN IMMEDIATE`) so a
+/// check-then-insert cannot interleave with another process.
 pub struct OfflineQueue {
     conn: Connection,
+    keys: Option<QueueKeys>,
+    policy: QueuePolicy,
 }
 
 impl OfflineQueue {
     /// Open (or create) the queue database at the given path.
     pub fn open(db_path: &Path) -> Result<Self> {
-        let conn = Connection::open(db_path)
-            .with_context(|| format!("Failed to open queue db at {:?}", db_path))?;
-
-        conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS pending_bundles (
-                id          INTEGER PRIMARY KEY AUTOINCREMENT,
-                bundle_id   TEXT NOT NULL,
-                bundle_json TEXT NOT NULL,
-                patient_id  TEXT NOT NULL,
-                clinic_id   TEXT NOT NULL,
-                created_at  TEXT NOT NULL,
-                retry_count INTEGER NOT NULL DEFAULT 0,
-                last_error  TEXT,
-                status      TEXT NOT NULL DEFAULT 'pending'
-            );
-            CREATE INDEX IF NOT EXISTS idx_status ON pending_bundles(status);
-            CREATE INDEX IF NOT EXISTS idx_created ON pending_bundles(created_at);",
+        let conn = Connection::open(db_path).with_context(BridgeError::Queue, || {
+            format!("Failed to open queue db at {:?}", db_path)
+        })?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        // WAL is persistent; setting it on every open is a no-op once set
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .context(BridgeError::Queue, "Failed to enable WAL on queue db")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        Self::init(conn)
+    }
+
+    /// Throwaway in-memory queue (tests, dry runs).
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(
+            Connection::open_in_memory()
+                .context(BridgeError::Queue, "Failed to open in-memory queue")?,
         )
-        .context("Failed to initialise queue schema")?;
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        migrations::migrate(&conn, MIGRATIONS)
+            .context(BridgeError::Queue, "Failed to initialise queue schema")?;
+
+        Ok(Self {
+            conn,
+            keys: None,
+            policy: QueuePolicy::default(),
+        })
+    }
 
-        Ok(Self { conn })
+    /// Applied schema migration version.
+    pub fn schema_version(&self) -> Result<u32> {
+        migrations::schema_version(&self.conn)
     }
 
-    /// Enqueue a bundle for later transmission.
+    /// Use a non-default transmission window / retry limit.
+    pub fn with_policy(mut self, policy: QueuePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn policy(&self) -> &QueuePolicy {
+        &self.policy
+    }
+
+    /// Encrypt newly queued bundles with the active key (and decrypt rows
+    /// written under any key in the set).
+    pub fn with_keys(mut self, keys: QueueKeys) -> Self {
+        self.keys = Some(keys);
+        self
+    }
+
+    /// Enqueue a bundle for later transmission to the default endpoint at
+    /// routine priority.
     pub fn enqueue(
         &self,
         bundle_id: &str,
@@ -64,28 +336,184 @@ impl OfflineQueue {
         patient_id: &str,
         clinic_id: &str,
     ) -> Result<i64> {
+        self.enqueue_routed(
+            bundle_id,
+            bundle_json,
+            patient_id,
+            clinic_id,
+            &QueueRouting::default(),
+        )
+    }
+
+    /// Enqueue a bundle with an explicit priority and destination.
+    ///
+    /// Rejected when a pending or sent row for the same destination already
+    /// has the same bundle id or the same content (archived sent rows
+    /// included), so a retry loop cannot queue (and later submit) a bundle
+    /// twice. Failed rows may be queued again, and the same bundle may be
+    /// queued once per destination.
+    pub fn enqueue_routed(
+        &self,
+        bundle_id: &str,
+        bundle_json: &str,
+        patient_id: &str,
+        clinic_id: &str,
+        routing: &QueueRouting,
+    ) -> Result<i64> {
+        let hash = content_hash(bundle_json);
+        let tx = write_transaction(&self.conn)?;
+        let duplicate: Option<(i64, String)> = tx
+            .query_row(
+                "SELECT id, bundle_id FROM pending_bundles
+                 WHERE (bundle_id = ?1 OR content_hash = ?2) AND status != 'failed'
+                   AND destination_endpoint IS ?3
+                 UNION ALL
+                 SELECT id, bundle_id FROM sent_archive
+                 WHERE (bundle_id = ?1 OR content_hash = ?2) AND destination_endpoint IS ?3
+                 LIMIT 1",
+                params![bundle_id, hash, routing.destination_endpoint],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .optional()?;
+        if let Some((row_id, existing)) = duplicate {
+            if existing == bundle_id {
+                bail!(
+                    Queue,
+                    "Bundle {} is already queued (row {})",
+                    bundle_id,
+                    row_id
+                );
+            }
+            bail!(
+                Queue,
+                "Bundle {} has the same content as queued bundle {} (row {})",
+                bundle_id,
+                existing,
+                row_id
+            );
+        }
+
         let now = Utc::now().to_rfc3339();
-        self.conn.execute(
+        let stored = self.seal(bundle_json, bundle_id)?;
+        tx.execute(
             "INSERT INTO pending_bundles
-                (bundle_id, bundle_json, patient_id, clinic_id, created_at, status)
-             VALUES (?1, ?2, ?3, ?4, ?5, 'pending')",
-            params![bundle_id, bundle_json, patient_id, clinic_id, now],
+                (bundle_id, bundle_json, content_hash, patient_id, clinic_id, created_at,
+                 priority, destination_endpoint, status)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 'pending')",
+            params![
+                bundle_id,
+                stored,
+                hash,
+                patient_id,
+                clinic_id,
+                now,
+                routing.priority.as_i64(),
+                routing.destination_endpoint
+            ],
         )?;
-        Ok(self.conn.last_insert_rowid())
+        let row_id = tx.last_insert_rowid();
+        tx.commit()?;
+        Ok(row_id)
     }
 
-    /// Retrieve all pending bundles not older than 7 days.
+    /// Retrieve all pending bundles inside the transmission window, highest
+    /// priority first, oldest first within a priority.
     pub fn pending_within_window(&self) -> Result<Vec<PendingBundle>> {
-        let cutoff = (Utc::now() - chrono::Duration::days(7)).to_rfc3339();
+        self.pending(None)
+    }
+
+    /// Pending bundles for one destination (`None` = default endpoint), in
+    /// the same order as [`Self::pending_within_window`].
+    pub fn pending_for_endpoint(&self, endpoint: Option<&str>) -> Result<Vec<PendingBundle>> {
+        self.pending(Some(endpoint))
+    }
+
+    /// Destinations with pending bundles and how many each has.
+    pub fn pending_endpoints(&self) -> Result<Vec<(Option<String>, i64)>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, bundle_id, bundle_json, patient_id, clinic_id,
-                    created_at, retry_count, last_error
-             FROM pending_bundles
-             WHERE status = 'pending' AND created_at >= ?1
-             ORDER BY created_at ASC",
+            "SELECT destination_endpoint, COUNT(*) FROM pending_bundles
+             WHERE status = 'pending'
+             GROUP BY destination_endpoint ORDER BY destination_endpoint",
         )?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .context(BridgeError::Queue, "Failed to list queue endpoints")
+    }
+
+    /// Rows matching every field set in `filter`, oldest first.
+    pub fn find(&self, filter: &QueueFilter) -> Result<Vec<PendingBundle>> {
+        self.rows(
+            "WHERE (?1 IS NULL OR patient_id = ?1)
+               AND (?2 IS NULL OR clinic_id = ?2)
+               AND (?3 IS NULL OR status = ?3)
+               AND (?4 IS NULL OR created_at >= ?4)
+               AND (?5 IS NULL OR created_at < ?5)
+             ORDER BY id",
+            params![
+                filter.patient_id,
+                filter.clinic_id,
+                filter.status.as_ref().map(BundleStatus::as_str),
+                filter.from.map(|d| d.to_string()),
+                filter.to.and_then(|d| d.succ_opt()).map(|d| d.to_string()),
+            ],
+        )
+    }
+
+    /// Every row for one patient (the clinic's patient number), in any status.
+    pub fn find_by_patient(&self, patient_id: &str) -> Result<Vec<PendingBundle>> {
+        self.find(&QueueFilter {
+            patient_id: Some(patient_id.to_string()),
+            ..QueueFilter::default()
+        })
+    }
 
-        let rows = stmt.query_map(params![cutoff], |row| {
+    /// Rows queued between `from` and `to` (inclusive dates, UTC).
+    pub fn find_by_date_range(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<PendingBundle>> {
+        self.find(&QueueFilter {
+            from: Some(from),
+            to: Some(to),
+            ..QueueFilter::default()
+        })
+    }
+
+    /// Rows in one status, e.g. the bundles already sent. Unlike
+    /// [`Self::pending_within_window`] this ignores the transmission window.
+    pub fn find_by_status(&self, status: BundleStatus) -> Result<Vec<PendingBundle>> {
+        self.find(&QueueFilter {
+            status: Some(status),
+            ..QueueFilter::default()
+        })
+    }
+
+    /// Rows from one facility.
+    pub fn find_by_clinic(&self, clinic_id: &str) -> Result<Vec<PendingBundle>> {
+        self.find(&QueueFilter {
+            clinic_id: Some(clinic_id.to_string()),
+            ..QueueFilter::default()
+        })
+    }
+
+    /// `endpoint`: `None` = every destination, `Some(x)` = destination `x`.
+    fn pending(&self, endpoint: Option<Option<&str>>) -> Result<Vec<PendingBundle>> {
+        self.rows(
+            "WHERE status = 'pending' AND created_at >= ?1
+               AND (?2 = 0 OR destination_endpoint IS ?3)
+             ORDER BY priority DESC, created_at ASC",
+            params![self.policy.cutoff(), endpoint.is_some(), endpoint.flatten()],
+        )
+    }
+
+    /// Rows selected by `clauses` (WHERE and ORDER BY), bundle JSON opened.
+    fn rows(&self, clauses: &str, params: impl rusqlite::Params) -> Result<Vec<PendingBundle>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, bundle_id, bundle_json, patient_id, clinic_id, created_at,
+                    retry_count, last_error, priority, destination_endpoint, status
+             FROM pending_bundles
+             {}",
+            clauses
+        ))?;
+
+        let rows = stmt.query_map(params, |row| {
             Ok(PendingBundle {
                 row_id: row.get(0)?,
                 bundle_id: row.get(1)?,
@@ -95,11 +523,179 @@ impl OfflineQueue {
                 created_at: row.get(5)?,
                 retry_count: row.get(6)?,
                 last_error: row.get(7)?,
+                priority: Priority::from_i64(row.get(8)?),
+                destination_endpoint: row.get(9)?,
+                status: BundleStatus::from_str(&row.get::<_, String>(10)?),
             })
         })?;
 
-        rows.collect::<rusqlite::Result<Vec<_>>>()
-            .context("Failed to query pending bundles")
+        let mut found = rows
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context(BridgeError::Queue, "Failed to query queued bundles")?;
+        for row in &mut found {
+            row.bundle_json = self.open_value(row.row_id, &row.bundle_id, &row.bundle_json)?;
+        }
+        Ok(found)
+    }
+
+    /// Stored form of a bundle: compacted and gzip'd, then encrypted when
+    /// keys are set, else `gz:` + base64.
+    fn seal(&self, bundle_json: &str, bundle_id: &str) -> Result<String> {
+        let compressed = gzip(&compact_json(bundle_json.as_bytes()))?;
+        match &self.keys {
+            Some(keys) => keys.encrypt(&compressed, bundle_id),
+            None => Ok(format!("{}{}", GZIP_PREFIX, STANDARD.encode(compressed))),
+        }
+    }
+
+    /// Bundle JSON from a stored `bundle_json`. Rows written before
+    /// compression hold plain JSON, or encrypted plain JSON.
+    fn open_value(&self, row_id: i64, bundle_id: &str, stored: &str) -> Result<String> {
+        let bytes = if queue_crypto::key_id(stored).is_some() {
+            self.keys
+                .as_ref()
+                .with_context(BridgeError::Queue, || {
+                    format!("Queue row {} is encrypted but no queue key is set", row_id)
+                })?
+                .decrypt(stored, bundle_id)
+                .with_context(BridgeError::Queue, || {
+                    format!("Cannot decrypt queue row {}", row_id)
+                })?
+        } else if let Some(encoded) = stored.strip_prefix(GZIP_PREFIX) {
+            STANDARD
+                .decode(encoded)
+                .with_context(BridgeError::Queue, || {
+                    format!("Queue row {} is not valid base64", row_id)
+                })?
+        } else {
+            return Ok(stored.to_string());
+        };
+        let json = if bytes.starts_with(&GZIP_MAGIC) {
+            gunzip(&bytes).with_context(BridgeError::Queue, || {
+                format!("Queue row {} is corrupt", row_id)
+            })?
+        } else {
+            bytes
+        };
+        String::from_utf8(json).with_context(BridgeError::Queue, || {
+            format!("Queue row {} is not UTF-8", row_id)
+        })
+    }
+
+    /// Re-encrypt every row not already under the active key, including
+    /// plaintext rows from before encryption was enabled. Runs in one
+    /// transaction; returns the number of rows rewritten.
+    pub fn reencrypt(&self) -> Result<usize> {
+        let keys = self.keys.as_ref().context(
+            BridgeError::Queue,
+            "Re-encrypting the queue needs a queue key set",
+        )?;
+        let tx = write_transaction(&self.conn)?;
+        let mut rewritten = 0;
+        for table in BUNDLE_TABLES {
+            let rows = {
+                let mut stmt =
+                    tx.prepare(&format!("SELECT id, bundle_id, bundle_json FROM {}", table))?;
+                let rows = stmt.query_map([], |r| {
+                    Ok((
+                        r.get::<_, i64>(0)?,
+                        r.get::<_, String>(1)?,
+                        r.get::<_, String>(2)?,
+                    ))
+                })?;
+                rows.collect::<rusqlite::Result<Vec<_>>>()?
+            };
+            for (row_id, bundle_id, stored) in rows {
+                if queue_crypto::key_id(&stored) == Some(keys.active.as_str()) {
+                    continue;
+                }
+                let plaintext = self.open_value(row_id, &bundle_id, &stored)?;
+                tx.execute(
+                    &format!("UPDATE {} SET bundle_json = ?2 WHERE id = ?1", table),
+                    params![row_id, self.seal(&plaintext, &bundle_id)?],
+                )?;
+                rewritten += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(rewritten)
+    }
+
+    /// Check every pending row before transmission: encrypted rows must
+    /// decrypt (GCM authenticates them) and the plaintext must still match
+    /// the content hash recorded at enqueue time.
+    pub fn verify(&self) -> Result<Vec<IntegrityIssue>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, bundle_id, bundle_json, content_hash
+             FROM pending_bundles WHERE status = 'pending' ORDER BY id",
+        )?;
+        let rows = stmt
+            .query_map([], |r| {
+                Ok((
+                    r.get::<_, i64>(0)?,
+                    r.get::<_, String>(1)?,
+                    r.get::<_, String>(2)?,
+                    r.get::<_, Option<String>>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut issues = Vec::new();
+        for (row_id, bundle_id, stored, hash) in rows {
+            let problem = match (self.open_value(row_id, &bundle_id, &stored), hash) {
+                (Err(e), _) => Some(format!("{:#}", e)),
+                (Ok(_), None) => Some("no content hash recorded".to_string()),
+                (Ok(plaintext), Some(hash)) if content_hash(&plaintext) != hash => {
+                    Some("content does not match its hash".to_string())
+                }
+                (Ok(_), Some(_)) => None,
+            };
+            if let Some(problem) = problem {
+                issues.push(IntegrityIssue {
+                    row_id,
+                    bundle_id,
+                    problem,
+                });
+            }
+        }
+        Ok(issues)
+    }
+
+    /// Ids of the keys rows are currently encrypted with.
+    pub fn key_ids_in_use(&self) -> Result<Vec<String>> {
+        // enc:v1:{id}:{ciphertext} — the id starts at character 8
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT substr(bundle_json, 8, instr(substr(bundle_json, 8), ':') - 1)
+             FROM pending_bundles WHERE bundle_json LIKE 'enc:v1:%'
+             UNION
+             SELECT DISTINCT substr(bundle_json, 8, instr(substr(bundle_json, 8), ':') - 1)
+             FROM sent_archive WHERE bundle_json LIKE 'enc:v1:%'",
+        )?;
+        let ids = stmt.query_map([], |r| r.get(0))?;
+        ids.collect::<rusqlite::Result<Vec<_>>>()
+            .context(BridgeError::Queue, "Failed to list queue key ids")
+    }
+
+    /// Replace the bundle of a pending row, e.g. after a correction; the row
+    /// keeps its place, priority and destination.
+    pub fn replace_pending(&self, row_id: i64, bundle_json: &str) -> Result<()> {
+        let bundle_id: String = self
+            .conn
+            .query_row(
+                "SELECT bundle_id FROM pending_bundles WHERE id = ?1 AND status = 'pending'",
+                params![row_id],
+                |r| r.get(0),
+            )
+            .optional()?
+            .with_context(BridgeError::Queue, || {
+                format!("Queue row {} is not pending", row_id)
+            })?;
+        let stored = self.seal(bundle_json, &bundle_id)?;
+        self.conn.execute(
+            "UPDATE pending_bundles SET bundle_json = ?2, content_hash = ?3 WHERE id = ?1",
+            params![row_id, stored, content_hash(bundle_json)],
+        )?;
+        Ok(())
     }
 
     /// Mark a bundle as successfully sent.
@@ -111,52 +707,254 @@ impl OfflineQueue {
         Ok(())
     }
 
-    /// Record a transmission failure and increment retry counter.
+    /// Record a transmission failure and increment retry counter; the bundle
+    /// is marked failed once it reaches the policy's retry limit.
     pub fn record_failure(&self, row_id: i64, error: &str) -> Result<()> {
         self.conn.execute(
             "UPDATE pending_bundles
              SET retry_count = retry_count + 1,
                  last_error  = ?2,
                  status      = CASE
-                     WHEN retry_count + 1 >= 10 THEN 'failed'
+                     WHEN retry_count + 1 >= ?3 THEN 'failed'
                      ELSE 'pending'
                  END
              WHERE id = ?1",
-            params![row_id, error],
+            params![row_id, error, self.policy.max_retries],
         )?;
         Ok(())
     }
 
-    /// Expire bundles older than 7 days (mark as failed, not deleted — for audit).
+    /// One transmission pass: hand every pending bundle inside the window to
+    /// `send` in queue order, marking it sent on success and recording the
+    /// failure otherwise. A failed send does not stop the pass.
+    pub fn flush<F>(&self, mut send: F) -> Result<FlushSummary>
+    where
+        F: FnMut(&PendingBundle) -> Result<()>,
+    {
+        let mut summary = FlushSummary::default();
+        for row in self.pending_within_window()? {
+            match send(&row) {
+                Ok(()) => {
+                    self.mark_sent(row.row_id)?;
+                    summary.sent += 1;
+                }
+                Err(e) => {
+                    self.record_failure(row.row_id, &format!("{:#}", e))?;
+                    summary.failed += 1;
+                }
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Expire bundles older than the transmission window (mark as failed,
+    /// not deleted — for audit).
     pub fn expire_old_bundles(&self) -> Result<usize> {
-        let cutoff = (Utc::now() - chrono::Duration::days(7)).to_rfc3339();
         let n = self.conn.execute(
             "UPDATE pending_bundles
-             SET status = 'failed', last_error = 'Transmission window (7 days) expired'
+             SET status = 'failed', last_error = ?2
              WHERE status = 'pending' AND created_at < ?1",
-            params![cutoff],
+            params![
+                self.policy.cutoff(),
+                format!(
+                    "Transmission window ({} days) expired",
+                    self.policy.transmission_window_days
+                )
+            ],
         )?;
         Ok(n)
     }
 
-    /// Queue statistics for monitoring / web UI.
-    pub fn stats(&self) -> Result<QueueStats> {
-        let pending: i64 = self.conn.query_row(
-            "SELECT COUNT(*) FROM pending_bundles WHERE status = 'pending'",
-            [],
-            |r| r.get(0),
+    /// Nightly upkeep: expire bundles past the transmission window, move
+    /// sent rows older than the policy's retention into the compressed
+    /// sent archive, then VACUUM. The report is also kept in the
+    /// maintenance log.
+    pub fn maintain(&self) -> Result<MaintenanceReport> {
+        let size_before = self.size_bytes()?;
+        let expired = self.expire_old_bundles()?;
+        let archived = self.archive_sent_rows()?;
+        self.conn
+            .execute_batch("VACUUM;")
+            .context(BridgeError::Queue, "Failed to vacuum queue db")?;
+        let report = MaintenanceReport {
+            ran_at: Utc::now().to_rfc3339(),
+            expired,
+            archived,
+            size_before,
+            size_after: self.size_bytes()?,
+        };
+        self.conn.execute(
+            "INSERT INTO maintenance_log (ran_at, report) VALUES (?1, ?2)",
+            params![report.ran_at, serde_json::to_string(&report)?],
         )?;
-        let sent: i64 = self.conn.query_row(
-            "SELECT COUNT(*) FROM pending_bundles WHERE status = 'sent'",
+        Ok(report)
+    }
+
+    /// Move sent rows past the retention period to `sent_archive`, keeping
+    /// their row id. Rows from before queue compression are compressed on
+    /// the way; encrypted rows are kept as they are.
+    fn archive_sent_rows(&self) -> Result<usize> {
+        let cutoff = (Utc::now() - chrono::Duration::days(self.policy.sent_retention_days.into()))
+            .to_rfc3339();
+        let now = Utc::now().to_rfc3339();
+        let tx = write_transaction(&self.conn)?;
+        let rows = {
+            let mut stmt = tx.prepare(
+                "SELECT id, bundle_json FROM pending_bundles
+                 WHERE status = 'sent' AND created_at < ?1",
+            )?;
+            let rows = stmt.query_map(params![cutoff], |r| {
+                Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?))
+            })?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        for (row_id, stored) in &rows {
+            let stored =
+                if queue_crypto::key_id(stored).is_some() || stored.starts_with(GZIP_PREFIX) {
+                    stored.clone()
+                } else {
+                    let compressed = gzip(&compact_json(stored.as_bytes()))?;
+                    format!("{}{}", GZIP_PREFIX, STANDARD.encode(compressed))
+                };
+            tx.execute(
+                "INSERT INTO sent_archive
+                    (id, bundle_id, bundle_json, content_hash, patient_id, clinic_id,
+                     destination_endpoint, created_at, archived_at)
+                 SELECT id, bundle_id, ?2, content_hash, patient_id, clinic_id,
+                        destination_endpoint, created_at, ?3
+                 FROM pending_bundles WHERE id = ?1",
+                params![row_id, stored, now],
+            )?;
+            tx.execute("DELETE FROM pending_bundles WHERE id = ?1", params![row_id])?;
+        }
+        tx.commit()
+            .context(BridgeError::Queue, "Failed to archive sent bundles")?;
+        Ok(rows.len())
+    }
+
+    /// Database size in bytes.
+    fn size_bytes(&self) -> Result<i64> {
+        Ok(self.conn.query_row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
             [],
             |r| r.get(0),
+        )?)
+    }
+
+    /// Whether a daemon scheduled to maintain the queue daily at `daily_at`
+    /// (local time) should run [`Self::maintain`] at `now`: past today's
+    /// time and not maintained since.
+    pub fn maintenance_due(&self, daily_at: NaiveTime, now: DateTime<Local>) -> Result<bool> {
+        let scheduled = now.date_naive().and_time(daily_at);
+        if now.naive_local() < scheduled {
+            return Ok(false);
+        }
+        let last: Option<String> =
+            self.conn
+                .query_row("SELECT MAX(ran_at) FROM maintenance_log", [], |r| r.get(0))?;
+        let last = last
+            .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+            .map(|t| t.with_timezone(&Local).naive_local());
+        Ok(last.is_none_or(|t| t < scheduled))
+    }
+
+    /// Alerts for `config` not raised yet: failed bundles, pending bundles
+    /// within `expiry_warning_hours` of the end of their window, and the
+    /// queue depth above its threshold. Once the depth is back under the
+    /// threshold its alert is cleared, so the next rise is raised again.
+    pub fn due_alerts(&self, config: &AlertConfig) -> Result<Vec<QueueAlert>> {
+        let mut alerts = Vec::new();
+        let mut stmt = self.conn.prepare(
+            "SELECT id, bundle_id, clinic_id, last_error FROM pending_bundles
+             WHERE status = 'failed'
+               AND id NOT IN (SELECT row_id FROM alert_log WHERE event = 'bundle_failed')
+             ORDER BY id",
         )?;
-        let failed: i64 = self.conn.query_row(
-            "SELECT COUNT(*) FROM pending_bundles WHERE status = 'failed'",
-            [],
+        let failed = stmt.query_map([], |r| {
+            Ok(QueueAlert::BundleFailed {
+                row_id: r.get(0)?,
+                bundle_id: r.get(1)?,
+                clinic_id: r.get(2)?,
+                last_error: r.get(3)?,
+            })
+        })?;
+        alerts.extend(failed.collect::<rusqlite::Result<Vec<_>>>()?);
+
+        let window = chrono::Duration::days(self.policy.transmission_window_days.into());
+        let warn_from =
+            Utc::now() - window + chrono::Duration::hours(config.expiry_warning_hours.into());
+        let mut stmt = self.conn.prepare(
+            "SELECT id, bundle_id, clinic_id, created_at FROM pending_bundles
+             WHERE status = 'pending' AND created_at < ?1
+               AND id NOT IN (SELECT row_id FROM alert_log WHERE event = 'window_expiring')
+             ORDER BY id",
+        )?;
+        let expiring = stmt.query_map(params![warn_from.to_rfc3339()], |r| {
+            Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get::<_, String>(3)?))
+        })?;
+        for row in expiring {
+            let (row_id, bundle_id, clinic_id, created_at) = row?;
+            let expires_at = DateTime::parse_from_rfc3339(&created_at)
+                .map(|t| (t + window).to_rfc3339())
+                .unwrap_or(created_at);
+            alerts.push(QueueAlert::WindowExpiring {
+                row_id,
+                bundle_id,
+                clinic_id,
+                expires_at,
+            });
+        }
+
+        if let Some(threshold) = config.depth_threshold {
+            let pending = self.count_with_status(BundleStatus::Pending)?;
+            if pending <= threshold {
+                self.conn
+                    .execute("DELETE FROM alert_log WHERE event = 'queue_depth'", [])?;
+            } else if !self.alert_raised("queue_depth", 0)? {
+                alerts.push(QueueAlert::QueueDepth { pending, threshold });
+            }
+        }
+        Ok(alerts)
+    }
+
+    /// Note that `alert` was delivered so [`Self::due_alerts`] skips it.
+    pub fn record_alert(&self, alert: &QueueAlert) -> Result<()> {
+        let (event, row_id) = alert.key();
+        self.conn.execute(
+            "INSERT OR IGNORE INTO alert_log (event, row_id, raised_at) VALUES (?1, ?2, ?3)",
+            params![event, row_id, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    fn alert_raised(&self, event: &str, row_id: i64) -> Result<bool> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT 1 FROM alert_log WHERE event = ?1 AND row_id = ?2",
+                params![event, row_id],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some())
+    }
+
+    /// Queue statistics for monitoring / web UI.
+    pub fn stats(&self) -> Result<QueueStats> {
+        Ok(QueueStats {
+            pending: self.count_with_status(BundleStatus::Pending)?,
+            sent: self.count_with_status(BundleStatus::Sent)?,
+            failed: self.count_with_status(BundleStatus::Failed)?,
+        })
+    }
+
+    fn count_with_status(&self, status: BundleStatus) -> Result<i64> {
+        let n = self.conn.query_row(
+            "SELECT COUNT(*) FROM pending_bundles WHERE status = ?1",
+            params![status.as_str()],
             |r| r.get(0),
         )?;
-        Ok(QueueStats { pending, sent, failed })
+        Ok(n)
     }
 }
 
@@ -170,6 +968,57 @@ pub struct PendingBundle {
     pub created_at: String,
     pub retry_count: i32,
     pub last_error: Option<String>,
+    pub priority: Priority,
+    pub destination_endpoint: Option<String>,
+    pub status: BundleStatus,
+}
+
+/// A queued row that failed [`OfflineQueue::verify`].
+#[derive(Debug)]
+pub struct IntegrityIssue {
+    pub row_id: i64,
+    pub bundle_id: String,
+    pub problem: String,
+}
+
+/// SHA-256 (hex) of a bundle's compacted JSON, so layout differences do
+/// not hide duplicates.
+fn content_hash(bundle_json: &str) -> String {
+    Sha256::digest(compact_json(bundle_json.as_bytes()))
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Outcome of one [`OfflineQueue::flush`] pass.
+#[derive(Debug, Default, PartialEq)]
+pub struct FlushSummary {
+    pub sent: usize,
+    /// Sends that failed this pass (retried later until the retry limit)
+    pub failed: usize,
+}
+
+/// Outcome of one [`OfflineQueue::maintain`] run.
+#[derive(Debug, Serialize)]
+pub struct MaintenanceReport {
+    pub ran_at: String,
+    /// Pending bundles past the transmission window, now failed
+    pub expired: usize,
+    /// Sent rows moved to the sent archive
+    pub archived: usize,
+    /// Database size before and after, in bytes
+    pub size_before: i64,
+    pub size_after: i64,
+}
+
+impl std::fmt::Display for MaintenanceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "expired {} bundle(s), archived {} sent row(s), database {} → {} bytes",
+            self.expired, self.archived, self.size_before, self.size_after
+        )
+    }
 }
 
 #[derive(Debug)]
@@ -190,11 +1039,67 @@ mod tests {
         (q, f)
     }
 
+    #[test]
+    fn waits_for_another_writer_instead_of_failing() {
+        let (q, f) = open_temp_queue();
+        let mode: String = q
+            .conn
+            .query_row("PRAGMA journal_mode", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+
+        // Another process holds the write lock for a moment
+        let path = f.path().to_path_buf();
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let worker = std::thread::spawn(move || {
+            let other = OfflineQueue::open(&path).unwrap();
+            let tx = write_transaction(&other.conn).unwrap();
+            locked_tx.send(()).unwrap();
+            std::thread::sleep(Duration::from_millis(300));
+            tx.commit().unwrap();
+        });
+        locked_rx.recv().unwrap();
+        q.enqueue("b1", "{}", "p1", "c1").unwrap();
+        worker.join().unwrap();
+        assert_eq!(q.stats().unwrap().pending, 1);
+    }
+
+    #[test]
+    fn stores_bundles_compressed() {
+        let (q, _f) = open_temp_queue();
+        let bundle = serde_json::json!({
+            "resourceType": "Bundle",
+            "id": "b1",
+            "entry": (0..50)
+                .map(|i| serde_json::json!({"resource": {"resourceType": "Observation", "id": i}}))
+                .collect::<Vec<_>>()
+        });
+        let pretty = serde_json::to_string_pretty(&bundle).unwrap();
+        q.enqueue("b1", &pretty, "p1", "c1").unwrap();
+
+        let raw: String = q
+            .conn
+            .query_row("SELECT bundle_json FROM pending_bundles", [], |r| r.get(0))
+            .unwrap();
+        assert!(raw.starts_with(GZIP_PREFIX));
+        assert!(
+            raw.len() * 5 < pretty.len(),
+            "{} vs {}",
+            raw.len(),
+            pretty.len()
+        );
+
+        let rows = q.pending_within_window().unwrap();
+        let restored: serde_json::Value = serde_json::from_str(&rows[0].bundle_json).unwrap();
+        assert_eq!(restored, bundle);
+        assert!(q.verify().unwrap().is_empty());
+    }
+
     #[test]
     fn enqueue_and_list() {
         let (q, _f) = open_temp_queue();
-        q.enqueue("b1", "{}", "p1", "c1").unwrap();
-        q.enqueue("b2", "{}", "p2", "c1").unwrap();
+        q.enqueue("b1", r#"{"id":"b1"}"#, "p1", "c1").unwrap();
+        q.enqueue("b2", r#"{"id":"b2"}"#, "p2", "c1").unwrap();
         let rows = q.pending_within_window().unwrap();
         assert_eq!(rows.len(), 2);
     }
@@ -210,6 +1115,36 @@ mod tests {
         assert_eq!(stats.sent, 1);
     }
 
+    #[test]
+    fn find_filters_by_patient_clinic_status_and_date() {
+        let (q, _f) = open_temp_queue();
+        let sent = q.enqueue("b1", r#"{"id":"b1"}"#, "p1", "c1").unwrap();
+        q.enqueue("b2", r#"{"id":"b2"}"#, "p2", "c1").unwrap();
+        q.enqueue("b3", r#"{"id":"b3"}"#, "p1", "c2").unwrap();
+        q.mark_sent(sent).unwrap();
+        let ids = |rows: Vec<PendingBundle>| -> Vec<String> {
+            rows.into_iter().map(|r| r.bundle_id).collect()
+        };
+
+        assert_eq!(ids(q.find_by_patient("p1").unwrap()), ["b1", "b3"]);
+        assert_eq!(ids(q.find_by_clinic("c1").unwrap()), ["b1", "b2"]);
+        let sent_rows = q.find_by_status(BundleStatus::Sent).unwrap();
+        assert_eq!(sent_rows[0].bundle_json, r#"{"id":"b1"}"#);
+        assert_eq!(ids(sent_rows), ["b1"]);
+        let all = q.find(&QueueFilter::default()).unwrap();
+        let day = |row: &PendingBundle| row.created_at[..10].parse::<NaiveDate>().unwrap();
+        let (first, last) = (day(&all[0]), day(&all[2]));
+        assert_eq!(q.find_by_date_range(first, last).unwrap().len(), 3);
+        let before = first.pred_opt().unwrap();
+        assert!(q.find_by_date_range(before, before).unwrap().is_empty());
+        let filter = QueueFilter {
+            patient_id: Some("p1".into()),
+            status: Some(BundleStatus::Pending),
+            ..QueueFilter::default()
+        };
+        assert_eq!(ids(q.find(&filter).unwrap()), ["b3"]);
+    }
+
     #[test]
     fn record_failure_increments_retry() {
         let (q, _f) = open_temp_queue();
@@ -219,4 +1154,282 @@ mod tests {
         assert_eq!(rows[0].retry_count, 1);
         assert_eq!(rows[0].last_error.as_deref(), Some("timeout"));
     }
+
+    #[test]
+    fn flush_marks_sent_and_keeps_failures_pending() {
+        let (q, _f) = open_temp_queue();
+        q.enqueue("b1", r#"{"id":"b1"}"#, "p1", "c1").unwrap();
+        q.enqueue("b2", r#"{"id":"b2"}"#, "p2", "c1").unwrap();
+        let summary = q
+            .flush(|row| {
+                if row.bundle_id == "b2" {
+                    bail!(Network, "HTTP 503");
+                }
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(summary, FlushSummary { sent: 1, failed: 1 });
+
+        let rows = q.pending_within_window().unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].bundle_id, "b2");
+        assert_eq!(rows[0].last_error.as_deref(), Some("HTTP 503"));
+    }
+
+    #[test]
+    fn maintain_archives_old_sent_rows_and_logs_the_report() {
+        let (q, _f) = open_temp_queue();
+        let old = q.enqueue("b1", r#"{"id":"b1"}"#, "p1", "c1").unwrap();
+        let recent = q.enqueue("b2", r#"{"id":"b2"}"#, "p2", "c1").unwrap();
+        q.enqueue("b3", r#"{"id":"b3"}"#, "p3", "c1").unwrap();
+        q.mark_sent(old).unwrap();
+        q.mark_sent(recent).unwrap();
+        let long_ago = (Utc::now() - chrono::Duration::days(31)).to_rfc3339();
+        q.conn
+            .execute(
+                "UPDATE pending_bundles SET created_at = ?2 WHERE id = ?1",
+                params![old, long_ago],
+            )
+            .unwrap();
+
+        let report = q.maintain().unwrap();
+        assert_eq!((report.expired, report.archived), (0, 1));
+        assert_eq!(q.stats().unwrap().sent, 1);
+        let archived: String = q
+            .conn
+            .query_row(
+                "SELECT bundle_json FROM sent_archive WHERE id = ?1",
+                params![old],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert!(archived.starts_with(GZIP_PREFIX));
+        let logged: i64 = q
+            .conn
+            .query_row("SELECT COUNT(*) FROM maintenance_log", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(logged, 1);
+
+        // An archived bundle still counts as sent
+        let again = q.enqueue("b1", r#"{"id":"b1"}"#, "p1", "c1");
+        assert!(again.unwrap_err().to_string().contains("already queued"));
+    }
+
+    #[test]
+    fn maintenance_is_due_once_a_day_after_its_time() {
+        use chrono::TimeZone;
+
+        let (q, _f) = open_temp_queue();
+        let at = NaiveTime::from_hms_opt(2, 0, 0).unwrap();
+        let local = |d, h| Local.with_ymd_and_hms(2026, 3, d, h, 0, 0).unwrap();
+        assert!(!q.maintenance_due(at, local(10, 1)).unwrap());
+        assert!(q.maintenance_due(at, local(10, 3)).unwrap());
+
+        let log = |ran_at: DateTime<Local>| {
+            q.conn
+                .execute(
+                    "INSERT INTO maintenance_log (ran_at, report) VALUES (?1, '{}')",
+                    params![ran_at.to_rfc3339()],
+                )
+                .unwrap();
+        };
+        log(local(10, 3));
+        assert!(!q.maintenance_due(at, local(10, 23)).unwrap());
+        assert!(!q.maintenance_due(at, local(11, 1)).unwrap());
+        assert!(q.maintenance_due(at, local(11, 2)).unwrap());
+    }
+
+    #[test]
+    fn due_alerts_are_raised_once_and_depth_rearms() {
+        let q = OfflineQueue::open_in_memory().unwrap();
+        let config = AlertConfig {
+            depth_threshold: Some(1),
+            ..AlertConfig::default()
+        };
+        let failed = q.enqueue("b1", "{}", "p1", "c1").unwrap();
+        q.enqueue("b2", r#"{"n":2}"#, "p2", "c1").unwrap();
+        let old = q.enqueue("b3", r#"{"n":3}"#, "p3", "c1").unwrap();
+        q.conn
+            .execute(
+                "UPDATE pending_bundles SET created_at = ?2 WHERE id = ?1",
+                params![
+                    old,
+                    (Utc::now() - chrono::Duration::days(6) - chrono::Duration::hours(1))
+                        .to_rfc3339()
+                ],
+            )
+            .unwrap();
+        for _ in 0..10 {
+            q.record_failure(failed, "HTTP 500").unwrap();
+        }
+
+        let alerts = q.due_alerts(&config).unwrap();
+        let keys: Vec<_> = alerts.iter().map(QueueAlert::key).collect();
+        assert_eq!(
+            keys,
+            [
+                ("bundle_failed", failed),
+                ("window_expiring", old),
+                ("queue_depth", 0)
+            ]
+        );
+        for alert in &alerts {
+            q.record_alert(alert).unwrap();
+        }
+        assert!(q.due_alerts(&config).unwrap().is_empty());
+
+        // Back under the threshold, then over it again
+        q.mark_sent(old).unwrap();
+        assert!(q.due_alerts(&config).unwrap().is_empty());
+        q.enqueue("b4", r#"{"n":4}"#, "p4", "c1").unwrap();
+        assert_eq!(
+            q.due_alerts(&config).unwrap(),
+            [QueueAlert::QueueDepth {
+                pending: 2,
+                threshold: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn encrypted_rows_hide_bundle_and_survive_rotation() {
+        let f = NamedTempFile::new().unwrap();
+        let mut keys = QueueKeys::generate();
+        let q = OfflineQueue::open(f.path())
+            .unwrap()
+            .with_keys(keys.clone());
+        q.enqueue("b1", r#"{"name":"Wanjiku"}"#, "p1", "c1")
+            .unwrap();
+        let raw: String = q
+            .conn
+            .query_row("SELECT bundle_json FROM pending_bundles", [], |r| r.get(0))
+            .unwrap();
+        assert!(!raw.contains("Wanjiku"));
+
+        // Rotate: new active key, re-encrypt, then retire the old one
+        let old = keys.active.clone();
+        keys.rotate();
+        let q = OfflineQueue::open(f.path())
+            .unwrap()
+            .with_keys(keys.clone());
+        q.enqueue("b2", r#"{"id":"b2"}"#, "p2", "c1").unwrap();
+        assert_eq!(q.reencrypt().unwrap(), 1);
+        assert_eq!(q.key_ids_in_use().unwrap(), vec![keys.active.clone()]);
+        assert_eq!(keys.retire_except(&q.key_ids_in_use().unwrap()), vec![old]);
+
+        let q = OfflineQueue::open(f.path()).unwrap().with_keys(keys);
+        let rows = q.pending_within_window().unwrap();
+        assert_eq!(rows[0].bundle_json, r#"{"name":"Wanjiku"}"#);
+
+        let without_keys = OfflineQueue::open(f.path()).unwrap();
+        assert!(without_keys.pending_within_window().is_err());
+    }
+
+    #[test]
+    fn migrates_queue_created_before_schema_versioning() {
+        let f = NamedTempFile::new().unwrap();
+        Connection::open(f.path())
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE pending_bundles (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT, bundle_id TEXT NOT NULL,
+                    bundle_json TEXT NOT NULL, patient_id TEXT NOT NULL,
+                    clinic_id TEXT NOT NULL, created_at TEXT NOT NULL,
+                    retry_count INTEGER NOT NULL DEFAULT 0, last_error TEXT,
+                    status TEXT NOT NULL DEFAULT 'pending');
+                 INSERT INTO pending_bundles (bundle_id, bundle_json, patient_id, clinic_id, created_at)
+                 VALUES ('b0', '{}', 'p0', 'c1', '2099-01-01T00:00:00+00:00');",
+            )
+            .unwrap();
+
+        let q = OfflineQueue::open(f.path()).unwrap();
+        assert_eq!(q.schema_version().unwrap(), MIGRATIONS.len() as u32);
+        assert_eq!(q.pending_within_window().unwrap()[0].bundle_id, "b0");
+        q.enqueue("b1", r#"{"id":"b1"}"#, "p1", "c1").unwrap();
+        assert_eq!(q.stats().unwrap().pending, 2);
+    }
+
+    #[test]
+    fn claims_jump_the_queue_and_endpoints_are_separate() {
+        let (q, _f) = open_temp_queue();
+        let national = QueueRouting {
+            priority: Priority::Routine,
+            destination_endpoint: Some("national-shr".to_string()),
+        };
+        let claims = QueueRouting {
+            priority: Priority::Claim,
+            destination_endpoint: Some("sha-claims".to_string()),
+        };
+        q.enqueue("shr-1", r#"{"id":"shr-1"}"#, "p1", "c1").unwrap();
+        q.enqueue_routed("shr-1", r#"{"id":"shr-1"}"#, "p1", "c1", &national)
+            .unwrap();
+        q.enqueue_routed("claim-1", r#"{"id":"claim-1"}"#, "p1", "c1", &claims)
+            .unwrap();
+
+        let order: Vec<_> = q
+            .pending_within_window()
+            .unwrap()
+            .into_iter()
+            .map(|p| p.bundle_id)
+            .collect();
+        assert_eq!(order, ["claim-1", "shr-1", "shr-1"]);
+
+        let national_rows = q.pending_for_endpoint(Some("national-shr")).unwrap();
+        assert_eq!(national_rows.len(), 1);
+        assert_eq!(q.pending_for_endpoint(None).unwrap().len(), 1);
+        assert_eq!(q.pending_endpoints().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn policy_sets_window_and_retry_limit() {
+        let (q, _f) = open_temp_queue();
+        let q = q.with_policy(QueuePolicy {
+            transmission_window_days: 14,
+            max_retries: 2,
+            ..QueuePolicy::default()
+        });
+        let old = (Utc::now() - chrono::Duration::days(10)).to_rfc3339();
+        q.conn
+            .execute(
+                "INSERT INTO pending_bundles
+                    (bundle_id, bundle_json, patient_id, clinic_id, created_at)
+                 VALUES ('b0', '{}', 'p0', 'c1', ?1)",
+                params![old],
+            )
+            .unwrap();
+        // Ten days old: outside the default window, inside a 14-day one
+        assert_eq!(q.pending_within_window().unwrap().len(), 1);
+        assert_eq!(q.expire_old_bundles().unwrap(), 0);
+
+        let id = q.enqueue("b1", r#"{"id":"b1"}"#, "p1", "c1").unwrap();
+        q.record_failure(id, "timeout").unwrap();
+        assert_eq!(q.stats().unwrap().failed, 0);
+        q.record_failure(id, "timeout").unwrap();
+        assert_eq!(q.stats().unwrap().failed, 1);
+    }
+
+    #[test]
+    fn rejects_duplicates_and_detects_tampering() {
+        let (q, _f) = open_temp_queue();
+        let id = q.enqueue("b1", r#"{"id":"b1"}"#, "p1", "c1").unwrap();
+        assert!(q.enqueue("b1", r#"{"id":"b1","v":2}"#, "p1", "c1").is_err());
+        assert!(q.enqueue("b9", r#"{"id":"b1"}"#, "p1", "c1").is_err());
+        assert!(q.verify().unwrap().is_empty());
+
+        q.conn
+            .execute(
+                "UPDATE pending_bundles SET bundle_json = '{\"id\":\"bX\"}' WHERE id = ?1",
+                params![id],
+            )
+            .unwrap();
+        let issues = q.verify().unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].row_id, id);
+
+        // A failed bundle may be queued again
+        q.conn
+            .execute("UPDATE pending_bundles SET status = 'failed'", [])
+            .unwrap();
+        assert!(q.enqueue("b1", r#"{"id":"b1"}"#, "p1", "c1").is_ok());
+    }
 }
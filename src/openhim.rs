@@ -0,0 +1,155 @@
+//! Submission through an OpenHIM interoperability layer.
+//!
+//! Most county SHRs are reached through OpenHIM channels rather than HAPI
+//! directly: the bundle goes to `{base_url}{channel path}` and the client
+//! authenticates with a certificate (mutual TLS), basic auth or a custom
+//! token, as its OpenHIM client record says. The config file holds no
+//! secrets; passwords and tokens come from the environment.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Deserialize;
+
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::http::TlsOptions;
+use crate::upload::UploadOptions;
+
+/// How the OpenHIM client authenticates besides (or instead of) its
+/// certificate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpenHimAuth {
+    /// Certificate only (or an open channel)
+    #[default]
+    None,
+    /// `OPENHIM_CLIENT_ID` / `OPENHIM_CLIENT_PASSWORD`
+    Basic,
+    /// `Authorization: Custom {OPENHIM_CLIENT_TOKEN}`
+    Custom,
+}
+
+/// `--openhim` config file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenHimConfig {
+    /// Channel listener, e.g. `https://openhim.county.go.ke:5000`
+    pub base_url: String,
+    /// Channel name → path; `default` is used unless another is chosen
+    pub channels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub client_cert: Option<PathBuf>,
+    #[serde(default)]
+    pub client_key: Option<PathBuf>,
+    #[serde(default)]
+    pub ca_cert: Option<PathBuf>,
+    #[serde(default)]
+    pub auth: OpenHimAuth,
+    /// Further fixed headers a mediator expects
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl OpenHimConfig {
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path).with_context(BridgeError::Config, || {
+            format!("Failed to read OpenHIM config {:?}", path)
+        })?;
+        let config: Self = serde_json::from_str(&raw)
+            .context(BridgeError::Config, "Invalid OpenHIM config JSON")?;
+        if config.client_key.is_some() && config.client_cert.is_none() {
+            bail!(Config, "OpenHIM config has client_key without client_cert");
+        }
+        Ok(config)
+    }
+
+    /// Full URL of a channel.
+    pub fn endpoint(&self, channel: &str) -> Result<String> {
+        let path = self
+            .channels
+            .get(channel)
+            .with_context(BridgeError::Config, || {
+                format!("OpenHIM config has no channel {:?}", channel)
+            })?;
+        Ok(format!(
+            "{}/{}",
+            self.base_url.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        ))
+    }
+
+    /// `options` with this client's certificate and headers added.
+    pub fn apply(&self, mut options: UploadOptions) -> Result<UploadOptions> {
+        if self.client_cert.is_some() || self.ca_cert.is_some() {
+            options.tls = Some(TlsOptions {
+                client_cert: self.client_cert.clone(),
+                client_key: self.client_key.clone(),
+                ca_cert: self.ca_cert.clone(),
+            });
+        }
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .filter(|v| !v.is_empty())
+                .with_context(BridgeError::Config, || {
+                    format!("OpenHIM {:?} auth needs {}", self.auth, name)
+                })
+        };
+        match self.auth {
+            OpenHimAuth::None => {}
+            OpenHimAuth::Basic => {
+                let credentials = format!(
+                    "{}:{}",
+                    var("OPENHIM_CLIENT_ID")?,
+                    var("OPENHIM_CLIENT_PASSWORD")?
+                );
+                options.headers.push(format!(
+                    "Authorization: Basic {}",
+                    STANDARD.encode(credentials)
+                ));
+            }
+            OpenHimAuth::Custom => options.headers.push(format!(
+                "Authorization: Custom {}",
+                var("OPENHIM_CLIENT_TOKEN")?
+            )),
+        }
+        options.headers.extend(
+            self.headers
+                .iter()
+                .map(|(name, value)| format!("{}: {}", name, value)),
+        );
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_channel_paths_and_adds_client_cert() {
+        let config: OpenHimConfig = serde_json::from_str(
+            r#"{"base_url": "https://openhim.example:5000/",
+                "channels": {"default": "/shr/fhir", "claims": "sha/claims"},
+                "client_cert": "/etc/kfb/client.pem",
+                "headers": {"X-Facility": "KEN-NAKURU-002"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.endpoint("default").unwrap(),
+            "https://openhim.example:5000/shr/fhir"
+        );
+        assert_eq!(
+            config.endpoint("claims").unwrap(),
+            "https://openhim.example:5000/sha/claims"
+        );
+        assert!(config.endpoint("labs").is_err());
+
+        let options = config.apply(UploadOptions::default()).unwrap();
+        assert_eq!(
+            options.tls.unwrap().client_cert,
+            Some(PathBuf::from("/etc/kfb/client.pem"))
+        );
+        assert_eq!(options.headers, ["X-Facility: KEN-NAKURU-002"]);
+    }
+}
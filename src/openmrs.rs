@@ -0,0 +1,574 @@
+//! Import from a KenyaEMR (OpenMRS) server over its REST API.
+//!
+//! Encounters in a date range are fetched from `/ws/rest/v1/encounter` and
+//! folded into one [`KenyanPatient`] per patient, one visit per patient per
+//! day — KenyaEMR records triage and consultation as separate encounters.
+//! Obs are recognised by concept UUID (CIEL by default, overridable for
+//! local concept dictionaries). Visits that lack what the Kenyan schema
+//! requires (temperature, blood pressure, weight, a diagnosis and a
+//! treatment) are left out and counted, never guessed.
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::NaiveDate;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::http::{self, url_encode, HttpRequest};
+use crate::kenyan::counties::official_location;
+use crate::kenyan::schema::{
+    gender_code, DiagnosisCategory, KenyanPatient, Location, Names, Visit, Vitals,
+};
+
+/// Custom representation: only the fields the conversion reads.
+const ENCOUNTER_REPRESENTATION: &str = "custom:(uuid,encounterDatetime,\
+patient:(uuid,identifiers:(identifier,identifierType:(display)),\
+person:(gender,birthdate,birthdateEstimated,preferredName:(givenName,middleName,familyName),\
+preferredAddress:(countyDistrict,stateProvince),attributes:(value,attributeType:(display)))),\
+obs:(concept:(uuid,display),value,groupMembers:(concept:(uuid,display),value)))";
+const PAGE_SIZE: u32 = 100;
+
+/// CIEL concept UUIDs are the concept id padded with `A` to 36 characters.
+fn ciel(id: &str) -> String {
+    format!("{:A<36}", id)
+}
+
+/// Concept UUIDs read for each Kenyan field.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ObsConcepts {
+    pub temperature: Vec<String>,
+    pub systolic: Vec<String>,
+    pub diastolic: Vec<String>,
+    pub weight: Vec<String>,
+    pub pulse: Vec<String>,
+    pub spo2: Vec<String>,
+    /// Optional in concept-map files written before height and MUAC were read
+    #[serde(default)]
+    pub height: Vec<String>,
+    #[serde(default)]
+    pub muac: Vec<String>,
+    pub complaint: Vec<String>,
+    pub diagnosis: Vec<String>,
+    pub treatment: Vec<String>,
+    /// Optional for the same reason
+    #[serde(default)]
+    pub clinical_note: Vec<String>,
+}
+
+impl Default for ObsConcepts {
+    fn default() -> Self {
+        let ciel_all = |ids: &[&str]| ids.iter().map(|id| ciel(id)).collect();
+        Self {
+            temperature: ciel_all(&["5088"]),
+            systolic: ciel_all(&["5085"]),
+            diastolic: ciel_all(&["5086"]),
+            weight: ciel_all(&["5089"]),
+            pulse: ciel_all(&["5087"]),
+            spo2: ciel_all(&["5092"]),
+            height: ciel_all(&["5090"]),
+            muac: ciel_all(&["1343"]),
+            // Chief complaint (coded), chief complaint (text)
+            complaint: ciel_all(&["5219", "160531"]),
+            // Problem added, diagnosis (non-coded)
+            diagnosis: ciel_all(&["6042", "161602"]),
+            // Medication orders, drug prescribed (text)
+            treatment: ciel_all(&["1282", "160632"]),
+            // Text of encounter note
+            clinical_note: ciel_all(&["162169"]),
+        }
+    }
+}
+
+impl ObsConcepts {
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path).with_context(BridgeError::Io, || {
+            format!("Failed to read OpenMRS concept map {:?}", path)
+        })?;
+        serde_json::from_str(&raw).context(BridgeError::Config, "Invalid OpenMRS concept map JSON")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Display {
+    display: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Concept {
+    uuid: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Obs {
+    concept: Concept,
+    #[serde(default)]
+    value: Value,
+    #[serde(default)]
+    group_members: Option<Vec<Obs>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Identifier {
+    identifier: String,
+    identifier_type: Display,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PersonName {
+    given_name: Option<String>,
+    middle_name: Option<String>,
+    family_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Address {
+    /// KenyaEMR keeps the county here ...
+    county_district: Option<String>,
+    /// ... and the sub-county here
+    state_province: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Attribute {
+    value: Value,
+    attribute_type: Display,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Person {
+    gender: Option<String>,
+    birthdate: Option<String>,
+    #[serde(default)]
+    birthdate_estimated: bool,
+    preferred_name: Option<PersonName>,
+    preferred_address: Option<Address>,
+    #[serde(default)]
+    attributes: Vec<Attribute>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Patient {
+    uuid: String,
+    #[serde(default)]
+    identifiers: Vec<Identifier>,
+    person: Person,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Encounter {
+    encounter_datetime: String,
+    patient: Patient,
+    #[serde(default)]
+    obs: Vec<Obs>,
+}
+
+/// Text of an obs value: coded answers by display name.
+fn value_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Object(o) => o.get("display").and_then(value_text),
+        _ => None,
+    }
+}
+
+/// Obs of one patient-day, flattened out of obs groups.
+#[derive(Default)]
+struct DayObs {
+    values: Vec<(String, Value)>,
+}
+
+impl DayObs {
+    fn add(&mut self, obs: Vec<Obs>) {
+        for o in obs {
+            match o.group_members {
+                Some(members) => self.add(members),
+                None => self.values.push((o.concept.uuid, o.value)),
+            }
+        }
+    }
+
+    fn texts(&self, concepts: &[String]) -> Vec<String> {
+        let mut found: Vec<String> = Vec::new();
+        for (uuid, value) in &self.values {
+            if let Some(text) = concepts.contains(uuid).then(|| value_text(value)).flatten() {
+                if !found.contains(&text) {
+                    found.push(text);
+                }
+            }
+        }
+        found
+    }
+
+    /// Latest recorded number (obs are in encounter order).
+    fn number(&self, concepts: &[String]) -> Option<f64> {
+        self.values
+            .iter()
+            .rev()
+            .filter(|(uuid, _)| concepts.contains(uuid))
+            .find_map(|(_, value)| value.as_f64())
+    }
+
+    fn visit(&self, date: NaiveDate, concepts: &ObsConcepts) -> Option<Visit> {
+        let joined = |c: &[String]| Some(self.texts(c).join("; ")).filter(|s| !s.is_empty());
+        Some(Visit {
+            date: date.to_string(),
+            complaint: joined(&concepts.complaint).unwrap_or_default(),
+            vitals: Vitals {
+                temperature_celsius: self.number(&concepts.temperature)?,
+                bp_systolic: self.number(&concepts.systolic)?.round() as i32,
+                bp_diastolic: self.number(&concepts.diastolic)?.round() as i32,
+                weight_kg: self.number(&concepts.weight)?,
+                pulse_rate: self.number(&concepts.pulse).map(|v| v.round() as i32),
+                o2_saturation: self.number(&concepts.spo2),
+                height_cm: self.number(&concepts.height),
+                muac_cm: self.number(&concepts.muac),
+                devices: Vec::new(),
+            },
+            diagnosis: joined(&concepts.diagnosis)?,
+            diagnosis_category: DiagnosisCategory::default(),
+            diagnosis_severity: None,
+            onset_date: None,
+            treatment: joined(&concepts.treatment)?,
+            clinical_note: joined(&concepts.clinical_note),
+            attending_puid: None,
+            attending_cadre: None,
+            vitals_taken_by: None,
+            sha_member_number: None,
+            sha_intervention_code: None,
+            attachments: Vec::new(),
+            lab_orders: Vec::new(),
+            imaging_orders: Vec::new(),
+            visit_id: None,
+            previous_visit_id: None,
+            programme: None,
+            hiv_who_stage: None,
+            tb_phase: None,
+            regimen: None,
+            review_date: None,
+            next_appointment_date: None,
+            anc: None,
+            screening: None,
+            triage_category: None,
+            danger_signs: Vec::new(),
+            department: None,
+        })
+    }
+}
+
+/// Records built from a batch of encounters, with what had to be left out.
+#[derive(Debug, Default)]
+pub struct OpenMrsImport {
+    pub records: Vec<KenyanPatient>,
+    /// Patient-days missing vitals, diagnosis or treatment
+    pub incomplete_visits: usize,
+    /// Patients without the identifiers or demographics a record needs
+    pub skipped_patients: usize,
+}
+
+/// Fold encounters (REST `results`, any order) into Kenyan records for
+/// facility `clinic_id`.
+pub fn encounters_to_kenyan(
+    encounters: &[Value],
+    clinic_id: &str,
+    concepts: &ObsConcepts,
+) -> Result<OpenMrsImport> {
+    // patient uuid → (patient, day → obs)
+    let mut patients: BTreeMap<String, (Patient, BTreeMap<NaiveDate, DayObs>)> = BTreeMap::new();
+    for raw in encounters {
+        let encounter: Encounter = serde_json::from_value(raw.clone())
+            .context(BridgeError::Mapping, "Unexpected OpenMRS encounter shape")?;
+        let date = parse_date(&encounter.encounter_datetime)
+            .context(BridgeError::Mapping, "Invalid OpenMRS encounterDatetime")?;
+        let (_, days) = patients
+            .entry(encounter.patient.uuid.clone())
+            .or_insert_with(|| (encounter.patient, BTreeMap::new()));
+        days.entry(date).or_default().add(encounter.obs);
+    }
+
+    let mut import = OpenMrsImport::default();
+    for (patient, days) in patients.into_values() {
+        let visits: Vec<Visit> = days
+            .iter()
+            .filter_map(|(date, obs)| obs.visit(*date, concepts))
+            .collect();
+        import.incomplete_visits += days.len() - visits.len();
+        if visits.is_empty() {
+            continue;
+        }
+        match patient_record(&patient, clinic_id, visits) {
+            Some(record) => import.records.push(record),
+            None => import.skipped_patients += 1,
+        }
+    }
+    Ok(import)
+}
+
+fn parse_date(datetime: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(datetime.get(..10)?, "%Y-%m-%d").ok()
+}
+
+fn patient_record(patient: &Patient, clinic_id: &str, visits: Vec<Visit>) -> Option<KenyanPatient> {
+    let identifier = |types: &[&str]| {
+        types.iter().find_map(|t| {
+            patient
+                .identifiers
+                .iter()
+                .find(|i| i.identifier_type.display.eq_ignore_ascii_case(t))
+                .map(|i| i.identifier.clone())
+        })
+    };
+    let person = &patient.person;
+    let name = person.preferred_name.as_ref()?;
+    let address = person.preferred_address.as_ref();
+    let mut data_quality = Vec::new();
+    let phone = person
+        .attributes
+        .iter()
+        .find(|a| {
+            a.attribute_type
+                .display
+                .eq_ignore_ascii_case("Telephone contact")
+        })
+        .and_then(|a| value_text(&a.value))
+        .unwrap_or_default();
+    Some(KenyanPatient {
+        clinic_id: clinic_id.to_string(),
+        patient_number: identifier(&["Patient Clinic Number", "OpenMRS ID"])?,
+        national_id: identifier(&["National ID"])?,
+        names: Names {
+            first: name.given_name.clone()?,
+            middle: name.middle_name.clone().unwrap_or_default(),
+            last: name.family_name.clone()?,
+        },
+        gender: gender_code(
+            person.gender.as_deref().unwrap_or_default(),
+            &mut data_quality,
+        ),
+        date_of_birth: parse_date(person.birthdate.as_deref()?)?,
+        birth_date_estimated: person.birthdate_estimated,
+        phone,
+        location: official_location(
+            Location {
+                county: address
+                    .and_then(|a| a.county_district.clone())
+                    .unwrap_or_default(),
+                subcounty: address
+                    .and_then(|a| a.state_province.clone())
+                    .unwrap_or_default(),
+            },
+            &mut data_quality,
+        ),
+        visits,
+        photo: None,
+        biometrics: Vec::new(),
+        immunizations: Vec::new(),
+        data_quality,
+    })
+}
+
+/// KenyaEMR REST client (HTTP basic auth).
+#[derive(Clone)]
+pub struct OpenMrsClient {
+    /// Server root, e.g. `https://emr.example.go.ke/openmrs`
+    pub base_url: String,
+    pub username: String,
+    password: String,
+    pub timeout_secs: u32,
+}
+
+// Hand-written so the password never ends up in debug output.
+impl std::fmt::Debug for OpenMrsClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpenMrsClient")
+            .field("base_url", &self.base_url)
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OpenMrsClient {
+    /// Client with credentials from `OPENMRS_USERNAME` / `OPENMRS_PASSWORD`.
+    pub fn from_env(base_url: &str) -> Result<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let (Some(username), Some(password)) = (var("OPENMRS_USERNAME"), var("OPENMRS_PASSWORD"))
+        else {
+            bail!(
+                Config,
+                "OpenMRS import needs OPENMRS_USERNAME and OPENMRS_PASSWORD"
+            );
+        };
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            username,
+            password,
+            timeout_secs: 60,
+        })
+    }
+
+    /// Every encounter dated within `[from, to]`, following result pages.
+    pub fn encounters(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Value>> {
+        let mut url = format!(
+            "{}/ws/rest/v1/encounter?fromdate={}&todate={}&limit={}&v={}",
+            self.base_url,
+            from,
+            to,
+            PAGE_SIZE,
+            url_encode(ENCOUNTER_REPRESENTATION)
+        );
+        let auth = STANDARD.encode(format!("{}:{}", self.username, self.password));
+        let mut encounters = Vec::new();
+        loop {
+            let response = http::send(&HttpRequest {
+                method: "GET",
+                url: &url,
+                headers: vec![
+                    format!("Authorization: Basic {}", auth),
+                    "Accept: application/json".to_string(),
+                ],
+                body: None,
+                timeout_secs: self.timeout_secs,
+                tls: None,
+            })?;
+            if !response.is_success() {
+                bail!(
+                    Network,
+                    "OpenMRS encounter search returned HTTP {}",
+                    response.status
+                );
+            }
+            let page: Value = serde_json::from_str(&response.body)
+                .context(BridgeError::Network, "Invalid OpenMRS response")?;
+            encounters.extend(page["results"].as_array().cloned().unwrap_or_default());
+            let next = page["links"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|l| l["rel"] == "next")
+                .and_then(|l| l["uri"].as_str());
+            match next {
+                Some(next) => url = next.to_string(),
+                None => break,
+            }
+        }
+        Ok(encounters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn encounter(datetime: &str, obs: Value) -> Value {
+        json!({
+            "uuid": "e-1",
+            "encounterDatetime": datetime,
+            "patient": {
+                "uuid": "p-1",
+                "identifiers": [
+                    {"identifier": "MRN-1", "identifierType": {"display": "OpenMRS ID"}},
+                    {"identifier": "27845612", "identifierType": {"display": "National ID"}}
+                ],
+                "person": {
+                    "gender": "F",
+                    "birthdate": "1985-03-15T00:00:00.000+0300",
+                    "preferredName": {"givenName": "Akinyi", "familyName": "Otieno"},
+                    "preferredAddress": {"countyDistrict": "Kisumu", "stateProvince": "Kisumu East"},
+                    "attributes": []
+                }
+            },
+            "obs": obs
+        })
+    }
+
+    fn obs(concept: &str, value: Value) -> Value {
+        json!({"concept": {"uuid": ciel(concept), "display": concept}, "value": value})
+    }
+
+    #[test]
+    fn triage_and_consultation_become_one_visit() {
+        let triage = encounter(
+            "2026-10-14T08:10:00.000+0300",
+            json!([{
+                "concept": {"uuid": ciel("1114"), "display": "Vitals"},
+                "value": null,
+                "groupMembers": [
+                    obs("5088", json!(38.2)),
+                    obs("5085", json!(118)),
+                    obs("5086", json!(76)),
+                    obs("5089", json!(61.5))
+                ]
+            }]),
+        );
+        let consult = encounter(
+            "2026-10-14T09:40:00.000+0300",
+            json!([
+                obs("5219", json!({"uuid": ciel("140238"), "display": "Fever"})),
+                obs(
+                    "6042",
+                    json!({"uuid": ciel("116128"), "display": "Malaria"})
+                ),
+                obs(
+                    "160632",
+                    json!("Artemether/Lumefantrine 80/480mg BD for 3 days")
+                )
+            ]),
+        );
+        // Triage only: no diagnosis, so not a complete visit
+        let next_day = encounter(
+            "2026-10-15T08:00:00.000+0300",
+            json!([obs("5088", json!(37.0))]),
+        );
+
+        let import = encounters_to_kenyan(
+            &[triage, consult, next_day],
+            "KEN-KISUMU-003",
+            &ObsConcepts::default(),
+        )
+        .unwrap();
+        assert_eq!(import.incomplete_visits, 1);
+        let record = &import.records[0];
+        assert_eq!(record.patient_number, "MRN-1");
+        assert_eq!(record.location.county, "Kisumu");
+        assert_eq!(record.visits.len(), 1);
+        let visit = &record.visits[0];
+        assert_eq!(visit.date, "2026-10-14");
+        assert_eq!(visit.complaint, "Fever");
+        assert_eq!(visit.diagnosis, "Malaria");
+        assert_eq!(visit.vitals.bp_systolic, 118);
+        assert!(crate::validation::validate_kenyan_patient(record).is_ok());
+    }
+
+    #[test]
+    fn patient_without_national_id_is_skipped() {
+        let mut enc = encounter(
+            "2026-10-14T09:40:00.000+0300",
+            json!([
+                obs("5088", json!(37.1)),
+                obs("5085", json!(120)),
+                obs("5086", json!(80)),
+                obs("5089", json!(70)),
+                obs("161602", json!("Lower back pain")),
+                obs("1282", json!({"display": "Paracetamol"}))
+            ]),
+        );
+        enc["patient"]["identifiers"]
+            .as_array_mut()
+            .unwrap()
+            .truncate(1);
+        let import = encounters_to_kenyan(&[enc], "KEN-1", &ObsConcepts::default()).unwrap();
+        assert!(import.records.is_empty());
+        assert_eq!(import.skipped_patients, 1);
+    }
+}
@@ -0,0 +1,448 @@
+//! Duplicate-patient detection before a synthetic CR ID is minted.
+//!
+//! When the Client Registry has no patient under a record's national ID the
+//! bridge falls back to a synthetic CR ID. That is wrong when the person is
+//! already registered under another national ID (typo, birth certificate
+//! number later replaced by an ID card). Before keeping the synthetic ID the
+//! record is matched on name, date of birth and phone — through the CR's
+//! `Patient/$match`, or a local index of previously seen patients — and a
+//! confident match with a different national ID is reported, or linked by
+//! reusing the registered CR ID.
+use std::path::{Path, PathBuf};
+
+use chrono::{NaiveDate, Utc};
+use fhir_parser::fhir::patient::Patient;
+use fhir_parser::masking::mask_identifier;
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+
+use crate::cr_lookup::{letters, within_one_edit};
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::http::{self, HttpRequest};
+use crate::systems::{CR_SYSTEM, NATIONAL_ID_SYSTEM};
+
+const SYNTHETIC_PREFIX: &str = "CR-SYNTH-";
+
+/// An existing patient the record may be.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchCandidate {
+    pub cr_id: String,
+    pub national_id: Option<String>,
+    /// 0–1; the registry's `search.score` or the local matcher's
+    pub score: f64,
+}
+
+#[derive(Debug, Clone)]
+pub enum MatchSource {
+    /// CR base URL; `AFYALINK_TOKEN` is sent as bearer token if set
+    Registry { base_url: String },
+    /// SQLite index of patients this installation has transformed
+    Local { db: PathBuf },
+}
+
+/// What to do with a confident match under another national ID.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchAction {
+    /// Report it and keep the synthetic CR ID
+    #[default]
+    Warn,
+    /// Use the matched patient's CR ID
+    Link,
+}
+
+#[derive(Debug, Clone)]
+pub struct PatientMatcher {
+    pub source: MatchSource,
+    pub action: MatchAction,
+    /// Minimum score counted as the same person
+    pub threshold: f64,
+    pub timeout_secs: u32,
+}
+
+impl PatientMatcher {
+    pub fn new(source: MatchSource, action: MatchAction) -> Self {
+        Self {
+            source,
+            action,
+            threshold: 0.8,
+            timeout_secs: 10,
+        }
+    }
+
+    /// Existing patients resembling `patient`, best first.
+    pub fn candidates(&self, patient: &Patient) -> Result<Vec<MatchCandidate>> {
+        let mut found = match &self.source {
+            MatchSource::Registry { base_url } => self.registry_match(base_url, patient)?,
+            MatchSource::Local { db } => LocalIndex::open(db)?.candidates(patient)?,
+        };
+        found.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(found)
+    }
+
+    /// Check a freshly mapped Patient. Only synthetic CR IDs are questioned;
+    /// returns the duplicate found, if any. Matching failures are reported
+    /// and leave the Patient as it was.
+    pub fn reconcile(&self, patient: &mut Patient) -> Option<MatchCandidate> {
+        let national_id = identifier(patient, NATIONAL_ID_SYSTEM);
+        let synthetic =
+            identifier(patient, CR_SYSTEM).is_some_and(|v| v.starts_with(SYNTHETIC_PREFIX));
+
+        let duplicate = if synthetic {
+            match self.candidates(patient) {
+                Ok(candidates) => candidates.into_iter().find(|c| {
+                    c.score >= self.threshold
+                        && c.national_id.is_some()
+                        && c.national_id != national_id
+                }),
+                Err(e) => {
+                    eprintln!(
+                        "[MPI] patient match unavailable, keeping synthetic CR ID: {:#}",
+                        e
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        if let Some(found) = &duplicate {
+            eprintln!(
+                "[MPI] record matches CR patient {} registered under national ID {} (score {:.2}){}",
+                mask_identifier(&found.cr_id),
+                mask_identifier(found.national_id.as_deref().unwrap_or_default()),
+                found.score,
+                if self.action == MatchAction::Link {
+                    "; linking to it"
+                } else {
+                    ""
+                }
+            );
+            if self.action == MatchAction::Link {
+                set_identifier(patient, CR_SYSTEM, &found.cr_id);
+            }
+        }
+
+        if let MatchSource::Local { db } = &self.source {
+            if let Err(e) = LocalIndex::open(db).and_then(|index| index.record(patient)) {
+                eprintln!("[MPI] could not update the local patient index: {:#}", e);
+            }
+        }
+        duplicate
+    }
+
+    fn registry_match(&self, base_url: &str, patient: &Patient) -> Result<Vec<MatchCandidate>> {
+        // Match on demographics, not on the identifier the CR did not know
+        let mut probe = patient.clone();
+        probe.id = None;
+        probe.identifier = None;
+        let parameters = json!({
+            "resourceType": "Parameters",
+            "parameter": [
+                {"name": "resource", "resource": probe},
+                {"name": "count", "valueInteger": 5}
+            ]
+        });
+        let body = serde_json::to_vec(&parameters)?;
+        let url = format!("{}/Patient/$match", base_url.trim_end_matches('/'));
+        let mut headers = vec![
+            "Content-Type: application/fhir+json".to_string(),
+            "Accept: application/fhir+json".to_string(),
+        ];
+        if let Ok(token) = std::env::var("AFYALINK_TOKEN") {
+            headers.push(format!("Authorization: Bearer {}", token));
+        }
+        let response = http::send(&HttpRequest {
+            method: "POST",
+            url: &url,
+            headers,
+            body: Some(&body),
+            timeout_secs: self.timeout_secs,
+            tls: None,
+        })?;
+        if !response.is_success() {
+            bail!(Network, "Patient $match returned HTTP {}", response.status);
+        }
+        let bundle: Value = serde_json::from_str(&response.body)
+            .context(BridgeError::Network, "Invalid $match response")?;
+        Ok(match_candidates(&bundle))
+    }
+}
+
+/// Candidates from a `$match` searchset Bundle.
+fn match_candidates(bundle: &Value) -> Vec<MatchCandidate> {
+    bundle["entry"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let resource = &entry["resource"];
+            let by_system = |system: &str| {
+                resource["identifier"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .find(|i| i["system"] == system)
+                    .and_then(|i| i["value"].as_str())
+                    .map(str::to_string)
+            };
+            let cr_id =
+                by_system(CR_SYSTEM).or_else(|| resource["id"].as_str().map(str::to_string))?;
+            Some(MatchCandidate {
+                cr_id,
+                national_id: by_system(NATIONAL_ID_SYSTEM),
+                score: entry["search"]["score"].as_f64().unwrap_or(0.0),
+            })
+        })
+        .collect()
+}
+
+fn identifier(patient: &Patient, system: &str) -> Option<String> {
+    patient
+        .identifier
+        .iter()
+        .flatten()
+        .find(|i| i.system.as_deref() == Some(system))
+        .map(|i| i.value.clone())
+}
+
+fn set_identifier(patient: &mut Patient, system: &str, value: &str) {
+    for i in patient.identifier.iter_mut().flatten() {
+        if i.system.as_deref() == Some(system) {
+            i.value = value.to_string();
+        }
+    }
+}
+
+/// The fields compared, normalised: names lower-case letters only, phone
+/// as its last nine digits (drops +254 / 0 prefixes).
+#[derive(Debug, Default, PartialEq)]
+struct Demographics {
+    family: String,
+    given: String,
+    birth_date: Option<NaiveDate>,
+    phone: String,
+}
+
+fn phone_key(s: &str) -> String {
+    let digits: Vec<char> = s.chars().filter(char::is_ascii_digit).collect();
+    digits[digits.len().saturating_sub(9)..].iter().collect()
+}
+
+impl Demographics {
+    fn of(patient: &Patient) -> Self {
+        let name = patient.name.iter().flatten().next();
+        Self {
+            family: letters(name.and_then(|n| n.family.as_deref()).unwrap_or_default()),
+            given: letters(
+                name.and_then(|n| n.given.as_ref())
+                    .and_then(|g| g.first())
+                    .map(String::as_str)
+                    .unwrap_or_default(),
+            ),
+            birth_date: patient.birth_date,
+            phone: patient
+                .telecom
+                .iter()
+                .flatten()
+                .find(|t| t.system.as_deref() == Some("phone"))
+                .map(|t| phone_key(&t.value))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Weighted agreement: date of birth 0.35, family name 0.25, given name
+    /// 0.2, phone 0.2. Names allow one typo at half weight and may be
+    /// swapped (family and given names are often recorded either way).
+    fn score(&self, other: &Self) -> f64 {
+        let name = |a: &str, b: &str, weight: f64| {
+            if a.is_empty() || b.is_empty() {
+                0.0
+            } else if a == b {
+                weight
+            } else if within_one_edit(a, b) {
+                weight / 2.0
+            } else {
+                0.0
+            }
+        };
+        let straight =
+            name(&self.family, &other.family, 0.25) + name(&self.given, &other.given, 0.2);
+        let swapped =
+            name(&self.family, &other.given, 0.25) + name(&self.given, &other.family, 0.2);
+        let dob = match (self.birth_date, other.birth_date) {
+            (Some(a), Some(b)) if a == b => 0.35,
+            _ => 0.0,
+        };
+        let phone = if !self.phone.is_empty() && self.phone == other.phone {
+            0.2
+        } else {
+            0.0
+        };
+        straight.max(swapped) + dob + phone
+    }
+}
+
+/// Patients seen by this installation, for matching without the CR.
+pub struct LocalIndex {
+    conn: Connection,
+}
+
+impl LocalIndex {
+    pub fn open(db_path: &Path) -> Result<Self> {
+        let conn = Connection::open(db_path).with_context(BridgeError::Storage, || {
+            format!("Failed to open patient index at {:?}", db_path)
+        })?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS patient_index (
+                national_id TEXT PRIMARY KEY,
+                cr_id       TEXT NOT NULL,
+                family      TEXT NOT NULL,
+                given       TEXT NOT NULL,
+                birth_date  TEXT,
+                phone       TEXT NOT NULL,
+                updated_at  TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_patient_dob ON patient_index(birth_date);
+            CREATE INDEX IF NOT EXISTS idx_patient_phone ON patient_index(phone);",
+        )
+        .context(
+            BridgeError::Storage,
+            "Failed to initialise patient index schema",
+        )?;
+        Ok(Self { conn })
+    }
+
+    /// Add or refresh a patient under its national ID.
+    pub fn record(&self, patient: &Patient) -> Result<()> {
+        let Some(national_id) = identifier(patient, NATIONAL_ID_SYSTEM) else {
+            return Ok(());
+        };
+        let cr_id = identifier(patient, CR_SYSTEM).unwrap_or_default();
+        let d = Demographics::of(patient);
+        self.conn.execute(
+            "INSERT OR REPLACE INTO patient_index
+                (national_id, cr_id, family, given, birth_date, phone, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                national_id,
+                cr_id,
+                d.family,
+                d.given,
+                d.birth_date.map(|b| b.to_string()),
+                d.phone,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Indexed patients sharing a date of birth or phone, scored.
+    pub fn candidates(&self, patient: &Patient) -> Result<Vec<MatchCandidate>> {
+        let probe = Demographics::of(patient);
+        let mut stmt = self.conn.prepare(
+            "SELECT national_id, cr_id, family, given, birth_date, phone FROM patient_index
+             WHERE birth_date = ?1 OR (phone != '' AND phone = ?2)",
+        )?;
+        let rows = stmt.query_map(
+            params![probe.birth_date.map(|b| b.to_string()), probe.phone],
+            |r| {
+                let birth_date: Option<String> = r.get(4)?;
+                Ok((
+                    r.get::<_, String>(0)?,
+                    r.get::<_, String>(1)?,
+                    Demographics {
+                        family: r.get(2)?,
+                        given: r.get(3)?,
+                        birth_date: birth_date.and_then(|b| b.parse().ok()),
+                        phone: r.get(5)?,
+                    },
+                ))
+            },
+        )?;
+        let mut found = Vec::new();
+        for row in rows {
+            let (national_id, cr_id, known) = row?;
+            found.push(MatchCandidate {
+                cr_id,
+                national_id: Some(national_id),
+                score: probe.score(&known),
+            });
+        }
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fhir_parser::fhir::patient::{ContactPoint, HumanName, Identifier};
+
+    fn patient(national_id: &str, cr_id: &str, family: &str, given: &str, phone: &str) -> Patient {
+        Patient {
+            resource_type: "Patient".into(),
+            id: None,
+            meta: None,
+            extension: None,
+            identifier: Some(vec![
+                Identifier {
+                    system: Some(CR_SYSTEM.into()),
+                    value: cr_id.into(),
+                },
+                Identifier {
+                    system: Some(NATIONAL_ID_SYSTEM.into()),
+                    value: national_id.into(),
+                },
+            ]),
+            name: Some(vec![HumanName {
+                use_field: None,
+                family: Some(family.into()),
+                given: Some(vec![given.into()]),
+            }]),
+            telecom: Some(vec![ContactPoint {
+                system: Some("phone".into()),
+                value: phone.into(),
+                use_field: None,
+            }]),
+            gender: Some("female".into()),
+            birth_date: NaiveDate::from_ymd_opt(1985, 3, 15),
+            birth_date_element: None,
+            address: None,
+            photo: None,
+            link: None,
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn scores_typos_and_swapped_names() {
+        let a = Demographics::of(&patient("1", "CR-1", "Kamau", "Wanjiru", "+254712345678"));
+        let typo = Demographics::of(&patient("2", "CR-2", "Kamau", "Wanjru", "0712345678"));
+        let swapped = Demographics::of(&patient("3", "CR-3", "Wanjiru", "Kamau", ""));
+        assert!((a.score(&typo) - 0.9).abs() < 1e-9);
+        assert!((a.score(&swapped) - 0.8).abs() < 1e-9);
+        assert!(within_one_edit("otieno", "otiemo"));
+        assert!(!within_one_edit("otieno", "ochieng"));
+    }
+
+    #[test]
+    fn links_to_locally_known_patient_under_other_national_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let matcher = PatientMatcher::new(
+            MatchSource::Local {
+                db: dir.path().join("mpi.db"),
+            },
+            MatchAction::Link,
+        );
+        let mut known = patient("27845612", "CR-LIVE-1", "Kamau", "Wanjiru", "+254712345678");
+        assert_eq!(matcher.reconcile(&mut known), None);
+
+        let mut incoming = patient("27845621", "CR-SYNTH-abc", "Kamau", "Wanjiru", "0712345678");
+        let found = matcher.reconcile(&mut incoming).unwrap();
+        assert_eq!(found.national_id.as_deref(), Some("27845612"));
+        assert_eq!(
+            identifier(&incoming, CR_SYSTEM).as_deref(),
+            Some("CR-LIVE-1")
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! In-process end-to-end pipeline for integration tests of embedding crates:
+//! input → validation → transform → bundle lint → submission → offline queue.
+//!
+//! Nothing leaves the process: submission goes through a [`Submitter`]
+//! (usually [`MockSubmitter`]) and failed submissions land in an in-memory
+//! [`OfflineQueue`], exactly as a facility without connectivity would queue
+//! them. Tests then assert on the returned [`PipelineRun`], the submitter and
+//! the queue.
+use fhir_parser::fhir::bundle::Bundle;
+
+use crate::bundle_lint::{lint_bundle, LintIssue, LintSeverity};
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::kenyan::schema::KenyanPatient;
+use crate::offline_queue::{OfflineQueue, Priority, QueueRouting};
+use crate::transform::{transform, FailurePolicy, TransformOptions};
+use crate::validation::{validate_kenyan_patient, validate_record};
+
+/// Where bundles go once they pass lint.
+pub trait Submitter {
+    fn submit(&mut self, bundle: &Bundle) -> Result<()>;
+}
+
+/// Records submitted bundles; can be told to fail to exercise queueing.
+#[derive(Debug, Default)]
+pub struct MockSubmitter {
+    pub submitted: Vec<Bundle>,
+    /// Number of upcoming submissions that fail (simulated outage)
+    pub fail_next: usize,
+}
+
+impl Submitter for MockSubmitter {
+    fn submit(&mut self, bundle: &Bundle) -> Result<()> {
+        if self.fail_next > 0 {
+            self.fail_next -= 1;
+            bail!(Network, "Simulated network failure");
+        }
+        self.submitted.push(bundle.clone());
+        Ok(())
+    }
+}
+
+/// What happened to one record.
+#[derive(Debug)]
+pub struct PipelineRun {
+    pub bundle: Bundle,
+    /// Lint warnings (errors abort the run)
+    pub lint_warnings: Vec<LintIssue>,
+    /// Data-quality notes from reading the record (see
+    /// [`KenyanPatient::data_quality`])
+    pub data_quality: Vec<String>,
+    pub submitted: bool,
+    /// Queue row id when submission failed and the bundle was queued
+    pub queued: Option<i64>,
+}
+
+pub struct Pipeline<S: Submitter = MockSubmitter> {
+    pub options: TransformOptions,
+    pub submitter: S,
+    pub queue: OfflineQueue,
+}
+
+impl Pipeline<MockSubmitter> {
+    /// Default options, a [`MockSubmitter`] and an in-memory queue.
+    pub fn mock() -> Result<Self> {
+        Self::new(MockSubmitter::default())
+    }
+}
+
+impl<S: Submitter> Pipeline<S> {
+    pub fn new(submitter: S) -> Result<Self> {
+        Ok(Self {
+            options: TransformOptions::default(),
+            submitter,
+            queue: OfflineQueue::open_in_memory()?,
+        })
+    }
+
+    /// Run a Kenyan JSON payload through the whole pipeline.
+    pub fn run_json(&mut self, json: &str) -> Result<PipelineRun> {
+        let kenyan: KenyanPatient = serde_json::from_str(json)
+            .context(BridgeError::Validation, "Invalid Kenyan JSON payload")?;
+        self.run(&kenyan)
+    }
+
+    /// Run a parsed record through the whole pipeline.
+    pub fn run(&mut self, kenyan: &KenyanPatient) -> Result<PipelineRun> {
+        let validate = match self.options.on_error {
+            FailurePolicy::Abort => validate_kenyan_patient,
+            FailurePolicy::Skip => validate_record,
+        };
+        validate(kenyan).context(BridgeError::Validation, "Patient record failed validation")?;
+        let bundle = transform(kenyan, &self.options)?;
+
+        let (errors, lint_warnings): (Vec<_>, Vec<_>) = lint_bundle(&bundle)
+            .into_iter()
+            .partition(|i| i.severity == LintSeverity::Error);
+        if let Some(first) = errors.first() {
+            bail!(
+                Validation,
+                "Bundle lint found {} error(s), first: {}",
+                errors.len(),
+                first
+            );
+        }
+
+        let (submitted, queued) = match self.submitter.submit(&bundle) {
+            Ok(()) => (true, None),
+            Err(e) => {
+                let bundle_id = bundle.id.as_deref().unwrap_or_default();
+                let json = serde_json::to_string(&bundle)?;
+                // Bundles carrying an SHA claim go ahead of routine SHR updates
+                let has_claim = bundle.entry.iter().flatten().any(|e| {
+                    e.resource
+                        .as_ref()
+                        .is_some_and(|r| r["resourceType"] == "Claim")
+                });
+                let routing = QueueRouting {
+                    priority: if has_claim {
+                        Priority::Claim
+                    } else {
+                        Priority::Routine
+                    },
+                    destination_endpoint: None,
+                };
+                let row = self.queue.enqueue_routed(
+                    bundle_id,
+                    &json,
+                    &kenyan.patient_number,
+                    &kenyan.clinic_id,
+                    &routing,
+                )?;
+                self.queue.record_failure(row, &e.to_string())?;
+                (false, Some(row))
+            }
+        };
+
+        Ok(PipelineRun {
+            bundle,
+            lint_warnings,
+            data_quality: kenyan.data_quality.clone(),
+            submitted,
+            queued,
+        })
+    }
+}
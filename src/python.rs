@@ -0,0 +1,244 @@
+//! Python bindings (`python` feature).
+//!
+//! The MOH data science team's ETL notebooks call the same mapping as the
+//! bridge instead of a pandas re-implementation that drifts from it. Built
+//! as the `kenya_fhir_bridge` extension module with maturin
+//! (`pyproject.toml`): `maturin develop --release`.
+//!
+//! ```python
+//! import json, kenya_fhir_bridge as kfb
+//! bundle = json.loads(kfb.transform(record, deterministic=True))
+//! kfb.diagnosis_codes("Malaria")["icd11"]  # {'code': '1F4Z', ...}
+//! ```
+//!
+//! Records go in as a dict or JSON text; bundles come back as JSON text. A
+//! record that breaks the input rules raises `kenya_fhir_bridge.ValidationError`
+//! (a `ValueError`), anything else a `RuntimeError`.
+use std::sync::OnceLock;
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString};
+
+use crate::error::{BridgeError, Context};
+use crate::kenyan::schema::KenyanPatient;
+use crate::mapper::condition::diagnosis_coding;
+use crate::terminology::complaint::{ComplaintTerminology, ConceptCode};
+use crate::terminology::formulary::Formulary;
+use crate::transform::{transform_json, BundleIdSource, TimestampSource, TransformOptions};
+use crate::validation::validate_kenyan_patient;
+
+create_exception!(
+    kenya_fhir_bridge,
+    ValidationError,
+    PyValueError,
+    "A record breaks the Kenyan input rules."
+);
+
+impl From<BridgeError> for PyErr {
+    fn from(e: BridgeError) -> Self {
+        match e {
+            BridgeError::Validation(_) | BridgeError::Mapping(_) => {
+                ValidationError::new_err(e.to_string())
+            }
+            _ => PyRuntimeError::new_err(e.to_string()),
+        }
+    }
+}
+
+/// The record as JSON text: a `str` as is, anything else through `json.dumps`.
+fn record_json(record: &Bound<'_, PyAny>) -> PyResult<String> {
+    if let Ok(text) = record.cast::<PyString>() {
+        return Ok(text.to_str()?.to_string());
+    }
+    record
+        .py()
+        .import("json")?
+        .call_method1("dumps", (record,))?
+        .extract()
+}
+
+/// `transform(record, deterministic=False) -> str`: the compact Bundle JSON.
+/// `deterministic` derives Bundle.id and Bundle.timestamp from the record,
+/// as the CLI's `--deterministic` does.
+#[pyfunction]
+#[pyo3(signature = (record, deterministic = false))]
+fn transform(py: Python<'_>, record: &Bound<'_, PyAny>, deterministic: bool) -> PyResult<String> {
+    let json = record_json(record)?;
+    let mut options = TransformOptions::default();
+    if deterministic {
+        options.bundle_id = BundleIdSource::FromInput;
+        options.timestamp = TimestampSource::FromInput;
+    }
+    // The CR lookup may go out to the network; let other threads run
+    Ok(py.detach(|| transform_json(&json, &options))?)
+}
+
+/// `validate(record) -> list[str]`: the data-quality notes of a record that
+/// passes; raises `ValidationError` for one that does not.
+#[pyfunction]
+fn validate(record: &Bound<'_, PyAny>) -> PyResult<Vec<String>> {
+    let kenyan: KenyanPatient = serde_json::from_str(&record_json(record)?)
+        .context(BridgeError::Validation, "Invalid Kenyan JSON payload")?;
+    validate_kenyan_patient(&kenyan)?;
+    Ok(kenyan.data_quality)
+}
+
+fn code<'py>(py: Python<'py>, code: &str, display: &str) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("code", code)?;
+    dict.set_item("display", display)?;
+    Ok(dict)
+}
+
+fn concept_code<'py>(
+    py: Python<'py>,
+    concept: Option<&ConceptCode>,
+) -> PyResult<Option<Bound<'py, PyDict>>> {
+    concept.map(|c| code(py, &c.code, &c.display)).transpose()
+}
+
+/// `diagnosis_codes(diagnosis) -> dict | None`: the diagnosis crosswalk row,
+/// `{"icd10": {...}, "icd11": {...}, "snomed": {...} or None}`, or `None`
+/// for a diagnosis it does not know.
+#[pyfunction]
+fn diagnosis_codes<'py>(py: Python<'py>, diagnosis: &str) -> PyResult<Option<Bound<'py, PyDict>>> {
+    let Some(dx) = diagnosis_coding(diagnosis) else {
+        return Ok(None);
+    };
+    let dict = PyDict::new(py);
+    dict.set_item("icd10", code(py, dx.icd10_code, dx.icd10_display)?)?;
+    dict.set_item("icd11", code(py, dx.icd11_code, dx.icd11_display)?)?;
+    dict.set_item(
+        "snomed",
+        dx.snomed.map(|(c, d)| code(py, c, d)).transpose()?,
+    )?;
+    Ok(Some(dict))
+}
+
+/// `medication_code(treatment) -> dict | None`: the KEML medicine a
+/// treatment note names, `{"name", "atc_code", "confidence"}`, or `None`
+/// below the formulary's match threshold.
+#[pyfunction]
+fn medication_code<'py>(py: Python<'py>, treatment: &str) -> PyResult<Option<Bound<'py, PyDict>>> {
+    static FORMULARY: OnceLock<Formulary> = OnceLock::new();
+    let Some(found) = FORMULARY.get_or_init(Formulary::default).lookup(treatment) else {
+        return Ok(None);
+    };
+    let dict = PyDict::new(py);
+    dict.set_item("name", &found.medicine.name)?;
+    dict.set_item("atc_code", &found.medicine.atc_code)?;
+    dict.set_item("confidence", found.confidence)?;
+    Ok(Some(dict))
+}
+
+/// `complaint_codes(complaint) -> list[dict]`: the presenting complaints a
+/// complaint text names, each `{"name", "snomed", "icpc2"}`.
+#[pyfunction]
+fn complaint_codes<'py>(py: Python<'py>, complaint: &str) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    static COMPLAINTS: OnceLock<ComplaintTerminology> = OnceLock::new();
+    COMPLAINTS
+        .get_or_init(ComplaintTerminology::default)
+        .matches(complaint)
+        .into_iter()
+        .map(|concept| {
+            let dict = PyDict::new(py);
+            dict.set_item("name", &concept.name)?;
+            dict.set_item("snomed", concept_code(py, concept.snomed.as_ref())?)?;
+            dict.set_item("icpc2", concept_code(py, concept.icpc2.as_ref())?)?;
+            Ok(dict)
+        })
+        .collect()
+}
+
+#[pymodule]
+#[pyo3(name = "kenya_fhir_bridge")]
+pub fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("ValidationError", m.py().get_type::<ValidationError>())?;
+    m.add_function(wrap_pyfunction!(transform, m)?)?;
+    m.add_function(wrap_pyfunction!(validate, m)?)?;
+    m.add_function(wrap_pyfunction!(diagnosis_codes, m)?)?;
+    m.add_function(wrap_pyfunction!(medication_code, m)?)?;
+    m.add_function(wrap_pyfunction!(complaint_codes, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call<'py>(
+        m: &Bound<'py, PyModule>,
+        name: &str,
+        arg: Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        m.getattr(name)?.call1((arg,))
+    }
+
+    #[test]
+    fn module_transforms_validates_and_looks_up_codes() {
+        Python::initialize();
+        Python::attach(|py| {
+            let m = PyModule::new(py, "kenya_fhir_bridge").unwrap();
+            python_module(&m).unwrap();
+
+            let text = include_str!("../tests/fixtures/kenyan_patient_1.json");
+            let record = py
+                .import("json")
+                .unwrap()
+                .call_method1("loads", (text,))
+                .unwrap();
+            let bundle: String = call(&m, "transform", record.clone())
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert!(bundle.starts_with("{\"resourceType\":\"Bundle\""));
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("deterministic", true).unwrap();
+            let twice: Vec<String> = (0..2)
+                .map(|_| {
+                    m.getattr("transform")
+                        .unwrap()
+                        .call((text,), Some(&kwargs))
+                        .unwrap()
+                        .extract()
+                        .unwrap()
+                })
+                .collect();
+            assert_eq!(twice[0], twice[1]);
+            assert!(call(&m, "validate", record).is_ok());
+            let refused = call(&m, "validate", PyDict::new(py).into_any()).unwrap_err();
+            assert!(refused.is_instance_of::<ValidationError>(py));
+
+            let malaria = call(
+                &m,
+                "diagnosis_codes",
+                PyString::new(py, "Malaria").into_any(),
+            )
+            .unwrap();
+            let icd11: String = malaria
+                .get_item("icd11")
+                .and_then(|c| c.get_item("code"))
+                .and_then(|c| c.extract())
+                .unwrap();
+            assert_eq!(icd11, "1F4Z");
+            assert!(call(
+                &m,
+                "diagnosis_codes",
+                PyString::new(py, "review").into_any()
+            )
+            .unwrap()
+            .is_none());
+            let atc: String = call(
+                &m,
+                "medication_code",
+                PyString::new(py, "Amoxycillin 500mg TDS for 7 days").into_any(),
+            )
+            .and_then(|found| found.get_item("atc_code"))
+            .and_then(|c| c.extract())
+            .unwrap();
+            assert_eq!(atc, "J01CA04");
+        });
+    }
+}
@@ -0,0 +1,169 @@
+//! Alerts on queue events for facility IT.
+//!
+//! A bundle that fails for good, a backlog that keeps growing or a bundle
+//! about to fall out of the transmission window is otherwise only found
+//! when someone reads the queue. [`notify()`] raises each such event once,
+//! to a webhook (JSON POST) and/or a local command (JSON on stdin, e.g. an
+//! SMS or mail script), and records it in the queue database so a
+//! restarted daemon or the next `serve --once` does not raise it again.
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::http;
+use crate::offline_queue::OfflineQueue;
+
+/// Where alerts go and when they are raised, e.g.
+/// `{"webhook": "https://it.example/hooks/queue", "depth_threshold": 500}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AlertConfig {
+    /// URL the alert JSON is POSTed to
+    pub webhook: Option<String>,
+    /// Program and arguments run with the alert JSON on stdin
+    pub command: Option<Vec<String>>,
+    /// Alert when more bundles than this are pending; `None` never alerts
+    pub depth_threshold: Option<i64>,
+    /// Warn about pending bundles this many hours before their
+    /// transmission window ends
+    pub expiry_warning_hours: u32,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            webhook: None,
+            command: None,
+            depth_threshold: None,
+            expiry_warning_hours: 24,
+        }
+    }
+}
+
+impl AlertConfig {
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path).with_context(BridgeError::Io, || {
+            format!("Failed to read alert config {:?}", path)
+        })?;
+        let config: Self =
+            serde_json::from_str(&raw).context(BridgeError::Config, "Invalid alert config JSON")?;
+        if config.webhook.is_none() && config.command.is_none() {
+            bail!(Config, "Alert config needs a webhook or a command");
+        }
+        if config.command.as_ref().is_some_and(|c| c.is_empty()) {
+            bail!(Config, "Alert command is empty");
+        }
+        Ok(config)
+    }
+}
+
+/// One queue event, sent as JSON tagged by `event`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum QueueAlert {
+    /// The bundle reached the retry limit or expired and will not be sent
+    BundleFailed {
+        row_id: i64,
+        bundle_id: String,
+        clinic_id: String,
+        last_error: Option<String>,
+    },
+    /// More bundles are pending than the configured threshold
+    QueueDepth { pending: i64, threshold: i64 },
+    /// The bundle is still pending and its transmission window ends soon
+    WindowExpiring {
+        row_id: i64,
+        bundle_id: String,
+        clinic_id: String,
+        expires_at: String,
+    },
+}
+
+impl QueueAlert {
+    /// `event` tag and queue row the alert log keys on (0 for queue depth).
+    pub fn key(&self) -> (&'static str, i64) {
+        match self {
+            QueueAlert::BundleFailed { row_id, .. } => ("bundle_failed", *row_id),
+            QueueAlert::QueueDepth { .. } => ("queue_depth", 0),
+            QueueAlert::WindowExpiring { row_id, .. } => ("window_expiring", *row_id),
+        }
+    }
+}
+
+impl std::fmt::Display for QueueAlert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueueAlert::BundleFailed {
+                bundle_id,
+                last_error,
+                ..
+            } => write!(
+                f,
+                "bundle {} failed: {}",
+                bundle_id,
+                last_error.as_deref().unwrap_or("no error recorded")
+            ),
+            QueueAlert::QueueDepth { pending, threshold } => {
+                write!(f, "{} bundle(s) pending (threshold {})", pending, threshold)
+            }
+            QueueAlert::WindowExpiring {
+                bundle_id,
+                expires_at,
+                ..
+            } => write!(
+                f,
+                "bundle {} still pending, window ends {}",
+                bundle_id, expires_at
+            ),
+        }
+    }
+}
+
+/// Send one alert to every configured target.
+pub fn deliver(config: &AlertConfig, alert: &QueueAlert) -> Result<()> {
+    let body = serde_json::to_vec(alert)?;
+    if let Some(url) = &config.webhook {
+        let response = http::post(url, "application/json", &body, 30)?;
+        if !response.is_success() {
+            bail!(Network, "Alert webhook returned HTTP {}", response.status);
+        }
+    }
+    if let Some([program, args @ ..]) = config.command.as_deref() {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .spawn()
+            .with_context(BridgeError::Io, || {
+                format!("Failed to run alert command {:?}", program)
+            })?;
+        child
+            .stdin
+            .take()
+            .context(BridgeError::Io, "Alert command stdin unavailable")?
+            .write_all(&body)?;
+        let status = child.wait()?;
+        if !status.success() {
+            bail!(Io, "Alert command {:?} exited with {}", program, status);
+        }
+    }
+    Ok(())
+}
+
+/// Raise the queue's alerts not raised yet; returns how many were
+/// delivered. An alert that cannot be delivered is retried next time.
+pub fn notify(queue: &OfflineQueue, config: &AlertConfig) -> Result<usize> {
+    let mut delivered = 0;
+    for alert in queue.due_alerts(config)? {
+        match deliver(config, &alert) {
+            Ok(()) => {
+                queue.record_alert(&alert)?;
+                delivered += 1;
+            }
+            Err(e) => eprintln!("[ALERT] could not deliver \"{}\": {:#}", alert, e),
+        }
+    }
+    Ok(delivered)
+}
@@ -0,0 +1,320 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{bail, BridgeError, Context, Result};
+
+/// Prefix of encrypted `bundle_json` values: `enc:v1:{key id}:{base64}`
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+#[cfg(any(windows, target_os = "macos"))]
+const KEYRING_SERVICE: &str = "kenya-fhir-bridge";
+#[cfg(any(windows, target_os = "macos"))]
+const KEYRING_USER: &str = "offline-queue";
+#[cfg(not(any(windows, target_os = "macos")))]
+const NO_KEYRING: &str = "No persistent OS keyring on this platform; use a queue key file";
+
+/// AES-256-GCM keys for the offline queue.
+///
+/// New rows are encrypted with the `active` key; older keys are kept until
+/// every row has been re-encrypted ([`OfflineQueue::reencrypt`]) so a
+/// rotation never strands queued bundles. The key set lives in a JSON file
+/// or in the OS keyring on Windows (Credential Manager) and macOS
+/// (Keychain). Linux kernel keyrings do not survive a reboot, which would
+/// strand the queue, so there a key file is required.
+///
+/// [`OfflineQueue::reencrypt`]: crate::offline_queue::OfflineQueue::reencrypt
+#[derive(Clone, Serialize, Deserialize)]
+pub struct QueueKeys {
+    /// Id of the key new rows are encrypted with
+    pub active: String,
+    /// Key id → base64 256-bit key
+    keys: BTreeMap<String, String>,
+}
+
+// Hand-written so key material never ends up in debug output.
+impl std::fmt::Debug for QueueKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueueKeys")
+            .field("active", &self.active)
+            .field("key_ids", &self.keys.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl QueueKeys {
+    /// A key set holding one freshly generated key.
+    pub fn generate() -> Self {
+        let mut keys = Self {
+            active: String::new(),
+            keys: BTreeMap::new(),
+        };
+        keys.rotate();
+        keys
+    }
+
+    /// Add a new random key and make it the active one; returns its id.
+    pub fn rotate(&mut self) -> &str {
+        let mut id = Utc::now().format("%Y%m%d%H%M%S").to_string();
+        // Two rotations within a second still get distinct ids
+        while self.keys.contains_key(&id) {
+            id.push('a');
+        }
+        let key = Aes256Gcm::generate_key(OsRng);
+        self.keys.insert(id.clone(), STANDARD.encode(key));
+        self.active = id;
+        &self.active
+    }
+
+    /// Forget every key except the active one and those in `keep`.
+    pub fn retire_except(&mut self, keep: &[String]) -> Vec<String> {
+        let retired: Vec<String> = self
+            .keys
+            .keys()
+            .filter(|id| **id != self.active && !keep.contains(id))
+            .cloned()
+            .collect();
+        for id in &retired {
+            self.keys.remove(id);
+        }
+        retired
+    }
+
+    pub fn key_ids(&self) -> impl Iterator<Item = &String> {
+        self.keys.keys()
+    }
+
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let keys: Self =
+            serde_json::from_str(raw).context(BridgeError::Queue, "Invalid queue key set JSON")?;
+        for (id, key) in &keys.keys {
+            if id.is_empty() || id.contains(':') {
+                bail!(Queue, "Invalid queue key id {:?}", id);
+            }
+            let bytes = STANDARD.decode(key).with_context(BridgeError::Queue, || {
+                format!("Queue key {} is not base64", id)
+            })?;
+            if bytes.len() != 32 {
+                bail!(Queue, "Queue key {} is not a 256-bit key", id);
+            }
+        }
+        if !keys.keys.contains_key(&keys.active) {
+            bail!(
+                Queue,
+                "Active queue key {} is not in the key set",
+                keys.active
+            );
+        }
+        Ok(keys)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path).with_context(BridgeError::Io, || {
+            format!("Failed to read queue keys {:?}", path)
+        })?;
+        Self::from_json(&raw)
+    }
+
+    /// Write the key set, readable by the owner only on Unix. The keys go
+    /// to a new file created with those permissions, then renamed over
+    /// `path`, so they are never readable by others, even briefly.
+    pub fn save_json_file(&self, path: &Path) -> Result<()> {
+        let json = self.to_json()?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        // Left by an interrupted save
+        let _ = fs::remove_file(&tmp);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options
+            .open(&tmp)
+            .and_then(|mut file| {
+                file.write_all(json.as_bytes())?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&tmp, path))
+            .with_context(BridgeError::Io, || {
+                format!("Failed to write queue keys {:?}", path)
+            })?;
+        Ok(())
+    }
+
+    /// Key set stored in the OS keyring, if one has been saved.
+    #[cfg(any(windows, target_os = "macos"))]
+    pub fn from_keyring() -> Result<Option<Self>> {
+        match keyring_entry()?.get_password() {
+            Ok(raw) => Self::from_json(&raw).map(Some),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e).context(
+                BridgeError::Queue,
+                "Failed to read queue keys from the OS keyring",
+            ),
+        }
+    }
+
+    #[cfg(any(windows, target_os = "macos"))]
+    pub fn save_to_keyring(&self) -> Result<()> {
+        keyring_entry()?.set_password(&self.to_json()?).context(
+            BridgeError::Queue,
+            "Failed to store queue keys in the OS keyring",
+        )
+    }
+
+    #[cfg(not(any(windows, target_os = "macos")))]
+    pub fn from_keyring() -> Result<Option<Self>> {
+        bail!(Queue, "{}", NO_KEYRING)
+    }
+
+    #[cfg(not(any(windows, target_os = "macos")))]
+    pub fn save_to_keyring(&self) -> Result<()> {
+        bail!(Queue, "{}", NO_KEYRING)
+    }
+
+    fn cipher(&self, id: &str) -> Result<Aes256Gcm> {
+        let key = self.keys.get(id).with_context(BridgeError::Queue, || {
+            format!("Queue key {} is not available", id)
+        })?;
+        let bytes = STANDARD.decode(key).with_context(BridgeError::Queue, || {
+            format!("Queue key {} is not base64", id)
+        })?;
+        Aes256Gcm::new_from_slice(&bytes)
+            .map_err(|_| BridgeError::Queue(format!("Queue key {} is not 256-bit", id)))
+    }
+
+    /// Encrypt with the active key. `aad` (the bundle id) is authenticated
+    /// but not stored, so a ciphertext cannot be moved to another row.
+    pub fn encrypt(&self, plaintext: &[u8], aad: &str) -> Result<String> {
+        let nonce = Aes256Gcm::generate_nonce(OsRng);
+        let ciphertext = self
+            .cipher(&self.active)?
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|_| BridgeError::Queue("Queue encryption failed".into()))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(format!(
+            "{}{}:{}",
+            PREFIX,
+            self.active,
+            STANDARD.encode(sealed)
+        ))
+    }
+
+    pub fn decrypt(&self, stored: &str, aad: &str) -> Result<Vec<u8>> {
+        let Some(id) = key_id(stored) else {
+            bail!(Queue, "Queue value is not encrypted");
+        };
+        let sealed = STANDARD
+            .decode(&stored[PREFIX.len() + id.len() + 1..])
+            .context(BridgeError::Queue, "Encrypted queue value is not base64")?;
+        if sealed.len() < NONCE_LEN {
+            bail!(Queue, "Encrypted queue value is truncated");
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce: [u8; NONCE_LEN] = nonce
+            .try_into()
+            .context(BridgeError::Queue, "Encrypted queue value is truncated")?;
+        self.cipher(id)?
+            .decrypt(
+                &Nonce::from(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                BridgeError::Queue(format!("Queue value failed authentication (key {})", id))
+            })
+    }
+}
+
+/// Id of the key a stored value is encrypted with; `None` for plaintext.
+pub fn key_id(stored: &str) -> Option<&str> {
+    stored
+        .strip_prefix(PREFIX)?
+        .split_once(':')
+        .map(|(id, _)| id)
+}
+
+#[cfg(any(windows, target_os = "macos"))]
+fn keyring_entry() -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+        .context(BridgeError::Queue, "OS keyring unavailable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_binds_to_bundle_id() {
+        let keys = QueueKeys::generate();
+        let stored = keys.encrypt(br#"{"resourceType":"Bundle"}"#, "b1").unwrap();
+        assert!(!stored.contains("Bundle"));
+        assert_eq!(key_id(&stored), Some(keys.active.as_str()));
+        assert_eq!(
+            keys.decrypt(&stored, "b1").unwrap(),
+            br#"{"resourceType":"Bundle"}"#
+        );
+        assert!(keys.decrypt(&stored, "b2").is_err());
+        assert!(QueueKeys::generate().decrypt(&stored, "b1").is_err());
+    }
+
+    #[test]
+    fn rotation_keeps_old_keys_until_retired() {
+        let mut keys = QueueKeys::generate();
+        let old = keys.encrypt(b"{}", "b1").unwrap();
+        let first = keys.active.clone();
+        keys.rotate();
+        assert_ne!(keys.active, first);
+        assert_eq!(keys.decrypt(&old, "b1").unwrap(), b"{}");
+
+        let reloaded = QueueKeys::from_json(&keys.to_json().unwrap()).unwrap();
+        assert_eq!(reloaded.key_ids().count(), 2);
+
+        assert_eq!(keys.retire_except(&[]), vec![first]);
+        assert!(keys.decrypt(&old, "b1").is_err());
+    }
+
+    #[test]
+    fn key_file_is_replaced_and_owner_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue-keys.json");
+        let mut keys = QueueKeys::generate();
+        keys.save_json_file(&path).unwrap();
+        keys.rotate();
+        keys.save_json_file(&path).unwrap();
+
+        let saved = QueueKeys::from_json_file(&path).unwrap();
+        assert_eq!(saved.active, keys.active);
+        assert!(!dir.path().join("queue-keys.json.tmp").exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! OPD register line list (MOH 204A/B) with the MOH 705 row of each visit.
+//!
+//! One CSV row per visit, in the column order of the paper registers, so
+//! records officers get the register from the data already captured instead
+//! of copying it by hand. Under-fives go to MOH 204A (age in months as
+//! well), everyone else to MOH 204B; the `moh705_row` column is the tally
+//! sheet row the diagnosis counts towards (see [`crate::dhis2`]).
+use std::collections::HashSet;
+
+use crate::error::{BridgeError, Context, Result};
+use chrono::{Datelike, NaiveDate};
+use fhir_parser::masking::mask_identifier;
+
+use crate::dhis2::{AgeBand, Dhis2Mapping};
+use crate::kenyan::schema::KenyanPatient;
+use crate::surveillance::csv_field;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    /// Under five years
+    Moh204A,
+    /// Five years and over
+    Moh204B,
+}
+
+impl Register {
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Moh204A => "MOH 204A",
+            Self::Moh204B => "MOH 204B",
+        }
+    }
+}
+
+/// One register line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisterEntry {
+    pub register: Register,
+    pub date: NaiveDate,
+    pub clinic_id: String,
+    /// Facility patient (OPD) number
+    pub opd_number: String,
+    pub name: String,
+    pub age_years: u32,
+    /// Filled for MOH 204A, which records infants' age in months
+    pub age_months: Option<u32>,
+    /// `M`, `F` or `U`
+    pub sex: &'static str,
+    pub county: String,
+    pub subcounty: String,
+    /// First attendance of the patient at this facility in the calendar
+    /// year, as far as the records given show
+    pub new_attendance: bool,
+    pub complaint: String,
+    pub diagnosis: String,
+    pub moh705_row: String,
+    pub treatment: String,
+    pub sha_member_number: Option<String>,
+}
+
+/// Register lines for visits dated within `[from, to]` (either bound
+/// optional), ordered by date, facility and OPD number.
+///
+/// New/revisit is decided over every visit given, so pass earlier months of
+/// the year as well when listing a later one.
+pub fn line_list(
+    records: &[KenyanPatient],
+    mapping: &Dhis2Mapping,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<Vec<RegisterEntry>> {
+    let mut visits = Vec::new();
+    for record in records {
+        for visit in &record.visits {
+            let date = NaiveDate::parse_from_str(&visit.date, "%Y-%m-%d").context(
+                BridgeError::Validation,
+                "Invalid visit date format — expected YYYY-MM-DD",
+            )?;
+            visits.push((date, record, visit));
+        }
+    }
+    visits.sort_by(|a, b| {
+        (a.0, &a.1.clinic_id, &a.1.patient_number).cmp(&(b.0, &b.1.clinic_id, &b.1.patient_number))
+    });
+
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for (date, record, visit) in visits {
+        let new_attendance = seen.insert((
+            record.clinic_id.as_str(),
+            record.patient_number.as_str(),
+            date.year(),
+        ));
+        if from.is_some_and(|f| date < f) || to.is_some_and(|t| date > t) {
+            continue;
+        }
+        let dob = record.date_of_birth;
+        let register = match AgeBand::at(dob, date) {
+            AgeBand::Under5 => Register::Moh204A,
+            AgeBand::FiveAndOver => Register::Moh204B,
+        };
+        let names = &record.names;
+        entries.push(RegisterEntry {
+            register,
+            date,
+            clinic_id: record.clinic_id.clone(),
+            opd_number: record.patient_number.clone(),
+            name: [&names.first, &names.middle, &names.last]
+                .iter()
+                .filter(|n| !n.is_empty())
+                .map(|n| n.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+            age_years: date.years_since(dob).unwrap_or(0),
+            age_months: (register == Register::Moh204A).then(|| months_between(dob, date)),
+            sex: match record.gender.as_str() {
+                "M" => "M",
+                "F" => "F",
+                _ => "U",
+            },
+            county: record.location.county.clone(),
+            subcounty: record.location.subcounty.clone(),
+            new_attendance,
+            complaint: visit.complaint.clone(),
+            diagnosis: visit.diagnosis.clone(),
+            moh705_row: mapping
+                .element_for(&visit.diagnosis)
+                .map(|e| e.name.clone())
+                .unwrap_or_default(),
+            treatment: visit.treatment.clone(),
+            sha_member_number: visit.sha_member_number.clone(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Completed months from `from` to `to` (0 if `to` is earlier).
+pub(crate) fn months_between(from: NaiveDate, to: NaiveDate) -> u32 {
+    let months = (to.year() - from.year()) * 12 + to.month() as i32
+        - from.month() as i32
+        - i32::from(to.day() < from.day());
+    months.max(0) as u32
+}
+
+/// CSV rendering, one row per visit. SHA member numbers are masked unless
+/// identifiers are revealed.
+pub fn to_csv(entries: &[RegisterEntry]) -> String {
+    let mut out = String::from(
+        "register,date,clinic_id,opd_number,name,age_years,age_months,sex,county,subcounty,\
+         attendance,complaint,diagnosis,moh705_row,treatment,sha_member_number\n",
+    );
+    for e in entries {
+        let fields = [
+            e.register.code().to_string(),
+            e.date.to_string(),
+            e.clinic_id.clone(),
+            e.opd_number.clone(),
+            e.name.clone(),
+            e.age_years.to_string(),
+            e.age_months.map(|m| m.to_string()).unwrap_or_default(),
+            e.sex.to_string(),
+            e.county.clone(),
+            e.subcounty.clone(),
+            if e.new_attendance { "New" } else { "Revisit" }.to_string(),
+            e.complaint.clone(),
+            e.diagnosis.clone(),
+            e.moh705_row.clone(),
+            e.treatment.clone(),
+            e.sha_member_number
+                .as_deref()
+                .map(mask_identifier)
+                .unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn infant_age_counts_completed_months() {
+        assert_eq!(months_between(date("2025-11-20"), date("2026-10-19")), 10);
+        assert_eq!(months_between(date("2025-11-20"), date("2026-10-20")), 11);
+        assert_eq!(months_between(date("2026-10-20"), date("2026-10-01")), 0);
+    }
+}
@@ -0,0 +1,127 @@
+use fhir_parser::fhir::bundle::Bundle;
+use fhir_parser::fhir::operation_outcome::{OperationOutcome, OperationOutcomeIssue};
+
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::http;
+
+/// What to send to the server's `$validate` operation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValidateTarget {
+    /// POST the whole transaction Bundle to `{server}/Bundle/$validate`.
+    Bundle,
+    /// POST every entry's resource to `{server}/{resourceType}/$validate`.
+    EachResource,
+}
+
+/// An OperationOutcome issue together with what was validated
+/// (`Bundle` or `{resourceType}/{id}`).
+#[derive(Debug, Clone)]
+pub struct RemoteIssue {
+    pub target: String,
+    pub issue: OperationOutcomeIssue,
+}
+
+/// Validate a generated bundle against a FHIR server's `$validate` operation.
+///
+/// Returns every issue from the returned OperationOutcome(s), warnings
+/// included; callers decide whether errors block submission.
+pub fn validate_remote(
+    server: &str,
+    bundle: &Bundle,
+    target: ValidateTarget,
+) -> Result<Vec<RemoteIssue>> {
+    let server = server.trim_end_matches('/');
+
+    match target {
+        ValidateTarget::Bundle => {
+            let body = serde_json::to_string(bundle)?;
+            let outcome = post_validate(&format!("{}/Bundle/$validate", server), &body)?;
+            Ok(label_issues("Bundle", outcome))
+        }
+        ValidateTarget::EachResource => {
+            let mut issues = Vec::new();
+            for resource in bundle
+                .entry
+                .iter()
+                .flatten()
+                .filter_map(|e| e.resource.as_ref())
+            {
+                let resource_type = resource
+                    .get("resourceType")
+                    .and_then(|v| v.as_str())
+                    .context(
+                        BridgeError::Validation,
+                        "Bundle entry resource has no resourceType",
+                    )?;
+                let id = resource.get("id").and_then(|v| v.as_str()).unwrap_or("?");
+                let body = serde_json::to_string(resource)?;
+                let outcome =
+                    post_validate(&format!("{}/{}/$validate", server, resource_type), &body)?;
+                issues.extend(label_issues(&format!("{}/{}", resource_type, id), outcome));
+            }
+            Ok(issues)
+        }
+    }
+}
+
+fn label_issues(target: &str, outcome: OperationOutcome) -> Vec<RemoteIssue> {
+    outcome
+        .issue
+        .into_iter()
+        .map(|issue| RemoteIssue {
+            target: target.to_string(),
+            issue,
+        })
+        .collect()
+}
+
+/// POST a resource to a `$validate` endpoint and parse the OperationOutcome.
+///
+/// Servers answer validation failures with 4xx + OperationOutcome, so the
+/// HTTP status is not treated as an error — only transport failures are.
+fn post_validate(url: &str, body: &str) -> Result<OperationOutcome> {
+    let response = http::post(url, "application/fhir+json", body.as_bytes(), 30)
+        .context(BridgeError::Network, "Remote validation request failed")?;
+    parse_operation_outcome(&response.body)
+}
+
+fn parse_operation_outcome(body: &str) -> Result<OperationOutcome> {
+    let outcome: OperationOutcome = serde_json::from_str(body).context(
+        BridgeError::Network,
+        "Remote validation response is not an OperationOutcome",
+    )?;
+    if outcome.resource_type != "OperationOutcome" {
+        bail!(
+            Network,
+            "Remote validation response is not an OperationOutcome"
+        );
+    }
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hapi_style_outcome() {
+        let body = r#"{
+            "resourceType": "OperationOutcome",
+            "issue": [
+                {"severity": "error", "code": "required",
+                 "diagnostics": "Encounter.class: minimum required = 1",
+                 "expression": ["Bundle.entry[2].resource.class"]},
+                {"severity": "information", "code": "informational"}
+            ]
+        }"#;
+        let outcome = parse_operation_outcome(body).unwrap();
+        assert_eq!(outcome.issue.len(), 2);
+        assert!(outcome.issue[0].is_error());
+        assert!(!outcome.issue[1].is_error());
+    }
+
+    #[test]
+    fn rejects_non_outcome_response() {
+        assert!(parse_operation_outcome(r#"{"resourceType": "Bundle"}"#).is_err());
+    }
+}
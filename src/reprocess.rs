@@ -0,0 +1,169 @@
+//! Compare archived bundles with a re-transform of the same input under the
+//! current terminology and mappers.
+//!
+//! The element diff is `fhir_parser::diff` (order-insensitive, ignores bundle
+//! id/timestamp); this module classifies it for reporting. Differences under a
+//! `coding` element are reported as coding changes with their values (codes
+//! are not PHI); everything else is a content change reported by path only.
+use fhir_parser::diff::diff_bundles;
+use fhir_parser::fhir::bundle::Bundle;
+use fhir_parser::masking::mask_reference;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Coding,
+    Content,
+}
+
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub kind: ChangeKind,
+    /// `Type/id` of the resource
+    pub resource: String,
+    /// JSON path inside the resource, e.g. `code.coding[0].code`
+    pub path: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// Differences between an archived bundle and its re-transform.
+#[derive(Debug, Clone, Default)]
+pub struct BundleDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changes: Vec<Change>,
+}
+
+impl BundleDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changes.is_empty()
+    }
+
+    pub fn count(&self, kind: ChangeKind) -> usize {
+        self.changes.iter().filter(|c| c.kind == kind).count()
+    }
+}
+
+fn is_coding_path(path: &str) -> bool {
+    path.split('.')
+        .any(|segment| segment == "coding" || segment.starts_with("coding["))
+}
+
+/// Diff `archived` against `current`, resource by resource.
+pub fn compare_bundles(archived: &Bundle, current: &Bundle) -> BundleDiff {
+    let diff = diff_bundles(archived, current);
+    BundleDiff {
+        added: diff.added,
+        removed: diff.removed,
+        changes: diff
+            .changed
+            .into_iter()
+            .map(|c| Change {
+                kind: if is_coding_path(&c.path) {
+                    ChangeKind::Coding
+                } else {
+                    ChangeKind::Content
+                },
+                resource: c.resource,
+                path: c.path,
+                before: c.before,
+                after: c.after,
+            })
+            .collect(),
+    }
+}
+
+fn show(value: &Option<Value>) -> String {
+    match value {
+        Some(v) => v.to_string(),
+        None => "(none)".to_string(),
+    }
+}
+
+/// Human-readable summary of one record's diff. Resource ids are masked and
+/// only coding values are printed.
+pub fn summarize(record: &str, diff: &BundleDiff) -> String {
+    let mut out = format!(
+        "{}: {} coding change(s), {} content change(s), {} added, {} removed\n",
+        record,
+        diff.count(ChangeKind::Coding),
+        diff.count(ChangeKind::Content),
+        diff.added.len(),
+        diff.removed.len()
+    );
+    for key in &diff.added {
+        out.push_str(&format!("  + {}\n", mask_reference(key)));
+    }
+    for key in &diff.removed {
+        out.push_str(&format!("  - {}\n", mask_reference(key)));
+    }
+    for c in &diff.changes {
+        match c.kind {
+            ChangeKind::Coding => out.push_str(&format!(
+                "  ~ {} {}: {} -> {}\n",
+                mask_reference(&c.resource),
+                c.path,
+                show(&c.before),
+                show(&c.after)
+            )),
+            ChangeKind::Content => {
+                out.push_str(&format!("  ~ {} {}\n", mask_reference(&c.resource), c.path))
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fhir_parser::fhir::bundle::BundleEntry;
+    use serde_json::json;
+
+    fn bundle(resources: Vec<Value>) -> Bundle {
+        Bundle {
+            resource_type: "Bundle".into(),
+            id: Some(uuid::Uuid::new_v4().to_string()),
+            meta: None,
+            timestamp: None,
+            bundle_type: Some("transaction".into()),
+            entry: Some(
+                resources
+                    .into_iter()
+                    .map(|r| BundleEntry {
+                        full_url: None,
+                        resource: Some(r),
+                        request: None,
+                    })
+                    .collect(),
+            ),
+            signature: None,
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn separates_coding_and_content_changes() {
+        let archived = bundle(vec![json!({
+            "resourceType": "Condition", "id": "c1",
+            "code": {"coding": [{"system": "http://hl7.org/fhir/sid/icd-10", "code": "A09"}],
+                     "text": "gastro"}
+        })]);
+        let current = bundle(vec![
+            json!({
+                "resourceType": "Condition", "id": "c1",
+                "code": {"coding": [{"system": "http://hl7.org/fhir/sid/icd-10", "code": "A09.9"}],
+                         "text": "gastroenteritis"}
+            }),
+            json!({"resourceType": "Observation", "id": "o1"}),
+        ]);
+
+        let diff = compare_bundles(&archived, &current);
+        assert_eq!(diff.added, vec!["Observation/o1".to_string()]);
+        assert_eq!(diff.count(ChangeKind::Coding), 1);
+        assert_eq!(diff.count(ChangeKind::Content), 1);
+        assert_eq!(diff.changes[0].path, "code.coding[0].code");
+        assert!(compare_bundles(&archived, &archived).is_empty());
+    }
+}
@@ -0,0 +1,791 @@
+//! Reverse mapping: FHIR transaction Bundle → `KenyanPatient`.
+//!
+//! Lets clinics pull their data back out of the SHR into the local format.
+//! Works on bundles from this crate and on AfyaLink bundles of the same
+//! shape: resources are recognised by identifier systems and LOINC codes,
+//! not by the ids this crate assigns. Visits are the bundle's Encounters in
+//! date order; Observations, Conditions, MedicationRequests and Claims are
+//! matched to a visit by their encounter reference.
+//!
+//! Lossy where the forward mapping is. The SHA member number comes from the
+//! patient's Coverage and is set on every visit. A visit whose Claim used the
+//! default intervention code gets that code back explicitly. A visit enrolled
+//! by `--profile` comes back with its programme set. Immunization history does
+//! not come back: the bundle carries only the forecast made from it.
+use chrono::{DateTime, NaiveDate};
+use fhir_parser::fhir::appointment::Appointment;
+use fhir_parser::fhir::bundle::Bundle;
+use fhir_parser::fhir::care_plan::CarePlan;
+use fhir_parser::fhir::claim::Claim;
+use fhir_parser::fhir::clinical_impression::ClinicalImpression;
+use fhir_parser::fhir::condition::Condition;
+use fhir_parser::fhir::coverage::Coverage;
+use fhir_parser::fhir::device::Device;
+use fhir_parser::fhir::diagnostic_report::DiagnosticReport;
+use fhir_parser::fhir::document_reference::DocumentReference;
+use fhir_parser::fhir::encounter::Encounter;
+use fhir_parser::fhir::episode_of_care::EpisodeOfCare;
+use fhir_parser::fhir::flag::Flag;
+use fhir_parser::fhir::location::Location as FhirLocation;
+use fhir_parser::fhir::medication_request::MedicationRequest;
+use fhir_parser::fhir::observation::{Attachment, CodeableConcept, Observation};
+use fhir_parser::fhir::organization::Organization;
+use fhir_parser::fhir::patient::Patient;
+use fhir_parser::fhir::practitioner::Practitioner;
+use fhir_parser::fhir::practitioner_role::PractitionerRole;
+use fhir_parser::fhir::service_request::ServiceRequest;
+use fhir_parser::fhir::specimen::Specimen;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::kenyan::schema::{
+    AntenatalFindings, Biometric, BiometricModality, Cadre, CareProgramme, DangerSign, Department,
+    DeviceKind, DiagnosisCategory, DocumentKind, ImagingOrder, InlineAttachment, KenyanPatient,
+    LabOrder, Location, Names, ScreeningFindings, ScreeningResult, Severity, SpecimenType, TbPhase,
+    Visit, VisitAttachment, Vitals, VitalsDevice,
+};
+use crate::mapper::antenatal::PREGNANCY_SNOMED;
+use crate::mapper::document_reference::document_type;
+use crate::mapper::imaging::IMAGING_SNOMED;
+use crate::mapper::programme::FOLLOW_UP_SNOMED;
+use crate::mapper::screening::{
+    HEARING_SCREEN_LOINC, VISUAL_ACUITY_LEFT_LOINC, VISUAL_ACUITY_RIGHT_LOINC,
+};
+use crate::mapper::triage::ACUITY_LOINC;
+use crate::systems::{
+    BIOMETRIC_TYPE_SYSTEM, BIRTH_DATE_ESTIMATED_URL, DANGER_SIGN_SYSTEM, FACILITY_SYSTEM,
+    HIV_WHO_STAGE_SYSTEM, HWR_SYSTEM, NATIONAL_ID_SYSTEM, SHA_MEMBER_SYSTEM, TB_PHASE_SYSTEM,
+};
+
+const PATIENT_NUMBER_SUFFIX: &str = "/patient-number";
+const VISIT_ID_SUFFIX: &str = "/visit-id";
+
+/// Every resource of type `T` in the bundle.
+fn resources<T: DeserializeOwned>(bundle: &Bundle, resource_type: &str) -> Result<Vec<T>> {
+    bundle
+        .entry
+        .as_deref()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|e| e.resource.as_ref())
+        .filter(|r| r.get("resourceType").and_then(Value::as_str) == Some(resource_type))
+        .map(|r| {
+            serde_json::from_value(r.clone()).with_context(BridgeError::Mapping, || {
+                format!("Invalid {}", resource_type)
+            })
+        })
+        .collect()
+}
+
+fn refers_to(reference: Option<&str>, resource_type: &str, id: Option<&str>) -> bool {
+    match (reference, id) {
+        (Some(r), Some(id)) => r == format!("{}/{}", resource_type, id),
+        _ => false,
+    }
+}
+
+fn has_code(concept: &CodeableConcept, code: &str) -> bool {
+    concept
+        .coding
+        .iter()
+        .flatten()
+        .any(|c| c.code.as_deref() == Some(code))
+}
+
+fn concept_text(concept: &CodeableConcept) -> Option<String> {
+    concept.text.clone().or_else(|| {
+        concept
+            .coding
+            .iter()
+            .flatten()
+            .find_map(|c| c.display.clone())
+    })
+}
+
+fn vitals_for(observations: &[&Observation], devices: &[Device], date: &str) -> Result<Vitals> {
+    let value = |code: &str| {
+        observations
+            .iter()
+            .find(|o| has_code(&o.code, code))
+            .and_then(|o| o.value_quantity.as_ref())
+            .map(|q| q.value)
+    };
+    let bp_component = |code: &str| {
+        observations
+            .iter()
+            .filter(|o| has_code(&o.code, "85354-9"))
+            .flat_map(|o| o.component.iter().flatten())
+            .find(|c| has_code(&c.code, code))
+            .and_then(|c| c.value_quantity.as_ref())
+            .map(|q| q.value)
+    };
+    let required = |v: Option<f64>, what: &str| {
+        v.with_context(BridgeError::Mapping, || {
+            format!("Visit on {} has no {} observation", date, what)
+        })
+    };
+
+    Ok(Vitals {
+        temperature_celsius: required(value("8310-5"), "temperature")?,
+        bp_systolic: required(bp_component("8480-6"), "systolic BP")?.round() as i32,
+        bp_diastolic: required(bp_component("8462-2"), "diastolic BP")?.round() as i32,
+        weight_kg: required(value("29463-7"), "weight")?,
+        pulse_rate: value("8867-4").map(|v| v.round() as i32),
+        o2_saturation: value("59408-5"),
+        height_cm: value("8302-2"),
+        muac_cm: value("56072-2"),
+        devices: devices
+            .iter()
+            .filter(|d| {
+                observations.iter().any(|o| {
+                    refers_to(
+                        o.device.as_ref().and_then(|r| r.reference.as_deref()),
+                        "Device",
+                        d.id.as_deref(),
+                    )
+                })
+            })
+            .filter_map(|d| {
+                let kind = DeviceKind::ALL
+                    .into_iter()
+                    .find(|k| d.type_field.as_ref().is_some_and(|t| has_code(t, k.code())))?;
+                Some(VitalsDevice {
+                    kind,
+                    id: d.identifier.as_ref()?.first()?.value.clone(),
+                })
+            })
+            .collect(),
+    })
+}
+
+fn antenatal_for(observations: &[&Observation]) -> Option<AntenatalFindings> {
+    let value = |code: &str| {
+        observations
+            .iter()
+            .find(|o| has_code(&o.code, code))
+            .and_then(|o| o.value_quantity.as_ref())
+            .map(|q| q.value)
+    };
+    Some(AntenatalFindings {
+        gestational_age_weeks: value("49051-6")?.round() as u8,
+        gravida: value("11996-6")?.round() as u8,
+        para: value("11977-6")?.round() as u8,
+        fundal_height_cm: value("11881-0"),
+        fetal_heart_rate: value("55283-6").map(|v| v.round() as u16),
+    })
+}
+
+fn screening_for(observations: &[&Observation]) -> Option<ScreeningFindings> {
+    let find = |code: &str| observations.iter().find(|o| has_code(&o.code, code));
+    let visual_acuity_left = find(VISUAL_ACUITY_LEFT_LOINC).and_then(|o| o.value_string.clone());
+    let visual_acuity_right = find(VISUAL_ACUITY_RIGHT_LOINC).and_then(|o| o.value_string.clone());
+    let hearing = find(HEARING_SCREEN_LOINC)
+        .and_then(|o| o.value_codeable_concept.as_ref())
+        .and_then(|v| {
+            ScreeningResult::ALL
+                .into_iter()
+                .find(|r| has_code(v, r.code()))
+        });
+    (visual_acuity_left.is_some() || visual_acuity_right.is_some() || hearing.is_some()).then_some(
+        ScreeningFindings {
+            visual_acuity_left,
+            visual_acuity_right,
+            hearing,
+        },
+    )
+}
+
+/// Condition.stage code from `system` (WHO stage, TB phase).
+fn stage_code<'a>(condition: Option<&'a Condition>, system: &str) -> Option<&'a str> {
+    condition?
+        .stage
+        .iter()
+        .flatten()
+        .filter_map(|s| s.summary.as_ref())
+        .flat_map(|c| c.coding.iter().flatten())
+        .find(|c| c.system.as_deref() == Some(system))?
+        .code
+        .as_deref()
+}
+
+/// The clinic visit number on an Encounter.
+fn visit_id(encounter: &Encounter) -> Option<String> {
+    encounter
+        .identifier
+        .iter()
+        .flatten()
+        .find(|i| {
+            i.system
+                .as_deref()
+                .is_some_and(|s| s.ends_with(VISIT_ID_SUFFIX))
+        })
+        .map(|i| i.value.clone())
+}
+
+/// Inline content of an attachment; ones given only by URL are skipped.
+fn inline(attachment: &Attachment) -> Option<InlineAttachment> {
+    Some(InlineAttachment {
+        content_type: attachment.content_type.clone()?,
+        data: attachment.data.clone()?,
+    })
+}
+
+/// VisitAttachment from a DocumentReference; None without a content type.
+fn visit_attachment(document: &DocumentReference) -> Option<VisitAttachment> {
+    let attachment = &document.content.first()?.attachment;
+    let kind = [
+        DocumentKind::LabReport,
+        DocumentKind::ImagingReport,
+        DocumentKind::ReferralLetter,
+        DocumentKind::ClinicalNote,
+    ]
+    .into_iter()
+    .find(|k| {
+        document
+            .doc_type
+            .as_ref()
+            .is_some_and(|t| has_code(t, document_type(*k).0))
+    })
+    .unwrap_or_default();
+    Some(VisitAttachment {
+        kind,
+        content_type: attachment.content_type.clone()?,
+        title: attachment.title.clone(),
+        data: attachment.data.clone(),
+        url: attachment.url.clone(),
+    })
+}
+
+/// Reconstruct the Kenyan record a bundle was produced from.
+pub fn bundle_to_kenyan(bundle: &Bundle) -> Result<KenyanPatient> {
+    let patients: Vec<Patient> = resources(bundle, "Patient")?;
+    let patient = match patients.as_slice() {
+        [p] => p,
+        [] => bail!(Mapping, "Bundle has no Patient"),
+        _ => bail!(
+            Mapping,
+            "Bundle has {} Patients; expected one",
+            patients.len()
+        ),
+    };
+    let identifiers = patient.identifier.as_deref().unwrap_or_default();
+    let identifier = |pred: &dyn Fn(&str) -> bool| {
+        identifiers
+            .iter()
+            .find(|i| i.system.as_deref().is_some_and(pred))
+    };
+
+    let national_id = identifier(&|s| s == NATIONAL_ID_SYSTEM)
+        .map(|i| i.value.clone())
+        .context(
+            BridgeError::Mapping,
+            "Patient has no national ID identifier",
+        )?;
+    let patient_number_ident = identifier(&|s| s.ends_with(PATIENT_NUMBER_SUFFIX)).context(
+        BridgeError::Mapping,
+        "Patient has no facility patient-number identifier",
+    )?;
+    // System is `{facility registry}/{clinic_id}/patient-number`
+    let clinic_from_system = patient_number_ident
+        .system
+        .as_deref()
+        .and_then(|s| s.strip_suffix(PATIENT_NUMBER_SUFFIX))
+        .and_then(|s| s.rsplit('/').next())
+        .map(str::to_string);
+    let organizations: Vec<Organization> = resources(bundle, "Organization")?;
+    let clinic_id = organizations
+        .iter()
+        .flat_map(|o| o.identifier.iter().flatten())
+        .find(|i| i.system.as_deref() == Some(FACILITY_SYSTEM))
+        .map(|i| i.value.clone())
+        .or(clinic_from_system)
+        .context(BridgeError::Mapping, "Bundle has no facility identifier")?;
+
+    let name = patient.name.as_ref().and_then(|n| n.first());
+    let given = name.and_then(|n| n.given.clone()).unwrap_or_default();
+    let names = Names {
+        first: given.first().cloned().unwrap_or_default(),
+        middle: given.get(1..).map(|m| m.join(" ")).unwrap_or_default(),
+        last: name.and_then(|n| n.family.clone()).unwrap_or_default(),
+    };
+    let address = patient.address.as_ref().and_then(|a| a.first());
+    let location = Location {
+        county: address.and_then(|a| a.district.clone()).unwrap_or_default(),
+        subcounty: address
+            .and_then(|a| a.line.as_ref().and_then(|l| l.first().cloned()))
+            .unwrap_or_default(),
+    };
+    let phone = patient
+        .telecom
+        .iter()
+        .flatten()
+        .find(|t| t.system.as_deref() == Some("phone"))
+        .map(|t| t.value.clone())
+        .unwrap_or_default();
+    let gender = match patient.gender.as_deref() {
+        Some("male") => "M",
+        Some("female") => "F",
+        Some("other") => "O",
+        _ => "U",
+    }
+    .to_string();
+    let date_of_birth: NaiveDate = patient
+        .birth_date
+        .context(BridgeError::Mapping, "Patient has no birthDate")?;
+    let birth_date_estimated = patient
+        .birth_date_element
+        .iter()
+        .flat_map(|e| &e.extension)
+        .any(|x| x.url == BIRTH_DATE_ESTIMATED_URL && x.value_boolean == Some(true));
+
+    let photo = patient.photo.iter().flatten().find_map(inline);
+    let biometrics = resources::<DocumentReference>(bundle, "DocumentReference")?
+        .iter()
+        .filter(|d| {
+            refers_to(
+                d.subject.as_ref().and_then(|s| s.reference.as_deref()),
+                "Patient",
+                patient.id.as_deref(),
+            )
+        })
+        .filter_map(|d| {
+            let modality = d
+                .doc_type
+                .iter()
+                .flat_map(|t| t.coding.iter().flatten())
+                .filter(|c| c.system.as_deref() == Some(BIOMETRIC_TYPE_SYSTEM))
+                .find_map(|c| BiometricModality::from_code(c.code.as_deref()?))?;
+            let content = d.content.first().and_then(|c| inline(&c.attachment))?;
+            Some(Biometric {
+                modality,
+                content_type: content.content_type,
+                data: content.data,
+            })
+        })
+        .collect();
+
+    let sha_member_number = resources::<Coverage>(bundle, "Coverage")?
+        .iter()
+        .flat_map(|c| c.identifier.iter().flatten())
+        .find(|i| i.system.as_deref() == Some(SHA_MEMBER_SYSTEM))
+        .map(|i| i.value.clone());
+    let practitioners: Vec<Practitioner> = resources(bundle, "Practitioner")?;
+    let devices: Vec<Device> = resources(bundle, "Device")?;
+    let roles: Vec<PractitionerRole> = resources(bundle, "PractitionerRole")?;
+    let locations: Vec<FhirLocation> = resources(bundle, "Location")?;
+    let observations: Vec<Observation> = resources(bundle, "Observation")?;
+    let conditions: Vec<Condition> = resources(bundle, "Condition")?;
+    let medications: Vec<MedicationRequest> = resources(bundle, "MedicationRequest")?;
+    let claims: Vec<Claim> = resources(bundle, "Claim")?;
+    let documents: Vec<DocumentReference> = resources(bundle, "DocumentReference")?;
+    let episodes: Vec<EpisodeOfCare> = resources(bundle, "EpisodeOfCare")?;
+    let care_plans: Vec<CarePlan> = resources(bundle, "CarePlan")?;
+    let appointments: Vec<Appointment> = resources(bundle, "Appointment")?;
+    let impressions: Vec<ClinicalImpression> = resources(bundle, "ClinicalImpression")?;
+    let flags: Vec<Flag> = resources(bundle, "Flag")?;
+    let service_requests: Vec<ServiceRequest> = resources(bundle, "ServiceRequest")?;
+    let specimens: Vec<Specimen> = resources(bundle, "Specimen")?;
+    let reports: Vec<DiagnosticReport> = resources(bundle, "DiagnosticReport")?;
+
+    let mut encounters: Vec<Encounter> = resources(bundle, "Encounter")?;
+    encounters.sort_by_key(|e| e.period.as_ref().and_then(|p| p.start.clone()));
+    if encounters.is_empty() {
+        bail!(Mapping, "Bundle has no Encounter");
+    }
+
+    let mut visits = Vec::new();
+    for enc in &encounters {
+        let enc_id = enc.id.as_deref();
+        let date = enc
+            .period
+            .as_ref()
+            .and_then(|p| p.start.clone())
+            .context(BridgeError::Mapping, "Encounter has no period.start")?;
+
+        let visit_obs: Vec<&Observation> = observations
+            .iter()
+            .filter(|o| {
+                refers_to(
+                    o.encounter.as_ref().and_then(|r| r.reference.as_deref()),
+                    "Encounter",
+                    enc_id,
+                )
+            })
+            .collect();
+        // The diagnosis, not the pregnancy Condition of an ANC visit
+        let condition = conditions.iter().find(|c| {
+            refers_to(
+                c.encounter.as_ref().and_then(|r| r.reference.as_deref()),
+                "Encounter",
+                enc_id,
+            ) && !c
+                .code
+                .as_ref()
+                .is_some_and(|k| has_code(k, PREGNANCY_SNOMED))
+        });
+        let medication = medications.iter().find(|m| {
+            refers_to(
+                m.encounter.as_ref().and_then(|r| r.reference.as_deref()),
+                "Encounter",
+                enc_id,
+            )
+        });
+        let claim = claims.iter().find(|c| {
+            c.encounter
+                .iter()
+                .flatten()
+                .any(|r| refers_to(r.reference.as_deref(), "Encounter", enc_id))
+        });
+
+        // Either a reference to an Encounter in this bundle or a conditional
+        // reference ending in `|{visit_id}`
+        let previous_visit_id = enc
+            .part_of
+            .as_ref()
+            .and_then(|r| r.reference.as_deref())
+            .and_then(|r| match r.split_once("?identifier=") {
+                Some((_, condition)) => condition.rsplit_once('|').map(|(_, v)| v.to_string()),
+                None => encounters
+                    .iter()
+                    .find(|e| refers_to(Some(r), "Encounter", e.id.as_deref()))
+                    .and_then(visit_id),
+            });
+        let programme = enc
+            .episode_of_care
+            .iter()
+            .flatten()
+            .filter_map(|r| r.reference.as_deref())
+            .find_map(|r| {
+                episodes
+                    .iter()
+                    .find(|e| refers_to(Some(r), "EpisodeOfCare", e.id.as_deref()))
+            })
+            .and_then(|e| {
+                CareProgramme::ALL
+                    .into_iter()
+                    .find(|p| e.type_field.iter().flatten().any(|t| has_code(t, p.code())))
+            });
+
+        // An order comes back only with a specimen type the schema knows
+        let lab_orders = service_requests
+            .iter()
+            .filter(|r| {
+                refers_to(
+                    r.encounter.as_ref().and_then(|e| e.reference.as_deref()),
+                    "Encounter",
+                    enc_id,
+                )
+            })
+            .filter_map(|r| {
+                let code = r.code.as_ref()?;
+                let specimen = specimens.iter().find(|s| {
+                    s.request.iter().flatten().any(|q| {
+                        refers_to(q.reference.as_deref(), "ServiceRequest", r.id.as_deref())
+                    })
+                })?;
+                Some(LabOrder {
+                    test_code: code.coding.as_ref()?.first()?.code.clone()?,
+                    test_name: code.text.clone(),
+                    specimen: SpecimenType::ALL.into_iter().find(|t| {
+                        specimen
+                            .type_field
+                            .as_ref()
+                            .is_some_and(|c| has_code(c, t.code()))
+                    })?,
+                    collected_at: specimen
+                        .collection
+                        .as_ref()
+                        .and_then(|c| c.collected_date_time.as_deref())
+                        .and_then(|at| DateTime::parse_from_rfc3339(at).ok()),
+                })
+            })
+            .collect();
+
+        let attachments = documents
+            .iter()
+            .filter(|d| {
+                d.context
+                    .iter()
+                    .flat_map(|c| c.encounter.iter().flatten())
+                    .any(|r| refers_to(r.reference.as_deref(), "Encounter", enc_id))
+            })
+            // Images and reports of an imaging order come back with it
+            .filter(|d| d.context.as_ref().is_none_or(|c| c.related.is_none()))
+            .filter_map(visit_attachment)
+            .collect();
+
+        let imaging_orders = service_requests
+            .iter()
+            .filter(|r| {
+                refers_to(
+                    r.encounter.as_ref().and_then(|e| e.reference.as_deref()),
+                    "Encounter",
+                    enc_id,
+                ) && r
+                    .category
+                    .iter()
+                    .flatten()
+                    .any(|c| has_code(c, IMAGING_SNOMED))
+            })
+            .filter_map(|r| {
+                let code = r.code.as_ref()?;
+                Some(ImagingOrder {
+                    study_code: code.coding.as_ref()?.first()?.code.clone()?,
+                    study_name: code.text.clone(),
+                    conclusion: reports
+                        .iter()
+                        .find(|report| {
+                            report.based_on.iter().flatten().any(|b| {
+                                refers_to(b.reference.as_deref(), "ServiceRequest", r.id.as_deref())
+                            })
+                        })
+                        .and_then(|report| report.conclusion.clone()),
+                    attachments: documents
+                        .iter()
+                        .filter(|d| {
+                            d.context
+                                .iter()
+                                .flat_map(|c| c.related.iter().flatten())
+                                .any(|q| {
+                                    refers_to(
+                                        q.reference.as_deref(),
+                                        "ServiceRequest",
+                                        r.id.as_deref(),
+                                    )
+                                })
+                        })
+                        .filter_map(visit_attachment)
+                        .collect(),
+                })
+            })
+            .collect();
+
+        // The Condition note keeps the raw complaint; reasonCode may be coded.
+        let complaint = condition
+            .and_then(|c| c.note.as_ref())
+            .and_then(|n| n.iter().find_map(|a| a.text.strip_prefix("Complaint: ")))
+            .map(str::to_string)
+            .or_else(|| {
+                let texts: Vec<String> = enc
+                    .reason_code
+                    .iter()
+                    .flatten()
+                    .filter_map(concept_text)
+                    .collect();
+                (!texts.is_empty()).then(|| texts.join(", "))
+            })
+            .unwrap_or_default();
+
+        let practitioner = enc
+            .participant
+            .iter()
+            .flatten()
+            .filter_map(|p| p.individual.reference.as_deref())
+            .find_map(|r| {
+                practitioners
+                    .iter()
+                    .find(|p| refers_to(Some(r), "Practitioner", p.id.as_deref()))
+            });
+        let puid = |p: &Practitioner| {
+            p.identifier
+                .iter()
+                .flatten()
+                .find(|i| i.system.as_deref() == Some(HWR_SYSTEM))
+                .map(|i| i.value.clone())
+        };
+        let attending_puid = practitioner.and_then(puid);
+        // Set only when someone other than the attending clinician took them
+        let vitals_taken_by = visit_obs
+            .iter()
+            .flat_map(|o| o.performer.iter().flatten())
+            .find_map(|r| {
+                practitioners
+                    .iter()
+                    .find(|p| refers_to(r.reference.as_deref(), "Practitioner", p.id.as_deref()))
+            })
+            .and_then(puid)
+            .filter(|p| attending_puid.as_ref() != Some(p));
+        let attending_cadre = practitioner
+            .and_then(|p| {
+                roles.iter().find(|r| {
+                    refers_to(
+                        r.practitioner.as_ref().and_then(|r| r.reference.as_deref()),
+                        "Practitioner",
+                        p.id.as_deref(),
+                    )
+                })
+            })
+            .and_then(|r| {
+                Cadre::ALL
+                    .into_iter()
+                    .find(|c| r.code.iter().flatten().any(|k| has_code(k, c.code())))
+            });
+
+        let treatment = medication
+            .and_then(|m| {
+                m.medication_codeable_concept
+                    .as_ref()
+                    .and_then(concept_text)
+                    .or_else(|| {
+                        m.dosage_instruction
+                            .as_ref()?
+                            .first()
+                            .map(|d| d.text.clone())
+                    })
+            })
+            .unwrap_or_default();
+        let care_plan = care_plans.iter().find(|p| {
+            refers_to(
+                p.encounter.as_ref().and_then(|r| r.reference.as_deref()),
+                "Encounter",
+                enc_id,
+            )
+        });
+        // A CarePlan without a regimen of its own repeats the treatment
+        let regimen = care_plan
+            .and_then(|p| p.description.clone())
+            .filter(|r| *r != treatment);
+        let review_date = care_plan
+            .into_iter()
+            .flat_map(|p| p.activity.iter().flatten())
+            .filter_map(|a| a.detail.as_ref())
+            .find(|d| {
+                d.code
+                    .as_ref()
+                    .is_some_and(|c| has_code(c, FOLLOW_UP_SNOMED))
+            })
+            .and_then(|d| d.scheduled_period.as_ref()?.start.as_deref())
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+
+        visits.push(Visit {
+            vitals: vitals_for(&visit_obs, &devices, &date)?,
+            complaint,
+            diagnosis: condition
+                .and_then(|c| c.code.as_ref())
+                .and_then(concept_text)
+                .unwrap_or_default(),
+            diagnosis_category: condition
+                .into_iter()
+                .flat_map(|c| c.category.iter().flatten())
+                .find_map(|k| {
+                    DiagnosisCategory::ALL
+                        .into_iter()
+                        .find(|d| has_code(k, d.code()))
+                })
+                .unwrap_or_default(),
+            diagnosis_severity: condition
+                .and_then(|c| c.severity.as_ref())
+                .and_then(|s| Severity::ALL.into_iter().find(|v| has_code(s, v.code()))),
+            onset_date: condition
+                .and_then(|c| c.onset_date_time.as_deref()?.get(..10))
+                .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()),
+            treatment,
+            clinical_note: impressions
+                .iter()
+                .find(|i| {
+                    refers_to(
+                        i.encounter.as_ref().and_then(|r| r.reference.as_deref()),
+                        "Encounter",
+                        enc_id,
+                    )
+                })
+                .and_then(|i| i.summary.clone()),
+            attending_puid,
+            attending_cadre,
+            vitals_taken_by,
+            sha_member_number: sha_member_number.clone(),
+            sha_intervention_code: claim
+                .and_then(|c| c.item.as_ref()?.first())
+                .and_then(|i| i.product_or_service.coding.as_ref()?.first()?.code.clone()),
+            attachments,
+            lab_orders,
+            imaging_orders,
+            visit_id: visit_id(enc),
+            previous_visit_id,
+            programme,
+            hiv_who_stage: stage_code(condition, HIV_WHO_STAGE_SYSTEM).and_then(|s| s.parse().ok()),
+            tb_phase: stage_code(condition, TB_PHASE_SYSTEM)
+                .and_then(|s| TbPhase::ALL.into_iter().find(|p| p.code() == s)),
+            regimen,
+            review_date,
+            // The Appointment is booked for the visit's Condition
+            next_appointment_date: appointments
+                .iter()
+                .find(|a| {
+                    a.reason_reference.iter().flatten().any(|r| {
+                        refers_to(
+                            r.reference.as_deref(),
+                            "Condition",
+                            condition.and_then(|c| c.id.as_deref()),
+                        )
+                    })
+                })
+                .and_then(|a| a.start.as_deref()?.get(..10))
+                .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()),
+            anc: antenatal_for(&visit_obs),
+            screening: screening_for(&visit_obs),
+            triage_category: visit_obs
+                .iter()
+                .find(|o| has_code(&o.code, ACUITY_LOINC))
+                .and_then(|o| o.value_codeable_concept.as_ref())
+                .and_then(|v| v.coding.as_ref()?.first()?.code.as_deref()?.parse().ok()),
+            danger_signs: flags
+                .iter()
+                .filter(|f| {
+                    refers_to(
+                        f.encounter.as_ref().and_then(|r| r.reference.as_deref()),
+                        "Encounter",
+                        enc_id,
+                    )
+                })
+                .flat_map(|f| f.code.coding.iter().flatten())
+                .filter(|c| c.system.as_deref() == Some(DANGER_SIGN_SYSTEM))
+                .filter_map(|c| {
+                    DangerSign::ALL
+                        .into_iter()
+                        .find(|s| c.code.as_deref() == Some(s.code()))
+                })
+                .collect(),
+            department: enc
+                .location
+                .iter()
+                .flatten()
+                .find_map(|l| {
+                    locations.iter().find(|loc| {
+                        refers_to(
+                            l.location.reference.as_deref(),
+                            "Location",
+                            loc.id.as_deref(),
+                        )
+                    })
+                })
+                .and_then(|loc| {
+                    Department::ALL.into_iter().find(|d| {
+                        loc.type_field
+                            .iter()
+                            .flatten()
+                            .any(|t| has_code(t, d.code()))
+                    })
+                }),
+            date,
+        });
+    }
+
+    Ok(KenyanPatient {
+        clinic_id,
+        patient_number: patient_number_ident.value.clone(),
+        national_id,
+        names,
+        gender,
+        date_of_birth,
+        birth_date_estimated,
+        phone,
+        location,
+        visits,
+        photo,
+        biometrics,
+        immunizations: Vec::new(),
+        data_quality: Vec::new(),
+    })
+}
@@ -0,0 +1,220 @@
+//! Exit-code contract and the machine-readable run summary.
+//!
+//! Facility wrapper scripts branch on the exit code: a record that fails
+//! validation has to go back to the clinic, a network failure only needs a
+//! retry. Codes are stable; new kinds get new codes.
+//!
+//! | Code | Meaning |
+//! |------|---------|
+//! | 0 | success |
+//! | 1 | any other failure |
+//! | 2 | bad command line (clap) |
+//! | 3 | input record or bundle failed validation |
+//! | 4 | record could not be mapped to FHIR |
+//! | 5 | file could not be read or written |
+//! | 6 | network: no response, or the server rejected the submission |
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use kenya_fhir_bridge::error::BridgeError;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    Validation,
+    Mapping,
+    Io,
+    Network,
+    Other,
+}
+
+impl FailureKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FailureKind::Validation => "validation",
+            FailureKind::Mapping => "mapping",
+            FailureKind::Io => "io",
+            FailureKind::Network => "network",
+            FailureKind::Other => "other",
+        }
+    }
+
+    pub fn exit_code(self) -> u8 {
+        match self {
+            FailureKind::Other => 1,
+            FailureKind::Validation => 3,
+            FailureKind::Mapping => 4,
+            FailureKind::Io => 5,
+            FailureKind::Network => 6,
+        }
+    }
+
+    /// Kind of a failed run. A network error anywhere in the chain wins
+    /// (a mapping step that needed the network failed because of it), then
+    /// the outermost [`Failure`] tag, then the library's [`BridgeError`]
+    /// kind, then a file-system error.
+    pub fn of(error: &anyhow::Error) -> Self {
+        let bridge = error.chain().find_map(|e| e.downcast_ref::<BridgeError>());
+        if let Some(BridgeError::Network(_)) = bridge {
+            return FailureKind::Network;
+        }
+        if let Some(failure) = error.downcast_ref::<Failure>() {
+            return failure.kind;
+        }
+        match bridge {
+            Some(BridgeError::Validation(_)) => FailureKind::Validation,
+            Some(BridgeError::Mapping(_) | BridgeError::Terminology(_)) => FailureKind::Mapping,
+            Some(BridgeError::Io(_)) => FailureKind::Io,
+            Some(_) => FailureKind::Other,
+            None if error.chain().any(|e| e.is::<std::io::Error>()) => FailureKind::Io,
+            None => FailureKind::Other,
+        }
+    }
+}
+
+/// An error message that also fixes the exit code: attach it with
+/// `.context(Failure::new(..))` or return it as the error.
+#[derive(Debug)]
+pub struct Failure {
+    pub kind: FailureKind,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new(kind: FailureKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// Written by `--summary-json`: overall outcome, record counts, and the
+/// status and timing of each record.
+#[derive(Debug, Serialize)]
+pub struct RunSummary {
+    pub command: String,
+    /// `ok` or the [`FailureKind`] of the run
+    pub status: String,
+    pub exit_code: u8,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u128,
+    pub counts: RecordCounts,
+    pub records: Vec<RecordSummary>,
+    #[serde(skip)]
+    started: Instant,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RecordCounts {
+    pub total: usize,
+    pub ok: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecordSummary {
+    pub input: PathBuf,
+    pub status: String,
+    pub error: Option<String>,
+    pub duration_ms: u128,
+}
+
+impl RunSummary {
+    pub fn start(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            status: "ok".to_string(),
+            exit_code: 0,
+            error: None,
+            started_at: Utc::now(),
+            duration_ms: 0,
+            counts: RecordCounts::default(),
+            records: Vec::new(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Note how one input record fared.
+    pub fn record(&mut self, input: &Path, result: &Result<()>, elapsed: Duration) {
+        self.counts.total += 1;
+        let (status, error) = match result {
+            Ok(()) => {
+                self.counts.ok += 1;
+                ("ok".to_string(), None)
+            }
+            Err(e) => {
+                self.counts.failed += 1;
+                (
+                    FailureKind::of(e).as_str().to_string(),
+                    Some(format!("{:#}", e)),
+                )
+            }
+        };
+        self.records.push(RecordSummary {
+            input: input.to_path_buf(),
+            status,
+            error,
+            duration_ms: elapsed.as_millis(),
+        });
+    }
+
+    /// Set the overall outcome; returns the process exit code.
+    pub fn finish(&mut self, result: &Result<()>) -> u8 {
+        self.duration_ms = self.started.elapsed().as_millis();
+        if let Err(e) = result {
+            let kind = FailureKind::of(e);
+            self.status = kind.as_str().to_string();
+            self.exit_code = kind.exit_code();
+            self.error = Some(format!("{:#}", e));
+        }
+        self.exit_code
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).with_context(|| format!("Failed to write {:?}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn failures_map_to_distinct_exit_codes() {
+        let validation: Result<()> = Err(anyhow!("visit_date is in the future")).context(
+            Failure::new(FailureKind::Validation, "Patient record failed validation"),
+        );
+        let io: Result<()> = std::fs::read("/nonexistent/record.json")
+            .map(|_| ())
+            .context("Failed to read record");
+        let network: Result<()> = Err(BridgeError::Network("refused".into()))
+            .context(Failure::new(FailureKind::Mapping, "Mapping to FHIR failed"));
+
+        let mut summary = RunSummary::start("validate");
+        for result in [&validation, &io, &network, &Ok(())] {
+            summary.record(Path::new("r.json"), result, Duration::ZERO);
+        }
+        let statuses: Vec<_> = summary.records.iter().map(|r| r.status.as_str()).collect();
+        assert_eq!(statuses, ["validation", "io", "network", "ok"]);
+        assert_eq!((summary.counts.ok, summary.counts.failed), (1, 3));
+
+        assert_eq!(summary.finish(&validation), 3);
+        assert_eq!(summary.status, "validation");
+        assert_eq!(FailureKind::of(&anyhow!("sqlite busy")).exit_code(), 1);
+    }
+}
@@ -0,0 +1,234 @@
+//! Syndromic surveillance signals derived from transformed visits.
+//!
+//! Each visit's complaint + diagnosis text is matched against syndrome rules
+//! (e.g. fever + rash → suspected measles) and counted per day, facility and
+//! county. Only aggregate counts leave the facility — no patient identifiers.
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{BridgeError, Context, Result};
+use crate::kenyan::schema::KenyanPatient;
+use crate::terminology::complaint::mentions;
+#[cfg(feature = "network")]
+use crate::{error::bail, http};
+
+/// A syndrome definition. Every group in `all_of` must match; a group
+/// matches when the visit text [`mentions`] any of its phrases.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SyndromeRule {
+    pub code: String,
+    pub name: String,
+    pub all_of: Vec<Vec<String>>,
+}
+
+/// The configured syndrome list — built-in IDSR-style defaults, or a county
+/// list loaded with `--rules <file>` (JSON array of `SyndromeRule`).
+#[derive(Debug, Clone)]
+pub struct SyndromeRules {
+    rules: Vec<SyndromeRule>,
+}
+
+// (code, name, all_of groups)
+const DEFAULT_RULES: &[(&str, &str, &[&[&str]])] = &[
+    (
+        "FEVER-RASH",
+        "Fever with rash (suspected measles)",
+        &[&["fever", "feverish"], &["rash"]],
+    ),
+    (
+        "AWD",
+        "Acute watery diarrhoea",
+        &[&[
+            "watery diarrhoea",
+            "watery diarrhea",
+            "watery stool",
+            "rice water",
+            "cholera",
+        ]],
+    ),
+    (
+        "BLOODY-DIARRHOEA",
+        "Bloody diarrhoea",
+        &[&[
+            "bloody diarrhoea",
+            "bloody diarrhea",
+            "blood in stool",
+            "dysentery",
+        ]],
+    ),
+    (
+        "ILI",
+        "Influenza-like illness",
+        &[&["fever", "feverish"], &["cough"]],
+    ),
+];
+
+impl Default for SyndromeRules {
+    fn default() -> Self {
+        let rules = DEFAULT_RULES
+            .iter()
+            .map(|(code, name, groups)| SyndromeRule {
+                code: code.to_string(),
+                name: name.to_string(),
+                all_of: groups
+                    .iter()
+                    .map(|g| g.iter().map(|t| t.to_string()).collect())
+                    .collect(),
+            })
+            .collect();
+        Self { rules }
+    }
+}
+
+impl SyndromeRules {
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path).with_context(BridgeError::Io, || {
+            format!("Failed to read syndrome rules {:?}", path)
+        })?;
+        let rules: Vec<SyndromeRule> = serde_json::from_str(&raw)
+            .context(BridgeError::Config, "Invalid syndrome rules JSON")?;
+        Ok(Self { rules })
+    }
+
+    /// Rules matched by a visit's free text.
+    pub fn matching(&self, text: &str) -> Vec<&SyndromeRule> {
+        self.rules
+            .iter()
+            .filter(|r| {
+                !r.all_of.is_empty()
+                    && r.all_of
+                        .iter()
+                        .all(|group| group.iter().any(|t| mentions(text, t)))
+            })
+            .collect()
+    }
+}
+
+/// Daily count of one syndrome at one facility.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SignalCount {
+    pub date: String,
+    pub clinic_id: String,
+    pub county: String,
+    pub syndrome_code: String,
+    pub syndrome_name: String,
+    pub count: u32,
+}
+
+/// Feed payload posted to the surveillance endpoint.
+#[derive(Debug, Serialize)]
+pub struct SurveillanceFeed {
+    pub generated_at: String,
+    pub signals: Vec<SignalCount>,
+}
+
+/// Count syndrome matches per (date, facility, syndrome).
+///
+/// `date` restricts the feed to a single day (YYYY-MM-DD).
+pub fn aggregate(
+    records: &[KenyanPatient],
+    rules: &SyndromeRules,
+    date: Option<&str>,
+) -> Vec<SignalCount> {
+    let mut counts: BTreeMap<(String, String, String), (String, String, u32)> = BTreeMap::new();
+
+    for record in records {
+        for visit in &record.visits {
+            if date.is_some_and(|d| d != visit.date) {
+                continue;
+            }
+            // A clause apart, so a negation in the complaint stops there
+            let text = format!("{}. {}", visit.complaint, visit.diagnosis);
+            for rule in rules.matching(&text) {
+                let key = (
+                    visit.date.clone(),
+                    record.clinic_id.clone(),
+                    rule.code.clone(),
+                );
+                let entry = counts
+                    .entry(key)
+                    .or_insert_with(|| (record.location.county.clone(), rule.name.clone(), 0));
+                entry.2 += 1;
+            }
+        }
+    }
+
+    counts
+        .into_iter()
+        .map(
+            |((date, clinic_id, syndrome_code), (county, syndrome_name, count))| SignalCount {
+                date,
+                clinic_id,
+                county,
+                syndrome_code,
+                syndrome_name,
+                count,
+            },
+        )
+        .collect()
+}
+
+/// CSV rendering of the feed (one row per signal count).
+pub fn to_csv(signals: &[SignalCount]) -> String {
+    let mut out = String::from("date,clinic_id,county,syndrome_code,syndrome_name,count\n");
+    for s in signals {
+        out.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            csv_field(&s.date),
+            csv_field(&s.clinic_id),
+            csv_field(&s.county),
+            csv_field(&s.syndrome_code),
+            csv_field(&s.syndrome_name),
+            s.count
+        ));
+    }
+    out
+}
+
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// POST the feed to the configured surveillance endpoint.
+#[cfg(feature = "network")]
+pub fn post_feed(endpoint: &str, content_type: &str, payload: &str) -> Result<()> {
+    let response = http::post(endpoint, content_type, payload.as_bytes(), 30)
+        .context(BridgeError::Network, "Surveillance feed submission failed")?;
+    if !response.is_success() {
+        bail!(
+            Network,
+            "Surveillance endpoint returned HTTP {}",
+            response.status
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fever_with_rash_needs_both_terms() {
+        let rules = SyndromeRules::default();
+        let codes =
+            |t: &str| -> Vec<String> { rules.matching(t).iter().map(|r| r.code.clone()).collect() };
+        assert_eq!(codes("High fever and generalised rash"), ["FEVER-RASH"]);
+        assert!(codes("Itchy rash on arms").is_empty());
+        assert_eq!(codes("Fever and cough"), ["ILI"]);
+        assert!(codes("Fever, no rash or cough").is_empty());
+        assert!(codes("Fever and thrashing").is_empty());
+    }
+
+    #[test]
+    fn csv_quotes_fields_with_commas() {
+        assert_eq!(csv_field("Fever, rash"), "\"Fever, rash\"");
+        assert_eq!(csv_field("AWD"), "AWD");
+    }
+}
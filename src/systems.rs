@@ -0,0 +1,254 @@
+//! Kenyan identifier and code system URIs, in one place.
+//!
+//! The mappers emit these built-in URIs. The `digitalhealth.go.ke` ones are
+//! placeholders until the Kenya IG publishes canonical URLs. A deployment
+//! whose registries use other URIs (UAT against production hosts, a future
+//! move like KMHFL to the Facility Registry) loads a [`SystemUris`] map
+//! instead of editing the mappers; it rewrites the bundle after mapping,
+//! like the FHIR-version and IG-profile passes.
+use std::path::Path;
+
+use fhir_parser::fhir::bundle::Bundle;
+use serde_json::{Map, Value};
+
+use crate::error::{bail, BridgeError, Context, Result};
+
+pub use fhir_parser::fhir::claim::{
+    SHA_COVERAGE_TYPE_SYSTEM, SHA_INTERVENTION_SYSTEM, SHA_MEMBER_SYSTEM, SHA_PAYER_SYSTEM,
+};
+
+// ── Registries ───────────────────────────────────────────────────────────────
+
+/// Client Registry ID (Maisha Namba / UPI)
+pub const CR_SYSTEM: &str = "http://cr.dha.go.ke/fhir/Patient";
+pub const NATIONAL_ID_SYSTEM: &str = "https://digitalhealth.go.ke/identifier/national-id";
+/// Facility Registry code; clinic-scoped systems hang below it (see
+/// [`patient_number_system`], [`visit_id_system`])
+pub const FACILITY_SYSTEM: &str = "http://facility-registry.dha.go.ke/fhir/Location";
+/// Health Worker Registry PUID
+pub const HWR_SYSTEM: &str = "http://hwr.dha.go.ke/fhir/Practitioner";
+/// Sub-county identifiers, `{county code}/{sub-county}`
+pub const SUBCOUNTY_SYSTEM: &str = "https://digitalhealth.go.ke/identifier/sub-county";
+/// Research pseudonym of a de-identified patient
+pub const PSEUDONYM_SYSTEM: &str = "https://digitalhealth.go.ke/identifier/research-pseudonym";
+
+/// System for the clinic's own patient numbers.
+pub fn patient_number_system(clinic_id: &str) -> String {
+    format!("{}/{}/patient-number", FACILITY_SYSTEM, clinic_id)
+}
+
+/// System for the clinic's visit numbers, alongside its patient numbers.
+pub fn visit_id_system(clinic_id: &str) -> String {
+    format!("{}/{}/visit-id", FACILITY_SYSTEM, clinic_id)
+}
+
+/// System for the clinic's asset numbers of its instruments.
+pub fn device_id_system(clinic_id: &str) -> String {
+    format!("{}/{}/device", FACILITY_SYSTEM, clinic_id)
+}
+
+// ── Code systems ─────────────────────────────────────────────────────────────
+
+/// KNBS county codes
+pub const COUNTY_CODE_SYSTEM: &str = "https://www.knbs.or.ke/fhir/CodeSystem/county";
+/// Biometric modality codes
+pub const BIOMETRIC_TYPE_SYSTEM: &str =
+    "https://digitalhealth.go.ke/fhir/CodeSystem/biometric-type";
+/// meta.tag flagging a data-quality problem for review
+pub const DATA_QUALITY_SYSTEM: &str = "https://digitalhealth.go.ke/fhir/CodeSystem/data-quality";
+/// Service delivery point codes
+pub const DEPARTMENT_SYSTEM: &str =
+    "https://digitalhealth.go.ke/fhir/CodeSystem/service-delivery-point";
+/// Derived nutrition observations that have no LOINC code
+pub const NUTRITION_SYSTEM: &str = "https://digitalhealth.go.ke/fhir/CodeSystem/nutrition";
+/// KTAS triage category codes
+pub const KTAS_SYSTEM: &str = "https://digitalhealth.go.ke/fhir/CodeSystem/ktas";
+/// WHO HIV clinical stage codes
+pub const HIV_WHO_STAGE_SYSTEM: &str = "https://digitalhealth.go.ke/fhir/CodeSystem/hiv-who-stage";
+/// TB treatment phase codes
+pub const TB_PHASE_SYSTEM: &str = "https://digitalhealth.go.ke/fhir/CodeSystem/tb-phase";
+/// Care programme codes
+pub const CARE_PROGRAMME_SYSTEM: &str =
+    "https://digitalhealth.go.ke/fhir/CodeSystem/care-programme";
+/// Vitals instrument codes
+pub const DEVICE_TYPE_SYSTEM: &str = "https://digitalhealth.go.ke/fhir/CodeSystem/device-type";
+/// Pass/fail screening results
+pub const SCREENING_RESULT_SYSTEM: &str =
+    "https://digitalhealth.go.ke/fhir/CodeSystem/screening-result";
+/// Triage danger-sign codes
+pub const DANGER_SIGN_SYSTEM: &str = "https://digitalhealth.go.ke/fhir/CodeSystem/danger-sign";
+/// KEPI vaccine codes
+pub const KEPI_VACCINE_SYSTEM: &str = "https://digitalhealth.go.ke/fhir/CodeSystem/kepi-vaccine";
+/// Health-worker cadre codes
+pub const CADRE_SYSTEM: &str = "https://digitalhealth.go.ke/fhir/CodeSystem/health-worker-cadre";
+
+// ── Extensions and canonical bases ───────────────────────────────────────────
+
+/// Flags a birthDate estimated from the stated age; FHIR R4 has no core
+/// extension for this
+pub const BIRTH_DATE_ESTIMATED_URL: &str =
+    "https://digitalhealth.go.ke/fhir/StructureDefinition/birthdate-estimated";
+/// Address extension carrying the KNBS county code
+pub const COUNTY_EXTENSION_URL: &str =
+    "https://digitalhealth.go.ke/fhir/StructureDefinition/county";
+/// Kenya IG profiles (see [`crate::ig_profile`])
+pub const KENYA_IG_BASE: &str = "https://digitalhealth.go.ke/fhir/StructureDefinition";
+/// DHA Measure definitions
+pub const MEASURE_BASE: &str = "http://dha.go.ke/fhir/Measure";
+
+/// Every built-in URI a [`SystemUris`] map may replace.
+const BUILT_IN: &[&str] = &[
+    CR_SYSTEM,
+    NATIONAL_ID_SYSTEM,
+    FACILITY_SYSTEM,
+    HWR_SYSTEM,
+    SUBCOUNTY_SYSTEM,
+    PSEUDONYM_SYSTEM,
+    SHA_PAYER_SYSTEM,
+    SHA_MEMBER_SYSTEM,
+    SHA_COVERAGE_TYPE_SYSTEM,
+    SHA_INTERVENTION_SYSTEM,
+    COUNTY_CODE_SYSTEM,
+    BIOMETRIC_TYPE_SYSTEM,
+    DATA_QUALITY_SYSTEM,
+    DEPARTMENT_SYSTEM,
+    NUTRITION_SYSTEM,
+    KTAS_SYSTEM,
+    HIV_WHO_STAGE_SYSTEM,
+    TB_PHASE_SYSTEM,
+    CARE_PROGRAMME_SYSTEM,
+    DEVICE_TYPE_SYSTEM,
+    SCREENING_RESULT_SYSTEM,
+    DANGER_SIGN_SYSTEM,
+    KEPI_VACCINE_SYSTEM,
+    CADRE_SYSTEM,
+    BIRTH_DATE_ESTIMATED_URL,
+    COUNTY_EXTENSION_URL,
+    KENYA_IG_BASE,
+    MEASURE_BASE,
+];
+
+/// Deployment URIs replacing built-in ones.
+///
+/// Loaded from a JSON object mapping a built-in URI, or a base some of
+/// them share (e.g. `http://facility-registry.dha.go.ke/fhir/Location`
+/// covers the clinic-scoped systems below it), to the deployment's.
+#[derive(Debug, Clone, Default)]
+pub struct SystemUris {
+    /// (built-in, deployment), longest built-in first
+    replacements: Vec<(String, String)>,
+}
+
+impl SystemUris {
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path).with_context(BridgeError::Io, || {
+            format!("Failed to read system URI map {:?}", path)
+        })?;
+        let map: Map<String, Value> = serde_json::from_str(&raw)
+            .context(BridgeError::Config, "Invalid system URI map JSON")?;
+        let mut replacements = Vec::new();
+        for (built_in, deployment) in map {
+            if !BUILT_IN.iter().any(|uri| under(uri, &built_in)) {
+                bail!(Config, "{} is not a built-in system URI", built_in);
+            }
+            let Some(deployment) = deployment.as_str() else {
+                bail!(Config, "System URI for {} is not a string", built_in);
+            };
+            replacements.push((built_in, deployment.to_string()));
+        }
+        replacements.sort_by_key(|(built_in, _)| std::cmp::Reverse(built_in.len()));
+        Ok(Self { replacements })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replacements.is_empty()
+    }
+
+    /// Rewrite the built-in URIs the mappers emitted to the deployment's.
+    pub fn apply(&self, bundle: &mut Bundle) {
+        let pairs: Vec<(&str, &str)> = self
+            .replacements
+            .iter()
+            .map(|(b, d)| (b.as_str(), d.as_str()))
+            .collect();
+        rewrite_bundle(bundle, &pairs);
+    }
+
+    /// Undo [`apply`](Self::apply), for code that reads bundles back
+    /// (`bundle to-kenyan`, `reconcile`).
+    pub fn revert(&self, bundle: &mut Bundle) {
+        let mut pairs: Vec<(&str, &str)> = self
+            .replacements
+            .iter()
+            .map(|(b, d)| (d.as_str(), b.as_str()))
+            .collect();
+        pairs.sort_by_key(|(from, _)| std::cmp::Reverse(from.len()));
+        rewrite_bundle(bundle, &pairs);
+    }
+}
+
+/// Whether `uri` is `base` or lies below it.
+fn under(uri: &str, base: &str) -> bool {
+    uri.strip_prefix(base)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || base.ends_with('/'))
+}
+
+fn rewrite_bundle(bundle: &mut Bundle, pairs: &[(&str, &str)]) {
+    if pairs.is_empty() {
+        return;
+    }
+    for resource in bundle
+        .entry
+        .iter_mut()
+        .flatten()
+        .filter_map(|e| e.resource.as_mut())
+    {
+        rewrite(resource, pairs);
+    }
+}
+
+/// Rewrite `system` and `url` values, meta.profile entries and the system
+/// of conditional references (`Patient?identifier={system}|{value}`).
+fn rewrite(value: &mut Value, pairs: &[(&str, &str)]) {
+    match value {
+        Value::Object(object) => {
+            for (key, child) in object.iter_mut() {
+                match (key.as_str(), child) {
+                    ("system" | "url", Value::String(uri)) => swap(uri, pairs),
+                    ("profile", Value::Array(profiles)) => {
+                        for profile in profiles {
+                            if let Value::String(uri) = profile {
+                                swap(uri, pairs);
+                            }
+                        }
+                    }
+                    ("reference", Value::String(reference)) => {
+                        if let Some((head, query)) = reference.split_once("identifier=") {
+                            let mut query = query.to_string();
+                            swap(&mut query, pairs);
+                            *reference = format!("{}identifier={}", head, query);
+                        }
+                    }
+                    (_, child) => rewrite(child, pairs),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                rewrite(item, pairs);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn swap(uri: &mut String, pairs: &[(&str, &str)]) {
+    // A conditional reference's query is `{system}|{value}`
+    let system_end = uri.find('|').unwrap_or(uri.len());
+    for (from, to) in pairs {
+        if under(&uri[..system_end], from) {
+            *uri = format!("{}{}", to, &uri[from.len()..]);
+            return;
+        }
+    }
+}
@@ -0,0 +1,363 @@
+use std::path::Path;
+
+use fhir_parser::fhir::observation::{CodeableConcept, Coding};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{BridgeError, Context, Result};
+
+const SNOMED_SYSTEM: &str = "http://snomed.info/sct";
+const ICPC2_SYSTEM: &str = "http://hl7.org/fhir/sid/icpc-2";
+
+/// Words that negate the findings after them in the same clause.
+const NEGATIONS: &[&str] = &[
+    "no", "not", "nil", "denies", "denied", "without", "negative",
+];
+
+/// Whether `text` mentions `term` as whole words ("rash", not "thrash"),
+/// leaving out mentions negated earlier in the same clause: "no fever",
+/// "denies cough or chills". A clause ends at punctuation or "but".
+pub fn mentions(text: &str, term: &str) -> bool {
+    let term = words(term);
+    if term.is_empty() {
+        return false;
+    }
+    let text = text.to_lowercase();
+    text.split([',', ';', '.', ':', '\n']).any(|clause| {
+        let clause = words(clause);
+        let mut negated = false;
+        (0..clause.len()).any(|i| {
+            let found = !negated && clause[i..].starts_with(&term);
+            match clause[i].as_str() {
+                "but" => negated = false,
+                w if NEGATIONS.contains(&w) => negated = true,
+                _ => {}
+            }
+            found
+        })
+    })
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// A code in one of the presenting-complaint code systems.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConceptCode {
+    pub code: String,
+    pub display: String,
+}
+
+/// One presenting complaint: the free-text terms that select it plus its
+/// SNOMED CT finding and ICPC-2 rubric.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ComplaintConcept {
+    /// Canonical name, used as CodeableConcept.text
+    pub name: String,
+    /// Phrases matched as whole words against the complaint text
+    pub terms: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snomed: Option<ConceptCode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icpc2: Option<ConceptCode>,
+}
+
+/// Presenting-complaint mini-terminology.
+///
+/// The built-in list covers the common OPD complaints; facilities can swap
+/// in their own list (same JSON shape as `ComplaintConcept`) with
+/// `--complaint-codes <file>`.
+#[derive(Debug, Clone)]
+pub struct ComplaintTerminology {
+    concepts: Vec<ComplaintConcept>,
+}
+
+/// (name, terms, SNOMED CT code, SNOMED display, ICPC-2 code, ICPC-2 display)
+type ComplaintRow = (
+    &'static str,
+    &'static [&'static str],
+    &'static str,
+    &'static str,
+    &'static str,
+    &'static str,
+);
+
+const DEFAULT_COMPLAINTS: &[ComplaintRow] = &[
+    (
+        "Fever",
+        &["fever", "feverish", "hotness of body"],
+        "386661006",
+        "Fever",
+        "A03",
+        "Fever",
+    ),
+    (
+        "Chills",
+        &["chills", "rigors"],
+        "43724002",
+        "Chill",
+        "A02",
+        "Chills",
+    ),
+    (
+        "Cough",
+        &["cough", "coughing"],
+        "49727002",
+        "Cough",
+        "R05",
+        "Cough",
+    ),
+    (
+        "Shortness of breath",
+        &[
+            "shortness of breath",
+            "difficulty breathing",
+            "breathlessness",
+        ],
+        "267036007",
+        "Dyspnea",
+        "R02",
+        "Shortness of breath/dyspnoea",
+    ),
+    (
+        "Chest pain",
+        &["chest pain"],
+        "29857009",
+        "Chest pain",
+        "A11",
+        "Chest pain NOS",
+    ),
+    (
+        "Headache",
+        &["headache", "headaches"],
+        "25064002",
+        "Headache",
+        "N01",
+        "Headache",
+    ),
+    (
+        "Dizziness",
+        &["dizziness", "dizzy"],
+        "404640003",
+        "Dizziness",
+        "N17",
+        "Vertigo/dizziness",
+    ),
+    (
+        "Abdominal pain",
+        &["abdominal pain", "stomach ache", "tummy ache"],
+        "21522001",
+        "Abdominal pain",
+        "D01",
+        "Abdominal pain/cramps general",
+    ),
+    (
+        "Diarrhoea",
+        &["diarrhoea", "diarrhea", "loose stools"],
+        "62315008",
+        "Diarrhea",
+        "D11",
+        "Diarrhoea",
+    ),
+    (
+        "Vomiting",
+        &["vomiting"],
+        "422400008",
+        "Vomiting",
+        "D10",
+        "Vomiting",
+    ),
+    (
+        "Painful urination",
+        &[
+            "burning sensation during urination",
+            "painful urination",
+            "dysuria",
+        ],
+        "49650001",
+        "Dysuria",
+        "U01",
+        "Dysuria/painful urination",
+    ),
+    (
+        "Rash",
+        &["rash"],
+        "271807003",
+        "Eruption of skin",
+        "S07",
+        "Rash generalized",
+    ),
+    (
+        "Joint pain",
+        &["joint pain", "joint pains"],
+        "57676002",
+        "Pain of joint",
+        "L20",
+        "Joint symptom/complaint NOS",
+    ),
+    (
+        "Night sweats",
+        &["night sweats"],
+        "42984000",
+        "Night sweats",
+        "A09",
+        "Sweating problem",
+    ),
+    (
+        "Weight loss",
+        &["weight loss"],
+        "89362005",
+        "Weight loss",
+        "T08",
+        "Weight loss",
+    ),
+];
+
+impl Default for ComplaintTerminology {
+    fn default() -> Self {
+        let concepts = DEFAULT_COMPLAINTS
+            .iter()
+            .map(
+                |(name, terms, sct, sct_display, icpc, icpc_display)| ComplaintConcept {
+                    name: name.to_string(),
+                    terms: terms.iter().map(|t| t.to_string()).collect(),
+                    snomed: Some(ConceptCode {
+                        code: sct.to_string(),
+                        display: sct_display.to_string(),
+                    }),
+                    icpc2: Some(ConceptCode {
+                        code: icpc.to_string(),
+                        display: icpc_display.to_string(),
+                    }),
+                },
+            )
+            .collect();
+        Self { concepts }
+    }
+}
+
+impl ComplaintTerminology {
+    /// Load a facility-specific complaint list (JSON array of concepts).
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path).with_context(BridgeError::Io, || {
+            format!("Failed to read complaint list {:?}", path)
+        })?;
+        let concepts: Vec<ComplaintConcept> = serde_json::from_str(&raw)
+            .context(BridgeError::Config, "Invalid complaint list JSON")?;
+        Ok(Self { concepts })
+    }
+
+    /// All concepts the complaint [`mentions`], in list order.
+    pub fn matches(&self, complaint: &str) -> Vec<&ComplaintConcept> {
+        self.concepts
+            .iter()
+            .filter(|c| c.terms.iter().any(|t| mentions(complaint, t)))
+            .collect()
+    }
+
+    /// Encounter.reasonCode for a complaint.
+    ///
+    /// One CodeableConcept per recognised complaint (SNOMED CT + ICPC-2 coding,
+    /// canonical name as text). Unrecognised complaints stay free text — the
+    /// verbatim complaint is also kept in Condition.note.
+    pub fn reason_codes(&self, complaint: &str) -> Vec<CodeableConcept> {
+        let matched = self.matches(complaint);
+        if matched.is_empty() {
+            return vec![CodeableConcept {
+                coding: None,
+                text: Some(complaint.to_string()),
+            }];
+        }
+
+        matched
+            .into_iter()
+            .map(|c| {
+                let mut coding = Vec::new();
+                if let Some(sct) = &c.snomed {
+                    coding.push(Coding {
+                        system: Some(SNOMED_SYSTEM.to_string()),
+                        code: Some(sct.code.clone()),
+                        display: Some(sct.display.clone()),
+                    });
+                }
+                if let Some(icpc) = &c.icpc2 {
+                    coding.push(Coding {
+                        system: Some(ICPC2_SYSTEM.to_string()),
+                        code: Some(icpc.code.clone()),
+                        display: Some(icpc.display.clone()),
+                    });
+                }
+                CodeableConcept {
+                    coding: if coding.is_empty() {
+                        None
+                    } else {
+                        Some(coding)
+                    },
+                    text: Some(c.name.clone()),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_each_recognised_complaint() {
+        let terms = ComplaintTerminology::default();
+        let reasons = terms.reason_codes("Fever, chills and headache");
+        let names: Vec<_> = reasons.iter().map(|r| r.text.as_deref().unwrap()).collect();
+        assert_eq!(names, ["Fever", "Chills", "Headache"]);
+        assert!(reasons
+            .iter()
+            .all(|r| r.coding.as_ref().unwrap().len() == 2));
+    }
+
+    #[test]
+    fn unknown_complaint_stays_free_text() {
+        let terms = ComplaintTerminology::default();
+        let reasons = terms.reason_codes("Routine check-up, no complaints");
+        assert_eq!(reasons.len(), 1);
+        assert!(reasons[0].coding.is_none());
+        assert_eq!(
+            reasons[0].text.as_deref(),
+            Some("Routine check-up, no complaints")
+        );
+    }
+
+    #[test]
+    fn matches_whole_words_only() {
+        assert!(mentions("Fever and joint pains", "joint pains"));
+        assert!(mentions("FEVER since Monday", "fever"));
+        assert!(!mentions("Thrashing in sleep", "rash"));
+        assert!(!mentions("Feverish", "fever"));
+        assert!(!mentions("Chest pains", "chest pain"));
+    }
+
+    #[test]
+    fn skips_negated_findings() {
+        assert!(!mentions("No fever", "fever"));
+        assert!(!mentions("Denies cough or chills", "chills"));
+        assert!(!mentions("nil rash", "rash"));
+        assert!(!mentions("Headache without vomiting", "vomiting"));
+        // The negation ends with its clause
+        assert!(mentions("No fever, cough for 3 days", "cough"));
+        assert!(mentions("Headache but no fever; vomiting", "vomiting"));
+        assert!(mentions("No fever but rash", "rash"));
+        assert!(mentions("Fever, not eating", "fever"));
+
+        let terms = ComplaintTerminology::default();
+        let names: Vec<_> = terms
+            .matches("Cough, no fever or night sweats")
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, ["Cough"]);
+    }
+}
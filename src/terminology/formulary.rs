@@ -0,0 +1,266 @@
+use fhir_parser::fhir::observation::Coding;
+
+const ATC_SYSTEM: &str = "http://www.whocc.no/atc";
+
+/// Minimum similarity for a fuzzy match to be coded. Below this the
+/// treatment stays free text rather than risk a wrong medication code.
+pub const MIN_CONFIDENCE: f64 = 0.85;
+
+/// One medicine on the formulary.
+#[derive(Debug, Clone)]
+pub struct Medicine {
+    /// Generic name, used as Coding.display
+    pub name: String,
+    /// Lower-case names (generic, common brand, abbreviation) matched
+    /// against the treatment text
+    pub terms: Vec<String>,
+    pub atc_code: String,
+}
+
+/// Best formulary match for a treatment string.
+#[derive(Debug, Clone)]
+pub struct FormularyMatch<'a> {
+    pub medicine: &'a Medicine,
+    /// 0.0–1.0 string similarity of the best-matching term
+    pub confidence: f64,
+}
+
+/// Kenyan Essential Medicines List lookup.
+///
+/// The embedded table covers the KEML medicines most often seen in OPD
+/// treatment notes, with their WHO ATC codes. Treatment text is free-form
+/// ("Amoxycillin 500mg TDS for 7 days"), so terms are matched fuzzily
+/// against runs of words, tolerating the usual spelling variants.
+#[derive(Debug, Clone)]
+pub struct Formulary {
+    medicines: Vec<Medicine>,
+}
+
+/// (generic name, terms, ATC code)
+type MedicineRow = (&'static str, &'static [&'static str], &'static str);
+
+const KEML: &[MedicineRow] = &[
+    ("Amoxicillin", &["amoxicillin", "amoxil"], "J01CA04"),
+    (
+        "Amoxicillin and clavulanic acid",
+        &[
+            "amoxicillin clavulanic acid",
+            "amoxicillin clavulanate",
+            "co-amoxiclav",
+            "augmentin",
+        ],
+        "J01CR02",
+    ),
+    (
+        "Benzylpenicillin",
+        &["benzylpenicillin", "benzyl penicillin", "crystapen"],
+        "J01CE01",
+    ),
+    ("Ceftriaxone", &["ceftriaxone"], "J01DD04"),
+    ("Azithromycin", &["azithromycin"], "J01FA10"),
+    ("Doxycycline", &["doxycycline"], "J01AA02"),
+    ("Ciprofloxacin", &["ciprofloxacin", "cipro"], "J01MA02"),
+    (
+        "Sulfamethoxazole and trimethoprim",
+        &["cotrimoxazole", "co-trimoxazole", "septrin"],
+        "J01EE01",
+    ),
+    ("Nitrofurantoin", &["nitrofurantoin"], "J01XE01"),
+    ("Metronidazole", &["metronidazole", "flagyl"], "P01AB01"),
+    ("Fluconazole", &["fluconazole"], "J02AC01"),
+    ("Clotrimazole", &["clotrimazole"], "D01AC01"),
+    (
+        "Artemether and lumefantrine",
+        &["artemether lumefantrine", "coartem"],
+        "P01BF01",
+    ),
+    ("Artesunate", &["artesunate"], "P01BE03"),
+    ("Albendazole", &["albendazole"], "P02CA03"),
+    ("Mebendazole", &["mebendazole"], "P02CA01"),
+    (
+        "Rifampicin, pyrazinamide, ethambutol and isoniazid",
+        &["rhze"],
+        "J04AM06",
+    ),
+    ("Isoniazid", &["isoniazid"], "J04AC01"),
+    (
+        "Tenofovir disoproxil, lamivudine and dolutegravir",
+        &["tenofovir lamivudine dolutegravir", "tld"],
+        "J05AR27",
+    ),
+    ("Amlodipine", &["amlodipine"], "C08CA01"),
+    ("Nifedipine", &["nifedipine"], "C08CA05"),
+    (
+        "Hydrochlorothiazide",
+        &["hydrochlorothiazide", "hctz"],
+        "C03AA03",
+    ),
+    ("Enalapril", &["enalapril"], "C09AA02"),
+    ("Losartan", &["losartan"], "C09CA01"),
+    ("Metformin", &["metformin"], "A10BA02"),
+    ("Glibenclamide", &["glibenclamide"], "A10BB01"),
+    (
+        "Paracetamol",
+        &["paracetamol", "acetaminophen", "panadol"],
+        "N02BE01",
+    ),
+    ("Ibuprofen", &["ibuprofen", "brufen"], "M01AE01"),
+    ("Diclofenac", &["diclofenac"], "M01AB05"),
+    ("Salbutamol", &["salbutamol", "ventolin"], "R03AC02"),
+    ("Prednisolone", &["prednisolone"], "H02AB06"),
+    (
+        "Chlorphenamine",
+        &["chlorphenamine", "chlorpheniramine", "piriton"],
+        "R06AB04",
+    ),
+    ("Cetirizine", &["cetirizine"], "R06AE07"),
+    ("Omeprazole", &["omeprazole"], "A02BC01"),
+    (
+        "Oral rehydration salts",
+        &["oral rehydration salts", "ors"],
+        "A07CA",
+    ),
+    (
+        "Zinc sulfate",
+        &["zinc sulfate", "zinc sulphate", "zinc"],
+        "A12CB01",
+    ),
+    (
+        "Ferrous sulfate",
+        &["ferrous sulfate", "ferrous sulphate"],
+        "B03AA07",
+    ),
+    ("Folic acid", &["folic acid"], "B03BB01"),
+];
+
+impl Default for Formulary {
+    fn default() -> Self {
+        let medicines = KEML
+            .iter()
+            .map(|(name, terms, atc)| Medicine {
+                name: name.to_string(),
+                terms: terms.iter().map(|t| t.to_string()).collect(),
+                atc_code: atc.to_string(),
+            })
+            .collect();
+        Self { medicines }
+    }
+}
+
+/// Lower-case alphabetic words; doses, slashes and hyphens are separators.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            cur[j + 1] = substitution.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+fn similarity(a: &str, b: &str) -> f64 {
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 0.0;
+    }
+    1.0 - levenshtein(a, b) as f64 / longest as f64
+}
+
+impl Formulary {
+    /// Best-matching medicine for a treatment string, if any term reaches
+    /// [`MIN_CONFIDENCE`]. Each term is compared against every run of the
+    /// same number of words; on a tie the longer term wins, so
+    /// "amoxicillin clavulanate" beats plain "amoxicillin".
+    pub fn lookup(&self, treatment: &str) -> Option<FormularyMatch<'_>> {
+        let text = words(treatment);
+        let mut best: Option<(FormularyMatch, usize)> = None;
+
+        for medicine in &self.medicines {
+            for term in &medicine.terms {
+                let term_words = words(term);
+                if term_words.is_empty() || term_words.len() > text.len() {
+                    continue;
+                }
+                let term = term_words.join(" ");
+                for window in text.windows(term_words.len()) {
+                    let confidence = similarity(&window.join(" "), &term);
+                    let better = match &best {
+                        None => true,
+                        Some((m, len)) => {
+                            confidence > m.confidence
+                                || (confidence == m.confidence && term.len() > *len)
+                        }
+                    };
+                    if confidence >= MIN_CONFIDENCE && better {
+                        best = Some((
+                            FormularyMatch {
+                                medicine,
+                                confidence,
+                            },
+                            term.len(),
+                        ));
+                    }
+                }
+            }
+        }
+        best.map(|(m, _)| m)
+    }
+
+    /// ATC Coding for MedicationRequest.medicationCodeableConcept, or `None`
+    /// when the treatment does not confidently match the formulary.
+    pub fn coding(&self, treatment: &str) -> Option<Coding> {
+        self.lookup(treatment).map(|m| Coding {
+            system: Some(ATC_SYSTEM.to_string()),
+            code: Some(m.medicine.atc_code.clone()),
+            display: Some(m.medicine.name.clone()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_misspelt_generic_name() {
+        let formulary = Formulary::default();
+        let m = formulary
+            .lookup("Amoxycillin 500mg TDS for 7 days")
+            .unwrap();
+        assert_eq!(m.medicine.atc_code, "J01CA04");
+        assert!(m.confidence < 1.0);
+    }
+
+    #[test]
+    fn prefers_combination_product() {
+        let formulary = Formulary::default();
+        let m = formulary
+            .lookup("Amoxicillin-clavulanate 625mg BD for 5 days")
+            .unwrap();
+        assert_eq!(m.medicine.atc_code, "J01CR02");
+        let m = formulary
+            .lookup("Artemether-Lumefantrine 80/480mg BD for 3 days")
+            .unwrap();
+        assert_eq!(m.medicine.atc_code, "P01BF01");
+    }
+
+    #[test]
+    fn non_medication_treatment_stays_uncoded() {
+        let formulary = Formulary::default();
+        assert!(formulary
+            .coding("Refer to TB clinic for sputum GeneXpert and chest X-ray")
+            .is_none());
+        assert!(formulary.coding("None required").is_none());
+    }
+}
@@ -0,0 +1,306 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Deserialize;
+
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::http::{self, url_encode, HttpRequest};
+pub use crate::terminology::Icd11Match;
+
+const DEFAULT_TOKEN_URL: &str = "https://icdaccessmanagement.who.int/connect/token";
+const DEFAULT_API_BASE: &str = "https://id.who.int";
+const DEFAULT_RELEASE: &str = "2024-01";
+
+/// Local cache of autocode results, including misses, so each distinct
+/// diagnosis string costs at most one API call and works offline afterwards.
+pub struct Icd11Cache {
+    conn: Connection,
+}
+
+impl Icd11Cache {
+    pub fn open(db_path: &Path) -> Result<Self> {
+        let conn = Connection::open(db_path).with_context(BridgeError::Storage, || {
+            format!("Failed to open terminology cache at {:?}", db_path)
+        })?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS icd11_autocode (
+                search_text TEXT NOT NULL,
+                release     TEXT NOT NULL,
+                code        TEXT,
+                display     TEXT,
+                fetched_at  TEXT NOT NULL,
+                PRIMARY KEY (search_text, release)
+            );",
+        )
+        .context(
+            BridgeError::Storage,
+            "Failed to initialise terminology cache schema",
+        )?;
+        Ok(Self { conn })
+    }
+
+    /// `None` = never looked up; `Some(None)` = looked up, no code.
+    pub fn get(&self, text: &str, release: &str) -> Result<Option<Option<Icd11Match>>> {
+        let row = self
+            .conn
+            .query_row(
+                "SELECT code, display FROM icd11_autocode
+                 WHERE search_text = ?1 AND release = ?2",
+                params![cache_key(text), release],
+                |r| {
+                    Ok((
+                        r.get::<_, Option<String>>(0)?,
+                        r.get::<_, Option<String>>(1)?,
+                    ))
+                },
+            )
+            .optional()?;
+        Ok(row.map(|(code, display)| {
+            code.map(|code| Icd11Match {
+                code,
+                display: display.unwrap_or_default(),
+            })
+        }))
+    }
+
+    pub fn put(&self, text: &str, release: &str, found: Option<&Icd11Match>) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO icd11_autocode
+                (search_text, release, code, display, fetched_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                cache_key(text),
+                release,
+                found.map(|m| &m.code),
+                found.map(|m| &m.display),
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+fn cache_key(text: &str) -> String {
+    text.trim().to_lowercase()
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+/// Response of `GET .../mms/autocode?searchText=`
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AutocodeResponse {
+    the_code: Option<String>,
+    matching_text: Option<String>,
+}
+
+/// WHO ICD-11 API client used to auto-code diagnoses the built-in crosswalk
+/// does not know.
+///
+/// Authenticates with OAuth2 client credentials (`ICD11_CLIENT_ID` /
+/// `ICD11_CLIENT_SECRET`, registered at icd.who.int/icdapi) and calls the
+/// MMS `autocode` endpoint. Every answer — including "no match" — is cached
+/// in SQLite, and any failure (offline, timeout, bad credentials) just means
+/// no code: the Condition stays provisional free text as before.
+#[derive(Clone)]
+pub struct Icd11Client {
+    pub client_id: String,
+    pub client_secret: String,
+    pub token_url: String,
+    /// `https://id.who.int`, or a local ICD-API container
+    pub api_base: String,
+    /// MMS release, e.g. `2024-01`
+    pub release: String,
+    pub timeout_secs: u32,
+    pub cache_path: PathBuf,
+    token: Arc<Mutex<Option<(String, Instant)>>>,
+}
+
+// Hand-written so the secret and bearer token never end up in debug output.
+impl std::fmt::Debug for Icd11Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Icd11Client")
+            .field("client_id", &self.client_id)
+            .field("api_base", &self.api_base)
+            .field("release", &self.release)
+            .field("cache_path", &self.cache_path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Icd11Client {
+    /// Client from `ICD11_CLIENT_ID` / `ICD11_CLIENT_SECRET`, with optional
+    /// `ICD11_TOKEN_URL`, `ICD11_API_BASE` and `ICD11_RELEASE` overrides.
+    pub fn from_env(cache_path: &Path) -> Result<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let (Some(client_id), Some(client_secret)) =
+            (var("ICD11_CLIENT_ID"), var("ICD11_CLIENT_SECRET"))
+        else {
+            bail!(
+                Config,
+                "ICD-11 autocoding needs ICD11_CLIENT_ID and ICD11_CLIENT_SECRET"
+            );
+        };
+        // Create the cache up front so a bad path fails the run, not each lookup
+        Icd11Cache::open(cache_path)?;
+        Ok(Self {
+            client_id,
+            client_secret,
+            token_url: var("ICD11_TOKEN_URL").unwrap_or_else(|| DEFAULT_TOKEN_URL.to_string()),
+            api_base: var("ICD11_API_BASE").unwrap_or_else(|| DEFAULT_API_BASE.to_string()),
+            release: var("ICD11_RELEASE").unwrap_or_else(|| DEFAULT_RELEASE.to_string()),
+            timeout_secs: 10,
+            cache_path: cache_path.to_path_buf(),
+            token: Arc::default(),
+        })
+    }
+
+    /// ICD-11 code for a free-text diagnosis: from the cache, else the API.
+    /// Errors are reported and treated as "no code".
+    pub fn autocode(&self, diagnosis: &str) -> Option<Icd11Match> {
+        match self.try_autocode(diagnosis) {
+            Ok(found) => found,
+            Err(e) => {
+                eprintln!(
+                    "[ICD11] autocode unavailable, leaving diagnosis uncoded: {:#}",
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    fn try_autocode(&self, diagnosis: &str) -> Result<Option<Icd11Match>> {
+        let cache = Icd11Cache::open(&self.cache_path)?;
+        if let Some(cached) = cache.get(diagnosis, &self.release)? {
+            return Ok(cached);
+        }
+
+        let url = format!(
+            "{}/icd/release/11/{}/mms/autocode?searchText={}",
+            self.api_base.trim_end_matches('/'),
+            self.release,
+            url_encode(diagnosis.trim())
+        );
+        let response = http::send(&HttpRequest {
+            method: "GET",
+            url: &url,
+            headers: vec![
+                format!("Authorization: Bearer {}", self.access_token()?),
+                "Accept: application/json".to_string(),
+                "Accept-Language: en".to_string(),
+                "API-Version: v2".to_string(),
+            ],
+            body: None,
+            timeout_secs: self.timeout_secs,
+            tls: None,
+        })?;
+        if !response.is_success() {
+            bail!(
+                Terminology,
+                "ICD-11 autocode returned HTTP {}",
+                response.status
+            );
+        }
+        let found = parse_autocode(&response.body)?;
+        cache.put(diagnosis, &self.release, found.as_ref())?;
+        Ok(found)
+    }
+
+    /// Bearer token, fetched once and reused until shortly before expiry.
+    fn access_token(&self) -> Result<String> {
+        let mut token = self.token.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((value, expires)) = token.as_ref() {
+            if Instant::now() < *expires {
+                return Ok(value.clone());
+            }
+        }
+
+        let body = format!(
+            "grant_type=client_credentials&scope=icdapi_access&client_id={}&client_secret={}",
+            url_encode(&self.client_id),
+            url_encode(&self.client_secret)
+        );
+        let response = http::send(&HttpRequest {
+            method: "POST",
+            url: &self.token_url,
+            headers: vec!["Content-Type: application/x-www-form-urlencoded".to_string()],
+            body: Some(body.as_bytes()),
+            timeout_secs: self.timeout_secs,
+            tls: None,
+        })?;
+        if !response.is_success() {
+            bail!(
+                Terminology,
+                "ICD-11 token request returned HTTP {}",
+                response.status
+            );
+        }
+        let parsed: TokenResponse = serde_json::from_str(&response.body)
+            .context(BridgeError::Terminology, "Invalid ICD-11 token response")?;
+        let expires = Instant::now() + Duration::from_secs(parsed.expires_in.saturating_sub(60));
+        *token = Some((parsed.access_token.clone(), expires));
+        Ok(parsed.access_token)
+    }
+}
+
+fn parse_autocode(body: &str) -> Result<Option<Icd11Match>> {
+    let parsed: AutocodeResponse = serde_json::from_str(body)
+        .context(BridgeError::Terminology, "Invalid ICD-11 autocode response")?;
+    Ok(parsed
+        .the_code
+        .filter(|c| !c.is_empty())
+        .map(|code| Icd11Match {
+            display: parsed.matching_text.unwrap_or_else(|| code.clone()),
+            code,
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_autocode_hit_and_miss() {
+        let hit = parse_autocode(
+            r#"{"searchText": "peptic ulcer", "theCode": "DA61",
+                "matchingText": "Peptic ulcer, site unspecified", "matchScore": 1}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            hit,
+            Some(Icd11Match {
+                code: "DA61".to_string(),
+                display: "Peptic ulcer, site unspecified".to_string(),
+            })
+        );
+        let miss = parse_autocode(r#"{"searchText": "review", "matchScore": 0}"#).unwrap();
+        assert_eq!(miss, None);
+    }
+
+    #[test]
+    fn cache_remembers_hits_and_misses() {
+        let f = tempfile::NamedTempFile::new().unwrap();
+        let cache = Icd11Cache::open(f.path()).unwrap();
+        assert_eq!(cache.get("Peptic ulcer", "2024-01").unwrap(), None);
+
+        let found = Icd11Match {
+            code: "DA61".to_string(),
+            display: "Peptic ulcer, site unspecified".to_string(),
+        };
+        cache.put("Peptic ulcer", "2024-01", Some(&found)).unwrap();
+        cache.put("Review", "2024-01", None).unwrap();
+        assert_eq!(
+            cache.get(" peptic ULCER ", "2024-01").unwrap(),
+            Some(Some(found))
+        );
+        assert_eq!(cache.get("review", "2024-01").unwrap(), Some(None));
+    }
+}
@@ -0,0 +1,13 @@
+pub mod complaint;
+pub mod formulary;
+#[cfg(all(feature = "sqlite", feature = "network"))]
+pub mod icd11;
+#[cfg(feature = "network")]
+pub mod translate;
+
+/// An ICD-11 MMS code found for a free-text diagnosis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Icd11Match {
+    pub code: String,
+    pub display: String,
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use fhir_parser::fhir::claim::ClaimDiagnosis;
+use fhir_parser::fhir::observation::{CodeableConcept, Coding};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::fhir_bundle::VisitResources;
+use crate::http::{self, url_encode, HttpRequest};
+
+const ICD11_SYSTEM: &str = "http://id.who.int/icd11/mms";
+
+/// Equivalences trusted enough to code with. `narrower`/`specializes`
+/// targets would claim more than the source says, `inexact`/`relatedto`
+/// are not codes for the same thing.
+const ACCEPTED_EQUIVALENCE: &[&str] = &["equal", "equivalent", "wider", "subsumes"];
+
+/// One ConceptMap to `$translate` against.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConceptMapRef {
+    /// ConceptMap canonical URL; omit to let the server choose by systems
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Source code system. The code sent is the concept's coding in this
+    /// system, or its text for local free-text term lists.
+    pub source_system: String,
+    pub target_system: String,
+    /// Drop existing codings in `target_system` instead of adding to them
+    #[serde(default)]
+    pub replace: bool,
+}
+
+/// `--terminology-config` file: server plus one optional map per domain.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TranslateConfig {
+    /// FHIR terminology server base URL (e.g. an OCL FHIR endpoint)
+    pub server: String,
+    #[serde(default)]
+    pub diagnosis: Option<ConceptMapRef>,
+    #[serde(default)]
+    pub medication: Option<ConceptMapRef>,
+    /// SHA intervention codes on Claim.item.productOrService
+    #[serde(default)]
+    pub intervention: Option<ConceptMapRef>,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u32,
+}
+
+fn default_timeout() -> u32 {
+    10
+}
+
+type MemoKey = (String, String, String);
+
+/// `ConceptMap/$translate` client applied on top of the built-in crosswalks.
+///
+/// Diagnosis (Condition.code), medication (medicationCodeableConcept) and
+/// SHA intervention (Claim.item.productOrService) codes are translated with
+/// the configured ConceptMaps, e.g. the Kenyan maps hosted on OCL. Matches
+/// are added to the existing codings, or replace those in the target system
+/// when the map says `replace`. A bearer token is taken from
+/// `TERMINOLOGY_TOKEN` if set. Server errors leave the crosswalk codes as
+/// they are. Answers are memoised per run.
+#[derive(Debug, Clone)]
+pub struct TerminologyService {
+    pub config: TranslateConfig,
+    memo: Arc<Mutex<HashMap<MemoKey, Vec<Coding>>>>,
+}
+
+impl TerminologyService {
+    pub fn new(config: TranslateConfig) -> Self {
+        Self {
+            config,
+            memo: Arc::default(),
+        }
+    }
+
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path).with_context(BridgeError::Io, || {
+            format!("Failed to read terminology config {:?}", path)
+        })?;
+        let config: TranslateConfig = serde_json::from_str(&raw)
+            .context(BridgeError::Config, "Invalid terminology config JSON")?;
+        Ok(Self::new(config))
+    }
+
+    /// `$translate` one code; returns the accepted target codings.
+    pub fn translate(&self, map: &ConceptMapRef, code: &str) -> Result<Vec<Coding>> {
+        let mut url = format!(
+            "{}/ConceptMap/$translate?system={}&code={}&targetsystem={}",
+            self.config.server.trim_end_matches('/'),
+            url_encode(&map.source_system),
+            url_encode(code),
+            url_encode(&map.target_system)
+        );
+        if let Some(map_url) = &map.url {
+            url.push_str(&format!("&url={}", url_encode(map_url)));
+        }
+        let mut headers = vec!["Accept: application/fhir+json".to_string()];
+        if let Ok(token) = std::env::var("TERMINOLOGY_TOKEN") {
+            headers.push(format!("Authorization: Bearer {}", token));
+        }
+        let response = http::send(&HttpRequest {
+            method: "GET",
+            url: &url,
+            headers,
+            body: None,
+            timeout_secs: self.config.timeout_secs,
+            tls: None,
+        })?;
+        if !response.is_success() {
+            bail!(Terminology, "$translate returned HTTP {}", response.status);
+        }
+        let parameters: Value = serde_json::from_str(&response.body)
+            .context(BridgeError::Terminology, "Invalid $translate response")?;
+        Ok(translate_matches(&parameters, &map.target_system))
+    }
+
+    fn translate_memoised(&self, map: &ConceptMapRef, code: &str) -> Vec<Coding> {
+        let key = (
+            map.source_system.clone(),
+            map.target_system.clone(),
+            code.to_string(),
+        );
+        if let Some(hit) = self
+            .memo
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+        {
+            return hit.clone();
+        }
+        let found = self.translate(map, code).unwrap_or_else(|e| {
+            eprintln!(
+                "[TERMINOLOGY] $translate failed, keeping built-in codes: {:#}",
+                e
+            );
+            Vec::new()
+        });
+        self.memo
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, found.clone());
+        found
+    }
+
+    /// Translate one concept in place; true when codings changed.
+    fn apply(&self, map: &ConceptMapRef, concept: &mut CodeableConcept) -> bool {
+        let source = concept
+            .coding
+            .iter()
+            .flatten()
+            .find(|c| c.system.as_deref() == Some(map.source_system.as_str()))
+            .and_then(|c| c.code.clone())
+            .or_else(|| concept.text.clone());
+        let Some(source) = source else {
+            return false;
+        };
+        let found = self.translate_memoised(map, &source);
+        merge_codings(concept, map, found)
+    }
+
+    /// Apply every configured map to one visit's resources.
+    pub fn apply_to_visit(&self, visit: &mut VisitResources) {
+        if let Some(map) = &self.config.diagnosis {
+            if let Some(code) = visit.condition.code.as_mut() {
+                let was_coded = code.coding.is_some();
+                if self.apply(map, code) && !was_coded {
+                    mark_confirmed(visit.condition.verification_status.as_mut());
+                }
+            }
+            sync_claim_diagnosis(visit);
+        }
+        if let Some(map) = &self.config.medication {
+            if let Some(med) = visit
+                .medication_request
+                .medication_codeable_concept
+                .as_mut()
+            {
+                self.apply(map, med);
+            }
+        }
+        if let Some(map) = &self.config.intervention {
+            if let Some(sha) = visit.sha_claims.as_mut() {
+                for item in sha.claim.item.iter_mut().flatten() {
+                    self.apply(map, &mut item.product_or_service);
+                }
+            }
+        }
+    }
+}
+
+/// Accepted target codings from a `$translate` Parameters response.
+fn translate_matches(parameters: &Value, target_system: &str) -> Vec<Coding> {
+    let params = parameters["parameter"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let matched = params
+        .iter()
+        .any(|p| p["name"] == "result" && p["valueBoolean"] == true);
+    if !matched {
+        return Vec::new();
+    }
+    params
+        .iter()
+        .filter(|p| p["name"] == "match")
+        .filter_map(|p| {
+            let parts = p["part"].as_array()?;
+            let part = |name: &str| parts.iter().find(|x| x["name"] == name);
+            let equivalence = part("equivalence")
+                .or_else(|| part("relationship"))
+                .and_then(|x| x["valueCode"].as_str())
+                .unwrap_or("equivalent");
+            if !ACCEPTED_EQUIVALENCE.contains(&equivalence) {
+                return None;
+            }
+            let concept = &part("concept")?["valueCoding"];
+            let system = concept["system"].as_str().unwrap_or(target_system);
+            (system == target_system).then(|| Coding {
+                system: Some(system.to_string()),
+                code: concept["code"].as_str().map(str::to_string),
+                display: concept["display"].as_str().map(str::to_string),
+            })
+        })
+        .filter(|c| c.code.is_some())
+        .collect()
+}
+
+/// Add (or, with `replace`, substitute) target-system codings; true when
+/// the concept changed.
+fn merge_codings(concept: &mut CodeableConcept, map: &ConceptMapRef, found: Vec<Coding>) -> bool {
+    if found.is_empty() {
+        return false;
+    }
+    let mut codings = concept.coding.take().unwrap_or_default();
+    let before = codings.len();
+    if map.replace {
+        codings.retain(|c| c.system.as_deref() != Some(map.target_system.as_str()));
+    }
+    let removed = before - codings.len();
+    let mut added = 0;
+    for coding in found {
+        let present = codings
+            .iter()
+            .any(|c| c.system == coding.system && c.code == coding.code);
+        if !present {
+            codings.push(coding);
+            added += 1;
+        }
+    }
+    concept.coding = Some(codings);
+    removed + added > 0
+}
+
+fn mark_confirmed(status: Option<&mut CodeableConcept>) {
+    for coding in status
+        .into_iter()
+        .flat_map(|s| s.coding.iter_mut().flatten())
+    {
+        if coding.code.as_deref() == Some("provisional") {
+            coding.code = Some("confirmed".to_string());
+            coding.display = Some("Confirmed".to_string());
+        }
+    }
+}
+
+/// The SHA Claim diagnosis is built from the crosswalk before translation;
+/// keep it in step with the Condition's (possibly new) ICD-11 code.
+fn sync_claim_diagnosis(visit: &mut VisitResources) {
+    let Some(sha) = visit.sha_claims.as_mut() else {
+        return;
+    };
+    let icd11 = visit
+        .condition
+        .code
+        .iter()
+        .flat_map(|c| c.coding.iter().flatten())
+        .find(|c| c.system.as_deref() == Some(ICD11_SYSTEM));
+    if let Some(coding) = icd11 {
+        sha.claim.diagnosis = Some(vec![ClaimDiagnosis {
+            sequence: 1,
+            diagnosis_codeable_concept: CodeableConcept {
+                coding: Some(vec![coding.clone()]),
+                text: coding.display.clone(),
+            },
+        }]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn icd10_to_icd11(replace: bool) -> ConceptMapRef {
+        ConceptMapRef {
+            url: None,
+            source_system: "http://hl7.org/fhir/sid/icd-10".to_string(),
+            target_system: ICD11_SYSTEM.to_string(),
+            replace,
+        }
+    }
+
+    #[test]
+    fn keeps_only_trusted_matches() {
+        let parameters = json!({"resourceType": "Parameters", "parameter": [
+            {"name": "result", "valueBoolean": true},
+            {"name": "match", "part": [
+                {"name": "equivalence", "valueCode": "equivalent"},
+                {"name": "concept", "valueCoding": {"system": ICD11_SYSTEM, "code": "1F40.Z"}}]},
+            {"name": "match", "part": [
+                {"name": "equivalence", "valueCode": "narrower"},
+                {"name": "concept", "valueCoding": {"system": ICD11_SYSTEM, "code": "1F40.0"}}]}
+        ]});
+        let found = translate_matches(&parameters, ICD11_SYSTEM);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].code.as_deref(), Some("1F40.Z"));
+
+        let unmatched = json!({"parameter": [{"name": "result", "valueBoolean": false}]});
+        assert!(translate_matches(&unmatched, ICD11_SYSTEM).is_empty());
+    }
+
+    #[test]
+    fn augments_or_replaces_target_codings() {
+        let concept = || CodeableConcept {
+            coding: Some(vec![
+                Coding {
+                    system: Some(ICD11_SYSTEM.to_string()),
+                    code: Some("1F4Z".to_string()),
+                    display: None,
+                },
+                Coding {
+                    system: Some("http://hl7.org/fhir/sid/icd-10".to_string()),
+                    code: Some("B54".to_string()),
+                    display: None,
+                },
+            ]),
+            text: Some("Malaria".to_string()),
+        };
+        let found = vec![Coding {
+            system: Some(ICD11_SYSTEM.to_string()),
+            code: Some("1F40.Z".to_string()),
+            display: None,
+        }];
+
+        let mut augmented = concept();
+        assert!(merge_codings(
+            &mut augmented,
+            &icd10_to_icd11(false),
+            found.clone()
+        ));
+        assert_eq!(augmented.coding.as_ref().unwrap().len(), 3);
+
+        let mut replaced = concept();
+        assert!(merge_codings(&mut replaced, &icd10_to_icd11(true), found));
+        let codes: Vec<_> = replaced
+            .coding
+            .unwrap()
+            .into_iter()
+            .filter_map(|c| c.code)
+            .collect();
+        assert_eq!(codes, ["B54", "1F40.Z"]);
+    }
+}
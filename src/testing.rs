@@ -0,0 +1,210 @@
+//! Golden-file snapshot helpers for bundles (`testing` feature).
+//!
+//! Two runs over the same record differ only in the Bundle id, its
+//! timestamp and, for signed bundles, the signature time and value.
+//! [`normalize`] replaces those with placeholders so a bundle can be compared
+//! with a checked-in golden JSON file; integrators use this to pin the
+//! output of their own complaint lists, terminology configs and mappings.
+//!
+//! Golden files are (re)written when `UPDATE_GOLDEN=1` is set, never
+//! silently on a missing file, so CI cannot pass against nothing.
+use std::path::Path;
+
+use fhir_parser::fhir::bundle::Bundle;
+use serde_json::Value;
+
+use crate::error::{bail, BridgeError, Context, Result};
+
+pub const BUNDLE_ID_PLACEHOLDER: &str = "<bundle-id>";
+pub const TIMESTAMP_PLACEHOLDER: &str = "<timestamp>";
+pub const SIGNATURE_PLACEHOLDER: &str = "<signature>";
+
+/// How many differences a failed comparison lists.
+const MAX_REPORTED: usize = 20;
+
+/// `bundle` with its run-specific values replaced by placeholders. Absent
+/// values stay absent. `meta.lastUpdated` is blanked wherever it occurs,
+/// for mappings that set it.
+pub fn normalize(bundle: &Value) -> Value {
+    let mut bundle = bundle.clone();
+    for (field, placeholder) in [
+        ("id", BUNDLE_ID_PLACEHOLDER),
+        ("timestamp", TIMESTAMP_PLACEHOLDER),
+    ] {
+        if bundle.get(field).is_some() {
+            bundle[field] = placeholder.into();
+        }
+    }
+    if let Some(signature) = bundle.get_mut("signature") {
+        if signature.get("when").is_some() {
+            signature["when"] = TIMESTAMP_PLACEHOLDER.into();
+        }
+        if signature.get("data").is_some() {
+            signature["data"] = SIGNATURE_PLACEHOLDER.into();
+        }
+    }
+    blank_last_updated(&mut bundle);
+    bundle
+}
+
+fn blank_last_updated(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            if let Some(Value::Object(meta)) = map.get_mut("meta") {
+                if meta.contains_key("lastUpdated") {
+                    meta.insert("lastUpdated".into(), TIMESTAMP_PLACEHOLDER.into());
+                }
+            }
+            map.values_mut().for_each(blank_last_updated);
+        }
+        Value::Array(items) => items.iter_mut().for_each(blank_last_updated),
+        _ => {}
+    }
+}
+
+/// JSON pointer paths where `actual` differs from `expected`, with both
+/// values, in document order.
+pub fn json_diff(expected: &Value, actual: &Value) -> Vec<String> {
+    let mut out = Vec::new();
+    diff_at("", expected, actual, &mut out);
+    out
+}
+
+fn diff_at(path: &str, expected: &Value, actual: &Value, out: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(e), Value::Object(a)) => {
+            for (key, ev) in e {
+                let child = format!("{}/{}", path, key);
+                match a.get(key) {
+                    Some(av) => diff_at(&child, ev, av, out),
+                    None => out.push(format!("{}: missing (expected {})", child, ev)),
+                }
+            }
+            for (key, av) in a.iter().filter(|(k, _)| !e.contains_key(*k)) {
+                out.push(format!("{}/{}: unexpected {}", path, key, av));
+            }
+        }
+        (Value::Array(e), Value::Array(a)) => {
+            for (i, (ev, av)) in e.iter().zip(a).enumerate() {
+                diff_at(&format!("{}/{}", path, i), ev, av, out);
+            }
+            if e.len() != a.len() {
+                out.push(format!(
+                    "{}: {} items, expected {}",
+                    if path.is_empty() { "/" } else { path },
+                    a.len(),
+                    e.len()
+                ));
+            }
+        }
+        _ if expected != actual => out.push(format!(
+            "{}: {} != expected {}",
+            if path.is_empty() { "/" } else { path },
+            actual,
+            expected
+        )),
+        _ => {}
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GoldenOutcome {
+    Matched,
+    /// `UPDATE_GOLDEN` was set and the file was (re)written
+    Updated,
+}
+
+/// Compare the normalized `bundle` with the golden file at `path`; errors
+/// list the differing paths.
+pub fn check_golden(bundle: &Bundle, path: &Path) -> Result<GoldenOutcome> {
+    let actual = normalize(&serde_json::to_value(bundle)?);
+    check_golden_value(&actual, path)
+}
+
+/// [`check_golden`] for an already normalized JSON value.
+pub fn check_golden_value(actual: &Value, path: &Path) -> Result<GoldenOutcome> {
+    if std::env::var("UPDATE_GOLDEN").is_ok_and(|v| v == "1") {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(BridgeError::Io, || format!("Failed to create {:?}", dir))?;
+        }
+        let mut json = serde_json::to_string_pretty(actual)?;
+        json.push('\n');
+        std::fs::write(path, json)
+            .with_context(BridgeError::Io, || format!("Failed to write {:?}", path))?;
+        return Ok(GoldenOutcome::Updated);
+    }
+    let raw = std::fs::read_to_string(path).with_context(BridgeError::Io, || {
+        format!(
+            "Failed to read golden file {:?} (set UPDATE_GOLDEN=1 to create it)",
+            path
+        )
+    })?;
+    let expected: Value =
+        serde_json::from_str(&raw).context(BridgeError::Io, "Invalid golden file JSON")?;
+    let diffs = json_diff(&expected, actual);
+    if !diffs.is_empty() {
+        let shown = diffs.len().min(MAX_REPORTED);
+        bail!(Validation, "Bundle differs from golden file {:?} in {} place(s):\n  {}{}\n(set UPDATE_GOLDEN=1 to accept)",
+            path,
+            diffs.len(),
+            diffs[..shown].join("\n  "),
+            if diffs.len() > shown { "\n  …" } else { "" }
+        );
+    }
+    Ok(GoldenOutcome::Matched)
+}
+
+/// [`check_golden`] that panics with the differences, for `#[test]`s.
+#[track_caller]
+pub fn assert_golden(bundle: &Bundle, path: impl AsRef<Path>) {
+    if let Err(e) = check_golden(bundle, path.as_ref()) {
+        panic!("{:#}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kenyan::schema::KenyanPatient;
+    use crate::transform::{transform, TransformOptions};
+    use serde_json::json;
+
+    #[test]
+    fn normalize_blanks_only_run_specific_values() {
+        let bundle = json!({
+            "resourceType": "Bundle",
+            "id": "7198c668",
+            "timestamp": "2026-10-16T12:06:43Z",
+            "entry": [{"resource": {"id": "enc-1", "meta": {"lastUpdated": "2026-10-16"}}}]
+        });
+        let normalized = normalize(&bundle);
+        assert_eq!(normalized["id"], BUNDLE_ID_PLACEHOLDER);
+        assert_eq!(normalized["timestamp"], TIMESTAMP_PLACEHOLDER);
+        assert_eq!(normalized["entry"][0]["resource"]["id"], "enc-1");
+        assert_eq!(
+            normalized["entry"][0]["resource"]["meta"]["lastUpdated"],
+            TIMESTAMP_PLACEHOLDER
+        );
+
+        let mut changed = normalized.clone();
+        changed["entry"][0]["resource"]["id"] = "enc-2".into();
+        changed["entry"][0]["resource"]["status"] = "finished".into();
+        assert_eq!(
+            json_diff(&normalized, &changed),
+            [
+                r#"/entry/0/resource/id: "enc-2" != expected "enc-1""#,
+                r#"/entry/0/resource/status: unexpected "finished""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn fixture_bundle_matches_golden_file() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+        let raw = std::fs::read_to_string(dir.join("fixtures/kenyan_patient_1.json")).unwrap();
+        let kenyan: KenyanPatient = serde_json::from_str(&raw).unwrap();
+        let bundle = transform(&kenyan, &TransformOptions::default()).unwrap();
+        assert_golden(&bundle, dir.join("golden/kenyan_patient_1.json"));
+    }
+}
@@ -0,0 +1,766 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use uuid::Uuid;
+
+use fhir_parser::fhir::bundle::Bundle;
+
+use crate::circuit_breaker::CircuitBreaker;
+use crate::deidentify::Deidentifier;
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::facility::FacilityDirectory;
+use crate::fhir_bundle::{
+    add_immunization_recommendation, add_operation_outcome, bundle_to_json,
+    create_transaction_bundle, JsonLayout, VisitResources,
+};
+use crate::fhir_version::FhirVersion;
+use crate::ig_profile::IgProfiles;
+use crate::immunization::forecast;
+use crate::kenyan::schema::{KenyanPatient, Visit};
+use crate::mapper::antenatal::map_antenatal;
+use crate::mapper::appointment::map_appointment;
+use crate::mapper::clinical_impression::map_clinical_impression;
+use crate::mapper::condition::{diagnosis_coding, map_condition};
+use crate::mapper::danger_sign::map_danger_signs;
+use crate::mapper::device::{link_devices, map_device};
+use crate::mapper::document_reference::{map_biometrics, map_visit_documents};
+use crate::mapper::encounter::map_encounter;
+use crate::mapper::episode_of_care::map_episode_of_care;
+use crate::mapper::imaging::map_imaging_orders;
+use crate::mapper::immunization::map_immunization_recommendation;
+use crate::mapper::lab_order::map_lab_orders;
+use crate::mapper::location::{location_id, map_location};
+use crate::mapper::medication_request::map_medication_request;
+use crate::mapper::nutrition::map_nutrition;
+use crate::mapper::observation::map_vitals;
+use crate::mapper::organization::{map_organization, map_parent_organizations};
+use crate::mapper::patient::map_patient;
+use crate::mapper::practitioner::{map_practitioner, map_practitioner_role};
+use crate::mapper::programme::{
+    condition_stage, map_care_plan, map_ncd_care_plan, ProgrammeProfile,
+};
+use crate::mapper::screening::map_screening;
+use crate::mapper::sha::{add_supporting_info, map_sha_claims};
+use crate::mapper::triage::map_triage;
+use crate::mapper::visit_key;
+#[cfg(all(feature = "sqlite", feature = "network"))]
+use crate::patient_match::PatientMatcher;
+use crate::systems::SystemUris;
+use crate::terminology::complaint::ComplaintTerminology;
+use crate::terminology::formulary::Formulary;
+#[cfg(all(feature = "sqlite", feature = "network"))]
+use crate::terminology::icd11::Icd11Client;
+#[cfg(feature = "network")]
+use crate::terminology::translate::TerminologyService;
+use crate::validation::{
+    validate_antenatal, validate_attachment, validate_imaging_order, validate_kenyan_patient,
+    validate_lab_order, validate_record, validate_screening, validate_stage, validate_triage,
+    validate_visit_date, validate_vitals,
+};
+#[cfg(feature = "sqlite")]
+use crate::visit_ledger::{visit_hash, VisitLedger};
+
+/// Mapping configuration shared by every record in a run.
+#[derive(Debug, Clone, Default)]
+pub struct TransformOptions {
+    /// Presenting-complaint list used for Encounter.reasonCode coding.
+    pub complaints: ComplaintTerminology,
+    /// KEML formulary used for MedicationRequest medication coding.
+    pub formulary: Formulary,
+    /// WHO ICD-11 API for diagnoses the crosswalk does not know (opt-in).
+    #[cfg(all(feature = "sqlite", feature = "network"))]
+    pub icd11: Option<Icd11Client>,
+    /// ConceptMap `$translate` on top of the built-in crosswalks (opt-in).
+    #[cfg(feature = "network")]
+    pub translate: Option<TerminologyService>,
+    /// Skips live CR lookups for a while after repeated failures.
+    pub cr_breaker: CircuitBreaker,
+    /// Duplicate check before a synthetic CR ID is kept (opt-in).
+    #[cfg(all(feature = "sqlite", feature = "network"))]
+    pub patient_match: Option<PatientMatcher>,
+    /// Telecom and address for the facility Organization (opt-in).
+    pub facilities: Option<FacilityDirectory>,
+    /// Bundle.id; random unless a stable one is asked for.
+    pub bundle_id: BundleIdSource,
+    /// Bundle.timestamp; the current time unless a stable one is asked for.
+    pub timestamp: TimestampSource,
+    /// Pseudonymize the bundle for research use (opt-in).
+    pub deidentify: Option<Deidentifier>,
+    /// Tag every resource with its Kenya IG profiles in meta.profile (opt-in).
+    pub ig_profiles: Option<IgProfiles>,
+    /// FHIR release of the bundle; the mappers build R4.
+    pub fhir_version: FhirVersion,
+    /// Deployment identifier and code system URIs replacing the built-in
+    /// ones; none by default.
+    pub system_uris: SystemUris,
+    /// Whether a bad visit field fails the record or only its resources.
+    pub on_error: FailurePolicy,
+    /// Check visits against a ledger of ones already transformed (opt-in);
+    /// [`transform_with_ledger`] hands back the visits to record.
+    #[cfg(feature = "sqlite")]
+    pub duplicate_visits: Option<DuplicateVisitCheck>,
+    /// National programme profile: its visits get an EpisodeOfCare,
+    /// Condition.stage and a regimen CarePlan (opt-in).
+    pub profile: Option<ProgrammeProfile>,
+    /// Map without side effects: synthetic CR IDs instead of the live
+    /// lookup, no patient matching, and the visit ledger is read but not
+    /// written.
+    pub dry_run: bool,
+}
+
+/// Visits seen in an earlier run, by [`visit_hash`].
+#[derive(Debug, Clone)]
+pub struct DuplicateVisitCheck {
+    /// [`VisitLedger`] database
+    pub db: PathBuf,
+    pub action: DuplicateVisitAction,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateVisitAction {
+    /// Report the repeat and map it anyway
+    #[default]
+    Warn,
+    /// Leave the repeated visit out; a record with nothing new fails
+    Skip,
+}
+
+/// What a visit field that cannot be mapped does to the bundle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// The whole record fails (the record is validated up front)
+    #[default]
+    Abort,
+    /// The affected resources are left out and an OperationOutcome entry
+    /// says which and why. Used for backfills, where one bad field should
+    /// not cost the rest of the record. A visit with a bad date loses all
+    /// its resources; bad vitals cost the vitals Observations; a bad
+    /// attachment its DocumentReference; bad screening findings their
+    /// Observations; a bad lab or imaging order its resources.
+    Skip,
+}
+
+impl FailurePolicy {
+    /// Under `Skip`, run `check`; a failure is noted in `skipped` as
+    /// `{what} skipped: {error}` and gives false. Under `Abort` the record
+    /// was validated up front, so this is always true.
+    fn keep(
+        self,
+        what: impl FnOnce() -> String,
+        check: impl FnOnce() -> Result<()>,
+        skipped: &mut Vec<String>,
+    ) -> bool {
+        if self == Self::Abort {
+            return true;
+        }
+        match check() {
+            Ok(()) => true,
+            Err(e) => {
+                skipped.push(format!("{} skipped: {:#}", what(), e));
+                false
+            }
+        }
+    }
+}
+
+/// Where Bundle.id comes from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BundleIdSource {
+    /// A new UUID v4 per run
+    #[default]
+    Random,
+    /// Given by the caller (a FHIR id: letters, digits, `-`, `.`; ≤ 64)
+    Fixed(String),
+    /// UUID v5 of the input record, so an unchanged record keeps its id.
+    /// Only the record is hashed — a changed mapping configuration gives
+    /// different content under the same id.
+    FromInput,
+}
+
+/// Where Bundle.timestamp comes from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TimestampSource {
+    #[default]
+    Now,
+    Fixed(DateTime<FixedOffset>),
+    /// Midnight UTC on the record's latest visit date
+    FromInput,
+}
+
+// Same private namespace as patient and synthetic CR ids.
+const BUNDLE_NAMESPACE: Uuid = uuid::uuid!("6ba7b810-9dad-11d1-80b4-00c04fd430c9");
+
+/// Check a caller-supplied Bundle.id against the FHIR id rules.
+pub fn fhir_id(id: &str) -> Result<String> {
+    let valid = (1..=64).contains(&id.len())
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+    if !valid {
+        bail!(
+            Validation,
+            "Bundle id must be 1–64 letters, digits, '-' or '.'"
+        );
+    }
+    Ok(id.to_string())
+}
+
+impl BundleIdSource {
+    fn resolve(&self, kenyan: &KenyanPatient) -> Result<String> {
+        Ok(match self {
+            Self::Random => Uuid::new_v4().to_string(),
+            Self::Fixed(id) => fhir_id(id)?,
+            Self::FromInput => {
+                // Struct field order is fixed, so the serialization is stable
+                let canonical = serde_json::to_vec(kenyan)?;
+                let mut name = b"bundle:".to_vec();
+                name.extend_from_slice(&canonical);
+                Uuid::new_v5(&BUNDLE_NAMESPACE, &name).to_string()
+            }
+        })
+    }
+}
+
+impl TimestampSource {
+    fn resolve(&self, kenyan: &KenyanPatient) -> Result<String> {
+        Ok(match self {
+            Self::Now => Utc::now().to_rfc3339(),
+            Self::Fixed(at) => at.to_rfc3339(),
+            Self::FromInput => {
+                // Validation has rejected bad dates unless they are being skipped
+                let date = kenyan
+                    .visits
+                    .iter()
+                    .filter_map(|v| NaiveDate::parse_from_str(&v.date, "%Y-%m-%d").ok())
+                    .max()
+                    .context(
+                        BridgeError::Validation,
+                        "At least one visit with a valid date is required",
+                    )?;
+                date.and_time(chrono::NaiveTime::MIN).and_utc().to_rfc3339()
+            }
+        })
+    }
+}
+
+/// Map a (validated) KenyanPatient record into a FHIR R4 transaction Bundle.
+///
+/// Patient, facility Organization and any biometric DocumentReferences are
+/// emitted once; every visit gets its own Encounter, vitals, Condition,
+/// MedicationRequest, a DocumentReference per attachment and — for SHA
+/// visits — Claim. A child with an immunization history also gets the KEPI
+/// doses due as of the latest visit. Resources left out under [`FailurePolicy::Skip`] are
+/// listed in an OperationOutcome entry.
+pub fn transform(kenyan: &KenyanPatient, options: &TransformOptions) -> Result<Bundle> {
+    map_record(kenyan, options).map(|mapped| mapped.bundle)
+}
+
+/// Visits of a bundle for the visit ledger.
+#[cfg(feature = "sqlite")]
+#[derive(Debug, Default)]
+pub struct LedgerVisits {
+    db: Option<PathBuf>,
+    bundle_id: String,
+    hashes: Vec<String>,
+}
+
+#[cfg(feature = "sqlite")]
+impl LedgerVisits {
+    /// Record the visits once the bundle has been written, sent or queued,
+    /// so one that never got out is not taken for a repeat next time.
+    pub fn record(&self) -> Result<()> {
+        if let Some(db) = &self.db {
+            let ledger = VisitLedger::open(db)?;
+            for hash in &self.hashes {
+                ledger.record(hash, &self.bundle_id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A record mapped by [`transform_with_ledger`].
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+pub struct Transformed {
+    pub bundle: Bundle,
+    /// To record in the [`TransformOptions::duplicate_visits`] ledger once
+    /// the bundle is out; none on a dry run
+    pub visits: LedgerVisits,
+    /// What [`FailurePolicy::Skip`] left out, as in the OperationOutcome
+    pub skipped: Vec<String>,
+    /// Problems found while mapping, like [`KenyanPatient::data_quality`]
+    pub data_quality: Vec<String>,
+}
+
+/// [`transform`], with the visits for the ledger and the notes for the log.
+#[cfg(feature = "sqlite")]
+pub fn transform_with_ledger(
+    kenyan: &KenyanPatient,
+    options: &TransformOptions,
+) -> Result<Transformed> {
+    let mapped = map_record(kenyan, options)?;
+    let visits = LedgerVisits {
+        db: options
+            .duplicate_visits
+            .as_ref()
+            .filter(|_| !options.dry_run)
+            .map(|check| check.db.clone()),
+        bundle_id: mapped.bundle.id.clone().unwrap_or_default(),
+        hashes: mapped.hashes,
+    };
+    Ok(Transformed {
+        bundle: mapped.bundle,
+        visits,
+        skipped: mapped.skipped,
+        data_quality: mapped.data_quality,
+    })
+}
+
+/// What [`map_record`] gives beside the bundle.
+#[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+struct Mapped {
+    bundle: Bundle,
+    /// Visit ledger hashes of the visits mapped
+    hashes: Vec<String>,
+    skipped: Vec<String>,
+    data_quality: Vec<String>,
+}
+
+fn map_record(kenyan: &KenyanPatient, options: &TransformOptions) -> Result<Mapped> {
+    let mut data_quality = Vec::new();
+    #[cfg_attr(not(all(feature = "sqlite", feature = "network")), allow(unused_mut))]
+    let mut patient = map_patient(
+        kenyan,
+        (!options.dry_run).then_some(&options.cr_breaker),
+        &mut data_quality,
+    );
+    #[cfg(all(feature = "sqlite", feature = "network"))]
+    match &options.patient_match {
+        Some(_) if options.dry_run => eprintln!("[DRY-RUN] patient matching skipped"),
+        Some(matcher) => {
+            matcher.reconcile(&mut patient);
+        }
+        None => {}
+    }
+    let patient_id = patient
+        .id
+        .as_ref()
+        .context(BridgeError::Mapping, "Patient.id not set")?
+        .clone();
+
+    let facility = options
+        .facilities
+        .as_ref()
+        .and_then(|facilities| facilities.contact(&kenyan.clinic_id));
+    let organization = map_organization(kenyan, facility.as_ref());
+    let documents = map_biometrics(&kenyan.biometrics, &patient_id);
+    let org_id = organization.id.as_deref().unwrap_or("org-unknown");
+
+    #[cfg(feature = "sqlite")]
+    let ledger = match &options.duplicate_visits {
+        Some(check) => Some((VisitLedger::open(&check.db)?, check.action)),
+        None => None,
+    };
+    #[cfg_attr(not(feature = "sqlite"), allow(unused_mut))]
+    let mut seen = Vec::new();
+    #[cfg_attr(not(feature = "sqlite"), allow(unused_mut))]
+    let mut repeats = 0;
+    let mut skipped = Vec::new();
+    let mut visits = Vec::new();
+    for (i, visit) in kenyan.visits.iter().enumerate() {
+        #[cfg(feature = "sqlite")]
+        let hash = visit_hash(&patient_id, &visit.date, &visit.diagnosis);
+        #[cfg(feature = "sqlite")]
+        if let Some((ledger, action)) = &ledger {
+            if let Some(first_seen) = ledger.first_seen(&hash)? {
+                eprintln!(
+                    "[DUPLICATE] visit {} was already transformed on {}{}",
+                    i + 1,
+                    first_seen,
+                    if *action == DuplicateVisitAction::Skip {
+                        "; left out"
+                    } else {
+                        ""
+                    }
+                );
+                if *action == DuplicateVisitAction::Skip {
+                    repeats += 1;
+                    continue;
+                }
+            }
+        }
+        let visit_date = || validate_visit_date(visit);
+        if !options
+            .on_error
+            .keep(|| format!("visit {}", i + 1), visit_date, &mut skipped)
+        {
+            continue;
+        }
+        let key = visit_key(&patient_id, i);
+        let mut skip = |what: String, check: Result<()>| {
+            options.on_error.keep(
+                || format!("visit {}: {}", i + 1, what),
+                || check,
+                &mut skipped,
+            )
+        };
+        let keep_vitals = skip("vital signs".to_string(), validate_vitals(visit));
+        let keep_stage = skip("programme stage".to_string(), validate_stage(visit));
+        let keep_triage = skip("triage category".to_string(), validate_triage(visit));
+        let keep_antenatal = skip(
+            "antenatal findings".to_string(),
+            validate_antenatal(kenyan, visit),
+        );
+        let keep_screening = skip("screening".to_string(), validate_screening(visit));
+        let keep_attachments: Vec<bool> = visit
+            .attachments
+            .iter()
+            .enumerate()
+            .map(|(j, a)| skip(format!("attachment {}", j + 1), validate_attachment(a)))
+            .collect();
+        let keep_lab_orders: Vec<bool> = visit
+            .lab_orders
+            .iter()
+            .enumerate()
+            .map(|(j, o)| skip(format!("lab order {}", j + 1), validate_lab_order(o)))
+            .collect();
+        let keep_imaging_orders: Vec<bool> = visit
+            .imaging_orders
+            .iter()
+            .enumerate()
+            .map(|(j, o)| {
+                skip(
+                    format!("imaging order {}", j + 1),
+                    validate_imaging_order(o),
+                )
+            })
+            .collect();
+        let mut resources = map_visit(kenyan, visit, &patient_id, &key, org_id, options)?;
+        if !keep_vitals {
+            resources.observations.clear();
+        }
+        if !keep_stage {
+            resources.condition.stage = None;
+        }
+        if !keep_antenatal {
+            resources.antenatal = None;
+        }
+        if !keep_screening {
+            resources.screening.clear();
+        }
+        if !keep_triage {
+            resources.encounter.priority = None;
+            resources.triage = None;
+        }
+        let mut keep = keep_attachments.into_iter();
+        resources.documents.retain(|_| keep.next().unwrap_or(true));
+        let mut keep = keep_lab_orders.into_iter();
+        resources.lab_orders.retain(|_| keep.next().unwrap_or(true));
+        let mut keep = keep_imaging_orders.into_iter();
+        resources
+            .imaging_orders
+            .retain(|_| keep.next().unwrap_or(true));
+        if let Some(sha) = &mut resources.sha_claims {
+            add_supporting_info(
+                &mut sha.claim,
+                &resources.observations,
+                &resources.condition,
+            );
+        }
+        visits.push(resources);
+        #[cfg(feature = "sqlite")]
+        seen.push(hash);
+    }
+    if visits.is_empty() && repeats > 0 && skipped.is_empty() {
+        bail!(Mapping, "Every visit in the record was already transformed");
+    }
+    if visits.is_empty() {
+        bail!(Mapping, "No visit could be mapped: {}", skipped.join("; "));
+    }
+
+    let mut bundle = create_transaction_bundle(
+        &patient,
+        &organization,
+        &map_parent_organizations(kenyan),
+        &documents,
+        &visits,
+        options.bundle_id.resolve(kenyan)?,
+        options.timestamp.resolve(kenyan)?,
+    );
+    // KEPI forecast as of the latest visit, for children with a history
+    let latest_visit = kenyan
+        .visits
+        .iter()
+        .filter_map(|v| NaiveDate::parse_from_str(&v.date, "%Y-%m-%d").ok())
+        .max();
+    if let Some(as_of) = latest_visit.filter(|_| !kenyan.immunizations.is_empty()) {
+        let doses = forecast(kenyan.date_of_birth, &kenyan.immunizations, as_of);
+        if !doses.is_empty() {
+            let recommendation =
+                map_immunization_recommendation(&doses, &patient_id, org_id, as_of);
+            add_immunization_recommendation(&mut bundle, &recommendation);
+        }
+    }
+    if !skipped.is_empty() {
+        add_operation_outcome(&mut bundle, &patient_id, &skipped);
+    }
+    if let Some(deidentifier) = &options.deidentify {
+        deidentifier.apply(&mut bundle)?;
+    }
+    options.fhir_version.apply(&mut bundle);
+    if let Some(profiles) = &options.ig_profiles {
+        profiles.apply(&mut bundle);
+    }
+    options.system_uris.apply(&mut bundle);
+    Ok(Mapped {
+        bundle,
+        hashes: seen,
+        skipped,
+        data_quality,
+    })
+}
+
+/// [`transform`] for callers holding the record as JSON text (the browser
+/// build, language bindings): parse, validate as `options.on_error` asks,
+/// map, and return the compact Bundle JSON.
+pub fn transform_json(record_json: &str, options: &TransformOptions) -> Result<String> {
+    let kenyan: KenyanPatient = serde_json::from_str(record_json)
+        .context(BridgeError::Validation, "Invalid Kenyan JSON payload")?;
+    let validate = match options.on_error {
+        FailurePolicy::Abort => validate_kenyan_patient,
+        FailurePolicy::Skip => validate_record,
+    };
+    validate(&kenyan).context(BridgeError::Validation, "Patient record failed validation")?;
+    let bundle = transform(&kenyan, options)?;
+    Ok(bundle_to_json(&bundle, JsonLayout::Compact)?)
+}
+
+fn map_visit(
+    kenyan: &KenyanPatient,
+    visit: &Visit,
+    patient_id: &str,
+    key: &str,
+    org_id: &str,
+    options: &TransformOptions,
+) -> Result<VisitResources> {
+    // Under a programme profile a matching diagnosis enrols the visit
+    let profile = options.profile.filter(|p| p.applies_to(visit));
+    let programme = visit.programme.or(profile.map(ProgrammeProfile::programme));
+
+    // Build practitioner from PUID if present
+    let practitioner = visit.attending_puid.as_deref().map(map_practitioner);
+    let practitioner_id = practitioner.as_ref().and_then(|p| p.id.as_deref());
+    let practitioner_role = visit
+        .attending_puid
+        .as_deref()
+        .zip(visit.attending_cadre)
+        .map(|(puid, cadre)| map_practitioner_role(puid, cadre, org_id));
+
+    let encounter = map_encounter(
+        kenyan,
+        visit,
+        patient_id,
+        key,
+        practitioner_id,
+        programme,
+        &options.complaints,
+    );
+    let encounter_id = encounter
+        .id
+        .as_ref()
+        .context(BridgeError::Mapping, "Encounter.id not set")?
+        .clone();
+
+    // Crosswalk first; the WHO API only for diagnoses it does not know
+    let dx = diagnosis_coding(&visit.diagnosis);
+    #[cfg(all(feature = "sqlite", feature = "network"))]
+    let autocoded = match (&dx, &options.icd11) {
+        (None, Some(client)) => client.autocode(&visit.diagnosis),
+        _ => None,
+    };
+    #[cfg(not(all(feature = "sqlite", feature = "network")))]
+    let autocoded: Option<crate::terminology::Icd11Match> = None;
+
+    // Whoever took the vitals, else the attending clinician
+    let vitals_performer = visit
+        .vitals_taken_by
+        .as_deref()
+        .filter(|puid| visit.attending_puid.as_deref() != Some(*puid))
+        .map(map_practitioner);
+    let vitals_performer_id = vitals_performer
+        .as_ref()
+        .or(practitioner.as_ref())
+        .and_then(|p| p.id.as_deref());
+    let mut observations = map_vitals(
+        &visit.vitals,
+        patient_id,
+        key,
+        &encounter_id,
+        &visit.date,
+        vitals_performer_id,
+    );
+    link_devices(&mut observations, &visit.vitals.devices, org_id);
+    observations.extend(map_nutrition(
+        &visit.vitals,
+        kenyan.date_of_birth,
+        &kenyan.gender,
+        patient_id,
+        key,
+        &encounter_id,
+        &visit.date,
+    ));
+    let mut condition = map_condition(visit, patient_id, key, &encounter_id, autocoded.as_ref());
+    let condition_id = condition
+        .id
+        .as_ref()
+        .context(BridgeError::Mapping, "Condition.id not set")?
+        .clone();
+    if let Some(p) = profile {
+        condition.stage = condition_stage(p, visit).map(|s| vec![s]);
+    }
+    let medication_request =
+        map_medication_request(visit, patient_id, key, &encounter_id, &options.formulary);
+    let medication_request_id = medication_request.id.as_deref().unwrap_or_default();
+    // Programme regimen under --profile, else an NCD plan up to the review
+    let care_plan = match profile {
+        Some(p) => Some(map_care_plan(
+            p,
+            visit,
+            patient_id,
+            key,
+            &encounter_id,
+            &condition_id,
+        )),
+        None => map_ncd_care_plan(
+            visit,
+            patient_id,
+            key,
+            &encounter_id,
+            &condition_id,
+            medication_request_id,
+        ),
+    };
+
+    let clinical_impression = visit
+        .clinical_note
+        .as_deref()
+        .filter(|note| !note.trim().is_empty())
+        .map(|note| {
+            map_clinical_impression(
+                note,
+                patient_id,
+                key,
+                &encounter_id,
+                &condition_id,
+                &visit.date,
+                practitioner_id,
+            )
+        });
+
+    let flags = map_danger_signs(
+        &visit.danger_signs,
+        patient_id,
+        key,
+        &encounter_id,
+        &visit.date,
+        practitioner_id,
+    );
+
+    // SHA Coverage + Claim — only present when sha_member_number is set
+    // ICD-11 code from the crosswalk or autocoding (same as the Condition)
+    let sha_claims = map_sha_claims(
+        visit,
+        patient_id,
+        key,
+        &encounter_id,
+        org_id,
+        dx.map(|d| d.icd11_code)
+            .or(autocoded.as_ref().map(|m| m.code.as_str())),
+        dx.map(|d| d.icd11_display)
+            .or(autocoded.as_ref().map(|m| m.display.as_str())),
+    );
+
+    let appointment = visit.next_appointment_date.map(|date| {
+        map_appointment(
+            date,
+            &visit.date,
+            patient_id,
+            key,
+            &condition_id,
+            practitioner_id,
+            visit
+                .department
+                .map(|department| location_id(org_id, department))
+                .as_deref(),
+        )
+    });
+
+    let documents = map_visit_documents(visit, patient_id, key, &encounter_id);
+    let lab_orders = map_lab_orders(
+        &visit.lab_orders,
+        patient_id,
+        key,
+        &encounter_id,
+        &visit.date,
+        practitioner_id,
+    );
+    let imaging_orders = map_imaging_orders(
+        &visit.imaging_orders,
+        patient_id,
+        key,
+        &encounter_id,
+        &visit.date,
+        practitioner_id,
+        &condition_id,
+    );
+    let antenatal = visit
+        .anc
+        .as_ref()
+        .map(|anc| map_antenatal(anc, patient_id, key, &encounter_id, &visit.date));
+    let screening = visit
+        .screening
+        .as_ref()
+        .map(|s| map_screening(s, patient_id, key, &encounter_id, &visit.date))
+        .unwrap_or_default();
+
+    #[cfg_attr(not(feature = "network"), allow(unused_mut))]
+    let mut resources = VisitResources {
+        encounter,
+        observations,
+        condition,
+        medication_request,
+        practitioner,
+        practitioner_role,
+        vitals_performer,
+        devices: visit
+            .vitals
+            .devices
+            .iter()
+            .map(|device| map_device(device, &kenyan.clinic_id, org_id))
+            .collect(),
+        sha_claims,
+        documents,
+        episode_of_care: programme
+            .map(|programme| map_episode_of_care(programme, patient_id, org_id)),
+        care_plan,
+        clinical_impression,
+        antenatal,
+        screening,
+        triage: visit
+            .triage_category
+            .map(|category| map_triage(category, patient_id, key, &encounter_id, &visit.date)),
+        flags,
+        location: visit
+            .department
+            .map(|department| map_location(department, org_id)),
+        appointment,
+        lab_orders,
+        imaging_orders,
+    };
+    #[cfg(feature = "network")]
+    if let Some(service) = &options.translate {
+        service.apply_to_visit(&mut resources);
+    }
+    Ok(resources)
+}
@@ -0,0 +1,438 @@
+//! Bundle delivery behind one interface.
+//!
+//! Most destinations take a FHIR transaction over HTTPS, directly or via an
+//! OpenHIM channel, but some county intakes are file-based: a folder a
+//! county job picks files up from, or an SFTP server. `send` and `serve`
+//! hand bundles to a [`BundleTransport`], chosen from a `--transport`
+//! config file, so another intake mechanism is one more implementation
+//! rather than a fork.
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::openhim::OpenHimConfig;
+use crate::upload::{compact_json, upload_bundle, UploadOptions};
+
+/// Outcome of a delivery that reached the destination; transport failures
+/// (unreachable host, refused login) are `Err`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryResult {
+    /// Accepted, with what the destination returned (HTTP status, path
+    /// written)
+    Delivered { receipt: String },
+    /// The destination refused the bundle
+    Rejected { reason: String },
+}
+
+/// A way of delivering bundles to one destination.
+pub trait BundleTransport {
+    /// Where bundles go, for logs and dry runs.
+    fn describe(&self) -> String;
+
+    /// Deliver one bundle (JSON) under its Bundle.id.
+    fn send(&self, bundle_id: &str, json: &[u8]) -> Result<DeliveryResult>;
+}
+
+/// FHIR POST over HTTPS ([`upload_bundle`]: compressed, resumable when
+/// large).
+pub struct HttpsTransport {
+    endpoint: String,
+    options: UploadOptions,
+}
+
+impl HttpsTransport {
+    pub fn new(endpoint: &str, options: UploadOptions) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            options,
+        }
+    }
+}
+
+impl BundleTransport for HttpsTransport {
+    fn describe(&self) -> String {
+        self.endpoint.clone()
+    }
+
+    fn send(&self, _bundle_id: &str, json: &[u8]) -> Result<DeliveryResult> {
+        let response = upload_bundle(&self.endpoint, json, &self.options)?;
+        Ok(if response.is_success() {
+            DeliveryResult::Delivered {
+                receipt: format!("HTTP {}", response.status),
+            }
+        } else {
+            DeliveryResult::Rejected {
+                reason: format!("Submission rejected (HTTP {})", response.status),
+            }
+        })
+    }
+}
+
+/// FHIR POST to an OpenHIM channel, with the client's certificate and
+/// credentials.
+pub struct OpenHimTransport {
+    channel: String,
+    https: HttpsTransport,
+}
+
+impl OpenHimTransport {
+    pub fn new(config: &OpenHimConfig, channel: &str, options: UploadOptions) -> Result<Self> {
+        Ok(Self {
+            channel: channel.to_string(),
+            https: HttpsTransport::new(&config.endpoint(channel)?, config.apply(options)?),
+        })
+    }
+}
+
+impl BundleTransport for OpenHimTransport {
+    fn describe(&self) -> String {
+        format!("OpenHIM channel {} ({})", self.channel, self.https.endpoint)
+    }
+
+    fn send(&self, bundle_id: &str, json: &[u8]) -> Result<DeliveryResult> {
+        self.https.send(bundle_id, json)
+    }
+}
+
+/// `{bundle_id}.json` files in a directory a county job collects from,
+/// each followed by its [`Manifest`]. Files are written under a temporary
+/// name and renamed into place, so a watcher never picks up half a bundle.
+pub struct DropFolderTransport {
+    dir: PathBuf,
+}
+
+impl DropFolderTransport {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+}
+
+impl BundleTransport for DropFolderTransport {
+    fn describe(&self) -> String {
+        format!("drop folder {}", self.dir.display())
+    }
+
+    fn send(&self, bundle_id: &str, json: &[u8]) -> Result<DeliveryResult> {
+        let name = file_name(bundle_id)?;
+        let body = compact_json(json);
+        let manifest = Manifest::new(bundle_id, &name, &body);
+        let path = self.write_into_place(&name, &body)?;
+        self.write_into_place(&manifest_name(bundle_id), &manifest.to_json()?)?;
+        Ok(DeliveryResult::Delivered {
+            receipt: path.display().to_string(),
+        })
+    }
+}
+
+impl DropFolderTransport {
+    fn write_into_place(&self, name: &str, contents: &[u8]) -> Result<PathBuf> {
+        let path = self.dir.join(name);
+        let partial = self.dir.join(format!(".{}.part", name));
+        std::fs::write(&partial, contents)
+            .with_context(BridgeError::Io, || format!("Failed to write {:?}", partial))?;
+        std::fs::rename(&partial, &path).with_context(BridgeError::Io, || {
+            format!("Failed to move {:?} into place", path)
+        })?;
+        Ok(path)
+    }
+}
+
+/// `{bundle_id}.json` files uploaded to a directory on an SFTP server,
+/// each followed by its [`Manifest`].
+///
+/// Like HTTP this shells out to curl (built with SFTP support, as the
+/// Windows and most Linux builds are) rather than link an SSH library.
+pub struct SftpTransport {
+    /// `sftp://host[:port]/path/`
+    url: String,
+    user: Option<String>,
+    identity_file: Option<PathBuf>,
+    /// Pinned server host key (base64 SHA-256), checked instead of
+    /// `known_hosts`
+    host_key_sha256: Option<String>,
+    timeout_secs: u32,
+}
+
+impl SftpTransport {
+    pub fn new(url: &str, user: Option<&str>, identity_file: Option<&Path>) -> Result<Self> {
+        if !url.starts_with("sftp://") {
+            bail!(Config, "SFTP url must start with sftp:// (got {:?})", url);
+        }
+        Ok(Self {
+            url: url.to_string(),
+            user: user.map(str::to_string),
+            identity_file: identity_file.map(Path::to_path_buf),
+            host_key_sha256: None,
+            timeout_secs: 60,
+        })
+    }
+
+    /// Accept only the server whose host key has this SHA-256 fingerprint
+    /// (base64, as `ssh-keygen -l` prints it without the `SHA256:` prefix).
+    pub fn with_host_key(mut self, sha256: &str) -> Self {
+        self.host_key_sha256 = Some(sha256.trim_start_matches("SHA256:").to_string());
+        self
+    }
+
+    /// Upload `contents` as `name` in the target directory; returns its URL.
+    fn upload(&self, name: &str, contents: &[u8]) -> Result<String> {
+        let url = format!("{}/{}", self.url.trim_end_matches('/'), name);
+        let mut cmd = Command::new("curl");
+        cmd.args(["--silent", "--show-error", "--ftp-create-dirs"])
+            .args(["--max-time", &self.timeout_secs.to_string()])
+            .args(["--upload-file", "-"]);
+        if let Some(user) = &self.user {
+            cmd.args(["--user", user]);
+        }
+        if let Some(key) = &self.identity_file {
+            cmd.arg("--key").arg(key);
+        }
+        if let Some(fingerprint) = &self.host_key_sha256 {
+            cmd.args(["--hostpubsha256", fingerprint]);
+        }
+        let mut child = cmd
+            .arg(&url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context(BridgeError::Network, "Failed to run curl")?;
+        child
+            .stdin
+            .take()
+            .context(BridgeError::Network, "curl stdin unavailable")?
+            .write_all(contents)?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            bail!(
+                Network,
+                "SFTP upload of {} failed: {}",
+                name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(url)
+    }
+}
+
+impl BundleTransport for SftpTransport {
+    fn describe(&self) -> String {
+        self.url.clone()
+    }
+
+    fn send(&self, bundle_id: &str, json: &[u8]) -> Result<DeliveryResult> {
+        let name = file_name(bundle_id)?;
+        let body = compact_json(json);
+        let manifest = Manifest::new(bundle_id, &name, &body);
+        let url = self.upload(&name, &body)?;
+        self.upload(&manifest_name(bundle_id), &manifest.to_json()?)?;
+        Ok(DeliveryResult::Delivered { receipt: url })
+    }
+}
+
+/// `{bundle_id}.manifest.json`, written after the bundle file: county
+/// jobs take a bundle once its manifest is there and check the file
+/// against its size and SHA-256.
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    pub bundle_id: String,
+    pub file: String,
+    pub bytes: usize,
+    /// Hex SHA-256 of the file
+    pub sha256: String,
+    pub written_at: String,
+}
+
+impl Manifest {
+    pub fn new(bundle_id: &str, file: &str, body: &[u8]) -> Self {
+        Self {
+            bundle_id: bundle_id.to_string(),
+            file: file.to_string(),
+            bytes: body.len(),
+            sha256: Sha256::digest(body)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+            written_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    fn to_json(&self) -> Result<Vec<u8>> {
+        let mut json = serde_json::to_vec_pretty(self)?;
+        json.push(b'\n');
+        Ok(json)
+    }
+}
+
+fn manifest_name(bundle_id: &str) -> String {
+    format!("{}.manifest.json", bundle_id)
+}
+
+/// A bundle id as a file name; ids that could leave the target directory
+/// are refused.
+fn file_name(bundle_id: &str) -> Result<String> {
+    if bundle_id.is_empty() || bundle_id.contains(['/', '\\']) || bundle_id.starts_with('.') {
+        bail!(
+            Config,
+            "Bundle id {:?} cannot be used as a file name",
+            bundle_id
+        );
+    }
+    Ok(format!("{}.json", bundle_id))
+}
+
+/// `--transport` config file, e.g. `{"type": "drop_folder", "dir":
+/// "D:/county-intake"}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransportConfig {
+    Https {
+        endpoint: String,
+    },
+    Openhim {
+        /// `--openhim` config file
+        config: PathBuf,
+        #[serde(default = "default_channel")]
+        channel: String,
+    },
+    DropFolder {
+        dir: PathBuf,
+    },
+    Sftp {
+        url: String,
+        #[serde(default)]
+        user: Option<String>,
+        /// Private key for key-based login
+        #[serde(default)]
+        identity_file: Option<PathBuf>,
+        /// Server host key fingerprint (SHA-256, base64) to pin
+        #[serde(default)]
+        host_key_sha256: Option<String>,
+    },
+    /// Kafka topic via a Confluent REST Proxy
+    #[cfg(feature = "broker")]
+    Kafka {
+        rest_url: String,
+        topic: String,
+    },
+    /// RabbitMQ exchange via the management API
+    #[cfg(feature = "broker")]
+    Amqp {
+        management_url: String,
+        #[serde(default = "default_vhost")]
+        vhost: String,
+        exchange: String,
+    },
+}
+
+fn default_channel() -> String {
+    "default".to_string()
+}
+
+#[cfg(feature = "broker")]
+fn default_vhost() -> String {
+    "/".to_string()
+}
+
+impl TransportConfig {
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path).with_context(BridgeError::Config, || {
+            format!("Failed to read transport config {:?}", path)
+        })?;
+        serde_json::from_str(&raw).context(BridgeError::Config, "Invalid transport config JSON")
+    }
+
+    /// The configured transport; `options` apply to the HTTP ones.
+    pub fn build(&self, options: UploadOptions) -> Result<Box<dyn BundleTransport>> {
+        Ok(match self {
+            TransportConfig::Https { endpoint } => Box::new(HttpsTransport::new(endpoint, options)),
+            TransportConfig::Openhim { config, channel } => Box::new(OpenHimTransport::new(
+                &OpenHimConfig::from_json_file(config)?,
+                channel,
+                options,
+            )?),
+            TransportConfig::DropFolder { dir } => {
+                if !dir.is_dir() {
+                    bail!(Config, "Drop folder {:?} does not exist", dir);
+                }
+                Box::new(DropFolderTransport::new(dir))
+            }
+            TransportConfig::Sftp {
+                url,
+                user,
+                identity_file,
+                host_key_sha256,
+            } => {
+                let sftp = SftpTransport::new(url, user.as_deref(), identity_file.as_deref())?;
+                Box::new(match host_key_sha256 {
+                    Some(sha256) => sftp.with_host_key(sha256),
+                    None => sftp,
+                })
+            }
+            #[cfg(feature = "broker")]
+            TransportConfig::Kafka { rest_url, topic } => {
+                Box::new(crate::broker::KafkaTransport::new(rest_url, topic))
+            }
+            #[cfg(feature = "broker")]
+            TransportConfig::Amqp {
+                management_url,
+                vhost,
+                exchange,
+            } => Box::new(crate::broker::AmqpTransport::new(
+                management_url,
+                vhost,
+                exchange,
+            )?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drop_folder_writes_compact_bundle_and_its_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let config: TransportConfig = serde_json::from_value(serde_json::json!({
+            "type": "drop_folder",
+            "dir": dir.path(),
+        }))
+        .unwrap();
+        let transport = config.build(UploadOptions::default()).unwrap();
+
+        let result = transport
+            .send("b1", b"{\n  \"resourceType\": \"Bundle\"\n}")
+            .unwrap();
+        let path = dir.path().join("b1.json");
+        assert_eq!(
+            result,
+            DeliveryResult::Delivered {
+                receipt: path.display().to_string()
+            }
+        );
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            r#"{"resourceType":"Bundle"}"#
+        );
+        let manifest: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(dir.path().join("b1.manifest.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(manifest["file"], "b1.json");
+        assert_eq!(manifest["bytes"], 25);
+        assert_eq!(
+            manifest["sha256"],
+            "397c79e6ce479194f75f390584db99271bf14440c24cb11200db8694f4e9f254"
+        );
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+        assert!(transport.send("../b2", b"{}").is_err());
+    }
+}
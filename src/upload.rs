@@ -0,0 +1,374 @@
+//! Bundle submission that survives flaky 3G links.
+//!
+//! - **Compression**: the body is gzip'd with `Content-Encoding: gzip`; a
+//!   server that answers 415 gets the plain body instead (negotiated per
+//!   upload).
+//! - **Resumable uploads**: payloads above `chunk_size` go through the tus
+//!   1.0 protocol when the endpoint advertises it (`Tus-Resumable` on
+//!   OPTIONS): create, then PATCH chunks; after a dropped connection the
+//!   current offset is read back with HEAD and the upload continues from
+//!   there instead of starting over.
+//! - Anything else is a single POST.
+//!
+//! A POST is only retried when it never reached the server (host not
+//! resolved, connection refused). After a timeout the server may already
+//! have committed the transaction, and a second one would file a second
+//! SHA Claim; the failure goes back to the caller (and the queue) instead.
+use std::io::{Read, Write};
+use std::thread::sleep;
+use std::time::Duration;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::error::{bail, BridgeError, Context, Result};
+use crate::http::{self, HttpRequest, HttpResponse, SendError, TlsOptions};
+
+const TUS_VERSION: &str = "1.0.0";
+const FHIR_JSON: &str = "application/fhir+json";
+/// tus `Upload-Metadata` (values are base64): filetype application/fhir+json
+const TUS_METADATA_FHIR: &str = "filetype YXBwbGljYXRpb24vZmhpcitqc29u";
+/// ... plus content-encoding gzip
+const TUS_METADATA_GZIP: &str = "content-encoding Z3ppcA==";
+
+#[derive(Debug, Clone)]
+pub struct UploadOptions {
+    pub compress: bool,
+    /// Bodies larger than this use resumable upload when available
+    pub chunk_size: usize,
+    /// Retries per request that failed before reaching the server
+    pub max_retries: u32,
+    pub timeout_secs: u32,
+    /// Extra `Name: value` headers on every request, e.g. an
+    /// interoperability layer's client credentials
+    pub headers: Vec<String>,
+    pub tls: Option<TlsOptions>,
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        Self {
+            compress: true,
+            chunk_size: 256 * 1024,
+            max_retries: 5,
+            timeout_secs: 60,
+            headers: Vec::new(),
+            tls: None,
+        }
+    }
+}
+
+impl UploadOptions {
+    /// A request's own headers plus the configured extra ones.
+    fn request_headers(&self, mut own: Vec<String>) -> Vec<String> {
+        own.extend(self.headers.iter().cloned());
+        own
+    }
+}
+
+pub fn gzip(body: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(body)?;
+    Ok(encoder.finish()?)
+}
+
+pub fn gunzip(body: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    GzDecoder::new(body)
+        .read_to_end(&mut out)
+        .context(BridgeError::Validation, "Invalid gzip data")?;
+    Ok(out)
+}
+
+/// Drop insignificant whitespace from JSON, leaving every token byte for
+/// byte as it was (numbers keep their precision). Pretty-printed bundles
+/// shrink by a third or more before compression.
+pub fn compact_json(json: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(json.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for &b in json {
+        if in_string {
+            out.push(b);
+            if escaped {
+                escaped = false;
+            } else if b == b'\\' {
+                escaped = true;
+            } else if b == b'"' {
+                in_string = false;
+            }
+        } else if !matches!(b, b' ' | b'\n' | b'\r' | b'\t') {
+            in_string = b == b'"';
+            out.push(b);
+        }
+    }
+    out
+}
+
+/// Retry `f` with linear backoff while its request never went out.
+fn with_retries<T>(max_retries: u32, mut f: impl FnMut() -> Result<T, SendError>) -> Result<T> {
+    let mut attempt = 0;
+    loop {
+        match f() {
+            Ok(v) => return Ok(v),
+            Err(e) if e.not_sent && attempt < max_retries => {
+                attempt += 1;
+                eprintln!("[UPLOAD] attempt {} failed, retrying: {}", attempt, e.error);
+                sleep(Duration::from_secs(2 * attempt as u64));
+            }
+            Err(e) => return Err(e.error),
+        }
+    }
+}
+
+fn supports_tus(endpoint: &str, options: &UploadOptions) -> bool {
+    http::send(&HttpRequest {
+        method: "OPTIONS",
+        url: endpoint,
+        headers: options.request_headers(vec![format!("Tus-Resumable: {}", TUS_VERSION)]),
+        body: None,
+        timeout_secs: options.timeout_secs,
+        tls: options.tls.as_ref(),
+    })
+    .map(|r| r.header("Tus-Version").is_some() || r.header("Tus-Resumable").is_some())
+    .unwrap_or(false)
+}
+
+/// Resolve a possibly relative `Location` against the endpoint.
+fn absolute_location(endpoint: &str, location: &str) -> String {
+    if location.starts_with("http://") || location.starts_with("https://") {
+        return location.to_string();
+    }
+    let origin_end = endpoint
+        .find("://")
+        .and_then(|i| endpoint[i + 3..].find('/').map(|j| i + 3 + j))
+        .unwrap_or(endpoint.len());
+    format!(
+        "{}/{}",
+        &endpoint[..origin_end],
+        location.trim_start_matches('/')
+    )
+}
+
+fn tus_upload(
+    endpoint: &str,
+    body: &[u8],
+    gzipped: bool,
+    options: &UploadOptions,
+) -> Result<HttpResponse> {
+    let mut metadata = TUS_METADATA_FHIR.to_string();
+    if gzipped {
+        metadata.push(',');
+        metadata.push_str(TUS_METADATA_GZIP);
+    }
+    let created = with_retries(options.max_retries, || {
+        http::try_send(&HttpRequest {
+            method: "POST",
+            url: endpoint,
+            headers: options.request_headers(vec![
+                format!("Tus-Resumable: {}", TUS_VERSION),
+                format!("Upload-Length: {}", body.len()),
+                format!("Upload-Metadata: {}", metadata),
+            ]),
+            body: None,
+            timeout_secs: options.timeout_secs,
+            tls: options.tls.as_ref(),
+        })
+    })?;
+    if created.status != 201 {
+        bail!(
+            Network,
+            "Upload creation rejected (HTTP {})",
+            created.status
+        );
+    }
+    let location = created.header("Location").context(
+        BridgeError::Network,
+        "Upload creation response has no Location",
+    )?;
+    let upload_url = absolute_location(endpoint, location);
+
+    let mut offset = 0;
+    let mut retries = 0;
+    let mut last = created;
+    while offset < body.len() {
+        let end = (offset + options.chunk_size).min(body.len());
+        let sent = http::send(&HttpRequest {
+            method: "PATCH",
+            url: &upload_url,
+            headers: options.request_headers(vec![
+                format!("Tus-Resumable: {}", TUS_VERSION),
+                format!("Upload-Offset: {}", offset),
+                "Content-Type: application/offset+octet-stream".to_string(),
+            ]),
+            body: Some(&body[offset..end]),
+            timeout_secs: options.timeout_secs,
+            tls: options.tls.as_ref(),
+        });
+        match sent {
+            Ok(r) if r.status == 204 => {
+                offset = r
+                    .header("Upload-Offset")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(end);
+                retries = 0;
+                last = r;
+            }
+            Ok(r) if r.status < 500 && r.status != 409 => {
+                bail!(Network, "Chunk upload rejected (HTTP {})", r.status);
+            }
+            failed => {
+                // Dropped link, 5xx or offset conflict: ask the server where we are.
+                if retries >= options.max_retries {
+                    return match failed {
+                        Err(e) => Err(e.context("Resumable upload gave up")),
+                        Ok(r) => bail!(Network, "Resumable upload gave up (HTTP {})", r.status),
+                    };
+                }
+                retries += 1;
+                sleep(Duration::from_secs(2 * retries as u64));
+                let head = http::send(&HttpRequest {
+                    method: "HEAD",
+                    url: &upload_url,
+                    headers: options
+                        .request_headers(vec![format!("Tus-Resumable: {}", TUS_VERSION)]),
+                    body: None,
+                    timeout_secs: options.timeout_secs,
+                    tls: options.tls.as_ref(),
+                });
+                if let Some(server_offset) = head
+                    .ok()
+                    .and_then(|h| h.header("Upload-Offset").and_then(|v| v.parse().ok()))
+                {
+                    offset = server_offset;
+                }
+                eprintln!("[UPLOAD] resuming at byte {} of {}", offset, body.len());
+            }
+        }
+    }
+    Ok(last)
+}
+
+fn single_post(
+    endpoint: &str,
+    body: &[u8],
+    gzipped: bool,
+    options: &UploadOptions,
+) -> Result<HttpResponse> {
+    let mut headers = vec![
+        format!("Content-Type: {}", FHIR_JSON),
+        "Accept: application/fhir+json, application/json".to_string(),
+    ];
+    if gzipped {
+        headers.push("Content-Encoding: gzip".to_string());
+    }
+    let headers = options.request_headers(headers);
+    with_retries(options.max_retries, || {
+        http::try_send(&HttpRequest {
+            method: "POST",
+            url: endpoint,
+            headers: headers.clone(),
+            body: Some(body),
+            timeout_secs: options.timeout_secs,
+            tls: options.tls.as_ref(),
+        })
+    })
+}
+
+/// Upload a bundle, compacting the JSON, then compressing and chunking as
+/// the endpoint allows.
+pub fn upload_bundle(endpoint: &str, json: &[u8], options: &UploadOptions) -> Result<HttpResponse> {
+    let compact = compact_json(json);
+    let json = compact.as_slice();
+    let compressed = if options.compress {
+        Some(gzip(json)?)
+    } else {
+        None
+    };
+    let payload = compressed.as_deref().unwrap_or(json);
+    let resumable = payload.len() > options.chunk_size && supports_tus(endpoint, options);
+
+    let send = |body: &[u8], gzipped: bool| {
+        if resumable {
+            tus_upload(endpoint, body, gzipped, options)
+        } else {
+            single_post(endpoint, body, gzipped, options)
+        }
+    };
+
+    let response = send(payload, compressed.is_some())?;
+    if response.status == 415 && compressed.is_some() {
+        eprintln!("[UPLOAD] endpoint does not accept gzip; sending uncompressed");
+        return send(json, false);
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_relative_tus_location() {
+        assert_eq!(
+            absolute_location("https://shr.example/fhir/uploads", "/files/abc"),
+            "https://shr.example/files/abc"
+        );
+        assert_eq!(
+            absolute_location("https://shr.example/fhir", "https://cdn.example/x"),
+            "https://cdn.example/x"
+        );
+    }
+
+    #[test]
+    fn compacts_json_without_touching_strings() {
+        let pretty = b"{\n  \"text\": \"BP 120 / 80 \\\" ok\",\n  \"value\": 36.80\n}";
+        assert_eq!(
+            compact_json(pretty),
+            b"{\"text\":\"BP 120 / 80 \\\" ok\",\"value\":36.80}"
+        );
+        assert_eq!(gunzip(&gzip(pretty).unwrap()).unwrap(), pretty);
+    }
+
+    #[test]
+    fn post_that_reached_the_server_is_not_resent() {
+        use std::net::TcpListener;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        // Takes the bundle, then drops the connection without answering
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/fhir", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&connections);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                counted.fetch_add(1, Ordering::SeqCst);
+                let mut stream = stream.unwrap();
+                let _ = stream.read(&mut [0; 4096]);
+            }
+        });
+        let options = UploadOptions {
+            compress: false,
+            max_retries: 2,
+            ..UploadOptions::default()
+        };
+        assert!(upload_bundle(&endpoint, b"{}", &options).is_err());
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/fhir", closed.local_addr().unwrap());
+        drop(closed);
+        let refused = http::try_send(&HttpRequest {
+            method: "POST",
+            url: &url,
+            headers: Vec::new(),
+            body: Some(b"{}"),
+            timeout_secs: 5,
+            tls: None,
+        })
+        .unwrap_err();
+        assert!(refused.not_sent);
+    }
+}
@@ -1,61 +1,332 @@
-/// Input validation for Kenyan clinic records.
-///
-/// All validation errors use generic messages — no PHI in errors or logs.
-use anyhow::{bail, Result};
+//! Input validation for Kenyan clinic records.
+//!
+//! All validation errors use generic messages — no PHI in errors or logs.
+use crate::error::{bail, BridgeError, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::immunization::is_scheduled;
+use crate::kenyan::schema::{ImagingOrder, KenyanPatient, LabOrder, Visit, VisitAttachment};
 
-use crate::kenyan::schema::KenyanPatient;
+/// Largest decoded photo or biometric template accepted inline. Registration
+/// photos are compressed JPEGs well under this; anything bigger is a scan or
+/// the wrong file.
+pub const MAX_ATTACHMENT_BYTES: usize = 1024 * 1024;
 
 /// Validate the full KenyanPatient record before mapping to FHIR.
+///
+/// Every visit is checked; errors name the visit by position only.
 pub fn validate_kenyan_patient(p: &KenyanPatient) -> Result<()> {
+    validate_record(p)?;
+    for (i, visit) in p.visits.iter().enumerate() {
+        validate_vitals(visit).map_err(|e| e.context(format!("visit {}", i + 1)))?;
+        validate_visit_date(visit).map_err(|e| e.context(format!("visit {}", i + 1)))?;
+        validate_stage(visit).map_err(|e| e.context(format!("visit {}", i + 1)))?;
+        validate_antenatal(p, visit).map_err(|e| e.context(format!("visit {}", i + 1)))?;
+        validate_triage(visit).map_err(|e| e.context(format!("visit {}", i + 1)))?;
+        validate_screening(visit).map_err(|e| e.context(format!("visit {}", i + 1)))?;
+        for (j, attachment) in visit.attachments.iter().enumerate() {
+            validate_attachment(attachment)
+                .map_err(|e| e.context(format!("visit {} attachment {}", i + 1, j + 1)))?;
+        }
+        for (j, order) in visit.lab_orders.iter().enumerate() {
+            validate_lab_order(order)
+                .map_err(|e| e.context(format!("visit {} lab order {}", i + 1, j + 1)))?;
+        }
+        for (j, order) in visit.imaging_orders.iter().enumerate() {
+            validate_imaging_order(order)
+                .map_err(|e| e.context(format!("visit {} imaging order {}", i + 1, j + 1)))?;
+        }
+    }
+    Ok(())
+}
+
+/// The record-level part of [`validate_kenyan_patient`]: identifiers, photo,
+/// biometrics and immunizations, at least one visit. Used when bad visit
+/// fields are skipped at mapping time instead (`FailurePolicy::Skip`).
+pub fn validate_record(p: &KenyanPatient) -> Result<()> {
     validate_identifiers(p)?;
-    validate_vitals(p)?;
-    validate_visit_date(p)?;
+    if p.visits.is_empty() {
+        bail!(Validation, "At least one visit is required");
+    }
+    if let Some(photo) = &p.photo {
+        if !matches!(photo.content_type.as_str(), "image/jpeg" | "image/png") {
+            bail!(
+                Validation,
+                "photo content_type must be image/jpeg or image/png"
+            );
+        }
+        validate_base64(&photo.data).map_err(|e| e.context("photo"))?;
+    }
+    for (i, biometric) in p.biometrics.iter().enumerate() {
+        validate_content_type(&biometric.content_type)
+            .and_then(|_| validate_base64(&biometric.data))
+            .map_err(|e| e.context(format!("biometric {}", i + 1)))?;
+    }
+    for (i, dose) in p.immunizations.iter().enumerate() {
+        if !is_scheduled(dose.vaccine, dose.dose) {
+            bail!(
+                Validation,
+                "immunization {}: dose number is not on the KEPI schedule",
+                i + 1
+            );
+        }
+        if dose.date < p.date_of_birth {
+            bail!(
+                Validation,
+                "immunization {}: date is before the date of birth",
+                i + 1
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Decoded size of standard base64 `data`, checked against the limit.
+pub fn validate_base64(data: &str) -> Result<usize> {
+    let bytes = STANDARD
+        .decode(data)
+        .map_err(|_| BridgeError::Validation("data is not valid base64".into()))?;
+    if bytes.is_empty() {
+        bail!(Validation, "data is empty");
+    }
+    if bytes.len() > MAX_ATTACHMENT_BYTES {
+        bail!(Validation, "data exceeds {} bytes", MAX_ATTACHMENT_BYTES);
+    }
+    Ok(bytes.len())
+}
+
+pub fn validate_attachment(attachment: &VisitAttachment) -> Result<()> {
+    if !matches!(
+        attachment.content_type.as_str(),
+        "application/pdf" | "image/jpeg" | "image/png"
+    ) {
+        bail!(
+            Validation,
+            "content_type must be application/pdf, image/jpeg or image/png"
+        );
+    }
+    match (&attachment.data, &attachment.url) {
+        (Some(data), None) => {
+            validate_base64(data)?;
+        }
+        (None, Some(url)) => {
+            if !(url.starts_with("https://") || url.starts_with("http://")) {
+                bail!(Validation, "url must be http or https");
+            }
+        }
+        _ => bail!(Validation, "exactly one of data or url is required"),
+    }
+    Ok(())
+}
+
+/// The test code must look like a LOINC code (`nnnnn-n`).
+pub fn validate_lab_order(order: &LabOrder) -> Result<()> {
+    if !is_loinc(&order.test_code) {
+        bail!(Validation, "test_code must be a LOINC code, e.g. 32700-7");
+    }
+    Ok(())
+}
+
+/// The study code must look like a LOINC code; attachments as for the
+/// visit's own.
+pub fn validate_imaging_order(order: &ImagingOrder) -> Result<()> {
+    if !is_loinc(&order.study_code) {
+        bail!(Validation, "study_code must be a LOINC code, e.g. 36643-5");
+    }
+    if order
+        .conclusion
+        .as_deref()
+        .is_some_and(|c| c.trim().is_empty())
+    {
+        bail!(Validation, "conclusion must not be empty");
+    }
+    for (i, attachment) in order.attachments.iter().enumerate() {
+        validate_attachment(attachment).map_err(|e| e.context(format!("attachment {}", i + 1)))?;
+    }
+    Ok(())
+}
+
+/// `nnnnn-n`: up to seven digits, a hyphen and a check digit.
+fn is_loinc(code: &str) -> bool {
+    code.split_once('-').is_some_and(|(number, check)| {
+        (1..=7).contains(&number.len())
+            && number.chars().all(|c| c.is_ascii_digit())
+            && check.len() == 1
+            && check.chars().all(|c| c.is_ascii_digit())
+    })
+}
+
+fn validate_content_type(content_type: &str) -> Result<()> {
+    let valid = content_type.split_once('/').is_some_and(|(kind, sub)| {
+        !kind.is_empty() && !sub.is_empty() && !content_type.contains(char::is_whitespace)
+    });
+    if !valid {
+        bail!(
+            Validation,
+            "content_type must be a MIME type such as application/octet-stream"
+        );
+    }
     Ok(())
 }
 
 fn validate_identifiers(p: &KenyanPatient) -> Result<()> {
     if p.clinic_id.trim().is_empty() {
-        bail!("clinic_id is required");
+        bail!(Validation, "clinic_id is required");
     }
     if p.patient_number.trim().is_empty() {
-        bail!("patient_number is required");
+        bail!(Validation, "patient_number is required");
     }
     if p.national_id.trim().is_empty() {
-        bail!("national_id is required");
+        bail!(Validation, "national_id is required");
     }
     // Sanitize: identifiers must be alphanumeric + limited punctuation
     for ch in p.clinic_id.chars() {
         if !ch.is_alphanumeric() && ch != '-' && ch != '_' {
-            bail!("Invalid clinic_id format");
+            bail!(Validation, "Invalid clinic_id format");
         }
     }
     Ok(())
 }
 
-fn validate_vitals(p: &KenyanPatient) -> Result<()> {
-    let v = &p.visit.vitals;
+pub fn validate_vitals(visit: &Visit) -> Result<()> {
+    let v = &visit.vitals;
 
     if !(35.0..=42.0).contains(&v.temperature_celsius) {
-        bail!("Temperature value out of valid clinical range (35–42 °C)");
+        bail!(
+            Validation,
+            "Temperature value out of valid clinical range (35–42 °C)"
+        );
     }
     if !(30..=300).contains(&v.bp_systolic) {
-        bail!("Systolic BP value out of valid clinical range (30–300 mmHg)");
+        bail!(
+            Validation,
+            "Systolic BP value out of valid clinical range (30–300 mmHg)"
+        );
     }
     if !(20..=200).contains(&v.bp_diastolic) {
-        bail!("Diastolic BP value out of valid clinical range (20–200 mmHg)");
+        bail!(
+            Validation,
+            "Diastolic BP value out of valid clinical range (20–200 mmHg)"
+        );
     }
     if v.bp_diastolic >= v.bp_systolic {
-        bail!("Diastolic BP must be less than systolic BP");
+        bail!(Validation, "Diastolic BP must be less than systolic BP");
     }
     if !(1.0..=500.0).contains(&v.weight_kg) {
-        bail!("Weight value out of valid clinical range (1–500 kg)");
+        bail!(
+            Validation,
+            "Weight value out of valid clinical range (1–500 kg)"
+        );
+    }
+    if v.height_cm.is_some_and(|h| !(30.0..=250.0).contains(&h)) {
+        bail!(
+            Validation,
+            "Height value out of valid clinical range (30–250 cm)"
+        );
+    }
+    if v.muac_cm.is_some_and(|m| !(5.0..=60.0).contains(&m)) {
+        bail!(
+            Validation,
+            "MUAC value out of valid clinical range (5–60 cm)"
+        );
     }
 
     Ok(())
 }
 
-fn validate_visit_date(p: &KenyanPatient) -> Result<()> {
-    chrono::NaiveDate::parse_from_str(&p.visit.date, "%Y-%m-%d")
-        .map_err(|_| anyhow::anyhow!("Invalid visit date format — expected YYYY-MM-DD"))?;
+pub fn validate_stage(visit: &Visit) -> Result<()> {
+    if visit.hiv_who_stage.is_some_and(|s| !(1..=4).contains(&s)) {
+        bail!(Validation, "hiv_who_stage must be 1, 2, 3 or 4");
+    }
+    Ok(())
+}
+
+pub fn validate_triage(visit: &Visit) -> Result<()> {
+    if visit.triage_category.is_some_and(|c| !(1..=5).contains(&c)) {
+        bail!(Validation, "triage_category must be 1 to 5");
+    }
+    Ok(())
+}
+
+/// Visual acuity is a Snellen fraction (`6/9`, `20/40`); a screening block
+/// must record something.
+pub fn validate_screening(visit: &Visit) -> Result<()> {
+    let Some(screening) = &visit.screening else {
+        return Ok(());
+    };
+    let acuities = [
+        &screening.visual_acuity_left,
+        &screening.visual_acuity_right,
+    ];
+    if acuities.iter().all(|a| a.is_none()) && screening.hearing.is_none() {
+        bail!(Validation, "Screening has no findings");
+    }
+    for acuity in acuities.into_iter().flatten() {
+        let snellen = acuity.split_once('/').is_some_and(|(distance, letters)| {
+            [distance, letters]
+                .iter()
+                .all(|n| n.parse::<f64>().is_ok_and(|n| n > 0.0))
+        });
+        if !snellen {
+            bail!(
+                Validation,
+                "Visual acuity must be a Snellen fraction, e.g. 6/9"
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn validate_antenatal(p: &KenyanPatient, visit: &Visit) -> Result<()> {
+    let Some(anc) = &visit.anc else {
+        return Ok(());
+    };
+    if p.gender != "F" {
+        bail!(Validation, "Antenatal findings require a female patient");
+    }
+    if !(4..=44).contains(&anc.gestational_age_weeks) {
+        bail!(
+            Validation,
+            "Gestational age out of valid range (4–44 weeks)"
+        );
+    }
+    if anc.gravida == 0 || anc.para >= anc.gravida {
+        bail!(
+            Validation,
+            "Gravida must be at least 1 and greater than para"
+        );
+    }
+    if anc
+        .fundal_height_cm
+        .is_some_and(|h| !(5.0..=50.0).contains(&h))
+    {
+        bail!(Validation, "Fundal height out of valid range (5–50 cm)");
+    }
+    if anc
+        .fetal_heart_rate
+        .is_some_and(|r| !(60..=220).contains(&r))
+    {
+        bail!(
+            Validation,
+            "Fetal heart rate out of valid range (60–220 bpm)"
+        );
+    }
+    Ok(())
+}
+
+pub fn validate_visit_date(visit: &Visit) -> Result<()> {
+    let date = chrono::NaiveDate::parse_from_str(&visit.date, "%Y-%m-%d").map_err(|_| {
+        BridgeError::Validation("Invalid visit date format — expected YYYY-MM-DD".into())
+    })?;
+    if visit.review_date.is_some_and(|review| review < date) {
+        bail!(Validation, "review_date is before the visit date");
+    }
+    if visit.next_appointment_date.is_some_and(|next| next < date) {
+        bail!(Validation, "next_appointment_date is before the visit date");
+    }
+    if visit.onset_date.is_some_and(|onset| onset > date) {
+        bail!(Validation, "onset_date is after the visit date");
+    }
     Ok(())
 }
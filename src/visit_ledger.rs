@@ -0,0 +1,100 @@
+//! Ledger of visits already turned into bundles.
+//!
+//! A record re-sent by the EMR, or a backfill run twice, would otherwise
+//! produce a second Encounter and — for SHA visits — a second Claim. Each
+//! visit is keyed by a SHA-256 of patient id, visit date and diagnosis; only
+//! the hash is stored, never the values.
+use std::path::Path;
+
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use crate::error::{BridgeError, Context, Result};
+
+/// Key of a visit: same patient, same day, same diagnosis (case and spacing
+/// ignored).
+pub fn visit_hash(patient_id: &str, date: &str, diagnosis: &str) -> String {
+    let diagnosis = diagnosis
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    let digest = Sha256::digest(format!("{}\u{1f}{}\u{1f}{}", patient_id, date, diagnosis));
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub struct VisitLedger {
+    conn: Connection,
+}
+
+impl VisitLedger {
+    pub fn open(db_path: &Path) -> Result<Self> {
+        let conn = Connection::open(db_path).with_context(BridgeError::Storage, || {
+            format!("Failed to open visit ledger at {:?}", db_path)
+        })?;
+        Self::init(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS seen_visits (
+                visit_hash TEXT PRIMARY KEY,
+                bundle_id  TEXT NOT NULL,
+                first_seen TEXT NOT NULL
+            );",
+        )
+        .context(
+            BridgeError::Storage,
+            "Failed to initialise visit ledger schema",
+        )?;
+        Ok(Self { conn })
+    }
+
+    /// When the visit was first recorded, if it was.
+    pub fn first_seen(&self, hash: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT first_seen FROM seen_visits WHERE visit_hash = ?1",
+                params![hash],
+                |r| r.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Record a visit; a visit already there keeps its first bundle.
+    pub fn record(&self, hash: &str, bundle_id: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO seen_visits (visit_hash, bundle_id, first_seen)
+             VALUES (?1, ?2, ?3)",
+            params![hash, bundle_id, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_visit_hashes_alike_and_is_recorded_once() {
+        let hash = visit_hash("p-1", "2026-03-03", "Malaria");
+        assert_eq!(hash, visit_hash("p-1", "2026-03-03", "  malaria "));
+        assert_ne!(hash, visit_hash("p-1", "2026-03-04", "Malaria"));
+        assert_ne!(hash, visit_hash("p-2", "2026-03-03", "Malaria"));
+
+        let ledger = VisitLedger::open_in_memory().unwrap();
+        assert_eq!(ledger.first_seen(&hash).unwrap(), None);
+        ledger.record(&hash, "bundle-1").unwrap();
+        let first = ledger.first_seen(&hash).unwrap();
+        assert!(first.is_some());
+        ledger.record(&hash, "bundle-2").unwrap();
+        assert_eq!(ledger.first_seen(&hash).unwrap(), first);
+    }
+}
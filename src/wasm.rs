@@ -0,0 +1,31 @@
+//! Browser entry points (`wasm` feature).
+//!
+//! Built for `wasm32-unknown-unknown` with `--no-default-features
+//! --features wasm`, the crate is the mapping and validation core: no
+//! SQLite stores and no calls out. A browser-based EMR can then check a
+//! record and generate its bundle client-side before uploading it. Records
+//! and bundles cross as JSON text, and a refused record throws a JS `Error`
+//! with the message the CLI would print. With no Client Registry to ask,
+//! the Patient carries the synthetic CR ID (see [`crate::cr_lookup`]).
+use wasm_bindgen::prelude::*;
+
+use crate::error::{BridgeError, Context};
+use crate::kenyan::schema::KenyanPatient;
+use crate::transform::{transform_json, TransformOptions};
+use crate::validation::validate_kenyan_patient;
+
+/// Kenyan record JSON → compact FHIR R4 Bundle JSON.
+#[wasm_bindgen(js_name = transformRecord)]
+pub fn transform_record(record_json: &str) -> Result<String, JsError> {
+    Ok(transform_json(record_json, &TransformOptions::default())?)
+}
+
+/// Check a Kenyan record JSON against the input rules; returns the
+/// data-quality notes for a record that passes.
+#[wasm_bindgen(js_name = validateRecord)]
+pub fn validate_record(record_json: &str) -> Result<Vec<String>, JsError> {
+    let kenyan: KenyanPatient = serde_json::from_str(record_json)
+        .context(BridgeError::Validation, "Invalid Kenyan JSON payload")?;
+    validate_kenyan_patient(&kenyan)?;
+    Ok(kenyan.data_quality)
+}
@@ -1,6 +1,96 @@
+// assert_cmd 2.1 deprecated `Command::cargo_bin`; keep the stable call until
+// the suite moves to `cargo_bin_cmd!`.
+#![allow(deprecated)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 
+// ── helpers ──────────────────────────────────────────────────────────────────
+
+/// A record from tests/fixtures, to edit before transforming it.
+fn fixture(name: &str) -> serde_json::Value {
+    let raw = std::fs::read_to_string(format!("tests/fixtures/{}", name)).unwrap();
+    serde_json::from_str(&raw).unwrap()
+}
+
+/// The bridge reading `record` from stdin; further args to be added.
+fn bridge_on(record: &serde_json::Value) -> Command {
+    let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
+    cmd.env_remove("AFYALINK_TOKEN")
+        .args(["--input", "-"])
+        .write_stdin(record.to_string());
+    cmd
+}
+
+/// The bundle `record` transforms into with `args`.
+fn transformed(record: &serde_json::Value, args: &[&str]) -> serde_json::Value {
+    let output = bridge_on(record).args(args).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+/// The bundle of fixture `name` once `mutate` has edited the record.
+fn transform_fixture(name: &str, mutate: impl FnOnce(&mut serde_json::Value)) -> serde_json::Value {
+    let mut record = fixture(name);
+    mutate(&mut record);
+    transformed(&record, &[])
+}
+
+/// The bundle's resources of `resource_type`, in entry order.
+fn resources_of<'a>(
+    bundle: &'a serde_json::Value,
+    resource_type: &str,
+) -> Vec<&'a serde_json::Value> {
+    bundle["entry"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| &e["resource"])
+        .filter(|r| r["resourceType"] == resource_type)
+        .collect()
+}
+
+/// The bundle's first resource of `resource_type`.
+fn find_resource<'a>(bundle: &'a serde_json::Value, resource_type: &str) -> &'a serde_json::Value {
+    resources_of(bundle, resource_type)
+        .first()
+        .copied()
+        .unwrap_or_else(|| panic!("no {}", resource_type))
+}
+
+/// `bundle <subcommand>` run on `bundle`, saved to a file first.
+fn bundle_subcommand(
+    subcommand: &str,
+    bundle: &serde_json::Value,
+    args: &[&str],
+) -> std::process::Output {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bundle.json");
+    std::fs::write(&path, bundle.to_string()).unwrap();
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["bundle", subcommand])
+        .arg(&path)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// The Kenyan record `bundle to-kenyan` reads back from `bundle`.
+fn to_kenyan(bundle: &serde_json::Value) -> serde_json::Value {
+    let output = bundle_subcommand("to-kenyan", bundle, &[]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
 // ── Fixture 1: Happy-path female patient (URTI) — JSON ────────────────────────
 
 #[test]
@@ -39,6 +129,47 @@ fn org_does_not_use_old_kmhfl_uri() {
         .stdout(predicate::str::contains("kmhfl.health.go.ke").not());
 }
 
+#[test]
+fn facility_is_part_of_subcounty_and_county_organizations() {
+    let mut record = fixture("kenyan_patient_1.json");
+    let organizations = |record: &serde_json::Value| {
+        let bundle = transformed(record, &[]);
+        resources_of(&bundle, "Organization")
+            .into_iter()
+            .map(|r| {
+                let parent = r["partOf"]["reference"].as_str().map(str::to_string);
+                (r["id"].as_str().unwrap().to_string(), parent)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // Nairobi / Westlands: facility → sub-county → county, facility first
+    let orgs = organizations(&record);
+    let ids: Vec<&str> = orgs.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(
+        ids,
+        [
+            "org-KEN-NAIROBI-001",
+            "org-subcounty-047-westlands",
+            "org-county-047"
+        ]
+    );
+    let parents: Vec<Option<&str>> = orgs.iter().map(|(_, p)| p.as_deref()).collect();
+    assert_eq!(
+        parents,
+        [
+            Some("Organization/org-subcounty-047-westlands"),
+            Some("Organization/org-county-047"),
+            None
+        ]
+    );
+
+    // A county off the KNBS list creates no health offices
+    record["location"]["county"] = "Atlantis".into();
+    let orgs = organizations(&record);
+    assert_eq!(orgs, [("org-KEN-NAIROBI-001".to_string(), None)]);
+}
+
 #[test]
 fn encounter_has_service_provider() {
     let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
@@ -137,6 +268,77 @@ fn condition_has_icd11_for_tb() {
         .stdout(predicate::str::contains("A15.9"));
 }
 
+#[test]
+fn condition_carries_snomed_as_third_coding() {
+    let output = Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["--input", "tests/fixtures/kenyan_patient_2_male_malaria.json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let bundle: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let condition = bundle["entry"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| &e["resource"])
+        .find(|r| r["resourceType"] == "Condition")
+        .unwrap();
+    let codings = condition["code"]["coding"].as_array().unwrap();
+    assert_eq!(codings.len(), 3);
+    assert_eq!(codings[0]["system"], "http://id.who.int/icd11/mms");
+    assert_eq!(codings[2]["system"], "http://snomed.info/sct");
+    assert_eq!(codings[2]["code"], "61462000");
+}
+
+#[test]
+fn condition_category_separates_problem_list_from_encounter_diagnosis() {
+    let bundle = transform_fixture("kenyan_patient_8_multi_visit.json", |record| {
+        record["visits"][0]["diagnosis_category"] = "problem_list_item".into();
+    });
+    let categories: Vec<&serde_json::Value> = resources_of(&bundle, "Condition")
+        .into_iter()
+        .map(|c| &c["category"][0]["coding"][0]["code"])
+        .collect();
+    assert_eq!(categories, ["problem-list-item", "encounter-diagnosis"]);
+
+    let restored = to_kenyan(&bundle);
+    assert_eq!(
+        restored["visits"][0]["diagnosis_category"],
+        "problem_list_item"
+    );
+    // The default is left out
+    assert!(restored["visits"][1].get("diagnosis_category").is_none());
+}
+
+#[test]
+fn condition_takes_severity_and_onset_and_records_the_visit_date() {
+    let mut record = fixture("kenyan_patient_2_male_malaria.json");
+    let visit_date = record["visit"]["date"].as_str().unwrap().to_string();
+
+    // Without them the visit date is only the recorded date
+    let bundle = transformed(&record, &[]);
+    let plain = find_resource(&bundle, "Condition");
+    assert_eq!(plain["recordedDate"], visit_date);
+    assert!(plain.get("onsetDateTime").is_none());
+    assert!(plain.get("severity").is_none());
+
+    record["visit"]["diagnosis_severity"] = "severe".into();
+    record["visit"]["onset_date"] = "2026-01-07".into();
+    let bundle = transformed(&record, &[]);
+    let graded = find_resource(&bundle, "Condition");
+    assert_eq!(graded["severity"]["coding"][0]["code"], "24484000");
+    assert_eq!(graded["onsetDateTime"], "2026-01-07");
+    assert_eq!(graded["recordedDate"], visit_date);
+
+    record["visit"]["onset_date"] = "2099-01-01".into();
+    bridge_on(&record)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("onset_date"));
+}
+
 // ── Encounter.class = OP (AfyaLink SHR requirement) ──────────────────────────
 
 #[test]
@@ -152,6 +354,29 @@ fn encounter_class_is_op_not_amb() {
         .stdout(predicate::str::contains("\"code\": \"AMB\"").not());
 }
 
+#[test]
+fn each_encounter_names_its_condition_as_billing_diagnosis() {
+    let bundle = transformed(&fixture("kenyan_patient_8_multi_visit.json"), &[]);
+    let encounters = resources_of(&bundle, "Encounter");
+    let conditions = resources_of(&bundle, "Condition");
+    assert!(encounters.len() > 1);
+    for encounter in encounters {
+        let diagnosis = &encounter["diagnosis"][0];
+        assert_eq!(diagnosis["rank"], 1);
+        assert_eq!(diagnosis["use"]["coding"][0]["code"], "billing");
+        let condition_id = diagnosis["condition"]["reference"]
+            .as_str()
+            .unwrap()
+            .strip_prefix("Condition/")
+            .unwrap();
+        let condition = conditions.iter().find(|r| r["id"] == condition_id).unwrap();
+        assert_eq!(
+            condition["encounter"]["reference"],
+            format!("Encounter/{}", encounter["id"].as_str().unwrap())
+        );
+    }
+}
+
 // ── Practitioner (HWR PUID) ───────────────────────────────────────────────────
 
 #[test]
@@ -184,6 +409,30 @@ fn bundle_has_no_practitioner_when_puid_absent() {
         .stdout(predicate::str::contains("\"resourceType\": \"Practitioner\"").not());
 }
 
+#[test]
+fn practitioner_role_links_clinician_facility_and_cadre() {
+    let bundle = transform_fixture("kenyan_patient_7_sha_puid.json", |record| {
+        record["visit"]["attending_cadre"] = "CO".into();
+    });
+    let id_of = |resource_type: &str| {
+        let resource = find_resource(&bundle, resource_type);
+        format!("{}/{}", resource_type, resource["id"].as_str().unwrap())
+    };
+    let role = find_resource(&bundle, "PractitionerRole");
+    assert_eq!(role["practitioner"]["reference"], id_of("Practitioner"));
+    assert_eq!(role["organization"]["reference"], id_of("Organization"));
+    assert_eq!(role["code"][0]["coding"][0]["code"], "clinical-officer");
+    assert_eq!(role["specialty"][0]["coding"][0]["code"], "394814009");
+
+    let restored = to_kenyan(&bundle);
+    assert_eq!(restored["visits"][0]["attending_cadre"], "clinical_officer");
+
+    // Without a cadre there is a Practitioner but no role
+    let bundle = transform_fixture("kenyan_patient_7_sha_puid.json", |_| {});
+    assert!(!resources_of(&bundle, "Practitioner").is_empty());
+    assert!(resources_of(&bundle, "PractitionerRole").is_empty());
+}
+
 // ── SHA Coverage + Claim (preauthorization) ───────────────────────────────────
 
 #[test]
@@ -222,6 +471,70 @@ fn sha_claim_contains_icd11_diagnosis() {
         .stdout(predicate::str::contains("id.who.int/icd11/mms"));
 }
 
+#[test]
+fn sha_claim_supporting_info_references_vitals_and_condition() {
+    let output = Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["--input", "tests/fixtures/kenyan_patient_7_sha_puid.json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let bundle: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let resources: Vec<_> = bundle["entry"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| &e["resource"])
+        .collect();
+    let claim = resources
+        .iter()
+        .find(|r| r["resourceType"] == "Claim")
+        .unwrap();
+    let references: Vec<&str> = claim["supportingInfo"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i["valueReference"]["reference"].as_str().unwrap())
+        .collect();
+    assert!(references[0].starts_with("Observation/"));
+    assert!(references.last().unwrap().starts_with("Condition/"));
+    // Every reference resolves inside the bundle
+    for reference in &references {
+        let (kind, id) = reference.split_once('/').unwrap();
+        assert!(resources
+            .iter()
+            .any(|r| r["resourceType"] == kind && r["id"] == id));
+    }
+    assert_eq!(
+        claim["supportingInfo"][0]["category"]["coding"][0]["code"],
+        "info"
+    );
+}
+
+#[test]
+fn sha_claim_is_priced_in_kes_from_the_tariff() {
+    let bundle_for = |code: &str| {
+        transform_fixture("kenyan_patient_7_sha_puid.json", |record| {
+            record["visit"]["sha_intervention_code"] = code.into();
+        })
+    };
+
+    let bundle = bundle_for("SHA-MAT-001");
+    let claim = find_resource(&bundle, "Claim");
+    let kes = serde_json::json!({"value": 10200.0, "currency": "KES"});
+    assert_eq!(claim["item"][0]["unitPrice"], kes);
+    assert_eq!(claim["item"][0]["net"], kes);
+    assert_eq!(claim["total"], kes);
+
+    // A code missing from the tariff leaves the Claim unpriced; lint warns
+    let bundle = bundle_for("SHA-XYZ-999");
+    assert!(find_resource(&bundle, "Claim").get("total").is_none());
+    let lint = bundle_subcommand("lint", &bundle, &[]);
+    assert!(lint.status.success());
+    assert!(String::from_utf8_lossy(&lint.stderr).contains("Claim has no total"));
+}
+
 #[test]
 fn bundle_has_no_sha_when_member_number_absent() {
     let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
@@ -283,6 +596,209 @@ fn cr_id_is_deterministic_for_same_national_id() {
     assert_eq!(cr1, cr2, "CR-SYNTH- ID must be deterministic across runs");
 }
 
+/// Run fixture 1 against a CR whose patient-search answers with `cr_patient`
+/// (curl reads the file:// base URL) and return the bundle's Patient.
+fn patient_after_cr_lookup(cr_patient: serde_json::Value) -> serde_json::Value {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("v1")).unwrap();
+    let search = serde_json::json!({
+        "resourceType": "Bundle",
+        "type": "searchset",
+        "entry": [{ "resource": cr_patient }]
+    });
+    std::fs::write(dir.path().join("v1/patient-search"), search.to_string()).unwrap();
+    let output = Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .env("AFYALINK_TOKEN", "test-token")
+        .env(
+            "AFYALINK_BASE_URL",
+            format!("file://{}", dir.path().display()),
+        )
+        .args(["--input", "tests/fixtures/kenyan_patient_1.json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let bundle: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    bundle["entry"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["resource"].clone())
+        .find(|r| r["resourceType"] == "Patient")
+        .unwrap()
+}
+
+#[test]
+fn cr_patient_with_other_demographics_is_linked_not_adopted() {
+    let cr_identifier = |patient: &serde_json::Value| {
+        patient["identifier"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|i| i["system"] == "http://cr.dha.go.ke/fhir/Patient")
+            .unwrap()["value"]
+            .clone()
+    };
+
+    // Same person, names swapped and a typo: the CR ID is adopted
+    let same = patient_after_cr_lookup(serde_json::json!({
+        "resourceType": "Patient",
+        "id": "CR-0001",
+        "name": [{ "family": "Wanjiro", "given": ["Kamau"] }],
+        "birthDate": "1985-03-15"
+    }));
+    assert_eq!(cr_identifier(&same), "CR-0001");
+    assert!(same.get("link").is_none());
+    assert!(same.get("meta").is_none());
+
+    // Someone else under this national ID: keep the synthetic ID, link the CR record
+    let other = patient_after_cr_lookup(serde_json::json!({
+        "resourceType": "Patient",
+        "id": "CR-0002",
+        "name": [{ "family": "Otieno", "given": ["Brian"] }],
+        "birthDate": "1990-07-01"
+    }));
+    assert!(cr_identifier(&other)
+        .as_str()
+        .unwrap()
+        .starts_with("CR-SYNTH-"));
+    assert_eq!(other["link"][0]["type"], "seealso");
+    assert_eq!(
+        other["link"][0]["other"]["reference"],
+        "Patient?identifier=http://cr.dha.go.ke/fhir/Patient|CR-0002"
+    );
+    assert_eq!(other["meta"]["tag"][0]["code"], "cr-demographics-mismatch");
+}
+
+#[test]
+fn cr_lookups_stop_after_repeated_failures() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = dir.path().join("cr-breaker.json");
+    let run = |base_url: String| {
+        Command::cargo_bin("kenya-fhir-bridge")
+            .unwrap()
+            .env("AFYALINK_TOKEN", "test-token")
+            .env("AFYALINK_BASE_URL", base_url)
+            .args([
+                "--input",
+                "tests/fixtures/kenyan_patient_1.json",
+                "--cr-failures",
+                "1",
+                "--cr-breaker-state",
+            ])
+            .arg(&state)
+            .output()
+            .unwrap()
+    };
+
+    // Registry unreachable: synthetic ID, and the breaker opens
+    let offline = run(format!("file://{}/missing", dir.path().display()));
+    assert!(offline.status.success());
+    assert!(String::from_utf8_lossy(&offline.stderr).contains("[CR] 1 lookups failed in a row"));
+    assert!(state.exists());
+
+    // Registry back, but within the cool-down: no lookup, still synthetic
+    std::fs::create_dir(dir.path().join("v1")).unwrap();
+    std::fs::write(
+        dir.path().join("v1/patient-search"),
+        r#"{"resourceType": "Bundle", "entry": [{"resource": {"resourceType": "Patient", "id": "CR-0001"}}]}"#,
+    )
+    .unwrap();
+    let cooling = run(format!("file://{}", dir.path().display()));
+    assert!(cooling.status.success());
+    let stdout = String::from_utf8_lossy(&cooling.stdout);
+    assert!(stdout.contains("CR-SYNTH-"));
+    assert!(!stdout.contains("CR-0001"));
+}
+
+#[test]
+fn reconcile_replaces_synthetic_cr_ids_in_queue_and_archive() {
+    use kenya_fhir_bridge::archive::BundleArchive;
+    use kenya_fhir_bridge::offline_queue::OfflineQueue;
+
+    let dir = tempfile::tempdir().unwrap();
+    let queue_db = dir.path().join("queue.db");
+    let archive_db = dir.path().join("archive.db");
+    let queued = dir.path().join("queued.json");
+    // Offline: one bundle waits in the queue, one already went out (archived)
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .env_remove("AFYALINK_TOKEN")
+        .args([
+            "--input",
+            "tests/fixtures/kenyan_patient_1.json",
+            "--output",
+        ])
+        .arg(&queued)
+        .assert()
+        .success();
+    OfflineQueue::open(&queue_db)
+        .unwrap()
+        .enqueue(
+            "queued",
+            &std::fs::read_to_string(&queued).unwrap(),
+            "p1",
+            "c1",
+        )
+        .unwrap();
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .env_remove("AFYALINK_TOKEN")
+        .args([
+            "--input",
+            "tests/fixtures/kenyan_patient_1.json",
+            "--archive",
+        ])
+        .arg(&archive_db)
+        .assert()
+        .success();
+
+    std::fs::create_dir(dir.path().join("v1")).unwrap();
+    std::fs::write(
+        dir.path().join("v1/patient-search"),
+        serde_json::json!({
+            "resourceType": "Bundle",
+            "entry": [{ "resource": {
+                "resourceType": "Patient",
+                "id": "CR-0001",
+                "name": [{ "family": "Kamau", "given": ["Wanjiru"] }],
+                "birthDate": "1985-03-15"
+            }}]
+        })
+        .to_string(),
+    )
+    .unwrap();
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .env("AFYALINK_TOKEN", "test-token")
+        .env(
+            "AFYALINK_BASE_URL",
+            format!("file://{}", dir.path().display()),
+        )
+        .args(["reconcile", "--db"])
+        .arg(&queue_db)
+        .arg("--archive")
+        .arg(&archive_db)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "1 queued bundle(s) rewritten, 1 archived bundle(s) queued again, 0 left synthetic",
+        ));
+
+    let rows = OfflineQueue::open(&queue_db)
+        .unwrap()
+        .pending_within_window()
+        .unwrap();
+    assert_eq!(rows.len(), 2);
+    for row in &rows {
+        assert!(row.bundle_json.contains("CR-0001"));
+        assert!(!row.bundle_json.contains("CR-SYNTH-"));
+    }
+    let archive = BundleArchive::open(&archive_db).unwrap();
+    assert!(archive.containing("CR-SYNTH-").unwrap().is_empty());
+    assert_eq!(archive.containing("CR-0001").unwrap().len(), 1);
+}
+
 // ── Vitals ────────────────────────────────────────────────────────────────────
 
 #[test]
@@ -436,6 +952,33 @@ fn bundle_includes_medication_request() {
         .stdout(predicate::str::contains("\"intent\": \"order\""));
 }
 
+#[test]
+fn medication_request_has_keml_atc_coding() {
+    let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
+    cmd.args(["--input", "tests/fixtures/kenyan_patient_1.json"]);
+
+    cmd.assert()
+        .success()
+        // Amoxicillin matched against the KEML formulary, text kept
+        .stdout(predicate::str::contains("http://www.whocc.no/atc"))
+        .stdout(predicate::str::contains("\"code\": \"J01CA04\""))
+        .stdout(predicate::str::contains("\"text\": \"Amoxicillin 500mg TDS for 7 days\""));
+}
+
+#[test]
+fn medication_request_dosage_is_structured() {
+    let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
+    cmd.args(["--input", "tests/fixtures/kenyan_patient_1.json"]);
+
+    // "500mg TDS for 7 days" → 3×/day for 7 days, 500 mg per dose
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"frequency\": 3"))
+        .stdout(predicate::str::contains("\"periodUnit\": \"d\""))
+        .stdout(predicate::str::contains("\"boundsDuration\""))
+        .stdout(predicate::str::contains("\"doseQuantity\""));
+}
+
 // ── FHIR R4 transaction bundle structure ─────────────────────────────────────
 
 #[test]
@@ -452,3 +995,2828 @@ fn all_entries_have_full_url_and_request() {
         .stdout(predicate::str::contains("\"method\""))
         .stdout(predicate::str::contains("\"url\""));
 }
+
+// ── Fixture 8: Multiple visits in one record ─────────────────────────────────
+
+#[test]
+fn multi_visit_record_emits_one_encounter_per_visit() {
+    let output = Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["--input", "tests/fixtures/kenyan_patient_8_multi_visit.json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let bundle: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let entries = bundle["entry"].as_array().unwrap();
+    let count = |rt: &str| {
+        entries
+            .iter()
+            .filter(|e| e["resource"]["resourceType"] == rt)
+            .count()
+    };
+
+    assert_eq!(count("Patient"), 1);
+    assert_eq!(count("Encounter"), 2);
+    assert_eq!(count("Condition"), 2);
+    assert_eq!(count("MedicationRequest"), 2);
+    assert_eq!(count("Claim"), 2);
+    // Shared across visits — emitted once
+    assert_eq!(count("Practitioner"), 1);
+    assert_eq!(count("Coverage"), 1);
+
+    // fullUrls must be unique within a transaction bundle
+    let mut urls: Vec<&str> = entries
+        .iter()
+        .map(|e| e["fullUrl"].as_str().unwrap())
+        .collect();
+    let total = urls.len();
+    urls.sort();
+    urls.dedup();
+    assert_eq!(urls.len(), total);
+}
+
+#[test]
+fn multi_visit_conditions_follow_each_visit_diagnosis() {
+    let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
+    cmd.args(["--input", "tests/fixtures/kenyan_patient_8_multi_visit.json"]);
+
+    cmd.assert()
+        .success()
+        // Visit 1: hypertension
+        .stdout(predicate::str::contains("BA00"))
+        // Visit 2: malaria
+        .stdout(predicate::str::contains("1F4Z"));
+}
+
+// ── Presenting-complaint coding ──────────────────────────────────────────────
+
+#[test]
+fn encounter_reason_code_is_coded_for_known_complaints() {
+    let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
+    cmd.args(["--input", "tests/fixtures/kenyan_patient_1.json"]);
+
+    cmd.assert()
+        .success()
+        // "Fever and cough" → SNOMED CT fever + ICPC-2 cough
+        .stdout(predicate::str::contains("http://snomed.info/sct"))
+        .stdout(predicate::str::contains("386661006"))
+        .stdout(predicate::str::contains("http://hl7.org/fhir/sid/icpc-2"))
+        .stdout(predicate::str::contains("\"R05\""));
+}
+
+// ── Remote $validate ──────────────────────────────────────────────────────────
+
+#[test]
+fn remote_validate_fails_when_server_unreachable() {
+    let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
+    cmd.args([
+        "--input",
+        "tests/fixtures/kenyan_patient_1.json",
+        "--remote-validate",
+        "http://127.0.0.1:9/fhir",
+    ]);
+
+    // Transport failure must stop the run — no bundle on stdout
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::contains("Remote validation request failed"));
+}
+
+// ── Syndromic surveillance feed ──────────────────────────────────────────────
+
+#[test]
+fn surveillance_feed_counts_syndromes_without_identifiers() {
+    let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
+    cmd.args([
+        "surveillance",
+        "--input",
+        "tests/fixtures/kenyan_patient_1.json",
+        "tests/fixtures/kenyan_patient_2_male_malaria.json",
+        "--feed-format",
+        "csv",
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "date,clinic_id,county,syndrome_code,syndrome_name,count",
+        ))
+        // Fixture 1: "Fever and cough" → influenza-like illness
+        .stdout(predicate::str::contains(
+            "2026-02-15,KEN-NAIROBI-001,Nairobi,ILI,Influenza-like illness,1",
+        ))
+        // Aggregate only — no patient identifiers
+        .stdout(predicate::str::contains("27845612").not());
+}
+
+// ── bundle lint ───────────────────────────────────────────────────────────────
+
+#[test]
+fn generated_bundles_pass_bundle_lint() {
+    let dir = tempfile::tempdir().unwrap();
+    let bundle_path = dir.path().join("bundle.json");
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["--input", "tests/fixtures/kenyan_patient_8_multi_visit.json", "--output"])
+        .arg(&bundle_path)
+        .assert()
+        .success();
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["bundle", "lint"])
+        .arg(&bundle_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Bundle lint passed"));
+}
+
+#[test]
+fn bundle_lint_reports_entry_without_full_url() {
+    let dir = tempfile::tempdir().unwrap();
+    let bundle_path = dir.path().join("broken.json");
+    std::fs::write(
+        &bundle_path,
+        r#"{"resourceType": "Bundle", "type": "transaction", "entry": [
+            {"resource": {"resourceType": "Organization", "id": "org-1",
+                          "identifier": [{"value": "X"}]},
+             "request": {"method": "PUT", "url": "Organization/org-1"}}
+        ]}"#,
+    )
+    .unwrap();
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["bundle", "lint"])
+        .arg(&bundle_path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("entry[0].fullUrl"));
+}
+
+#[test]
+fn bundle_lint_masks_identifiers_unless_show_identifiers() {
+    let dir = tempfile::tempdir().unwrap();
+    let bundle_path = dir.path().join("dangling.json");
+    std::fs::write(
+        &bundle_path,
+        r#"{"resourceType": "Bundle", "type": "transaction", "entry": [
+            {"fullUrl": "urn:uuid:obs-1",
+             "resource": {"resourceType": "Observation", "id": "obs-1", "status": "final",
+                          "code": {"text": "Weight"},
+                          "subject": {"reference": "Patient/27845612"}},
+             "request": {"method": "PUT", "url": "Observation/obs-1"}}
+        ]}"#,
+    )
+    .unwrap();
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["bundle", "lint"])
+        .arg(&bundle_path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Patient/****5612"))
+        .stderr(predicate::str::contains("27845612").not());
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["bundle", "lint", "--show-identifiers"])
+        .arg(&bundle_path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Patient/27845612"));
+}
+
+// ── DHA indicator MeasureReports ─────────────────────────────────────────────
+
+#[test]
+fn measures_emit_measure_reports_and_csv() {
+    let dir = tempfile::tempdir().unwrap();
+    for fixture in [
+        "kenyan_patient_3_no_phone_hypertension.json",
+        "kenyan_patient_7_sha_puid.json",
+    ] {
+        Command::cargo_bin("kenya-fhir-bridge")
+            .unwrap()
+            .args(["--input", &format!("tests/fixtures/{}", fixture), "--output"])
+            .arg(dir.path().join(fixture))
+            .assert()
+            .success();
+    }
+    let csv_path = dir.path().join("indicators.csv");
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["measures", "--from", "2026-02-01", "--to", "2026-02-28", "--bundles"])
+        .arg(dir.path())
+        .arg("--csv")
+        .arg(&csv_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"resourceType\": \"MeasureReport\""))
+        .stdout(predicate::str::contains("Measure/htn-bp-recorded"));
+
+    let csv = std::fs::read_to_string(&csv_path).unwrap();
+    assert!(csv.contains("htn-bp-recorded,"));
+    assert!(csv.contains(",org-KEN-MOMBASA-007,2026-02-01,2026-02-28,1,1,1.000"));
+}
+
+#[test]
+fn measures_count_vitals_against_their_own_visit() {
+    let dir = tempfile::tempdir().unwrap();
+    // Two visits on one day; only the first had SpO2 taken
+    let bundle = transform_fixture("kenyan_patient_8_multi_visit.json", |record| {
+        record["visits"][0]["vitals"]["o2_saturation"] = 97.0.into();
+        record["visits"][1]["date"] = "2026-03-03".into();
+    });
+    let bundles = dir.path().join("bundles");
+    std::fs::create_dir(&bundles).unwrap();
+    std::fs::write(bundles.join("bundle.json"), bundle.to_string()).unwrap();
+    let csv_path = dir.path().join("indicators.csv");
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args([
+            "measures",
+            "--from",
+            "2026-03-01",
+            "--to",
+            "2026-03-31",
+            "--bundles",
+        ])
+        .arg(&bundles)
+        .arg("--csv")
+        .arg(&csv_path)
+        .assert()
+        .success();
+
+    let csv = std::fs::read_to_string(&csv_path).unwrap();
+    let spo2 = csv
+        .lines()
+        .find(|line| line.starts_with("opd-spo2-recorded,"))
+        .unwrap();
+    assert!(spo2.ends_with(",1,2,0.500"), "{}", spo2);
+}
+
+// ── Bulk Data export ─────────────────────────────────────────────────────────
+
+#[test]
+fn export_bulk_writes_each_resource_once_per_type_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let bundles = dir.path().join("bundles");
+    std::fs::create_dir(&bundles).unwrap();
+    // Patient 2 twice, as when a record is transformed again
+    for (fixture, name) in [
+        ("kenyan_patient_2_male_malaria.json", "a.json"),
+        ("kenyan_patient_2_male_malaria.json", "b.json"),
+        ("kenyan_patient_8_multi_visit.json", "c.json"),
+    ] {
+        Command::cargo_bin("kenya-fhir-bridge")
+            .unwrap()
+            .env_remove("AFYALINK_TOKEN")
+            .args([
+                "--input",
+                &format!("tests/fixtures/{}", fixture),
+                "--output",
+            ])
+            .arg(bundles.join(name))
+            .assert()
+            .success();
+    }
+    let out = dir.path().join("ndjson");
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["export", "bulk", "--bundles"])
+        .arg(&bundles)
+        .arg("--output")
+        .arg(&out)
+        .assert()
+        .success();
+
+    let lines = |resource_type: &str| -> Vec<serde_json::Value> {
+        std::fs::read_to_string(out.join(format!("{}.ndjson", resource_type)))
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    };
+    let patients = lines("Patient");
+    assert_eq!(patients.len(), 2);
+    assert!(patients.iter().all(|p| p["resourceType"] == "Patient"));
+    assert_eq!(lines("Encounter").len(), 3);
+    assert!(out.join("Observation.ndjson").exists());
+    assert!(!out.join("Bundle.ndjson").exists());
+}
+
+// ── reprocess --compare ──────────────────────────────────────────────────────
+
+#[test]
+fn reprocess_compare_reports_coding_changes_against_archive() {
+    let dir = tempfile::tempdir().unwrap();
+    let archived = dir.path().join("archived");
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["reprocess", "--input", "tests/fixtures/kenyan_patient_1.json", "--output"])
+        .arg(&archived)
+        .assert()
+        .success();
+
+    // Simulate a bundle produced by an older crosswalk
+    let path = archived.join("kenyan_patient_1.json");
+    let mut bundle: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    for entry in bundle["entry"].as_array_mut().unwrap() {
+        if entry["resource"]["resourceType"] == "Condition" {
+            entry["resource"]["code"]["coding"][0]["code"] = "OLD-CODE".into();
+        }
+    }
+    std::fs::write(&path, bundle.to_string()).unwrap();
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["reprocess", "--compare", "--input", "tests/fixtures/kenyan_patient_1.json", "--archived"])
+        .arg(&archived)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("kenyan_patient_1: 1 coding change(s), 0 content change(s)"))
+        .stdout(predicate::str::contains("\"OLD-CODE\" -> "))
+        .stdout(predicate::str::contains("Compared 1 record(s): 0 unchanged"));
+}
+
+#[test]
+fn reprocess_resume_carries_on_from_the_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input");
+    let output = dir.path().join("output");
+    let manifest = dir.path().join("manifest.jsonl");
+    std::fs::create_dir(&input).unwrap();
+    std::fs::copy("tests/fixtures/kenyan_patient_1.json", input.join("a.json")).unwrap();
+    // Cut short, as a power cut would leave an export
+    std::fs::write(input.join("b.json"), "{\"clinic_id\": ").unwrap();
+
+    let reprocess = || {
+        let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
+        cmd.arg("reprocess")
+            .arg("--input")
+            .arg(&input)
+            .arg("--output")
+            .arg(&output)
+            .arg("--manifest")
+            .arg(&manifest);
+        cmd
+    };
+    reprocess().assert().failure();
+    let entries: Vec<serde_json::Value> = std::fs::read_to_string(&manifest)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["status"], "ok");
+    assert_eq!(entries[1]["status"], "failed");
+    assert!(entries[1]["detail"].as_str().unwrap().contains("b.json"));
+
+    std::fs::copy("tests/fixtures/kenyan_patient_1.json", input.join("b.json")).unwrap();
+    std::fs::remove_dir_all(&output).unwrap();
+    reprocess()
+        .arg("--resume")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("resuming: 1 already done"));
+    assert!(!output.join("a.json").exists());
+    assert!(output.join("b.json").exists());
+}
+
+// ── bundle archive ───────────────────────────────────────────────────────────
+
+#[test]
+fn archived_bundles_are_full_text_searchable() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("archive.db");
+
+    for fixture in ["kenyan_patient_1.json", "kenyan_patient_3_no_phone_hypertension.json"] {
+        Command::cargo_bin("kenya-fhir-bridge")
+            .unwrap()
+            .args(["--input", &format!("tests/fixtures/{}", fixture), "--archive"])
+            .arg(&db)
+            .assert()
+            .success();
+    }
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .arg("archive")
+        .arg("--db")
+        .arg(&db)
+        .args(["search", "--text", "amoxicillin"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[Amoxicillin]"))
+        .stdout(predicate::str::contains("KEN-NAIROBI-001"))
+        .stdout(predicate::str::contains("1 match(es)"));
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .arg("archive")
+        .arg("--db")
+        .arg(&db)
+        .args(["search", "--text", "no-such-drug"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 match(es)"));
+}
+
+#[test]
+fn archive_history_lists_a_patients_visits_latest_first() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("archive.db");
+
+    for fixture in ["kenyan_patient_1.json", "kenyan_patient_8_multi_visit.json"] {
+        Command::cargo_bin("kenya-fhir-bridge")
+            .unwrap()
+            .args([
+                "--input",
+                &format!("tests/fixtures/{}", fixture),
+                "--archive",
+            ])
+            .arg(&db)
+            .assert()
+            .success();
+    }
+
+    let output = Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .arg("archive")
+        .arg("--db")
+        .arg(&db)
+        .args(["history", "--patient", "55012"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", stdout);
+    assert!(lines[0].starts_with("2026-03-24  KEN-KIAMBU-004  Malaria"));
+    assert!(lines[1].starts_with("2026-03-03  KEN-KIAMBU-004  Hypertension"));
+    assert_eq!(lines[2], "2 visit(s)");
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .arg("archive")
+        .arg("--db")
+        .arg(&db)
+        .args([
+            "history",
+            "--patient",
+            "55012",
+            "--facility",
+            "KEN-NAIROBI-001",
+        ])
+        .assert()
+        .success()
+        .stdout("0 visit(s)\n");
+}
+
+// ── submit ───────────────────────────────────────────────────────────────────
+
+#[test]
+fn submit_reports_unreachable_endpoint() {
+    let dir = tempfile::tempdir().unwrap();
+    let bundle_path = dir.path().join("bundle.json");
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["--input", "tests/fixtures/kenyan_patient_1.json", "--output"])
+        .arg(&bundle_path)
+        .assert()
+        .success();
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .arg("submit")
+        .arg(&bundle_path)
+        .args(["--endpoint", "http://127.0.0.1:9/fhir", "--retries", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("HTTP request failed"));
+}
+
+#[test]
+fn submit_through_openhim_needs_client_token() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("openhim.json");
+    std::fs::write(
+        &config,
+        r#"{"base_url": "http://127.0.0.1:9", "channels": {"default": "/shr/fhir"}, "auth": "custom"}"#,
+    )
+    .unwrap();
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .env_remove("OPENHIM_CLIENT_TOKEN")
+        .args([
+            "submit",
+            "tests/fixtures/kenyan_patient_1.json",
+            "--openhim",
+        ])
+        .arg(&config)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("OPENHIM_CLIENT_TOKEN"));
+
+    // An unknown channel is a config error, not a request to nowhere
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .env("OPENHIM_CLIENT_TOKEN", "t")
+        .args([
+            "submit",
+            "tests/fixtures/kenyan_patient_1.json",
+            "--openhim",
+        ])
+        .arg(&config)
+        .args(["--channel", "claims"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no channel \"claims\""));
+}
+
+#[test]
+fn submit_through_drop_folder_transport_writes_bundle_and_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    let bundle_path = dir.path().join("bundle.json");
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args([
+            "--input",
+            "tests/fixtures/kenyan_patient_1.json",
+            "--output",
+        ])
+        .arg(&bundle_path)
+        .assert()
+        .success();
+    let bundle: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&bundle_path).unwrap()).unwrap();
+    let intake = dir.path().join("intake");
+    std::fs::create_dir(&intake).unwrap();
+    let transport = dir.path().join("transport.json");
+    std::fs::write(
+        &transport,
+        serde_json::json!({ "type": "drop_folder", "dir": intake }).to_string(),
+    )
+    .unwrap();
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .arg("submit")
+        .arg(&bundle_path)
+        .arg("--transport")
+        .arg(&transport)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Submitted ("));
+    let id = bundle["id"].as_str().unwrap();
+    let written: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(intake.join(format!("{}.json", id))).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(written, bundle);
+    let manifest: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(intake.join(format!("{}.manifest.json", id))).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(manifest["bundle_id"], id);
+}
+
+// ── bundle to-kenyan (round trip) ────────────────────────────────────────────
+
+#[test]
+fn bundle_to_kenyan_round_trips_multi_visit_record() {
+    let dir = tempfile::tempdir().unwrap();
+    let bundle_path = dir.path().join("bundle.json");
+    let fixture = "tests/fixtures/kenyan_patient_8_multi_visit.json";
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["--input", fixture, "--output"])
+        .arg(&bundle_path)
+        .assert()
+        .success();
+
+    let output = Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["bundle", "to-kenyan"])
+        .arg(&bundle_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let mut original: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(fixture).unwrap()).unwrap();
+    let restored: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    // The second visit has no intervention code; the Claim carries the default.
+    original["visits"][1]["sha_intervention_code"] = "SHA-OPD-001".into();
+    assert_eq!(restored, original);
+}
+
+#[test]
+fn bundle_to_kenyan_gives_same_day_visits_their_own_vitals() {
+    let mut record = fixture("kenyan_patient_8_multi_visit.json");
+    record["visits"][1]["date"] = record["visits"][0]["date"].clone();
+
+    let restored = to_kenyan(&transformed(&record, &[]));
+    for i in 0..2 {
+        assert_eq!(
+            restored["visits"][i]["vitals"],
+            record["visits"][i]["vitals"]
+        );
+    }
+}
+
+// ── ICD-11 autocoding ────────────────────────────────────────────────────────
+
+#[test]
+fn icd11_autocode_uses_cache_and_falls_back_offline() {
+    use kenya_fhir_bridge::terminology::icd11::{Icd11Cache, Icd11Match};
+
+    let dir = tempfile::tempdir().unwrap();
+    let cache_path = dir.path().join("terminology.db");
+    Icd11Cache::open(&cache_path)
+        .unwrap()
+        .put(
+            "Peptic ulcer disease",
+            "2024-01",
+            Some(&Icd11Match {
+                code: "DA61".to_string(),
+                display: "Peptic ulcer, site unspecified".to_string(),
+            }),
+        )
+        .unwrap();
+
+    let mut record = fixture("kenyan_patient_1.json");
+    let mut run = |diagnosis: &str| {
+        record["visit"]["diagnosis"] = diagnosis.into();
+        bridge_on(&record)
+            .arg("--icd11-autocode")
+            .arg("--icd11-cache")
+            .arg(&cache_path)
+            // Unreachable API: only cached answers can code anything
+            .env("ICD11_CLIENT_ID", "test-client")
+            .env("ICD11_CLIENT_SECRET", "test-secret")
+            .env("ICD11_TOKEN_URL", "http://127.0.0.1:9/connect/token")
+            .env("ICD11_API_BASE", "http://127.0.0.1:9")
+            .assert()
+            .success()
+    };
+
+    run("Peptic ulcer disease")
+        .stdout(predicate::str::contains("\"code\": \"DA61\""))
+        .stdout(predicate::str::contains("\"code\": \"confirmed\""));
+    run("Acute otitis media")
+        .stdout(predicate::str::contains("\"code\": \"provisional\""))
+        .stderr(predicate::str::contains("[ICD11]"));
+}
+
+// ── Bundle signing ───────────────────────────────────────────────────────────
+
+#[test]
+fn signed_bundle_verifies_and_rejects_tampering() {
+    let dir = tempfile::tempdir().unwrap();
+    let bundle_path = dir.path().join("bundle.json");
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args([
+            "--input",
+            "tests/fixtures/kenyan_patient_1.json",
+            "--sign-key",
+            "tests/fixtures/facility_signing_key.pem",
+            "--output",
+        ])
+        .arg(&bundle_path)
+        .assert()
+        .success();
+
+    let mut bundle: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&bundle_path).unwrap()).unwrap();
+    assert_eq!(bundle["signature"]["sigFormat"], "application/jose");
+    assert!(bundle["signature"]["who"]["reference"]
+        .as_str()
+        .unwrap()
+        .starts_with("Organization/"));
+
+    let verify = |path: &std::path::Path| {
+        let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
+        cmd.args(["bundle", "verify", "--public-key"])
+            .arg("tests/fixtures/facility_signing_key.pub.pem")
+            .arg(path);
+        cmd.assert()
+    };
+    verify(&bundle_path)
+        .success()
+        .stdout(predicate::str::contains("Bundle signature valid"));
+
+    bundle["entry"][0]["resource"]["gender"] = "male".into();
+    let tampered = dir.path().join("tampered.json");
+    std::fs::write(&tampered, bundle.to_string()).unwrap();
+    verify(&tampered)
+        .failure()
+        .stderr(predicate::str::contains("does not match"));
+
+    // Elements the model does not cover are kept on parsing, so they are
+    // covered by the signature too
+    let mut bundle: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&bundle_path).unwrap()).unwrap();
+    bundle["link"] = serde_json::json!([{"relation": "self", "url": "https://example.org"}]);
+    std::fs::write(&tampered, bundle.to_string()).unwrap();
+    verify(&tampered)
+        .failure()
+        .stderr(predicate::str::contains("does not match"));
+}
+
+// ── Offline queue encryption ─────────────────────────────────────────────────
+
+#[test]
+fn queue_rotate_key_encrypts_existing_rows() {
+    use kenya_fhir_bridge::offline_queue::OfflineQueue;
+    use kenya_fhir_bridge::queue_crypto::QueueKeys;
+
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("queue.db");
+    let keys_path = dir.path().join("queue-keys.json");
+    OfflineQueue::open(&db)
+        .unwrap()
+        .enqueue("b1", r#"{"resourceType":"Bundle"}"#, "p1", "c1")
+        .unwrap();
+
+    let rotate = || {
+        let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
+        cmd.args(["queue", "--db"])
+            .arg(&db)
+            .args(["rotate-key", "--keys"])
+            .arg(&keys_path);
+        cmd.assert().success()
+    };
+    rotate().stdout(predicate::str::contains("Re-encrypted 1 queued bundle(s)"));
+    let first = QueueKeys::from_json_file(&keys_path).unwrap();
+    rotate().stdout(predicate::str::contains("retired 1 old key(s)"));
+    let second = QueueKeys::from_json_file(&keys_path).unwrap();
+    assert_ne!(first.active, second.active);
+    assert_eq!(second.key_ids().count(), 1);
+
+    let queue = OfflineQueue::open(&db).unwrap();
+    assert!(queue.pending_within_window().is_err());
+    let rows = queue.with_keys(second).pending_within_window().unwrap();
+    assert_eq!(rows[0].bundle_json, r#"{"resourceType":"Bundle"}"#);
+}
+
+#[test]
+fn queue_verify_flags_tampered_rows() {
+    use kenya_fhir_bridge::offline_queue::OfflineQueue;
+
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("queue.db");
+    let queue = OfflineQueue::open(&db).unwrap();
+    queue.enqueue("b1", r#"{"id":"b1"}"#, "p1", "c1").unwrap();
+    queue.enqueue("b2", r#"{"id":"b2"}"#, "p2", "c1").unwrap();
+
+    let verify = || {
+        let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
+        cmd.args(["queue", "--db"]).arg(&db).arg("verify");
+        cmd.assert()
+    };
+    verify()
+        .success()
+        .stdout(predicate::str::contains("2 pending bundle(s) intact"));
+
+    rusqlite::Connection::open(&db)
+        .unwrap()
+        .execute(
+            "UPDATE pending_bundles SET bundle_json = '{}' WHERE bundle_id = 'b2'",
+            [],
+        )
+        .unwrap();
+    verify()
+        .failure()
+        .stderr(predicate::str::contains("row 2 (bundle b2)"))
+        .stderr(predicate::str::contains("1 bad row(s)"));
+}
+
+#[test]
+fn queue_expire_honours_policy_window() {
+    use kenya_fhir_bridge::offline_queue::OfflineQueue;
+
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("queue.db");
+    OfflineQueue::open(&db).unwrap();
+    let ten_days_ago = (chrono::Utc::now() - chrono::Duration::days(10)).to_rfc3339();
+    rusqlite::Connection::open(&db)
+        .unwrap()
+        .execute(
+            "INSERT INTO pending_bundles (bundle_id, bundle_json, patient_id, clinic_id, created_at)
+             VALUES ('b0', '{}', 'p0', 'c1', ?1)",
+            [ten_days_ago],
+        )
+        .unwrap();
+    let policy = dir.path().join("policy.json");
+    std::fs::write(&policy, r#"{"transmission_window_days": 14}"#).unwrap();
+
+    let expire = |policy: Option<&std::path::Path>| {
+        let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
+        cmd.args(["queue", "--db"]).arg(&db);
+        if let Some(p) = policy {
+            cmd.arg("--policy").arg(p);
+        }
+        cmd.arg("expire").assert().success()
+    };
+    expire(Some(&policy)).stdout(predicate::str::contains(
+        "Expired 0 bundle(s) older than 14 days",
+    ));
+    expire(None).stdout(predicate::str::contains(
+        "Expired 1 bundle(s) older than 7 days",
+    ));
+}
+
+#[test]
+fn queue_list_filters_rows_by_patient_clinic_and_status() {
+    use kenya_fhir_bridge::offline_queue::OfflineQueue;
+
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("queue.db");
+    let queue = OfflineQueue::open(&db).unwrap();
+    let sent = queue.enqueue("b1", r#"{"id":"b1"}"#, "p1", "c1").unwrap();
+    queue.enqueue("b2", r#"{"id":"b2"}"#, "p2", "c1").unwrap();
+    queue.enqueue("b3", r#"{"id":"b3"}"#, "p1", "c2").unwrap();
+    queue.mark_sent(sent).unwrap();
+
+    let list = |filters: &[&str]| {
+        let output = Command::cargo_bin("kenya-fhir-bridge")
+            .unwrap()
+            .args(["queue", "--db"])
+            .arg(&db)
+            .arg("list")
+            .args(filters)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let all = list(&[]);
+    assert!(all.contains("  sent  "));
+    assert!(all.ends_with("3 bundle(s)\n"));
+    let p1 = list(&["--patient", "p1"]);
+    assert!(p1.contains(" b1 ") && p1.contains(" b3 ") && !p1.contains(" b2 "));
+    let pending_c1 = list(&["--clinic", "c1", "--status", "pending"]);
+    assert!(pending_c1.contains(" b2 "));
+    assert!(pending_c1.ends_with("1 bundle(s)\n"));
+    assert!(list(&["--to", "2000-01-01"]).ends_with("0 bundle(s)\n"));
+}
+
+#[test]
+fn queue_maintain_archives_old_sent_rows_and_writes_report() {
+    use kenya_fhir_bridge::offline_queue::OfflineQueue;
+
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("queue.db");
+    let queue = OfflineQueue::open(&db).unwrap();
+    let id = queue.enqueue("b1", r#"{"id":"b1"}"#, "p1", "c1").unwrap();
+    queue.mark_sent(id).unwrap();
+    drop(queue);
+    let forty_days_ago = (chrono::Utc::now() - chrono::Duration::days(40)).to_rfc3339();
+    rusqlite::Connection::open(&db)
+        .unwrap()
+        .execute(
+            "UPDATE pending_bundles SET created_at = ?1",
+            [forty_days_ago],
+        )
+        .unwrap();
+
+    let report = dir.path().join("report.json");
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["queue", "--db"])
+        .arg(&db)
+        .arg("maintain")
+        .arg("--report")
+        .arg(&report)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("archived 1 sent row(s)"));
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    assert_eq!(report["archived"], 1);
+    assert_eq!(report["expired"], 0);
+}
+
+// ── Output layout ────────────────────────────────────────────────────────────
+
+#[test]
+fn output_files_are_compact_unless_pretty_requested() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |name: &str, extra: &[&str]| {
+        let path = dir.path().join(name);
+        Command::cargo_bin("kenya-fhir-bridge")
+            .unwrap()
+            .args(["--input", "tests/fixtures/kenyan_patient_1.json"])
+            .args(extra)
+            .arg("--output")
+            .arg(&path)
+            .assert()
+            .success();
+        std::fs::read_to_string(path).unwrap()
+    };
+    let compact = write("compact.json", &[]);
+    let pretty = write("pretty.json", &["--pretty"]);
+    assert!(!compact.contains('\n'));
+    assert!(pretty.contains("\n  \"resourceType\": \"Bundle\""));
+    assert!(compact.len() < pretty.len());
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["--input", "tests/fixtures/kenyan_patient_1.json", "--compact"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("{\"resourceType\":\"Bundle\""));
+}
+
+// ── DHIS2 report ─────────────────────────────────────────────────────────────
+
+#[test]
+fn report_dhis2_counts_month_by_diagnosis_age_and_gender() {
+    let output = Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["report", "dhis2", "--period", "202602", "--input"])
+        .args([
+            "tests/fixtures/kenyan_patient_1.json",
+            "tests/fixtures/kenyan_patient_2_male_malaria.json",
+            "tests/fixtures/kenyan_patient_3_no_phone_hypertension.json",
+            "tests/fixtures/kenyan_patient_7_sha_puid.json",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let set: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(set["dataSet"], "MOH705");
+    assert_eq!(set["period"], "202602");
+    let values = set["dataValues"].as_array().unwrap();
+
+    // The January malaria visit is outside the period; the two hypertension
+    // visits are at different clinics
+    assert_eq!(values.len(), 3);
+    let urti = values
+        .iter()
+        .find(|v| v["dataElement"] == "MOH705_URTI")
+        .unwrap();
+    assert_eq!(urti["orgUnit"], "KEN-NAIROBI-001");
+    assert_eq!(urti["categoryOptionCombo"], "OVER5_FEMALE");
+    assert_eq!(urti["value"], "1");
+    let htn = values
+        .iter()
+        .filter(|v| v["dataElement"] == "MOH705_HYPERTENSION")
+        .count();
+    assert_eq!(htn, 2);
+    assert!(!String::from_utf8_lossy(&output).contains("Wanjiru"));
+}
+
+// ── OPD register ─────────────────────────────────────────────────────────────
+
+#[test]
+fn report_register_lists_visits_with_new_and_revisit() {
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["report", "register", "--from", "2026-03-01", "--input"])
+        .args([
+            "tests/fixtures/kenyan_patient_8_multi_visit.json",
+            "tests/fixtures/kenyan_patient_1.json",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("register,date,"))
+        .stdout(predicate::str::contains(
+            "MOH 204B,2026-03-03,KEN-KIAMBU-004,",
+        ))
+        .stdout(predicate::str::contains(",New,"))
+        .stdout(predicate::str::contains(",Revisit,"))
+        .stdout(predicate::str::contains(",Malaria,Malaria,"))
+        // February visit is before --from
+        .stdout(predicate::str::contains("2026-02-15").not());
+}
+
+#[test]
+fn report_register_reads_pending_queue_bundles() {
+    use kenya_fhir_bridge::offline_queue::OfflineQueue;
+
+    let dir = tempfile::tempdir().unwrap();
+    let bundle_path = dir.path().join("bundle.json");
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args([
+            "--input",
+            "tests/fixtures/kenyan_patient_2_male_malaria.json",
+        ])
+        .arg("--output")
+        .arg(&bundle_path)
+        .assert()
+        .success();
+    let db = dir.path().join("queue.db");
+    OfflineQueue::open(&db)
+        .unwrap()
+        .enqueue(
+            "b1",
+            &std::fs::read_to_string(&bundle_path).unwrap(),
+            "p1",
+            "c1",
+        )
+        .unwrap();
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["report", "register", "--queue"])
+        .arg(&db)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "MOH 204B,2026-01-10,KEN-KISUMU-003,",
+        ))
+        .stdout(predicate::str::contains(",M,"));
+}
+
+// ── OpenMRS import ───────────────────────────────────────────────────────────
+
+#[test]
+fn import_openmrs_requires_credentials() {
+    let dir = tempfile::tempdir().unwrap();
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .env_remove("OPENMRS_USERNAME")
+        .env_remove("OPENMRS_PASSWORD")
+        .args([
+            "import",
+            "openmrs",
+            "--base-url",
+            "http://127.0.0.1:9/openmrs",
+        ])
+        .args(["--clinic-id", "KEN-KISUMU-003"])
+        .args(["--from", "2026-10-01", "--to", "2026-10-15", "--output"])
+        .arg(dir.path().join("bundles"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("OPENMRS_USERNAME"));
+}
+
+// ── Duplicate patients ───────────────────────────────────────────────────────
+
+#[test]
+fn match_db_links_a_known_patient_under_another_national_id() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("mpi.db");
+    let transform = |record: &serde_json::Value| {
+        let output = bridge_on(record)
+            .arg("--match-db")
+            .arg(&db)
+            .args(["--match-action", "link"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let bundle: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let cr_id = find_resource(&bundle, "Patient")["identifier"][0]["value"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        (cr_id, String::from_utf8(output.stderr).unwrap())
+    };
+
+    let mut record = fixture("kenyan_patient_1.json");
+    let (known, _) = transform(&record);
+    assert!(known.starts_with("CR-SYNTH-"));
+    record["national_id"] = "27845621".into();
+    record["patient_number"] = "12399".into();
+
+    let (linked, stderr) = transform(&record);
+    assert_eq!(linked, known);
+    assert!(stderr.contains("[MPI] record matches CR patient"));
+    assert!(!stderr.contains("27845612"));
+}
+
+// ── Synthetic records ────────────────────────────────────────────────────────
+
+#[test]
+fn generated_records_transform_into_bundles() {
+    let dir = tempfile::tempdir().unwrap();
+    let generate = |out: &std::path::Path| {
+        Command::cargo_bin("kenya-fhir-bridge")
+            .unwrap()
+            .args(["generate", "-n", "12", "--seed", "7", "--max-visits", "3"])
+            .args(["--clinic-id", "KEN-KISUMU-003"])
+            .args(["--clinic-id", "KEN-NAKURU-002"])
+            .arg("--output")
+            .arg(out)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Wrote 12 synthetic records"));
+    };
+    generate(&dir.path().join("a"));
+    generate(&dir.path().join("b"));
+
+    let mut files: Vec<_> = std::fs::read_dir(dir.path().join("a"))
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    files.sort();
+    assert_eq!(files.len(), 12);
+    for file in &files {
+        let again = dir.path().join("b").join(file.file_name().unwrap());
+        assert_eq!(std::fs::read(file).unwrap(), std::fs::read(again).unwrap());
+        Command::cargo_bin("kenya-fhir-bridge")
+            .unwrap()
+            .env_remove("AFYALINK_TOKEN")
+            .arg("--input")
+            .arg(file)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("\"resourceType\": \"Bundle\""));
+    }
+}
+
+// ── Deterministic bundles ────────────────────────────────────────────────────
+
+#[test]
+fn deterministic_runs_are_byte_identical() {
+    let run = |extra: &[&str]| {
+        let output = Command::cargo_bin("kenya-fhir-bridge")
+            .unwrap()
+            .env_remove("AFYALINK_TOKEN")
+            .arg("--input")
+            .arg("tests/fixtures/kenyan_patient_8_multi_visit.json")
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        output.stdout
+    };
+    let first = run(&["--deterministic"]);
+    assert_eq!(first, run(&["--deterministic"]));
+    let bundle: serde_json::Value = serde_json::from_slice(&first).unwrap();
+    assert_eq!(bundle["timestamp"], "2026-03-24T00:00:00+00:00");
+
+    let fixed: serde_json::Value = serde_json::from_slice(&run(&[
+        "--deterministic",
+        "--bundle-id",
+        "audit-2026-10",
+        "--timestamp",
+        "2026-10-16T08:00:00+03:00",
+    ]))
+    .unwrap();
+    assert_eq!(fixed["id"], "audit-2026-10");
+    assert_eq!(fixed["timestamp"], "2026-10-16T08:00:00+03:00");
+    assert_eq!(fixed["entry"], bundle["entry"]);
+}
+
+#[test]
+fn transform_json_gives_the_cli_bundle() {
+    use kenya_fhir_bridge::transform::{
+        transform_json, BundleIdSource, TimestampSource, TransformOptions,
+    };
+
+    let path = "tests/fixtures/kenyan_patient_8_multi_visit.json";
+    let output = Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .env_remove("AFYALINK_TOKEN")
+        .args(["--input", path, "--deterministic"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let options = TransformOptions {
+        bundle_id: BundleIdSource::FromInput,
+        timestamp: TimestampSource::FromInput,
+        ..TransformOptions::default()
+    };
+    let json = transform_json(&std::fs::read_to_string(path).unwrap(), &options).unwrap();
+    assert!(!json.contains('\n'));
+    let cli: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+        cli
+    );
+
+    let refused = transform_json("{\"national_id\": \"1\"}", &options).unwrap_err();
+    assert!(matches!(
+        refused,
+        kenya_fhir_bridge::error::BridgeError::Validation(_)
+    ));
+}
+
+// ── Stated age ───────────────────────────────────────────────────────────────
+
+#[test]
+fn stated_age_gives_an_estimated_birth_date() {
+    let mut record = fixture("kenyan_patient_1.json");
+    record.as_object_mut().unwrap().remove("date_of_birth");
+    record["age"] = serde_json::json!({"years": 1, "months": 6});
+
+    let bundle = transformed(&record, &[]);
+    let patient = &bundle["entry"][1]["resource"];
+    assert_eq!(patient["resourceType"], "Patient");
+    // 18 months before the 2026-02-15 visit
+    assert_eq!(patient["birthDate"], "2024-08-15");
+    assert_eq!(patient["_birthDate"]["extension"][0]["valueBoolean"], true);
+
+    record.as_object_mut().unwrap().remove("age");
+    bridge_on(&record)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("date_of_birth or age is required"));
+}
+
+#[test]
+fn stated_age_counts_back_from_the_earliest_valid_visit_date() {
+    let mut record = fixture("kenyan_patient_8_multi_visit.json");
+    record.as_object_mut().unwrap().remove("date_of_birth");
+    record["age"] = 40.into();
+    record["visits"][0]["date"] = "15/01/2026".into();
+
+    let output = bridge_on(&record)
+        .args(["--on-error", "skip"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("[SKIPPED] visit 1"));
+    let bundle: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let second_visit = record["visits"][1]["date"].as_str().unwrap();
+    let patient = find_resource(&bundle, "Patient");
+    assert_eq!(patient["birthDate"], format!("1986{}", &second_visit[4..]));
+
+    for visit in record["visits"].as_array_mut().unwrap() {
+        visit["date"] = "not a date".into();
+    }
+    bridge_on(&record)
+        .args(["--on-error", "skip"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("An age needs a valid visit date"));
+}
+
+#[test]
+fn photo_and_fingerprint_map_to_patient_photo_and_document_reference() {
+    let mut record = fixture("kenyan_patient_1.json");
+    // Not real images or templates; only the base64 shape matters here
+    record["photo"] = serde_json::json!({"content_type": "image/jpeg", "data": "/9j/4AAQSkZJRg=="});
+    record["biometrics"] = serde_json::json!([{
+        "modality": "fingerprint",
+        "content_type": "application/octet-stream",
+        "data": "Rk1SACAyMAA="
+    }]);
+
+    let bundle = transformed(&record, &[]);
+    let patient = &bundle["entry"][1]["resource"];
+    assert_eq!(patient["photo"][0]["contentType"], "image/jpeg");
+    assert_eq!(patient["photo"][0]["size"], 10);
+    let doc = &bundle["entry"][2]["resource"];
+    assert_eq!(doc["resourceType"], "DocumentReference");
+    assert_eq!(doc["type"]["coding"][0]["code"], "fingerprint");
+    assert_eq!(doc["securityLabel"][0]["coding"][0]["code"], "R");
+    assert_eq!(
+        doc["content"][0]["attachment"]["contentType"],
+        "application/octet-stream"
+    );
+
+    let restored = to_kenyan(&bundle);
+    assert_eq!(restored["photo"], record["photo"]);
+    assert_eq!(restored["biometrics"], record["biometrics"]);
+
+    record["photo"]["content_type"] = "application/pdf".into();
+    bridge_on(&record)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "photo content_type must be image/jpeg or image/png",
+        ));
+}
+
+#[test]
+fn visit_attachments_become_encounter_document_references() {
+    let mut record = fixture("kenyan_patient_1.json");
+    record["visit"]["attachments"] = serde_json::json!([
+        {"kind": "lab_report", "content_type": "application/pdf",
+         "title": "Malaria RDT", "data": "JVBERi0xLjQ="},
+        {"kind": "referral_letter", "content_type": "image/jpeg",
+         "url": "https://docs.example.go.ke/referrals/8812.jpg"}
+    ]);
+
+    let bundle = transformed(&record, &[]);
+    let encounter = find_resource(&bundle, "Encounter");
+    let docs = resources_of(&bundle, "DocumentReference");
+    assert_eq!(docs.len(), 2);
+    for doc in &docs {
+        assert_eq!(
+            doc["context"]["encounter"][0]["reference"],
+            format!("Encounter/{}", encounter["id"].as_str().unwrap())
+        );
+    }
+    assert_eq!(docs[0]["type"]["coding"][0]["code"], "11502-2");
+    assert_eq!(docs[0]["content"][0]["attachment"]["size"], 8);
+    assert_eq!(
+        docs[1]["content"][0]["attachment"]["url"],
+        "https://docs.example.go.ke/referrals/8812.jpg"
+    );
+
+    let restored = to_kenyan(&bundle);
+    assert_eq!(
+        restored["visits"][0]["attachments"],
+        record["visit"]["attachments"]
+    );
+
+    record["visit"]["attachments"][1]["data"] = "JVBERi0xLjQ=".into();
+    bridge_on(&record)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "exactly one of data or url is required",
+        ));
+}
+
+// ── de-identification ────────────────────────────────────────────────────────
+
+#[test]
+fn deidentify_strips_direct_identifiers_and_keeps_clinical_content() {
+    let record = fixture("kenyan_patient_7_sha_puid.json");
+    let key = "research-key-2026-0001";
+
+    let run = || {
+        let output = bridge_on(&record)
+            .env("DEIDENTIFY_KEY", key)
+            .arg("--deidentify")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let json = run();
+    for identifying in [
+        "Amina",
+        "Njoroge",
+        "+254720880001",
+        "34567890",
+        "88001",
+        "1990-08-22",
+        "Kasarani",
+        "SHA/",
+    ] {
+        assert!(
+            !json.contains(identifying),
+            "{} left in bundle",
+            identifying
+        );
+    }
+    let bundle: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let patient = find_resource(&bundle, "Patient");
+    assert_eq!(patient["birthDate"], "1990");
+    assert_eq!(patient["address"][0]["district"], "Nairobi");
+    assert!(!resources_of(&bundle, "Condition").is_empty());
+    assert!(resources_of(&bundle, "Claim").is_empty());
+    // The sub-county health office goes; the facility hangs off the county
+    assert_eq!(
+        find_resource(&bundle, "Organization")["partOf"]["reference"],
+        "Organization/org-county-047"
+    );
+
+    // Same key, same pseudonym; references still resolve
+    let again: serde_json::Value = serde_json::from_str(&run()).unwrap();
+    assert_eq!(
+        find_resource(&bundle, "Patient")["id"],
+        find_resource(&again, "Patient")["id"]
+    );
+    assert!(bundle_subcommand("lint", &bundle, &[]).status.success());
+
+    bridge_on(&record)
+        .env_remove("DEIDENTIFY_KEY")
+        .arg("--deidentify")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--deidentify needs DEIDENTIFY_KEY",
+        ));
+}
+
+// ── partial-failure policy ───────────────────────────────────────────────────
+
+#[test]
+fn on_error_skip_leaves_out_bad_vitals_and_reports_them() {
+    let mut record = fixture("kenyan_patient_8_multi_visit.json");
+    // A unit mix-up in a backfill: Fahrenheit in the Celsius field
+    record["visits"][1]["vitals"]["temperature_celsius"] = 101.3.into();
+
+    bridge_on(&record)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("visit 2"));
+
+    let output = bridge_on(&record)
+        .args(["--on-error", "skip"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("[SKIPPED] visit 2: vital signs skipped: Temperature value out of valid"));
+    let bundle: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(resources_of(&bundle, "Encounter").len(), 2);
+    assert_eq!(resources_of(&bundle, "Condition").len(), 2);
+    // Only the first visit's vitals
+    assert!(resources_of(&bundle, "Observation")
+        .iter()
+        .all(|o| o["effectiveDateTime"] == record["visits"][0]["date"]));
+    let outcome = find_resource(&bundle, "OperationOutcome");
+    assert_eq!(outcome["issue"].as_array().unwrap().len(), 1);
+    assert_eq!(outcome["issue"][0]["code"], "processing");
+}
+
+// ── duplicate visits ─────────────────────────────────────────────────────────
+
+#[test]
+fn visit_ledger_skips_visits_already_transformed() {
+    let dir = tempfile::tempdir().unwrap();
+    let ledger = dir.path().join("visits.db");
+    let mut record = fixture("kenyan_patient_8_multi_visit.json");
+    let transform = |record: &serde_json::Value, action: &str| {
+        bridge_on(record)
+            .arg("--visit-ledger")
+            .arg(&ledger)
+            .args(["--duplicate-visits", action])
+            .output()
+            .unwrap()
+    };
+
+    // A bundle that was never written does not count as transformed
+    bridge_on(&record)
+        .arg("--output")
+        .arg(dir.path().join("missing/bundle.json"))
+        .arg("--visit-ledger")
+        .arg(&ledger)
+        .assert()
+        .failure();
+    let first = transform(&record, "skip");
+    assert!(first.status.success());
+    let warned = transform(&record, "warn");
+    assert!(warned.status.success());
+    assert!(String::from_utf8_lossy(&warned.stderr)
+        .contains("[DUPLICATE] visit 2 was already transformed"));
+    let again = transform(&record, "skip");
+    assert!(!again.status.success());
+    assert!(String::from_utf8_lossy(&again.stderr)
+        .contains("Every visit in the record was already transformed"));
+
+    // A re-sent export with one new visit: only that visit goes out
+    let mut new_visit = record["visits"][1].clone();
+    new_visit["date"] = "2026-04-14".into();
+    record["visits"].as_array_mut().unwrap().push(new_visit);
+    let output = transform(&record, "skip");
+    assert!(output.status.success());
+    let bundle: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let encounters = resources_of(&bundle, "Encounter");
+    assert_eq!(encounters.len(), 1);
+    assert!(encounters[0]["period"]["start"]
+        .as_str()
+        .unwrap()
+        .starts_with("2026-04-14"));
+}
+
+// ── longitudinal care ────────────────────────────────────────────────────────
+
+#[test]
+fn programme_visits_share_an_episode_of_care_and_link_follow_ups() {
+    let mut record = fixture("kenyan_patient_8_multi_visit.json");
+    for (i, visit) in record["visits"]
+        .as_array_mut()
+        .unwrap()
+        .iter_mut()
+        .enumerate()
+    {
+        visit["visit_id"] = format!("V-{}", 101 + i).into();
+        visit["previous_visit_id"] = format!("V-{}", 100 + i).into();
+        visit["programme"] = "ncd".into();
+    }
+
+    let bundle = transformed(&record, &[]);
+    let episodes = resources_of(&bundle, "EpisodeOfCare");
+    assert_eq!(episodes.len(), 1);
+    assert_eq!(episodes[0]["type"][0]["coding"][0]["code"], "ncd");
+    let episode_ref = format!("EpisodeOfCare/{}", episodes[0]["id"].as_str().unwrap());
+    let encounters = resources_of(&bundle, "Encounter");
+    for encounter in &encounters {
+        assert_eq!(encounter["episodeOfCare"][0]["reference"], episode_ref);
+    }
+    // The first visit follows one sent earlier; the second follows the first
+    assert_eq!(
+        encounters[0]["partOf"]["reference"],
+        "Encounter?identifier=http://facility-registry.dha.go.ke/fhir/Location/KEN-KIAMBU-004/visit-id|V-100"
+    );
+    assert_eq!(
+        encounters[1]["partOf"]["reference"],
+        format!("Encounter/{}", encounters[0]["id"].as_str().unwrap())
+    );
+
+    assert!(bundle_subcommand("lint", &bundle, &[]).status.success());
+    let restored = to_kenyan(&bundle);
+    for field in ["visit_id", "previous_visit_id", "programme"] {
+        for i in 0..2 {
+            assert_eq!(restored["visits"][i][field], record["visits"][i][field]);
+        }
+    }
+}
+
+#[test]
+fn tb_profile_adds_treatment_phase_and_regimen_care_plan() {
+    let mut record = fixture("kenyan_patient_4_tb_low_spo2.json");
+    record["visit"]["tb_phase"] = "intensive".into();
+    record["visit"]["regimen"] = "2RHZE/4RH".into();
+
+    let bundle = transformed(&record, &["--profile", "tb"]);
+    let condition = find_resource(&bundle, "Condition");
+    let episode = find_resource(&bundle, "EpisodeOfCare");
+    let care_plan = find_resource(&bundle, "CarePlan");
+    let encounter = find_resource(&bundle, "Encounter");
+    assert_eq!(
+        condition["stage"][0]["summary"]["coding"][0]["code"],
+        "intensive"
+    );
+    assert_eq!(episode["type"][0]["coding"][0]["code"], "tb");
+    assert_eq!(
+        encounter["episodeOfCare"][0]["reference"],
+        format!("EpisodeOfCare/{}", episode["id"].as_str().unwrap())
+    );
+    assert_eq!(care_plan["description"], "2RHZE/4RH");
+    assert_eq!(
+        care_plan["addresses"][0]["reference"],
+        format!("Condition/{}", condition["id"].as_str().unwrap())
+    );
+
+    assert!(bundle_subcommand("lint", &bundle, &[]).status.success());
+    let restored = to_kenyan(&bundle);
+    assert_eq!(restored["visits"][0]["tb_phase"], "intensive");
+    assert_eq!(restored["visits"][0]["regimen"], "2RHZE/4RH");
+
+    // Without the profile the same record maps as before
+    let plain = transformed(&record, &[]);
+    assert!(resources_of(&plain, "CarePlan").is_empty());
+    assert!(resources_of(&plain, "EpisodeOfCare").is_empty());
+}
+
+#[test]
+fn ncd_visit_with_review_date_gets_a_care_plan_with_the_follow_up() {
+    let mut record = fixture("kenyan_patient_3_no_phone_hypertension.json");
+
+    // No review date, no plan
+    assert!(resources_of(&transformed(&record, &[]), "CarePlan").is_empty());
+
+    record["visit"]["review_date"] = "2026-03-01".into();
+    let bundle = transformed(&record, &[]);
+    let plans = resources_of(&bundle, "CarePlan");
+    assert_eq!(plans.len(), 1);
+    let plan = plans[0];
+    assert_eq!(plan["title"], "Hypertension care plan");
+    assert_eq!(plan["category"][0]["coding"][0]["code"], "ncd");
+    assert_eq!(plan["period"]["end"], "2026-03-01");
+    assert!(plan["activity"][0]["reference"]["reference"]
+        .as_str()
+        .unwrap()
+        .starts_with("MedicationRequest/"));
+    let follow_up = &plan["activity"][1]["detail"];
+    assert_eq!(follow_up["code"]["coding"][0]["code"], "390906007");
+    assert_eq!(follow_up["status"], "scheduled");
+    assert_eq!(follow_up["scheduledPeriod"]["start"], "2026-03-01");
+
+    // The review date comes back from the bundle
+    let restored = to_kenyan(&bundle);
+    assert_eq!(restored["visits"][0]["review_date"], "2026-03-01");
+    assert!(restored["visits"][0].get("regimen").is_none());
+
+    // Not a chronic NCD: a review date alone gives no plan
+    record["visit"]["diagnosis"] = "Malaria".into();
+    assert!(resources_of(&transformed(&record, &[]), "CarePlan").is_empty());
+
+    // A review before the visit is rejected
+    record["visit"]["review_date"] = "2026-01-01".into();
+    bridge_on(&record)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "review_date is before the visit date",
+        ));
+}
+
+// ── antenatal care ───────────────────────────────────────────────────────────
+
+#[test]
+fn anc_visit_maps_to_pregnancy_condition_and_loinc_observations() {
+    let record = fixture("kenyan_patient_9_anc.json");
+    let bundle = transformed(&record, &[]);
+    let observations = resources_of(&bundle, "Observation");
+    let loinc_value = |code: &str| {
+        observations
+            .iter()
+            .find(|r| r["code"]["coding"][0]["code"] == code)
+            .map(|r| r["valueQuantity"]["value"].as_f64().unwrap())
+    };
+    assert_eq!(loinc_value("49051-6"), Some(28.0));
+    assert_eq!(loinc_value("11996-6"), Some(2.0));
+    assert_eq!(loinc_value("11977-6"), Some(1.0));
+    assert_eq!(loinc_value("11881-0"), Some(27.5));
+    assert_eq!(loinc_value("55283-6"), Some(142.0));
+    let pregnancy = resources_of(&bundle, "Condition")
+        .into_iter()
+        .find(|r| r["code"]["text"] == "Pregnancy")
+        .expect("pregnancy Condition");
+    assert_eq!(pregnancy["code"]["coding"][0]["code"], "77386006");
+    assert_eq!(pregnancy["stage"][0]["summary"]["text"], "Third trimester");
+
+    assert!(bundle_subcommand("lint", &bundle, &[]).status.success());
+    let restored = to_kenyan(&bundle);
+    assert_eq!(restored["visits"][0]["anc"], record["visit"]["anc"]);
+    assert_eq!(
+        restored["visits"][0]["diagnosis"],
+        record["visit"]["diagnosis"]
+    );
+}
+
+#[test]
+fn anc_findings_are_rejected_for_male_patients() {
+    let mut record = fixture("kenyan_patient_9_anc.json");
+    record["gender"] = "M".into();
+    bridge_on(&record)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Antenatal findings require a female patient",
+        ));
+}
+
+// ── nutrition ────────────────────────────────────────────────────────────────
+
+#[test]
+fn anthropometrics_give_bmi_weight_for_age_and_muac_observations() {
+    let by_id = |bundle: &serde_json::Value, prefix: &str| {
+        resources_of(bundle, "Observation")
+            .into_iter()
+            .find(|o| o["id"].as_str().unwrap().starts_with(prefix))
+            .cloned()
+    };
+
+    // An 18-month-old girl, 7.5 kg: underweight, MUAC in the MAM band
+    let child = transform_fixture("kenyan_patient_6_uti.json", |child| {
+        child["date_of_birth"] = "2024-08-10".into();
+        child["visit"]["vitals"]["weight_kg"] = 7.5.into();
+        child["visit"]["vitals"]["height_cm"] = 76.0.into();
+        child["visit"]["vitals"]["muac_cm"] = 11.8.into();
+    });
+    let wfa = by_id(&child, "wfa-").expect("weight-for-age");
+    let z = wfa["valueQuantity"]["value"].as_f64().unwrap();
+    assert!((-3.0..-2.0).contains(&z), "z = {}", z);
+    assert_eq!(wfa["interpretation"][0]["coding"][0]["code"], "L");
+    let muac = by_id(&child, "muac-class-").expect("MUAC class");
+    assert_eq!(muac["valueCodeableConcept"]["coding"][0]["code"], "mam");
+    assert!(muac["derivedFrom"][0]["reference"]
+        .as_str()
+        .unwrap()
+        .starts_with("Observation/muac-"));
+    let bmi = by_id(&child, "bmi-").expect("BMI");
+    assert!(bmi.get("interpretation").is_none());
+
+    // An adult gets a BMI category and no child measures
+    let adult = transform_fixture("kenyan_patient_6_uti.json", |adult| {
+        adult["visit"]["vitals"]["height_cm"] = 160.0.into();
+    });
+    let bmi = by_id(&adult, "bmi-").expect("BMI");
+    assert_eq!(bmi["code"]["coding"][0]["code"], "39156-5");
+    assert_eq!(bmi["valueQuantity"]["value"], 23.83);
+    assert_eq!(bmi["interpretation"][0]["text"], "Normal weight");
+    assert!(by_id(&adult, "wfa-").is_none());
+}
+
+// ── school-health screening ──────────────────────────────────────────────────
+
+#[test]
+fn school_screening_gives_visual_acuity_and_hearing_observations() {
+    let mut record = fixture("kenyan_patient_6_uti.json");
+    record["visit"]["screening"] = serde_json::json!({
+        "visual_acuity_left": "6/6",
+        "visual_acuity_right": "6/18",
+        "hearing": "fail"
+    });
+
+    let bundle = transformed(&record, &[]);
+    let observation = |loinc: &str| {
+        resources_of(&bundle, "Observation")
+            .into_iter()
+            .find(|r| r["code"]["coding"][0]["code"] == loinc)
+            .unwrap_or_else(|| panic!("no {}", loinc))
+    };
+    assert_eq!(observation("79880-1")["valueString"], "6/6");
+    assert_eq!(observation("79882-7")["valueString"], "6/18");
+    let hearing = observation("46216-8");
+    assert_eq!(hearing["valueCodeableConcept"]["coding"][0]["code"], "fail");
+    assert_eq!(hearing["interpretation"][0]["coding"][0]["code"], "A");
+
+    let restored = to_kenyan(&bundle);
+    assert_eq!(
+        restored["visits"][0]["screening"],
+        record["visit"]["screening"]
+    );
+
+    // Acuity has to be a Snellen fraction
+    record["visit"]["screening"]["visual_acuity_left"] = "good".into();
+    bridge_on(&record)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Snellen"));
+}
+
+// ── immunization forecast ────────────────────────────────────────────────────
+
+/// A ten-week-old with the birth and six-week doses on the card.
+fn immunized_infant() -> serde_json::Value {
+    let mut record = fixture("kenyan_patient_6_uti.json");
+    record["date_of_birth"] = "2025-12-01".into();
+    record["immunizations"] = serde_json::json!([
+        {"vaccine": "bcg", "dose": 1, "date": "2025-12-01"},
+        {"vaccine": "opv", "dose": 0, "date": "2025-12-01"},
+        {"vaccine": "opv", "dose": 1, "date": "2026-01-12"},
+        {"vaccine": "penta", "dose": 1, "date": "2026-01-12"},
+        {"vaccine": "pcv", "dose": 1, "date": "2026-01-12"},
+        {"vaccine": "rota", "dose": 1, "date": "2026-01-12"}
+    ]);
+    record
+}
+
+#[test]
+fn immunization_history_gives_the_kepi_doses_due_next() {
+    let bundle = transformed(&immunized_infant(), &[]);
+    let recommendation = find_resource(&bundle, "ImmunizationRecommendation");
+    // As of the visit (2026-02-10)
+    assert_eq!(recommendation["date"], "2026-02-10");
+    let doses = recommendation["recommendation"].as_array().unwrap();
+    let vaccines: Vec<&str> = doses
+        .iter()
+        .map(|d| d["vaccineCode"][0]["coding"][0]["code"].as_str().unwrap())
+        .collect();
+    // BCG is complete
+    assert_eq!(vaccines, ["opv", "penta", "pcv", "rota", "ipv", "mr"]);
+    let penta = &doses[1];
+    assert_eq!(penta["doseNumberPositiveInt"], 2);
+    assert_eq!(penta["forecastStatus"]["coding"][0]["code"], "due");
+    assert_eq!(
+        penta["dateCriterion"][0]["code"]["coding"][0]["code"],
+        "30980-7"
+    );
+    assert_eq!(penta["dateCriterion"][0]["value"], "2026-02-09");
+    assert_eq!(penta["dateCriterion"][1]["value"], "2026-03-09");
+
+    // Invalid dose numbers are rejected
+    let mut record = immunized_infant();
+    record["immunizations"][0]["dose"] = 2.into();
+    bridge_on(&record)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not on the KEPI schedule"));
+}
+
+#[test]
+fn report_immunization_due_lists_due_and_overdue_doses() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("record.json");
+    std::fs::write(&input, immunized_infant().to_string()).unwrap();
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args([
+            "report",
+            "immunization-due",
+            "--as-of",
+            "2026-04-01",
+            "--input",
+        ])
+        .arg(&input)
+        // No immunization history: not listed
+        .arg("tests/fixtures/kenyan_patient_1.json")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("clinic_id,patient_number,"))
+        .stdout(predicate::str::contains(
+            "DPT-HepB-Hib (pentavalent),2,2026-02-09,overdue,51",
+        ))
+        .stdout(predicate::str::contains(
+            "Inactivated polio vaccine,1,2026-03-09,due,23",
+        ))
+        // Due in September
+        .stdout(predicate::str::contains("Measles-rubella").not())
+        .stdout(predicate::str::contains("KEN-NAIROBI-001").not());
+}
+
+// ── triage ───────────────────────────────────────────────────────────────────
+
+#[test]
+fn triage_category_sets_encounter_priority_and_triage_observation() {
+    let mut record = fixture("kenyan_patient_2_male_malaria.json");
+    record["visit"]["triage_category"] = 2.into();
+
+    let bundle = transformed(&record, &[]);
+    let encounter = find_resource(&bundle, "Encounter");
+    assert_eq!(encounter["priority"]["coding"][0]["code"], "EM");
+    let triage = resources_of(&bundle, "Observation")
+        .into_iter()
+        .find(|r| r["code"]["coding"][0]["code"] == "11283-9")
+        .expect("triage Observation");
+    assert_eq!(triage["valueCodeableConcept"]["coding"][0]["code"], "2");
+    assert_eq!(
+        triage["valueCodeableConcept"]["coding"][0]["display"],
+        "Emergency"
+    );
+
+    let restored = to_kenyan(&bundle);
+    assert_eq!(restored["visits"][0]["triage_category"], 2);
+
+    record["visit"]["triage_category"] = 7.into();
+    bridge_on(&record)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("triage_category must be 1 to 5"));
+}
+
+// ── department (Location) ────────────────────────────────────────────────────
+
+#[test]
+fn department_becomes_encounter_location_managed_by_the_facility() {
+    let bundle = transform_fixture("kenyan_patient_1.json", |record| {
+        record["visit"]["department"] = "MCH".into();
+    });
+    let location = find_resource(&bundle, "Location");
+    assert_eq!(location["type"][0]["coding"][0]["code"], "mch");
+    assert_eq!(location["physicalType"]["coding"][0]["code"], "wi");
+    assert_eq!(
+        location["managingOrganization"]["reference"],
+        format!(
+            "Organization/{}",
+            find_resource(&bundle, "Organization")["id"]
+                .as_str()
+                .unwrap()
+        )
+    );
+    assert_eq!(
+        find_resource(&bundle, "Encounter")["location"][0]["location"]["reference"],
+        format!("Location/{}", location["id"].as_str().unwrap())
+    );
+
+    let restored = to_kenyan(&bundle);
+    assert_eq!(restored["visits"][0]["department"], "mch");
+}
+
+// ── follow-up appointment ────────────────────────────────────────────────────
+
+#[test]
+fn next_appointment_date_books_an_appointment_for_tracing() {
+    let bundle = transform_fixture("kenyan_patient_7_sha_puid.json", |record| {
+        record["visit"]["department"] = "ccc".into();
+        record["visit"]["next_appointment_date"] = "2026-03-20".into();
+    });
+    let id_of = |resource_type: &str| {
+        find_resource(&bundle, resource_type)["id"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    let appointment = find_resource(&bundle, "Appointment");
+    assert_eq!(appointment["status"], "booked");
+    assert_eq!(appointment["start"], "2026-03-20T08:00:00+03:00");
+    assert_eq!(
+        appointment["reasonReference"][0]["reference"],
+        format!("Condition/{}", id_of("Condition"))
+    );
+    let actors: Vec<String> = appointment["participant"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["actor"]["reference"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(
+        actors,
+        [
+            format!("Patient/{}", id_of("Patient")),
+            format!("Practitioner/{}", id_of("Practitioner")),
+            format!("Location/{}", id_of("Location")),
+        ]
+    );
+
+    let restored = to_kenyan(&bundle);
+    assert_eq!(restored["visits"][0]["next_appointment_date"], "2026-03-20");
+}
+
+// ── lab orders ───────────────────────────────────────────────────────────────
+
+#[test]
+fn lab_orders_become_service_requests_with_their_specimens() {
+    let mut record = fixture("kenyan_patient_7_sha_puid.json");
+    record["visit"]["lab_orders"] = serde_json::json!([
+        { "test_code": "4548-4", "test_name": "HbA1c", "specimen": "blood",
+          "collected_at": "2026-03-06T09:30:00+03:00" },
+        { "test_code": "5794-3", "specimen": "urine" }
+    ]);
+
+    let bundle = transformed(&record, &[]);
+    let requests = resources_of(&bundle, "ServiceRequest");
+    let specimens = resources_of(&bundle, "Specimen");
+    assert_eq!((requests.len(), specimens.len()), (2, 2));
+    let hba1c = requests[0];
+    assert_eq!(hba1c["status"], "active");
+    assert_eq!(hba1c["intent"], "order");
+    assert_eq!(hba1c["code"]["coding"][0]["code"], "4548-4");
+    assert_eq!(
+        hba1c["encounter"]["reference"],
+        format!(
+            "Encounter/{}",
+            find_resource(&bundle, "Encounter")["id"].as_str().unwrap()
+        )
+    );
+    assert_eq!(
+        hba1c["specimen"][0]["reference"],
+        format!("Specimen/{}", specimens[0]["id"].as_str().unwrap())
+    );
+    assert_eq!(specimens[0]["status"], "available");
+    assert_eq!(specimens[0]["type"]["coding"][0]["code"], "BLD");
+    // Not collected yet
+    assert!(specimens[1].get("status").is_none());
+    assert!(specimens[1].get("collection").is_none());
+
+    let restored = to_kenyan(&bundle);
+    assert_eq!(
+        restored["visits"][0]["lab_orders"],
+        record["visit"]["lab_orders"]
+    );
+}
+
+#[test]
+fn lab_order_with_a_non_loinc_code_is_rejected() {
+    let mut record = fixture("kenyan_patient_7_sha_puid.json");
+    record["visit"]["lab_orders"] =
+        serde_json::json!([{ "test_code": "HBA1C", "specimen": "blood" }]);
+    bridge_on(&record)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("lab order 1"));
+}
+
+// ── imaging ──────────────────────────────────────────────────────────────────
+
+#[test]
+fn imaging_order_with_a_conclusion_gets_a_report_and_linked_pdf() {
+    let mut record = fixture("kenyan_patient_7_sha_puid.json");
+    record["visit"]["imaging_orders"] = serde_json::json!([
+        {
+            "study_code": "36643-5",
+            "study_name": "Chest X-ray",
+            "conclusion": "Right upper lobe cavitation, consistent with pulmonary TB",
+            "attachments": [{
+                "kind": "imaging_report",
+                "content_type": "application/pdf",
+                "url": "https://pacs.example.org/reports/cxr-1.pdf"
+            }]
+        },
+        { "study_code": "24627-2" }
+    ]);
+
+    let bundle = transformed(&record, &[]);
+    let requests = resources_of(&bundle, "ServiceRequest");
+    let reports = resources_of(&bundle, "DiagnosticReport");
+    assert_eq!((requests.len(), reports.len()), (2, 1));
+    let cxr_ref = format!("ServiceRequest/{}", requests[0]["id"].as_str().unwrap());
+    assert_eq!(requests[0]["status"], "completed");
+    assert_eq!(requests[0]["code"]["coding"][0]["code"], "36643-5");
+    assert_eq!(
+        requests[0]["reasonReference"][0]["reference"],
+        format!(
+            "Condition/{}",
+            find_resource(&bundle, "Condition")["id"].as_str().unwrap()
+        )
+    );
+    // Not reported yet
+    assert_eq!(requests[1]["status"], "active");
+    assert_eq!(reports[0]["status"], "final");
+    assert_eq!(reports[0]["basedOn"][0]["reference"], cxr_ref);
+    assert!(reports[0]["conclusion"]
+        .as_str()
+        .unwrap()
+        .contains("pulmonary TB"));
+    let documents = resources_of(&bundle, "DocumentReference");
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0]["type"]["coding"][0]["code"], "18748-4");
+    assert_eq!(documents[0]["context"]["related"][0]["reference"], cxr_ref);
+
+    let restored = to_kenyan(&bundle);
+    assert_eq!(
+        restored["visits"][0]["imaging_orders"],
+        record["visit"]["imaging_orders"]
+    );
+    // The report PDF belongs to the order, not the visit
+    assert!(restored["visits"][0].get("attachments").is_none());
+}
+
+// ── triage danger signs ──────────────────────────────────────────────────────
+
+#[test]
+fn danger_signs_become_high_priority_flags_on_the_encounter() {
+    let mut record = fixture("kenyan_patient_7_sha_puid.json");
+    record["visit"]["triage_category"] = 1.into();
+    record["visit"]["danger_signs"] = serde_json::json!(["convulsions", "severe_dehydration"]);
+
+    let bundle = transformed(&record, &[]);
+    let encounter = find_resource(&bundle, "Encounter");
+    let flags = resources_of(&bundle, "Flag");
+    assert_eq!(flags.len(), 2);
+    assert_eq!(flags[0]["code"]["coding"][0]["code"], "convulsions");
+    assert_eq!(flags[1]["code"]["coding"][0]["code"], "severe-dehydration");
+    for flag in &flags {
+        assert_eq!(flag["status"], "active");
+        assert_eq!(flag["category"][0]["coding"][0]["code"], "clinical");
+        assert_eq!(
+            flag["extension"][0]["valueCodeableConcept"]["coding"][0]["code"],
+            "PH"
+        );
+        assert_eq!(
+            flag["encounter"]["reference"],
+            format!("Encounter/{}", encounter["id"].as_str().unwrap())
+        );
+        assert_eq!(flag["period"]["start"], record["visit"]["date"]);
+    }
+
+    let restored = to_kenyan(&bundle);
+    assert_eq!(
+        restored["visits"][0]["danger_signs"],
+        serde_json::json!(["convulsions", "severe_dehydration"])
+    );
+}
+
+// ── clinical note ────────────────────────────────────────────────────────────
+
+#[test]
+fn clinical_note_becomes_a_clinical_impression_of_the_condition() {
+    let mut record = fixture("kenyan_patient_7_sha_puid.json");
+    let note = "Looks dehydrated; mother reports poor feeding for two days. Review if no better.";
+    record["visit"]["clinical_note"] = note.into();
+
+    let bundle = transformed(&record, &[]);
+    let impression = find_resource(&bundle, "ClinicalImpression");
+    assert_eq!(impression["status"], "completed");
+    assert_eq!(impression["summary"], note);
+    assert_eq!(
+        impression["problem"][0]["reference"],
+        format!(
+            "Condition/{}",
+            find_resource(&bundle, "Condition")["id"].as_str().unwrap()
+        )
+    );
+    assert_eq!(
+        impression["assessor"]["reference"],
+        format!(
+            "Practitioner/{}",
+            find_resource(&bundle, "Practitioner")["id"]
+                .as_str()
+                .unwrap()
+        )
+    );
+
+    let restored = to_kenyan(&bundle);
+    assert_eq!(restored["visits"][0]["clinical_note"], note);
+
+    // Free text may name people, so research bundles leave it out
+    bridge_on(&record)
+        .env("DEIDENTIFY_KEY", "research-key-2026-0001")
+        .arg("--deidentify")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ClinicalImpression").not());
+}
+
+// ── Kenya IG profiles ────────────────────────────────────────────────────────
+
+#[test]
+fn ig_profiles_tag_meta_profile_and_a_map_replaces_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let record = fixture("kenyan_patient_1.json");
+
+    let bundle = transformed(&record, &["--ig-profiles"]);
+    for resource in bundle["entry"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| &e["resource"])
+    {
+        let profile = resource["meta"]["profile"][0].as_str().unwrap();
+        assert!(
+            profile.starts_with("https://digitalhealth.go.ke/fhir/StructureDefinition/ke-"),
+            "{}",
+            profile
+        );
+    }
+    assert_eq!(
+        find_resource(&bundle, "Patient")["meta"]["profile"],
+        serde_json::json!(["https://digitalhealth.go.ke/fhir/StructureDefinition/ke-patient"])
+    );
+
+    // A facility map: only the listed types are tagged
+    let map = dir.path().join("profiles.json");
+    std::fs::write(
+        &map,
+        r#"{"Patient": ["http://example.org/StructureDefinition/ke-client"]}"#,
+    )
+    .unwrap();
+    let bundle = transformed(&record, &["--ig-profile-map", map.to_str().unwrap()]);
+    for resource in bundle["entry"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| &e["resource"])
+    {
+        let expected = match resource["resourceType"].as_str() {
+            Some("Patient") => {
+                serde_json::json!(["http://example.org/StructureDefinition/ke-client"])
+            }
+            _ => serde_json::Value::Null,
+        };
+        assert_eq!(resource["meta"]["profile"], expected);
+    }
+
+    // Without the flag nothing is tagged
+    let bundle = transformed(&record, &[]);
+    assert!(bundle["entry"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|e| e["resource"].get("meta").is_none()));
+}
+
+// ── FHIR version ─────────────────────────────────────────────────────────────
+
+#[test]
+fn fhir_version_r5_rewrites_renamed_elements() {
+    let bundle = transformed(
+        &fixture("kenyan_patient_7_sha_puid.json"),
+        &["--fhir-version", "r5"],
+    );
+
+    let encounter = find_resource(&bundle, "Encounter");
+    assert_eq!(encounter["status"], "completed");
+    assert_eq!(encounter["class"][0]["coding"][0]["code"], "OP");
+    assert!(encounter.get("period").is_none());
+    assert!(encounter["actualPeriod"]["start"].is_string());
+    assert!(encounter["reason"][0]["value"][0]["concept"].is_object());
+    assert!(encounter["participant"][0]["actor"]["reference"].is_string());
+    assert!(
+        encounter["diagnosis"][0]["condition"][0]["reference"]["reference"]
+            .as_str()
+            .unwrap()
+            .starts_with("Condition/")
+    );
+    assert!(find_resource(&bundle, "MedicationRequest")["medication"]["concept"].is_object());
+    let coverage = find_resource(&bundle, "Coverage");
+    assert!(coverage.get("payor").is_none());
+    assert_eq!(coverage["kind"], "insurance");
+    assert_eq!(
+        coverage["insurer"]["reference"],
+        "Organization/org-sha-payer"
+    );
+}
+
+// ── vitals performer and devices ─────────────────────────────────────────────
+
+#[test]
+fn vitals_record_who_took_them_and_the_instruments_used() {
+    let mut record = fixture("kenyan_patient_7_sha_puid.json");
+    record["visit"]["vitals_taken_by"] = "HWR-KE-67890".into();
+    record["visit"]["vitals"]["devices"] = serde_json::json!([
+        {"kind": "pulse_oximeter", "id": "OX/2024/07"},
+        {"kind": "bp_monitor", "id": "BP-113"}
+    ]);
+
+    let bundle = transformed(&record, &[]);
+    let observation = |loinc: &str| {
+        resources_of(&bundle, "Observation")
+            .into_iter()
+            .find(|r| r["code"]["coding"][0]["code"] == loinc)
+            .unwrap()
+    };
+
+    for loinc in ["8310-5", "85354-9", "59408-5"] {
+        assert_eq!(
+            observation(loinc)["performer"][0]["reference"],
+            "Practitioner/prac-HWR-KE-67890"
+        );
+    }
+    assert!(resources_of(&bundle, "Practitioner")
+        .iter()
+        .any(|r| r["id"] == "prac-HWR-KE-67890"));
+
+    let org_id = "org-KEN-NAIROBI-005";
+    let oximeter = format!("Device/dev-{}-OX-2024-07", org_id);
+    assert_eq!(
+        observation("59408-5")["device"]["reference"],
+        oximeter.as_str()
+    );
+    assert_eq!(
+        observation("8867-4")["device"]["reference"],
+        oximeter.as_str()
+    );
+    assert_eq!(
+        observation("85354-9")["device"]["reference"],
+        format!("Device/dev-{}-BP-113", org_id)
+    );
+    assert!(observation("8310-5").get("device").is_none());
+    let device = resources_of(&bundle, "Device")
+        .into_iter()
+        .find(|r| r["identifier"][0]["value"] == "OX/2024/07")
+        .unwrap();
+    assert_eq!(device["type"]["coding"][0]["code"], "pulse-oximeter");
+    assert_eq!(
+        device["owner"]["reference"],
+        format!("Organization/{}", org_id)
+    );
+
+    assert!(bundle_subcommand("lint", &bundle, &[]).status.success());
+    let restored = to_kenyan(&bundle);
+    assert_eq!(restored["visits"][0]["vitals_taken_by"], "HWR-KE-67890");
+    assert_eq!(
+        restored["visits"][0]["vitals"]["devices"],
+        record["visit"]["vitals"]["devices"]
+    );
+}
+
+#[test]
+fn attending_clinician_is_the_vitals_performer_by_default() {
+    let bundle = transformed(&fixture("kenyan_patient_7_sha_puid.json"), &[]);
+    for observation in resources_of(&bundle, "Observation") {
+        assert_eq!(
+            observation["performer"][0]["reference"],
+            "Practitioner/prac-HWR-KE-12345"
+        );
+        assert!(observation.get("device").is_none());
+    }
+}
+
+// ── facility contact details ─────────────────────────────────────────────────
+
+#[test]
+fn facility_contacts_give_the_organization_telecom_and_address() {
+    let dir = tempfile::tempdir().unwrap();
+    let contacts = dir.path().join("facilities.json");
+    std::fs::write(
+        &contacts,
+        r#"{"KEN-NAIROBI-001": {
+            "phone": "+254202000000",
+            "email": "records@example.org",
+            "address": "P.O. Box 100-00100",
+            "town": "Nairobi",
+            "county": "Nairobi",
+            "subcounty": "Westlands"
+        }}"#,
+    )
+    .unwrap();
+    let record = fixture("kenyan_patient_1.json");
+    let organization = |extra: &[&str]| {
+        let mut args = vec!["--facility-contacts", contacts.to_str().unwrap()];
+        args.extend_from_slice(extra);
+        let bundle = transformed(&record, &args);
+        resources_of(&bundle, "Organization")
+            .into_iter()
+            .find(|r| r["id"] == "org-KEN-NAIROBI-001")
+            .unwrap()
+            .clone()
+    };
+
+    let facility = organization(&[]);
+    assert_eq!(
+        facility["telecom"],
+        serde_json::json!([
+            {"system": "phone", "value": "+254202000000", "use": "work"},
+            {"system": "email", "value": "records@example.org", "use": "work"}
+        ])
+    );
+    let address = &facility["address"][0];
+    assert_eq!(
+        address["line"],
+        serde_json::json!(["P.O. Box 100-00100", "Westlands"])
+    );
+    assert_eq!(address["city"], "Nairobi");
+    assert_eq!(address["district"], "Nairobi");
+    assert_eq!(address["extension"][0]["valueCoding"]["code"], "047");
+
+    // R5 keeps them in contact
+    let facility = organization(&["--fhir-version", "r5"]);
+    assert!(facility.get("telecom").is_none());
+    assert_eq!(
+        facility["contact"][0]["telecom"][0]["value"],
+        "+254202000000"
+    );
+    assert_eq!(facility["contact"][0]["address"]["city"], "Nairobi");
+}
+
+// ── system URIs ──────────────────────────────────────────────────────────────
+
+#[test]
+fn system_uri_map_rewrites_built_in_systems_and_to_kenyan_reverts_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let map = dir.path().join("systems.json");
+    std::fs::write(
+        &map,
+        r#"{
+            "http://cr.dha.go.ke/fhir/Patient": "https://cr.uat.example.org/fhir/Patient",
+            "http://facility-registry.dha.go.ke/fhir/Location": "https://fr.uat.example.org/fhir/Facility",
+            "http://sha.health.go.ke/identifier/member": "https://sha.uat.example.org/identifier/member"
+        }"#,
+    )
+    .unwrap();
+    let record = fixture("kenyan_patient_7_sha_puid.json");
+    let bundle = transformed(&record, &["--system-uris", map.to_str().unwrap()]);
+    let mapped = bundle.to_string();
+    assert!(!mapped.contains("cr.dha.go.ke"));
+    assert!(!mapped.contains("facility-registry.dha.go.ke"));
+    assert!(!mapped.contains("sha.health.go.ke/identifier/member"));
+    // Systems the map leaves out are kept
+    assert!(mapped.contains("http://hwr.dha.go.ke/fhir/Practitioner"));
+
+    let systems: Vec<&str> = find_resource(&bundle, "Patient")["identifier"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|i| i["system"].as_str())
+        .collect();
+    assert!(systems.contains(&"https://cr.uat.example.org/fhir/Patient"));
+    assert!(systems.iter().any(
+        |s| s.starts_with("https://fr.uat.example.org/fhir/Facility/")
+            && s.ends_with("/patient-number")
+    ));
+
+    // Read back with the same map, the bundle gives the record the plain one does
+    let output = bundle_subcommand(
+        "to-kenyan",
+        &bundle,
+        &["--system-uris", map.to_str().unwrap()],
+    );
+    assert!(output.status.success());
+    let restored: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(restored, to_kenyan(&transformed(&record, &[])));
+
+    // A key that is no built-in URI is a configuration error
+    std::fs::write(&map, r#"{"http://example.org/fhir/Patient": "x"}"#).unwrap();
+    bridge_on(&record)
+        .args(["--system-uris", map.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not a built-in system URI"));
+}
+
+// ── subcommands ──────────────────────────────────────────────────────────────
+
+#[test]
+fn transform_subcommand_matches_the_implicit_default() {
+    let run = |subcommand: &[&str]| {
+        let output = Command::cargo_bin("kenya-fhir-bridge")
+            .unwrap()
+            .env_remove("AFYALINK_TOKEN")
+            .args(subcommand)
+            .args([
+                "--input",
+                "tests/fixtures/kenyan_patient_1.json",
+                "--deterministic",
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        output.stdout
+    };
+    assert_eq!(run(&["transform"]), run(&[]));
+}
+
+#[test]
+fn validate_checks_each_record_without_transforming() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::copy(
+        "tests/fixtures/kenyan_patient_1.json",
+        dir.path().join("a.json"),
+    )
+    .unwrap();
+    let mut record = fixture("kenyan_patient_9_anc.json");
+    record["gender"] = "M".into();
+    std::fs::write(dir.path().join("b.json"), record.to_string()).unwrap();
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["validate", "--input"])
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("a.json: valid"))
+        .stderr(predicate::str::contains(
+            "Antenatal findings require a female patient",
+        ))
+        .stderr(predicate::str::contains(
+            "1 of 2 record(s) failed validation",
+        ));
+}
+
+#[test]
+fn serve_once_records_failed_sends_and_keeps_them_queued() {
+    use kenya_fhir_bridge::offline_queue::OfflineQueue;
+
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("queue.db");
+    OfflineQueue::open(&db)
+        .unwrap()
+        .enqueue("b1", r#"{"resourceType":"Bundle","id":"b1"}"#, "p1", "c1")
+        .unwrap();
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["serve", "--once", "--db"])
+        .arg(&db)
+        .args(["--endpoint", "http://127.0.0.1:9/fhir", "--retries", "0"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "[SERVE] sent 0, failed 1, expired 0",
+        ));
+
+    let rows = OfflineQueue::open(&db)
+        .unwrap()
+        .pending_within_window()
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].retry_count, 1);
+}
+
+#[test]
+fn serve_alerts_once_when_a_bundle_fails_for_good() {
+    use kenya_fhir_bridge::offline_queue::OfflineQueue;
+
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("queue.db");
+    OfflineQueue::open(&db)
+        .unwrap()
+        .enqueue("b1", r#"{"resourceType":"Bundle","id":"b1"}"#, "p1", "c1")
+        .unwrap();
+    let policy = dir.path().join("policy.json");
+    std::fs::write(&policy, r#"{"max_retries": 1}"#).unwrap();
+    let received = dir.path().join("alerts.jsonl");
+    let alerts = dir.path().join("alerts.json");
+    std::fs::write(
+        &alerts,
+        serde_json::json!({
+            "command": ["sh", "-c", format!("cat >> {}; echo >> {}", received.display(), received.display())]
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let serve = || {
+        Command::cargo_bin("kenya-fhir-bridge")
+            .unwrap()
+            .args(["serve", "--once", "--db"])
+            .arg(&db)
+            .arg("--policy")
+            .arg(&policy)
+            .arg("--alerts")
+            .arg(&alerts)
+            .args(["--endpoint", "http://127.0.0.1:9/fhir", "--retries", "0"])
+            .assert()
+            .success()
+    };
+    serve().stderr(predicate::str::contains("[SERVE] raised 1 alert(s)"));
+    serve();
+
+    let raised = std::fs::read_to_string(&received).unwrap();
+    assert_eq!(raised.lines().count(), 1);
+    let alert: serde_json::Value = serde_json::from_str(raised.trim()).unwrap();
+    assert_eq!(alert["event"], "bundle_failed");
+    assert_eq!(alert["bundle_id"], "b1");
+}
+
+#[test]
+fn mllp_listener_queues_converted_messages_and_acks_them() {
+    use kenya_fhir_bridge::mllp::{read_frame, write_frame};
+    use kenya_fhir_bridge::offline_queue::OfflineQueue;
+    use std::io::BufReader;
+    use std::net::{TcpListener, TcpStream};
+
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("queue.db");
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut listener =
+        std::process::Command::new(assert_cmd::cargo::cargo_bin("kenya-fhir-bridge"))
+            .args([
+                "mllp-listen",
+                "--bind",
+                &format!("127.0.0.1:{}", port),
+                "--db",
+            ])
+            .arg(&db)
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+    let stream = (0..50)
+        .find_map(|_| {
+            TcpStream::connect(("127.0.0.1", port)).ok().or_else(|| {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                None
+            })
+        })
+        .expect("listener did not start");
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+
+    let adt = "MSH|^~\\&|KENYAEMR|12345|KFB|MOH|20250310091500||ADT^A04|MSG0001|P|2.5\r\
+PID|1||KE-2025-000123^^^12345^MR~23456789^^^KEN^NI||Wanjiru^Grace^Njeri||19900415|F|||^^Nakuru Town East^Nakuru||0712345678\r\
+PV1|1|O|OPD||||HWR-0042^Otieno^James\r\
+PV2|||^Fever and cough\r\
+OBX|1|NM|8310-5^Body temperature^LN||38.4|Cel\r\
+OBX|2|NM|8480-6^Systolic BP^LN||118|mm[Hg]\r\
+OBX|3|NM|8462-4^Diastolic BP^LN||76|mm[Hg]\r\
+OBX|4|NM|29463-7^Body weight^LN||61.5|kg\r\
+DG1|1||J06.9^Acute upper respiratory infection^I10\r\
+RXE|1|^Paracetamol 500mg tablets\r";
+    write_frame(&mut writer, adt).unwrap();
+    let ack = read_frame(&mut reader).unwrap().unwrap();
+    write_frame(
+        &mut writer,
+        &adt.replace("MSG0001", "MSG0002").replace("DG1|", "ZZZ|"),
+    )
+    .unwrap();
+    let nak = read_frame(&mut reader).unwrap().unwrap();
+    listener.kill().unwrap();
+    listener.wait().unwrap();
+
+    assert!(ack.contains("\rMSA|AA|MSG0001|"), "{}", ack);
+    assert!(nak.contains("\rMSA|AE|MSG0002|"), "{}", nak);
+    assert!(nak.contains("no diagnosis"));
+    let queued = OfflineQueue::open(&db)
+        .unwrap()
+        .pending_within_window()
+        .unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].patient_id, "KE-2025-000123");
+    assert_eq!(queued[0].clinic_id, "12345");
+}
+
+#[test]
+fn watch_mode_queues_dropped_records_and_files_them_away() {
+    use kenya_fhir_bridge::offline_queue::OfflineQueue;
+
+    let dir = tempfile::tempdir().unwrap();
+    let inbox = dir.path().join("inbox");
+    std::fs::create_dir(&inbox).unwrap();
+    let db = dir.path().join("queue.db");
+    let mut watcher = std::process::Command::new(assert_cmd::cargo::cargo_bin("kenya-fhir-bridge"))
+        .env_remove("AFYALINK_TOKEN")
+        .arg("--watch")
+        .arg(&inbox)
+        .arg("--queue-db")
+        .arg(&db)
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    // Drop the files once the watch is up, as an EMR export would
+    while !inbox.join("processed").exists() {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    std::fs::copy(
+        "tests/fixtures/kenyan_patient_1.json",
+        inbox.join("visit.json"),
+    )
+    .unwrap();
+    std::fs::write(inbox.join("broken.json"), "{\"clinic_id\": ").unwrap();
+    let done = (0..100).any(|_| {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        inbox.join("processed/visit.json").exists() && inbox.join("failed/broken.json").exists()
+    });
+    watcher.kill().unwrap();
+    watcher.wait().unwrap();
+
+    assert!(done, "records were not filed away");
+    assert!(!inbox.join("visit.json").exists());
+    let note = std::fs::read_to_string(inbox.join("failed/broken.json.error.txt")).unwrap();
+    assert!(note.contains("Invalid Kenyan JSON payload"), "{}", note);
+    let queued = OfflineQueue::open(&db)
+        .unwrap()
+        .pending_within_window()
+        .unwrap();
+    assert_eq!(queued.len(), 1);
+}
+
+#[test]
+fn queue_options_are_rejected_without_watch() {
+    for (flag, value) in [("--queue-db", "queue.db"), ("--queue-policy", "policy.json")] {
+        Command::cargo_bin("kenya-fhir-bridge")
+            .unwrap()
+            .arg("--input")
+            .arg("tests/fixtures/kenyan_patient_1.json")
+            .arg(flag)
+            .arg(value)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+}
+
+// ── exit codes and run summary ───────────────────────────────────────────────
+
+#[test]
+fn exit_codes_distinguish_validation_io_and_network_failures() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut record = fixture("kenyan_patient_9_anc.json");
+    record["gender"] = "M".into();
+    let invalid = dir.path().join("invalid.json");
+    std::fs::write(&invalid, record.to_string()).unwrap();
+
+    let bridge = || {
+        let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
+        cmd.env_remove("AFYALINK_TOKEN");
+        cmd
+    };
+    bridge().arg("--input").arg(&invalid).assert().code(3);
+    bridge()
+        .args(["--input", "tests/fixtures/does_not_exist.json"])
+        .assert()
+        .code(5);
+    bridge()
+        .args([
+            "send",
+            "tests/fixtures/kenyan_patient_1.json",
+            "--endpoint",
+            "http://127.0.0.1:9/fhir",
+            "--retries",
+            "0",
+        ])
+        .assert()
+        .code(6);
+    bridge()
+        .args(["transform", "--no-such-flag"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn summary_json_lists_each_record_with_status_and_timing() {
+    let dir = tempfile::tempdir().unwrap();
+    let records = dir.path().join("records");
+    std::fs::create_dir(&records).unwrap();
+    std::fs::copy(
+        "tests/fixtures/kenyan_patient_1.json",
+        records.join("a.json"),
+    )
+    .unwrap();
+    std::fs::write(records.join("b.json"), "{not json").unwrap();
+    let summary_path = dir.path().join("summary.json");
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .args(["validate", "--input"])
+        .arg(&records)
+        .arg("--summary-json")
+        .arg(&summary_path)
+        .assert()
+        .code(3);
+
+    let summary: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&summary_path).unwrap()).unwrap();
+    assert_eq!(summary["command"], "validate");
+    assert_eq!(summary["status"], "validation");
+    assert_eq!(summary["exit_code"], 3);
+    assert_eq!(summary["counts"]["total"], 2);
+    assert_eq!(summary["counts"]["failed"], 1);
+    assert_eq!(summary["records"][0]["status"], "ok");
+    assert_eq!(summary["records"][1]["status"], "validation");
+    assert!(summary["records"][1]["error"]
+        .as_str()
+        .unwrap()
+        .contains("Invalid Kenyan JSON payload"));
+    assert!(summary["records"][0]["duration_ms"].is_u64());
+}
+
+// ── dry run ──────────────────────────────────────────────────────────────────
+
+#[test]
+fn dry_run_prints_the_bundle_and_leaves_no_trace() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("bundle.json");
+    let archive = dir.path().join("archive.db");
+    let ledger = dir.path().join("ledger.db");
+    let transform = |dry_run: bool| {
+        let mut cmd = Command::cargo_bin("kenya-fhir-bridge").unwrap();
+        cmd.env_remove("AFYALINK_TOKEN")
+            .args([
+                "--input",
+                "tests/fixtures/kenyan_patient_1.json",
+                "--output",
+            ])
+            .arg(&output)
+            .arg("--archive")
+            .arg(&archive)
+            .arg("--visit-ledger")
+            .arg(&ledger)
+            .args(["--duplicate-visits", "skip"]);
+        if dry_run {
+            cmd.arg("--dry-run");
+        }
+        cmd.assert().success()
+    };
+
+    let run = transform(true)
+        .stderr(predicate::str::contains("[DRY-RUN] would generate"))
+        .stderr(predicate::str::contains("Encounter 1"))
+        .stderr(predicate::str::contains("not archived"));
+    let bundle: serde_json::Value = serde_json::from_slice(&run.get_output().stdout).unwrap();
+    assert_eq!(bundle["resourceType"], "Bundle");
+    assert!(!output.exists());
+    assert!(!archive.exists());
+
+    // The dry run did not record the visit, so the real run maps it
+    transform(false);
+    assert!(output.exists());
+
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .arg("send")
+        .arg(&output)
+        .args(["--endpoint", "http://127.0.0.1:9/fhir", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("would submit"))
+        .stdout(predicate::str::contains("http://127.0.0.1:9/fhir"));
+}
+
+// ── stdin input ──────────────────────────────────────────────────────────────
+
+#[test]
+fn input_dash_reads_json_or_xml_from_stdin() {
+    for fixture in [
+        "tests/fixtures/kenyan_patient_1.json",
+        "tests/fixtures/kenyan_patient_1.xml",
+    ] {
+        let output = Command::cargo_bin("kenya-fhir-bridge")
+            .unwrap()
+            .env_remove("AFYALINK_TOKEN")
+            .args(["--input", "-"])
+            .write_stdin(std::fs::read(fixture).unwrap())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", fixture);
+        let bundle: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(bundle["resourceType"], "Bundle");
+    }
+}
+
+// ── input format detection ───────────────────────────────────────────────────
+
+#[test]
+fn input_format_is_detected_when_not_given() {
+    Command::cargo_bin("kenya-fhir-bridge")
+        .unwrap()
+        .env_remove("AFYALINK_TOKEN")
+        .args(["--input", "tests/fixtures/kenyan_patient_1.xml"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"resourceType\": \"Bundle\""));
+
+    let dir = tempfile::tempdir().unwrap();
+    let hl7 = dir.path().join("adt.txt");
+    std::fs::write(
+        &hl7,
+        "MSH|^~\\&|KENYAEMR|KEN-NAIROBI-001|||202603240900||ADT^A04|1|P|2.5\r",
+    )
+    .unwrap();
+    let csv = dir.path().join("visits.csv");
+    std::fs::write(
+        &csv,
+        "patient_number,visit_date,diagnosis\nP001,2026-03-24,URTI\n",
+    )
+    .unwrap();
+    for (input, kind) in [(&hl7, "HL7 v2"), (&csv, "CSV")] {
+        Command::cargo_bin("kenya-fhir-bridge")
+            .unwrap()
+            .env_remove("AFYALINK_TOKEN")
+            .arg("--input")
+            .arg(input)
+            .assert()
+            .code(3)
+            .stderr(predicate::str::contains(format!("looks like {}", kind)));
+    }
+}
@@ -0,0 +1,109 @@
+//! End-to-end flows through the in-process `pipeline` API (no binary).
+
+use kenya_fhir_bridge::pipeline::Pipeline;
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("tests/fixtures/{}", name)).unwrap()
+}
+
+#[test]
+fn record_is_transformed_linted_and_submitted() {
+    let mut pipeline = Pipeline::mock().unwrap();
+
+    let run = pipeline
+        .run_json(&fixture("kenyan_patient_8_multi_visit.json"))
+        .unwrap();
+
+    assert!(run.submitted);
+    assert!(run.queued.is_none());
+    assert_eq!(pipeline.submitter.submitted.len(), 1);
+    assert_eq!(pipeline.queue.stats().unwrap().pending, 0);
+}
+
+#[test]
+fn failed_submission_is_queued_for_retry() {
+    let mut pipeline = Pipeline::mock().unwrap();
+    pipeline.submitter.fail_next = 1;
+
+    let run = pipeline
+        .run_json(&fixture("kenyan_patient_1.json"))
+        .unwrap();
+    assert!(!run.submitted);
+    assert!(run.queued.is_some());
+
+    let pending = pipeline.queue.pending_within_window().unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].retry_count, 1);
+    assert_eq!(pending[0].bundle_id, run.bundle.id.unwrap());
+
+    // Link is back: the next record goes straight through
+    let run = pipeline
+        .run_json(&fixture("kenyan_patient_1.json"))
+        .unwrap();
+    assert!(run.submitted);
+    assert_eq!(pipeline.submitter.submitted.len(), 1);
+}
+
+#[test]
+fn invalid_record_stops_before_submission() {
+    let mut pipeline = Pipeline::mock().unwrap();
+    let mut record: serde_json::Value =
+        serde_json::from_str(&fixture("kenyan_patient_1.json")).unwrap();
+    record["national_id"] = "".into();
+
+    assert!(pipeline.run_json(&record.to_string()).is_err());
+    assert!(pipeline.submitter.submitted.is_empty());
+    assert_eq!(pipeline.queue.stats().unwrap().pending, 0);
+}
+
+#[test]
+fn queued_sha_claims_are_sent_before_routine_bundles() {
+    use kenya_fhir_bridge::offline_queue::Priority;
+
+    let mut pipeline = Pipeline::mock().unwrap();
+    pipeline.submitter.fail_next = 2;
+    pipeline
+        .run_json(&fixture("kenyan_patient_1.json"))
+        .unwrap();
+    let claim = pipeline
+        .run_json(&fixture("kenyan_patient_7_sha_puid.json"))
+        .unwrap();
+
+    let pending = pipeline.queue.pending_within_window().unwrap();
+    assert_eq!(pending.len(), 2);
+    assert_eq!(pending[0].bundle_id, claim.bundle.id.unwrap());
+    assert_eq!(pending[0].priority, Priority::Claim);
+    assert_eq!(pending[1].priority, Priority::Routine);
+}
+
+#[test]
+fn gender_spellings_are_normalized_and_unknown_values_flagged() {
+    let mut pipeline = Pipeline::mock().unwrap();
+    let mut record: serde_json::Value =
+        serde_json::from_str(&fixture("kenyan_patient_1.json")).unwrap();
+    let patient_gender = |run: &kenya_fhir_bridge::pipeline::PipelineRun| {
+        let entries = run.bundle.entry.as_ref().unwrap();
+        entries[1].resource.as_ref().unwrap()["gender"].clone()
+    };
+
+    let spellings = [
+        ("Male", "male"),
+        ("2", "female"),
+        ("F ", "female"),
+        ("intersex", "other"),
+    ];
+    for (sent, expected) in spellings {
+        record["gender"] = sent.into();
+        let run = pipeline.run_json(&record.to_string()).unwrap();
+        assert_eq!(patient_gender(&run), expected, "gender {:?}", sent);
+        assert!(run.data_quality.is_empty());
+    }
+
+    record["gender"] = "K".into();
+    let run = pipeline.run_json(&record.to_string()).unwrap();
+    assert_eq!(patient_gender(&run), "unknown");
+    assert_eq!(
+        run.data_quality,
+        ["gender value not recognised; recorded as unknown"]
+    );
+}